@@ -0,0 +1,278 @@
+//! A bus which sends accesses to unmapped addresses to a fallback bus
+
+use core::marker::PhantomData;
+
+use emulator_hal::{BasicBusError, BusAccess, Instant as EmuInstant, Transaction};
+
+/// An error which can report whether it was caused by an access to an unmapped address
+pub trait UnmappedError {
+    /// Returns true if the error was caused by an access to an address with no device
+    fn is_unmapped(&self) -> bool;
+}
+
+impl UnmappedError for BasicBusError {
+    fn is_unmapped(&self) -> bool {
+        matches!(self, BasicBusError::UnmappedAddress)
+    }
+}
+
+/// A bus which tries each access on a primary bus, and repeats it on a fallback bus if the
+/// primary bus reports that the address is unmapped
+///
+/// This is meant for wrapping a `BusRouter`, so that the gaps in the memory map can be handled
+/// by a single device, instead of mapping a device over every gap.  Using an `OpenBus` as the
+/// fallback models systems where reading an unmapped address returns a floating bus value
+/// rather than faulting.  Other errors from the primary bus are returned unchanged
+pub struct FallthroughBus<Primary, Fallback> {
+    /// The bus which is accessed first
+    pub primary: Primary,
+    /// The bus which is accessed when the primary bus reports an unmapped address
+    pub fallback: Fallback,
+}
+
+impl<Primary, Fallback> FallthroughBus<Primary, Fallback> {
+    /// Construct a new bus which falls through from `primary` to `fallback`
+    pub fn new(primary: Primary, fallback: Fallback) -> Self {
+        Self { primary, fallback }
+    }
+}
+
+impl<Address, Primary, Fallback> BusAccess<Address> for FallthroughBus<Primary, Fallback>
+where
+    Address: Copy,
+    Primary: BusAccess<Address>,
+    Primary::Error: UnmappedError + From<Fallback::Error>,
+    Fallback: BusAccess<Address, Instant = Primary::Instant>,
+{
+    type Instant = Primary::Instant;
+    type Error = Primary::Error;
+
+    #[inline]
+    fn read(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        match self.primary.read(now, addr, data) {
+            Err(err) if err.is_unmapped() => Ok(self.fallback.read(now, addr, data)?),
+            result => result,
+        }
+    }
+
+    #[inline]
+    fn write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        match self.primary.write(now, addr, data) {
+            Err(err) if err.is_unmapped() => Ok(self.fallback.write(now, addr, data)?),
+            result => result,
+        }
+    }
+
+    #[inline]
+    fn read_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        match self.primary.read_ext(now, addr, data, transaction) {
+            Err(err) if err.is_unmapped() => {
+                Ok(self.fallback.read_ext(now, addr, data, transaction)?)
+            }
+            result => result,
+        }
+    }
+
+    #[inline]
+    fn write_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        match self.primary.write_ext(now, addr, data, transaction) {
+            Err(err) if err.is_unmapped() => {
+                Ok(self.fallback.write_ext(now, addr, data, transaction)?)
+            }
+            result => result,
+        }
+    }
+
+    #[inline]
+    fn read_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        match self.primary.read_timed(now, addr, data) {
+            Err(err) if err.is_unmapped() => Ok(self.fallback.read_timed(now, addr, data)?),
+            result => result,
+        }
+    }
+
+    #[inline]
+    fn write_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        match self.primary.write_timed(now, addr, data) {
+            Err(err) if err.is_unmapped() => Ok(self.fallback.write_timed(now, addr, data)?),
+            result => result,
+        }
+    }
+
+    #[inline]
+    fn peek(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        match self.primary.peek(now, addr, data) {
+            Err(err) if err.is_unmapped() => Ok(self.fallback.peek(now, addr, data)?),
+            result => result,
+        }
+    }
+
+    #[inline]
+    fn poke(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        match self.primary.poke(now, addr, data) {
+            Err(err) if err.is_unmapped() => Ok(self.fallback.poke(now, addr, data)?),
+            result => result,
+        }
+    }
+
+    #[inline]
+    fn read_modify_write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        modify: &mut dyn FnMut(&mut [u8]),
+    ) -> Result<usize, Self::Error> {
+        match self.primary.read_modify_write(now, addr, data, modify) {
+            Err(err) if err.is_unmapped() => {
+                Ok(self.fallback.read_modify_write(now, addr, data, modify)?)
+            }
+            result => result,
+        }
+    }
+}
+
+/// A bus where every address reads as the same value, and every write is ignored
+///
+/// This models the floating data bus of many older systems, where reading an address that no
+/// device responds to returns a fixed value such as 0xFF, set by pull-up resistors
+#[derive(Copy, Clone, Debug, Default)]
+pub struct OpenBus<Instant> {
+    /// The value of each byte read
+    pub value: u8,
+    instant: PhantomData<Instant>,
+}
+
+impl<Instant> OpenBus<Instant> {
+    /// Construct a new bus that reads as `value` everywhere
+    pub fn new(value: u8) -> Self {
+        Self {
+            value,
+            instant: PhantomData,
+        }
+    }
+}
+
+impl<Address, Instant> BusAccess<Address> for OpenBus<Instant>
+where
+    Address: Copy,
+    Instant: EmuInstant,
+{
+    type Instant = Instant;
+    type Error = BasicBusError;
+
+    #[inline]
+    fn read(
+        &mut self,
+        _now: Self::Instant,
+        _addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        data.fill(self.value);
+        Ok(data.len())
+    }
+
+    #[inline]
+    fn write(
+        &mut self,
+        _now: Self::Instant,
+        _addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        Ok(data.len())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::BusRouter;
+    use emulator_hal::{BusAccessExt, DeviceId};
+    use std::time::Duration;
+
+    struct Rom(Vec<u8>);
+
+    impl BusAccess<u16> for Rom {
+        type Instant = Duration;
+        type Error = BasicBusError;
+
+        fn read(
+            &mut self,
+            _now: Duration,
+            addr: u16,
+            data: &mut [u8],
+        ) -> Result<usize, Self::Error> {
+            let addr = addr as usize;
+            data.copy_from_slice(&self.0[addr..addr + data.len()]);
+            Ok(data.len())
+        }
+
+        fn write(
+            &mut self,
+            _now: Duration,
+            _addr: u16,
+            _data: &[u8],
+        ) -> Result<usize, Self::Error> {
+            Err(BasicBusError::ReadOnly)
+        }
+    }
+
+    #[test]
+    fn test_open_bus_fallback() {
+        let mut router = BusRouter::<u16, Duration, BasicBusError>::new();
+        router
+            .insert(DeviceId(0), 0x8000..0x8004, Box::new(Rom(vec![1, 2, 3, 4])))
+            .unwrap();
+        let mut bus = FallthroughBus::new(router, OpenBus::new(0xFF));
+
+        assert_eq!(bus.read_beu16(Duration::ZERO, 0x8002).unwrap(), 0x0304);
+        assert_eq!(bus.read_beu16(Duration::ZERO, 0x1000).unwrap(), 0xFFFF);
+        bus.write_u8(Duration::ZERO, 0x1000, 0).unwrap();
+        assert!(matches!(
+            bus.write_u8(Duration::ZERO, 0x8000, 0),
+            Err(BasicBusError::ReadOnly)
+        ));
+    }
+}
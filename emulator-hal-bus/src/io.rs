@@ -0,0 +1,50 @@
+//! A router for CPUs with separate memory and I/O spaces
+
+use emulator_hal::SpaceRouter;
+
+use crate::router::BusRouter;
+
+/// A router for a CPU with separate memory and I/O spaces, such as the Z80, with its own set of
+/// devices mapped into each space
+///
+/// Accesses to `(MemOrIo::Memory, addr)` are dispatched to the devices in `primary`, and
+/// accesses to `(MemOrIo::Io, port)` to the devices in `secondary`.  Many Z80 systems only decode
+/// the lower 8 bits of the port address, which can be modelled by wrapping each I/O device in a
+/// `MaskAdapter`, so that it's mirrored regardless of the upper byte
+pub type IoRouter<Address, Instant, Error> =
+    SpaceRouter<BusRouter<Address, Instant, Error>, BusRouter<Address, Instant, Error>>;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use emulator_hal::{BasicBusError, BusAccessExt, DeviceId, IoBus, MaskAdapter, MemOrIo};
+    use std::time::Duration;
+
+    #[test]
+    fn test_separate_device_sets() {
+        let now = Duration::ZERO;
+        let mut bus: IoRouter<u16, Duration, BasicBusError> = IoRouter::default();
+        bus.primary
+            .insert(DeviceId(0), 0x0000..0x8000, Box::new(vec![0_u8; 0x8000]))
+            .unwrap();
+        // two ports which ignore the upper byte of the port address
+        bus.secondary
+            .insert(
+                DeviceId(1),
+                0x0000..0x1000,
+                Box::new(MaskAdapter::new(vec![0_u8; 2], 0x01)),
+            )
+            .unwrap();
+
+        // the same address is a different device in each space
+        bus.write_u8(now, (MemOrIo::Memory, 0x0001), 0xAA).unwrap();
+        bus.write_u8(now, (MemOrIo::Io, 0x0001), 0x55).unwrap();
+        assert_eq!(bus.read_u8(now, (MemOrIo::Memory, 0x0001)).unwrap(), 0xAA);
+        assert!(bus.read_u8(now, (MemOrIo::Io, 0x1001)).is_err());
+
+        let mut cpu_bus = IoBus::new(bus);
+        assert_eq!(cpu_bus.in_u8(now, 0x0F01).unwrap(), 0x55);
+        assert_eq!(cpu_bus.read_u8(now, 0x0001).unwrap(), 0xAA);
+        assert!(cpu_bus.read_u8(now, 0x8000).is_err());
+    }
+}
@@ -0,0 +1,20 @@
+#![doc = include_str!("../README.md")]
+#![warn(missing_docs)]
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+mod fallthrough;
+pub use crate::fallthrough::*;
+
+mod io;
+pub use crate::io::*;
+
+mod paged;
+pub use crate::paged::*;
+
+mod prefetch;
+pub use crate::prefetch::*;
+
+mod router;
+pub use crate::router::*;
@@ -0,0 +1,348 @@
+//! A bus router which finds devices with a page table, instead of searching their ranges
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::{Range, Sub};
+
+use emulator_hal::{
+    BasicBusError, BusAccess, DeviceId, ErrorType, Instant as EmuInstant, Transaction,
+};
+
+use crate::router::{BoxedBus, RouterError};
+
+/// A page with no devices in it
+const PAGE_UNMAPPED: u32 = u32::MAX;
+/// A page that contains part of a device, and must be looked up in the fallback list
+const PAGE_MIXED: u32 = u32::MAX - 1;
+
+/// A bus that maps ranges of addresses to devices like `BusRouter`, but finds the device for
+/// an address in constant time using a page table
+///
+/// The addresses from 0 up to `paged_end` are divided into pages of `page_size` bytes, and the
+/// table holds the index of the device that covers each whole page.  Devices which only cover
+/// part of a page, such as a block of I/O registers smaller than a page, or which extend past
+/// `paged_end`, are also kept in a fallback list, which is searched for any address in a page
+/// that isn't wholly covered by one device, or that's past `paged_end`.  The page table has one
+/// 4-byte entry per page, so the page size should be chosen to keep it reasonably small, such
+/// as 4 KiB pages for a 24-bit address space.
+///
+/// As with `BusRouter`, each device is given the address relative to the start of its range,
+/// and accesses to addresses that aren't mapped return `BasicBusError::UnmappedAddress`
+pub struct PagedRouter<Address, Instant, Error>
+where
+    Address: Copy,
+{
+    page_bits: u32,
+    table: Vec<u32>,
+    fallback: Vec<usize>,
+    ranges: Vec<Range<Address>>,
+    ids: Vec<DeviceId>,
+    devices: Vec<BoxedBus<Address, Instant, Error>>,
+}
+
+impl<Address, Instant, Error> PagedRouter<Address, Instant, Error>
+where
+    Address: Copy + Ord + Sub<Output = Address> + Into<u64>,
+{
+    /// Construct a new router with no devices, and a page table covering the addresses below
+    /// `paged_end`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `page_size` is not a power of two
+    pub fn new(page_size: u64, paged_end: u64) -> Self {
+        assert!(
+            page_size.is_power_of_two(),
+            "page size must be a power of two"
+        );
+        let page_bits = page_size.trailing_zeros();
+        Self {
+            page_bits,
+            table: vec![PAGE_UNMAPPED; (paged_end >> page_bits) as usize],
+            fallback: Vec::new(),
+            ranges: Vec::new(),
+            ids: Vec::new(),
+            devices: Vec::new(),
+        }
+    }
+
+    /// Map the given device to the given range of addresses, with the given id
+    ///
+    /// The range must not be empty, and must not overlap the range of any other device, and the
+    /// id must not already be used by another device
+    pub fn insert(
+        &mut self,
+        id: DeviceId,
+        range: Range<Address>,
+        device: BoxedBus<Address, Instant, Error>,
+    ) -> Result<(), RouterError> {
+        if range.start >= range.end {
+            return Err(RouterError::EmptyRange);
+        }
+        if self.ids.contains(&id) {
+            return Err(RouterError::DuplicateId(id));
+        }
+        if let Some(index) = self
+            .ranges
+            .iter()
+            .position(|existing| range.start < existing.end && existing.start < range.end)
+        {
+            return Err(RouterError::Overlap(self.ids[index]));
+        }
+
+        let index = self.devices.len();
+        let (start, end) = (range.start.into(), range.end.into());
+        let first_page = start >> self.page_bits;
+        let last_page = (end - 1) >> self.page_bits;
+        let mut partial = false;
+        for page in first_page..=last_page {
+            let page_start = page << self.page_bits;
+            let page_end = page_start.saturating_add(1 << self.page_bits);
+            let entry = match self.table.get_mut(page as usize) {
+                Some(entry) => entry,
+                None => {
+                    partial = true;
+                    break;
+                }
+            };
+            if start <= page_start && page_end <= end {
+                *entry = index as u32;
+            } else {
+                *entry = PAGE_MIXED;
+                partial = true;
+            }
+        }
+        if partial {
+            self.fallback.push(index);
+        }
+
+        self.ranges.push(range);
+        self.ids.push(id);
+        self.devices.push(device);
+        Ok(())
+    }
+
+    /// Returns the number of devices in the router
+    pub fn len(&self) -> usize {
+        self.devices.len()
+    }
+
+    /// Returns true if the router contains no devices
+    pub fn is_empty(&self) -> bool {
+        self.devices.is_empty()
+    }
+
+    /// Returns the range of addresses mapped to each device, in the order they were inserted
+    pub fn ranges(&self) -> &[Range<Address>] {
+        &self.ranges
+    }
+
+    /// Returns the id of each device, in the same order as `ranges()`
+    pub fn ids(&self) -> &[DeviceId] {
+        &self.ids
+    }
+
+    /// Returns the device with the given id, if any
+    pub fn device_mut(&mut self, id: DeviceId) -> Option<&mut BoxedBus<Address, Instant, Error>> {
+        let index = self.ids.iter().position(|existing| *existing == id)?;
+        Some(&mut self.devices[index])
+    }
+
+    /// Returns the device that contains the given address, and the address relative to its range
+    #[inline]
+    fn lookup(
+        &mut self,
+        addr: Address,
+    ) -> Option<(&mut BoxedBus<Address, Instant, Error>, Address)> {
+        let page = addr.into() >> self.page_bits;
+        let index = match self.table.get(page as usize) {
+            Some(&PAGE_UNMAPPED) => return None,
+            Some(&PAGE_MIXED) | None => *self
+                .fallback
+                .iter()
+                .find(|index| self.ranges[**index].contains(&addr))?,
+            Some(&index) => index as usize,
+        };
+        Some((&mut self.devices[index], addr - self.ranges[index].start))
+    }
+}
+
+impl<Address, Instant, Error> BusAccess<Address> for PagedRouter<Address, Instant, Error>
+where
+    Address: Copy + Ord + Sub<Output = Address> + Into<u64>,
+    Instant: EmuInstant,
+    Error: ErrorType + From<BasicBusError>,
+{
+    type Instant = Instant;
+    type Error = Error;
+
+    #[inline]
+    fn read(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        match self.lookup(addr) {
+            Some((device, offset)) => device.read(now, offset, data),
+            None => Err(BasicBusError::UnmappedAddress.into()),
+        }
+    }
+
+    #[inline]
+    fn write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        match self.lookup(addr) {
+            Some((device, offset)) => device.write(now, offset, data),
+            None => Err(BasicBusError::UnmappedAddress.into()),
+        }
+    }
+
+    #[inline]
+    fn peek(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        match self.lookup(addr) {
+            Some((device, offset)) => device.peek(now, offset, data),
+            None => Err(BasicBusError::UnmappedAddress.into()),
+        }
+    }
+
+    #[inline]
+    fn poke(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        match self.lookup(addr) {
+            Some((device, offset)) => device.poke(now, offset, data),
+            None => Err(BasicBusError::UnmappedAddress.into()),
+        }
+    }
+
+    #[inline]
+    fn read_modify_write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        modify: &mut dyn FnMut(&mut [u8]),
+    ) -> Result<usize, Self::Error> {
+        match self.lookup(addr) {
+            Some((device, offset)) => device.read_modify_write(now, offset, data, modify),
+            None => Err(BasicBusError::UnmappedAddress.into()),
+        }
+    }
+
+    #[inline]
+    fn read_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        match self.lookup(addr) {
+            Some((device, offset)) => device.read_timed(now, offset, data),
+            None => Err(BasicBusError::UnmappedAddress.into()),
+        }
+    }
+
+    #[inline]
+    fn write_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        match self.lookup(addr) {
+            Some((device, offset)) => device.write_timed(now, offset, data),
+            None => Err(BasicBusError::UnmappedAddress.into()),
+        }
+    }
+
+    #[inline]
+    fn read_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        match self.lookup(addr) {
+            Some((device, offset)) => device.read_ext(now, offset, data, transaction),
+            None => Err(BasicBusError::UnmappedAddress.into()),
+        }
+    }
+
+    #[inline]
+    fn write_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        match self.lookup(addr) {
+            Some((device, offset)) => device.write_ext(now, offset, data, transaction),
+            None => Err(BasicBusError::UnmappedAddress.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use emulator_hal::BusAccessExt;
+    use std::time::Duration;
+
+    fn memory(size: usize, value: u8) -> BoxedBus<u32, Duration, BasicBusError> {
+        Box::new(vec![value; size])
+    }
+
+    #[test]
+    fn test_paged_dispatch() {
+        let now = Duration::ZERO;
+        let mut router = PagedRouter::<u32, Duration, BasicBusError>::new(0x1000, 0x1_0000);
+        router
+            .insert(DeviceId(1), 0x0000..0x4000, memory(0x4000, 1))
+            .unwrap();
+        router
+            .insert(DeviceId(2), 0xE000..0xE010, memory(0x10, 2))
+            .unwrap();
+        router
+            .insert(DeviceId(3), 0xE800..0xF800, memory(0x1000, 3))
+            .unwrap();
+        router
+            .insert(DeviceId(4), 0xF800..0x2_0000, memory(0x1_0800, 4))
+            .unwrap();
+
+        assert_eq!(router.read_u8(now, 0x3FFF).unwrap(), 1);
+        assert_eq!(router.read_u8(now, 0xE00F).unwrap(), 2);
+        assert!(router.read_u8(now, 0xE010).is_err());
+        assert_eq!(router.read_u8(now, 0xE800).unwrap(), 3);
+        assert_eq!(router.read_u8(now, 0xF7FF).unwrap(), 3);
+        assert_eq!(router.read_u8(now, 0xF800).unwrap(), 4);
+        assert_eq!(router.read_u8(now, 0x1_FFFF).unwrap(), 4);
+        assert!(matches!(
+            router.read_u8(now, 0x8000),
+            Err(BasicBusError::UnmappedAddress)
+        ));
+        assert!(router.read_u8(now, 0x2_0000).is_err());
+
+        router.write_beu16(now, 0xE004, 0x1234).unwrap();
+        let device = router.device_mut(DeviceId(2)).unwrap();
+        assert_eq!(device.read_beu16(now, 0x0004).unwrap(), 0x1234);
+        assert_eq!(
+            router.insert(DeviceId(5), 0x3000..0x5000, memory(0x2000, 0)),
+            Err(RouterError::Overlap(DeviceId(1)))
+        );
+    }
+}
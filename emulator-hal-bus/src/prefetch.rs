@@ -0,0 +1,340 @@
+//! A fetch buffer which serves sequential reads from a small cached window
+
+use emulator_hal::{BusAccess, Transaction};
+
+/// Which `BusAccess` method a read through the window was made with
+#[derive(Copy, Clone)]
+enum ReadKind {
+    Read,
+    Timed,
+    Ext(Transaction),
+}
+
+impl ReadKind {
+    /// Returns the descriptor that the window is filled with, where `None` is a plain read
+    fn transaction(self) -> Option<Transaction> {
+        match self {
+            ReadKind::Read | ReadKind::Timed => None,
+            ReadKind::Ext(transaction) => Some(transaction),
+        }
+    }
+}
+
+/// A bus adapter which caches a window of `SIZE` bytes, and serves reads that fall within it
+/// without accessing the wrapped bus
+///
+/// This is meant for interpreters that fetch their instructions a byte or word at a time, where
+/// the per-access dispatch through routers and adapters can dominate the cost of a fetch.  When
+/// a read misses the window, the window is refilled with `SIZE` bytes starting at the address
+/// read.  Writes are always passed through, and invalidate the window if they overlap it.
+///
+/// Since reads are served from the window without reaching the device, this should only wrap
+/// memory without read side effects.  If the memory can be changed without going through this
+/// adapter, such as by DMA or another CPU, `invalidate()` must be called afterwards.  If the
+/// window can't be filled, such as near the end of memory, the read is passed through instead.
+/// Reads larger than the window are also passed through.  A read made with `read_ext()` is only
+/// served from the window if the window was filled by a read with the same descriptor, and reads
+/// with disabled byte lanes are always passed through.  Reads served from the window by
+/// `read_timed()` complete at the time they were made
+pub struct PrefetchBus<Bus, const SIZE: usize> {
+    /// The underlying bus that this object caches reads from
+    pub inner: Bus,
+    buffer: [u8; SIZE],
+    base: usize,
+    filled: usize,
+    transaction: Option<Transaction>,
+}
+
+impl<Bus, const SIZE: usize> PrefetchBus<Bus, SIZE> {
+    /// Construct a new adapter for the given bus, with an empty window
+    pub fn new(inner: Bus) -> Self {
+        Self {
+            inner,
+            buffer: [0; SIZE],
+            base: 0,
+            filled: 0,
+            transaction: None,
+        }
+    }
+
+    /// Discard the contents of the window, so that the next read is fetched from the bus
+    #[inline]
+    pub fn invalidate(&mut self) {
+        self.filled = 0;
+    }
+
+    /// Returns the offset into the window of the given range, if it's contained in the window
+    #[inline]
+    fn offset_of(&self, addr: usize, len: usize) -> Option<usize> {
+        let offset = addr.checked_sub(self.base)?;
+        if offset.checked_add(len)? <= self.filled {
+            Some(offset)
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    fn overlaps(&self, addr: usize, len: usize) -> bool {
+        addr < self.base + self.filled && self.base < addr.saturating_add(len)
+    }
+
+    #[inline]
+    fn read_inner<Address>(
+        inner: &mut Bus,
+        now: Bus::Instant,
+        addr: Address,
+        data: &mut [u8],
+        kind: ReadKind,
+    ) -> Result<(usize, Bus::Instant), Bus::Error>
+    where
+        Address: Copy,
+        Bus: BusAccess<Address>,
+    {
+        match kind {
+            ReadKind::Read => Ok((inner.read(now, addr, data)?, now)),
+            ReadKind::Timed => inner.read_timed(now, addr, data),
+            ReadKind::Ext(transaction) => Ok((inner.read_ext(now, addr, data, transaction)?, now)),
+        }
+    }
+
+    /// Read through the window, and return the number of bytes read and the time the read
+    /// completed, which is `now` if the read was served from the window
+    fn read_cached<Address>(
+        &mut self,
+        now: Bus::Instant,
+        addr: Address,
+        data: &mut [u8],
+        kind: ReadKind,
+    ) -> Result<(usize, Bus::Instant), Bus::Error>
+    where
+        Address: TryInto<usize> + Copy,
+        Bus: BusAccess<Address>,
+    {
+        let start = match addr.try_into() {
+            Ok(start) if data.len() <= SIZE => start,
+            _ => return Self::read_inner(&mut self.inner, now, addr, data, kind),
+        };
+
+        if self.transaction == kind.transaction() {
+            if let Some(offset) = self.offset_of(start, data.len()) {
+                data.copy_from_slice(&self.buffer[offset..offset + data.len()]);
+                return Ok((data.len(), now));
+            }
+        }
+
+        self.filled = 0;
+        match Self::read_inner(&mut self.inner, now, addr, &mut self.buffer, kind) {
+            Ok((count, done)) if count >= data.len() => {
+                self.base = start;
+                self.filled = count.min(SIZE);
+                self.transaction = kind.transaction();
+                data.copy_from_slice(&self.buffer[..data.len()]);
+                Ok((data.len(), done))
+            }
+            _ => Self::read_inner(&mut self.inner, now, addr, data, kind),
+        }
+    }
+}
+
+impl<Address, Bus, const SIZE: usize> BusAccess<Address> for PrefetchBus<Bus, SIZE>
+where
+    Address: TryInto<usize> + Copy,
+    Bus: BusAccess<Address>,
+{
+    type Instant = Bus::Instant;
+    type Error = Bus::Error;
+
+    fn read(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        self.read_cached(now, addr, data, ReadKind::Read)
+            .map(|(count, _)| count)
+    }
+
+    fn write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        match addr.try_into() {
+            Ok(start) if !self.overlaps(start, data.len()) => {}
+            _ => self.invalidate(),
+        }
+        self.inner.write(now, addr, data)
+    }
+
+    fn read_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        if (0..data.len()).any(|index| !transaction.is_byte_enabled(index)) {
+            return self.inner.read_ext(now, addr, data, transaction);
+        }
+        let transaction = transaction.with_byte_enables(u32::MAX);
+        self.read_cached(now, addr, data, ReadKind::Ext(transaction))
+            .map(|(count, _)| count)
+    }
+
+    fn write_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        match addr.try_into() {
+            Ok(start) if !self.overlaps(start, data.len()) => {}
+            _ => self.invalidate(),
+        }
+        self.inner.write_ext(now, addr, data, transaction)
+    }
+
+    fn read_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        self.read_cached(now, addr, data, ReadKind::Timed)
+    }
+
+    fn write_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        match addr.try_into() {
+            Ok(start) if !self.overlaps(start, data.len()) => {}
+            _ => self.invalidate(),
+        }
+        self.inner.write_timed(now, addr, data)
+    }
+
+    fn peek(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        self.inner.peek(now, addr, data)
+    }
+
+    fn poke(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        match addr.try_into() {
+            Ok(start) if !self.overlaps(start, data.len()) => {}
+            _ => self.invalidate(),
+        }
+        self.inner.poke(now, addr, data)
+    }
+
+    fn read_modify_write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        modify: &mut dyn FnMut(&mut [u8]),
+    ) -> Result<usize, Self::Error> {
+        match addr.try_into() {
+            Ok(start) if !self.overlaps(start, data.len()) => {}
+            _ => self.invalidate(),
+        }
+        self.inner.read_modify_write(now, addr, data, modify)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use emulator_hal::BasicBusError;
+    use emulator_hal::BusAccessExt;
+    use std::time::Duration;
+
+    /// Memory which counts the number of reads made from it
+    struct Memory {
+        contents: Vec<u8>,
+        reads: usize,
+    }
+
+    impl BusAccess<u32> for Memory {
+        type Instant = Duration;
+        type Error = BasicBusError;
+
+        fn read(
+            &mut self,
+            _now: Duration,
+            addr: u32,
+            data: &mut [u8],
+        ) -> Result<usize, Self::Error> {
+            self.reads += 1;
+            let addr = addr as usize;
+            let contents = self
+                .contents
+                .get(addr..addr + data.len())
+                .ok_or(BasicBusError::UnmappedAddress)?;
+            data.copy_from_slice(contents);
+            Ok(data.len())
+        }
+
+        fn write(&mut self, _now: Duration, addr: u32, data: &[u8]) -> Result<usize, Self::Error> {
+            let addr = addr as usize;
+            self.contents[addr..addr + data.len()].copy_from_slice(data);
+            Ok(data.len())
+        }
+    }
+
+    #[test]
+    fn test_sequential_fetches() {
+        let memory = Memory {
+            contents: (0..64).collect(),
+            reads: 0,
+        };
+        let mut bus = PrefetchBus::<_, 16>::new(memory);
+
+        for addr in 0..16 {
+            assert_eq!(bus.read_u8(Duration::ZERO, addr).unwrap(), addr as u8);
+        }
+        assert_eq!(bus.inner.reads, 1);
+        assert_eq!(bus.read_beu16(Duration::ZERO, 15).unwrap(), 0x0F10);
+        assert_eq!(bus.inner.reads, 2);
+
+        // writes into the window invalidate it, but writes outside it don't
+        bus.write_u8(Duration::ZERO, 40, 0xAA).unwrap();
+        assert_eq!(bus.read_u8(Duration::ZERO, 16).unwrap(), 16);
+        assert_eq!(bus.inner.reads, 2);
+        bus.write_u8(Duration::ZERO, 17, 0xBB).unwrap();
+        assert_eq!(bus.read_u8(Duration::ZERO, 17).unwrap(), 0xBB);
+        assert_eq!(bus.inner.reads, 3);
+    }
+
+    #[test]
+    fn test_reads_near_end_pass_through() {
+        let memory = Memory {
+            contents: (0..20).collect(),
+            reads: 0,
+        };
+        let mut bus = PrefetchBus::<_, 16>::new(memory);
+
+        assert_eq!(bus.read_u8(Duration::ZERO, 10).unwrap(), 10);
+        assert_eq!(bus.read_u8(Duration::ZERO, 11).unwrap(), 11);
+        assert_eq!(bus.inner.reads, 4);
+        assert!(bus.read_u8(Duration::ZERO, 20).is_err());
+
+        let mut data = [0; 20];
+        bus.read(Duration::ZERO, 0, &mut data).unwrap();
+        assert_eq!(data[19], 19);
+    }
+}
@@ -0,0 +1,312 @@
+//! A bus which dispatches transactions to devices mapped at ranges of addresses
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::ops::{Range, Sub};
+
+use emulator_hal::{
+    BasicBusError, BusAccess, DeviceId, ErrorType, Instant as EmuInstant, Transaction,
+};
+
+/// A boxed device that can be inserted into a `BusRouter`
+pub type BoxedBus<Address, Instant, Error> =
+    Box<dyn BusAccess<Address, Instant = Instant, Error = Error>>;
+
+/// An error returned when a device cannot be inserted into a `BusRouter`
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RouterError {
+    /// The range given contains no addresses
+    EmptyRange,
+    /// The range given overlaps the range of the device with the given id
+    Overlap(DeviceId),
+    /// A device with the given id has already been inserted
+    DuplicateId(DeviceId),
+}
+
+/// A bus that maps ranges of addresses to devices, and dispatches each transaction to the device
+/// that contains its address
+///
+/// The device is given the address relative to the start of its range, so a device mapped at
+/// `0x8000..0xC000` will see an access to `0x8010` as an access to `0x10`.  Accesses to addresses
+/// that aren't mapped to any device return `BasicBusError::UnmappedAddress`, converted into the
+/// router's error type
+///
+/// Each device is identified by the `DeviceId` it's inserted with, which would normally be
+/// assigned by the system's `DeviceRegistry`.  The ranges are stored in their own contiguous
+/// array, separate from the boxed devices, so that finding the device for an address only scans
+/// the ranges, without touching each device's allocation
+pub struct BusRouter<Address, Instant, Error>
+where
+    Address: Copy,
+{
+    ranges: Vec<Range<Address>>,
+    ids: Vec<DeviceId>,
+    devices: Vec<BoxedBus<Address, Instant, Error>>,
+}
+
+impl<Address, Instant, Error> Default for BusRouter<Address, Instant, Error>
+where
+    Address: Copy,
+{
+    fn default() -> Self {
+        Self {
+            ranges: Vec::new(),
+            ids: Vec::new(),
+            devices: Vec::new(),
+        }
+    }
+}
+
+impl<Address, Instant, Error> BusRouter<Address, Instant, Error>
+where
+    Address: Copy + Ord + Sub<Output = Address>,
+{
+    /// Construct a new router with no devices
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Map the given device to the given range of addresses, with the given id
+    ///
+    /// The range must not be empty, and must not overlap the range of any other device, and the
+    /// id must not already be used by another device
+    pub fn insert(
+        &mut self,
+        id: DeviceId,
+        range: Range<Address>,
+        device: BoxedBus<Address, Instant, Error>,
+    ) -> Result<(), RouterError> {
+        if range.start >= range.end {
+            return Err(RouterError::EmptyRange);
+        }
+        if self.ids.contains(&id) {
+            return Err(RouterError::DuplicateId(id));
+        }
+        if let Some(index) = self
+            .ranges
+            .iter()
+            .position(|existing| range.start < existing.end && existing.start < range.end)
+        {
+            return Err(RouterError::Overlap(self.ids[index]));
+        }
+        self.ranges.push(range);
+        self.ids.push(id);
+        self.devices.push(device);
+        Ok(())
+    }
+
+    /// Returns the number of devices in the router
+    pub fn len(&self) -> usize {
+        self.devices.len()
+    }
+
+    /// Returns true if the router contains no devices
+    pub fn is_empty(&self) -> bool {
+        self.devices.is_empty()
+    }
+
+    /// Returns the range of addresses mapped to each device, in the order they were inserted
+    pub fn ranges(&self) -> &[Range<Address>] {
+        &self.ranges
+    }
+
+    /// Returns the id of each device, in the same order as `ranges()`
+    pub fn ids(&self) -> &[DeviceId] {
+        &self.ids
+    }
+
+    /// Returns the device with the given id, if any
+    pub fn device_mut(&mut self, id: DeviceId) -> Option<&mut BoxedBus<Address, Instant, Error>> {
+        let index = self.ids.iter().position(|existing| *existing == id)?;
+        Some(&mut self.devices[index])
+    }
+
+    /// Returns the device that contains the given address, and the address relative to its range
+    #[inline]
+    fn lookup(
+        &mut self,
+        addr: Address,
+    ) -> Option<(&mut BoxedBus<Address, Instant, Error>, Address)> {
+        let index = self.ranges.iter().position(|range| range.contains(&addr))?;
+        Some((&mut self.devices[index], addr - self.ranges[index].start))
+    }
+}
+
+impl<Address, Instant, Error> BusAccess<Address> for BusRouter<Address, Instant, Error>
+where
+    Address: Copy + Ord + Sub<Output = Address>,
+    Instant: EmuInstant,
+    Error: ErrorType + From<BasicBusError>,
+{
+    type Instant = Instant;
+    type Error = Error;
+
+    #[inline]
+    fn read(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        match self.lookup(addr) {
+            Some((device, offset)) => device.read(now, offset, data),
+            None => Err(BasicBusError::UnmappedAddress.into()),
+        }
+    }
+
+    #[inline]
+    fn write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        match self.lookup(addr) {
+            Some((device, offset)) => device.write(now, offset, data),
+            None => Err(BasicBusError::UnmappedAddress.into()),
+        }
+    }
+
+    #[inline]
+    fn peek(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        match self.lookup(addr) {
+            Some((device, offset)) => device.peek(now, offset, data),
+            None => Err(BasicBusError::UnmappedAddress.into()),
+        }
+    }
+
+    #[inline]
+    fn poke(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        match self.lookup(addr) {
+            Some((device, offset)) => device.poke(now, offset, data),
+            None => Err(BasicBusError::UnmappedAddress.into()),
+        }
+    }
+
+    #[inline]
+    fn read_modify_write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        modify: &mut dyn FnMut(&mut [u8]),
+    ) -> Result<usize, Self::Error> {
+        match self.lookup(addr) {
+            Some((device, offset)) => device.read_modify_write(now, offset, data, modify),
+            None => Err(BasicBusError::UnmappedAddress.into()),
+        }
+    }
+
+    #[inline]
+    fn read_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        match self.lookup(addr) {
+            Some((device, offset)) => device.read_timed(now, offset, data),
+            None => Err(BasicBusError::UnmappedAddress.into()),
+        }
+    }
+
+    #[inline]
+    fn write_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        match self.lookup(addr) {
+            Some((device, offset)) => device.write_timed(now, offset, data),
+            None => Err(BasicBusError::UnmappedAddress.into()),
+        }
+    }
+
+    #[inline]
+    fn read_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        match self.lookup(addr) {
+            Some((device, offset)) => device.read_ext(now, offset, data, transaction),
+            None => Err(BasicBusError::UnmappedAddress.into()),
+        }
+    }
+
+    #[inline]
+    fn write_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        match self.lookup(addr) {
+            Some((device, offset)) => device.write_ext(now, offset, data, transaction),
+            None => Err(BasicBusError::UnmappedAddress.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use emulator_hal::BusAccessExt;
+    use std::time::Duration;
+
+    #[test]
+    fn test_dispatch_to_devices() {
+        let mut router = BusRouter::<u32, Duration, BasicBusError>::new();
+        router
+            .insert(DeviceId(4), 0x0000..0x0100, Box::new(vec![0_u8; 0x100]))
+            .unwrap();
+        router
+            .insert(DeviceId(2), 0x8000..0x8010, Box::new(vec![0_u8; 0x10]))
+            .unwrap();
+
+        router.write_beu16(Duration::ZERO, 0x8004, 0x1234).unwrap();
+        assert_eq!(router.read_beu16(Duration::ZERO, 0x8004).unwrap(), 0x1234);
+        assert_eq!(router.read_beu16(Duration::ZERO, 0x0004).unwrap(), 0);
+        assert!(matches!(
+            router.read_u8(Duration::ZERO, 0x4000),
+            Err(BasicBusError::UnmappedAddress)
+        ));
+        assert_eq!(router.ranges(), &[0x0000..0x0100, 0x8000..0x8010]);
+        assert_eq!(router.ids(), &[DeviceId(4), DeviceId(2)]);
+
+        let device = router.device_mut(DeviceId(2)).unwrap();
+        assert_eq!(device.read_beu16(Duration::ZERO, 0x0004).unwrap(), 0x1234);
+        assert!(router.device_mut(DeviceId(1)).is_none());
+    }
+
+    #[test]
+    fn test_reject_overlapping_ranges() {
+        let mut router = BusRouter::<u32, Duration, BasicBusError>::new();
+        router
+            .insert(DeviceId(3), 0x1000..0x2000, Box::new(vec![0_u8; 0x1000]))
+            .unwrap();
+
+        let result = router.insert(DeviceId(0), 0x1800..0x2800, Box::new(vec![0_u8; 0x1000]));
+        assert_eq!(result, Err(RouterError::Overlap(DeviceId(3))));
+        let result = router.insert(DeviceId(0), 0x3000..0x3000, Box::new(Vec::new()));
+        assert_eq!(result, Err(RouterError::EmptyRange));
+        let result = router.insert(DeviceId(3), 0x3000..0x4000, Box::new(vec![0_u8; 0x1000]));
+        assert_eq!(result, Err(RouterError::DuplicateId(DeviceId(3))));
+        assert_eq!(router.len(), 1);
+    }
+}
@@ -0,0 +1,305 @@
+//! Construction of a system of devices from its description
+
+use std::collections::BTreeMap;
+use std::ops::Sub;
+use std::path::{Path, PathBuf};
+
+use emulator_hal::{BasicBusError, BusAdapter, DeviceRegistry, ErrorType, Instant as EmuInstant};
+use emulator_hal_bus::{BoxedBus, BusRouter};
+use emulator_hal_memory::MemoryBlock;
+
+use crate::config::{ConfigError, DeviceConfig, SystemConfig};
+
+/// A function which constructs a device from its description
+pub type DeviceFactory<Address, Instant, Error> =
+    Box<dyn Fn(&DeviceConfig) -> Result<BoxedBus<Address, Instant, Error>, ConfigError>>;
+
+/// A system constructed from a `SystemConfig`
+pub struct System<Address, Instant, Error>
+where
+    Address: Copy,
+{
+    /// The system bus, which contains every device in the description
+    pub bus: BusRouter<Address, Instant, Error>,
+    /// The names of the devices on the bus, where each `DeviceId` is also the device's id in the router
+    pub devices: DeviceRegistry,
+    /// The frequency of each named clock in the system, in hertz
+    pub clocks: BTreeMap<String, u32>,
+}
+
+impl<Address, Instant, Error> System<Address, Instant, Error>
+where
+    Address: Copy,
+    Instant: EmuInstant,
+{
+    /// Returns the period of the named clock, if it was given in the description
+    pub fn clock_period(&self, name: &str) -> Option<Instant::Duration> {
+        self.clocks
+            .get(name)
+            .map(|frequency| Instant::hertz_to_duration(*frequency as u64))
+    }
+}
+
+/// Builds a `System` from a `SystemConfig`
+///
+/// The `ram` and `rom` device types are built in, using `MemoryBlock`.  Other device types can
+/// be registered with a function that constructs the device from its description
+pub struct SystemBuilder<Address, Instant, Error> {
+    factories: BTreeMap<String, DeviceFactory<Address, Instant, Error>>,
+    base_path: PathBuf,
+}
+
+impl<Address, Instant, Error> Default for SystemBuilder<Address, Instant, Error> {
+    fn default() -> Self {
+        Self {
+            factories: BTreeMap::new(),
+            base_path: PathBuf::new(),
+        }
+    }
+}
+
+impl<Address, Instant, Error> SystemBuilder<Address, Instant, Error>
+where
+    Address: Copy + Ord + Sub<Output = Address> + TryFrom<u64> + TryInto<usize> + 'static,
+    Instant: EmuInstant + 'static,
+    Error: ErrorType + From<BasicBusError> + 'static,
+{
+    /// Construct a new builder which only supports the built in device types
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a function to construct devices with the given type name
+    pub fn register<F>(&mut self, kind: &str, factory: F)
+    where
+        F: Fn(&DeviceConfig) -> Result<BoxedBus<Address, Instant, Error>, ConfigError> + 'static,
+    {
+        self.factories.insert(kind.to_string(), Box::new(factory));
+    }
+
+    /// Set the directory that relative image file paths are loaded from
+    pub fn set_base_path<P: AsRef<Path>>(&mut self, path: P) {
+        self.base_path = path.as_ref().to_path_buf();
+    }
+
+    /// Load a description from a file and build it, loading image files relative to its directory
+    pub fn load<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+    ) -> Result<System<Address, Instant, Error>, ConfigError> {
+        let path = path.as_ref();
+        let config = SystemConfig::load(path)?;
+        if let Some(parent) = path.parent() {
+            self.set_base_path(parent);
+        }
+        self.build(&config)
+    }
+
+    /// Build a system from the given description
+    pub fn build(
+        &self,
+        config: &SystemConfig,
+    ) -> Result<System<Address, Instant, Error>, ConfigError> {
+        let mut bus = BusRouter::new();
+        let mut devices = DeviceRegistry::new();
+
+        for device_config in config.devices.iter() {
+            let (size, device) = self.build_device(device_config)?;
+            let range = address_range(device_config, size)?;
+
+            let id = devices
+                .register(&device_config.name)
+                .map_err(|_| ConfigError::DuplicateName(device_config.name.clone()))?;
+            bus.insert(id, range, device)
+                .map_err(|error| ConfigError::Router {
+                    device: device_config.name.clone(),
+                    error,
+                })?;
+        }
+
+        Ok(System {
+            bus,
+            devices,
+            clocks: config.clocks.clone(),
+        })
+    }
+
+    fn build_device(
+        &self,
+        config: &DeviceConfig,
+    ) -> Result<(u64, BoxedBus<Address, Instant, Error>), ConfigError> {
+        match config.kind.as_str() {
+            "ram" => {
+                let size = required_size(config)?;
+                let contents = match &config.file {
+                    Some(file) => self.read_image(config, file, size)?,
+                    None => vec![0; size as usize],
+                };
+                Ok((size, memory_device(MemoryBlock::from(contents))))
+            }
+            "rom" => {
+                let file = config
+                    .file
+                    .as_ref()
+                    .ok_or_else(|| ConfigError::MissingField {
+                        device: config.name.clone(),
+                        field: "file",
+                    })?;
+                let contents = match config.size {
+                    Some(size) => self.read_image(config, file, size)?,
+                    None => self.read_file(file)?,
+                };
+                let size = contents.len() as u64;
+                let mut memory = MemoryBlock::from(contents);
+                memory.read_only();
+                Ok((size, memory_device(memory)))
+            }
+            kind => {
+                let factory =
+                    self.factories
+                        .get(kind)
+                        .ok_or_else(|| ConfigError::UnknownDeviceType {
+                            device: config.name.clone(),
+                            kind: kind.to_string(),
+                        })?;
+                let size = required_size(config)?;
+                Ok((size, factory(config)?))
+            }
+        }
+    }
+
+    fn read_file(&self, file: &Path) -> Result<Vec<u8>, ConfigError> {
+        let path = self.base_path.join(file);
+        std::fs::read(&path).map_err(|err| ConfigError::Io(path, err))
+    }
+
+    /// Read an image file, padded with zeros to the given size
+    fn read_image(
+        &self,
+        config: &DeviceConfig,
+        file: &Path,
+        size: u64,
+    ) -> Result<Vec<u8>, ConfigError> {
+        let mut contents = self.read_file(file)?;
+        if contents.len() as u64 > size {
+            return Err(ConfigError::ImageTooLarge {
+                device: config.name.clone(),
+            });
+        }
+        contents.resize(size as usize, 0);
+        Ok(contents)
+    }
+}
+
+fn memory_device<Address, Instant, Error>(
+    memory: MemoryBlock<Instant>,
+) -> BoxedBus<Address, Instant, Error>
+where
+    Address: Copy + TryInto<usize> + 'static,
+    Instant: EmuInstant + 'static,
+    Error: ErrorType + From<BasicBusError> + 'static,
+{
+    Box::new(BusAdapter::<Address, Address, _, Error>::new(
+        memory,
+        |addr| addr,
+    ))
+}
+
+fn required_size(config: &DeviceConfig) -> Result<u64, ConfigError> {
+    config.size.ok_or_else(|| ConfigError::MissingField {
+        device: config.name.clone(),
+        field: "size",
+    })
+}
+
+fn address_range<Address>(
+    config: &DeviceConfig,
+    size: u64,
+) -> Result<std::ops::Range<Address>, ConfigError>
+where
+    Address: TryFrom<u64>,
+{
+    let invalid = || ConfigError::InvalidAddress {
+        device: config.name.clone(),
+    };
+    let end = config.address.checked_add(size).ok_or_else(invalid)?;
+    let start = Address::try_from(config.address).map_err(|_| invalid())?;
+    let end = Address::try_from(end).map_err(|_| invalid())?;
+    Ok(start..end)
+}
+
+#[cfg(all(test, feature = "toml"))]
+mod test {
+    use super::*;
+    use emulator_hal::{BusAccessExt, DeviceId, NoBus};
+    use std::time::Duration;
+
+    const SYSTEM: &str = r#"
+        name = "test board"
+        clocks = { cpu = 1000000 }
+
+        [[devices]]
+        name = "rom"
+        type = "rom"
+        address = 0x0000
+        size = 0x100
+        file = "test.bin"
+
+        [[devices]]
+        name = "ram"
+        type = "ram"
+        address = 0x8000
+        size = 0x1000
+
+        [[devices]]
+        name = "null"
+        type = "null"
+        address = 0xF000
+        size = 0x10
+    "#;
+
+    #[test]
+    fn test_build_system() {
+        let directory = std::env::temp_dir().join("emulator-hal-config-test");
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("test.bin"), [0x12, 0x34, 0x56, 0x78]).unwrap();
+
+        let mut builder = SystemBuilder::<u32, Duration, BasicBusError>::new();
+        builder.register("null", |_| Ok(Box::new(NoBus::new())));
+        builder.set_base_path(&directory);
+
+        let config = SystemConfig::from_toml(SYSTEM).unwrap();
+        let mut system = builder.build(&config).unwrap();
+
+        assert_eq!(system.devices.id("ram"), Some(DeviceId(1)));
+        assert_eq!(system.clock_period("cpu"), Some(Duration::from_micros(1)));
+
+        let bus = &mut system.bus;
+        assert_eq!(bus.read_beu32(Duration::ZERO, 0x0000).unwrap(), 0x1234_5678);
+        assert_eq!(bus.read_beu32(Duration::ZERO, 0x00FC).unwrap(), 0);
+        assert!(matches!(
+            bus.write_beu32(Duration::ZERO, 0x0000, 0),
+            Err(BasicBusError::ShortTransfer(0))
+        ));
+        assert_eq!(bus.read_beu32(Duration::ZERO, 0x0000).unwrap(), 0x1234_5678);
+
+        bus.write_beu32(Duration::ZERO, 0x8010, 0xCAFE_BABE)
+            .unwrap();
+        assert_eq!(bus.read_beu32(Duration::ZERO, 0x8010).unwrap(), 0xCAFE_BABE);
+        assert!(bus.read_u8(Duration::ZERO, 0x4000).is_err());
+    }
+
+    #[test]
+    fn test_unknown_device_type() {
+        let builder = SystemBuilder::<u32, Duration, BasicBusError>::new();
+        let config = SystemConfig::from_toml(
+            "[[devices]]\nname = \"uart\"\ntype = \"mc6850\"\naddress = 0x100\nsize = 2\n",
+        )
+        .unwrap();
+
+        assert!(matches!(
+            builder.build(&config),
+            Err(ConfigError::UnknownDeviceType { .. })
+        ));
+    }
+}
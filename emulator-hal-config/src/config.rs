@@ -0,0 +1,267 @@
+//! The description of a system, which can be loaded from a TOML or RON file
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use emulator_hal_bus::RouterError;
+
+/// An error that occurred while loading a description or building a system from it
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ConfigError {
+    /// A file could not be read
+    Io(PathBuf, io::Error),
+    /// The description could not be parsed
+    Parse(String),
+    /// The format of the file could not be determined from its extension
+    UnsupportedFormat(PathBuf),
+    /// A device has a type that is neither built in nor registered with the builder
+    UnknownDeviceType {
+        /// The name of the device
+        device: String,
+        /// The type given for the device
+        kind: String,
+    },
+    /// A device is missing a field that is required for its type
+    MissingField {
+        /// The name of the device
+        device: String,
+        /// The name of the missing field
+        field: &'static str,
+    },
+    /// A device's address range can't be represented by the system's address type
+    InvalidAddress {
+        /// The name of the device
+        device: String,
+    },
+    /// A device's image file is larger than the size given for the device
+    ImageTooLarge {
+        /// The name of the device
+        device: String,
+    },
+    /// More than one device has the same name
+    DuplicateName(String),
+    /// A device could not be inserted into the router
+    Router {
+        /// The name of the device
+        device: String,
+        /// The error returned by the router
+        error: RouterError,
+    },
+    /// A registered device type failed to construct a device
+    Device {
+        /// The name of the device
+        device: String,
+        /// A description of the error
+        message: String,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, err) => write!(f, "error reading {}: {}", path.display(), err),
+            ConfigError::Parse(message) => write!(f, "error parsing system: {}", message),
+            ConfigError::UnsupportedFormat(path) => {
+                write!(f, "unsupported file format: {}", path.display())
+            }
+            ConfigError::UnknownDeviceType { device, kind } => {
+                write!(f, "device {} has an unknown type {:?}", device, kind)
+            }
+            ConfigError::MissingField { device, field } => {
+                write!(f, "device {} is missing the field {:?}", device, field)
+            }
+            ConfigError::InvalidAddress { device } => {
+                write!(f, "device {} has an invalid address range", device)
+            }
+            ConfigError::ImageTooLarge { device } => {
+                write!(f, "the image for device {} is larger than its size", device)
+            }
+            ConfigError::DuplicateName(device) => {
+                write!(f, "more than one device is named {}", device)
+            }
+            ConfigError::Router { device, error } => {
+                write!(f, "device {} cannot be mapped: {:?}", device, error)
+            }
+            ConfigError::Device { device, message } => write!(f, "device {}: {}", device, message),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// A parameter value given to a device in its `params` table
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum ConfigValue {
+    /// A boolean value
+    Bool(bool),
+    /// An integer value
+    Integer(i64),
+    /// A floating point value
+    Float(f64),
+    /// A string value
+    String(String),
+}
+
+impl ConfigValue {
+    /// Returns the value if it's a boolean
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            ConfigValue::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Returns the value if it's an integer
+    pub fn as_integer(&self) -> Option<i64> {
+        match self {
+            ConfigValue::Integer(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Returns the value if it's a number, converting integers into floats
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            ConfigValue::Integer(value) => Some(*value as f64),
+            ConfigValue::Float(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Returns the value if it's a string
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            ConfigValue::String(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+/// The description of a single device mapped onto the system bus
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeviceConfig {
+    /// The unique name of the device
+    pub name: String,
+    /// The type of the device, either `ram`, `rom`, or a type registered with the builder
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// The address of the first byte of the device on the system bus
+    pub address: u64,
+    /// The number of bytes of the address space the device occupies
+    ///
+    /// This is required for all types except `rom`, which defaults to the size of its image
+    #[serde(default)]
+    pub size: Option<u64>,
+    /// The image file loaded into the device, relative to the directory of the description
+    #[serde(default)]
+    pub file: Option<PathBuf>,
+    /// Any other parameters, which are given to the constructor of registered device types
+    #[serde(default)]
+    pub params: BTreeMap<String, ConfigValue>,
+}
+
+impl DeviceConfig {
+    /// Returns the parameter with the given name, if it was given
+    pub fn param(&self, name: &str) -> Option<&ConfigValue> {
+        self.params.get(name)
+    }
+}
+
+/// The description of a complete system
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SystemConfig {
+    /// A human-readable name for the system
+    #[serde(default)]
+    pub name: Option<String>,
+    /// The frequency of each named clock in the system, in hertz
+    #[serde(default)]
+    pub clocks: BTreeMap<String, u32>,
+    /// The devices mapped onto the system bus
+    #[serde(default)]
+    pub devices: Vec<DeviceConfig>,
+}
+
+impl SystemConfig {
+    /// Parse a system description in the TOML format
+    #[cfg(feature = "toml")]
+    pub fn from_toml(text: &str) -> Result<Self, ConfigError> {
+        toml::from_str(text).map_err(|err| ConfigError::Parse(err.to_string()))
+    }
+
+    /// Parse a system description in the RON format
+    #[cfg(feature = "ron")]
+    pub fn from_ron(text: &str) -> Result<Self, ConfigError> {
+        ron::from_str(text).map_err(|err| ConfigError::Parse(err.to_string()))
+    }
+
+    /// Load a system description from a file, using its extension to determine the format
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        match path.extension().and_then(|extension| extension.to_str()) {
+            #[cfg(feature = "toml")]
+            Some("toml") => Self::from_toml(&read_text(path)?),
+            #[cfg(feature = "ron")]
+            Some("ron") => Self::from_ron(&read_text(path)?),
+            _ => Err(ConfigError::UnsupportedFormat(path.to_path_buf())),
+        }
+    }
+}
+
+#[cfg(any(feature = "toml", feature = "ron"))]
+fn read_text(path: &Path) -> Result<String, ConfigError> {
+    std::fs::read_to_string(path).map_err(|err| ConfigError::Io(path.to_path_buf(), err))
+}
+
+#[cfg(all(test, feature = "toml", feature = "ron"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_toml_and_ron_are_equivalent() {
+        let from_toml = SystemConfig::from_toml(
+            r#"
+            name = "board"
+            clocks = { cpu = 4000000 }
+
+            [[devices]]
+            name = "uart"
+            type = "mc6850"
+            address = 0x2000
+            size = 2
+            params = { baud = 9600, echo = true }
+            "#,
+        )
+        .unwrap();
+
+        let from_ron = SystemConfig::from_ron(
+            r#"(
+                name: Some("board"),
+                clocks: { "cpu": 4000000 },
+                devices: [
+                    (
+                        name: "uart",
+                        type: "mc6850",
+                        address: 0x2000,
+                        size: Some(2),
+                        params: { "baud": 9600, "echo": true },
+                    ),
+                ],
+            )"#,
+        )
+        .unwrap();
+
+        assert_eq!(from_toml, from_ron);
+        assert_eq!(
+            from_toml.devices[0].param("baud"),
+            Some(&ConfigValue::Integer(9600))
+        );
+    }
+}
@@ -0,0 +1,8 @@
+#![doc = include_str!("../README.md")]
+#![warn(missing_docs)]
+
+mod builder;
+pub use crate::builder::*;
+
+mod config;
+pub use crate::config::*;
@@ -0,0 +1,349 @@
+#![doc = include_str!("../README.md")]
+#![warn(missing_docs)]
+
+use std::collections::VecDeque;
+use std::fmt::{self, Write as _};
+use std::io;
+use std::time::{Duration, Instant as StdInstant};
+
+use emulator_hal::{BusAccess, Inspect};
+use emulator_hal_trace::{Access, TraceAddress, TraceEvent};
+
+/// The number of bytes shown on each line of a hexdump
+const HEXDUMP_WIDTH: usize = 16;
+
+/// A titled section of a dashboard
+struct Pane {
+    title: String,
+    lines: Vec<String>,
+}
+
+/// The contents of the dashboard for a single refresh, which is built up one pane at a time
+///
+/// Each pane is rendered in a box with the given title, with the panes stacked vertically in
+/// the order they were added
+#[derive(Default)]
+pub struct Frame {
+    panes: Vec<Pane>,
+}
+
+impl Frame {
+    /// Construct a new empty frame
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a pane containing the given text
+    pub fn text(&mut self, title: &str, text: &str) {
+        self.panes.push(Pane {
+            title: title.to_string(),
+            lines: text.lines().map(|line| line.to_string()).collect(),
+        });
+    }
+
+    /// Add a pane containing the `brief_summary()` of the given device
+    pub fn brief_summary<Address, Bus, Device>(
+        &mut self,
+        title: &str,
+        device: &mut Device,
+        bus: &mut Bus,
+    ) -> Result<(), Device::Error>
+    where
+        Address: Copy,
+        Bus: BusAccess<Address>,
+        Device: Inspect<Address, Bus, String>,
+    {
+        let mut output = String::new();
+        device.brief_summary(bus, &mut output)?;
+        self.text(title, &output);
+        Ok(())
+    }
+
+    /// Add a pane containing the `detailed_summary()` of the given device
+    pub fn detailed_summary<Address, Bus, Device>(
+        &mut self,
+        title: &str,
+        device: &mut Device,
+        bus: &mut Bus,
+    ) -> Result<(), Device::Error>
+    where
+        Address: Copy,
+        Bus: BusAccess<Address>,
+        Device: Inspect<Address, Bus, String>,
+    {
+        let mut output = String::new();
+        device.detailed_summary(bus, &mut output)?;
+        self.text(title, &output);
+        Ok(())
+    }
+
+    /// Add a pane containing a hexdump of `length` bytes of the given bus, starting at `start`
+    ///
+    /// Any lines that cannot be read from the bus are shown as `??`
+    pub fn hexdump<Address, Bus>(
+        &mut self,
+        title: &str,
+        bus: &mut Bus,
+        now: Bus::Instant,
+        start: Address,
+        length: usize,
+    ) where
+        Address: TraceAddress,
+        Bus: BusAccess<Address>,
+    {
+        let start = start.to_raw();
+        let mut lines = Vec::new();
+        for offset in (0..length).step_by(HEXDUMP_WIDTH) {
+            let addr = start.wrapping_add(offset as u64);
+            let mut data = [0; HEXDUMP_WIDTH];
+            let data = &mut data[..HEXDUMP_WIDTH.min(length - offset)];
+
+            let mut line = format!("{:08x}: ", addr);
+            match bus.read(now, Address::from_raw(addr), data) {
+                Ok(_) => {
+                    for byte in data.iter() {
+                        let _ = write!(line, "{:02x} ", byte);
+                    }
+                    line.push_str(&"   ".repeat(HEXDUMP_WIDTH - data.len()));
+                    line.push(' ');
+                    line.extend(data.iter().map(|byte| {
+                        if byte.is_ascii_graphic() || *byte == b' ' {
+                            *byte as char
+                        } else {
+                            '.'
+                        }
+                    }));
+                }
+                Err(_) => line.push_str(&"?? ".repeat(data.len())),
+            }
+            lines.push(line.trim_end().to_string());
+        }
+
+        self.panes.push(Pane {
+            title: title.to_string(),
+            lines,
+        });
+    }
+
+    /// Add a pane containing the last `limit` transactions from a trace
+    ///
+    /// The events can come from either a `TraceRecord` or `BufferedRecord` using `as_event()`
+    pub fn trace<'a, Address, Instant>(
+        &mut self,
+        title: &str,
+        events: impl IntoIterator<Item = TraceEvent<'a, Address, Instant>>,
+        limit: usize,
+    ) where
+        Address: TraceAddress + 'a,
+        Instant: fmt::Debug + 'a,
+    {
+        let mut lines = VecDeque::with_capacity(limit);
+        for event in events {
+            if lines.len() == limit {
+                lines.pop_front();
+            }
+            if limit > 0 {
+                lines.push_back(format_event(&event));
+            }
+        }
+
+        self.panes.push(Pane {
+            title: title.to_string(),
+            lines: lines.into(),
+        });
+    }
+
+    /// Render the frame as text, with each pane boxed and truncated to the given width
+    pub fn render(&self, width: usize) -> String {
+        let inner = width.saturating_sub(4);
+        let mut output = String::new();
+        for pane in self.panes.iter() {
+            let title = truncate(&pane.title, inner.saturating_sub(2));
+            let fill = inner.saturating_sub(title.chars().count() + 1);
+            let _ = writeln!(output, "┌─ {} {}┐", title, "─".repeat(fill));
+            for line in pane.lines.iter() {
+                let line = truncate(line, inner);
+                let padding = inner - line.chars().count();
+                let _ = writeln!(output, "│ {}{} │", line, " ".repeat(padding));
+            }
+            let _ = writeln!(output, "└{}┘", "─".repeat(inner + 2));
+        }
+        output
+    }
+}
+
+fn truncate(text: &str, width: usize) -> &str {
+    match text.char_indices().nth(width) {
+        Some((index, _)) => &text[..index],
+        None => text,
+    }
+}
+
+fn format_event<Address, Instant>(event: &TraceEvent<'_, Address, Instant>) -> String
+where
+    Address: TraceAddress,
+    Instant: fmt::Debug,
+{
+    let access = match event.access {
+        Access::Read => 'R',
+        Access::Write => 'W',
+    };
+    let mut line = format!(
+        "{:?} {} {} {:08x}:",
+        event.instant,
+        event.device,
+        access,
+        event.addr.to_raw()
+    );
+    for byte in event.data.iter() {
+        let _ = write!(line, " {:02x}", byte);
+    }
+    if event.count.is_none() {
+        line.push_str(" (error)");
+    }
+    line
+}
+
+/// Draws frames to a terminal, clearing the screen each time
+///
+/// The dashboard can be refreshed from inside the main loop of an emulator, by checking
+/// `should_refresh()` after each step, and only building and drawing a new `Frame` when it
+/// returns true, so that the cost of rendering doesn't slow down the emulator
+pub struct Dashboard<W>
+where
+    W: io::Write,
+{
+    output: W,
+    /// The width of the dashboard in characters
+    pub width: usize,
+    /// The minimum amount of real time between each refresh
+    pub refresh_interval: Duration,
+    last_draw: Option<StdInstant>,
+}
+
+impl Dashboard<io::Stdout> {
+    /// Construct a new dashboard which draws to stdout
+    pub fn stdout() -> Self {
+        Self::new(io::stdout())
+    }
+}
+
+impl<W> Dashboard<W>
+where
+    W: io::Write,
+{
+    /// Construct a new dashboard which draws to the given output, refreshing 10 times per second
+    pub fn new(output: W) -> Self {
+        Self {
+            output,
+            width: 80,
+            refresh_interval: Duration::from_millis(100),
+            last_draw: None,
+        }
+    }
+
+    /// Returns true if the refresh interval has elapsed since the last frame was drawn
+    pub fn should_refresh(&self) -> bool {
+        match self.last_draw {
+            Some(last_draw) => last_draw.elapsed() >= self.refresh_interval,
+            None => true,
+        }
+    }
+
+    /// Clear the terminal and draw the given frame
+    pub fn draw(&mut self, frame: &Frame) -> Result<(), io::Error> {
+        self.output.write_all(b"\x1b[H\x1b[2J")?;
+        self.output.write_all(frame.render(self.width).as_bytes())?;
+        self.output.flush()?;
+        self.last_draw = Some(StdInstant::now());
+        Ok(())
+    }
+
+    /// Returns the underlying output
+    pub fn into_inner(self) -> W {
+        self.output
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use emulator_hal::BusAccessExt;
+    use emulator_hal::{DeviceId, Instant};
+    use emulator_hal_trace::{RecordingBus, TraceRecord};
+
+    struct Cpu {
+        pc: u32,
+    }
+
+    impl Inspect<u32, Vec<u8>, String> for Cpu {
+        type InfoType = ();
+        type Error = fmt::Error;
+
+        fn inspect(&mut self, _info: (), _bus: &mut Vec<u8>, _writer: &mut String) -> fmt::Result {
+            Ok(())
+        }
+
+        fn brief_summary(&mut self, _bus: &mut Vec<u8>, writer: &mut String) -> fmt::Result {
+            writeln!(writer, "pc: {:04x}", self.pc)
+        }
+
+        fn detailed_summary(&mut self, bus: &mut Vec<u8>, writer: &mut String) -> fmt::Result {
+            self.brief_summary(bus, writer)
+        }
+    }
+
+    #[test]
+    fn test_render_frame() {
+        let mut memory = b"Hello, World!\x00\xff\x10".to_vec();
+        let mut cpu = Cpu { pc: 0x1234 };
+
+        let mut frame = Frame::new();
+        frame.brief_summary("cpu", &mut cpu, &mut memory).unwrap();
+        frame.hexdump("memory", &mut memory, Duration::START, 0_u32, 20);
+
+        assert_eq!(
+            frame.render(76),
+            "\
+┌─ cpu ────────────────────────────────────────────────────────────────────┐
+│ pc: 1234                                                                 │
+└──────────────────────────────────────────────────────────────────────────┘
+┌─ memory ─────────────────────────────────────────────────────────────────┐
+│ 00000000: 48 65 6c 6c 6f 2c 20 57 6f 72 6c 64 21 00 ff 10  Hello, World! │
+│ 00000010: ?? ?? ?? ??                                                    │
+└──────────────────────────────────────────────────────────────────────────┘
+"
+        );
+    }
+
+    #[test]
+    fn test_draw_trace() {
+        let mut records: Vec<TraceRecord<u32, Duration>> = Vec::new();
+        let mut bus = RecordingBus::new(DeviceId(2), vec![0_u8; 16], &mut records);
+        for i in 0..4 {
+            bus.write_beu16(Duration::from_nanos(i * 10), i as u32 * 2, 0xABCD)
+                .unwrap();
+        }
+        bus.read_u8(Duration::from_nanos(50), 0x20).unwrap_err();
+
+        let mut frame = Frame::new();
+        frame.trace("trace", records.iter().map(|record| record.as_event()), 2);
+
+        let mut dashboard = Dashboard::new(Vec::new());
+        dashboard.width = 40;
+        assert!(dashboard.should_refresh());
+        dashboard.draw(&frame).unwrap();
+        assert!(!dashboard.should_refresh());
+
+        let output = String::from_utf8(dashboard.into_inner()).unwrap();
+        assert_eq!(
+            output,
+            "\x1b[H\x1b[2J\
+┌─ trace ──────────────────────────────┐
+│ 30ns #2 W 00000006: ab cd            │
+│ 50ns #2 R 00000020: 00 (error)       │
+└──────────────────────────────────────┘
+"
+        );
+    }
+}
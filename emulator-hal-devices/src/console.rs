@@ -0,0 +1,303 @@
+//! A register-mapped text console
+
+use alloc::string::String;
+use core::str;
+
+use emulator_hal::{BasicBusError, BusAccess, ByteOrder, ErrorType, ShortTransfer};
+use emulator_hal_serial::SerialPort;
+
+/// The register number of the data register
+pub const CONSOLE_DATA: usize = 0;
+/// The register number of the status register
+pub const CONSOLE_STATUS: usize = 1;
+
+/// Status bit which is set when a received character can be read from the data register
+pub const CONSOLE_RX_READY: u8 = 0x01;
+/// Status bit which is set when a character can be written to the data register
+pub const CONSOLE_TX_READY: u8 = 0x02;
+
+/// An error that occurred while accessing a `ConsoleDevice`
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ConsoleError<PortError> {
+    /// The access was outside of the console's registers
+    Bus(BasicBusError),
+    /// The console's port returned an error
+    Port(PortError),
+}
+
+impl<PortError> ErrorType for ConsoleError<PortError> where PortError: ErrorType {}
+
+impl<PortError> From<BasicBusError> for ConsoleError<PortError> {
+    fn from(err: BasicBusError) -> Self {
+        ConsoleError::Bus(err)
+    }
+}
+
+impl<PortError> From<ShortTransfer> for ConsoleError<PortError> {
+    fn from(err: ShortTransfer) -> Self {
+        ConsoleError::Bus(err.into())
+    }
+}
+
+/// A console device for printing text from, and optionally reading text into, an emulated system
+///
+/// The console has two registers, each `width` bytes wide, with the character in the least
+/// significant byte according to `order`.  Writing the data register transmits a byte, and
+/// reading it returns the next received byte, or 0 if there is none.  The status register has
+/// the `CONSOLE_RX_READY` and `CONSOLE_TX_READY` bits.  Other bytes of the registers are ignored
+/// on write and read as 0.
+///
+/// Bytes are passed to the port unchanged, so UTF-8 text can be written one byte at a time.  If
+/// `capture()` is enabled, the transmitted text is also decoded and saved, with any invalid
+/// sequences replaced by U+FFFD
+pub struct ConsoleDevice<Port> {
+    port: Port,
+    receive: bool,
+    received: Option<u8>,
+    /// The width of each register in bytes
+    pub width: usize,
+    /// The order of the bytes in each register
+    pub order: ByteOrder,
+    decoder: Utf8Decoder,
+    transcript: Option<String>,
+}
+
+impl<Port> ConsoleDevice<Port>
+where
+    Port: SerialPort,
+{
+    /// Construct a new console with 1 byte wide registers, which transmits to the given port,
+    /// and receives from it if `receive` is true
+    pub fn new(port: Port, receive: bool) -> Self {
+        Self {
+            port,
+            receive,
+            received: None,
+            width: 1,
+            order: ByteOrder::Little,
+            decoder: Utf8Decoder::default(),
+            transcript: None,
+        }
+    }
+
+    /// Set the width and byte order of the registers
+    pub fn with_width(mut self, width: usize, order: ByteOrder) -> Self {
+        self.width = width.max(1);
+        self.order = order;
+        self
+    }
+
+    /// Save the text that is transmitted, so that it can be checked with `transcript()`
+    pub fn capture(mut self) -> Self {
+        self.transcript = Some(String::new());
+        self
+    }
+
+    /// Returns the text that has been transmitted since the console was constructed, if
+    /// `capture()` was enabled
+    pub fn transcript(&self) -> Option<&str> {
+        self.transcript.as_deref()
+    }
+
+    /// Returns a reference to the underlying port
+    pub fn port(&mut self) -> &mut Port {
+        &mut self.port
+    }
+
+    fn poll(&mut self, now: Port::Instant) -> Result<(), Port::Error> {
+        if self.receive && self.received.is_none() {
+            self.received = self.port.receive(now)?;
+        }
+        Ok(())
+    }
+
+    fn transmit(&mut self, now: Port::Instant, byte: u8) -> Result<(), Port::Error> {
+        if let Some(transcript) = self.transcript.as_mut() {
+            self.decoder.push(byte, transcript);
+        }
+        self.port.transmit(now, byte)
+    }
+
+    /// Returns the register number, and true if the address is the least significant byte
+    fn decode(&self, addr: usize) -> Result<(usize, bool), BasicBusError> {
+        let register = addr / self.width;
+        if register > CONSOLE_STATUS {
+            return Err(BasicBusError::UnmappedAddress);
+        }
+        let low_byte = match self.order {
+            ByteOrder::Little => 0,
+            ByteOrder::Big => self.width - 1,
+        };
+        Ok((register, addr % self.width == low_byte))
+    }
+}
+
+#[cfg(feature = "std")]
+impl<Instant> ConsoleDevice<emulator_hal_serial::StreamPort<std::io::Stdout, Instant>>
+where
+    Instant: emulator_hal::Instant,
+{
+    /// Construct a new console which transmits to stdout, and doesn't receive
+    pub fn stdout() -> Self {
+        Self::new(
+            emulator_hal_serial::StreamPort::new(std::io::empty(), std::io::stdout()),
+            false,
+        )
+    }
+
+    /// Construct a new console which transmits to stdout, and receives from stdin
+    pub fn stdio() -> Self {
+        Self::new(emulator_hal_serial::StreamPort::stdio(), true)
+    }
+}
+
+impl<Address, Port> BusAccess<Address> for ConsoleDevice<Port>
+where
+    Address: TryInto<usize> + Copy,
+    Port: SerialPort,
+{
+    type Instant = Port::Instant;
+    type Error = ConsoleError<Port::Error>;
+
+    fn read(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        let addr = addr
+            .try_into()
+            .map_err(|_| BasicBusError::UnmappedAddress)?;
+        self.poll(now).map_err(ConsoleError::Port)?;
+        for (i, byte) in data.iter_mut().enumerate() {
+            *byte = match self.decode(addr + i)? {
+                (CONSOLE_DATA, true) => self.received.take().unwrap_or(0),
+                (CONSOLE_STATUS, true) => {
+                    let status = self.port.line_status(now).map_err(ConsoleError::Port)?;
+                    let mut value = 0;
+                    if self.received.is_some() {
+                        value |= CONSOLE_RX_READY;
+                    }
+                    if status.transmit_ready {
+                        value |= CONSOLE_TX_READY;
+                    }
+                    value
+                }
+                _ => 0,
+            };
+        }
+        Ok(data.len())
+    }
+
+    fn peek(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        self.poll(now).map_err(ConsoleError::Port)?;
+        let received = self.received;
+        let result = self.read(now, addr, data);
+        self.received = received;
+        result
+    }
+
+    fn write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        let addr = addr
+            .try_into()
+            .map_err(|_| BasicBusError::UnmappedAddress)?;
+        for (i, byte) in data.iter().enumerate() {
+            if let (CONSOLE_DATA, true) = self.decode(addr + i)? {
+                self.transmit(now, *byte).map_err(ConsoleError::Port)?;
+            }
+        }
+        Ok(data.len())
+    }
+}
+
+/// Decodes UTF-8 text that arrives one byte at a time
+#[derive(Default)]
+struct Utf8Decoder {
+    pending: [u8; 4],
+    length: usize,
+}
+
+impl Utf8Decoder {
+    fn push(&mut self, byte: u8, output: &mut String) {
+        self.pending[self.length] = byte;
+        self.length += 1;
+
+        match str::from_utf8(&self.pending[..self.length]) {
+            Ok(text) => {
+                output.push_str(text);
+                self.length = 0;
+            }
+            // the sequence is incomplete, so wait for more bytes
+            Err(err) if err.error_len().is_none() && self.length < self.pending.len() => {}
+            Err(_) => {
+                output.push(char::REPLACEMENT_CHARACTER);
+                // the last byte might be the start of a new sequence
+                let length = self.length;
+                self.length = 0;
+                if length > 1 {
+                    self.push(byte, output);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use emulator_hal::BusAccessExt;
+    use emulator_hal_serial::LoopbackPort;
+    use std::time::Duration;
+
+    #[test]
+    fn test_utf8_written_bytewise() {
+        let port = LoopbackPort::new(Duration::ZERO, 64);
+        let mut console = ConsoleDevice::new(port, false).capture();
+
+        for byte in "héllo ✓".bytes() {
+            console
+                .write_u8(Duration::ZERO, CONSOLE_DATA, byte)
+                .unwrap();
+        }
+        for byte in [0xFF, b'!', 0xE2, 0x9C, b'?'] {
+            console
+                .write_u8(Duration::ZERO, CONSOLE_DATA, byte)
+                .unwrap();
+        }
+        assert_eq!(console.transcript(), Some("héllo ✓\u{FFFD}!\u{FFFD}?"));
+    }
+
+    #[test]
+    fn test_wide_registers_with_receive() {
+        let port = LoopbackPort::new(Duration::from_micros(10), 64);
+        let mut console = ConsoleDevice::new(port, true).with_width(4, ByteOrder::Big);
+
+        console
+            .write_beu32(Duration::ZERO, 0_u32, b'A' as u32)
+            .unwrap();
+        assert_eq!(
+            console.read_beu32(Duration::ZERO, 4_u32).unwrap(),
+            CONSOLE_TX_READY as u32
+        );
+
+        let later = Duration::from_micros(10);
+        assert_eq!(
+            console.read_beu32(later, 4_u32).unwrap(),
+            (CONSOLE_RX_READY | CONSOLE_TX_READY) as u32
+        );
+        assert_eq!(console.read_beu32(later, 0_u32).unwrap(), b'A' as u32);
+        assert_eq!(console.read_beu32(later, 0_u32).unwrap(), 0);
+        assert!(console.read_u8(later, 8_u32).is_err());
+    }
+}
@@ -0,0 +1,572 @@
+//! A programmable multi-channel DMA engine
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::convert::Infallible;
+
+use emulator_hal::{BasicBusError, BusAccess, BusRequest, Instant as EmuInstant, Signal, Step};
+
+/// The number of bytes of address space used by each channel's registers
+pub const DMA_CHANNEL_REGISTERS: usize = 16;
+
+/// Control register bit which starts a transfer, and which is cleared when the transfer ends
+pub const DMA_ENABLE: u8 = 0x01;
+/// Control register bit which transfers the whole block at once, instead of one byte per step
+pub const DMA_BURST: u8 = 0x02;
+/// Control register bit which raises the interrupt output while the channel's done or error flag
+/// is set
+pub const DMA_INTERRUPT: u8 = 0x04;
+/// Control register bit which keeps the source address fixed, such as for reading a FIFO
+pub const DMA_SOURCE_FIXED: u8 = 0x08;
+/// Control register bit which keeps the destination address fixed, such as for writing a FIFO
+pub const DMA_DEST_FIXED: u8 = 0x10;
+
+/// Status register bit which is set when a transfer completes, and is cleared by writing a 1 to it
+pub const DMA_DONE: u8 = 0x01;
+/// Status register bit which is set when a transfer is stopped by a bus error, and is cleared by
+/// writing a 1 to it
+pub const DMA_ERROR: u8 = 0x02;
+
+#[derive(Clone, Debug, Default)]
+struct Channel {
+    control: u8,
+    status: u8,
+    source: u32,
+    dest: u32,
+    length: u32,
+}
+
+impl Channel {
+    fn active(&self) -> bool {
+        self.control & DMA_ENABLE != 0 && self.length > 0
+    }
+
+    /// Transfer a single byte, and update the addresses and remaining length
+    fn transfer<Address, Bus>(&mut self, now: Bus::Instant, bus: &mut Bus) -> Result<(), ()>
+    where
+        Address: TryFrom<u32> + Copy,
+        Bus: BusAccess<Address>,
+    {
+        let source = Address::try_from(self.source).map_err(|_| ())?;
+        let dest = Address::try_from(self.dest).map_err(|_| ())?;
+        let mut byte = [0];
+        if bus.read(now, source, &mut byte).map_err(|_| ())? < 1 {
+            return Err(());
+        }
+        if bus.write(now, dest, &byte).map_err(|_| ())? < 1 {
+            return Err(());
+        }
+
+        if self.control & DMA_SOURCE_FIXED == 0 {
+            self.source = self.source.wrapping_add(1);
+        }
+        if self.control & DMA_DEST_FIXED == 0 {
+            self.dest = self.dest.wrapping_add(1);
+        }
+        self.length -= 1;
+        Ok(())
+    }
+
+    fn finish(&mut self, status: u8) {
+        self.control &= !DMA_ENABLE;
+        self.status |= status;
+    }
+
+    fn read(&self, register: usize) -> u8 {
+        match register {
+            0 => self.control,
+            1 => self.status,
+            4..=7 => self.source.to_be_bytes()[register - 4],
+            8..=11 => self.dest.to_be_bytes()[register - 8],
+            12..=15 => self.length.to_be_bytes()[register - 12],
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, register: usize, value: u8) {
+        match register {
+            0 => self.control = value,
+            1 => self.status &= !value,
+            4..=7 => set_byte(&mut self.source, register - 4, value),
+            8..=11 => set_byte(&mut self.dest, register - 8, value),
+            12..=15 => set_byte(&mut self.length, register - 12, value),
+            _ => {}
+        }
+    }
+}
+
+/// Set the byte at the given index of a big endian register
+fn set_byte(register: &mut u32, index: usize, value: u8) {
+    let mut bytes = register.to_be_bytes();
+    bytes[index] = value;
+    *register = u32::from_be_bytes(bytes);
+}
+
+/// A DMA engine with any number of channels, which copies blocks of memory over the bus while
+/// it's being stepped
+///
+/// The engine is a bus master like a CPU, so it's given the system bus when it's stepped, and
+/// its own registers are accessed through the system bus by the CPU.  Each step, the
+/// lowest-numbered active channel is given the bus.  It either transfers one byte, or the whole
+/// of its remaining block if it's in burst mode, which keeps the other channels waiting.  The
+/// next step happens one `clock_period` after each byte transferred.  When the length reaches 0,
+/// the channel is disabled and its done flag is set.  If the bus returns an error, the channel
+/// is disabled and its error flag is set instead, leaving the addresses at the failed transfer.
+///
+/// Each channel occupies `DMA_CHANNEL_REGISTERS` bytes of the address space, starting at
+/// `channel * DMA_CHANNEL_REGISTERS`, with the 32-bit registers in big endian order:
+///
+/// | Offset | Register |
+/// |--------|----------|
+/// | 0      | control (`DMA_ENABLE` and other control bits) |
+/// | 1      | status (`DMA_DONE` and `DMA_ERROR`) |
+/// | 4-7    | source address |
+/// | 8-11   | destination address |
+/// | 12-15  | length in bytes |
+///
+/// The `interrupt` line is active while any channel with `DMA_INTERRUPT` set has its done or
+/// error flag set
+pub struct DmaEngine<Instant>
+where
+    Instant: EmuInstant,
+{
+    channels: Vec<Channel>,
+    /// The time taken to transfer one byte
+    pub clock_period: Instant::Duration,
+    /// The interrupt output line
+    pub interrupt: Signal,
+}
+
+impl<Instant> DmaEngine<Instant>
+where
+    Instant: EmuInstant,
+{
+    /// Construct a new DMA engine with the given number of channels, and the time taken to
+    /// transfer one byte
+    pub fn new(channels: usize, clock_period: Instant::Duration) -> Self {
+        Self {
+            channels: vec![Channel::default(); channels],
+            clock_period,
+            interrupt: Signal::new(),
+        }
+    }
+
+    /// Returns the number of channels in the engine
+    pub fn channels(&self) -> usize {
+        self.channels.len()
+    }
+
+    /// Returns true if any channel has a transfer in progress
+    pub fn is_busy(&self) -> bool {
+        self.channels.iter().any(Channel::active)
+    }
+
+    /// Perform the next transfer of the highest priority active channel, and return the number
+    /// of bytes transferred
+    pub fn transfer<Address, Bus>(&mut self, now: Instant, bus: &mut Bus) -> u32
+    where
+        Address: TryFrom<u32> + Copy,
+        Bus: BusAccess<Address, Instant = Instant>,
+    {
+        let channel = match self.channels.iter_mut().find(|channel| channel.active()) {
+            Some(channel) => channel,
+            None => return 0,
+        };
+
+        let mut count = 0;
+        loop {
+            if channel.transfer(now, bus).is_err() {
+                channel.finish(DMA_ERROR);
+                break;
+            }
+            count += 1;
+            if channel.length == 0 {
+                channel.finish(DMA_DONE);
+                break;
+            }
+            if channel.control & DMA_BURST == 0 {
+                break;
+            }
+        }
+        self.update_interrupt();
+        count
+    }
+
+    fn update_interrupt(&self) {
+        let active = self.channels.iter().any(|channel| {
+            channel.status & (DMA_DONE | DMA_ERROR) != 0 && channel.control & DMA_INTERRUPT != 0
+        });
+        self.interrupt.set(active);
+    }
+
+    fn channel(&mut self, addr: usize) -> Result<&mut Channel, BasicBusError> {
+        self.channels
+            .get_mut(addr / DMA_CHANNEL_REGISTERS)
+            .ok_or(BasicBusError::UnmappedAddress)
+    }
+}
+
+impl<Address, Instant> BusAccess<Address> for DmaEngine<Instant>
+where
+    Address: TryInto<usize> + Copy,
+    Instant: EmuInstant,
+{
+    type Instant = Instant;
+    type Error = BasicBusError;
+
+    fn read(
+        &mut self,
+        _now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        let addr = addr
+            .try_into()
+            .map_err(|_| BasicBusError::UnmappedAddress)?;
+        for (i, byte) in data.iter_mut().enumerate() {
+            *byte = self
+                .channel(addr + i)?
+                .read((addr + i) % DMA_CHANNEL_REGISTERS);
+        }
+        Ok(data.len())
+    }
+
+    fn write(
+        &mut self,
+        _now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        let addr = addr
+            .try_into()
+            .map_err(|_| BasicBusError::UnmappedAddress)?;
+        for (i, byte) in data.iter().enumerate() {
+            self.channel(addr + i)?
+                .write((addr + i) % DMA_CHANNEL_REGISTERS, *byte);
+        }
+        self.update_interrupt();
+        Ok(data.len())
+    }
+}
+
+impl<Address, Bus, Instant> Step<Address, Bus> for DmaEngine<Instant>
+where
+    Address: TryFrom<u32> + Copy,
+    Bus: BusAccess<Address, Instant = Instant>,
+    Instant: EmuInstant,
+    Instant::Duration: Copy,
+{
+    type Error = Infallible;
+
+    fn is_running(&mut self) -> bool {
+        true
+    }
+
+    fn reset(&mut self, _now: Instant, _bus: &mut Bus) -> Result<(), Self::Error> {
+        self.channels.fill(Channel::default());
+        self.interrupt.lower();
+        Ok(())
+    }
+
+    fn step(&mut self, now: Instant, bus: &mut Bus) -> Result<Instant, Self::Error> {
+        let count = self.transfer(now, bus);
+        Ok(now + self.clock_period * count.max(1))
+    }
+}
+
+impl<Instant> BusRequest for DmaEngine<Instant>
+where
+    Instant: EmuInstant,
+{
+    fn bus_requested(&mut self) -> bool {
+        self.is_busy()
+    }
+}
+
+/// The side of a transfer that caused a `DmaChannel` to stop
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DmaError {
+    /// The source address was out of range, or reading it returned an error
+    Source,
+    /// The destination address was out of range, or writing it returned an error
+    Destination,
+}
+
+/// A single DMA channel which copies data from one bus to another, one unit at a time
+///
+/// This is a building block for emulating DMA controllers, which doesn't have any registers of
+/// its own.  The source and destination can be different bus objects, such as memory and a
+/// device's data port, and each transfer moves `width` bytes, which must be between 1 and 8.
+/// Each call to `step()` performs one transfer, and returns the time of the next transfer,
+/// which is one `period` later, so the transfer rate is set by `period`.  The channel requests
+/// the bus while a transfer is in progress, so it can be given the bus by a `BusArbiter`,
+/// stealing cycles from the CPU
+#[derive(Clone, Debug)]
+pub struct DmaChannel<Instant>
+where
+    Instant: EmuInstant,
+{
+    /// The address of the next unit to read from the source
+    pub source: u64,
+    /// The address of the next unit to write to the destination
+    pub dest: u64,
+    /// The number of units left to transfer
+    pub remaining: u64,
+    /// The number of bytes moved by each transfer
+    pub width: usize,
+    /// The time taken by each transfer
+    pub period: Instant::Duration,
+    /// Whether the source address is incremented after each transfer, instead of kept fixed
+    pub source_increment: bool,
+    /// Whether the destination address is incremented after each transfer, instead of kept fixed
+    pub dest_increment: bool,
+}
+
+impl<Instant> DmaChannel<Instant>
+where
+    Instant: EmuInstant,
+    Instant::Duration: Copy,
+{
+    /// Construct a new idle channel which moves `width` bytes each `period`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `width` is 0 or greater than 8
+    pub fn new(width: usize, period: Instant::Duration) -> Self {
+        assert!((1..=8).contains(&width), "invalid DMA width: {}", width);
+        Self {
+            source: 0,
+            dest: 0,
+            remaining: 0,
+            width,
+            period,
+            source_increment: true,
+            dest_increment: true,
+        }
+    }
+
+    /// Start a transfer of `count` units from `source` to `dest`
+    pub fn start(&mut self, source: u64, dest: u64, count: u64) {
+        self.source = source;
+        self.dest = dest;
+        self.remaining = count;
+    }
+
+    /// Stop the transfer in progress, if any
+    pub fn stop(&mut self) {
+        self.remaining = 0;
+    }
+
+    /// Returns true if a transfer is in progress
+    pub fn is_active(&self) -> bool {
+        self.remaining > 0
+    }
+
+    /// Move the next unit from `source_bus` to `dest_bus`, and return the time of the next
+    /// transfer
+    ///
+    /// If the channel is idle, nothing is transferred.  If either access fails, the transfer is
+    /// stopped, with the addresses left at the failed unit
+    pub fn step<SourceAddress, DestAddress, SourceBus, DestBus>(
+        &mut self,
+        now: Instant,
+        source_bus: &mut SourceBus,
+        dest_bus: &mut DestBus,
+    ) -> Result<Instant, DmaError>
+    where
+        SourceAddress: TryFrom<u64> + Copy,
+        DestAddress: TryFrom<u64> + Copy,
+        SourceBus: BusAccess<SourceAddress, Instant = Instant>,
+        DestBus: BusAccess<DestAddress, Instant = Instant>,
+    {
+        if !self.is_active() {
+            return Ok(now + self.period);
+        }
+
+        let result = self.transfer(now, source_bus, dest_bus);
+        if result.is_err() {
+            self.stop();
+        }
+        result.map(|_| now + self.period)
+    }
+
+    fn transfer<SourceAddress, DestAddress, SourceBus, DestBus>(
+        &mut self,
+        now: Instant,
+        source_bus: &mut SourceBus,
+        dest_bus: &mut DestBus,
+    ) -> Result<(), DmaError>
+    where
+        SourceAddress: TryFrom<u64> + Copy,
+        DestAddress: TryFrom<u64> + Copy,
+        SourceBus: BusAccess<SourceAddress, Instant = Instant>,
+        DestBus: BusAccess<DestAddress, Instant = Instant>,
+    {
+        let mut data = [0; 8];
+        let data = &mut data[..self.width];
+
+        let source = SourceAddress::try_from(self.source).map_err(|_| DmaError::Source)?;
+        source_bus
+            .read(now, source, data)
+            .map_err(|_| DmaError::Source)?;
+        let dest = DestAddress::try_from(self.dest).map_err(|_| DmaError::Destination)?;
+        dest_bus
+            .write(now, dest, data)
+            .map_err(|_| DmaError::Destination)?;
+
+        if self.source_increment {
+            self.source = self.source.wrapping_add(self.width as u64);
+        }
+        if self.dest_increment {
+            self.dest = self.dest.wrapping_add(self.width as u64);
+        }
+        self.remaining -= 1;
+        Ok(())
+    }
+}
+
+impl<Instant> BusRequest for DmaChannel<Instant>
+where
+    Instant: EmuInstant,
+{
+    fn bus_requested(&mut self) -> bool {
+        self.remaining > 0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use emulator_hal::BusAccessExt;
+    use std::time::Duration;
+
+    fn system() -> (DmaEngine<Duration>, Vec<u8>) {
+        (
+            DmaEngine::new(2, Duration::from_nanos(10)),
+            (0..0x100).map(|i| i as u8).collect(),
+        )
+    }
+
+    fn start(
+        dma: &mut DmaEngine<Duration>,
+        channel: u32,
+        source: u32,
+        dest: u32,
+        length: u32,
+        control: u8,
+    ) {
+        let base = channel * DMA_CHANNEL_REGISTERS as u32;
+        dma.write_beu32(Duration::ZERO, base + 4, source).unwrap();
+        dma.write_beu32(Duration::ZERO, base + 8, dest).unwrap();
+        dma.write_beu32(Duration::ZERO, base + 12, length).unwrap();
+        dma.write_u8(Duration::ZERO, base, DMA_ENABLE | control)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_cycle_stealing_and_burst() {
+        let (mut dma, mut memory) = system();
+        let irq = dma.interrupt.clone();
+
+        start(&mut dma, 1, 0x00, 0x80, 4, DMA_INTERRUPT);
+        start(&mut dma, 0, 0x10, 0x40, 8, DMA_BURST);
+
+        // the burst on channel 0 holds the bus until it's finished
+        let now = Step::<u32, _>::step(&mut dma, Duration::ZERO, &mut memory).unwrap();
+        assert_eq!(now, Duration::from_nanos(80));
+        assert_eq!(
+            memory[0x40..0x48],
+            [0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17]
+        );
+        assert_eq!(dma.read_u8(now, 1_u32).unwrap(), DMA_DONE);
+        assert!(!irq.get());
+
+        // channel 1 then copies one byte per step
+        let now = Step::<u32, _>::step(&mut dma, now, &mut memory).unwrap();
+        assert_eq!(now, Duration::from_nanos(90));
+        assert_eq!(dma.read_beu32(now, 28_u32).unwrap(), 3);
+        while dma.is_busy() {
+            Step::<u32, _>::step(&mut dma, now, &mut memory).unwrap();
+        }
+        assert_eq!(memory[0x80..0x84], [0, 1, 2, 3]);
+        assert!(irq.get());
+
+        dma.write_u8(now, 17_u32, DMA_DONE).unwrap();
+        assert!(!irq.get());
+    }
+
+    #[test]
+    fn test_fixed_address_and_bus_error() {
+        let (mut dma, mut memory) = system();
+        let irq = dma.interrupt.clone();
+
+        start(
+            &mut dma,
+            0,
+            0x20,
+            0xFE,
+            4,
+            DMA_BURST | DMA_SOURCE_FIXED | DMA_INTERRUPT,
+        );
+        assert_eq!(dma.transfer::<u32, _>(Duration::ZERO, &mut memory), 2);
+        assert_eq!(memory[0xFE..], [0x20, 0x20]);
+        assert_eq!(dma.read_u8(Duration::ZERO, 1_u32).unwrap(), DMA_ERROR);
+        assert_eq!(dma.read_beu32(Duration::ZERO, 8_u32).unwrap(), 0x100);
+        assert_eq!(dma.read_u8(Duration::ZERO, 0_u32).unwrap() & DMA_ENABLE, 0);
+        assert!(irq.get());
+        assert!(dma.read_u8(Duration::ZERO, 32_u32).is_err());
+    }
+
+    struct Port(Vec<u16>);
+
+    impl BusAccess<u8> for Port {
+        type Instant = Duration;
+        type Error = BasicBusError;
+
+        fn read(
+            &mut self,
+            _now: Duration,
+            _addr: u8,
+            _data: &mut [u8],
+        ) -> Result<usize, Self::Error> {
+            Err(BasicBusError::UnmappedAddress)
+        }
+
+        fn write(&mut self, _now: Duration, addr: u8, data: &[u8]) -> Result<usize, Self::Error> {
+            if addr != 0x10 {
+                return Err(BasicBusError::UnmappedAddress);
+            }
+            self.0.push(u16::from_be_bytes([data[0], data[1]]));
+            Ok(data.len())
+        }
+    }
+
+    #[test]
+    fn test_channel_between_buses() {
+        let mut memory: Vec<u8> = (0..0x10).collect();
+        let mut port = Port(vec![]);
+        let mut channel = DmaChannel::new(2, Duration::from_nanos(100));
+        channel.dest_increment = false;
+
+        channel.start(0x04, 0x10, 3);
+        let mut now = Duration::ZERO;
+        while channel.bus_requested() {
+            now = channel
+                .step::<u32, u8, _, _>(now, &mut memory, &mut port)
+                .unwrap();
+        }
+        assert_eq!(now, Duration::from_nanos(300));
+        assert_eq!(port.0, [0x0405, 0x0607, 0x0809]);
+        assert_eq!(channel.source, 0x0A);
+
+        // the destination address doesn't fit in the port's address type
+        channel.start(0x00, 0x100, 2);
+        assert_eq!(
+            channel.step::<u32, u8, _, _>(now, &mut memory, &mut port),
+            Err(DmaError::Destination)
+        );
+        assert!(!channel.is_active());
+        assert_eq!(channel.source, 0x00);
+    }
+}
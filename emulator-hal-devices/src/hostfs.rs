@@ -0,0 +1,496 @@
+//! A bridge for guest programs to access files on the host
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::vec::Vec;
+
+use core::convert::Infallible;
+
+use emulator_hal::{BasicBusError, BusAccess, Instant as EmuInstant, Step};
+
+/// The offset of the 32-bit address of the command block, in big endian order
+pub const HOSTFS_BLOCK: usize = 0;
+/// The offset of the doorbell and status register
+pub const HOSTFS_STATUS: usize = 4;
+
+/// Status register bit which is set from when the doorbell is written until the command is done
+pub const HOSTFS_BUSY: u8 = 0x01;
+
+/// Command to open the file named by the buffer, and return its handle
+pub const HOSTFS_OPEN: u8 = 1;
+/// Command to read up to `length` bytes from a file into the buffer
+pub const HOSTFS_READ: u8 = 2;
+/// Command to write `length` bytes from the buffer to a file
+pub const HOSTFS_WRITE: u8 = 3;
+/// Command to close a file
+pub const HOSTFS_CLOSE: u8 = 4;
+
+/// Open mode which opens an existing file for reading
+pub const HOSTFS_MODE_READ: u8 = 0;
+/// Open mode which creates or truncates a file for writing
+pub const HOSTFS_MODE_WRITE: u8 = 1;
+/// Open mode which creates or appends to a file for writing
+pub const HOSTFS_MODE_APPEND: u8 = 2;
+
+/// Result stored in the command block when the command succeeded
+pub const HOSTFS_OK: u8 = 0;
+/// Result stored in the command block when the file doesn't exist
+pub const HOSTFS_NOT_FOUND: u8 = 1;
+/// Result stored in the command block when the path is outside of the shared directory, or
+/// the host denied access
+pub const HOSTFS_DENIED: u8 = 2;
+/// Result stored in the command block when the handle isn't open, or there are no free handles
+pub const HOSTFS_BAD_HANDLE: u8 = 3;
+/// Result stored in the command block when the command or mode is not recognized
+pub const HOSTFS_BAD_COMMAND: u8 = 4;
+/// Result stored in the command block when any other error occurred on the host
+pub const HOSTFS_IO_ERROR: u8 = 5;
+/// Result stored in the device's status register when the command block couldn't be accessed
+pub const HOSTFS_BUS_ERROR: u8 = 0x80;
+
+const MAX_HANDLES: usize = 16;
+const MAX_PATH: u32 = 1024;
+/// The number of bytes transferred between the file and guest memory at a time, so that the
+/// length given by the guest doesn't decide how much is allocated on the host
+const TRANSFER_CHUNK: usize = 512;
+
+/// A command block read from guest memory
+struct Command {
+    operation: u8,
+    mode: u8,
+    handle: u8,
+    buffer: u32,
+    length: u32,
+}
+
+/// A device which lets guest programs open, read, write, and close files in a directory on the
+/// host, without emulating a disk controller
+///
+/// The guest writes a 12 byte command block to its memory, writes the block's address to the
+/// `HOSTFS_BLOCK` register, and then writes any value to the `HOSTFS_STATUS` register to ring
+/// the doorbell.  The device is a bus master, and executes the command the next time it's
+/// stepped, reading and writing guest memory through the bus it's given.  When it's done, it
+/// stores the results in the command block, and clears `HOSTFS_BUSY`.  The command block has
+/// its 32-bit fields in big endian order:
+///
+/// | Offset | Field |
+/// |--------|-------|
+/// | 0      | command (`HOSTFS_OPEN`, `HOSTFS_READ`, `HOSTFS_WRITE`, or `HOSTFS_CLOSE`) |
+/// | 1      | mode for `HOSTFS_OPEN` (`HOSTFS_MODE_*`) |
+/// | 2      | file handle, which is set by `HOSTFS_OPEN` and given to the other commands |
+/// | 3      | result, which is set to `HOSTFS_OK` or an error code |
+/// | 4-7    | buffer address, which holds the file name for `HOSTFS_OPEN` |
+/// | 8-11   | buffer length, which is set to the number of bytes transferred |
+///
+/// File names are UTF-8 paths relative to the shared directory, and paths that would leave it
+/// are denied.  If the command block itself can't be accessed, the status register is set to
+/// `HOSTFS_BUS_ERROR` instead
+pub struct HostFsBridge<Instant>
+where
+    Instant: EmuInstant,
+{
+    root: PathBuf,
+    files: Vec<Option<File>>,
+    block: u32,
+    pending: bool,
+    status: u8,
+    /// The period between checks for a new command
+    pub poll_period: Instant::Duration,
+}
+
+impl<Instant> HostFsBridge<Instant>
+where
+    Instant: EmuInstant,
+{
+    /// Construct a new bridge which shares the files in the given directory with the guest
+    pub fn new<P: AsRef<Path>>(root: P, poll_period: Instant::Duration) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+            files: (0..MAX_HANDLES).map(|_| None).collect(),
+            block: 0,
+            pending: false,
+            status: 0,
+            poll_period,
+        }
+    }
+
+    /// Returns the directory shared with the guest
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Close all of the files opened by the guest
+    pub fn close_all(&mut self) {
+        self.files.iter_mut().for_each(|file| *file = None);
+    }
+
+    /// Execute the pending command, if any, using the given bus to access guest memory
+    pub fn execute<Address, Bus>(&mut self, now: Instant, bus: &mut Bus)
+    where
+        Address: TryFrom<u32> + Copy,
+        Bus: BusAccess<Address, Instant = Instant>,
+    {
+        if !self.pending {
+            return;
+        }
+        self.pending = false;
+        self.status = match self.execute_block(now, bus) {
+            Ok(()) => 0,
+            Err(_) => HOSTFS_BUS_ERROR,
+        };
+    }
+
+    fn execute_block<Address, Bus>(&mut self, now: Instant, bus: &mut Bus) -> Result<(), ()>
+    where
+        Address: TryFrom<u32> + Copy,
+        Bus: BusAccess<Address, Instant = Instant>,
+    {
+        let mut block = [0; 12];
+        bus.read(now, address(self.block)?, &mut block)
+            .map_err(|_| ())?;
+        let command = Command {
+            operation: block[0],
+            mode: block[1],
+            handle: block[2],
+            buffer: u32::from_be_bytes([block[4], block[5], block[6], block[7]]),
+            length: u32::from_be_bytes([block[8], block[9], block[10], block[11]]),
+        };
+
+        let (handle, result, length) = match self.run(now, bus, &command)? {
+            Ok((handle, length)) => (handle, HOSTFS_OK, length),
+            Err(result) => (command.handle, result, 0),
+        };
+        block[2] = handle;
+        block[3] = result;
+        block[8..12].copy_from_slice(&length.to_be_bytes());
+        bus.write(now, address(self.block)?, &block)
+            .map_err(|_| ())?;
+        Ok(())
+    }
+
+    /// Run a command, and return the handle and length, or the result code of the failure
+    #[allow(clippy::type_complexity)]
+    fn run<Address, Bus>(
+        &mut self,
+        now: Instant,
+        bus: &mut Bus,
+        command: &Command,
+    ) -> Result<Result<(u8, u32), u8>, ()>
+    where
+        Address: TryFrom<u32> + Copy,
+        Bus: BusAccess<Address, Instant = Instant>,
+    {
+        let handle = command.handle as usize;
+        match command.operation {
+            HOSTFS_OPEN => {
+                let mut name = vec![0; command.length.min(MAX_PATH) as usize];
+                bus.read(now, address(command.buffer)?, &mut name)
+                    .map_err(|_| ())?;
+                Ok(self.open(&name, command.mode))
+            }
+            HOSTFS_READ => {
+                let file = match self.files.get_mut(handle).and_then(Option::as_mut) {
+                    Some(file) => file,
+                    None => return Ok(Err(HOSTFS_BAD_HANDLE)),
+                };
+                let mut data = [0; TRANSFER_CHUNK];
+                let mut length = 0;
+                while length < command.length as usize {
+                    let chunk = (command.length as usize - length).min(TRANSFER_CHUNK);
+                    let count = match read_fully(file, &mut data[..chunk]) {
+                        Ok(count) => count,
+                        Err(err) => return Ok(Err(error_result(&err))),
+                    };
+                    bus.write(now, buffer_address(command, length)?, &data[..count])
+                        .map_err(|_| ())?;
+                    length += count;
+                    if count < chunk {
+                        break;
+                    }
+                }
+                Ok(Ok((command.handle, length as u32)))
+            }
+            HOSTFS_WRITE => {
+                let file = match self.files.get_mut(handle).and_then(Option::as_mut) {
+                    Some(file) => file,
+                    None => return Ok(Err(HOSTFS_BAD_HANDLE)),
+                };
+                let mut data = [0; TRANSFER_CHUNK];
+                let mut length = 0;
+                while length < command.length as usize {
+                    let chunk = (command.length as usize - length).min(TRANSFER_CHUNK);
+                    bus.read(now, buffer_address(command, length)?, &mut data[..chunk])
+                        .map_err(|_| ())?;
+                    if let Err(err) = file.write_all(&data[..chunk]) {
+                        return Ok(Err(error_result(&err)));
+                    }
+                    length += chunk;
+                }
+                Ok(Ok((command.handle, command.length)))
+            }
+            HOSTFS_CLOSE => Ok(match self.files.get_mut(handle).and_then(Option::take) {
+                Some(_) => Ok((command.handle, 0)),
+                None => Err(HOSTFS_BAD_HANDLE),
+            }),
+            _ => Ok(Err(HOSTFS_BAD_COMMAND)),
+        }
+    }
+
+    fn open(&mut self, name: &[u8], mode: u8) -> Result<(u8, u32), u8> {
+        let name = core::str::from_utf8(name).map_err(|_| HOSTFS_NOT_FOUND)?;
+        let relative = Path::new(name);
+        if !relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+        {
+            return Err(HOSTFS_DENIED);
+        }
+
+        let mut options = OpenOptions::new();
+        match mode {
+            HOSTFS_MODE_READ => options.read(true),
+            HOSTFS_MODE_WRITE => options.write(true).create(true).truncate(true),
+            HOSTFS_MODE_APPEND => options.append(true).create(true),
+            _ => return Err(HOSTFS_BAD_COMMAND),
+        };
+
+        let handle = self
+            .files
+            .iter()
+            .position(Option::is_none)
+            .ok_or(HOSTFS_BAD_HANDLE)?;
+        let file = options
+            .open(self.root.join(relative))
+            .map_err(|err| error_result(&err))?;
+        self.files[handle] = Some(file);
+        Ok((handle as u8, 0))
+    }
+}
+
+fn address<Address: TryFrom<u32>>(addr: u32) -> Result<Address, ()> {
+    Address::try_from(addr).map_err(|_| ())
+}
+
+/// Returns the address of the byte at `offset` in the command's buffer
+fn buffer_address<Address: TryFrom<u32>>(command: &Command, offset: usize) -> Result<Address, ()> {
+    let offset = u32::try_from(offset).map_err(|_| ())?;
+    address(command.buffer.checked_add(offset).ok_or(())?)
+}
+
+fn error_result(err: &io::Error) -> u8 {
+    match err.kind() {
+        io::ErrorKind::NotFound => HOSTFS_NOT_FOUND,
+        io::ErrorKind::PermissionDenied => HOSTFS_DENIED,
+        _ => HOSTFS_IO_ERROR,
+    }
+}
+
+/// Read until the buffer is full or the end of the file is reached
+fn read_fully(file: &mut File, data: &mut [u8]) -> Result<usize, io::Error> {
+    let mut length = 0;
+    while length < data.len() {
+        match file.read(&mut data[length..]) {
+            Ok(0) => break,
+            Ok(count) => length += count,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(length)
+}
+
+impl<Address, Instant> BusAccess<Address> for HostFsBridge<Instant>
+where
+    Address: TryInto<usize> + Copy,
+    Instant: EmuInstant,
+{
+    type Instant = Instant;
+    type Error = BasicBusError;
+
+    fn read(
+        &mut self,
+        _now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        let addr = addr
+            .try_into()
+            .map_err(|_| BasicBusError::UnmappedAddress)?;
+        for (i, byte) in data.iter_mut().enumerate() {
+            *byte = match addr + i {
+                register @ HOSTFS_BLOCK..=3 => self.block.to_be_bytes()[register],
+                HOSTFS_STATUS if self.pending => HOSTFS_BUSY,
+                HOSTFS_STATUS => self.status,
+                _ => return Err(BasicBusError::UnmappedAddress),
+            };
+        }
+        Ok(data.len())
+    }
+
+    fn write(
+        &mut self,
+        _now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        let addr = addr
+            .try_into()
+            .map_err(|_| BasicBusError::UnmappedAddress)?;
+        for (i, byte) in data.iter().enumerate() {
+            match addr + i {
+                register @ HOSTFS_BLOCK..=3 => {
+                    let mut block = self.block.to_be_bytes();
+                    block[register] = *byte;
+                    self.block = u32::from_be_bytes(block);
+                }
+                HOSTFS_STATUS => self.pending = true,
+                _ => return Err(BasicBusError::UnmappedAddress),
+            }
+        }
+        Ok(data.len())
+    }
+}
+
+impl<Address, Bus, Instant> Step<Address, Bus> for HostFsBridge<Instant>
+where
+    Address: TryFrom<u32> + Copy,
+    Bus: BusAccess<Address, Instant = Instant>,
+    Instant: EmuInstant,
+    Instant::Duration: Copy,
+{
+    type Error = Infallible;
+
+    fn is_running(&mut self) -> bool {
+        true
+    }
+
+    fn reset(&mut self, _now: Instant, _bus: &mut Bus) -> Result<(), Self::Error> {
+        self.close_all();
+        self.block = 0;
+        self.pending = false;
+        self.status = 0;
+        Ok(())
+    }
+
+    fn step(&mut self, now: Instant, bus: &mut Bus) -> Result<Instant, Self::Error> {
+        self.execute(now, bus);
+        Ok(now + self.poll_period)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use emulator_hal::BusAccessExt;
+    use std::time::Duration;
+
+    /// Run a command with its block at 0x00, and return the block after it's done
+    fn run(
+        bridge: &mut HostFsBridge<Duration>,
+        memory: &mut Vec<u8>,
+        command: [u8; 3],
+        buffer: u32,
+        length: u32,
+    ) -> [u8; 12] {
+        memory[0..3].copy_from_slice(&command);
+        memory.write_beu32(Duration::ZERO, 4, buffer).unwrap();
+        memory.write_beu32(Duration::ZERO, 8, length).unwrap();
+        bridge.write_u8(Duration::ZERO, HOSTFS_STATUS, 0).unwrap();
+        assert_eq!(
+            bridge.read_u8(Duration::ZERO, HOSTFS_STATUS).unwrap(),
+            HOSTFS_BUSY
+        );
+        Step::<u32, _>::step(bridge, Duration::ZERO, memory).unwrap();
+        assert_eq!(bridge.read_u8(Duration::ZERO, HOSTFS_STATUS).unwrap(), 0);
+        memory[0..12].try_into().unwrap()
+    }
+
+    #[test]
+    fn test_write_and_read_back() {
+        let root = std::env::temp_dir().join(format!("emulator-hal-hostfs-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let mut bridge = HostFsBridge::new(&root, Duration::from_micros(1));
+        let mut memory = vec![0_u8; 0x100];
+        memory[0x40..0x48].copy_from_slice(b"test.txt");
+        memory[0x80..0x85].copy_from_slice(b"hello");
+
+        let block = run(
+            &mut bridge,
+            &mut memory,
+            [HOSTFS_OPEN, HOSTFS_MODE_WRITE, 0],
+            0x40,
+            8,
+        );
+        assert_eq!(block[3], HOSTFS_OK);
+        let handle = block[2];
+        let block = run(&mut bridge, &mut memory, [HOSTFS_WRITE, 0, handle], 0x80, 5);
+        assert_eq!(block[8..12], 5_u32.to_be_bytes());
+        run(&mut bridge, &mut memory, [HOSTFS_CLOSE, 0, handle], 0, 0);
+
+        let block = run(
+            &mut bridge,
+            &mut memory,
+            [HOSTFS_OPEN, HOSTFS_MODE_READ, 0],
+            0x40,
+            8,
+        );
+        let handle = block[2];
+        let block = run(&mut bridge, &mut memory, [HOSTFS_READ, 0, handle], 0xA0, 16);
+        assert_eq!(block[3], HOSTFS_OK);
+        assert_eq!(block[8..12], 5_u32.to_be_bytes());
+        assert_eq!(&memory[0xA0..0xA5], b"hello");
+
+        run(&mut bridge, &mut memory, [HOSTFS_CLOSE, 0, handle], 0, 0);
+        let block = run(&mut bridge, &mut memory, [HOSTFS_READ, 0, handle], 0xA0, 16);
+        assert_eq!(block[3], HOSTFS_BAD_HANDLE);
+
+        // the length given by the guest is only an upper bound, and isn't allocated up front
+        let block = run(
+            &mut bridge,
+            &mut memory,
+            [HOSTFS_OPEN, HOSTFS_MODE_READ, 0],
+            0x40,
+            8,
+        );
+        let handle = block[2];
+        let block = run(
+            &mut bridge,
+            &mut memory,
+            [HOSTFS_READ, 0, handle],
+            0xC0,
+            u32::MAX,
+        );
+        assert_eq!(block[8..12], 5_u32.to_be_bytes());
+        assert_eq!(&memory[0xC0..0xC5], b"hello");
+        run(&mut bridge, &mut memory, [HOSTFS_CLOSE, 0, handle], 0, 0);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_paths_outside_root_denied() {
+        let mut bridge = HostFsBridge::new(std::env::temp_dir(), Duration::from_micros(1));
+        let mut memory = vec![0_u8; 0x100];
+        memory[0x40..0x4D].copy_from_slice(b"../etc/passwd");
+
+        let block = run(
+            &mut bridge,
+            &mut memory,
+            [HOSTFS_OPEN, HOSTFS_MODE_READ, 0],
+            0x40,
+            13,
+        );
+        assert_eq!(block[3], HOSTFS_DENIED);
+
+        bridge
+            .write_beu32(Duration::ZERO, HOSTFS_BLOCK, 0x1000)
+            .unwrap();
+        bridge.write_u8(Duration::ZERO, HOSTFS_STATUS, 0).unwrap();
+        Step::<u32, _>::step(&mut bridge, Duration::ZERO, &mut memory).unwrap();
+        assert_eq!(
+            bridge.read_u8(Duration::ZERO, HOSTFS_STATUS).unwrap(),
+            HOSTFS_BUS_ERROR
+        );
+    }
+}
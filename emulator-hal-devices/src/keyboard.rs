@@ -0,0 +1,245 @@
+//! A scanned keyboard matrix
+
+use alloc::vec::Vec;
+
+use emulator_hal::{BasicBusError, BusAccess, Instant as EmuInstant, Step};
+use emulator_hal_input::{InputEvent, InputSource, KeyCode};
+
+/// The offset of the row select register
+pub const KEYBOARD_ROWS: usize = 0;
+/// The offset of the column register, which is read only
+pub const KEYBOARD_COLUMNS: usize = 1;
+
+/// A keyboard matrix of up to 8 rows and 8 columns, scanned by writing a row select value and
+/// reading back the columns
+///
+/// Each key of the emulated keyboard sits at the crossing of a row and a column, and host keys
+/// are mapped onto those positions with `map_key()`.  A host key can be mapped to more than one
+/// position, such as a shift key and a number key for a host key with a shifted symbol.  Reading
+/// the column register returns the keys pressed in all of the selected rows combined.
+///
+/// Most machines use active low lines, where a row is selected by writing a 0 to its bit, and a
+/// pressed key reads as a 0 in its column's bit, so this is the default.  Setting `active_low` to
+/// false makes both the selection and the result active high.  Machines that select the rows
+/// some other way, such as with address lines, can call `scan()` directly instead of mapping
+/// the registers.
+///
+/// The matrix polls its `InputSource` for events each time it's stepped
+pub struct KeyboardMatrix<Source>
+where
+    Source: InputSource,
+{
+    source: Source,
+    rows: usize,
+    columns: usize,
+    keymap: Vec<(KeyCode, u8, u8)>,
+    pressed: Vec<KeyCode>,
+    row_select: u8,
+    /// True if the row select and column lines are active low
+    pub active_low: bool,
+    /// The period between polls of the input source
+    pub poll_period: <Source::Instant as EmuInstant>::Duration,
+}
+
+impl<Source> KeyboardMatrix<Source>
+where
+    Source: InputSource,
+{
+    /// Construct a new keyboard matrix with the given size, which has no keys mapped
+    pub fn new(
+        source: Source,
+        rows: usize,
+        columns: usize,
+        poll_period: <Source::Instant as EmuInstant>::Duration,
+    ) -> Self {
+        assert!(
+            rows <= 8 && columns <= 8,
+            "a keyboard matrix can have at most 8 rows and 8 columns"
+        );
+        Self {
+            source,
+            rows,
+            columns,
+            keymap: Vec::new(),
+            pressed: Vec::new(),
+            row_select: 0xFF,
+            active_low: true,
+            poll_period,
+        }
+    }
+
+    /// Map the given host key to the key at the given row and column
+    pub fn map_key(&mut self, key: KeyCode, row: usize, column: usize) {
+        assert!(
+            row < self.rows && column < self.columns,
+            "key position is outside of the matrix"
+        );
+        self.keymap.push((key, row as u8, column as u8));
+    }
+
+    /// Returns true if the key at the given row and column is held down
+    pub fn is_pressed(&self, row: usize, column: usize) -> bool {
+        self.keymap.iter().any(|(key, key_row, key_column)| {
+            *key_row as usize == row && *key_column as usize == column && self.pressed.contains(key)
+        })
+    }
+
+    /// Returns the column lines when the given row select value is written
+    pub fn scan(&self, select: u8) -> u8 {
+        let (select, mask) = if self.active_low {
+            (!select, 0xFF)
+        } else {
+            (select, 0)
+        };
+
+        let mut columns = 0;
+        for (key, row, column) in self.keymap.iter() {
+            if select & (1 << row) != 0 && self.pressed.contains(key) {
+                columns |= 1 << column;
+            }
+        }
+        columns ^ mask
+    }
+
+    /// Apply all of the events from the input source that have occurred by time `now`
+    pub fn poll(&mut self, now: Source::Instant) -> Result<(), Source::Error> {
+        while let Some(event) = self.source.poll_event(now)? {
+            match event {
+                InputEvent::KeyDown(key) if !self.pressed.contains(&key) => self.pressed.push(key),
+                InputEvent::KeyUp(key) => self.pressed.retain(|pressed| *pressed != key),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<Address, Source> BusAccess<Address> for KeyboardMatrix<Source>
+where
+    Address: TryInto<usize> + Copy,
+    Source: InputSource,
+{
+    type Instant = Source::Instant;
+    type Error = BasicBusError;
+
+    fn read(
+        &mut self,
+        _now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        let addr = addr
+            .try_into()
+            .map_err(|_| BasicBusError::UnmappedAddress)?;
+        for (i, byte) in data.iter_mut().enumerate() {
+            *byte = match addr + i {
+                KEYBOARD_ROWS => self.row_select,
+                KEYBOARD_COLUMNS => self.scan(self.row_select),
+                _ => return Err(BasicBusError::UnmappedAddress),
+            };
+        }
+        Ok(data.len())
+    }
+
+    fn write(
+        &mut self,
+        _now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        let addr = addr
+            .try_into()
+            .map_err(|_| BasicBusError::UnmappedAddress)?;
+        for (i, byte) in data.iter().enumerate() {
+            match addr + i {
+                KEYBOARD_ROWS => self.row_select = *byte,
+                KEYBOARD_COLUMNS => {}
+                _ => return Err(BasicBusError::UnmappedAddress),
+            }
+        }
+        Ok(data.len())
+    }
+}
+
+impl<Address, Bus, Source> Step<Address, Bus> for KeyboardMatrix<Source>
+where
+    Address: Copy,
+    Bus: BusAccess<Address, Instant = Source::Instant>,
+    Source: InputSource,
+    <Source::Instant as EmuInstant>::Duration: Copy,
+{
+    type Error = Source::Error;
+
+    fn is_running(&mut self) -> bool {
+        true
+    }
+
+    fn reset(&mut self, _now: Source::Instant, _bus: &mut Bus) -> Result<(), Self::Error> {
+        self.row_select = if self.active_low { 0xFF } else { 0 };
+        Ok(())
+    }
+
+    fn step(
+        &mut self,
+        now: Source::Instant,
+        _bus: &mut Bus,
+    ) -> Result<Source::Instant, Self::Error> {
+        self.poll(now)?;
+        Ok(now + self.poll_period)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use emulator_hal::BusAccessExt;
+    use emulator_hal::NoBus;
+    use emulator_hal_input::InputQueue;
+    use std::time::Duration;
+
+    const KEY_A: KeyCode = KeyCode(0x04);
+    const KEY_QUOTE: KeyCode = KeyCode(0x34);
+
+    #[test]
+    fn test_scan_active_low() {
+        let mut queue = InputQueue::new();
+        queue.push(Duration::ZERO, InputEvent::KeyDown(KEY_A));
+        queue.push(Duration::ZERO, InputEvent::KeyDown(KEY_QUOTE));
+        queue.push(Duration::from_millis(5), InputEvent::KeyUp(KEY_QUOTE));
+
+        let mut keyboard = KeyboardMatrix::new(queue, 8, 8, Duration::from_millis(1));
+        keyboard.map_key(KEY_A, 1, 2);
+        // a shifted symbol on the host presses shift and 2 on the emulated keyboard
+        keyboard.map_key(KEY_QUOTE, 0, 7);
+        keyboard.map_key(KEY_QUOTE, 3, 1);
+
+        let mut bus = NoBus::new();
+        let next = Step::<u8, _>::step(&mut keyboard, Duration::ZERO, &mut bus).unwrap();
+        assert_eq!(next, Duration::from_millis(1));
+        assert!(keyboard.is_pressed(1, 2));
+
+        keyboard.write_u8(next, KEYBOARD_ROWS, !0x02).unwrap();
+        assert_eq!(keyboard.read_u8(next, KEYBOARD_COLUMNS).unwrap(), !0x04);
+        keyboard.write_u8(next, KEYBOARD_ROWS, !0x09).unwrap();
+        assert_eq!(keyboard.read_u8(next, KEYBOARD_COLUMNS).unwrap(), !0x82);
+        assert_eq!(keyboard.scan(0xFF), 0xFF);
+
+        keyboard.poll(Duration::from_millis(5)).unwrap();
+        assert_eq!(keyboard.scan(0x00), !0x04);
+    }
+
+    #[test]
+    fn test_scan_active_high() {
+        let mut queue = InputQueue::new();
+        queue.push(Duration::ZERO, InputEvent::KeyDown(KEY_A));
+
+        let mut keyboard = KeyboardMatrix::new(queue, 4, 5, Duration::from_millis(1));
+        keyboard.active_low = false;
+        keyboard.map_key(KEY_A, 3, 4);
+        keyboard.poll(Duration::ZERO).unwrap();
+
+        assert_eq!(keyboard.scan(0x08), 0x10);
+        assert_eq!(keyboard.scan(0x07), 0x00);
+        assert!(keyboard.read_u8(Duration::ZERO, 2_u8).is_err());
+    }
+}
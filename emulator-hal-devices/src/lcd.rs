@@ -0,0 +1,278 @@
+//! A character LCD in the style of the HD44780
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+
+use emulator_hal::{BasicBusError, BusAccess, Instant as EmuInstant};
+
+/// The offset of the command register, which reads as the current address
+pub const LCD_COMMAND: usize = 0;
+/// The offset of the data register
+pub const LCD_DATA: usize = 1;
+
+const DDRAM_SIZE: usize = 0x80;
+const LINE_LENGTH: u8 = 0x28;
+
+type OutputFn = Box<dyn FnMut(&[String])>;
+
+/// A character LCD with up to 4 rows and 40 columns, in the style of the HD44780
+///
+/// The display has a command register and a data register.  Writing a character to the data
+/// register stores it at the current address of the display memory, and moves the address to
+/// the next character.  The following commands are supported, and other commands are ignored:
+///
+/// | Command     | Action |
+/// |-------------|--------|
+/// | 0x01        | clear the display, and move to address 0 |
+/// | 0x02        | move to address 0 |
+/// | 0x04 - 0x07 | set the direction of the address after each character, with bit 1 set to increment |
+/// | 0x08 - 0x0F | set the display control, with bit 2 set to turn the display on |
+/// | 0x80 - 0xFF | set the address to the lower 7 bits |
+///
+/// Rows 0 and 1 start at addresses 0x00 and 0x40, and rows 2 and 3 continue on from them,
+/// starting at `columns` and `0x40 + columns`.  The device is never busy, so the busy flag in
+/// bit 7 of the command register always reads as 0.  Custom characters are not supported.
+///
+/// The text shown can be read with `lines()`, or sent to a callback given to `with_output()`
+/// each time it changes
+pub struct CharacterLcd<Instant> {
+    rows: usize,
+    columns: usize,
+    memory: [u8; DDRAM_SIZE],
+    address: u8,
+    increment: bool,
+    display_on: bool,
+    shown: Vec<String>,
+    output: Option<OutputFn>,
+    instant: PhantomData<Instant>,
+}
+
+impl<Instant> CharacterLcd<Instant> {
+    /// Construct a new, cleared display with the given number of rows and columns
+    pub fn new(rows: usize, columns: usize) -> Self {
+        assert!(
+            rows <= 4 && columns <= 40 && rows * columns <= 80,
+            "a character LCD can have at most 80 characters, in 4 rows of up to 40 columns"
+        );
+        Self {
+            rows,
+            columns,
+            memory: [b' '; DDRAM_SIZE],
+            address: 0,
+            increment: true,
+            display_on: false,
+            shown: Vec::new(),
+            output: None,
+            instant: PhantomData,
+        }
+    }
+
+    /// Call the given function with the lines of text whenever the text shown changes
+    pub fn with_output<F>(mut self, output: F) -> Self
+    where
+        F: FnMut(&[String]) + 'static,
+    {
+        self.output = Some(Box::new(output));
+        self
+    }
+
+    /// Returns the text of each row, which is blank while the display is off
+    ///
+    /// Characters outside of printable ASCII are shown as spaces
+    pub fn lines(&self) -> Vec<String> {
+        (0..self.rows)
+            .map(|row| {
+                let start = self.row_address(row) as usize;
+                self.memory[start..start + self.columns]
+                    .iter()
+                    .map(|byte| match byte {
+                        0x20..=0x7E if self.display_on => *byte as char,
+                        _ => ' ',
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn row_address(&self, row: usize) -> u8 {
+        let offset = if row >= 2 { self.columns as u8 } else { 0 };
+        (row as u8 % 2) * 0x40 + offset
+    }
+
+    fn command(&mut self, value: u8) {
+        match value {
+            0x80..=0xFF => self.address = value & 0x7F,
+            0x08..=0x0F => self.display_on = value & 0x04 != 0,
+            0x04..=0x07 => self.increment = value & 0x02 != 0,
+            0x02..=0x03 => self.address = 0,
+            0x01 => {
+                self.memory.fill(b' ');
+                self.address = 0;
+                self.increment = true;
+            }
+            _ => {}
+        }
+    }
+
+    /// Move the address to the next character, wrapping between the two lines of memory
+    fn advance(&mut self) {
+        let line = self.address & 0x40;
+        let column = self.address & 0x3F;
+        self.address = match (self.increment, column) {
+            (true, column) if column + 1 >= LINE_LENGTH => line ^ 0x40,
+            (true, column) => line | (column + 1),
+            (false, 0) => (line ^ 0x40) | (LINE_LENGTH - 1),
+            (false, column) => line | (column - 1),
+        };
+    }
+
+    fn update_output(&mut self) {
+        if self.output.is_none() {
+            return;
+        }
+        let lines = self.lines();
+        if lines != self.shown {
+            if let Some(output) = self.output.as_mut() {
+                output(&lines);
+            }
+            self.shown = lines;
+        }
+    }
+}
+
+impl<Address, Instant> BusAccess<Address> for CharacterLcd<Instant>
+where
+    Address: TryInto<usize> + Copy,
+    Instant: EmuInstant,
+{
+    type Instant = Instant;
+    type Error = BasicBusError;
+
+    fn read(
+        &mut self,
+        _now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        let addr = addr
+            .try_into()
+            .map_err(|_| BasicBusError::UnmappedAddress)?;
+        for (i, byte) in data.iter_mut().enumerate() {
+            *byte = match addr + i {
+                LCD_COMMAND => self.address,
+                LCD_DATA => {
+                    let value = self.memory[self.address as usize];
+                    self.advance();
+                    value
+                }
+                _ => return Err(BasicBusError::UnmappedAddress),
+            };
+        }
+        Ok(data.len())
+    }
+
+    fn peek(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        let address = self.address;
+        let result = self.read(now, addr, data);
+        self.address = address;
+        result
+    }
+
+    fn write(
+        &mut self,
+        _now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        let addr = addr
+            .try_into()
+            .map_err(|_| BasicBusError::UnmappedAddress)?;
+        for (i, byte) in data.iter().enumerate() {
+            match addr + i {
+                LCD_COMMAND => self.command(*byte),
+                LCD_DATA => {
+                    self.memory[self.address as usize] = *byte;
+                    self.advance();
+                }
+                _ => return Err(BasicBusError::UnmappedAddress),
+            }
+        }
+        self.update_output();
+        Ok(data.len())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use emulator_hal::BusAccessExt;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    fn write_text(lcd: &mut CharacterLcd<Duration>, text: &str) {
+        for byte in text.bytes() {
+            lcd.write_u8(Duration::ZERO, LCD_DATA, byte).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_rows_and_output() {
+        let shown = Rc::new(RefCell::new(Vec::new()));
+        let updates = shown.clone();
+        let mut lcd = CharacterLcd::<Duration>::new(4, 8)
+            .with_output(move |lines| updates.borrow_mut().push(lines.join("|")));
+
+        lcd.write_u8(Duration::ZERO, LCD_COMMAND, 0x0C).unwrap();
+        write_text(&mut lcd, "Hi");
+        lcd.write_u8(Duration::ZERO, LCD_COMMAND, 0x80 | 0x48)
+            .unwrap();
+        write_text(&mut lcd, "row4");
+        lcd.write_u8(Duration::ZERO, LCD_COMMAND, 0x80 | 0x40)
+            .unwrap();
+        write_text(&mut lcd, "row2");
+
+        assert_eq!(
+            lcd.lines(),
+            ["Hi      ", "row2    ", "        ", "row4    "]
+        );
+        assert_eq!(shown.borrow().len(), 11);
+        assert_eq!(shown.borrow()[0], "        |        |        |        ");
+
+        lcd.write_u8(Duration::ZERO, LCD_COMMAND, 0x01).unwrap();
+        assert_eq!(lcd.read_u8(Duration::ZERO, LCD_COMMAND).unwrap(), 0);
+        assert_eq!(lcd.lines()[1], "        ");
+    }
+
+    #[test]
+    fn test_address_wraps_between_lines() {
+        let mut lcd = CharacterLcd::<Duration>::new(2, 16);
+        lcd.write_u8(Duration::ZERO, LCD_COMMAND, 0x0C).unwrap();
+
+        lcd.write_u8(Duration::ZERO, LCD_COMMAND, 0x80 | 0x27)
+            .unwrap();
+        write_text(&mut lcd, "AB");
+        assert_eq!(lcd.read_u8(Duration::ZERO, LCD_COMMAND).unwrap(), 0x41);
+        assert_eq!(lcd.lines()[1], "B               ");
+        let mut data = [0; 2];
+        lcd.peek(Duration::ZERO, LCD_COMMAND, &mut data).unwrap();
+        assert_eq!(data, [0x41, b' ']);
+        assert_eq!(lcd.read_u8(Duration::ZERO, LCD_COMMAND).unwrap(), 0x41);
+
+        // decrement back across the start of the second line
+        lcd.write_u8(Duration::ZERO, LCD_COMMAND, 0x04).unwrap();
+        lcd.write_u8(Duration::ZERO, LCD_COMMAND, 0x80 | 0x40)
+            .unwrap();
+        write_text(&mut lcd, "C");
+        assert_eq!(lcd.read_u8(Duration::ZERO, LCD_COMMAND).unwrap(), 0x27);
+        assert_eq!(lcd.lines()[1], "C               ");
+        assert!(lcd.read_u8(Duration::ZERO, 2_u32).is_err());
+    }
+}
@@ -0,0 +1,34 @@
+#![doc = include_str!("../README.md")]
+#![warn(missing_docs)]
+#![cfg_attr(all(not(feature = "std"), not(test)), no_std)]
+
+extern crate alloc;
+
+mod console;
+pub use crate::console::*;
+
+mod dma;
+pub use crate::dma::*;
+
+#[cfg(feature = "std")]
+mod hostfs;
+#[cfg(feature = "std")]
+pub use crate::hostfs::*;
+
+mod keyboard;
+pub use crate::keyboard::*;
+
+mod lcd;
+pub use crate::lcd::*;
+
+mod parallel;
+pub use crate::parallel::*;
+
+mod random;
+pub use crate::random::*;
+
+mod regmap;
+pub use crate::regmap::*;
+
+mod segment;
+pub use crate::segment::*;
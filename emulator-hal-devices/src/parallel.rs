@@ -0,0 +1,268 @@
+//! An 8-bit parallel port with handshaking lines
+
+use core::convert::Infallible;
+
+use emulator_hal::{BasicBusError, BusAccess, Instant as EmuInstant, Signal, Step};
+
+/// The offset of the data register
+pub const PARALLEL_DATA: usize = 0;
+/// The offset of the direction register, where a 1 bit makes the corresponding data line an output
+pub const PARALLEL_DIRECTION: usize = 1;
+/// The offset of the control and status register
+pub const PARALLEL_CONTROL: usize = 2;
+
+/// Control bit which drives the strobe line
+pub const PARALLEL_STROBE: u8 = 0x01;
+/// Status bit which reflects the current level of the acknowledge line (read only)
+pub const PARALLEL_ACK: u8 = 0x02;
+/// Status bit which reflects the current level of the busy line (read only)
+pub const PARALLEL_BUSY: u8 = 0x04;
+/// Status bit which is set when the acknowledge line becomes active, and is cleared by writing a
+/// 1 to it
+pub const PARALLEL_ACK_EDGE: u8 = 0x08;
+/// Control bit which raises the interrupt output while `PARALLEL_ACK_EDGE` is set
+pub const PARALLEL_INTERRUPT: u8 = 0x10;
+
+/// An 8-bit parallel port, such as a printer port or a general purpose latch
+///
+/// The port has three registers, at the `PARALLEL_*` offsets.  Each data line is either an
+/// output, driven from the value last written to the data register, or an input, driven by the
+/// attached peripheral with `set_input()`.  Reading the data register returns the output
+/// latch for output lines, and the input value for input lines.  With all lines set as outputs,
+/// the port behaves as a simple latch.
+///
+/// The `strobe` line is driven by the port, and the `ack` and `busy` lines are driven by the
+/// peripheral for handshaking.  When the `ack` line becomes active, the port latches
+/// `PARALLEL_ACK_EDGE`, and raises `interrupt` if it's enabled.  The port samples its input lines
+/// whenever it's stepped or accessed
+pub struct ParallelPort<Instant>
+where
+    Instant: EmuInstant,
+{
+    output: u8,
+    input: u8,
+    direction: u8,
+    control: u8,
+    last_ack: bool,
+    ack_edge: bool,
+    /// The period between samples of the input lines
+    pub sample_period: Instant::Duration,
+    /// The strobe line, driven by the port
+    pub strobe: Signal,
+    /// The acknowledge line, driven by the peripheral
+    pub ack: Signal,
+    /// The busy line, driven by the peripheral
+    pub busy: Signal,
+    /// The interrupt output line
+    pub interrupt: Signal,
+}
+
+impl<Instant> ParallelPort<Instant>
+where
+    Instant: EmuInstant,
+{
+    /// Construct a new parallel port with all data lines set as inputs
+    pub fn new(sample_period: Instant::Duration) -> Self {
+        Self {
+            output: 0,
+            input: 0,
+            direction: 0,
+            control: 0,
+            last_ack: false,
+            ack_edge: false,
+            sample_period,
+            strobe: Signal::new(),
+            ack: Signal::new(),
+            busy: Signal::new(),
+            interrupt: Signal::new(),
+        }
+    }
+
+    /// Returns the value driven onto the data lines by the port, with input lines as 0
+    pub fn output(&self) -> u8 {
+        self.output & self.direction
+    }
+
+    /// Set the value driven onto the data lines by the peripheral, which only affects input lines
+    pub fn set_input(&mut self, value: u8) {
+        self.input = value;
+    }
+
+    /// Sample the handshaking lines, and update the interrupt output
+    pub fn sample(&mut self) {
+        let ack = self.ack.get();
+        if ack && !self.last_ack {
+            self.ack_edge = true;
+        }
+        self.last_ack = ack;
+        self.interrupt
+            .set(self.ack_edge && self.control & PARALLEL_INTERRUPT != 0);
+    }
+
+    fn read_register(&self, register: usize) -> Option<u8> {
+        match register {
+            PARALLEL_DATA => Some((self.output & self.direction) | (self.input & !self.direction)),
+            PARALLEL_DIRECTION => Some(self.direction),
+            PARALLEL_CONTROL => {
+                let mut value = self.control;
+                if self.ack.get() {
+                    value |= PARALLEL_ACK;
+                }
+                if self.busy.get() {
+                    value |= PARALLEL_BUSY;
+                }
+                if self.ack_edge {
+                    value |= PARALLEL_ACK_EDGE;
+                }
+                Some(value)
+            }
+            _ => None,
+        }
+    }
+
+    fn write_register(&mut self, register: usize, value: u8) -> Option<()> {
+        match register {
+            PARALLEL_DATA => self.output = value,
+            PARALLEL_DIRECTION => self.direction = value,
+            PARALLEL_CONTROL => {
+                self.control = value & (PARALLEL_STROBE | PARALLEL_INTERRUPT);
+                if value & PARALLEL_ACK_EDGE != 0 {
+                    self.ack_edge = false;
+                }
+                self.strobe.set(value & PARALLEL_STROBE != 0);
+            }
+            _ => return None,
+        }
+        Some(())
+    }
+}
+
+impl<Address, Instant> BusAccess<Address> for ParallelPort<Instant>
+where
+    Address: TryInto<usize> + Copy,
+    Instant: EmuInstant,
+{
+    type Instant = Instant;
+    type Error = BasicBusError;
+
+    fn read(
+        &mut self,
+        _now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        let addr = addr
+            .try_into()
+            .map_err(|_| BasicBusError::UnmappedAddress)?;
+        self.sample();
+        for (i, byte) in data.iter_mut().enumerate() {
+            *byte = self
+                .read_register(addr + i)
+                .ok_or(BasicBusError::UnmappedAddress)?;
+        }
+        Ok(data.len())
+    }
+
+    fn write(
+        &mut self,
+        _now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        let addr = addr
+            .try_into()
+            .map_err(|_| BasicBusError::UnmappedAddress)?;
+        for (i, byte) in data.iter().enumerate() {
+            self.write_register(addr + i, *byte)
+                .ok_or(BasicBusError::UnmappedAddress)?;
+        }
+        self.sample();
+        Ok(data.len())
+    }
+}
+
+impl<Address, Bus, Instant> Step<Address, Bus> for ParallelPort<Instant>
+where
+    Address: Copy,
+    Bus: BusAccess<Address, Instant = Instant>,
+    Instant: EmuInstant,
+    Instant::Duration: Copy,
+{
+    type Error = Infallible;
+
+    fn is_running(&mut self) -> bool {
+        true
+    }
+
+    fn reset(&mut self, _now: Instant, _bus: &mut Bus) -> Result<(), Self::Error> {
+        self.output = 0;
+        self.direction = 0;
+        self.control = 0;
+        self.ack_edge = false;
+        self.strobe.lower();
+        self.interrupt.lower();
+        Ok(())
+    }
+
+    fn step(&mut self, now: Instant, _bus: &mut Bus) -> Result<Instant, Self::Error> {
+        self.sample();
+        Ok(now + self.sample_period)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use emulator_hal::BusAccessExt;
+    use emulator_hal::NoBus;
+    use std::time::Duration;
+
+    #[test]
+    fn test_mixed_direction_data() {
+        let mut port = ParallelPort::<Duration>::new(Duration::from_micros(1));
+
+        port.write_u8(Duration::ZERO, PARALLEL_DIRECTION, 0xF0)
+            .unwrap();
+        port.write_u8(Duration::ZERO, PARALLEL_DATA, 0xAB).unwrap();
+        port.set_input(0x5C);
+
+        assert_eq!(port.output(), 0xA0);
+        assert_eq!(port.read_u8(Duration::ZERO, PARALLEL_DATA).unwrap(), 0xAC);
+        assert!(port.read_u8(Duration::ZERO, 3_usize).is_err());
+    }
+
+    #[test]
+    fn test_strobe_and_acknowledge() {
+        let mut port = ParallelPort::<Duration>::new(Duration::from_micros(1));
+        let mut bus = NoBus::new();
+        let printer_ack = port.ack.clone();
+        let irq = port.interrupt.clone();
+
+        port.write_u8(
+            Duration::ZERO,
+            PARALLEL_CONTROL,
+            PARALLEL_INTERRUPT | PARALLEL_STROBE,
+        )
+        .unwrap();
+        assert!(port.strobe.get());
+
+        printer_ack.raise();
+        let next = Step::<usize, _>::step(&mut port, Duration::ZERO, &mut bus).unwrap();
+        assert_eq!(next, Duration::from_micros(1));
+        assert!(irq.get());
+        assert_eq!(
+            port.read_u8(next, PARALLEL_CONTROL).unwrap(),
+            PARALLEL_INTERRUPT | PARALLEL_STROBE | PARALLEL_ACK | PARALLEL_ACK_EDGE
+        );
+
+        printer_ack.lower();
+        port.write_u8(
+            next,
+            PARALLEL_CONTROL,
+            PARALLEL_INTERRUPT | PARALLEL_ACK_EDGE,
+        )
+        .unwrap();
+        assert!(!irq.get());
+        assert!(!port.strobe.get());
+    }
+}
@@ -0,0 +1,233 @@
+//! A deterministic pseudo-random number source
+
+use core::marker::PhantomData;
+
+use emulator_hal::{
+    BasicBusError, BusAccess, Instant as EmuInstant, Snapshot, SnapshotError, StateReader,
+    StateWriter,
+};
+
+/// The offset of the 64-bit seed register, in big endian order
+pub const RANDOM_SEED: usize = 0;
+/// The offset of the data register, where each byte read returns a new random byte
+pub const RANDOM_DATA: usize = 8;
+/// The number of bytes of address space used by the device's registers
+pub const RANDOM_REGISTERS: usize = 16;
+
+/// A register-mapped pseudo-random number source
+///
+/// Unlike a source backed by the host's random number generator, the sequence of values only
+/// depends on the seed and the number of values read, so guest software that needs entropy
+/// behaves the same every time it's run, replayed, or rewound with a save state.  The sequence
+/// is generated with SplitMix64, so every seed, including 0, gives a usable sequence.
+///
+/// | Offset | Register |
+/// |--------|----------|
+/// | 0-7    | seed, which restarts the sequence when any byte of it is written |
+/// | 8-15   | data, where every byte read is the next byte of the sequence |
+///
+/// The whole data register can be read at once to get several random bytes in one access
+pub struct RandomDevice<Instant> {
+    seed: u64,
+    state: u64,
+    buffer: [u8; 8],
+    buffered: usize,
+    instant: PhantomData<Instant>,
+}
+
+impl<Instant> RandomDevice<Instant> {
+    /// Construct a new random number source with the given seed
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            state: seed,
+            buffer: [0; 8],
+            buffered: 0,
+            instant: PhantomData,
+        }
+    }
+
+    /// Returns the current seed
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Restart the sequence from the given seed
+    pub fn reseed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.state = seed;
+        self.buffered = 0;
+    }
+
+    /// Returns the next byte of the sequence
+    pub fn next_byte(&mut self) -> u8 {
+        if self.buffered == 0 {
+            self.buffer = self.next_u64().to_le_bytes();
+            self.buffered = self.buffer.len();
+        }
+        self.buffered -= 1;
+        self.buffer[self.buffered]
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut value = self.state;
+        value = (value ^ (value >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        value = (value ^ (value >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        value ^ (value >> 31)
+    }
+}
+
+impl<Address, Instant> BusAccess<Address> for RandomDevice<Instant>
+where
+    Address: TryInto<usize> + Copy,
+    Instant: EmuInstant,
+{
+    type Instant = Instant;
+    type Error = BasicBusError;
+
+    fn read(
+        &mut self,
+        _now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        let addr = addr
+            .try_into()
+            .map_err(|_| BasicBusError::UnmappedAddress)?;
+        for (i, byte) in data.iter_mut().enumerate() {
+            *byte = match addr + i {
+                register @ RANDOM_SEED..=7 => self.seed.to_be_bytes()[register],
+                RANDOM_DATA..=15 => self.next_byte(),
+                _ => return Err(BasicBusError::UnmappedAddress),
+            };
+        }
+        Ok(data.len())
+    }
+
+    fn peek(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        // read from a copy, so that the upcoming bytes are shown without being consumed
+        let mut copy = Self { ..*self };
+        copy.read(now, addr, data)
+    }
+
+    fn write(
+        &mut self,
+        _now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        let addr = addr
+            .try_into()
+            .map_err(|_| BasicBusError::UnmappedAddress)?;
+        let mut seed = self.seed.to_be_bytes();
+        for (i, byte) in data.iter().enumerate() {
+            match addr + i {
+                register @ RANDOM_SEED..=7 => seed[register] = *byte,
+                RANDOM_DATA..=15 => {}
+                _ => return Err(BasicBusError::UnmappedAddress),
+            }
+        }
+        if addr < RANDOM_DATA {
+            self.reseed(u64::from_be_bytes(seed));
+        }
+        Ok(data.len())
+    }
+}
+
+impl<Instant> Snapshot for RandomDevice<Instant> {
+    fn save_state(&self, writer: &mut dyn StateWriter) -> Result<(), SnapshotError> {
+        writer.write_u64(self.seed)?;
+        writer.write_u64(self.state)?;
+        writer.write_bytes(&self.buffer)?;
+        writer.write_u8(self.buffered as u8)
+    }
+
+    fn load_state(&mut self, reader: &mut dyn StateReader) -> Result<(), SnapshotError> {
+        self.seed = reader.read_u64()?;
+        self.state = reader.read_u64()?;
+        reader.read_bytes(&mut self.buffer)?;
+        self.buffered = reader.read_u8()? as usize;
+        if self.buffered > self.buffer.len() {
+            return Err(SnapshotError::InvalidData);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use emulator_hal::BusAccessExt;
+    use std::time::Duration;
+
+    struct State(u32, Vec<u8>);
+
+    impl StateReader for State {
+        fn version(&self) -> u32 {
+            self.0
+        }
+
+        fn read_bytes(&mut self, data: &mut [u8]) -> Result<(), SnapshotError> {
+            if data.len() > self.1.len() {
+                return Err(SnapshotError::UnexpectedEnd);
+            }
+            let rest = self.1.split_off(data.len());
+            data.copy_from_slice(&self.1);
+            self.1 = rest;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_reseed_repeats_sequence() {
+        let mut random = RandomDevice::<Duration>::new(0);
+
+        let mut upcoming = [0; 8];
+        random
+            .peek(Duration::ZERO, RANDOM_DATA, &mut upcoming)
+            .unwrap();
+        let first = random.read_beu64(Duration::ZERO, RANDOM_DATA).unwrap();
+        assert_eq!(first, u64::from_be_bytes(upcoming));
+        let second = random.read_beu32(Duration::ZERO, RANDOM_DATA).unwrap();
+        assert_ne!(first, 0);
+        assert_ne!(first as u32, second);
+
+        random.write_u8(Duration::ZERO, 7_usize, 0).unwrap();
+        assert_eq!(
+            random.read_beu64(Duration::ZERO, RANDOM_DATA).unwrap(),
+            first
+        );
+
+        random
+            .write_beu64(Duration::ZERO, RANDOM_SEED, 0x1234)
+            .unwrap();
+        assert_eq!(random.seed(), 0x1234);
+        assert_ne!(
+            random.read_beu64(Duration::ZERO, RANDOM_DATA).unwrap(),
+            first
+        );
+        assert!(random.read_u8(Duration::ZERO, RANDOM_REGISTERS).is_err());
+    }
+
+    #[test]
+    fn test_restore_from_snapshot() {
+        let mut random = RandomDevice::<Duration>::new(42);
+        random.read_beu16(Duration::ZERO, RANDOM_DATA).unwrap();
+
+        let mut saved = Vec::new();
+        random.save_state(&mut saved).unwrap();
+        let expected: Vec<u8> = (0..12).map(|_| random.next_byte()).collect();
+
+        let mut restored = RandomDevice::<Duration>::new(0);
+        restored.load_state(&mut State(0, saved)).unwrap();
+        let values: Vec<u8> = (0..12).map(|_| restored.next_byte()).collect();
+        assert_eq!(values, expected);
+        assert_eq!(restored.seed(), 42);
+    }
+}
@@ -0,0 +1,405 @@
+//! A declarative map of a device's registers, with a `BusAccess` implementation
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+
+use emulator_hal::{BasicBusError, BusAccess, ByteOrder, Instant as EmuInstant};
+
+/// A handler that is called when a register is read, which is given the register's stored value
+/// and returns the value that is read
+pub type RegisterRead = Box<dyn FnMut(&mut u64) -> u64>;
+
+/// A handler that is called when a register is written, which is given the register's stored
+/// value, the value that was written, and a mask of the bits that were written, and updates the
+/// stored value
+pub type RegisterWrite = Box<dyn FnMut(&mut u64, u64, u64)>;
+
+/// A single register in a `RegisterMap`
+///
+/// By default, a register stores the values written to it and returns them when it's read.
+/// Either side can be replaced with a handler, such as to clear a status register when it's read,
+/// or to start an operation when a command register is written
+pub struct Register {
+    offset: usize,
+    width: usize,
+    reset: u64,
+    value: u64,
+    read_only: bool,
+    on_read: Option<RegisterRead>,
+    on_write: Option<RegisterWrite>,
+}
+
+impl Register {
+    /// Construct a new register of `width` bytes at `offset`, with the given reset value
+    ///
+    /// # Panics
+    ///
+    /// Panics if `width` is not between 1 and 8
+    pub fn new(offset: usize, width: usize, reset: u64) -> Self {
+        assert!(
+            (1..=8).contains(&width),
+            "register width must be between 1 and 8 bytes"
+        );
+        let reset = reset & Self::mask(width);
+        Self {
+            offset,
+            width,
+            reset,
+            value: reset,
+            read_only: false,
+            on_read: None,
+            on_write: None,
+        }
+    }
+
+    /// Ignore writes to this register, unless it has a write handler
+    pub fn read_only(mut self) -> Self {
+        self.read_only = true;
+        self
+    }
+
+    /// Call the given handler to get the value when the register is read
+    pub fn on_read<F>(mut self, handler: F) -> Self
+    where
+        F: FnMut(&mut u64) -> u64 + 'static,
+    {
+        self.on_read = Some(Box::new(handler));
+        self
+    }
+
+    /// Call the given handler to update the stored value when the register is written
+    pub fn on_write<F>(mut self, handler: F) -> Self
+    where
+        F: FnMut(&mut u64, u64, u64) + 'static,
+    {
+        self.on_write = Some(Box::new(handler));
+        self
+    }
+
+    /// Returns the offset of the register's first byte
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns the width of the register in bytes
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    fn mask(width: usize) -> u64 {
+        u64::MAX >> (64 - width * 8)
+    }
+
+    fn end(&self) -> usize {
+        self.offset + self.width
+    }
+
+    fn read(&mut self) -> u64 {
+        let value = match self.on_read.as_mut() {
+            Some(handler) => handler(&mut self.value),
+            None => self.value,
+        };
+        value & Self::mask(self.width)
+    }
+
+    fn write(&mut self, value: u64, mask: u64) {
+        let mask = mask & Self::mask(self.width);
+        let value = value & mask;
+        match self.on_write.as_mut() {
+            Some(handler) => handler(&mut self.value, value, mask),
+            None if !self.read_only => self.value = (self.value & !mask) | value,
+            None => {}
+        }
+        self.value &= Self::mask(self.width);
+    }
+}
+
+/// A device made from a list of registers, which implements `BusAccess` for them
+///
+/// Each register is declared with its offset, width, and reset value, and optionally with
+/// handlers for reads and writes, and the map takes care of decoding accesses.  An access can
+/// cover any part of one or more registers, such as a byte of a 32-bit register, or a 32-bit
+/// access that starts in the middle of one register and ends in the next.  Each register that
+/// an access covers has its handler called once.  A write that only covers some bytes of a
+/// register is given to the handler with a mask of the bits in those bytes, and by default only
+/// those bits of the stored value are changed.  If any byte of an access isn't part of a
+/// register, `BasicBusError::UnmappedAddress` is returned and no registers are accessed.
+///
+/// Accesses made with `peek()` and `poke()` use the stored values without calling any handlers
+pub struct RegisterMap<Instant> {
+    order: ByteOrder,
+    registers: Vec<Register>,
+    instant: PhantomData<Instant>,
+}
+
+impl<Instant> RegisterMap<Instant> {
+    /// Construct a new map with no registers, using the given byte order for every register
+    pub fn new(order: ByteOrder) -> Self {
+        Self {
+            order,
+            registers: Vec::new(),
+            instant: PhantomData,
+        }
+    }
+
+    /// Add a register to the map
+    ///
+    /// # Panics
+    ///
+    /// Panics if the register overlaps a register that is already in the map
+    pub fn with_register(mut self, register: Register) -> Self {
+        let index = self
+            .registers
+            .partition_point(|other| other.offset < register.offset);
+        let overlaps_prev = index > 0 && self.registers[index - 1].end() > register.offset;
+        let overlaps_next = self
+            .registers
+            .get(index)
+            .map(|next| next.offset < register.end())
+            .unwrap_or(false);
+        assert!(
+            !overlaps_prev && !overlaps_next,
+            "register at offset {:#x} overlaps another register",
+            register.offset
+        );
+        self.registers.insert(index, register);
+        self
+    }
+
+    /// Returns the stored value of the register at `offset`, if there is one
+    pub fn value(&self, offset: usize) -> Option<u64> {
+        self.registers
+            .iter()
+            .find(|register| register.offset == offset)
+            .map(|register| register.value)
+    }
+
+    /// Set the stored value of the register at `offset`, without calling its write handler
+    ///
+    /// This is used by the device to update its own registers, such as to set a status bit.
+    /// Returns false if there is no register at `offset`
+    pub fn set_value(&mut self, offset: usize, value: u64) -> bool {
+        match self
+            .registers
+            .iter_mut()
+            .find(|register| register.offset == offset)
+        {
+            Some(register) => {
+                register.value = value & Register::mask(register.width);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Set every register back to its reset value
+    pub fn reset(&mut self) {
+        for register in self.registers.iter_mut() {
+            register.value = register.reset;
+        }
+    }
+
+    /// Returns the registers in the map, in order of their offsets
+    pub fn registers(&self) -> &[Register] {
+        &self.registers
+    }
+
+    /// Returns the index of the register containing the given offset
+    fn find(&self, offset: usize) -> Option<usize> {
+        let index = self
+            .registers
+            .partition_point(|register| register.end() <= offset);
+        match self.registers.get(index) {
+            Some(register) if register.offset <= offset => Some(index),
+            _ => None,
+        }
+    }
+
+    /// Returns the start offset of an access, if every byte of it is part of a register
+    fn decode<Address>(&self, addr: Address, len: usize) -> Result<usize, BasicBusError>
+    where
+        Address: TryInto<usize>,
+    {
+        let start = addr
+            .try_into()
+            .map_err(|_| BasicBusError::UnmappedAddress)?;
+        let mut offset = start;
+        while offset < start + len {
+            let index = self.find(offset).ok_or(BasicBusError::UnmappedAddress)?;
+            offset = self.registers[index].end();
+        }
+        Ok(start)
+    }
+
+    /// Calls `f` for each register covered by an access that has already been decoded, with the
+    /// register, the range of its bytes that are covered, and the range of the access's data
+    fn for_each_part<F>(&mut self, start: usize, len: usize, mut f: F)
+    where
+        F: FnMut(&mut Register, ByteOrder, core::ops::Range<usize>, core::ops::Range<usize>),
+    {
+        let mut i = 0;
+        while i < len {
+            let index = self.find(start + i).unwrap();
+            let register = &mut self.registers[index];
+            let first = start + i - register.offset;
+            let count = (register.width - first).min(len - i);
+            f(register, self.order, first..first + count, i..i + count);
+            i += count;
+        }
+    }
+}
+
+fn to_bytes(value: u64, width: usize, order: ByteOrder) -> [u8; 8] {
+    let mut bytes = [0; 8];
+    match order {
+        ByteOrder::Big => bytes[..width].copy_from_slice(&value.to_be_bytes()[8 - width..]),
+        ByteOrder::Little => bytes[..width].copy_from_slice(&value.to_le_bytes()[..width]),
+    }
+    bytes
+}
+
+fn from_bytes(bytes: [u8; 8], width: usize, order: ByteOrder) -> u64 {
+    let mut buffer = [0; 8];
+    match order {
+        ByteOrder::Big => {
+            buffer[8 - width..].copy_from_slice(&bytes[..width]);
+            u64::from_be_bytes(buffer)
+        }
+        ByteOrder::Little => {
+            buffer[..width].copy_from_slice(&bytes[..width]);
+            u64::from_le_bytes(buffer)
+        }
+    }
+}
+
+impl<Address, Instant> BusAccess<Address> for RegisterMap<Instant>
+where
+    Address: TryInto<usize> + Copy,
+    Instant: EmuInstant,
+{
+    type Instant = Instant;
+    type Error = BasicBusError;
+
+    fn read(
+        &mut self,
+        _now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        let start = self.decode(addr, data.len())?;
+        self.for_each_part(start, data.len(), |register, order, bytes, part| {
+            let value = to_bytes(register.read(), register.width, order);
+            data[part].copy_from_slice(&value[bytes]);
+        });
+        Ok(data.len())
+    }
+
+    fn write(
+        &mut self,
+        _now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        let start = self.decode(addr, data.len())?;
+        self.for_each_part(start, data.len(), |register, order, bytes, part| {
+            let mut value = [0; 8];
+            let mut mask = [0; 8];
+            value[bytes.clone()].copy_from_slice(&data[part]);
+            mask[bytes].fill(0xFF);
+            register.write(
+                from_bytes(value, register.width, order),
+                from_bytes(mask, register.width, order),
+            );
+        });
+        Ok(data.len())
+    }
+
+    fn peek(
+        &mut self,
+        _now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        let start = self.decode(addr, data.len())?;
+        self.for_each_part(start, data.len(), |register, order, bytes, part| {
+            let value = to_bytes(register.value, register.width, order);
+            data[part].copy_from_slice(&value[bytes]);
+        });
+        Ok(data.len())
+    }
+
+    fn poke(
+        &mut self,
+        _now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        let start = self.decode(addr, data.len())?;
+        self.for_each_part(start, data.len(), |register, order, bytes, part| {
+            let mut value = to_bytes(register.value, register.width, order);
+            value[bytes].copy_from_slice(&data[part]);
+            register.value = from_bytes(value, register.width, order);
+        });
+        Ok(data.len())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::rc::Rc;
+    use core::cell::Cell;
+    use emulator_hal::{BusAccessExt, Instant};
+    use std::time::Duration;
+
+    #[test]
+    fn test_partial_and_misaligned_accesses() {
+        let now = Duration::START;
+        let started = Rc::new(Cell::new(0));
+        let command = started.clone();
+        let mut map = RegisterMap::<Duration>::new(ByteOrder::Big)
+            .with_register(Register::new(0, 4, 0x1234_5678))
+            .with_register(Register::new(4, 2, 0xAB00).read_only())
+            .with_register(
+                Register::new(6, 1, 0)
+                    .on_write(move |_, value, _| command.set(command.get() + value)),
+            )
+            // write 1 to clear the status bits, which also clear when read
+            .with_register(
+                Register::new(8, 2, 0x0103)
+                    .on_read(core::mem::take)
+                    .on_write(|value, written, _| *value &= !written),
+            );
+
+        assert_eq!(map.read_u8(now, 1_u32).unwrap(), 0x34);
+        assert_eq!(map.read_beu16(now, 3_u32).unwrap(), 0x78AB);
+        map.write_u8(now, 2_u32, 0xFF).unwrap();
+        assert_eq!(map.value(0), Some(0x1234_FF78));
+
+        // a write across three registers calls each handler once
+        map.write_beu32(now, 3_u32, 0xAACD_EF02).unwrap();
+        assert_eq!(map.value(0), Some(0x1234_FFAA));
+        assert_eq!(map.value(4), Some(0xAB00));
+        assert_eq!(started.get(), 2);
+
+        // only the written byte is cleared
+        map.write_u8(now, 9_u32, 0x01).unwrap();
+        let mut data = [0; 2];
+        map.peek(now, 8_u32, &mut data).unwrap();
+        assert_eq!(data, [0x01, 0x02]);
+        assert_eq!(map.read_beu16(now, 8_u32).unwrap(), 0x0102);
+        assert_eq!(map.value(8), Some(0));
+
+        // an access that touches a gap fails without any effect
+        assert!(matches!(
+            map.write_beu32(now, 6_u32, 0x0101_0101),
+            Err(BasicBusError::UnmappedAddress)
+        ));
+        assert_eq!(started.get(), 2);
+
+        map.reset();
+        assert_eq!(map.read_beu32(now, 0_u32).unwrap(), 0x1234_5678);
+        assert_eq!(map.value(8), Some(0x0103));
+    }
+}
@@ -0,0 +1,238 @@
+//! A multi-digit seven-segment display
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+
+use emulator_hal::{BasicBusError, BusAccess, Instant as EmuInstant};
+use emulator_hal_display::{PixelFormat, VideoMode, VideoSink};
+
+/// The bit of a digit register for the top segment
+pub const SEGMENT_A: u8 = 0x01;
+/// The bit of a digit register for the upper right segment
+pub const SEGMENT_B: u8 = 0x02;
+/// The bit of a digit register for the lower right segment
+pub const SEGMENT_C: u8 = 0x04;
+/// The bit of a digit register for the bottom segment
+pub const SEGMENT_D: u8 = 0x08;
+/// The bit of a digit register for the lower left segment
+pub const SEGMENT_E: u8 = 0x10;
+/// The bit of a digit register for the upper left segment
+pub const SEGMENT_F: u8 = 0x20;
+/// The bit of a digit register for the middle segment
+pub const SEGMENT_G: u8 = 0x40;
+/// The bit of a digit register for the decimal point
+pub const SEGMENT_DP: u8 = 0x80;
+
+/// The width in pixels of each digit when rendered to a `VideoSink`
+pub const SEGMENT_DIGIT_WIDTH: u32 = 10;
+/// The height in pixels of each digit when rendered to a `VideoSink`
+pub const SEGMENT_DIGIT_HEIGHT: u32 = 13;
+
+const LIT: (u8, u8, u8) = (0xFF, 0x20, 0x10);
+const UNLIT: (u8, u8, u8) = (0x30, 0x08, 0x04);
+
+/// The characters shown by common segment patterns, used to convert the display to text
+const CHARACTERS: [(u8, char); 23] = [
+    (0x3F, '0'),
+    (0x06, '1'),
+    (0x5B, '2'),
+    (0x4F, '3'),
+    (0x66, '4'),
+    (0x6D, '5'),
+    (0x7D, '6'),
+    (0x07, '7'),
+    (0x7F, '8'),
+    (0x6F, '9'),
+    (0x77, 'A'),
+    (0x7C, 'b'),
+    (0x39, 'C'),
+    (0x5E, 'd'),
+    (0x79, 'E'),
+    (0x71, 'F'),
+    (0x76, 'H'),
+    (0x38, 'L'),
+    (0x73, 'P'),
+    (0x3E, 'U'),
+    (0x40, '-'),
+    (0x08, '_'),
+    (0x00, ' '),
+];
+
+/// A row of seven-segment digits, each with a decimal point
+///
+/// Each digit has a register at its own offset, with the leftmost digit at offset 0, where each
+/// bit drives one segment as given by the `SEGMENT_*` constants.  The display can be converted
+/// to text with `text()`, or drawn to a `VideoSink` with `render()`
+pub struct SevenSegment<Instant> {
+    digits: Vec<u8>,
+    instant: PhantomData<Instant>,
+}
+
+impl<Instant> SevenSegment<Instant>
+where
+    Instant: EmuInstant,
+{
+    /// Construct a new display with the given number of digits, all turned off
+    pub fn new(digits: usize) -> Self {
+        Self {
+            digits: vec![0; digits],
+            instant: PhantomData,
+        }
+    }
+
+    /// Returns the segments of each digit
+    pub fn digits(&self) -> &[u8] {
+        &self.digits
+    }
+
+    /// Returns the text shown by the display, with a `.` after each digit with its decimal point
+    /// lit
+    ///
+    /// Hexadecimal digits, and a few letters and symbols, are recognized, and any other pattern
+    /// of segments is shown as `?`
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        for segments in self.digits.iter() {
+            let character = CHARACTERS
+                .iter()
+                .find(|(pattern, _)| *pattern == segments & !SEGMENT_DP)
+                .map(|(_, character)| *character)
+                .unwrap_or('?');
+            text.push(character);
+            if segments & SEGMENT_DP != 0 {
+                text.push('.');
+            }
+        }
+        text
+    }
+
+    /// Draw the display as a single frame to the given sink, with each digit
+    /// `SEGMENT_DIGIT_WIDTH` pixels wide
+    pub fn render<Sink>(&self, now: Instant, sink: &mut Sink) -> Result<(), Sink::Error>
+    where
+        Sink: VideoSink<Instant = Instant>,
+    {
+        let requested = VideoMode::new(
+            self.digits.len() as u32 * SEGMENT_DIGIT_WIDTH,
+            SEGMENT_DIGIT_HEIGHT,
+            PixelFormat::Rgb888,
+        );
+        let mode = match sink.mode() {
+            Some(mode) if mode.width == requested.width && mode.height == requested.height => mode,
+            _ => sink.negotiate(requested)?,
+        };
+
+        let pixel_size = mode.format.bytes_per_pixel();
+        let mut scanline = vec![0; mode.scanline_size()];
+        for y in 0..SEGMENT_DIGIT_HEIGHT {
+            for (i, pixel) in scanline.chunks_exact_mut(pixel_size).enumerate() {
+                let digit = self.digits[i / SEGMENT_DIGIT_WIDTH as usize];
+                let x = i as u32 % SEGMENT_DIGIT_WIDTH;
+                let (red, green, blue) = match segment_at(x, y) {
+                    Some(segment) if digit & segment != 0 => LIT,
+                    Some(_) => UNLIT,
+                    None => (0, 0, 0),
+                };
+                mode.format.encode(red, green, blue, pixel);
+            }
+            sink.submit_scanline(now, y, &scanline)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the segment drawn at the given pixel of a digit, if any
+fn segment_at(x: u32, y: u32) -> Option<u8> {
+    match (x, y) {
+        (2..=6, 0) => Some(SEGMENT_A),
+        (7, 1..=5) => Some(SEGMENT_B),
+        (7, 7..=11) => Some(SEGMENT_C),
+        (2..=6, 12) => Some(SEGMENT_D),
+        (1, 7..=11) => Some(SEGMENT_E),
+        (1, 1..=5) => Some(SEGMENT_F),
+        (2..=6, 6) => Some(SEGMENT_G),
+        (9, 12) => Some(SEGMENT_DP),
+        _ => None,
+    }
+}
+
+impl<Address, Instant> BusAccess<Address> for SevenSegment<Instant>
+where
+    Address: TryInto<usize> + Copy,
+    Instant: EmuInstant,
+{
+    type Instant = Instant;
+    type Error = BasicBusError;
+
+    fn read(
+        &mut self,
+        _now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        let addr = addr
+            .try_into()
+            .map_err(|_| BasicBusError::UnmappedAddress)?;
+        let digits = self
+            .digits
+            .get(addr..addr + data.len())
+            .ok_or(BasicBusError::UnmappedAddress)?;
+        data.copy_from_slice(digits);
+        Ok(data.len())
+    }
+
+    fn write(
+        &mut self,
+        _now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        let addr = addr
+            .try_into()
+            .map_err(|_| BasicBusError::UnmappedAddress)?;
+        let digits = self
+            .digits
+            .get_mut(addr..addr + data.len())
+            .ok_or(BasicBusError::UnmappedAddress)?;
+        digits.copy_from_slice(data);
+        Ok(data.len())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use emulator_hal::BusAccessExt;
+    use emulator_hal_display::FrameBuffer;
+    use std::time::Duration;
+
+    #[test]
+    fn test_text() {
+        let mut display = SevenSegment::<Duration>::new(4);
+
+        display
+            .write_beu32(Duration::ZERO, 0_u32, 0x7F_86_40_5C)
+            .unwrap();
+        assert_eq!(display.text(), "81.-?");
+        assert!(display.write_u8(Duration::ZERO, 4_u32, 0).is_err());
+    }
+
+    #[test]
+    fn test_render_segments() {
+        let mut display = SevenSegment::<Duration>::new(2);
+        let mut sink = FrameBuffer::with_format(PixelFormat::Luma8);
+
+        display.write_u8(Duration::ZERO, 1_u32, SEGMENT_G).unwrap();
+        display.render(Duration::ZERO, &mut sink).unwrap();
+
+        let frame = sink.frame().unwrap();
+        assert_eq!(frame.len(), 20 * 13);
+        let pixel = |x: usize, y: usize| frame[y * 20 + x];
+        assert_eq!(pixel(0, 0), 0);
+        assert_ne!(pixel(4, 6), 0);
+        assert!(pixel(14, 6) > pixel(4, 6));
+        assert_eq!(pixel(14, 6), pixel(14 + 1, 6));
+    }
+}
@@ -0,0 +1,255 @@
+//! An in-memory video sink
+
+use alloc::vec::Vec;
+
+use emulator_hal::Instant as EmuInstant;
+
+use crate::output::FrameOutput;
+use crate::video::{PixelFormat, VideoError, VideoMode, VideoSink};
+
+/// A video sink which collects scanlines into frames in memory
+///
+/// Scanlines are drawn into a back buffer, and the frame becomes available from `frame()` when
+/// its last scanline is submitted.  A front-end can check `frame_count()` to detect when a new
+/// frame is ready, and then copy it to a window or texture.  The sink accepts any resolution,
+/// and any pixel format unless it was constructed with `with_format()`.
+///
+/// A video device can also render into a `FrameBuffer` of its own and expose it through
+/// `FrameOutput`, which reports each completed frame once from `frame_completed()`
+pub struct FrameBuffer<Instant> {
+    format: Option<PixelFormat>,
+    mode: Option<VideoMode>,
+    back: Vec<u8>,
+    front: Vec<u8>,
+    frame_count: u64,
+    frame_instant: Option<Instant>,
+    reported: u64,
+}
+
+impl<Instant> Default for FrameBuffer<Instant> {
+    fn default() -> Self {
+        Self {
+            format: None,
+            mode: None,
+            back: Vec::new(),
+            front: Vec::new(),
+            frame_count: 0,
+            frame_instant: None,
+            reported: 0,
+        }
+    }
+}
+
+impl<Instant> FrameBuffer<Instant>
+where
+    Instant: EmuInstant,
+{
+    /// Construct a new frame buffer which accepts any pixel format
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Construct a new frame buffer which always negotiates the given pixel format
+    pub fn with_format(format: PixelFormat) -> Self {
+        Self {
+            format: Some(format),
+            ..Self::default()
+        }
+    }
+
+    /// Returns the last completed frame, or `None` if no frame has been completed since the mode
+    /// was negotiated
+    pub fn frame(&self) -> Option<&[u8]> {
+        if self.front.is_empty() {
+            None
+        } else {
+            Some(&self.front)
+        }
+    }
+
+    /// Returns the number of frames that have been completed
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    /// Returns the time that the last completed frame was finished
+    pub fn frame_instant(&self) -> Option<Instant> {
+        self.frame_instant
+    }
+
+    fn complete_frame(&mut self, now: Instant) {
+        core::mem::swap(&mut self.front, &mut self.back);
+        if self.back.len() != self.front.len() {
+            self.back.resize(self.front.len(), 0);
+        }
+        self.frame_count += 1;
+        self.frame_instant = Some(now);
+    }
+}
+
+impl<Instant> VideoSink for FrameBuffer<Instant>
+where
+    Instant: EmuInstant,
+{
+    type Instant = Instant;
+    type Error = VideoError;
+
+    fn negotiate(&mut self, requested: VideoMode) -> Result<VideoMode, Self::Error> {
+        let mode = VideoMode {
+            format: self.format.unwrap_or(requested.format),
+            ..requested
+        };
+        if mode.frame_size() == 0 {
+            return Err(VideoError::UnsupportedMode);
+        }
+
+        self.mode = Some(mode);
+        self.back.clear();
+        self.back.resize(mode.frame_size(), 0);
+        self.front.clear();
+        Ok(mode)
+    }
+
+    fn mode(&self) -> Option<VideoMode> {
+        self.mode
+    }
+
+    fn submit_scanline(
+        &mut self,
+        now: Self::Instant,
+        line: u32,
+        data: &[u8],
+    ) -> Result<(), Self::Error> {
+        let mode = self.mode.ok_or(VideoError::NoMode)?;
+        if line >= mode.height {
+            return Err(VideoError::InvalidScanline(line));
+        }
+        if data.len() != mode.scanline_size() {
+            return Err(VideoError::InvalidLength(data.len()));
+        }
+
+        let start = line as usize * mode.scanline_size();
+        self.back[start..start + data.len()].copy_from_slice(data);
+        if line == mode.height - 1 {
+            self.complete_frame(now);
+        }
+        Ok(())
+    }
+
+    fn submit_frame(&mut self, now: Self::Instant, data: &[u8]) -> Result<(), Self::Error> {
+        let mode = self.mode.ok_or(VideoError::NoMode)?;
+        if data.len() != mode.frame_size() {
+            return Err(VideoError::InvalidLength(data.len()));
+        }
+
+        self.back.copy_from_slice(data);
+        self.complete_frame(now);
+        Ok(())
+    }
+}
+
+impl<Instant> FrameOutput for FrameBuffer<Instant>
+where
+    Instant: EmuInstant,
+{
+    type Instant = Instant;
+
+    fn output_mode(&self) -> Option<VideoMode> {
+        self.mode
+    }
+
+    fn frame(&self) -> Option<&[u8]> {
+        FrameBuffer::frame(self)
+    }
+
+    fn frame_completed(&mut self) -> Option<Self::Instant> {
+        if self.reported == self.frame_count {
+            return None;
+        }
+        self.reported = self.frame_count;
+        self.frame_instant
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_scanlines_complete_frame() {
+        let mut sink = FrameBuffer::with_format(PixelFormat::Rgb565);
+
+        let mode = sink
+            .negotiate(VideoMode::new(2, 2, PixelFormat::Rgb888))
+            .unwrap();
+        assert_eq!(mode, VideoMode::new(2, 2, PixelFormat::Rgb565));
+
+        let mut line = [0; 4];
+        mode.format.encode(0xFF, 0x00, 0x00, &mut line[0..2]);
+        mode.format.encode(0x00, 0x00, 0xFF, &mut line[2..4]);
+        sink.submit_scanline(Duration::from_millis(1), 0, &line)
+            .unwrap();
+        assert_eq!(sink.frame(), None);
+        sink.submit_scanline(Duration::from_millis(2), 1, &line)
+            .unwrap();
+
+        assert_eq!(
+            sink.frame(),
+            Some(&[0x00, 0xF8, 0x1F, 0x00, 0x00, 0xF8, 0x1F, 0x00][..])
+        );
+        assert_eq!(sink.frame_count(), 1);
+        assert_eq!(sink.frame_instant(), Some(Duration::from_millis(2)));
+        assert!(matches!(
+            sink.submit_scanline(Duration::from_millis(3), 2, &line),
+            Err(VideoError::InvalidScanline(2))
+        ));
+    }
+
+    #[test]
+    fn test_submit_frame() {
+        let mut sink = FrameBuffer::new();
+        assert!(matches!(
+            sink.submit_frame(Duration::ZERO, &[0; 4]),
+            Err(VideoError::NoMode)
+        ));
+
+        sink.negotiate(VideoMode::new(2, 2, PixelFormat::Luma8))
+            .unwrap();
+        sink.submit_frame(Duration::from_millis(16), &[1, 2, 3, 4])
+            .unwrap();
+        assert_eq!(sink.frame(), Some(&[1, 2, 3, 4][..]));
+        assert!(matches!(
+            sink.submit_frame(Duration::from_millis(32), &[1, 2, 3]),
+            Err(VideoError::InvalidLength(3))
+        ));
+    }
+
+    #[test]
+    fn test_blit_frame_output() {
+        let mut device = FrameBuffer::new();
+        let mut window = FrameBuffer::new();
+        device
+            .negotiate(VideoMode::new(2, 1, PixelFormat::Luma8))
+            .unwrap();
+        assert!(!device.blit(&mut window).unwrap());
+
+        device
+            .submit_frame(Duration::from_millis(16), &[5, 6])
+            .unwrap();
+        assert!(device.blit(&mut window).unwrap());
+        assert!(!device.blit(&mut window).unwrap());
+        assert_eq!(window.mode(), device.output_mode());
+        assert_eq!(window.frame(), Some(&[5, 6][..]));
+        assert_eq!(window.frame_instant(), Some(Duration::from_millis(16)));
+
+        let mut rgb_window = FrameBuffer::with_format(PixelFormat::Rgb888);
+        device
+            .submit_frame(Duration::from_millis(32), &[7, 8])
+            .unwrap();
+        assert!(matches!(
+            device.blit(&mut rgb_window),
+            Err(VideoError::UnsupportedMode)
+        ));
+    }
+}
@@ -0,0 +1,14 @@
+#![doc = include_str!("../README.md")]
+#![warn(missing_docs)]
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+mod framebuffer;
+pub use crate::framebuffer::*;
+
+mod output;
+pub use crate::output::*;
+
+mod video;
+pub use crate::video::*;
@@ -0,0 +1,80 @@
+//! The interface for front-ends to take completed frames from video devices
+
+use emulator_hal::Instant as EmuInstant;
+
+use crate::video::{VideoError, VideoMode, VideoSink};
+
+/// A video device which renders complete frames that a front-end can take and display
+///
+/// This is the counterpart of `VideoSink`, for devices that render into their own frame buffer
+/// rather than pushing their output to a sink.  After stepping the system, a front-end calls
+/// `frame_completed()` to find out whether a new frame was finished, and at what time, which
+/// acts as the vertical sync signal of the device.  It can then borrow the frame with `frame()`
+/// to copy it to a window or texture, or pass it on to a sink with `blit()`
+pub trait FrameOutput {
+    /// The type for the time that frames are completed at
+    type Instant: EmuInstant;
+
+    /// Returns the resolution and pixel format of the frames, if the device is producing video
+    fn output_mode(&self) -> Option<VideoMode>;
+
+    /// Returns the last completed frame, with the scanlines in order from top to bottom
+    ///
+    /// The frame is `output_mode().frame_size()` bytes long, or `None` if no frame has been
+    /// completed in the current mode
+    fn frame(&self) -> Option<&[u8]>;
+
+    /// Returns the time that the last frame was completed at, if a frame has been completed
+    /// since this was last called
+    fn frame_completed(&mut self) -> Option<Self::Instant>;
+
+    /// Submit the last completed frame to `sink`, if a new frame has been completed since
+    /// `frame_completed()` was last called, and return true if a frame was submitted
+    ///
+    /// The mode is negotiated with the sink first if it differs from the sink's current mode.
+    /// Returns `VideoError::UnsupportedMode` if the sink won't accept the pixel format of the
+    /// frame, since the frame can't be converted to another format
+    fn blit<Sink>(&mut self, sink: &mut Sink) -> Result<bool, Sink::Error>
+    where
+        Self: Sized,
+        Sink: VideoSink<Instant = Self::Instant>,
+        Sink::Error: From<VideoError>,
+    {
+        let now = match self.frame_completed() {
+            Some(now) => now,
+            None => return Ok(false),
+        };
+        let (mode, frame) = match (self.output_mode(), self.frame()) {
+            (Some(mode), Some(frame)) => (mode, frame),
+            _ => return Ok(false),
+        };
+
+        if sink.mode() != Some(mode) && sink.negotiate(mode)? != mode {
+            return Err(VideoError::UnsupportedMode.into());
+        }
+        sink.submit_frame(now, frame)?;
+        Ok(true)
+    }
+}
+
+impl<T> FrameOutput for &mut T
+where
+    T: FrameOutput + ?Sized,
+{
+    type Instant = T::Instant;
+
+    #[inline]
+    fn output_mode(&self) -> Option<VideoMode> {
+        T::output_mode(self)
+    }
+
+    #[inline]
+    fn frame(&self) -> Option<&[u8]> {
+        T::frame(self)
+    }
+
+    #[inline]
+    fn frame_completed(&mut self) -> Option<Self::Instant> {
+        T::frame_completed(self)
+    }
+}
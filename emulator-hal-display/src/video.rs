@@ -0,0 +1,180 @@
+//! The interface between video devices and the front-ends that display their output
+
+use emulator_hal::{ErrorType, Instant as EmuInstant};
+
+/// An error that occurred while sending video to a sink
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum VideoError {
+    /// The sink cannot display the requested resolution
+    UnsupportedMode,
+    /// Video was submitted before a mode was negotiated
+    NoMode,
+    /// A scanline number was past the bottom of the frame
+    InvalidScanline(u32),
+    /// The data submitted was not the size of a scanline or frame in the current mode
+    InvalidLength(usize),
+}
+
+impl ErrorType for VideoError {}
+
+/// The layout of a single pixel in video data
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum PixelFormat {
+    /// 8 bits of luminance
+    Luma8,
+    /// 16 bits with 5 bits of red, 6 bits of green, and 5 bits of blue, in little endian order
+    Rgb565,
+    /// 8 bits each of red, green, and blue
+    Rgb888,
+    /// 8 bits each of red, green, blue, and alpha, where alpha is always fully opaque
+    Rgba8888,
+    /// 8 bits each of blue, green, red, and alpha, where alpha is always fully opaque
+    Bgra8888,
+}
+
+impl PixelFormat {
+    /// Returns the number of bytes used by each pixel
+    pub const fn bytes_per_pixel(self) -> usize {
+        match self {
+            PixelFormat::Luma8 => 1,
+            PixelFormat::Rgb565 => 2,
+            PixelFormat::Rgb888 => 3,
+            PixelFormat::Rgba8888 | PixelFormat::Bgra8888 => 4,
+        }
+    }
+
+    /// Encode an 8-bit per channel colour into `output`, which must be `bytes_per_pixel()` long
+    pub fn encode(self, red: u8, green: u8, blue: u8, output: &mut [u8]) {
+        match self {
+            PixelFormat::Luma8 => {
+                let luma = (red as u32 * 299 + green as u32 * 587 + blue as u32 * 114) / 1000;
+                output[0] = luma as u8;
+            }
+            PixelFormat::Rgb565 => {
+                let pixel =
+                    ((red as u16 >> 3) << 11) | ((green as u16 >> 2) << 5) | (blue as u16 >> 3);
+                output.copy_from_slice(&pixel.to_le_bytes());
+            }
+            PixelFormat::Rgb888 => output.copy_from_slice(&[red, green, blue]),
+            PixelFormat::Rgba8888 => output.copy_from_slice(&[red, green, blue, 0xFF]),
+            PixelFormat::Bgra8888 => output.copy_from_slice(&[blue, green, red, 0xFF]),
+        }
+    }
+}
+
+/// The resolution and pixel format of video sent to a sink
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct VideoMode {
+    /// The number of pixels in each scanline
+    pub width: u32,
+    /// The number of scanlines in each frame
+    pub height: u32,
+    /// The layout of each pixel
+    pub format: PixelFormat,
+}
+
+impl VideoMode {
+    /// Construct a new video mode
+    pub const fn new(width: u32, height: u32, format: PixelFormat) -> Self {
+        Self {
+            width,
+            height,
+            format,
+        }
+    }
+
+    /// Returns the number of bytes in each scanline
+    pub const fn scanline_size(&self) -> usize {
+        self.width as usize * self.format.bytes_per_pixel()
+    }
+
+    /// Returns the number of bytes in each frame
+    pub const fn frame_size(&self) -> usize {
+        self.scanline_size() * self.height as usize
+    }
+}
+
+/// A destination for the video output of an emulated device, such as a window or a recording
+///
+/// Before sending video, the device negotiates a mode with the sink by requesting the
+/// resolution and pixel format that it produces.  The sink returns the mode that it will
+/// accept, which always has the requested resolution, but may have a different pixel format
+/// that the device must then render in, such as with `PixelFormat::encode()`.  The device can
+/// renegotiate whenever its resolution changes.
+///
+/// Video can either be submitted one scanline at a time, as it would be generated by a CRT
+/// controller, or as a complete frame.  Each is submitted with the time it was generated, so
+/// that the sink can pace its output to match the emulated time
+pub trait VideoSink {
+    /// The type for the time that video is generated at
+    type Instant: EmuInstant;
+
+    /// The type of an error returned by this sink
+    type Error: ErrorType;
+
+    /// Request the given mode, and return the mode the sink will accept
+    fn negotiate(&mut self, requested: VideoMode) -> Result<VideoMode, Self::Error>;
+
+    /// Returns the mode that was last negotiated, if any
+    fn mode(&self) -> Option<VideoMode>;
+
+    /// Submit a single scanline, where line 0 is the top of the frame
+    ///
+    /// The frame is complete when the last scanline of the frame is submitted
+    fn submit_scanline(
+        &mut self,
+        now: Self::Instant,
+        line: u32,
+        data: &[u8],
+    ) -> Result<(), Self::Error>;
+
+    /// Submit a complete frame, with the scanlines in order from top to bottom
+    ///
+    /// The default implementation submits each scanline in turn
+    fn submit_frame(&mut self, now: Self::Instant, data: &[u8]) -> Result<(), Self::Error> {
+        let size = self
+            .mode()
+            .map(|mode| mode.scanline_size())
+            .unwrap_or(data.len())
+            .max(1);
+        for (line, scanline) in data.chunks(size).enumerate() {
+            self.submit_scanline(now, line as u32, scanline)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T> VideoSink for &mut T
+where
+    T: VideoSink + ?Sized,
+{
+    type Instant = T::Instant;
+    type Error = T::Error;
+
+    #[inline]
+    fn negotiate(&mut self, requested: VideoMode) -> Result<VideoMode, Self::Error> {
+        T::negotiate(self, requested)
+    }
+
+    #[inline]
+    fn mode(&self) -> Option<VideoMode> {
+        T::mode(self)
+    }
+
+    #[inline]
+    fn submit_scanline(
+        &mut self,
+        now: Self::Instant,
+        line: u32,
+        data: &[u8],
+    ) -> Result<(), Self::Error> {
+        T::submit_scanline(self, now, line, data)
+    }
+
+    #[inline]
+    fn submit_frame(&mut self, now: Self::Instant, data: &[u8]) -> Result<(), Self::Error> {
+        T::submit_frame(self, now, data)
+    }
+}
@@ -0,0 +1,122 @@
+//! A generic GDB architecture built from the `Registers` trait
+
+use core::fmt;
+use core::marker::PhantomData;
+
+use emulator_hal::{ByteOrder, RegisterInfo, RegisterKind};
+use gdbstub::arch::{Arch, Registers as GdbRegisters};
+use gdbstub::internal::{BeBytes, LeBytes};
+use num_traits::{FromPrimitive, PrimInt, Unsigned};
+
+/// An unsigned integer type that can be used as an address by GDB
+pub trait GdbUsize:
+    fmt::Debug + Default + FromPrimitive + PrimInt + Unsigned + BeBytes + LeBytes + Into<u64>
+{
+}
+
+impl<T> GdbUsize for T where
+    T: fmt::Debug + Default + FromPrimitive + PrimInt + Unsigned + BeBytes + LeBytes + Into<u64>
+{
+}
+
+/// Describes a CPU architecture to GDB
+///
+/// The registers themselves are described at runtime by the CPU's `Registers` implementation,
+/// so this only needs the details GDB can't get from there
+pub trait GdbArch: 'static {
+    /// The type of an address, which must match the `Address` type of the CPU
+    type Usize: GdbUsize;
+
+    /// The byte order that register values are sent to GDB in
+    const BYTE_ORDER: ByteOrder;
+
+    /// The name GDB uses for this architecture, such as `"m68k"` or `"riscv:rv32"`, if GDB
+    /// knows the architecture
+    const ARCHITECTURE: Option<&'static str> = None;
+}
+
+/// A `gdbstub` architecture for the CPU described by `A`
+pub struct HalArch<A>(PhantomData<A>);
+
+impl<A> Arch for HalArch<A>
+where
+    A: GdbArch,
+{
+    type Usize = A::Usize;
+    type Registers = RegisterBlock<A::Usize>;
+    type BreakpointKind = usize;
+    type RegId = ();
+}
+
+/// The values of all the registers of a CPU, in the format sent to GDB
+///
+/// The registers are stored in the order given by `Registers::registers()`, each using the
+/// number of bytes given in its `RegisterInfo`
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RegisterBlock<Usize> {
+    pc: Usize,
+    bytes: Vec<u8>,
+}
+
+impl<Usize> RegisterBlock<Usize> {
+    /// Returns the serialized register values
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub(crate) fn set(&mut self, pc: Usize, bytes: Vec<u8>) {
+        self.pc = pc;
+        self.bytes = bytes;
+    }
+}
+
+impl<Usize> GdbRegisters for RegisterBlock<Usize>
+where
+    Usize: GdbUsize,
+{
+    type ProgramCounter = Usize;
+
+    fn pc(&self) -> Self::ProgramCounter {
+        self.pc
+    }
+
+    fn gdb_serialize(&self, mut write_byte: impl FnMut(Option<u8>)) {
+        for byte in self.bytes.iter() {
+            write_byte(Some(*byte));
+        }
+    }
+
+    fn gdb_deserialize(&mut self, bytes: &[u8]) -> Result<(), ()> {
+        self.bytes = bytes.to_vec();
+        Ok(())
+    }
+}
+
+/// Returns a GDB target description of the given registers
+pub fn target_description<RegisterId>(
+    architecture: Option<&str>,
+    registers: &[RegisterInfo<RegisterId>],
+) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\"?><!DOCTYPE target SYSTEM \"gdb-target.dtd\"><target version=\"1.0\">",
+    );
+    if let Some(architecture) = architecture {
+        xml.push_str(&format!("<architecture>{}</architecture>", architecture));
+    }
+    xml.push_str("<feature name=\"org.emulator-hal.cpu\">");
+    for info in registers {
+        let kind = match info.kind {
+            RegisterKind::ProgramCounter => "code_ptr",
+            RegisterKind::StackPointer | RegisterKind::FramePointer => "data_ptr",
+            _ => "int",
+        };
+        xml.push_str(&format!(
+            "<reg name=\"{}\" bitsize=\"{}\" type=\"{}\"/>",
+            info.name,
+            info.size * 8,
+            kind
+        ));
+    }
+    xml.push_str("</feature></target>");
+    xml
+}
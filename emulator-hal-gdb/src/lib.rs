@@ -0,0 +1,8 @@
+#![doc = include_str!("../README.md")]
+#![warn(missing_docs)]
+
+mod arch;
+pub use crate::arch::*;
+
+mod target;
+pub use crate::target::*;
@@ -0,0 +1,609 @@
+//! A `gdbstub` target for any CPU implementing the emulator-hal traits
+
+use core::convert::Infallible;
+use core::marker::PhantomData;
+
+use emulator_hal::StopReason as HalStopReason;
+use emulator_hal::{BusAccess, ByteOrder, Debug, RegisterValue, Registers, Step};
+use gdbstub::common::Signal;
+use gdbstub::conn::ConnectionExt;
+use gdbstub::stub::run_blocking::{BlockingEventLoop, Event, WaitForStopReasonError};
+use gdbstub::stub::{DisconnectReason, GdbStub, GdbStubError, SingleThreadStopReason};
+use gdbstub::target::ext::base::singlethread::{
+    SingleThreadBase, SingleThreadResume, SingleThreadResumeOps, SingleThreadSingleStep,
+    SingleThreadSingleStepOps,
+};
+use gdbstub::target::ext::base::BaseOps;
+use gdbstub::target::ext::breakpoints::{
+    Breakpoints, BreakpointsOps, SwBreakpoint, SwBreakpointOps,
+};
+use gdbstub::target::ext::target_description_xml_override::{
+    TargetDescriptionXmlOverride, TargetDescriptionXmlOverrideOps,
+};
+use gdbstub::target::{Target, TargetError, TargetResult};
+
+use crate::arch::{target_description, GdbArch, HalArch, RegisterBlock};
+
+/// The number of instructions executed between checks for a message from GDB while running
+const GDB_POLL_INTERVAL: u32 = 1024;
+
+/// The stop reason reported to GDB
+pub type StopReason<A> = SingleThreadStopReason<<A as GdbArch>::Usize>;
+
+/// A GDB target which debugs `cpu`, running it on `bus`
+///
+/// Registers are accessed through the CPU's `Registers` implementation, and are described to
+/// GDB with a target description generated from `Registers::registers()`.  Memory is accessed
+/// with `BusAccess::peek` and `BusAccess::poke`, so that the debugger doesn't trigger side
+/// effects in devices.  Breakpoints are kept by the target and checked against
+/// `Debug::get_execution_address()` after each step, so the CPU doesn't need to support them.
+///
+/// If the CPU returns an error from `step()`, the error is stored in `last_error` and reported
+/// to GDB as a `SIGABRT`, so that the CPU state can still be inspected.  A CPU that has stopped
+/// running is reported as terminated
+pub struct GdbTarget<A, Cpu, Bus>
+where
+    A: GdbArch,
+    Bus: BusAccess<A::Usize>,
+    Cpu: Debug<A::Usize, Bus, String> + Registers,
+{
+    /// The CPU being debugged
+    pub cpu: Cpu,
+    /// The bus the CPU is running on
+    pub bus: Bus,
+    /// The last error returned by the CPU's `step()`, if any
+    pub last_error: Option<<Cpu as Step<A::Usize, Bus>>::Error>,
+    now: Bus::Instant,
+    breakpoints: Vec<A::Usize>,
+    single_step: bool,
+    arch: PhantomData<A>,
+}
+
+impl<A, Cpu, Bus> GdbTarget<A, Cpu, Bus>
+where
+    A: GdbArch,
+    Bus: BusAccess<A::Usize>,
+    Cpu: Debug<A::Usize, Bus, String> + Registers,
+{
+    /// Construct a new target for the given CPU and bus, with the CPU's next step at `now`
+    pub fn new(cpu: Cpu, bus: Bus, now: Bus::Instant) -> Self {
+        Self {
+            cpu,
+            bus,
+            last_error: None,
+            now,
+            breakpoints: Vec::new(),
+            single_step: false,
+            arch: PhantomData,
+        }
+    }
+
+    /// Returns the time of the CPU's next step
+    pub fn now(&self) -> Bus::Instant {
+        self.now
+    }
+
+    /// Returns the addresses of the breakpoints set by GDB
+    pub fn breakpoints(&self) -> &[A::Usize] {
+        &self.breakpoints
+    }
+
+    /// Run a debugging session over the given connection, such as a `TcpStream`, until GDB
+    /// disconnects
+    pub fn serve<Conn>(
+        &mut self,
+        conn: Conn,
+    ) -> Result<DisconnectReason, GdbStubError<Infallible, Conn::Error>>
+    where
+        Conn: ConnectionExt,
+    {
+        GdbStub::new(conn).run_blocking::<GdbEventLoop<A, Cpu, Bus, Conn>>(self)
+    }
+
+    /// Run the CPU until it stops, or until `incoming()` returns true, in which case `None` is
+    /// returned
+    fn run(&mut self, mut incoming: impl FnMut() -> bool) -> Option<StopReason<A>> {
+        if self.single_step {
+            self.single_step = false;
+            return Some(match self.step_cpu() {
+                Ok(()) => SingleThreadStopReason::DoneStep,
+                Err(reason) => reason,
+            });
+        }
+
+        let mut count: u32 = 0;
+        loop {
+            if let Err(reason) = self.step_cpu() {
+                return Some(reason);
+            }
+            if let Ok(address) = self.cpu.get_execution_address() {
+                if self.breakpoints.contains(&address) {
+                    return Some(SingleThreadStopReason::SwBreak(()));
+                }
+            }
+            count = count.wrapping_add(1);
+            if count % GDB_POLL_INTERVAL == 0 && incoming() {
+                return None;
+            }
+        }
+    }
+
+    fn step_cpu(&mut self) -> Result<(), StopReason<A>> {
+        if !self.cpu.is_running() {
+            return Err(SingleThreadStopReason::Terminated(Signal::SIGSTOP));
+        }
+        match self.cpu.step(self.now, &mut self.bus) {
+            Ok(next) => {
+                self.now = next;
+                match self.cpu.take_stop_reason() {
+                    None => Ok(()),
+                    Some(HalStopReason::Breakpoint(_)) => Err(SingleThreadStopReason::SwBreak(())),
+                    Some(HalStopReason::Halted) => {
+                        Err(SingleThreadStopReason::Terminated(Signal::SIGSTOP))
+                    }
+                    Some(_) => Err(SingleThreadStopReason::Signal(Signal::SIGTRAP)),
+                }
+            }
+            Err(err) => {
+                self.last_error = Some(err);
+                Err(SingleThreadStopReason::Signal(Signal::SIGABRT))
+            }
+        }
+    }
+
+    fn register_layout(&self) -> Vec<(Cpu::RegisterId, usize)> {
+        self.cpu
+            .registers()
+            .iter()
+            .map(|info| (info.id, info.size))
+            .collect()
+    }
+}
+
+impl<A, Cpu, Bus> Target for GdbTarget<A, Cpu, Bus>
+where
+    A: GdbArch,
+    Bus: BusAccess<A::Usize>,
+    Cpu: Debug<A::Usize, Bus, String> + Registers,
+{
+    type Arch = HalArch<A>;
+    type Error = Infallible;
+
+    #[inline(always)]
+    fn base_ops(&mut self) -> BaseOps<'_, Self::Arch, Self::Error> {
+        BaseOps::SingleThread(self)
+    }
+
+    #[inline(always)]
+    fn support_breakpoints(&mut self) -> Option<BreakpointsOps<'_, Self>> {
+        Some(self)
+    }
+
+    #[inline(always)]
+    fn support_target_description_xml_override(
+        &mut self,
+    ) -> Option<TargetDescriptionXmlOverrideOps<'_, Self>> {
+        Some(self)
+    }
+}
+
+impl<A, Cpu, Bus> SingleThreadBase for GdbTarget<A, Cpu, Bus>
+where
+    A: GdbArch,
+    Bus: BusAccess<A::Usize>,
+    Cpu: Debug<A::Usize, Bus, String> + Registers,
+{
+    fn read_registers(&mut self, regs: &mut RegisterBlock<A::Usize>) -> TargetResult<(), Self> {
+        let mut bytes = Vec::new();
+        for (id, size) in self.register_layout() {
+            let value = self
+                .cpu
+                .read_register(id)
+                .map_err(|_| TargetError::NonFatal)?
+                .as_u64();
+            match A::BYTE_ORDER {
+                ByteOrder::Big => bytes.extend_from_slice(&value.to_be_bytes()[8 - size..]),
+                ByteOrder::Little => bytes.extend_from_slice(&value.to_le_bytes()[..size]),
+            }
+        }
+        let pc = self
+            .cpu
+            .get_execution_address()
+            .map_err(|_| TargetError::NonFatal)?;
+        regs.set(pc, bytes);
+        Ok(())
+    }
+
+    fn write_registers(&mut self, regs: &RegisterBlock<A::Usize>) -> TargetResult<(), Self> {
+        let mut bytes = regs.bytes();
+        for (id, size) in self.register_layout() {
+            if bytes.len() < size {
+                return Err(TargetError::NonFatal);
+            }
+            let (data, rest) = bytes.split_at(size);
+            bytes = rest;
+
+            let mut value = [0; 8];
+            let value = match A::BYTE_ORDER {
+                ByteOrder::Big => {
+                    value[8 - size..].copy_from_slice(data);
+                    u64::from_be_bytes(value)
+                }
+                ByteOrder::Little => {
+                    value[..size].copy_from_slice(data);
+                    u64::from_le_bytes(value)
+                }
+            };
+            let value = RegisterValue::from_u64(size, value).ok_or(TargetError::NonFatal)?;
+            self.cpu
+                .write_register(id, value)
+                .map_err(|_| TargetError::NonFatal)?;
+        }
+        Ok(())
+    }
+
+    fn read_addrs(&mut self, start_addr: A::Usize, data: &mut [u8]) -> TargetResult<usize, Self> {
+        self.bus
+            .peek(self.now, start_addr, data)
+            .map_err(|_| TargetError::NonFatal)
+    }
+
+    fn write_addrs(&mut self, start_addr: A::Usize, data: &[u8]) -> TargetResult<(), Self> {
+        self.bus
+            .poke(self.now, start_addr, data)
+            .map_err(|_| TargetError::NonFatal)?;
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn support_resume(&mut self) -> Option<SingleThreadResumeOps<'_, Self>> {
+        Some(self)
+    }
+}
+
+impl<A, Cpu, Bus> SingleThreadResume for GdbTarget<A, Cpu, Bus>
+where
+    A: GdbArch,
+    Bus: BusAccess<A::Usize>,
+    Cpu: Debug<A::Usize, Bus, String> + Registers,
+{
+    fn resume(&mut self, _signal: Option<Signal>) -> Result<(), Self::Error> {
+        self.single_step = false;
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn support_single_step(&mut self) -> Option<SingleThreadSingleStepOps<'_, Self>> {
+        Some(self)
+    }
+}
+
+impl<A, Cpu, Bus> SingleThreadSingleStep for GdbTarget<A, Cpu, Bus>
+where
+    A: GdbArch,
+    Bus: BusAccess<A::Usize>,
+    Cpu: Debug<A::Usize, Bus, String> + Registers,
+{
+    fn step(&mut self, _signal: Option<Signal>) -> Result<(), Self::Error> {
+        self.single_step = true;
+        Ok(())
+    }
+}
+
+impl<A, Cpu, Bus> Breakpoints for GdbTarget<A, Cpu, Bus>
+where
+    A: GdbArch,
+    Bus: BusAccess<A::Usize>,
+    Cpu: Debug<A::Usize, Bus, String> + Registers,
+{
+    #[inline(always)]
+    fn support_sw_breakpoint(&mut self) -> Option<SwBreakpointOps<'_, Self>> {
+        Some(self)
+    }
+}
+
+impl<A, Cpu, Bus> SwBreakpoint for GdbTarget<A, Cpu, Bus>
+where
+    A: GdbArch,
+    Bus: BusAccess<A::Usize>,
+    Cpu: Debug<A::Usize, Bus, String> + Registers,
+{
+    fn add_sw_breakpoint(&mut self, addr: A::Usize, _kind: usize) -> TargetResult<bool, Self> {
+        if !self.breakpoints.contains(&addr) {
+            self.breakpoints.push(addr);
+        }
+        Ok(true)
+    }
+
+    fn remove_sw_breakpoint(&mut self, addr: A::Usize, _kind: usize) -> TargetResult<bool, Self> {
+        match self
+            .breakpoints
+            .iter()
+            .position(|breakpoint| *breakpoint == addr)
+        {
+            Some(index) => {
+                self.breakpoints.remove(index);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+impl<A, Cpu, Bus> TargetDescriptionXmlOverride for GdbTarget<A, Cpu, Bus>
+where
+    A: GdbArch,
+    Bus: BusAccess<A::Usize>,
+    Cpu: Debug<A::Usize, Bus, String> + Registers,
+{
+    fn target_description_xml(
+        &self,
+        annex: &[u8],
+        offset: u64,
+        length: usize,
+        buf: &mut [u8],
+    ) -> TargetResult<usize, Self> {
+        if annex != b"target.xml" {
+            return Err(TargetError::NonFatal);
+        }
+
+        let xml = target_description(A::ARCHITECTURE, self.cpu.registers());
+        let xml = xml.as_bytes();
+        let start = (offset as usize).min(xml.len());
+        let end = start.saturating_add(length).min(xml.len());
+        let count = (end - start).min(buf.len());
+        buf[..count].copy_from_slice(&xml[start..start + count]);
+        Ok(count)
+    }
+}
+
+/// The event loop used by `GdbTarget::serve`, which runs the CPU in the same thread as the
+/// connection, and checks for messages from GDB periodically while running
+pub struct GdbEventLoop<A, Cpu, Bus, Conn>(PhantomData<(A, Cpu, Bus, Conn)>);
+
+impl<A, Cpu, Bus, Conn> BlockingEventLoop for GdbEventLoop<A, Cpu, Bus, Conn>
+where
+    A: GdbArch,
+    Bus: BusAccess<A::Usize>,
+    Cpu: Debug<A::Usize, Bus, String> + Registers,
+    Conn: ConnectionExt,
+{
+    type Target = GdbTarget<A, Cpu, Bus>;
+    type Connection = Conn;
+    type StopReason = StopReason<A>;
+
+    fn wait_for_stop_reason(
+        target: &mut Self::Target,
+        conn: &mut Self::Connection,
+    ) -> Result<Event<Self::StopReason>, WaitForStopReasonError<Infallible, Conn::Error>> {
+        let incoming = || conn.peek().map(|byte| byte.is_some()).unwrap_or(true);
+        match target.run(incoming) {
+            Some(reason) => Ok(Event::TargetStopped(reason)),
+            None => {
+                let byte = conn.read().map_err(WaitForStopReasonError::Connection)?;
+                Ok(Event::IncomingData(byte))
+            }
+        }
+    }
+
+    fn on_interrupt(
+        _target: &mut Self::Target,
+    ) -> Result<Option<Self::StopReason>, <Self::Target as Target>::Error> {
+        Ok(Some(SingleThreadStopReason::Signal(Signal::SIGINT)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use emulator_hal::BusAccessExt;
+    use emulator_hal::{BasicBusError, Inspect, RegisterInfo, RegisterKind};
+    use gdbstub::arch::Registers as GdbRegisters;
+    use std::fmt;
+    use std::time::Duration;
+
+    struct TestArch;
+
+    impl GdbArch for TestArch {
+        type Usize = u16;
+        const BYTE_ORDER: ByteOrder = ByteOrder::Big;
+    }
+
+    struct Memory(Vec<u8>);
+
+    impl BusAccess<u16> for Memory {
+        type Instant = Duration;
+        type Error = BasicBusError;
+
+        fn read(
+            &mut self,
+            _now: Duration,
+            addr: u16,
+            data: &mut [u8],
+        ) -> Result<usize, Self::Error> {
+            let addr = addr as usize;
+            let source = self
+                .0
+                .get(addr..addr + data.len())
+                .ok_or(BasicBusError::UnmappedAddress)?;
+            data.copy_from_slice(source);
+            Ok(data.len())
+        }
+
+        fn write(&mut self, _now: Duration, addr: u16, data: &[u8]) -> Result<usize, Self::Error> {
+            let addr = addr as usize;
+            self.0[addr..addr + data.len()].copy_from_slice(data);
+            Ok(data.len())
+        }
+    }
+
+    /// A CPU which adds each byte it fetches to its accumulator, and halts on 0xFF
+    #[derive(Default)]
+    struct Cpu {
+        acc: u8,
+        pc: u16,
+        halted: bool,
+    }
+
+    const REGISTERS: &[RegisterInfo<u8>] = &[
+        RegisterInfo {
+            id: 0,
+            name: "acc",
+            size: 1,
+            kind: RegisterKind::General,
+        },
+        RegisterInfo {
+            id: 1,
+            name: "pc",
+            size: 2,
+            kind: RegisterKind::ProgramCounter,
+        },
+    ];
+
+    impl Step<u16, Memory> for Cpu {
+        type Error = BasicBusError;
+
+        fn is_running(&mut self) -> bool {
+            !self.halted
+        }
+
+        fn reset(&mut self, _now: Duration, _bus: &mut Memory) -> Result<(), Self::Error> {
+            *self = Cpu::default();
+            Ok(())
+        }
+
+        fn step(&mut self, now: Duration, bus: &mut Memory) -> Result<Duration, Self::Error> {
+            let byte = bus.read_u8(now, self.pc)?;
+            self.pc += 1;
+            if byte == 0xFF {
+                self.halted = true;
+            } else {
+                self.acc = self.acc.wrapping_add(byte);
+            }
+            Ok(now + Duration::from_micros(1))
+        }
+    }
+
+    impl Inspect<u16, Memory, String> for Cpu {
+        type InfoType = ();
+        type Error = fmt::Error;
+
+        fn inspect(&mut self, _info: (), _bus: &mut Memory, _writer: &mut String) -> fmt::Result {
+            Ok(())
+        }
+
+        fn brief_summary(&mut self, _bus: &mut Memory, _writer: &mut String) -> fmt::Result {
+            Ok(())
+        }
+
+        fn detailed_summary(&mut self, _bus: &mut Memory, _writer: &mut String) -> fmt::Result {
+            Ok(())
+        }
+    }
+
+    impl Debug<u16, Memory, String> for Cpu {
+        type DebugError = ();
+
+        fn get_execution_address(&mut self) -> Result<u16, ()> {
+            Ok(self.pc)
+        }
+
+        fn set_execution_address(&mut self, address: u16) -> Result<(), ()> {
+            self.pc = address;
+            Ok(())
+        }
+
+        fn add_breakpoint(&mut self, _address: u16) {}
+        fn remove_breakpoint(&mut self, _address: u16) {}
+        fn clear_breakpoints(&mut self) {}
+    }
+
+    impl Registers for Cpu {
+        type RegisterId = u8;
+        type Error = ();
+
+        fn registers(&self) -> &[RegisterInfo<u8>] {
+            REGISTERS
+        }
+
+        fn read_register(&mut self, id: u8) -> Result<RegisterValue, ()> {
+            match id {
+                0 => Ok(self.acc.into()),
+                1 => Ok(self.pc.into()),
+                _ => Err(()),
+            }
+        }
+
+        fn write_register(&mut self, id: u8, value: RegisterValue) -> Result<(), ()> {
+            match (id, value) {
+                (0, RegisterValue::U8(value)) => self.acc = value,
+                (1, RegisterValue::U16(value)) => self.pc = value,
+                _ => return Err(()),
+            }
+            Ok(())
+        }
+    }
+
+    fn target(program: &[u8]) -> GdbTarget<TestArch, Cpu, Memory> {
+        let mut memory = vec![0; 0x100];
+        memory[..program.len()].copy_from_slice(program);
+        GdbTarget::new(Cpu::default(), Memory(memory), Duration::ZERO)
+    }
+
+    #[test]
+    fn test_register_and_memory_access() {
+        let mut target = target(&[1, 2, 3]);
+        target.cpu.acc = 0x42;
+        target.cpu.pc = 0x1234;
+
+        let mut regs = RegisterBlock::default();
+        target.read_registers(&mut regs).ok().unwrap();
+        assert_eq!(regs.bytes(), &[0x42, 0x12, 0x34]);
+        assert_eq!(regs.pc(), 0x1234);
+
+        regs.gdb_deserialize(&[0x10, 0x00, 0x02]).unwrap();
+        target.write_registers(&regs).ok().unwrap();
+        assert_eq!((target.cpu.acc, target.cpu.pc), (0x10, 0x0002));
+
+        let mut data = [0; 3];
+        assert_eq!(target.read_addrs(0, &mut data).ok(), Some(3));
+        assert_eq!(data, [1, 2, 3]);
+        assert!(target.read_addrs(0xFFFF, &mut data).is_err());
+
+        let mut buf = [0; 512];
+        let count = target
+            .target_description_xml(b"target.xml", 0, buf.len(), &mut buf)
+            .ok()
+            .unwrap();
+        let xml = std::str::from_utf8(&buf[..count]).unwrap();
+        assert!(xml.contains("<reg name=\"pc\" bitsize=\"16\" type=\"code_ptr\"/>"));
+    }
+
+    #[test]
+    fn test_breakpoints_and_stepping() {
+        let mut target = target(&[1, 1, 1, 1, 0xFF]);
+
+        target.add_sw_breakpoint(3, 0).ok().unwrap();
+        SingleThreadResume::resume(&mut target, None).unwrap();
+        assert!(matches!(
+            target.run(|| false),
+            Some(SingleThreadStopReason::SwBreak(()))
+        ));
+        assert_eq!((target.cpu.acc, target.cpu.pc), (3, 3));
+
+        SingleThreadSingleStep::step(&mut target, None).unwrap();
+        assert!(matches!(
+            target.run(|| false),
+            Some(SingleThreadStopReason::DoneStep)
+        ));
+        assert_eq!(target.cpu.pc, 4);
+
+        target.remove_sw_breakpoint(3, 0).ok().unwrap();
+        SingleThreadResume::resume(&mut target, None).unwrap();
+        assert!(matches!(
+            target.run(|| false),
+            Some(SingleThreadStopReason::Terminated(Signal::SIGSTOP))
+        ));
+        assert_eq!(target.now(), Duration::from_micros(5));
+    }
+}
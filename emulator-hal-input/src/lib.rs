@@ -0,0 +1,11 @@
+#![doc = include_str!("../README.md")]
+#![warn(missing_docs)]
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+mod queue;
+pub use crate::queue::*;
+
+mod source;
+pub use crate::source::*;
@@ -0,0 +1,126 @@
+//! A queue of timestamped input events
+
+use alloc::collections::VecDeque;
+use core::convert::Infallible;
+
+use emulator_hal::Instant as EmuInstant;
+
+use crate::source::{InputEvent, InputSource};
+
+/// An input source which delivers events that have been pushed into it at their given times
+///
+/// A front-end can push events as they arrive from its window, stamped with the emulated time
+/// they should take effect, or a test can push a scripted sequence of key presses ahead of time.
+/// Events are delivered in the order they were pushed, once their time has been reached
+#[derive(Clone, Debug, Default)]
+pub struct InputQueue<Instant> {
+    events: VecDeque<(Instant, InputEvent)>,
+}
+
+impl<Instant> InputQueue<Instant>
+where
+    Instant: EmuInstant,
+{
+    /// Construct a new empty queue
+    pub fn new() -> Self {
+        Self {
+            events: VecDeque::new(),
+        }
+    }
+
+    /// Add an event to the end of the queue, to be delivered at or after time `at`
+    pub fn push(&mut self, at: Instant, event: InputEvent) {
+        self.events.push_back((at, event));
+    }
+
+    /// Returns the number of events that have not been delivered yet
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Returns true if all of the events have been delivered
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+impl<Instant> InputSource for InputQueue<Instant>
+where
+    Instant: EmuInstant,
+{
+    type Instant = Instant;
+    type Error = Infallible;
+
+    fn poll_event(&mut self, now: Self::Instant) -> Result<Option<InputEvent>, Self::Error> {
+        Ok(self.poll_timed_event(now)?.map(|(_, event)| event))
+    }
+
+    fn poll_timed_event(
+        &mut self,
+        now: Self::Instant,
+    ) -> Result<Option<(Self::Instant, InputEvent)>, Self::Error> {
+        match self.events.front() {
+            Some((at, _)) if *at <= now => Ok(self.events.pop_front()),
+            _ => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::source::{Axis, Button, KeyCode};
+    use std::time::Duration;
+
+    #[test]
+    fn test_events_delivered_in_time() {
+        let mut queue = InputQueue::new();
+        queue.push(Duration::from_millis(5), InputEvent::KeyDown(KeyCode(0x04)));
+        queue.push(Duration::from_millis(10), InputEvent::KeyUp(KeyCode(0x04)));
+
+        assert_eq!(queue.poll_event(Duration::from_millis(4)).unwrap(), None);
+        assert_eq!(
+            queue.poll_event(Duration::from_millis(12)).unwrap(),
+            Some(InputEvent::KeyDown(KeyCode(0x04)))
+        );
+        assert_eq!(
+            queue.poll_event(Duration::from_millis(12)).unwrap(),
+            Some(InputEvent::KeyUp(KeyCode(0x04)))
+        );
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_controller_events_keep_their_time() {
+        let mut queue = InputQueue::new();
+        let fire = Button {
+            controller: 0,
+            button: 1,
+        };
+        let stick_x = Axis {
+            controller: 0,
+            axis: 0,
+        };
+        queue.push(Duration::from_millis(3), InputEvent::ButtonDown(fire));
+        queue.push(
+            Duration::from_millis(7),
+            InputEvent::AxisMoved(stick_x, -0x4000),
+        );
+
+        assert_eq!(
+            queue.poll_timed_event(Duration::from_millis(16)).unwrap(),
+            Some((Duration::from_millis(3), InputEvent::ButtonDown(fire)))
+        );
+        assert_eq!(
+            queue.poll_timed_event(Duration::from_millis(16)).unwrap(),
+            Some((
+                Duration::from_millis(7),
+                InputEvent::AxisMoved(stick_x, -0x4000)
+            ))
+        );
+        assert_eq!(
+            queue.poll_timed_event(Duration::from_millis(16)).unwrap(),
+            None
+        );
+    }
+}
@@ -0,0 +1,103 @@
+//! The interface between front-ends and emulated input devices
+
+use emulator_hal::{ErrorType, Instant as EmuInstant};
+
+/// Identifies a key on the host's keyboard
+///
+/// By convention, the code is the key's USB HID keyboard usage ID (for example, 0x04 for A and
+/// 0x28 for Return), which most windowing libraries can convert their key codes to.  Devices
+/// map these codes to the keys of the emulated keyboard, so the meaning only needs to agree
+/// between the front-end and the device's configuration
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct KeyCode(pub u16);
+
+/// Identifies a button on one of the host's game controllers
+///
+/// Controllers and their buttons are numbered by the front-end, such as in the order that its
+/// windowing library reports them, and devices map them to the buttons of the emulated
+/// joystick or joypad in their configuration
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Button {
+    /// The number of the controller that the button is on
+    pub controller: u8,
+    /// The number of the button on the controller
+    pub button: u8,
+}
+
+/// Identifies an analog axis, such as one direction of a thumbstick, on one of the host's game
+/// controllers
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Axis {
+    /// The number of the controller that the axis is on
+    pub controller: u8,
+    /// The number of the axis on the controller
+    pub axis: u8,
+}
+
+/// An event from a host input device
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum InputEvent {
+    /// A key was pressed
+    KeyDown(KeyCode),
+    /// A key was released
+    KeyUp(KeyCode),
+    /// A controller button was pressed
+    ButtonDown(Button),
+    /// A controller button was released
+    ButtonUp(Button),
+    /// A controller axis moved to the given position, where 0 is the centre, and negative values
+    /// are to the left or up
+    AxisMoved(Axis, i16),
+}
+
+/// A source of input events, which an emulated input controller polls for the events that have
+/// occurred on the host
+///
+/// Each poll is given the current time of the emulated device, so that sources can deliver
+/// events at the time they were recorded or scheduled.  Sources backed by a live window can
+/// ignore it and return events as soon as they arrive
+pub trait InputSource {
+    /// The type for the time that events occur at
+    type Instant: EmuInstant;
+
+    /// The type of an error returned by this source
+    type Error: ErrorType;
+
+    /// Returns the next event that has occurred by time `now`, or `None` if there are no more
+    fn poll_event(&mut self, now: Self::Instant) -> Result<Option<InputEvent>, Self::Error>;
+
+    /// Returns the next event that has occurred by time `now`, along with the time it occurred
+    ///
+    /// Devices that latch their inputs at particular times, such as a joypad that is read once
+    /// per frame, can use the time to apply the event at the right point.  The default
+    /// implementation calls `poll_event()` and returns `now` as the time of the event, for
+    /// sources which don't know when their events occurred
+    fn poll_timed_event(
+        &mut self,
+        now: Self::Instant,
+    ) -> Result<Option<(Self::Instant, InputEvent)>, Self::Error> {
+        Ok(self.poll_event(now)?.map(|event| (now, event)))
+    }
+}
+
+impl<T> InputSource for &mut T
+where
+    T: InputSource + ?Sized,
+{
+    type Instant = T::Instant;
+    type Error = T::Error;
+
+    #[inline]
+    fn poll_event(&mut self, now: Self::Instant) -> Result<Option<InputEvent>, Self::Error> {
+        T::poll_event(self, now)
+    }
+
+    #[inline]
+    fn poll_timed_event(
+        &mut self,
+        now: Self::Instant,
+    ) -> Result<Option<(Self::Instant, InputEvent)>, Self::Error> {
+        T::poll_timed_event(self, now)
+    }
+}
@@ -0,0 +1,8 @@
+#![doc = include_str!("../README.md")]
+#![warn(missing_docs)]
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+mod priority;
+pub use crate::priority::*;
@@ -0,0 +1,344 @@
+//! A priority interrupt controller with maskable level and edge triggered inputs
+
+use alloc::vec::Vec;
+use core::convert::Infallible;
+use core::marker::PhantomData;
+
+use emulator_hal::{
+    BasicBusError, BusAccess, Instant as EmuInstant, Interrupt, InterruptController, Signal,
+};
+
+/// The maximum number of inputs a `PriorityController` can have
+pub const INTC_MAX_INPUTS: usize = 32;
+
+/// The offset of the 32-bit pending register, in big endian order
+pub const INTC_PENDING: usize = 0;
+/// The offset of the 32-bit enable register, in big endian order
+pub const INTC_ENABLE: usize = 4;
+/// The offset of the current input register
+pub const INTC_CURRENT: usize = 8;
+
+/// The value of the current input register when no enabled input is pending
+pub const INTC_NONE: u8 = 0xFF;
+
+/// How an input of an interrupt controller detects a request
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Trigger {
+    /// The input is pending while its line is active
+    Level,
+    /// The input becomes pending when its line changes from inactive to active, and stays
+    /// pending until it's acknowledged or cleared, even if the line is lowered
+    Edge,
+}
+
+struct Input {
+    line: Signal,
+    interrupt: Interrupt,
+    trigger: Trigger,
+    vector: Option<u8>,
+    last: bool,
+}
+
+/// An interrupt controller which combines up to 32 interrupt lines into prioritized interrupt
+/// requests to a CPU
+///
+/// Each input is a `Signal` line, such as the `interrupt` output of a peripheral, and is
+/// assigned the `Interrupt` it requests, whether it's level or edge triggered, and optionally
+/// the vector it supplies when acknowledged.  Inputs without a vector use `base_vector` plus
+/// the input number.  When several inputs are pending, the one with the highest `Interrupt` is
+/// taken first, and inputs with the same `Interrupt` are taken in order of their input number.
+///
+/// The inputs are sampled whenever the controller is accessed, so an edge triggered input must
+/// stay active until the controller is next accessed, such as the next time the CPU checks for
+/// interrupts.  Inputs are numbered in the order they're added, with input 0 at bit 0 of the
+/// registers:
+///
+/// | Offset | Register |
+/// |--------|----------|
+/// | 0-3    | pending inputs, where writing a 1 clears a pending edge triggered input |
+/// | 4-7    | enabled inputs, which are all disabled at first |
+/// | 8      | the number of the highest priority enabled input that's pending, or `INTC_NONE` |
+///
+/// Inputs that request `Interrupt::Nmi` can't be masked, and are pending regardless of the
+/// enable register.  The `output` line is active while any enabled input is pending
+pub struct PriorityController<Instant> {
+    inputs: Vec<Input>,
+    latched: u32,
+    enabled: u32,
+    /// The vector supplied by input 0 if it doesn't have its own vector, which is incremented
+    /// for each following input
+    pub base_vector: u8,
+    /// The vector supplied when an interrupt is acknowledged, but no input is requesting it
+    pub spurious_vector: u8,
+    /// The output line, which is active while an interrupt is pending
+    pub output: Signal,
+    instant: PhantomData<Instant>,
+}
+
+impl<Instant> PriorityController<Instant> {
+    /// Construct a new interrupt controller without any inputs
+    pub fn new(base_vector: u8, spurious_vector: u8) -> Self {
+        Self {
+            inputs: Vec::new(),
+            latched: 0,
+            enabled: 0,
+            base_vector,
+            spurious_vector,
+            output: Signal::new(),
+            instant: PhantomData,
+        }
+    }
+
+    /// Add an input which requests `interrupt` when `line` is activated, and return its number
+    ///
+    /// # Panics
+    ///
+    /// Panics if the controller already has `INTC_MAX_INPUTS` inputs
+    pub fn add_input(
+        &mut self,
+        line: Signal,
+        interrupt: Interrupt,
+        trigger: Trigger,
+        vector: Option<u8>,
+    ) -> usize {
+        assert!(
+            self.inputs.len() < INTC_MAX_INPUTS,
+            "too many interrupt controller inputs"
+        );
+        self.inputs.push(Input {
+            line,
+            interrupt,
+            trigger,
+            vector,
+            last: false,
+        });
+        self.inputs.len() - 1
+    }
+
+    /// Returns the number of inputs
+    pub fn inputs(&self) -> usize {
+        self.inputs.len()
+    }
+
+    /// Sample the inputs, and return a bit mask of the inputs that are pending
+    fn sample(&mut self) -> u32 {
+        let mut pending = 0;
+        for (i, input) in self.inputs.iter_mut().enumerate() {
+            let active = input.line.get();
+            match input.trigger {
+                Trigger::Level if active => pending |= 1 << i,
+                Trigger::Level => {}
+                Trigger::Edge if active && !input.last => self.latched |= 1 << i,
+                Trigger::Edge => {}
+            }
+            input.last = active;
+        }
+        pending | self.latched
+    }
+
+    /// Returns the highest priority input that's pending and enabled, optionally only
+    /// considering inputs that request `interrupt`
+    fn highest(&self, pending: u32, interrupt: Option<Interrupt>) -> Option<usize> {
+        let mut highest: Option<usize> = None;
+        for (i, input) in self.inputs.iter().enumerate() {
+            let enabled = self.enabled & (1 << i) != 0 || input.interrupt == Interrupt::Nmi;
+            if pending & (1 << i) == 0 || !enabled {
+                continue;
+            }
+            if interrupt.map_or(false, |interrupt| interrupt != input.interrupt) {
+                continue;
+            }
+            if highest.map_or(true, |highest| {
+                input.interrupt > self.inputs[highest].interrupt
+            }) {
+                highest = Some(i);
+            }
+        }
+        self.output.set(highest.is_some());
+        highest
+    }
+
+    fn read_register(&mut self, register: usize) -> Result<u8, BasicBusError> {
+        let pending = self.sample();
+        match register {
+            0..=3 => Ok(pending.to_be_bytes()[register - INTC_PENDING]),
+            4..=7 => Ok(self.enabled.to_be_bytes()[register - INTC_ENABLE]),
+            INTC_CURRENT => Ok(self
+                .highest(pending, None)
+                .map_or(INTC_NONE, |input| input as u8)),
+            _ => Err(BasicBusError::UnmappedAddress),
+        }
+    }
+
+    fn write_register(&mut self, register: usize, value: u8) -> Result<(), BasicBusError> {
+        // sample first, so an edge that arrived before the write can be cleared by it
+        self.sample();
+        match register {
+            0..=3 => {
+                let shift = (3 - (register - INTC_PENDING)) * 8;
+                self.latched &= !((value as u32) << shift);
+            }
+            4..=7 => {
+                let mut bytes = self.enabled.to_be_bytes();
+                bytes[register - INTC_ENABLE] = value;
+                self.enabled = u32::from_be_bytes(bytes);
+            }
+            INTC_CURRENT => {}
+            _ => return Err(BasicBusError::UnmappedAddress),
+        }
+        let pending = self.sample();
+        self.highest(pending, None);
+        Ok(())
+    }
+}
+
+impl<Instant> InterruptController for PriorityController<Instant>
+where
+    Instant: EmuInstant,
+{
+    type Instant = Instant;
+    type Error = Infallible;
+
+    fn pending(&mut self, _now: Self::Instant) -> Option<Interrupt> {
+        let pending = self.sample();
+        self.highest(pending, None)
+            .map(|input| self.inputs[input].interrupt)
+    }
+
+    fn acknowledge(
+        &mut self,
+        _now: Self::Instant,
+        interrupt: Interrupt,
+    ) -> Result<u8, Self::Error> {
+        let pending = self.sample();
+        let vector = match self.highest(pending, Some(interrupt)) {
+            Some(input) => {
+                self.latched &= !(1 << input);
+                self.inputs[input]
+                    .vector
+                    .unwrap_or_else(|| self.base_vector.wrapping_add(input as u8))
+            }
+            None => self.spurious_vector,
+        };
+        let pending = self.sample();
+        self.highest(pending, None);
+        Ok(vector)
+    }
+}
+
+impl<Address, Instant> BusAccess<Address> for PriorityController<Instant>
+where
+    Address: TryInto<usize> + Copy,
+    Instant: EmuInstant,
+{
+    type Instant = Instant;
+    type Error = BasicBusError;
+
+    fn read(
+        &mut self,
+        _now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        let addr = addr
+            .try_into()
+            .map_err(|_| BasicBusError::UnmappedAddress)?;
+        for (i, byte) in data.iter_mut().enumerate() {
+            *byte = self.read_register(addr + i)?;
+        }
+        Ok(data.len())
+    }
+
+    fn write(
+        &mut self,
+        _now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        let addr = addr
+            .try_into()
+            .map_err(|_| BasicBusError::UnmappedAddress)?;
+        for (i, byte) in data.iter().enumerate() {
+            self.write_register(addr + i, *byte)?;
+        }
+        Ok(data.len())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use emulator_hal::BusAccessExt;
+    use std::time::Duration;
+
+    #[test]
+    fn test_priority_and_masking() {
+        let timer = Signal::new();
+        let uart = Signal::new();
+        let disk = Signal::new();
+        let mut intc = PriorityController::<Duration>::new(64, 24);
+        intc.add_input(timer.clone(), Interrupt::Irq(6), Trigger::Level, None);
+        intc.add_input(uart.clone(), Interrupt::Irq(4), Trigger::Level, Some(0x40));
+        intc.add_input(disk.clone(), Interrupt::Irq(4), Trigger::Level, None);
+        let now = Duration::ZERO;
+
+        uart.raise();
+        disk.raise();
+        assert_eq!(intc.pending(now), None);
+        assert_eq!(intc.read_beu32(now, INTC_PENDING).unwrap(), 0b110);
+        assert_eq!(intc.read_u8(now, INTC_CURRENT).unwrap(), INTC_NONE);
+
+        intc.write_beu32(now, INTC_ENABLE, 0b101).unwrap();
+        assert!(intc.output.get());
+        timer.raise();
+        assert_eq!(intc.pending(now), Some(Interrupt::Irq(6)));
+        assert_eq!(intc.acknowledge(now, Interrupt::Irq(6)), Ok(64));
+
+        // the uart is masked, so the disk is taken even though it has the same priority
+        timer.lower();
+        assert_eq!(intc.read_u8(now, INTC_CURRENT).unwrap(), 2);
+        assert_eq!(intc.acknowledge(now, Interrupt::Irq(4)), Ok(66));
+        intc.write_beu32(now, INTC_ENABLE, 0b111).unwrap();
+        assert_eq!(intc.acknowledge(now, Interrupt::Irq(4)), Ok(0x40));
+
+        uart.lower();
+        disk.lower();
+        assert_eq!(intc.pending(now), None);
+        assert!(!intc.output.get());
+        assert_eq!(intc.acknowledge(now, Interrupt::Irq(4)), Ok(24));
+    }
+
+    #[test]
+    fn test_edge_triggered_inputs() {
+        let vsync = Signal::new();
+        let reset = Signal::new();
+        let mut intc = PriorityController::<Duration>::new(0, 0xFF);
+        intc.add_input(vsync.clone(), Interrupt::Irq(2), Trigger::Edge, Some(0x10));
+        intc.add_input(reset.clone(), Interrupt::Nmi, Trigger::Edge, None);
+        intc.write_u8(Duration::ZERO, 7_u32, 0x01).unwrap();
+        let now = Duration::ZERO;
+
+        // a pulse stays pending after the line is lowered, until it's acknowledged
+        vsync.raise();
+        assert_eq!(intc.pending(now), Some(Interrupt::Irq(2)));
+        vsync.lower();
+        assert_eq!(intc.pending(now), Some(Interrupt::Irq(2)));
+        assert_eq!(intc.acknowledge(now, Interrupt::Irq(2)), Ok(0x10));
+        assert_eq!(intc.pending(now), None);
+
+        // holding the line active doesn't raise the interrupt again
+        vsync.raise();
+        intc.write_u8(now, 3_u32, 0x01).unwrap();
+        assert_eq!(intc.pending(now), None);
+
+        // the nmi is taken first, even though it isn't enabled
+        vsync.lower();
+        assert_eq!(intc.pending(now), None);
+        vsync.raise();
+        reset.raise();
+        assert_eq!(intc.pending(now), Some(Interrupt::Nmi));
+        assert_eq!(intc.acknowledge(now, Interrupt::Nmi), Ok(1));
+        assert_eq!(intc.pending(now), Some(Interrupt::Irq(2)));
+        assert!(intc.read_u8(now, 9_u32).is_err());
+    }
+}
@@ -0,0 +1,121 @@
+//! A bus that reads back the last values written to it
+
+use alloc::collections::BTreeMap;
+use core::marker::PhantomData;
+
+use emulator_hal::{BasicBusError, BusAccess, Instant as EmuInstant};
+
+/// A bus where every address reads back the last value written to it, or zero if it has never
+/// been written
+///
+/// The bus covers the whole address space without allocating memory up front, and only stores
+/// the bytes that have been written, so it can stand in for any device or memory when testing a
+/// controller that writes to a bus and reads the values back
+#[derive(Clone, Debug, Default)]
+pub struct EchoBus<Instant> {
+    values: BTreeMap<usize, u8>,
+    instant: PhantomData<Instant>,
+}
+
+impl<Instant> EchoBus<Instant> {
+    /// Returns a new echo bus with nothing written to it
+    pub fn new() -> Self {
+        Self {
+            values: BTreeMap::new(),
+            instant: PhantomData,
+        }
+    }
+
+    /// Returns the last value written to the given address, if it has been written
+    pub fn get(&self, addr: usize) -> Option<u8> {
+        self.values.get(&addr).copied()
+    }
+
+    /// Returns the number of distinct addresses that have been written
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns true if nothing has been written
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Forget all of the values that have been written
+    pub fn clear(&mut self) {
+        self.values.clear();
+    }
+}
+
+impl<Address, Instant> BusAccess<Address> for EchoBus<Instant>
+where
+    Address: TryInto<usize> + Copy,
+    Instant: EmuInstant,
+{
+    type Instant = Instant;
+    type Error = BasicBusError;
+
+    fn read(
+        &mut self,
+        _now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        let addr = addr
+            .try_into()
+            .map_err(|_| BasicBusError::UnmappedAddress)?;
+        for (i, byte) in data.iter_mut().enumerate() {
+            *byte = self.values.get(&addr.wrapping_add(i)).copied().unwrap_or(0);
+        }
+        Ok(data.len())
+    }
+
+    fn write(
+        &mut self,
+        _now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        let addr = addr
+            .try_into()
+            .map_err(|_| BasicBusError::UnmappedAddress)?;
+        for (i, byte) in data.iter().enumerate() {
+            self.values.insert(addr.wrapping_add(i), *byte);
+        }
+        Ok(data.len())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::NullBus;
+    use emulator_hal::BusAccessExt;
+    use std::time::Duration;
+
+    #[test]
+    fn test_echo_last_written() {
+        let mut bus = EchoBus::<Duration>::new();
+
+        bus.write_beu32(Duration::ZERO, 0xFFFF_FFF0_u32, 0x1234_5678)
+            .unwrap();
+        bus.write_u8(Duration::ZERO, 0xFFFF_FFF1_u32, 0xAA).unwrap();
+        assert_eq!(
+            bus.read_beu32(Duration::ZERO, 0xFFFF_FFF0_u32).unwrap(),
+            0x12AA_5678
+        );
+        assert_eq!(bus.read_leu16(Duration::ZERO, 0x10_u32).unwrap(), 0);
+        assert_eq!(bus.get(0xFFFF_FFF3), Some(0x78));
+        assert_eq!(bus.len(), 4);
+    }
+
+    #[test]
+    fn test_null_reads_zero() {
+        let mut bus = NullBus::<Duration>::new();
+        let mut data = [0xFF; 4];
+
+        assert_eq!(bus.write(Duration::ZERO, 0_u32, &[1, 2, 3]).unwrap(), 3);
+        assert_eq!(bus.read(Duration::ZERO, 0_u32, &mut data).unwrap(), 4);
+        assert_eq!(data, [0; 4]);
+    }
+}
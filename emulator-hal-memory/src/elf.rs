@@ -0,0 +1,296 @@
+//! Loading of program images in the ELF format
+
+use emulator_hal::{BusAccess, ByteOrder};
+
+use crate::ImageError;
+
+const ELF_MAGIC: &[u8] = b"\x7fELF";
+const CLASS_32: u8 = 1;
+const CLASS_64: u8 = 2;
+const DATA_LITTLE: u8 = 1;
+const DATA_BIG: u8 = 2;
+const PT_LOAD: u32 = 1;
+/// The number of zeros written at a time to fill the end of a segment
+const ZERO_CHUNK: usize = 256;
+
+/// Reads the fields of an ELF file in its byte order and word size
+struct ElfReader<'a> {
+    image: &'a [u8],
+    order: ByteOrder,
+    is_64: bool,
+}
+
+impl<'a> ElfReader<'a> {
+    fn new(image: &'a [u8]) -> Result<Self, ImageError> {
+        if image.get(..4) != Some(ELF_MAGIC) || image.len() < 0x34 {
+            return Err(ImageError::InvalidElf("not an ELF file"));
+        }
+        let is_64 = match image[4] {
+            CLASS_32 => false,
+            CLASS_64 => true,
+            _ => return Err(ImageError::InvalidElf("unknown class")),
+        };
+        let order = match image[5] {
+            DATA_LITTLE => ByteOrder::Little,
+            DATA_BIG => ByteOrder::Big,
+            _ => return Err(ImageError::InvalidElf("unknown byte order")),
+        };
+        Ok(Self {
+            image,
+            order,
+            is_64,
+        })
+    }
+
+    fn bytes<const N: usize>(&self, offset: usize) -> Result<[u8; N], ImageError> {
+        offset
+            .checked_add(N)
+            .and_then(|end| self.image.get(offset..end))
+            .map(|bytes| bytes.try_into().unwrap())
+            .ok_or(ImageError::InvalidElf("truncated file"))
+    }
+
+    fn u16(&self, offset: usize) -> Result<u16, ImageError> {
+        let bytes = self.bytes(offset)?;
+        Ok(match self.order {
+            ByteOrder::Little => u16::from_le_bytes(bytes),
+            ByteOrder::Big => u16::from_be_bytes(bytes),
+        })
+    }
+
+    fn u32(&self, offset: usize) -> Result<u32, ImageError> {
+        let bytes = self.bytes(offset)?;
+        Ok(match self.order {
+            ByteOrder::Little => u32::from_le_bytes(bytes),
+            ByteOrder::Big => u32::from_be_bytes(bytes),
+        })
+    }
+
+    /// Read an address or offset field, which is 32 or 64 bits depending on the class
+    fn word(&self, offset: usize) -> Result<u64, ImageError> {
+        if !self.is_64 {
+            return Ok(self.u32(offset)? as u64);
+        }
+        let bytes = self.bytes(offset)?;
+        Ok(match self.order {
+            ByteOrder::Little => u64::from_le_bytes(bytes),
+            ByteOrder::Big => u64::from_be_bytes(bytes),
+        })
+    }
+
+    /// Returns the contents of the file in the given range
+    fn slice(&self, offset: u64, len: u64) -> Result<&'a [u8], ImageError> {
+        usize::try_from(offset)
+            .ok()
+            .zip(usize::try_from(len).ok())
+            .and_then(|(offset, len)| self.image.get(offset..offset.checked_add(len)?))
+            .ok_or(ImageError::InvalidElf("segment outside of the file"))
+    }
+}
+
+/// Write the loadable segments of an ELF file into a bus object, and return the entry point
+///
+/// Each `PT_LOAD` segment is written at its physical address, which is the same as its virtual
+/// address for most bare-metal programs, and the part of the segment past the end of its data
+/// in the file, such as the `.bss` section, is filled with zeros.  The segments are written with
+/// `poke()`, so that they can be loaded into read-only memory.  If the bus returns an error or
+/// writes fewer bytes than requested, `ImageError::OutOfRange` is returned, and the segments
+/// before it will have already been written
+pub fn write_elf<Address, Bus>(
+    bus: &mut Bus,
+    now: Bus::Instant,
+    image: &[u8],
+) -> Result<u64, ImageError>
+where
+    Address: TryFrom<u64> + Copy,
+    Bus: BusAccess<Address>,
+{
+    let elf = ElfReader::new(image)?;
+    let (entry, phoff, phentsize, phnum) = if elf.is_64 {
+        (
+            elf.word(0x18)?,
+            elf.word(0x20)?,
+            elf.u16(0x36)?,
+            elf.u16(0x38)?,
+        )
+    } else {
+        (
+            elf.word(0x18)?,
+            elf.word(0x1C)?,
+            elf.u16(0x2A)?,
+            elf.u16(0x2C)?,
+        )
+    };
+
+    for i in 0..phnum as u64 {
+        let header = phoff
+            .checked_add(i * phentsize as u64)
+            .and_then(|header| usize::try_from(header).ok())
+            .ok_or(ImageError::InvalidElf("truncated file"))?;
+        if elf.u32(header)? != PT_LOAD {
+            continue;
+        }
+
+        let (offset, paddr, filesz, memsz) = if elf.is_64 {
+            (
+                elf.word(header + 8)?,
+                elf.word(header + 24)?,
+                elf.word(header + 32)?,
+                elf.word(header + 40)?,
+            )
+        } else {
+            (
+                elf.word(header + 4)?,
+                elf.word(header + 12)?,
+                elf.word(header + 16)?,
+                elf.word(header + 20)?,
+            )
+        };
+
+        write_segment(bus, now, paddr, elf.slice(offset, filesz)?)?;
+        if memsz > filesz {
+            let too_large = ImageError::InvalidElf("segment too large");
+            let mut addr = paddr.checked_add(filesz).ok_or(too_large.clone())?;
+            let end = paddr.checked_add(memsz).ok_or(too_large)?;
+            let zeros = [0; ZERO_CHUNK];
+            while addr < end {
+                let length = (end - addr).min(ZERO_CHUNK as u64) as usize;
+                write_segment(bus, now, addr, &zeros[..length])?;
+                addr += length as u64;
+            }
+        }
+    }
+    Ok(entry)
+}
+
+fn write_segment<Address, Bus>(
+    bus: &mut Bus,
+    now: Bus::Instant,
+    addr: u64,
+    data: &[u8],
+) -> Result<(), ImageError>
+where
+    Address: TryFrom<u64> + Copy,
+    Bus: BusAccess<Address>,
+{
+    if data.is_empty() {
+        return Ok(());
+    }
+    let out_of_range = ImageError::OutOfRange {
+        address: addr as usize,
+    };
+    let address = Address::try_from(addr).map_err(|_| out_of_range.clone())?;
+    match bus.poke(now, address, data) {
+        Ok(written) if written == data.len() => Ok(()),
+        _ => Err(out_of_range),
+    }
+}
+
+/// Load an ELF file into a bus object, as with `write_elf()`, and return the entry point
+///
+/// Errors in the contents of the file are returned with the kind `InvalidData`
+#[cfg(feature = "std")]
+pub fn load_elf<Address, Bus>(
+    bus: &mut Bus,
+    now: Bus::Instant,
+    filename: &str,
+) -> Result<u64, std::io::Error>
+where
+    Address: TryFrom<u64> + Copy,
+    Bus: BusAccess<Address>,
+{
+    let image = std::fs::read(filename)?;
+    Ok(write_elf(bus, now, &image)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::MemoryBlock;
+    use emulator_hal::Instant;
+    use std::time::Duration;
+
+    /// Build a 32-bit big endian ELF file for the 68000, with a text segment and a data segment
+    /// followed by bss
+    fn m68k_program() -> Vec<u8> {
+        let mut elf = vec![0; 0x34 + 3 * 0x20];
+        elf[..8].copy_from_slice(&[0x7F, b'E', b'L', b'F', CLASS_32, DATA_BIG, 1, 0]);
+        elf[0x12..0x14].copy_from_slice(&4_u16.to_be_bytes());
+        elf[0x18..0x1C].copy_from_slice(&0x400_u32.to_be_bytes());
+        elf[0x1C..0x20].copy_from_slice(&0x34_u32.to_be_bytes());
+        elf[0x2A..0x2C].copy_from_slice(&0x20_u16.to_be_bytes());
+        elf[0x2C..0x2E].copy_from_slice(&3_u16.to_be_bytes());
+
+        // the type, offset, vaddr, paddr, filesz, and memsz of each program header, with a note
+        // in between the two loadable segments
+        let headers = [
+            [PT_LOAD, 0x94, 0x400, 0x400, 4, 4],
+            [4, 0, 0, 0, 0, 0],
+            [PT_LOAD, 0x98, 0x800, 0x800, 2, 6],
+        ];
+        for (i, header) in headers.iter().enumerate() {
+            for (j, value) in header.iter().enumerate() {
+                let field = 0x34 + i * 0x20 + j * 4;
+                elf[field..field + 4].copy_from_slice(&value.to_be_bytes());
+            }
+        }
+        elf.extend_from_slice(&[0x4E, 0x71, 0x4E, 0x75, 0xAB, 0xCD]);
+        elf
+    }
+
+    #[test]
+    fn test_load_segments() {
+        let mut memory = MemoryBlock::<Duration>::from(vec![0xFF; 0x1000]);
+        memory.read_only();
+
+        let entry = write_elf::<u64, _>(&mut memory, Duration::START, &m68k_program()).unwrap();
+        assert_eq!(entry, 0x400);
+        assert_eq!(
+            memory.read_into_vec(0x400, 4).unwrap(),
+            [0x4E, 0x71, 0x4E, 0x75]
+        );
+        assert_eq!(
+            memory.read_into_vec(0x800, 8).unwrap(),
+            [0xAB, 0xCD, 0, 0, 0, 0, 0xFF, 0xFF]
+        );
+
+        let mut small = MemoryBlock::<Duration>::from(vec![0; 0x800]);
+        assert_eq!(
+            write_elf::<u64, _>(&mut small, Duration::START, &m68k_program()),
+            Err(ImageError::OutOfRange { address: 0x800 })
+        );
+
+        // the bss is filled a chunk at a time, until it runs past the end of memory
+        let mut program = m68k_program();
+        program[0x34 + 2 * 0x20 + 20..0x34 + 2 * 0x20 + 24]
+            .copy_from_slice(&u32::MAX.to_be_bytes());
+        let mut memory = MemoryBlock::<Duration>::from(vec![0xFF; 0x1000]);
+        assert_eq!(
+            write_elf::<u64, _>(&mut memory, Duration::START, &program),
+            Err(ImageError::OutOfRange { address: 0xF02 })
+        );
+        assert_eq!(memory.read_into_vec(0xF00, 4).unwrap(), [0, 0, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn test_invalid_elf() {
+        let mut memory = MemoryBlock::<Duration>::from(vec![0; 0x1000]);
+        let mut program = m68k_program();
+
+        assert_eq!(
+            write_elf::<u64, _>(&mut memory, Duration::START, b"#!/bin/sh\n"),
+            Err(ImageError::InvalidElf("not an ELF file"))
+        );
+        program[0x38] = 0xFF;
+        assert_eq!(
+            write_elf::<u64, _>(&mut memory, Duration::START, &program),
+            Err(ImageError::InvalidElf("segment outside of the file"))
+        );
+        let mut program = m68k_program();
+        program.truncate(0x40);
+        assert_eq!(
+            write_elf::<u64, _>(&mut memory, Duration::START, &program),
+            Err(ImageError::InvalidElf("truncated file"))
+        );
+    }
+}
@@ -0,0 +1,212 @@
+//! Loading of memory images in the Intel HEX format
+
+use alloc::vec::Vec;
+
+use crate::{ImageError, MemoryBlock};
+
+const RECORD_DATA: u8 = 0x00;
+const RECORD_END: u8 = 0x01;
+const RECORD_SEGMENT_ADDRESS: u8 = 0x02;
+const RECORD_START_SEGMENT: u8 = 0x03;
+const RECORD_LINEAR_ADDRESS: u8 = 0x04;
+const RECORD_START_LINEAR: u8 = 0x05;
+
+/// Parse the data records of an Intel HEX file, and call `data` with the address and contents
+/// of each one
+///
+/// Extended segment and linear address records change the base address of the records that
+/// follow them, start address records are ignored, and parsing stops at the end of file record
+fn parse_ihex<F>(text: &str, mut data: F) -> Result<(), ImageError>
+where
+    F: FnMut(usize, &[u8]) -> Result<(), ImageError>,
+{
+    let mut base = 0;
+    for (i, line) in text.lines().enumerate() {
+        let line_number = i + 1;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let record = decode_record(line).ok_or(ImageError::InvalidRecord { line: line_number })?;
+        if record
+            .iter()
+            .fold(0_u8, |sum, byte| sum.wrapping_add(*byte))
+            != 0
+        {
+            return Err(ImageError::Checksum { line: line_number });
+        }
+
+        let length = record[0] as usize;
+        let offset = u16::from_be_bytes([record[1], record[2]]) as usize;
+        let kind = record[3];
+        let contents = &record[4..record.len() - 1];
+        match kind {
+            RECORD_DATA => data(base + offset, contents)?,
+            RECORD_END => break,
+            RECORD_SEGMENT_ADDRESS | RECORD_LINEAR_ADDRESS if length == 2 => {
+                let value = u16::from_be_bytes([contents[0], contents[1]]) as usize;
+                base = if kind == RECORD_SEGMENT_ADDRESS {
+                    value << 4
+                } else {
+                    value << 16
+                };
+            }
+            RECORD_START_SEGMENT | RECORD_START_LINEAR if length == 4 => {}
+            RECORD_SEGMENT_ADDRESS
+            | RECORD_LINEAR_ADDRESS
+            | RECORD_START_SEGMENT
+            | RECORD_START_LINEAR => return Err(ImageError::InvalidRecord { line: line_number }),
+            _ => {
+                return Err(ImageError::UnsupportedRecord {
+                    line: line_number,
+                    kind,
+                })
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Decode the hex digits of a record into bytes, and check that its length is consistent
+fn decode_record(line: &str) -> Option<Vec<u8>> {
+    let digits = line.strip_prefix(':')?.as_bytes();
+    if digits.len() % 2 != 0 {
+        return None;
+    }
+    let record = digits
+        .chunks(2)
+        .map(|pair| {
+            let pair = core::str::from_utf8(pair).ok()?;
+            u8::from_str_radix(pair, 16).ok()
+        })
+        .collect::<Option<Vec<u8>>>()?;
+    if record.len() < 5 || record.len() != record[0] as usize + 5 {
+        return None;
+    }
+    Some(record)
+}
+
+impl<Instant> MemoryBlock<Instant> {
+    /// Construct a memory block from the contents of an Intel HEX file
+    ///
+    /// The block starts at address 0, and is sized to end at the last byte of data in the file.
+    /// Bytes not given by the file are set to 0.  Since a single record can be placed anywhere
+    /// in a 4 GiB address space, the block is limited to `max_size` bytes, and
+    /// `ImageError::OutOfRange` is returned for any data past it
+    pub fn from_ihex(text: &str, max_size: usize) -> Result<Self, ImageError> {
+        let mut memory = MemoryBlock::from(Vec::new());
+        parse_ihex(text, |addr, data| {
+            let end = addr + data.len();
+            if end > max_size {
+                return Err(ImageError::OutOfRange {
+                    address: addr.max(max_size),
+                });
+            }
+            if end > memory.len() {
+                memory.resize(end);
+            }
+            memory.contents[addr..end].copy_from_slice(data);
+            Ok(())
+        })?;
+        Ok(memory)
+    }
+}
+
+impl<Instant, Storage> MemoryBlock<Instant, Storage>
+where
+    Storage: AsRef<[u8]> + AsMut<[u8]>,
+{
+    /// Write the data in the contents of an Intel HEX file into this memory block, at the
+    /// addresses given in the file
+    ///
+    /// Data is written even if the block is read only.  If any data falls outside of the
+    /// block, `ImageError::OutOfRange` is returned, and the records before it will have already
+    /// been written
+    pub fn write_ihex(&mut self, text: &str) -> Result<(), ImageError> {
+        parse_ihex(text, |addr, data| {
+            let size = self.len();
+            self.slice_mut(addr, data.len())
+                .map_err(|_| ImageError::OutOfRange {
+                    address: addr.max(size),
+                })?
+                .copy_from_slice(data);
+            Ok(())
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl<Instant> MemoryBlock<Instant> {
+    /// Load an Intel HEX file into a new `MemoryBlock` of at most `max_size` bytes, as with
+    /// `from_ihex()`
+    ///
+    /// Errors in the contents of the file are returned with the kind `InvalidData`
+    pub fn load_ihex(filename: &str, max_size: usize) -> Result<Self, std::io::Error> {
+        let text = std::fs::read_to_string(filename)?;
+        Ok(MemoryBlock::from_ihex(&text, max_size)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use emulator_hal::{BusAccessExt, Instant};
+    use std::time::Duration;
+
+    const PROGRAM: &str = "\
+:0400100001020304E2
+:020000040001F9
+:03000000AABBCCCC
+:04000005000000CD2A
+:00000001FF
+";
+
+    #[test]
+    fn test_extended_linear_address() {
+        let mut memory = MemoryBlock::<Duration>::from_ihex(PROGRAM, 0x2_0000).unwrap();
+
+        assert_eq!(
+            memory.read_beu32(Duration::START, 0x0010_usize).unwrap(),
+            0x0102_0304
+        );
+        assert_eq!(
+            memory.read_into_vec(0x1_0000, 3).unwrap(),
+            [0xAA, 0xBB, 0xCC]
+        );
+        assert!(memory.read_u8(Duration::START, 0x1_0003_usize).is_err());
+
+        let mut small = MemoryBlock::<Duration>::from(vec![0; 0x100]);
+        assert_eq!(
+            small.write_ihex(PROGRAM),
+            Err(ImageError::OutOfRange { address: 0x1_0000 })
+        );
+        assert_eq!(small.read_into_vec(0x10, 4).unwrap(), [1, 2, 3, 4]);
+
+        // a block isn't grown past the maximum size, even if the file says so
+        assert_eq!(
+            MemoryBlock::<Duration>::from_ihex(PROGRAM, 0x1_0002).err(),
+            Some(ImageError::OutOfRange { address: 0x1_0002 })
+        );
+        let far = ":02000004FFFFFC\n:01000000AA55\n";
+        assert_eq!(
+            MemoryBlock::<Duration>::from_ihex(far, 0x1_0000).err(),
+            Some(ImageError::OutOfRange {
+                address: 0xFFFF_0000
+            })
+        );
+    }
+
+    #[test]
+    fn test_invalid_records() {
+        let result = MemoryBlock::<Duration>::from_ihex(":0400100001020304E3\n", 0x100);
+        assert_eq!(result.err(), Some(ImageError::Checksum { line: 1 }));
+        let result = MemoryBlock::<Duration>::from_ihex("\n:04001000010203E3\n", 0x100);
+        assert_eq!(result.err(), Some(ImageError::InvalidRecord { line: 2 }));
+        let result = MemoryBlock::<Duration>::from_ihex(":00000006FA\n", 0x100);
+        assert_eq!(
+            result.err(),
+            Some(ImageError::UnsupportedRecord { line: 1, kind: 6 })
+        );
+    }
+}
@@ -0,0 +1,60 @@
+//! Errors for loading memory images from object file formats
+
+use core::fmt;
+
+/// An error in the contents of a memory image, such as an Intel HEX or ELF file
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ImageError {
+    /// The record on the given line is malformed, such as having invalid hex digits or a length
+    /// that doesn't match its data
+    InvalidRecord {
+        /// The line number of the record, starting from 1
+        line: usize,
+    },
+    /// The checksum of the record on the given line doesn't match its contents
+    Checksum {
+        /// The line number of the record, starting from 1
+        line: usize,
+    },
+    /// The record on the given line has a type that isn't supported
+    UnsupportedRecord {
+        /// The line number of the record, starting from 1
+        line: usize,
+        /// The record type
+        kind: u8,
+    },
+    /// The ELF file is malformed or uses an unsupported format, for the given reason
+    InvalidElf(&'static str),
+    /// The image contains data at an address outside of the memory block
+    OutOfRange {
+        /// The address of the first byte that doesn't fit
+        address: usize,
+    },
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImageError::InvalidRecord { line } => write!(f, "invalid record on line {}", line),
+            ImageError::Checksum { line } => write!(f, "checksum mismatch on line {}", line),
+            ImageError::UnsupportedRecord { line, kind } => {
+                write!(f, "unsupported record type {} on line {}", kind, line)
+            }
+            ImageError::InvalidElf(reason) => write!(f, "invalid ELF file: {}", reason),
+            ImageError::OutOfRange { address } => {
+                write!(f, "data at {:#x} is outside of the memory block", address)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ImageError {}
+
+#[cfg(feature = "std")]
+impl From<ImageError> for std::io::Error {
+    fn from(err: ImageError) -> Self {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, err)
+    }
+}
@@ -2,38 +2,227 @@
 #![warn(missing_docs)]
 #![cfg_attr(all(not(feature = "std"), not(test)), no_std)]
 
+#[cfg(feature = "alloc")]
 extern crate alloc;
 
+#[cfg(feature = "alloc")]
+mod echo;
+#[cfg(feature = "alloc")]
+pub use crate::echo::*;
+
+#[cfg(feature = "alloc")]
+mod elf;
+#[cfg(feature = "alloc")]
+pub use crate::elf::*;
+
+#[cfg(feature = "alloc")]
+mod ihex;
+
+mod image;
+pub use crate::image::*;
+
+mod null;
+pub use crate::null::*;
+
+#[cfg(feature = "alloc")]
+mod srec;
+
+#[cfg(feature = "alloc")]
+mod shared;
+#[cfg(feature = "alloc")]
+pub use crate::shared::*;
+
+#[cfg(feature = "alloc")]
+use alloc::vec;
+#[cfg(feature = "alloc")]
 use alloc::vec::Vec;
 use core::marker::PhantomData;
+#[cfg(feature = "alloc")]
+use core::ops::Range;
+
+use emulator_hal::{
+    BasicBusError, BusAccess, Instant as EmuInstant, Snapshot, SnapshotError, StateReader,
+    StateWriter,
+};
 
-use emulator_hal::{BasicBusError, BusAccess, Instant as EmuInstant};
+/// The storage used by a `MemoryBlock` when none is given
+#[cfg(feature = "alloc")]
+type DefaultStorage = Vec<u8>;
+/// The storage used by a `MemoryBlock` when none is given
+#[cfg(not(feature = "alloc"))]
+type DefaultStorage = &'static mut [u8];
 
-/// A contiguous block of memory, backed by a `Vec`
-pub struct MemoryBlock<Instant> {
+/// A contiguous block of memory, backed by a `Vec` or any other storage of bytes
+///
+/// The storage can be anything that can be borrowed as a mutable byte slice, such as a
+/// `Vec<u8>`, `Box<[u8]>`, or `[u8; N]`, or a `&mut [u8]` borrowed from a buffer owned by the
+/// frontend, like a frame of shared memory, which avoids copying it into the block.  Without the
+/// `alloc` feature, the storage defaults to `&'static mut [u8]`, and dirty tracking isn't
+/// available.  Only blocks backed by a `Vec` can be resized
+pub struct MemoryBlock<Instant, Storage = DefaultStorage> {
     read_only: bool,
-    contents: Vec<u8>,
+    partial: bool,
+    contents: Storage,
+    #[cfg(feature = "alloc")]
+    page_size: Option<usize>,
+    #[cfg(feature = "alloc")]
+    dirty: Vec<bool>,
     instant: PhantomData<Instant>,
 }
 
-impl<Instant> MemoryBlock<Instant> {
-    /// Construct a memory block from a given `Vec`
-    pub fn from(contents: Vec<u8>) -> Self {
+impl<Instant, Storage> MemoryBlock<Instant, Storage>
+where
+    Storage: AsRef<[u8]> + AsMut<[u8]>,
+{
+    /// Construct a memory block from the given storage, such as a `Vec`
+    pub fn from(contents: Storage) -> Self {
         MemoryBlock {
             read_only: false,
+            partial: false,
             contents,
+            #[cfg(feature = "alloc")]
+            page_size: None,
+            #[cfg(feature = "alloc")]
+            dirty: Vec::new(),
             instant: PhantomData,
         }
     }
 
+    /// Returns the size of the block in bytes
+    pub fn len(&self) -> usize {
+        self.contents.as_ref().len()
+    }
+
+    /// Returns true if the block has a size of zero
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the storage of the block, such as to give a borrowed buffer back to its owner
+    pub fn into_inner(self) -> Storage {
+        self.contents
+    }
+
     /// Make this memory block read only
     pub fn read_only(&mut self) {
         self.read_only = true;
     }
 
+    /// Allow accesses that cross the end of this memory block to transfer the bytes that fit
+    ///
+    /// By default, any access that doesn't fit entirely inside the block returns an error.  With
+    /// partial accesses, `read` and `write` instead transfer the bytes up to the end of the
+    /// block, and return the smaller count, so the bus master can handle the short access.  An
+    /// access that starts past the end of the block still returns an error
+    pub fn partial_access(&mut self) {
+        self.partial = true;
+    }
+
+    /// Start tracking which pages of `page_size` bytes have been modified, with every page clean
+    ///
+    /// Every write, poke, or load marks the pages it touches as dirty, so that a frontend can
+    /// find the parts of the block that changed since it last called `clear_dirty()`, such as
+    /// for incremental save states or for uploading only the changed parts of video memory
+    ///
+    /// # Panics
+    ///
+    /// Panics if `page_size` is zero
+    #[cfg(feature = "alloc")]
+    pub fn track_dirty(&mut self, page_size: usize) {
+        assert!(page_size != 0, "page size must not be zero");
+        self.page_size = Some(page_size);
+        self.dirty = vec![false; (self.len() + page_size - 1) / page_size];
+    }
+
+    /// Returns the ranges of addresses in dirty pages, with adjacent dirty pages combined
+    ///
+    /// This is empty if dirty tracking hasn't been enabled with `track_dirty()`
+    #[cfg(feature = "alloc")]
+    pub fn dirty_ranges(&self) -> Vec<Range<usize>> {
+        let mut ranges: Vec<Range<usize>> = Vec::new();
+        let page_size = self.page_size.unwrap_or_default();
+        for (page, _) in self.dirty.iter().enumerate().filter(|(_, dirty)| **dirty) {
+            let start = page * page_size;
+            let end = (start + page_size).min(self.len());
+            match ranges.last_mut() {
+                Some(range) if range.end == start => range.end = end,
+                _ => ranges.push(start..end),
+            }
+        }
+        ranges
+    }
+
+    /// Mark every page as clean
+    #[cfg(feature = "alloc")]
+    pub fn clear_dirty(&mut self) {
+        self.dirty.fill(false);
+    }
+
+    /// Returns a copy of `len` bytes starting at `addr`, as a new `Vec`
+    ///
+    /// This copies directly out of the block, without going through a zeroed buffer or the
+    /// generic `BusAccess` path, which makes it suited to large transfers like DMA
+    #[cfg(feature = "alloc")]
+    pub fn read_into_vec(&self, addr: usize, len: usize) -> Result<Vec<u8>, BasicBusError> {
+        Ok(self.slice(addr, len)?.to_vec())
+    }
+
+    /// Write all of `data` starting at `addr`, checking the bounds once for the whole transfer
+    ///
+    /// Like `write`, the data is discarded if the block is read only
+    pub fn write_all_at(&mut self, addr: usize, data: &[u8]) -> Result<(), BasicBusError> {
+        if self.read_only {
+            self.slice(addr, data.len())?;
+        } else {
+            self.slice_mut(addr, data.len())?.copy_from_slice(data);
+        }
+        Ok(())
+    }
+
+    /// Returns the number of bytes of an access of `len` bytes at `addr` that should be
+    /// transferred, which is limited to the end of the block if partial accesses are allowed
+    #[inline]
+    fn access_len(&self, addr: usize, len: usize) -> usize {
+        if self.partial && addr < self.len() {
+            len.min(self.len() - addr)
+        } else {
+            len
+        }
+    }
+
+    #[inline]
+    fn slice(&self, addr: usize, len: usize) -> Result<&[u8], BasicBusError> {
+        addr.checked_add(len)
+            .and_then(|end| self.contents.as_ref().get(addr..end))
+            .ok_or(BasicBusError::UnmappedAddress)
+    }
+
+    /// Returns the given range of the block to be modified, and marks its pages as dirty
+    #[inline]
+    fn slice_mut(&mut self, addr: usize, len: usize) -> Result<&mut [u8], BasicBusError> {
+        let end = addr
+            .checked_add(len)
+            .filter(|end| *end <= self.len())
+            .ok_or(BasicBusError::UnmappedAddress)?;
+        #[cfg(feature = "alloc")]
+        if let Some(page_size) = self.page_size.filter(|_| len != 0) {
+            self.dirty[addr / page_size..=(end - 1) / page_size].fill(true);
+        }
+        Ok(&mut self.contents.as_mut()[addr..end])
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<Instant> MemoryBlock<Instant, Vec<u8>> {
     /// Resize the underlying `Vec` to be the given `newsize`
+    ///
+    /// If dirty tracking is enabled, any pages added are marked as dirty
     pub fn resize(&mut self, new_size: usize) {
         self.contents.resize(new_size, 0);
+        if let Some(page_size) = self.page_size {
+            self.dirty
+                .resize((new_size + page_size - 1) / page_size, true);
+        }
     }
 }
 
@@ -41,7 +230,7 @@ impl<Instant> MemoryBlock<Instant> {
 use std::io;
 
 #[cfg(feature = "std")]
-impl<Instant> MemoryBlock<Instant> {
+impl<Instant> MemoryBlock<Instant, Vec<u8>> {
     /// Load the binary contents of a file into a new `MemoryBlock`
     ///
     /// The resulting `MemoryBlock` will be sized to exactly the length of the file
@@ -49,21 +238,36 @@ impl<Instant> MemoryBlock<Instant> {
         let contents = std::fs::read(filename)?;
         Ok(MemoryBlock::from(contents))
     }
+}
 
+#[cfg(feature = "std")]
+impl<Instant, Storage> MemoryBlock<Instant, Storage>
+where
+    Storage: AsRef<[u8]> + AsMut<[u8]>,
+{
     /// Load the binary contents of a file into an existing `MemoryBlock` at the given address
     ///
-    /// The `MemoryBlock` must already be big enough to contain the contents of the file
+    /// The `MemoryBlock` must already be big enough to contain the contents of the file, or an
+    /// error of kind `InvalidInput` is returned and the block is left unchanged
     pub fn load_at(&mut self, addr: usize, filename: &str) -> Result<(), io::Error> {
         let contents = std::fs::read(filename)?;
-        self.contents[addr..addr + contents.len()].copy_from_slice(&contents);
+        self.slice_mut(addr, contents.len())
+            .map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "file extends past the end of the memory block",
+                )
+            })?
+            .copy_from_slice(&contents);
         Ok(())
     }
 }
 
-impl<Address, Instant> BusAccess<Address> for MemoryBlock<Instant>
+impl<Address, Instant, Storage> BusAccess<Address> for MemoryBlock<Instant, Storage>
 where
     Address: TryInto<usize> + Copy,
     Instant: EmuInstant,
+    Storage: AsRef<[u8]> + AsMut<[u8]>,
 {
     type Instant = Instant;
     type Error = BasicBusError;
@@ -77,13 +281,9 @@ where
         let addr = addr
             .try_into()
             .map_err(|_| BasicBusError::UnmappedAddress)?;
-
-        if addr + data.len() > self.contents.len() {
-            return Err(BasicBusError::UnmappedAddress);
-        }
-
-        data.copy_from_slice(&self.contents[addr..addr + data.len()]);
-        Ok(data.len())
+        let len = self.access_len(addr, data.len());
+        data[..len].copy_from_slice(self.slice(addr, len)?);
+        Ok(len)
     }
 
     fn write(&mut self, _now: Instant, addr: Address, data: &[u8]) -> Result<usize, Self::Error> {
@@ -94,13 +294,39 @@ where
         let addr = addr
             .try_into()
             .map_err(|_| BasicBusError::UnmappedAddress)?;
+        let len = self.access_len(addr, data.len());
+        self.slice_mut(addr, len)?.copy_from_slice(&data[..len]);
+        Ok(len)
+    }
 
-        if addr + data.len() > self.contents.len() {
-            return Err(BasicBusError::UnmappedAddress);
-        }
+    /// Write into the block even if it's read only, such as for loading a program into ROM
+    fn poke(&mut self, _now: Instant, addr: Address, data: &[u8]) -> Result<usize, Self::Error> {
+        let addr = addr
+            .try_into()
+            .map_err(|_| BasicBusError::UnmappedAddress)?;
+        let len = self.access_len(addr, data.len());
+        self.slice_mut(addr, len)?.copy_from_slice(&data[..len]);
+        Ok(len)
+    }
+}
 
-        self.contents[addr..addr + data.len()].copy_from_slice(data);
-        Ok(data.len())
+/// Saves the contents of the block, which must be restored into a block of the same size
+impl<Instant, Storage> Snapshot for MemoryBlock<Instant, Storage>
+where
+    Storage: AsRef<[u8]> + AsMut<[u8]>,
+{
+    fn save_state(&self, writer: &mut dyn StateWriter) -> Result<(), SnapshotError> {
+        writer.write_u64(self.len() as u64)?;
+        writer.write_bytes(self.contents.as_ref())
+    }
+
+    fn load_state(&mut self, reader: &mut dyn StateReader) -> Result<(), SnapshotError> {
+        if reader.read_u64()? != self.len() as u64 {
+            return Err(SnapshotError::InvalidData);
+        }
+        #[cfg(feature = "alloc")]
+        self.dirty.fill(true);
+        reader.read_bytes(self.contents.as_mut())
     }
 }
 
@@ -108,20 +334,12 @@ where
 mod tests {
     use super::*;
     use alloc::vec;
+    use emulator_hal::BusAccessExt;
     use emulator_hal::Instant;
     use std::time::Duration;
 
     #[test]
     fn test_memory_block() {
-        #[derive(Clone, Debug, PartialEq, Eq)]
-        struct SimpleError();
-
-        impl From<core::num::TryFromIntError> for SimpleError {
-            fn from(_: core::num::TryFromIntError) -> Self {
-                SimpleError()
-            }
-        }
-
         let mut memory = MemoryBlock::<Duration>::from(vec![0; 1024]);
 
         let number = 0x1234_5678;
@@ -129,4 +347,161 @@ mod tests {
         let result = memory.read_leu32(Duration::START, 0).unwrap();
         assert_eq!(result, number);
     }
+
+    #[test]
+    fn test_other_storage() {
+        let mut buffer = [0; 16];
+        let mut memory = MemoryBlock::<Duration, &mut [u8]>::from(&mut buffer[..]);
+        memory.write_beu16(Duration::START, 2, 0x1234).unwrap();
+        assert!(memory.read_u8(Duration::START, 16).is_err());
+        drop(memory);
+        assert_eq!(&buffer[..4], &[0, 0, 0x12, 0x34]);
+
+        let mut memory = MemoryBlock::<Duration, _>::from([0xFF; 4]);
+        memory.read_only();
+        assert!(matches!(
+            memory.write_u8(Duration::START, 0, 0),
+            Err(BasicBusError::ShortTransfer(0))
+        ));
+        assert_eq!(memory.len(), 4);
+        assert_eq!(memory.into_inner(), [0xFF; 4]);
+
+        let mut memory = MemoryBlock::<Duration, _>::from(vec![0; 8].into_boxed_slice());
+        memory.write_all_at(4, &[1, 2, 3, 4]).unwrap();
+        assert_eq!(memory.read_beu32(Duration::START, 4).unwrap(), 0x0102_0304);
+    }
+
+    #[test]
+    fn test_access_past_end_of_address_space() {
+        let mut memory = MemoryBlock::<Duration>::from(vec![0; 1024]);
+
+        let result = memory.read_leu32(Duration::START, u64::MAX);
+        assert!(matches!(result, Err(BasicBusError::UnmappedAddress)));
+        let result = memory.write_leu32(Duration::START, usize::MAX - 1, 0);
+        assert!(matches!(result, Err(BasicBusError::UnmappedAddress)));
+    }
+
+    #[test]
+    fn test_partial_access() {
+        let mut memory = MemoryBlock::<Duration>::from((0..8).collect());
+        let mut data = [0xFF; 4];
+
+        assert!(memory.read(Duration::START, 6_usize, &mut data).is_err());
+        memory.partial_access();
+        assert_eq!(memory.read(Duration::START, 6_usize, &mut data).unwrap(), 2);
+        assert_eq!(data, [6, 7, 0xFF, 0xFF]);
+        assert_eq!(
+            memory.write(Duration::START, 7_usize, &[1, 2, 3]).unwrap(),
+            1
+        );
+        assert_eq!(memory.read_into_vec(4, 4).unwrap(), [4, 5, 6, 1]);
+        assert!(memory.read(Duration::START, 8_usize, &mut data).is_err());
+    }
+
+    #[test]
+    fn test_dirty_pages() {
+        let mut memory = MemoryBlock::<Duration>::from(vec![0; 0x500]);
+        memory.write_u8(Duration::START, 0x10_usize, 1).unwrap();
+        assert!(memory.dirty_ranges().is_empty());
+
+        memory.track_dirty(0x100);
+        memory
+            .write_beu32(Duration::START, 0x1FE_usize, 0x1234_5678)
+            .unwrap();
+        memory.poke(Duration::START, 0x480_usize, &[1]).unwrap();
+        assert!(memory.read_into_vec(0x300, 0x100).is_ok());
+        assert_eq!(memory.dirty_ranges(), [0x100..0x300, 0x400..0x500]);
+
+        memory.clear_dirty();
+        memory.read_only();
+        memory.write_all_at(0x000, &[1, 2]).unwrap();
+        assert!(memory.dirty_ranges().is_empty());
+        memory.resize(0x580);
+        assert_eq!(memory.dirty_ranges(), vec![0x500..0x580]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_load_past_end() {
+        let path = std::env::temp_dir().join(format!("emulator-hal-load-{}", std::process::id()));
+        std::fs::write(&path, [0xAA; 16]).unwrap();
+        let filename = path.to_str().unwrap();
+        let mut memory = MemoryBlock::<Duration>::from(vec![0; 32]);
+
+        memory.load_at(16, filename).unwrap();
+        let err = memory.load_at(17, filename).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(memory.load_at(usize::MAX, filename).is_err());
+        assert_eq!(memory.read_into_vec(16, 16).unwrap(), [0xAA; 16]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_bulk_transfers() {
+        let mut memory = MemoryBlock::<Duration>::from(vec![0; 0x1000]);
+        let data: Vec<u8> = (0..0x800).map(|i| i as u8).collect();
+
+        memory.write_all_at(0x400, &data).unwrap();
+        assert_eq!(memory.read_into_vec(0x400, 0x800).unwrap(), data);
+        assert_eq!(memory.read_u8(Duration::START, 0x401_usize).unwrap(), 1);
+
+        assert!(memory.write_all_at(0x801, &data).is_err());
+        assert!(memory.read_into_vec(usize::MAX, 2).is_err());
+        assert_eq!(memory.read_into_vec(0xC00, 0x400).unwrap().len(), 0x400);
+
+        memory.read_only();
+        memory.write_all_at(0, &[0xFF; 4]).unwrap();
+        assert_eq!(memory.read_into_vec(0, 4).unwrap(), [0; 4]);
+    }
+
+    /// A saved state which is read back from the start
+    struct State(Vec<u8>);
+
+    impl StateWriter for State {
+        fn write_bytes(&mut self, data: &[u8]) -> Result<(), SnapshotError> {
+            self.0.extend_from_slice(data);
+            Ok(())
+        }
+    }
+
+    impl StateReader for State {
+        fn version(&self) -> u32 {
+            0
+        }
+
+        fn read_bytes(&mut self, data: &mut [u8]) -> Result<(), SnapshotError> {
+            if data.len() > self.0.len() {
+                return Err(SnapshotError::UnexpectedEnd);
+            }
+            data.copy_from_slice(&self.0[..data.len()]);
+            self.0.drain(..data.len());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_save_and_load_state() {
+        let mut memory = MemoryBlock::<Duration>::from(vec![0; 64]);
+        memory
+            .write_beu32(Duration::START, 8_usize, 0x1234_5678)
+            .unwrap();
+
+        let mut state = State(Vec::new());
+        memory.save_state(&mut state).unwrap();
+        memory.write_beu32(Duration::START, 8_usize, 0).unwrap();
+        memory.load_state(&mut state).unwrap();
+        assert_eq!(
+            memory.read_beu32(Duration::START, 8_usize).unwrap(),
+            0x1234_5678
+        );
+
+        let mut state = State(Vec::new());
+        memory.save_state(&mut state).unwrap();
+        let mut smaller = MemoryBlock::<Duration>::from(vec![0; 32]);
+        assert!(matches!(
+            smaller.load_state(&mut state),
+            Err(SnapshotError::InvalidData)
+        ));
+    }
 }
@@ -0,0 +1,191 @@
+//! A memory block that can be mapped into more than one bus
+
+use alloc::rc::Rc;
+use core::cell::{Ref, RefCell, RefMut};
+
+use emulator_hal::{BasicBusError, BusAccess, Instant as EmuInstant, Transaction};
+
+use crate::{DefaultStorage, MemoryBlock};
+
+/// A handle to a `MemoryBlock` that is shared by every clone of the handle
+///
+/// Each clone can be mapped into a different bus, to model dual-ported memory, such as video
+/// memory that is accessed by both the CPU and the video chip, or mailbox memory between two
+/// CPUs.  A write through one handle is seen by reads through all of the others.  The block is
+/// only borrowed for the duration of each access, so the handles can be used in any order, but
+/// only from a single thread
+///
+/// # Panics
+///
+/// Accesses panic if the block is already borrowed, such as by holding the result of
+/// `borrow_mut()` while accessing the block through another handle
+pub struct SharedMemoryBlock<Instant, Storage = DefaultStorage> {
+    block: Rc<RefCell<MemoryBlock<Instant, Storage>>>,
+}
+
+impl<Instant, Storage> Clone for SharedMemoryBlock<Instant, Storage> {
+    fn clone(&self) -> Self {
+        Self {
+            block: self.block.clone(),
+        }
+    }
+}
+
+impl<Instant, Storage> SharedMemoryBlock<Instant, Storage>
+where
+    Storage: AsRef<[u8]> + AsMut<[u8]>,
+{
+    /// Construct the first handle to the given block, which can then be cloned for each bus
+    pub fn new(block: MemoryBlock<Instant, Storage>) -> Self {
+        Self {
+            block: Rc::new(RefCell::new(block)),
+        }
+    }
+
+    /// Borrow the shared block, such as to read its contents without going through a bus
+    pub fn borrow(&self) -> Ref<'_, MemoryBlock<Instant, Storage>> {
+        self.block.borrow()
+    }
+
+    /// Mutably borrow the shared block, such as to load it or to check which pages are dirty
+    pub fn borrow_mut(&self) -> RefMut<'_, MemoryBlock<Instant, Storage>> {
+        self.block.borrow_mut()
+    }
+
+    /// Returns the number of handles to the shared block, including this one
+    pub fn handles(&self) -> usize {
+        Rc::strong_count(&self.block)
+    }
+
+    /// Returns true if both handles share the same block
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.block, &other.block)
+    }
+}
+
+impl<Address, Instant, Storage> BusAccess<Address> for SharedMemoryBlock<Instant, Storage>
+where
+    Address: TryInto<usize> + Copy,
+    Instant: EmuInstant,
+    Storage: AsRef<[u8]> + AsMut<[u8]>,
+{
+    type Instant = Instant;
+    type Error = BasicBusError;
+
+    #[inline]
+    fn read(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        self.block.borrow_mut().read(now, addr, data)
+    }
+
+    #[inline]
+    fn write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        self.block.borrow_mut().write(now, addr, data)
+    }
+
+    #[inline]
+    fn read_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        self.block
+            .borrow_mut()
+            .read_ext(now, addr, data, transaction)
+    }
+
+    #[inline]
+    fn write_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        self.block
+            .borrow_mut()
+            .write_ext(now, addr, data, transaction)
+    }
+
+    #[inline]
+    fn read_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        self.block.borrow_mut().read_timed(now, addr, data)
+    }
+
+    #[inline]
+    fn write_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        self.block.borrow_mut().write_timed(now, addr, data)
+    }
+
+    #[inline]
+    fn peek(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        self.block.borrow_mut().peek(now, addr, data)
+    }
+
+    #[inline]
+    fn poke(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        self.block.borrow_mut().poke(now, addr, data)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::vec;
+    use emulator_hal::{BusAccessExt, Instant};
+    use std::time::Duration;
+
+    #[test]
+    fn test_shared_between_ports() {
+        let mut cpu_port = SharedMemoryBlock::new(MemoryBlock::<Duration>::from(vec![0; 0x100]));
+        let mut video_port = cpu_port.clone();
+        assert_eq!(cpu_port.handles(), 2);
+        assert!(cpu_port.ptr_eq(&video_port));
+
+        cpu_port
+            .write_beu16(Duration::START, 0x10_u32, 0x1234)
+            .unwrap();
+        assert_eq!(
+            video_port.read_beu16(Duration::START, 0x10_u16).unwrap(),
+            0x1234
+        );
+
+        video_port.borrow_mut().read_only();
+        assert!(cpu_port.write_u8(Duration::START, 0x10_u32, 0).is_err());
+        assert_eq!(
+            cpu_port.borrow().read_into_vec(0x10, 2).unwrap(),
+            [0x12, 0x34]
+        );
+    }
+}
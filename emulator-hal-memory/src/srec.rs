@@ -0,0 +1,178 @@
+//! Loading of memory images in the Motorola S-record format
+
+use alloc::vec::Vec;
+
+use crate::{ImageError, MemoryBlock};
+
+/// Returns the number of address bytes used by the given record type, or `None` if the type
+/// isn't supported
+fn address_size(kind: u8) -> Option<usize> {
+    match kind {
+        0 | 1 | 5 | 9 => Some(2),
+        2 | 6 | 8 => Some(3),
+        3 | 7 => Some(4),
+        _ => None,
+    }
+}
+
+/// Parse the records of an S-record file, call `data` with the address and contents of each
+/// data record, and return the entry point from the termination record, if there is one
+///
+/// Header and count records are ignored, and parsing stops at the first termination record
+fn parse_srec<F>(text: &str, mut data: F) -> Result<Option<usize>, ImageError>
+where
+    F: FnMut(usize, &[u8]) -> Result<(), ImageError>,
+{
+    for (i, line) in text.lines().enumerate() {
+        let line_number = i + 1;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let (kind, record) =
+            decode_record(line).ok_or(ImageError::InvalidRecord { line: line_number })?;
+        if record
+            .iter()
+            .fold(0_u8, |sum, byte| sum.wrapping_add(*byte))
+            != 0xFF
+        {
+            return Err(ImageError::Checksum { line: line_number });
+        }
+
+        let size = address_size(kind).ok_or(ImageError::UnsupportedRecord {
+            line: line_number,
+            kind,
+        })?;
+        if record.len() < size + 2 {
+            return Err(ImageError::InvalidRecord { line: line_number });
+        }
+        let address = record[1..size + 1]
+            .iter()
+            .fold(0, |address, byte| address << 8 | *byte as usize);
+        let contents = &record[size + 1..record.len() - 1];
+        match kind {
+            1..=3 => data(address, contents)?,
+            7..=9 => return Ok(Some(address)),
+            _ => {}
+        }
+    }
+    Ok(None)
+}
+
+/// Decode the type and the hex digits of a record into bytes, and check that its length is
+/// consistent
+fn decode_record(line: &str) -> Option<(u8, Vec<u8>)> {
+    let line = line.strip_prefix('S')?;
+    let kind = line.get(..1)?.parse().ok()?;
+    let digits = line.get(1..)?.as_bytes();
+    if digits.len() % 2 != 0 {
+        return None;
+    }
+    let record = digits
+        .chunks(2)
+        .map(|pair| {
+            let pair = core::str::from_utf8(pair).ok()?;
+            u8::from_str_radix(pair, 16).ok()
+        })
+        .collect::<Option<Vec<u8>>>()?;
+    if record.len() < 2 || record.len() != record[0] as usize + 1 {
+        return None;
+    }
+    Some((kind, record))
+}
+
+impl<Instant, Storage> MemoryBlock<Instant, Storage>
+where
+    Storage: AsRef<[u8]> + AsMut<[u8]>,
+{
+    /// Write the data in the contents of a Motorola S-record file (S19, S28, or S37) into this
+    /// memory block, at the addresses given in the file, and return the entry point from the
+    /// S7, S8, or S9 record, if any
+    ///
+    /// Data is written even if the block is read only.  If any data falls outside of the
+    /// block, `ImageError::OutOfRange` is returned, and the records before it will have already
+    /// been written
+    pub fn write_srec(&mut self, text: &str) -> Result<Option<usize>, ImageError> {
+        parse_srec(text, |addr, data| {
+            let size = self.len();
+            self.slice_mut(addr, data.len())
+                .map_err(|_| ImageError::OutOfRange {
+                    address: addr.max(size),
+                })?
+                .copy_from_slice(data);
+            Ok(())
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl<Instant, Storage> MemoryBlock<Instant, Storage>
+where
+    Storage: AsRef<[u8]> + AsMut<[u8]>,
+{
+    /// Load a Motorola S-record file into this memory block, as with `write_srec()`, and return
+    /// the entry point
+    ///
+    /// Errors in the contents of the file are returned with the kind `InvalidData`
+    pub fn load_srec(&mut self, filename: &str) -> Result<Option<usize>, std::io::Error> {
+        let text = std::fs::read_to_string(filename)?;
+        Ok(self.write_srec(&text)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use emulator_hal::{BusAccessExt, Instant};
+    use std::time::Duration;
+
+    const PROGRAM: &str = "\
+S008000068656C6C6FE3
+S107001001020304DE
+S207010000AABBCCC6
+S30700000020DEAD4D
+S5030003F9
+S70500000400F6
+";
+
+    #[test]
+    fn test_load_records() {
+        let mut memory = MemoryBlock::<Duration>::from(vec![0; 0x1_0004]);
+        assert_eq!(memory.write_srec(PROGRAM), Ok(Some(0x400)));
+
+        assert_eq!(
+            memory.read_beu32(Duration::START, 0x0010_usize).unwrap(),
+            0x0102_0304
+        );
+        assert_eq!(memory.read_into_vec(0x20, 2).unwrap(), [0xDE, 0xAD]);
+        assert_eq!(
+            memory.read_into_vec(0x1_0000, 4).unwrap(),
+            [0xAA, 0xBB, 0xCC, 0x00]
+        );
+
+        let mut small = MemoryBlock::<Duration>::from(vec![0; 0x100]);
+        assert_eq!(
+            small.write_srec(PROGRAM),
+            Err(ImageError::OutOfRange { address: 0x1_0000 })
+        );
+        assert_eq!(small.write_srec("S9030400F8\n"), Ok(Some(0x400)));
+    }
+
+    #[test]
+    fn test_invalid_records() {
+        let mut memory = MemoryBlock::<Duration>::from(vec![0; 0x100]);
+        assert_eq!(
+            memory.write_srec("S107001001020304DF\n"),
+            Err(ImageError::Checksum { line: 1 })
+        );
+        assert_eq!(
+            memory.write_srec("\nS1070010010203DE\n"),
+            Err(ImageError::InvalidRecord { line: 2 })
+        );
+        assert_eq!(
+            memory.write_srec("S4030000FC\n"),
+            Err(ImageError::UnsupportedRecord { line: 1, kind: 4 })
+        );
+    }
+}
@@ -0,0 +1,78 @@
+//! The interface between emulated network controllers and the networks they're connected to
+
+use emulator_hal::{ErrorType, Instant as EmuInstant};
+
+/// The maximum size of an Ethernet frame, without the frame check sequence
+pub const MAX_FRAME_SIZE: usize = 1514;
+
+/// An error that occurred while using a network interface
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum NetError {
+    /// The frame is larger than the interface can send
+    FrameTooLarge(usize),
+    /// The buffer given is too small for the next received frame, which has the given size
+    BufferTooSmall(usize),
+    /// The capture file is not in a supported format
+    InvalidCapture,
+    /// An error occurred while accessing the host's interface or file
+    #[cfg(feature = "std")]
+    Io(std::io::Error),
+}
+
+impl ErrorType for NetError {}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for NetError {
+    fn from(err: std::io::Error) -> Self {
+        NetError::Io(err)
+    }
+}
+
+/// A connection to an Ethernet network, which an emulated network controller sends and receives
+/// frames through
+///
+/// Frames are complete Ethernet frames starting with the destination address, and without the
+/// preamble or frame check sequence.  Each operation is given the current time of the emulated
+/// device, so that interfaces can deliver frames at the time they would arrive, or record when
+/// they were sent.  Interfaces backed by the host's network can ignore it
+pub trait NetworkInterface {
+    /// The type for the time that frames are sent and received at
+    type Instant: EmuInstant;
+
+    /// The type of an error returned by this interface
+    type Error: ErrorType;
+
+    /// Send a frame at time `now`
+    fn send(&mut self, now: Self::Instant, frame: &[u8]) -> Result<(), Self::Error>;
+
+    /// Receive the next frame that has arrived by time `now` into `buffer`, and return its size,
+    /// or `None` if no frame has arrived
+    fn receive(
+        &mut self,
+        now: Self::Instant,
+        buffer: &mut [u8],
+    ) -> Result<Option<usize>, Self::Error>;
+}
+
+impl<T> NetworkInterface for &mut T
+where
+    T: NetworkInterface + ?Sized,
+{
+    type Instant = T::Instant;
+    type Error = T::Error;
+
+    #[inline]
+    fn send(&mut self, now: Self::Instant, frame: &[u8]) -> Result<(), Self::Error> {
+        T::send(self, now, frame)
+    }
+
+    #[inline]
+    fn receive(
+        &mut self,
+        now: Self::Instant,
+        buffer: &mut [u8],
+    ) -> Result<Option<usize>, Self::Error> {
+        T::receive(self, now, buffer)
+    }
+}
@@ -0,0 +1,16 @@
+#![doc = include_str!("../README.md")]
+#![warn(missing_docs)]
+#![cfg_attr(all(not(feature = "std"), not(test)), no_std)]
+
+mod interface;
+pub use crate::interface::*;
+
+#[cfg(feature = "std")]
+mod pcap;
+#[cfg(feature = "std")]
+pub use crate::pcap::*;
+
+#[cfg(all(feature = "std", target_os = "linux"))]
+mod tap;
+#[cfg(all(feature = "std", target_os = "linux"))]
+pub use crate::tap::*;
@@ -0,0 +1,227 @@
+//! Replay of frames from packet capture files
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use emulator_hal::Instant as EmuInstant;
+
+use crate::interface::{NetError, NetworkInterface};
+
+const MAGIC_MICROSECONDS: u32 = 0xa1b2_c3d4;
+const MAGIC_NANOSECONDS: u32 = 0xa1b2_3c4d;
+const LINKTYPE_ETHERNET: u32 = 1;
+
+/// A network interface which receives the frames stored in a pcap capture file
+///
+/// Each frame is received at its time in the capture relative to the first frame, starting
+/// from the `start` time given when the capture is loaded.  This can be used to test network
+/// controllers and the software running on them with a known sequence of traffic.  Frames that
+/// are sent are discarded, and can be inspected with `sent()`
+pub struct PcapReplay<Instant> {
+    frames: VecDeque<(Instant, Vec<u8>)>,
+    sent: Vec<Vec<u8>>,
+}
+
+impl<Instant> PcapReplay<Instant>
+where
+    Instant: EmuInstant,
+{
+    /// Load a capture from the given file
+    pub fn open<P: AsRef<Path>>(path: P, start: Instant) -> Result<Self, NetError> {
+        Self::new(BufReader::new(File::open(path)?), start)
+    }
+
+    /// Load a capture from the given reader, which must be an Ethernet capture in the classic
+    /// pcap format, with either microsecond or nanosecond timestamps
+    pub fn new<R: Read>(mut reader: R, start: Instant) -> Result<Self, NetError> {
+        let mut header = [0; 24];
+        reader.read_exact(&mut header)?;
+
+        let (big_endian, nanoseconds) = match u32::from_le_bytes(word(&header, 0)) {
+            MAGIC_MICROSECONDS => (false, false),
+            MAGIC_NANOSECONDS => (false, true),
+            magic if magic.swap_bytes() == MAGIC_MICROSECONDS => (true, false),
+            magic if magic.swap_bytes() == MAGIC_NANOSECONDS => (true, true),
+            _ => return Err(NetError::InvalidCapture),
+        };
+        let read_u32 = |data: &[u8], offset| {
+            if big_endian {
+                u32::from_be_bytes(word(data, offset))
+            } else {
+                u32::from_le_bytes(word(data, offset))
+            }
+        };
+        if read_u32(&header, 20) & 0x0FFF_FFFF != LINKTYPE_ETHERNET {
+            return Err(NetError::InvalidCapture);
+        }
+
+        let subsecond_hertz = if nanoseconds {
+            1_000_000_000
+        } else {
+            1_000_000
+        };
+        let mut first = None;
+        let mut frames = VecDeque::new();
+        let mut record = [0; 16];
+        while read_record_header(&mut reader, &mut record)? {
+            let timestamp = (read_u32(&record, 0) as u64, read_u32(&record, 4));
+            let mut frame = vec![0; read_u32(&record, 8) as usize];
+            reader.read_exact(&mut frame)?;
+
+            let (first_seconds, first_subseconds) = *first.get_or_insert(timestamp);
+            let mut ticks = (timestamp.0 * subsecond_hertz + timestamp.1 as u64)
+                .saturating_sub(first_seconds * subsecond_hertz + first_subseconds as u64);
+
+            // the duration type can only be multiplied by a u32, so add the offset in steps
+            let mut instant = start;
+            while ticks > 0 {
+                let step = ticks.min(u32::MAX as u64);
+                instant = instant + Instant::hertz_to_duration(subsecond_hertz) * step as u32;
+                ticks -= step;
+            }
+            frames.push_back((instant, frame));
+        }
+
+        Ok(Self {
+            frames,
+            sent: Vec::new(),
+        })
+    }
+
+    /// Returns the number of frames that have not been received yet
+    pub fn remaining(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Returns the frames that have been sent, in the order they were sent
+    pub fn sent(&self) -> &[Vec<u8>] {
+        &self.sent
+    }
+}
+
+fn word(data: &[u8], offset: usize) -> [u8; 4] {
+    [
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ]
+}
+
+/// Read the header of the next record, or return false at the end of the file
+fn read_record_header<R: Read>(reader: &mut R, record: &mut [u8; 16]) -> Result<bool, NetError> {
+    let mut filled = 0;
+    while filled < record.len() {
+        match reader.read(&mut record[filled..])? {
+            0 if filled == 0 => return Ok(false),
+            0 => return Err(NetError::InvalidCapture),
+            count => filled += count,
+        }
+    }
+    Ok(true)
+}
+
+impl<Instant> NetworkInterface for PcapReplay<Instant>
+where
+    Instant: EmuInstant,
+{
+    type Instant = Instant;
+    type Error = NetError;
+
+    fn send(&mut self, _now: Self::Instant, frame: &[u8]) -> Result<(), Self::Error> {
+        self.sent.push(frame.to_vec());
+        Ok(())
+    }
+
+    fn receive(
+        &mut self,
+        now: Self::Instant,
+        buffer: &mut [u8],
+    ) -> Result<Option<usize>, Self::Error> {
+        match self.frames.front() {
+            Some((arrival, frame)) if *arrival <= now => {
+                if frame.len() > buffer.len() {
+                    return Err(NetError::BufferTooSmall(frame.len()));
+                }
+                buffer[..frame.len()].copy_from_slice(frame);
+                let size = frame.len();
+                self.frames.pop_front();
+                Ok(Some(size))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    fn capture() -> Vec<u8> {
+        let mut data = Vec::new();
+        for value in [
+            MAGIC_MICROSECONDS,
+            0x0004_0002,
+            0,
+            0,
+            65535,
+            LINKTYPE_ETHERNET,
+        ] {
+            data.extend(value.to_le_bytes());
+        }
+        for (seconds, microseconds, frame) in [(10, 999_000, &[1, 2, 3][..]), (11, 1_000, &[4, 5])]
+        {
+            for value in [
+                seconds,
+                microseconds,
+                frame.len() as u32,
+                frame.len() as u32,
+            ] {
+                data.extend(value.to_le_bytes());
+            }
+            data.extend(frame);
+        }
+        data
+    }
+
+    #[test]
+    fn test_replay_at_capture_times() {
+        let start = Duration::from_secs(1);
+        let mut replay = PcapReplay::new(&capture()[..], start).unwrap();
+        assert_eq!(replay.remaining(), 2);
+
+        let mut buffer = [0; 16];
+        assert_eq!(replay.receive(start, &mut buffer).unwrap(), Some(3));
+        assert_eq!(buffer[..3], [1, 2, 3]);
+
+        let second = start + Duration::from_millis(2);
+        assert!(matches!(
+            replay.receive(second, &mut [0; 1]),
+            Err(NetError::BufferTooSmall(2))
+        ));
+        assert_eq!(
+            replay
+                .receive(second - Duration::from_nanos(1), &mut buffer)
+                .unwrap(),
+            None
+        );
+        assert_eq!(replay.receive(second, &mut buffer).unwrap(), Some(2));
+        assert_eq!(replay.receive(second, &mut buffer).unwrap(), None);
+
+        replay.send(second, &[0xFF; 14]).unwrap();
+        assert_eq!(replay.sent(), &[vec![0xFF; 14]]);
+    }
+
+    #[test]
+    fn test_reject_invalid_capture() {
+        let mut data = capture();
+        data[20] = 101;
+        assert!(matches!(
+            PcapReplay::new(&data[..], Duration::ZERO),
+            Err(NetError::InvalidCapture)
+        ));
+    }
+}
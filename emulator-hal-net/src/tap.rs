@@ -0,0 +1,102 @@
+//! A network interface backed by a host TAP device
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+
+use emulator_hal::Instant as EmuInstant;
+
+use crate::interface::{NetError, NetworkInterface, MAX_FRAME_SIZE};
+
+const TUNSETIFF: libc::c_ulong = 0x4004_54ca;
+const IFF_TAP: libc::c_short = 0x0002;
+const IFF_NO_PI: libc::c_short = 0x1000;
+
+#[repr(C)]
+struct InterfaceRequest {
+    name: [libc::c_char; libc::IFNAMSIZ],
+    flags: libc::c_short,
+    _padding: [u8; 22],
+}
+
+/// A network interface connected to a TAP device on the host
+///
+/// Frames sent by the emulated controller appear on the host's TAP interface, which can then be
+/// bridged or routed to a real network.  The TAP device must either already exist and be owned
+/// by the current user, or the emulator must have permission to create it.  The time given to
+/// each operation is ignored
+pub struct TapInterface<Instant> {
+    file: File,
+    name: String,
+    instant: PhantomData<Instant>,
+}
+
+impl<Instant> TapInterface<Instant> {
+    /// Open the TAP device with the given name, such as `tap0`
+    pub fn open(name: &str) -> Result<Self, NetError> {
+        if name.len() >= libc::IFNAMSIZ {
+            return Err(io::Error::from(io::ErrorKind::InvalidInput).into());
+        }
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open("/dev/net/tun")?;
+
+        let mut request = InterfaceRequest {
+            name: [0; libc::IFNAMSIZ],
+            flags: IFF_TAP | IFF_NO_PI,
+            _padding: [0; 22],
+        };
+        for (dest, byte) in request.name.iter_mut().zip(name.bytes()) {
+            *dest = byte as libc::c_char;
+        }
+
+        // Safety: the request is a valid `ifreq` structure that lives for the duration of the call
+        if unsafe { libc::ioctl(file.as_raw_fd(), TUNSETIFF as _, &mut request) } < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+
+        Ok(Self {
+            file,
+            name: name.to_string(),
+            instant: PhantomData,
+        })
+    }
+
+    /// Returns the name of the host's interface
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl<Instant> NetworkInterface for TapInterface<Instant>
+where
+    Instant: EmuInstant,
+{
+    type Instant = Instant;
+    type Error = NetError;
+
+    fn send(&mut self, _now: Self::Instant, frame: &[u8]) -> Result<(), Self::Error> {
+        if frame.len() > MAX_FRAME_SIZE {
+            return Err(NetError::FrameTooLarge(frame.len()));
+        }
+        self.file.write_all(frame)?;
+        Ok(())
+    }
+
+    fn receive(
+        &mut self,
+        _now: Self::Instant,
+        buffer: &mut [u8],
+    ) -> Result<Option<usize>, Self::Error> {
+        match self.file.read(buffer) {
+            Ok(size) => Ok(Some(size)),
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
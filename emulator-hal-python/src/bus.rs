@@ -0,0 +1,65 @@
+//! Python bindings for assembling devices into a bus
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use emulator_hal::{BasicBusError, DeviceId};
+use emulator_hal_bus::BusRouter;
+
+use crate::MemoryBlock;
+
+/// The router behind a `Bus`, which is shared with the devices that access it while being stepped
+pub(crate) type SharedRouter = Rc<RefCell<BusRouter<u64, Duration, BasicBusError>>>;
+
+/// A bus that dispatches each access to the device mapped at its address
+///
+/// Devices are given the address relative to the start of their range, and accesses to
+/// addresses that aren't mapped to any device raise `BusError`
+#[pyclass(unsendable)]
+pub struct Bus {
+    pub(crate) inner: SharedRouter,
+}
+
+bus_methods!(Bus {
+    /// Construct a new bus with no devices
+    #[new]
+    fn new() -> Self {
+        Self {
+            inner: Rc::new(RefCell::new(BusRouter::new())),
+        }
+    }
+
+    /// Map the given memory block to the addresses from `start` up to but not including `end`
+    ///
+    /// The range must not be empty or overlap another device, and the id must not already be
+    /// used by another device
+    fn map(&mut self, id: u32, start: u64, end: u64, memory: &MemoryBlock) -> PyResult<()> {
+        self.inner
+            .borrow_mut()
+            .insert(DeviceId(id), start..end, Box::new(memory.inner.clone()))
+            .map_err(|err| PyValueError::new_err(format!("{:?}", err)))
+    }
+
+    /// Returns the id of each device, in the order they were mapped
+    fn ids(&self) -> Vec<u32> {
+        self.inner.borrow().ids().iter().map(|id| id.0).collect()
+    }
+
+    /// Returns the `(start, end)` range of each device, in the same order as `ids()`
+    fn ranges(&self) -> Vec<(u64, u64)> {
+        self.inner
+            .borrow()
+            .ranges()
+            .iter()
+            .map(|range| (range.start, range.end))
+            .collect()
+    }
+
+    fn __len__(&self) -> usize {
+        self.inner.borrow().len()
+    }
+});
@@ -0,0 +1,126 @@
+//! Python bindings for controlling the execution of a device for debugging
+
+use std::time::Duration;
+
+use pyo3::prelude::*;
+
+use emulator_hal::{Debug, Inspect, Step, StopReason};
+
+use crate::bus::Bus;
+use crate::device::PyDevice;
+
+/// Controls the execution of a CPU device implemented in Python, with simulated time measured in
+/// nanoseconds
+///
+/// The device is a Python object with a `step(now, bus)` method, like the devices given to a
+/// `Scheduler`, and an `execution_address` attribute, which is the address of the instruction
+/// that will be executed by the next step.  It can also have `brief_summary(bus)`,
+/// `detailed_summary(bus)` and `inspect(info, bus)` methods, which return strings.  The
+/// breakpoints are kept by the debugger, and are checked after each step, so that the device
+/// stops before executing the instruction at a breakpoint
+#[pyclass(unsendable)]
+pub struct Debugger {
+    device: PyDevice,
+}
+
+#[pymethods]
+impl Debugger {
+    /// Construct a debugger for the given device, with no breakpoints
+    #[new]
+    fn new(device: PyObject) -> Self {
+        Self {
+            device: PyDevice::new(device),
+        }
+    }
+
+    /// The address where execution will take place the next time the device is stepped
+    #[getter]
+    fn get_execution_address(&mut self) -> PyResult<u64> {
+        self.device.get_execution_address()
+    }
+
+    #[setter]
+    fn set_execution_address(&mut self, address: u64) -> PyResult<()> {
+        self.device.set_execution_address(address)
+    }
+
+    /// Add a breakpoint at the given address
+    fn add_breakpoint(&mut self, address: u64) {
+        self.device.add_breakpoint(address);
+    }
+
+    /// Remove the breakpoint at the given address
+    fn remove_breakpoint(&mut self, address: u64) {
+        self.device.remove_breakpoint(address);
+    }
+
+    /// Remove all breakpoints
+    fn clear_breakpoints(&mut self) {
+        self.device.clear_breakpoints();
+    }
+
+    /// Returns the addresses of the breakpoints, in the order they were added
+    fn breakpoints(&self) -> Vec<u64> {
+        self.device.breakpoints().to_vec()
+    }
+
+    /// Step the device once at the given time, and return the time to step it again
+    #[pyo3(signature = (bus, now = 0))]
+    fn step(&mut self, bus: &Bus, now: u64) -> PyResult<u64> {
+        let next = self
+            .device
+            .step(Duration::from_nanos(now), &mut bus.inner.clone())?;
+        // a single step isn't stopped by a breakpoint, so the reason isn't kept for later
+        self.device.take_stop_reason();
+        Ok(next.as_nanos() as u64)
+    }
+
+    /// Step the device until it stops, or until `limit` steps have been taken
+    ///
+    /// Returns the time to step the device again, and the reason it stopped, which is `None`
+    /// if the limit was reached first, or a tuple of one of `"breakpoint"` or `"watchpoint"`
+    /// and the address, `"trap"` and the vector number, or `"halted"` and `None`
+    #[allow(clippy::type_complexity)]
+    #[pyo3(signature = (bus, now = 0, limit = 1_000_000))]
+    fn run_until_stop(
+        &mut self,
+        bus: &Bus,
+        now: u64,
+        limit: usize,
+    ) -> PyResult<(u64, Option<(&'static str, Option<u64>)>)> {
+        let (next, reason) =
+            self.device
+                .run_until_stop(Duration::from_nanos(now), &mut bus.inner.clone(), limit)?;
+        let reason = reason.map(|reason| match reason {
+            StopReason::Breakpoint(addr) => ("breakpoint", Some(addr)),
+            StopReason::Watchpoint(addr) => ("watchpoint", Some(addr)),
+            StopReason::Trap(vector) => ("trap", Some(vector as u64)),
+            _ => ("halted", None),
+        });
+        Ok((next.as_nanos() as u64, reason))
+    }
+
+    /// Returns the device's description of the given information
+    fn inspect(&mut self, bus: &Bus, info: String) -> PyResult<String> {
+        let mut output = String::new();
+        self.device
+            .inspect(info, &mut bus.inner.clone(), &mut output)?;
+        Ok(output)
+    }
+
+    /// Returns a brief summary of the device's current state
+    fn brief_summary(&mut self, bus: &Bus) -> PyResult<String> {
+        let mut output = String::new();
+        self.device
+            .brief_summary(&mut bus.inner.clone(), &mut output)?;
+        Ok(output)
+    }
+
+    /// Returns a detailed summary of the device's current state
+    fn detailed_summary(&mut self, bus: &Bus) -> PyResult<String> {
+        let mut output = String::new();
+        self.device
+            .detailed_summary(&mut bus.inner.clone(), &mut output)?;
+        Ok(output)
+    }
+}
@@ -0,0 +1,185 @@
+//! An adapter for devices implemented in Python
+
+use std::fmt::Write;
+use std::time::Duration;
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use emulator_hal::{Debug, Inspect, Step, StopReason};
+
+use crate::bus::{Bus, SharedRouter};
+
+/// A device implemented by a Python object, which can be stepped by a `Scheduler` or a `Debugger`
+///
+/// The object must have a `step(now, bus)` method, which returns the time to step it again, and
+/// can optionally have `reset(now, bus)` and `is_running()` methods.  To be debugged, it must
+/// also have an `execution_address` attribute, which can be assigned to, and can optionally
+/// have `brief_summary(bus)`, `detailed_summary(bus)` and `inspect(info, bus)` methods, which
+/// return strings.  The breakpoints are kept by the adapter, and the execution address is
+/// checked against them after each step, so the device stops before executing the instruction
+/// at a breakpoint
+pub(crate) struct PyDevice {
+    object: PyObject,
+    breakpoints: Vec<u64>,
+    stop_reason: Option<StopReason<u64>>,
+}
+
+impl PyDevice {
+    pub(crate) fn new(object: PyObject) -> Self {
+        Self {
+            object,
+            breakpoints: Vec::new(),
+            stop_reason: None,
+        }
+    }
+
+    pub(crate) fn breakpoints(&self) -> &[u64] {
+        &self.breakpoints
+    }
+
+    /// Call the given method of the object with the current time and the bus, if it has one
+    fn call_with_bus(
+        &self,
+        py: Python<'_>,
+        name: &str,
+        now: Option<Duration>,
+        bus: &SharedRouter,
+    ) -> PyResult<Option<PyObject>> {
+        let object = self.object.bind(py);
+        if !object.hasattr(name)? {
+            return Ok(None);
+        }
+        let bus = Bus { inner: bus.clone() };
+        let result = match now {
+            Some(now) => object.call_method1(name, (now.as_nanos() as u64, bus))?,
+            None => object.call_method1(name, (bus,))?,
+        };
+        Ok(Some(result.unbind()))
+    }
+
+    /// Write the string returned by the given method of the object, if it has one
+    fn write_summary(
+        &mut self,
+        name: &str,
+        bus: &mut SharedRouter,
+        writer: &mut String,
+    ) -> Result<(), PyErr> {
+        Python::with_gil(|py| {
+            if let Some(summary) = self.call_with_bus(py, name, None, bus)? {
+                writer.push_str(&summary.extract::<String>(py)?);
+            }
+            Ok(())
+        })
+    }
+}
+
+impl Step<u64, SharedRouter> for PyDevice {
+    type Error = PyErr;
+
+    fn is_running(&mut self) -> bool {
+        Python::with_gil(|py| {
+            let object = self.object.bind(py);
+            let running = match object.hasattr("is_running") {
+                Ok(true) => object
+                    .call_method0("is_running")
+                    .and_then(|result| result.extract::<bool>()),
+                Ok(false) => Ok(true),
+                Err(err) => Err(err),
+            };
+            // the error can't be returned from here, so it's reported and the device is stopped
+            running.unwrap_or_else(|err| {
+                err.write_unraisable(py, Some(object));
+                false
+            })
+        })
+    }
+
+    fn reset(&mut self, now: Duration, bus: &mut SharedRouter) -> Result<(), Self::Error> {
+        Python::with_gil(|py| self.call_with_bus(py, "reset", Some(now), bus))?;
+        Ok(())
+    }
+
+    fn step(&mut self, now: Duration, bus: &mut SharedRouter) -> Result<Duration, Self::Error> {
+        Python::with_gil(|py| {
+            let next = self
+                .call_with_bus(py, "step", Some(now), bus)?
+                .ok_or_else(|| PyValueError::new_err("device has no step() method"))?;
+            let next = Duration::from_nanos(next.extract::<u64>(py)?);
+
+            if self.breakpoints.is_empty() {
+                return Ok(next);
+            }
+            let addr = self.get_execution_address()?;
+            if self.breakpoints.contains(&addr) {
+                self.stop_reason = Some(StopReason::Breakpoint(addr));
+            }
+            Ok(next)
+        })
+    }
+}
+
+impl Inspect<u64, SharedRouter, String> for PyDevice {
+    type InfoType = String;
+    type Error = PyErr;
+
+    fn inspect(
+        &mut self,
+        info: Self::InfoType,
+        bus: &mut SharedRouter,
+        writer: &mut String,
+    ) -> Result<(), Self::Error> {
+        Python::with_gil(|py| {
+            let bus = Bus { inner: bus.clone() };
+            let result = self.object.bind(py).call_method1("inspect", (info, bus))?;
+            write!(writer, "{}", result.extract::<String>()?)
+                .map_err(|err| PyValueError::new_err(err.to_string()))
+        })
+    }
+
+    fn brief_summary(
+        &mut self,
+        bus: &mut SharedRouter,
+        writer: &mut String,
+    ) -> Result<(), Self::Error> {
+        self.write_summary("brief_summary", bus, writer)
+    }
+
+    fn detailed_summary(
+        &mut self,
+        bus: &mut SharedRouter,
+        writer: &mut String,
+    ) -> Result<(), Self::Error> {
+        self.write_summary("detailed_summary", bus, writer)
+    }
+}
+
+impl Debug<u64, SharedRouter, String> for PyDevice {
+    type DebugError = PyErr;
+
+    fn get_execution_address(&mut self) -> Result<u64, Self::DebugError> {
+        Python::with_gil(|py| self.object.getattr(py, "execution_address")?.extract(py))
+    }
+
+    fn set_execution_address(&mut self, address: u64) -> Result<(), Self::DebugError> {
+        Python::with_gil(|py| self.object.setattr(py, "execution_address", address))
+    }
+
+    fn add_breakpoint(&mut self, address: u64) {
+        if !self.breakpoints.contains(&address) {
+            self.breakpoints.push(address);
+        }
+    }
+
+    fn remove_breakpoint(&mut self, address: u64) {
+        self.breakpoints.retain(|breakpoint| *breakpoint != address);
+    }
+
+    fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    fn take_stop_reason(&mut self) -> Option<StopReason<u64>> {
+        self.stop_reason.take()
+    }
+}
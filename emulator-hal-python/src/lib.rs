@@ -0,0 +1,425 @@
+#![doc = include_str!("../README.md")]
+#![warn(missing_docs)]
+
+use std::time::Duration;
+
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyIOError, PyValueError};
+use pyo3::prelude::*;
+
+use emulator_hal::{BasicBusError, ByteOrder};
+use emulator_hal_memory::{MemoryBlock as RustMemoryBlock, SharedMemoryBlock};
+
+/// Implements the python methods of a class whose `inner` field is a bus, along with the given
+/// methods that are specific to the class
+macro_rules! bus_methods {
+    ($name:ident { $($methods:tt)* }) => {
+        #[pymethods]
+        impl $name {
+            $($methods)*
+
+            /// Read the given number of bytes from the given address
+            #[pyo3(signature = (addr, length, now = 0))]
+            fn read<'py>(
+                &mut self,
+                py: Python<'py>,
+                addr: u64,
+                length: usize,
+                now: u64,
+            ) -> PyResult<Bound<'py, pyo3::types::PyBytes>> {
+                let mut data = vec![0; length];
+                let count = emulator_hal::BusAccess::read(
+                    &mut self.inner,
+                    Duration::from_nanos(now),
+                    addr,
+                    &mut data,
+                )
+                .map_err($crate::bus_error)?;
+                Ok(pyo3::types::PyBytes::new(py, &data[..count]))
+            }
+
+            /// Write the given bytes to the given address, and return the number of bytes written
+            #[pyo3(signature = (addr, data, now = 0))]
+            fn write(&mut self, addr: u64, data: &[u8], now: u64) -> PyResult<usize> {
+                emulator_hal::BusAccess::write(&mut self.inner, Duration::from_nanos(now), addr, data)
+                    .map_err($crate::bus_error)
+            }
+
+            /// Read a single u8 value from the given address
+            #[pyo3(signature = (addr, now = 0))]
+            fn read_u8(&mut self, addr: u64, now: u64) -> PyResult<u8> {
+                emulator_hal::BusAccessExt::read_u8(&mut self.inner, Duration::from_nanos(now), addr)
+                    .map_err($crate::bus_error)
+            }
+
+            /// Read a single u16 value in the given byte order from the given address
+            #[pyo3(signature = (addr, order = "big", now = 0))]
+            fn read_u16(&mut self, addr: u64, order: &str, now: u64) -> PyResult<u16> {
+                emulator_hal::BusAccessExt::read_u16(
+                    &mut self.inner,
+                    $crate::byte_order(order)?,
+                    Duration::from_nanos(now),
+                    addr,
+                )
+                .map_err($crate::bus_error)
+            }
+
+            /// Read a single u32 value in the given byte order from the given address
+            #[pyo3(signature = (addr, order = "big", now = 0))]
+            fn read_u32(&mut self, addr: u64, order: &str, now: u64) -> PyResult<u32> {
+                emulator_hal::BusAccessExt::read_u32(
+                    &mut self.inner,
+                    $crate::byte_order(order)?,
+                    Duration::from_nanos(now),
+                    addr,
+                )
+                .map_err($crate::bus_error)
+            }
+
+            /// Write a single u8 value to the given address
+            #[pyo3(signature = (addr, value, now = 0))]
+            fn write_u8(&mut self, addr: u64, value: u8, now: u64) -> PyResult<()> {
+                emulator_hal::BusAccessExt::write_u8(
+                    &mut self.inner,
+                    Duration::from_nanos(now),
+                    addr,
+                    value,
+                )
+                .map_err($crate::bus_error)
+            }
+
+            /// Write a single u16 value in the given byte order to the given address
+            #[pyo3(signature = (addr, value, order = "big", now = 0))]
+            fn write_u16(&mut self, addr: u64, value: u16, order: &str, now: u64) -> PyResult<()> {
+                emulator_hal::BusAccessExt::write_u16(
+                    &mut self.inner,
+                    $crate::byte_order(order)?,
+                    Duration::from_nanos(now),
+                    addr,
+                    value,
+                )
+                .map_err($crate::bus_error)
+            }
+
+            /// Write a single u32 value in the given byte order to the given address
+            #[pyo3(signature = (addr, value, order = "big", now = 0))]
+            fn write_u32(&mut self, addr: u64, value: u32, order: &str, now: u64) -> PyResult<()> {
+                emulator_hal::BusAccessExt::write_u32(
+                    &mut self.inner,
+                    $crate::byte_order(order)?,
+                    Duration::from_nanos(now),
+                    addr,
+                    value,
+                )
+                .map_err($crate::bus_error)
+            }
+        }
+    };
+}
+
+mod bus;
+pub use crate::bus::*;
+
+mod debug;
+pub use crate::debug::*;
+
+mod device;
+
+mod scheduler;
+pub use crate::scheduler::*;
+
+create_exception!(
+    emulator_hal,
+    BusError,
+    PyException,
+    "An error returned by a bus transaction"
+);
+
+fn bus_error(err: BasicBusError) -> PyErr {
+    BusError::new_err(format!("{:?}", err))
+}
+
+fn byte_order(order: &str) -> PyResult<ByteOrder> {
+    match order {
+        "big" => Ok(ByteOrder::Big),
+        "little" => Ok(ByteOrder::Little),
+        _ => Err(PyValueError::new_err(
+            "byte order must be either 'big' or 'little'",
+        )),
+    }
+}
+
+/// A contiguous block of memory, with simulated time measured in nanoseconds
+///
+/// The same block can be mapped into one or more `Bus` objects, and is still accessible
+/// directly afterwards
+#[pyclass(unsendable)]
+pub struct MemoryBlock {
+    inner: SharedMemoryBlock<Duration>,
+}
+
+bus_methods!(MemoryBlock {
+    /// Construct a zero-filled memory block of the given size in bytes
+    #[new]
+    fn new(size: usize) -> Self {
+        Self {
+            inner: SharedMemoryBlock::new(RustMemoryBlock::from(vec![0; size])),
+        }
+    }
+
+    /// Construct a memory block containing a copy of the given bytes
+    #[staticmethod]
+    fn from_bytes(contents: Vec<u8>) -> Self {
+        Self {
+            inner: SharedMemoryBlock::new(RustMemoryBlock::from(contents)),
+        }
+    }
+
+    /// Load the binary contents of a file into a new memory block
+    #[staticmethod]
+    fn load(filename: &str) -> PyResult<Self> {
+        let block =
+            RustMemoryBlock::load(filename).map_err(|err| PyIOError::new_err(err.to_string()))?;
+        Ok(Self {
+            inner: SharedMemoryBlock::new(block),
+        })
+    }
+
+    /// Load the binary contents of a file into this memory block at the given address
+    fn load_at(&mut self, addr: usize, filename: &str) -> PyResult<()> {
+        self.inner
+            .borrow_mut()
+            .load_at(addr, filename)
+            .map_err(|err| PyIOError::new_err(err.to_string()))
+    }
+
+    /// Make this memory block read only
+    fn read_only(&mut self) {
+        self.inner.borrow_mut().read_only();
+    }
+
+    /// Resize this memory block to the given size in bytes
+    fn resize(&mut self, new_size: usize) {
+        self.inner.borrow_mut().resize(new_size);
+    }
+});
+
+/// The `emulator_hal` python module
+#[pymodule]
+#[pyo3(name = "emulator_hal")]
+fn python_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("BusError", m.py().get_type::<BusError>())?;
+    m.add_class::<MemoryBlock>()?;
+    m.add_class::<Bus>()?;
+    m.add_class::<Scheduler>()?;
+    m.add_class::<Debugger>()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pyo3::ffi::c_str;
+    use pyo3::types::PyDict;
+    use std::ffi::CStr;
+    use std::sync::Once;
+
+    fn run_python(code: &CStr) {
+        // the module can only be added before the interpreter is started, which is shared by
+        // all of the tests
+        static INIT: Once = Once::new();
+        INIT.call_once(|| {
+            pyo3::append_to_inittab!(python_module);
+            pyo3::prepare_freethreaded_python();
+        });
+
+        // each test has its own globals, which are cleared before returning so that the classes
+        // defined by the test, and the unsendable objects they refer to, are dropped by this thread
+        Python::with_gil(|py| {
+            let globals = PyDict::new(py);
+            let result = py.run(code, Some(&globals), None);
+            globals.clear();
+            if let Err(err) = result {
+                err.display(py);
+                panic!("python raised {}", err);
+            }
+        });
+    }
+
+    #[test]
+    fn test_memory_block_from_python() {
+        run_python(c_str!(
+            r#"
+from emulator_hal import MemoryBlock, BusError
+
+memory = MemoryBlock(0x100)
+memory.write_u32(0x10, 0x12345678)
+assert memory.read(0x10, 4) == b"\x12\x34\x56\x78"
+assert memory.read_u16(0x10, order="little") == 0x3412
+
+try:
+    memory.read(0xFF, 2)
+    assert False
+except BusError:
+    pass
+"#
+        ));
+    }
+
+    #[test]
+    fn test_bus_from_python() {
+        run_python(c_str!(
+            r#"
+from emulator_hal import Bus, MemoryBlock, BusError
+
+ram = MemoryBlock(0x100)
+rom = MemoryBlock.from_bytes(b"\xAA\xBB")
+rom.read_only()
+
+bus = Bus()
+bus.map(1, 0x1000, 0x1100, ram)
+bus.map(2, 0x0000, 0x0002, rom)
+assert len(bus) == 2
+assert bus.ids() == [1, 2]
+assert bus.ranges() == [(0x1000, 0x1100), (0x0000, 0x0002)]
+
+# the memory is still shared with the bus after it's mapped
+bus.write_u16(0x1010, 0x1234)
+assert ram.read(0x10, 2) == b"\x12\x34"
+ram.write_u8(0x20, 0x56)
+assert bus.read_u8(0x1020) == 0x56
+assert bus.read_u16(0x0000, order="little") == 0xBBAA
+
+for addr in [0x0800, 0x1100]:
+    try:
+        bus.read_u8(addr)
+        assert False
+    except BusError:
+        pass
+
+try:
+    bus.map(3, 0x10FF, 0x1200, MemoryBlock(0x101))
+    assert False
+except ValueError:
+    pass
+"#
+        ));
+    }
+
+    #[test]
+    fn test_scheduler_from_python() {
+        run_python(c_str!(
+            r#"
+from emulator_hal import Bus, MemoryBlock, Scheduler
+
+class Counter:
+    def __init__(self, name, period, addr, log):
+        self.name = name
+        self.period = period
+        self.addr = addr
+        self.log = log
+
+    def reset(self, now, bus):
+        bus.write_u8(self.addr, 0)
+
+    def step(self, now, bus):
+        self.log.append((self.name, now))
+        bus.write_u8(self.addr, bus.read_u8(self.addr) + 1)
+        return now + self.period
+
+class Faulty:
+    def step(self, now, bus):
+        raise RuntimeError("device failed")
+
+memory = MemoryBlock(0x10)
+bus = Bus()
+bus.map(0, 0x0, 0x10, memory)
+
+log = []
+scheduler = Scheduler()
+scheduler.insert(1, Counter("a", 300, 0x0, log))
+scheduler.insert(2, Counter("b", 200, 0x1, log))
+assert len(scheduler) == 2
+
+try:
+    scheduler.insert(2, Counter("c", 100, 0x2, log))
+    assert False
+except ValueError:
+    pass
+
+scheduler.reset(bus)
+scheduler.run_until(bus, 650)
+assert scheduler.now() == 650
+assert log == [("a", 0), ("b", 0), ("b", 200), ("a", 300), ("b", 400), ("a", 600), ("b", 600)]
+assert memory.read(0x0, 2) == b"\x03\x04"
+assert scheduler.next_due(1) == 900
+assert scheduler.next_due(3) is None
+
+assert scheduler.step(bus) == 2
+assert log[-1] == ("b", 800)
+
+scheduler.insert(3, Faulty())
+try:
+    scheduler.step(bus)
+    assert False
+except RuntimeError as err:
+    assert str(err) == "device failed"
+"#
+        ));
+    }
+
+    #[test]
+    fn test_debugger_from_python() {
+        run_python(c_str!(
+            r#"
+from emulator_hal import Bus, MemoryBlock, Debugger
+
+# a cpu that adds each byte it executes to its accumulator, until it executes a zero byte
+class Cpu:
+    def __init__(self):
+        self.execution_address = 0
+        self.acc = 0
+        self.running = True
+
+    def is_running(self):
+        return self.running
+
+    def step(self, now, bus):
+        value = bus.read_u8(self.execution_address)
+        if value == 0:
+            self.running = False
+        self.acc += value
+        self.execution_address += 1
+        return now + 4
+
+    def brief_summary(self, bus):
+        return "pc: {:04x} acc: {}".format(self.execution_address, self.acc)
+
+memory = MemoryBlock.from_bytes(b"\x01\x02\x03\x04\x05\x00")
+bus = Bus()
+bus.map(0, 0x0, 0x6, memory)
+
+cpu = Cpu()
+debugger = Debugger(cpu)
+debugger.add_breakpoint(3)
+debugger.add_breakpoint(5)
+debugger.remove_breakpoint(5)
+assert debugger.breakpoints() == [3]
+
+assert debugger.run_until_stop(bus) == (12, ("breakpoint", 3))
+assert debugger.execution_address == 3
+assert cpu.acc == 6
+assert debugger.brief_summary(bus) == "pc: 0003 acc: 6"
+
+assert debugger.step(bus, now=12) == 16
+assert debugger.run_until_stop(bus, now=16, limit=1) == (20, None)
+
+debugger.clear_breakpoints()
+debugger.execution_address = 0
+assert cpu.execution_address == 0
+assert debugger.run_until_stop(bus, now=20) == (44, ("halted", None))
+assert cpu.acc == 30
+"#
+        ));
+    }
+}
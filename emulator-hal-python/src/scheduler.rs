@@ -0,0 +1,84 @@
+//! Python bindings for stepping devices through simulated time
+
+use std::time::Duration;
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use emulator_hal::{DeviceId, Scheduler as RustScheduler};
+
+use crate::bus::{Bus, SharedRouter};
+use crate::device::PyDevice;
+
+/// Steps a set of devices sharing a bus, always stepping the device whose next step is the
+/// earliest, with simulated time measured in nanoseconds
+///
+/// Each device is a Python object with a `step(now, bus)` method, which is given the time of
+/// the step and the `Bus`, and returns the time it should be stepped again.  It can also have a
+/// `reset(now, bus)` method, and an `is_running()` method, which stops it from being stepped
+/// when it returns false.  An exception raised by a device is raised by the scheduler method
+/// that stepped it
+#[pyclass(unsendable)]
+pub struct Scheduler {
+    inner: RustScheduler<u64, SharedRouter, PyErr>,
+}
+
+#[pymethods]
+impl Scheduler {
+    /// Construct a new scheduler with no devices, starting at time 0
+    #[new]
+    fn new() -> Self {
+        Self {
+            inner: RustScheduler::new(),
+        }
+    }
+
+    /// Add a device with the given id, which will first be stepped at the current time
+    fn insert(&mut self, id: u32, device: PyObject) -> PyResult<()> {
+        if self.inner.next_due(DeviceId(id)).is_some() {
+            return Err(PyValueError::new_err(format!(
+                "device {} is already in the scheduler",
+                id
+            )));
+        }
+        self.inner
+            .insert(DeviceId(id), Box::new(PyDevice::new(device)));
+        Ok(())
+    }
+
+    /// Returns the current simulated time, which is the time of the last step
+    fn now(&self) -> u64 {
+        self.inner.now().as_nanos() as u64
+    }
+
+    /// Returns the time that the given device will next be stepped at, or `None` if there is
+    /// no device with that id
+    fn next_due(&self, id: u32) -> Option<u64> {
+        self.inner
+            .next_due(DeviceId(id))
+            .map(|next| next.as_nanos() as u64)
+    }
+
+    /// Reset every device, and schedule them all to be stepped at the current time
+    fn reset(&mut self, bus: &Bus) -> PyResult<()> {
+        self.inner.reset(&mut bus.inner.clone())
+    }
+
+    /// Step the running device that is due the earliest, and return its id, or `None` if no
+    /// devices are running
+    fn step(&mut self, bus: &Bus) -> PyResult<Option<u32>> {
+        let id = self.inner.step(&mut bus.inner.clone())?;
+        Ok(id.map(|id| id.0))
+    }
+
+    /// Step devices until the next device is due at or after `until`, and then advance the
+    /// current time to `until`
+    fn run_until(&mut self, bus: &Bus, until: u64) -> PyResult<()> {
+        self.inner
+            .run_until(&mut bus.inner.clone(), Duration::from_nanos(until))
+    }
+
+    fn __len__(&self) -> usize {
+        self.inner.len()
+    }
+}
@@ -0,0 +1,397 @@
+#![doc = include_str!("../README.md")]
+#![warn(missing_docs)]
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use emulator_hal::{Snapshot, SnapshotError, StateReader};
+
+/// The magic number at the start of every save state
+pub const SAVE_STATE_MAGIC: [u8; 4] = *b"EHSS";
+
+/// The version of the container format produced by `SaveState`
+pub const SAVE_STATE_VERSION: u8 = 1;
+
+/// The largest size that the entries of a compressed save state can be decompressed to
+///
+/// This stops a small corrupt or malicious file from being decompressed into an unbounded amount
+/// of memory.  It's large enough for the full memory of most emulated systems
+pub const MAX_DECOMPRESSED_SIZE: usize = 64 * 1024 * 1024;
+
+const FLAG_COMPRESSED: u8 = 0x01;
+
+struct Entry {
+    name: String,
+    version: u32,
+    data: Vec<u8>,
+}
+
+/// The saved state of a whole system, made up of a named blob for each device
+#[derive(Default)]
+pub struct SaveState {
+    entries: Vec<Entry>,
+}
+
+impl SaveState {
+    /// Construct a new empty save state
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Save the state of the given device under the given name, replacing any existing state with that name
+    pub fn save<S>(&mut self, name: &str, device: &S) -> Result<(), SnapshotError>
+    where
+        S: Snapshot + ?Sized,
+    {
+        let mut data = Vec::new();
+        device.save_state(&mut data)?;
+
+        let entry = Entry {
+            name: name.to_string(),
+            version: device.state_version(),
+            data,
+        };
+
+        match self.entries.iter_mut().find(|entry| entry.name == name) {
+            Some(existing) => *existing = entry,
+            None => self.entries.push(entry),
+        }
+        Ok(())
+    }
+
+    /// Restore the state of the given device from the state saved under the given name
+    ///
+    /// Returns `SnapshotError::NotFound` if there is no state with the given name
+    pub fn load<S>(&self, name: &str, device: &mut S) -> Result<(), SnapshotError>
+    where
+        S: Snapshot + ?Sized,
+    {
+        let entry = self
+            .entries
+            .iter()
+            .find(|entry| entry.name == name)
+            .ok_or(SnapshotError::NotFound)?;
+
+        let mut reader = BlobReader {
+            data: &entry.data,
+            version: entry.version,
+        };
+        device.load_state(&mut reader)
+    }
+
+    /// Returns true if this save state contains a device with the given name
+    pub fn contains(&self, name: &str) -> bool {
+        self.entries.iter().any(|entry| entry.name == name)
+    }
+
+    /// Remove the state of the device with the given name, returning true if it was present
+    pub fn remove(&mut self, name: &str) -> bool {
+        let len = self.entries.len();
+        self.entries.retain(|entry| entry.name != name);
+        self.entries.len() != len
+    }
+
+    /// Returns an iterator over the names of every device in this save state
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|entry| entry.name.as_str())
+    }
+
+    /// Encode this save state into an uncompressed byte representation
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut output = Vec::new();
+        output.extend_from_slice(&SAVE_STATE_MAGIC);
+        output.push(SAVE_STATE_VERSION);
+        output.push(0);
+        self.encode_entries(&mut output);
+        output
+    }
+
+    /// Encode this save state into a byte representation compressed with deflate
+    ///
+    /// The `level` is the compression level, from 0 to 10
+    #[cfg(feature = "compression")]
+    pub fn to_compressed_bytes(&self, level: u8) -> Vec<u8> {
+        let mut entries = Vec::new();
+        self.encode_entries(&mut entries);
+
+        let mut output = Vec::new();
+        output.extend_from_slice(&SAVE_STATE_MAGIC);
+        output.push(SAVE_STATE_VERSION);
+        output.push(FLAG_COMPRESSED);
+        output.extend_from_slice(&miniz_oxide::deflate::compress_to_vec(&entries, level));
+        output
+    }
+
+    /// Decode a save state from the given bytes, which may or may not be compressed
+    ///
+    /// A compressed state that decompresses to more than `MAX_DECOMPRESSED_SIZE` bytes is
+    /// rejected as invalid data
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SnapshotError> {
+        if bytes.len() < 6 || bytes[0..4] != SAVE_STATE_MAGIC {
+            return Err(SnapshotError::InvalidData);
+        }
+        if bytes[4] > SAVE_STATE_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(bytes[4] as u32));
+        }
+
+        let flags = bytes[5];
+        if flags & !FLAG_COMPRESSED != 0 {
+            return Err(SnapshotError::Unsupported);
+        }
+
+        if flags & FLAG_COMPRESSED != 0 {
+            Self::decode_compressed(&bytes[6..])
+        } else {
+            Self::decode_entries(&bytes[6..])
+        }
+    }
+
+    #[cfg(feature = "compression")]
+    fn decode_compressed(bytes: &[u8]) -> Result<Self, SnapshotError> {
+        let entries =
+            miniz_oxide::inflate::decompress_to_vec_with_limit(bytes, MAX_DECOMPRESSED_SIZE)
+                .map_err(|_| SnapshotError::InvalidData)?;
+        Self::decode_entries(&entries)
+    }
+
+    #[cfg(not(feature = "compression"))]
+    fn decode_compressed(_bytes: &[u8]) -> Result<Self, SnapshotError> {
+        Err(SnapshotError::Unsupported)
+    }
+
+    fn encode_entries(&self, output: &mut Vec<u8>) {
+        output.extend_from_slice(&(self.entries.len() as u32).to_le_bytes());
+        for entry in self.entries.iter() {
+            output.extend_from_slice(&(entry.name.len() as u16).to_le_bytes());
+            output.extend_from_slice(entry.name.as_bytes());
+            output.extend_from_slice(&entry.version.to_le_bytes());
+            output.extend_from_slice(&(entry.data.len() as u32).to_le_bytes());
+            output.extend_from_slice(&entry.data);
+        }
+    }
+
+    fn decode_entries(bytes: &[u8]) -> Result<Self, SnapshotError> {
+        let mut reader = BlobReader {
+            data: bytes,
+            version: 0,
+        };
+
+        let count = reader.read_u32()?;
+        let mut entries = Vec::new();
+        for _ in 0..count {
+            let length = reader.read_u16()? as usize;
+            let name = core::str::from_utf8(reader.take(length)?)
+                .map_err(|_| SnapshotError::InvalidData)?
+                .to_string();
+            let version = reader.read_u32()?;
+            let length = reader.read_u32()? as usize;
+            let data = reader.take(length)?.to_vec();
+
+            entries.push(Entry {
+                name,
+                version,
+                data,
+            });
+        }
+        Ok(Self { entries })
+    }
+}
+
+/// A collection of devices that can be saved and restored together
+///
+/// Each device is registered with a unique name, which is used to identify its state in the
+/// resulting `SaveState`
+#[derive(Default)]
+pub struct SnapshotRegistry<'a> {
+    devices: Vec<(&'a str, &'a mut dyn Snapshot)>,
+}
+
+impl<'a> SnapshotRegistry<'a> {
+    /// Construct a new empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the given device under the given name
+    pub fn register(&mut self, name: &'a str, device: &'a mut dyn Snapshot) {
+        self.devices.push((name, device));
+    }
+
+    /// Save the state of every registered device
+    pub fn save(&self) -> Result<SaveState, SnapshotError> {
+        let mut state = SaveState::new();
+        for (name, device) in self.devices.iter() {
+            state.save(name, &**device)?;
+        }
+        Ok(state)
+    }
+
+    /// Restore the state of every registered device that is present in the given save state
+    ///
+    /// Devices which are not present in the save state are left unmodified, and their names are
+    /// returned, so that the caller can decide whether a partial restore is acceptable
+    pub fn load(&mut self, state: &SaveState) -> Result<Vec<&'a str>, SnapshotError> {
+        let mut missing = Vec::new();
+        for (name, device) in self.devices.iter_mut() {
+            if state.contains(name) {
+                state.load(name, &mut **device)?;
+            } else {
+                missing.push(*name);
+            }
+        }
+        Ok(missing)
+    }
+}
+
+struct BlobReader<'a> {
+    data: &'a [u8],
+    version: u32,
+}
+
+impl<'a> BlobReader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], SnapshotError> {
+        if length > self.data.len() {
+            return Err(SnapshotError::UnexpectedEnd);
+        }
+        let (data, rest) = self.data.split_at(length);
+        self.data = rest;
+        Ok(data)
+    }
+}
+
+impl<'a> StateReader for BlobReader<'a> {
+    fn version(&self) -> u32 {
+        self.version
+    }
+
+    fn read_bytes(&mut self, data: &mut [u8]) -> Result<(), SnapshotError> {
+        data.copy_from_slice(self.take(data.len())?);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use emulator_hal::StateWriter;
+
+    #[derive(Default)]
+    struct Cpu {
+        pc: u32,
+        running: bool,
+    }
+
+    impl Snapshot for Cpu {
+        fn state_version(&self) -> u32 {
+            2
+        }
+
+        fn save_state(&self, writer: &mut dyn StateWriter) -> Result<(), SnapshotError> {
+            writer.write_u32(self.pc)?;
+            writer.write_bool(self.running)
+        }
+
+        fn load_state(&mut self, reader: &mut dyn StateReader) -> Result<(), SnapshotError> {
+            match reader.version() {
+                // version 1 stored the program counter as a u16, and had no running flag
+                1 => {
+                    self.pc = reader.read_u16()? as u32;
+                    self.running = true;
+                }
+                2 => {
+                    self.pc = reader.read_u32()?;
+                    self.running = reader.read_bool()?;
+                }
+                version => return Err(SnapshotError::UnsupportedVersion(version)),
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_save_and_load_registry() {
+        let mut cpu1 = Cpu {
+            pc: 0x1234,
+            running: true,
+        };
+        let mut cpu2 = Cpu {
+            pc: 0x5678,
+            running: false,
+        };
+
+        let mut registry = SnapshotRegistry::new();
+        registry.register("cpu1", &mut cpu1);
+        registry.register("cpu2", &mut cpu2);
+        let bytes = registry.save().unwrap().to_bytes();
+
+        let state = SaveState::from_bytes(&bytes).unwrap();
+        let mut cpu1 = Cpu::default();
+        let mut cpu3 = Cpu::default();
+
+        let mut registry = SnapshotRegistry::new();
+        registry.register("cpu1", &mut cpu1);
+        registry.register("cpu3", &mut cpu3);
+        let missing = registry.load(&state).unwrap();
+
+        assert_eq!(missing, vec!["cpu3"]);
+        assert_eq!(cpu1.pc, 0x1234);
+        assert!(cpu1.running);
+        assert_eq!(cpu3.pc, 0);
+    }
+
+    #[test]
+    fn test_load_older_version() {
+        let mut state = SaveState::new();
+        state.entries.push(Entry {
+            name: "cpu".to_string(),
+            version: 1,
+            data: vec![0x34, 0x12],
+        });
+
+        let mut cpu = Cpu::default();
+        state.load("cpu", &mut cpu).unwrap();
+        assert_eq!(cpu.pc, 0x1234);
+        assert!(cpu.running);
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn test_compressed_state() {
+        let mut state = SaveState::new();
+        state
+            .save(
+                "cpu",
+                &Cpu {
+                    pc: 0x1234,
+                    running: true,
+                },
+            )
+            .unwrap();
+
+        let bytes = state.to_compressed_bytes(6);
+        let state = SaveState::from_bytes(&bytes).unwrap();
+
+        let mut cpu = Cpu::default();
+        state.load("cpu", &mut cpu).unwrap();
+        assert_eq!(cpu.pc, 0x1234);
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn test_decompressed_size_limit() {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&SAVE_STATE_MAGIC);
+        bytes.push(SAVE_STATE_VERSION);
+        bytes.push(FLAG_COMPRESSED);
+        let entries = vec![0; MAX_DECOMPRESSED_SIZE + 1];
+        bytes.extend_from_slice(&miniz_oxide::deflate::compress_to_vec(&entries, 1));
+
+        assert!(matches!(
+            SaveState::from_bytes(&bytes),
+            Err(SnapshotError::InvalidData)
+        ));
+    }
+}
@@ -0,0 +1,149 @@
+//! Timing of characters sent over a serial line at a given baud rate
+
+use emulator_hal::Instant as EmuInstant;
+
+use crate::port::{LineStatus, SerialPort};
+
+/// The baud rate and frame format of a serial line, used to find how long each character takes
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BaudRate {
+    /// The number of bits transferred per second
+    pub baud: u32,
+    /// The number of bits in each character, including the start, parity, and stop bits
+    pub bits_per_frame: u32,
+}
+
+impl BaudRate {
+    /// Construct a new rate with the common 8N1 framing, which is 10 bits per character
+    pub const fn new(baud: u32) -> Self {
+        Self {
+            baud,
+            bits_per_frame: 10,
+        }
+    }
+
+    /// Construct a new rate with the given number of data bits, parity bit, and stop bits
+    pub const fn with_frame(baud: u32, data_bits: u32, parity: bool, stop_bits: u32) -> Self {
+        Self {
+            baud,
+            bits_per_frame: 1 + data_bits + parity as u32 + stop_bits,
+        }
+    }
+
+    /// Returns the number of characters that can be transferred per second
+    pub const fn characters_per_second(&self) -> u32 {
+        self.baud / self.bits_per_frame
+    }
+
+    /// Returns the time it takes to transfer one character
+    pub fn frame_time<Instant: EmuInstant>(&self) -> Instant::Duration {
+        Instant::cycles_to_duration(self.baud as u64, self.bits_per_frame)
+    }
+}
+
+/// A serial port wrapper that limits the rate bytes are transferred to the given baud rate
+///
+/// Host ports deliver bytes as fast as they arrive, so pasting text into a terminal can send
+/// bytes faster than an emulated UART without a FIFO would be able to receive them, and software
+/// that busy-waits on the transmit ready flag would never wait.  This wrapper only reports a byte
+/// as received once a character time has passed since the previous one, and only reports the
+/// transmitter as ready once the previous byte would have finished being sent.  Bytes
+/// transmitted while the transmitter is still busy are passed through, but delay the transmitter
+/// being ready again as though they were queued
+pub struct PacedPort<Port>
+where
+    Port: SerialPort,
+{
+    /// The port that bytes are transferred over
+    pub inner: Port,
+    /// The rate bytes are transferred at
+    pub rate: BaudRate,
+    transmit_ready_at: Option<Port::Instant>,
+    receive_ready_at: Option<Port::Instant>,
+}
+
+impl<Port> PacedPort<Port>
+where
+    Port: SerialPort,
+{
+    /// Construct a new wrapper which transfers bytes over `inner` at the given rate
+    pub fn new(inner: Port, rate: BaudRate) -> Self {
+        Self {
+            inner,
+            rate,
+            transmit_ready_at: None,
+            receive_ready_at: None,
+        }
+    }
+
+    fn is_ready(ready_at: Option<Port::Instant>, now: Port::Instant) -> bool {
+        ready_at.map(|ready_at| ready_at <= now).unwrap_or(true)
+    }
+}
+
+impl<Port> SerialPort for PacedPort<Port>
+where
+    Port: SerialPort,
+{
+    type Instant = Port::Instant;
+    type Error = Port::Error;
+
+    fn transmit(&mut self, now: Self::Instant, byte: u8) -> Result<(), Self::Error> {
+        self.inner.transmit(now, byte)?;
+        let start = match self.transmit_ready_at {
+            Some(ready_at) if ready_at > now => ready_at,
+            _ => now,
+        };
+        self.transmit_ready_at = Some(start + self.rate.frame_time::<Port::Instant>());
+        Ok(())
+    }
+
+    fn receive(&mut self, now: Self::Instant) -> Result<Option<u8>, Self::Error> {
+        if !Self::is_ready(self.receive_ready_at, now) {
+            return Ok(None);
+        }
+        let byte = self.inner.receive(now)?;
+        if byte.is_some() {
+            self.receive_ready_at = Some(now + self.rate.frame_time::<Port::Instant>());
+        }
+        Ok(byte)
+    }
+
+    fn line_status(&mut self, now: Self::Instant) -> Result<LineStatus, Self::Error> {
+        let mut status = self.inner.line_status(now)?;
+        status.data_ready &= Self::is_ready(self.receive_ready_at, now);
+        status.transmit_ready &= Self::is_ready(self.transmit_ready_at, now);
+        Ok(status)
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod test {
+    use super::*;
+    use crate::LoopbackPort;
+    use std::time::Duration;
+
+    #[test]
+    fn test_paced_port() {
+        let rate = BaudRate::new(9600);
+        assert_eq!(rate.characters_per_second(), 960);
+        assert_eq!(
+            rate.frame_time::<Duration>(),
+            Duration::from_nanos(1_041_666)
+        );
+        assert_eq!(BaudRate::with_frame(9600, 7, true, 2).bits_per_frame, 11);
+
+        let frame = rate.frame_time::<Duration>();
+        let mut port = PacedPort::new(LoopbackPort::new(Duration::ZERO, 8), rate);
+        port.transmit(Duration::ZERO, b'A').unwrap();
+        port.transmit(Duration::ZERO, b'B').unwrap();
+        let status = port.line_status(frame).unwrap();
+        assert!(status.data_ready && !status.transmit_ready);
+        assert!(port.line_status(frame * 2).unwrap().transmit_ready);
+
+        assert_eq!(port.receive(Duration::ZERO).unwrap(), Some(b'A'));
+        assert!(!port.line_status(Duration::ZERO).unwrap().data_ready);
+        assert_eq!(port.receive(frame / 2).unwrap(), None);
+        assert_eq!(port.receive(frame).unwrap(), Some(b'B'));
+    }
+}
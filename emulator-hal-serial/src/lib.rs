@@ -0,0 +1,32 @@
+#![doc = include_str!("../README.md")]
+#![warn(missing_docs)]
+#![cfg_attr(all(not(feature = "std"), not(test)), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+mod baud;
+pub use crate::baud::*;
+
+#[cfg(feature = "alloc")]
+mod loopback;
+#[cfg(feature = "alloc")]
+pub use crate::loopback::*;
+
+mod port;
+pub use crate::port::*;
+
+#[cfg(all(feature = "std", unix))]
+mod pty;
+#[cfg(all(feature = "std", unix))]
+pub use crate::pty::*;
+
+#[cfg(feature = "std")]
+mod stream;
+#[cfg(feature = "std")]
+pub use crate::stream::*;
+
+#[cfg(feature = "std")]
+mod tcp;
+#[cfg(feature = "std")]
+pub use crate::tcp::*;
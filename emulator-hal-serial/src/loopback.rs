@@ -0,0 +1,120 @@
+//! A serial port which receives every byte it transmits
+
+use alloc::collections::VecDeque;
+
+use emulator_hal::Instant as EmuInstant;
+
+use crate::port::{LineStatus, SerialError, SerialPort};
+
+/// A serial port that receives each byte it transmits, after a fixed delay
+///
+/// This can be used to test UART devices without a host port, with the delay set to the time
+/// it takes to transfer one character at the device's baud rate.  Only `capacity` bytes can be
+/// in flight at once, and any bytes transmitted while it's full are dropped and flagged as an
+/// overrun in the line status
+pub struct LoopbackPort<Instant>
+where
+    Instant: EmuInstant,
+{
+    queue: VecDeque<(Instant, u8)>,
+    delay: Instant::Duration,
+    capacity: usize,
+    overrun: bool,
+}
+
+impl<Instant> LoopbackPort<Instant>
+where
+    Instant: EmuInstant,
+{
+    /// Construct a new loopback port which delivers each byte after `delay`
+    pub fn new(delay: Instant::Duration, capacity: usize) -> Self {
+        Self {
+            queue: VecDeque::with_capacity(capacity),
+            delay,
+            capacity,
+            overrun: false,
+        }
+    }
+
+    /// Returns the number of bytes that have been transmitted but not yet received
+    pub fn pending(&self) -> usize {
+        self.queue.len()
+    }
+}
+
+impl<Instant> SerialPort for LoopbackPort<Instant>
+where
+    Instant: EmuInstant,
+    Instant::Duration: Copy,
+{
+    type Instant = Instant;
+    type Error = SerialError;
+
+    fn transmit(&mut self, now: Self::Instant, byte: u8) -> Result<(), Self::Error> {
+        if self.queue.len() >= self.capacity {
+            self.overrun = true;
+            return Err(SerialError::Overrun);
+        }
+        self.queue.push_back((now + self.delay, byte));
+        Ok(())
+    }
+
+    fn receive(&mut self, now: Self::Instant) -> Result<Option<u8>, Self::Error> {
+        match self.queue.front() {
+            Some((arrival, _)) if *arrival <= now => {
+                Ok(self.queue.pop_front().map(|(_, byte)| byte))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn line_status(&mut self, now: Self::Instant) -> Result<LineStatus, Self::Error> {
+        let status = LineStatus {
+            data_ready: matches!(self.queue.front(), Some((arrival, _)) if *arrival <= now),
+            transmit_ready: self.queue.len() < self.capacity,
+            overrun: self.overrun,
+            connected: true,
+        };
+        self.overrun = false;
+        Ok(status)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_bytes_arrive_after_delay() {
+        let mut port = LoopbackPort::new(Duration::from_micros(100), 2);
+
+        port.transmit(Duration::ZERO, b'A').unwrap();
+        port.transmit(Duration::from_micros(50), b'B').unwrap();
+        assert!(matches!(
+            port.transmit(Duration::from_micros(60), b'C'),
+            Err(SerialError::Overrun)
+        ));
+
+        assert_eq!(port.receive(Duration::from_micros(99)).unwrap(), None);
+        let status = port.line_status(Duration::from_micros(100)).unwrap();
+        assert!(status.data_ready && status.overrun && !status.transmit_ready);
+        assert!(
+            !port
+                .line_status(Duration::from_micros(100))
+                .unwrap()
+                .overrun
+        );
+
+        assert_eq!(
+            port.receive(Duration::from_micros(100)).unwrap(),
+            Some(b'A')
+        );
+        assert_eq!(port.receive(Duration::from_micros(100)).unwrap(), None);
+        assert_eq!(
+            port.receive(Duration::from_micros(150)).unwrap(),
+            Some(b'B')
+        );
+        assert_eq!(port.pending(), 0);
+    }
+}
@@ -0,0 +1,87 @@
+//! The interface between serial devices and the ports they are connected to
+
+use emulator_hal::{ErrorType, Instant as EmuInstant};
+
+/// An error that occurred while using a serial port
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum SerialError {
+    /// The other end of the port has been closed
+    Disconnected,
+
+    /// A byte was received while the port's receive buffer was full, and has been discarded
+    Overrun,
+
+    /// An error occurred while accessing the host's port
+    #[cfg(feature = "std")]
+    Io(std::io::Error),
+}
+
+impl ErrorType for SerialError {}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for SerialError {
+    fn from(err: std::io::Error) -> Self {
+        SerialError::Io(err)
+    }
+}
+
+/// The state of a serial port's line, similar to a UART line status register
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct LineStatus {
+    /// At least one byte can be read with `receive()`
+    pub data_ready: bool,
+    /// A byte can be written with `transmit()` without being dropped
+    pub transmit_ready: bool,
+    /// At least one received byte was dropped since the status was last checked
+    pub overrun: bool,
+    /// The other end of the port is connected
+    pub connected: bool,
+}
+
+/// A byte-oriented serial port that an emulated UART can be connected to
+///
+/// Each operation is given the current time of the emulated device, so that ports can model
+/// the time it takes to transfer a byte, or record when each byte was sent.  Ports backed by the
+/// host, which have no notion of simulated time, can ignore it
+pub trait SerialPort {
+    /// The type for the time that operations occur at
+    type Instant: EmuInstant;
+
+    /// The type of an error returned by this port
+    type Error: ErrorType;
+
+    /// Send a byte out of the port at time `now`
+    fn transmit(&mut self, now: Self::Instant, byte: u8) -> Result<(), Self::Error>;
+
+    /// Returns the next byte that has been received by time `now`, if there is one
+    fn receive(&mut self, now: Self::Instant) -> Result<Option<u8>, Self::Error>;
+
+    /// Returns the current state of the port's line at time `now`
+    ///
+    /// Reading the status clears the overrun flag
+    fn line_status(&mut self, now: Self::Instant) -> Result<LineStatus, Self::Error>;
+}
+
+impl<T> SerialPort for &mut T
+where
+    T: SerialPort + ?Sized,
+{
+    type Instant = T::Instant;
+    type Error = T::Error;
+
+    #[inline]
+    fn transmit(&mut self, now: Self::Instant, byte: u8) -> Result<(), Self::Error> {
+        T::transmit(self, now, byte)
+    }
+
+    #[inline]
+    fn receive(&mut self, now: Self::Instant) -> Result<Option<u8>, Self::Error> {
+        T::receive(self, now)
+    }
+
+    #[inline]
+    fn line_status(&mut self, now: Self::Instant) -> Result<LineStatus, Self::Error> {
+        T::line_status(self, now)
+    }
+}
@@ -0,0 +1,139 @@
+//! A serial port backed by a host pseudo-terminal
+
+use std::ffi::CStr;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::path::{Path, PathBuf};
+
+use emulator_hal::Instant as EmuInstant;
+
+use crate::port::{LineStatus, SerialError, SerialPort};
+use crate::stream::StreamPort;
+
+/// A serial port connected to a new pseudo-terminal on the host
+///
+/// A terminal program, such as `screen` or `minicom`, can connect to the emulated device by
+/// opening the path returned by `path()`.  The terminal is put into raw mode so that bytes are
+/// passed through unchanged
+pub struct PtyPort<Instant> {
+    port: StreamPort<File, Instant>,
+    path: PathBuf,
+    // Keeping the subsidiary end open prevents reads from failing when no terminal is connected
+    _subsidiary: File,
+}
+
+impl<Instant> PtyPort<Instant> {
+    /// Open a new pseudo-terminal
+    pub fn open() -> Result<Self, SerialError> {
+        // Safety: the file descriptor is checked before being owned by the `File`, and the name
+        // returned by `ptsname()` is copied before any other call could overwrite it
+        let (main, path) = unsafe {
+            let fd = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+            if fd < 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+            let main = File::from_raw_fd(fd);
+            if libc::grantpt(fd) != 0 || libc::unlockpt(fd) != 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+            let name = libc::ptsname(fd);
+            if name.is_null() {
+                return Err(io::Error::last_os_error().into());
+            }
+            let path = PathBuf::from(CStr::from_ptr(name).to_string_lossy().into_owned());
+            (main, path)
+        };
+
+        let subsidiary = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NOCTTY)
+            .open(&path)?;
+        set_raw_mode(&subsidiary)?;
+
+        let reader = main.try_clone()?;
+        Ok(Self {
+            port: StreamPort::new(reader, main),
+            path,
+            _subsidiary: subsidiary,
+        })
+    }
+
+    /// Returns the path of the terminal device that other programs can open
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+fn set_raw_mode(file: &File) -> Result<(), io::Error> {
+    // Safety: `termios` is a plain C struct which is fully initialized by `tcgetattr()`
+    unsafe {
+        let mut termios = std::mem::zeroed::<libc::termios>();
+        if libc::tcgetattr(file.as_raw_fd(), &mut termios) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        libc::cfmakeraw(&mut termios);
+        if libc::tcsetattr(file.as_raw_fd(), libc::TCSANOW, &termios) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+impl<Instant> SerialPort for PtyPort<Instant>
+where
+    Instant: EmuInstant,
+{
+    type Instant = Instant;
+    type Error = SerialError;
+
+    #[inline]
+    fn transmit(&mut self, now: Self::Instant, byte: u8) -> Result<(), Self::Error> {
+        self.port.transmit(now, byte)
+    }
+
+    #[inline]
+    fn receive(&mut self, now: Self::Instant) -> Result<Option<u8>, Self::Error> {
+        self.port.receive(now)
+    }
+
+    #[inline]
+    fn line_status(&mut self, now: Self::Instant) -> Result<LineStatus, Self::Error> {
+        self.port.line_status(now)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::time::Duration;
+
+    #[test]
+    fn test_pty_port() {
+        let mut port = PtyPort::open().unwrap();
+        let mut terminal = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(port.path())
+            .unwrap();
+
+        terminal.write_all(b"x").unwrap();
+        let mut received = None;
+        for _ in 0..100 {
+            received = port.receive(Duration::ZERO).unwrap();
+            if received.is_some() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(received, Some(b'x'));
+
+        port.transmit(Duration::ZERO, b'y').unwrap();
+        let mut data = [0; 1];
+        terminal.read_exact(&mut data).unwrap();
+        assert_eq!(&data, b"y");
+    }
+}
@@ -0,0 +1,143 @@
+//! Serial ports backed by the host's byte streams, such as stdio
+
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+use emulator_hal::Instant as EmuInstant;
+
+use crate::port::{LineStatus, SerialError, SerialPort};
+
+/// A serial port that transmits to a host writer, and receives from a host reader
+///
+/// The reader is read by a background thread so that `receive()` never blocks the emulator.
+/// When the reader reaches the end of its input, the port is reported as disconnected.  The
+/// time given to each operation is ignored
+pub struct StreamPort<W, Instant>
+where
+    W: Write,
+{
+    writer: W,
+    receiver: Receiver<u8>,
+    next: Option<u8>,
+    connected: bool,
+    instant: PhantomData<Instant>,
+}
+
+impl<Instant> StreamPort<io::Stdout, Instant> {
+    /// Construct a new port connected to the host's stdin and stdout
+    ///
+    /// Since stdin is read from a background thread, only one such port should be created
+    pub fn stdio() -> Self {
+        Self::new(io::stdin(), io::stdout())
+    }
+}
+
+impl<W, Instant> StreamPort<W, Instant>
+where
+    W: Write,
+{
+    /// Construct a new port which receives from `reader` and transmits to `writer`
+    pub fn new<R>(mut reader: R, writer: W) -> Self
+    where
+        R: Read + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let mut buffer = [0; 256];
+            loop {
+                match reader.read(&mut buffer) {
+                    Ok(0) => break,
+                    Ok(count) => {
+                        for byte in buffer[..count].iter() {
+                            if sender.send(*byte).is_err() {
+                                return;
+                            }
+                        }
+                    }
+                    Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                    Err(_) => break,
+                }
+            }
+        });
+
+        Self {
+            writer,
+            receiver,
+            next: None,
+            connected: true,
+            instant: PhantomData,
+        }
+    }
+
+    /// Returns the underlying writer
+    pub fn writer(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    fn poll(&mut self) {
+        if self.next.is_none() && self.connected {
+            match self.receiver.try_recv() {
+                Ok(byte) => self.next = Some(byte),
+                Err(TryRecvError::Empty) => {}
+                Err(TryRecvError::Disconnected) => self.connected = false,
+            }
+        }
+    }
+}
+
+impl<W, Instant> SerialPort for StreamPort<W, Instant>
+where
+    W: Write,
+    Instant: EmuInstant,
+{
+    type Instant = Instant;
+    type Error = SerialError;
+
+    fn transmit(&mut self, _now: Self::Instant, byte: u8) -> Result<(), Self::Error> {
+        self.writer.write_all(&[byte])?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn receive(&mut self, _now: Self::Instant) -> Result<Option<u8>, Self::Error> {
+        self.poll();
+        match self.next.take() {
+            Some(byte) => Ok(Some(byte)),
+            None if !self.connected => Err(SerialError::Disconnected),
+            None => Ok(None),
+        }
+    }
+
+    fn line_status(&mut self, _now: Self::Instant) -> Result<LineStatus, Self::Error> {
+        self.poll();
+        Ok(LineStatus {
+            data_ready: self.next.is_some(),
+            transmit_ready: true,
+            overrun: false,
+            connected: self.connected,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_stream_port() {
+        let mut port = StreamPort::new(&b"hi"[..], Vec::new());
+
+        let mut received = Vec::new();
+        while let Ok(byte) = port.receive(Duration::ZERO) {
+            received.extend(byte);
+        }
+        assert_eq!(received, b"hi");
+        assert!(!port.line_status(Duration::ZERO).unwrap().connected);
+
+        port.transmit(Duration::ZERO, b'!').unwrap();
+        assert_eq!(port.writer(), b"!");
+    }
+}
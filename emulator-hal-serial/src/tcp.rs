@@ -0,0 +1,146 @@
+//! A serial port backed by a host TCP socket
+
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+
+use emulator_hal::Instant as EmuInstant;
+
+use crate::port::{LineStatus, SerialError, SerialPort};
+
+/// A serial port that listens for a TCP connection on the host
+///
+/// A client such as `telnet` or `nc` can connect to the emulated device at the address returned
+/// by `local_addr()`.  One client can be connected at a time, and another client can connect
+/// after it disconnects.  Like a serial cable that isn't plugged in, bytes transmitted while no
+/// client is connected are discarded.  The socket is never blocked on, so bytes which can't be
+/// sent immediately are dropped and reported as an overrun.  The time given to each operation is
+/// ignored
+pub struct TcpPort<Instant> {
+    listener: TcpListener,
+    stream: Option<TcpStream>,
+    next: Option<u8>,
+    instant: PhantomData<Instant>,
+}
+
+impl<Instant> TcpPort<Instant> {
+    /// Listen for connections on the given address
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> Result<Self, SerialError> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener,
+            stream: None,
+            next: None,
+            instant: PhantomData,
+        })
+    }
+
+    /// Returns the address that the port is listening on
+    pub fn local_addr(&self) -> Result<SocketAddr, SerialError> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Returns true if a client is currently connected
+    pub fn is_connected(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    fn poll(&mut self) -> Result<(), SerialError> {
+        if self.stream.is_none() {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    stream.set_nonblocking(true)?;
+                    stream.set_nodelay(true)?;
+                    self.stream = Some(stream);
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        if let (None, Some(stream)) = (self.next, self.stream.as_mut()) {
+            let mut buffer = [0];
+            match stream.read(&mut buffer) {
+                Ok(0) => self.stream = None,
+                Ok(_) => self.next = Some(buffer[0]),
+                Err(err)
+                    if err.kind() == io::ErrorKind::WouldBlock
+                        || err.kind() == io::ErrorKind::Interrupted => {}
+                Err(_) => self.stream = None,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<Instant> SerialPort for TcpPort<Instant>
+where
+    Instant: EmuInstant,
+{
+    type Instant = Instant;
+    type Error = SerialError;
+
+    fn transmit(&mut self, _now: Self::Instant, byte: u8) -> Result<(), Self::Error> {
+        self.poll()?;
+        if let Some(stream) = self.stream.as_mut() {
+            match stream.write(&[byte]) {
+                Ok(0) => self.stream = None,
+                Ok(_) => {}
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                    return Err(SerialError::Overrun)
+                }
+                Err(_) => self.stream = None,
+            }
+        }
+        Ok(())
+    }
+
+    fn receive(&mut self, _now: Self::Instant) -> Result<Option<u8>, Self::Error> {
+        self.poll()?;
+        Ok(self.next.take())
+    }
+
+    fn line_status(&mut self, _now: Self::Instant) -> Result<LineStatus, Self::Error> {
+        self.poll()?;
+        Ok(LineStatus {
+            data_ready: self.next.is_some(),
+            transmit_ready: true,
+            overrun: false,
+            connected: self.stream.is_some(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_tcp_port() {
+        let mut port = TcpPort::bind("127.0.0.1:0").unwrap();
+        let now = Duration::ZERO;
+        port.transmit(now, b'x').unwrap();
+        assert!(!port.line_status(now).unwrap().connected);
+
+        let mut client = TcpStream::connect(port.local_addr().unwrap()).unwrap();
+        client.write_all(b"hi").unwrap();
+        let mut received = Vec::new();
+        while received.len() < 2 {
+            received.extend(port.receive(now).unwrap());
+        }
+        assert_eq!(received, b"hi");
+        assert!(port.is_connected());
+
+        port.transmit(now, b'!').unwrap();
+        let mut buffer = [0];
+        client.read_exact(&mut buffer).unwrap();
+        assert_eq!(buffer, *b"!");
+
+        drop(client);
+        while port.is_connected() {
+            port.receive(now).unwrap();
+        }
+    }
+}
@@ -0,0 +1,314 @@
+//! I2C style buses, where targets are addressed at the start of each transaction
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use emulator_hal::{ErrorType, Instant as EmuInstant};
+
+/// The direction of an I2C transaction, as given by the lowest bit of the address byte
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum I2cDirection {
+    /// The controller writes bytes to the target
+    Write,
+    /// The controller reads bytes from the target
+    Read,
+}
+
+/// A peripheral attached to an I2C style bus, such as an EEPROM, real-time clock, or sensor
+///
+/// A transaction begins with a start condition addressed to the target, which the target
+/// acknowledges, followed by bytes written to or read from it, and ends with a stop condition.
+/// A repeated start can change the direction without a stop, such as to set a register number
+/// and then read from it
+pub trait I2cTarget {
+    /// The type for the time that transfers occur at
+    type Instant: EmuInstant;
+
+    /// The type of an error returned by this target
+    type Error: ErrorType;
+
+    /// Returns the 7-bit address that the target responds to
+    fn address(&self) -> u8;
+
+    /// Begin a transaction in the given direction, and return true if the target acknowledges it
+    fn start(&mut self, now: Self::Instant, direction: I2cDirection) -> Result<bool, Self::Error>;
+
+    /// Write a byte to the target, and return true if the target acknowledges it
+    fn write(&mut self, now: Self::Instant, byte: u8) -> Result<bool, Self::Error>;
+
+    /// Read a byte from the target, where `last` is true if the controller will not acknowledge
+    /// it, to end the read
+    fn read(&mut self, now: Self::Instant, last: bool) -> Result<u8, Self::Error>;
+
+    /// End the current transaction
+    fn stop(&mut self, now: Self::Instant) -> Result<(), Self::Error>;
+}
+
+/// The controller's side of an I2C style bus, with any number of targets
+pub trait I2cBus {
+    /// The type for the time that transfers occur at
+    type Instant: EmuInstant;
+
+    /// The type of an error returned by this bus
+    type Error: ErrorType;
+
+    /// Send a start, or repeated start, condition to the target with the given 7-bit address,
+    /// and return true if a target acknowledged it
+    fn start(
+        &mut self,
+        now: Self::Instant,
+        address: u8,
+        direction: I2cDirection,
+    ) -> Result<bool, Self::Error>;
+
+    /// Write a byte to the addressed target, and return true if it was acknowledged
+    fn write(&mut self, now: Self::Instant, byte: u8) -> Result<bool, Self::Error>;
+
+    /// Read a byte from the addressed target, where `last` is true to not acknowledge it
+    ///
+    /// If no target acknowledged the start condition, the data line is not driven and reads
+    /// as 0xFF
+    fn read(&mut self, now: Self::Instant, last: bool) -> Result<u8, Self::Error>;
+
+    /// Send a stop condition, which ends the current transaction
+    fn stop(&mut self, now: Self::Instant) -> Result<(), Self::Error>;
+
+    /// Write `data` to the target with the given address in one transaction, and return the
+    /// number of bytes that were acknowledged
+    fn write_frame(
+        &mut self,
+        now: Self::Instant,
+        address: u8,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        let mut count = 0;
+        if self.start(now, address, I2cDirection::Write)? {
+            for byte in data {
+                if !self.write(now, *byte)? {
+                    break;
+                }
+                count += 1;
+            }
+        }
+        self.stop(now)?;
+        Ok(count)
+    }
+
+    /// Fill `data` from the target with the given address in one transaction, and return false
+    /// if the target didn't acknowledge its address
+    fn read_frame(
+        &mut self,
+        now: Self::Instant,
+        address: u8,
+        data: &mut [u8],
+    ) -> Result<bool, Self::Error> {
+        let acknowledged = self.start(now, address, I2cDirection::Read)?;
+        let length = data.len();
+        for (i, byte) in data.iter_mut().enumerate() {
+            *byte = self.read(now, i + 1 == length)?;
+        }
+        self.stop(now)?;
+        Ok(acknowledged)
+    }
+}
+
+impl<T> I2cBus for &mut T
+where
+    T: I2cBus + ?Sized,
+{
+    type Instant = T::Instant;
+    type Error = T::Error;
+
+    #[inline]
+    fn start(
+        &mut self,
+        now: Self::Instant,
+        address: u8,
+        direction: I2cDirection,
+    ) -> Result<bool, Self::Error> {
+        T::start(self, now, address, direction)
+    }
+
+    #[inline]
+    fn write(&mut self, now: Self::Instant, byte: u8) -> Result<bool, Self::Error> {
+        T::write(self, now, byte)
+    }
+
+    #[inline]
+    fn read(&mut self, now: Self::Instant, last: bool) -> Result<u8, Self::Error> {
+        T::read(self, now, last)
+    }
+
+    #[inline]
+    fn stop(&mut self, now: Self::Instant) -> Result<(), Self::Error> {
+        T::stop(self, now)
+    }
+}
+
+/// A boxed target that can be attached to an `I2cRouter`
+pub type DynI2cTarget<Instant, Error> = Box<dyn I2cTarget<Instant = Instant, Error = Error>>;
+
+/// An I2C bus which connects a controller to targets by their addresses
+///
+/// A start condition is passed to the first attached target with a matching address, which
+/// stays addressed until the next start or stop condition
+pub struct I2cRouter<Instant, Error> {
+    targets: Vec<DynI2cTarget<Instant, Error>>,
+    addressed: Option<usize>,
+}
+
+impl<Instant, Error> Default for I2cRouter<Instant, Error> {
+    fn default() -> Self {
+        Self {
+            targets: Vec::new(),
+            addressed: None,
+        }
+    }
+}
+
+impl<Instant, Error> I2cRouter<Instant, Error>
+where
+    Instant: EmuInstant,
+    Error: ErrorType,
+{
+    /// Construct a new bus with no targets attached
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Attach a target to the bus
+    pub fn attach(&mut self, target: DynI2cTarget<Instant, Error>) {
+        self.targets.push(target);
+    }
+}
+
+impl<Instant, Error> I2cBus for I2cRouter<Instant, Error>
+where
+    Instant: EmuInstant,
+    Error: ErrorType,
+{
+    type Instant = Instant;
+    type Error = Error;
+
+    fn start(
+        &mut self,
+        now: Self::Instant,
+        address: u8,
+        direction: I2cDirection,
+    ) -> Result<bool, Self::Error> {
+        self.addressed = None;
+        let index = match self
+            .targets
+            .iter()
+            .position(|target| target.address() == address)
+        {
+            Some(index) => index,
+            None => return Ok(false),
+        };
+
+        let acknowledged = self.targets[index].start(now, direction)?;
+        if acknowledged {
+            self.addressed = Some(index);
+        }
+        Ok(acknowledged)
+    }
+
+    fn write(&mut self, now: Self::Instant, byte: u8) -> Result<bool, Self::Error> {
+        match self.addressed {
+            Some(index) => self.targets[index].write(now, byte),
+            None => Ok(false),
+        }
+    }
+
+    fn read(&mut self, now: Self::Instant, last: bool) -> Result<u8, Self::Error> {
+        match self.addressed {
+            Some(index) => self.targets[index].read(now, last),
+            None => Ok(0xFF),
+        }
+    }
+
+    fn stop(&mut self, now: Self::Instant) -> Result<(), Self::Error> {
+        match self.addressed.take() {
+            Some(index) => self.targets[index].stop(now),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::convert::Infallible;
+    use std::time::Duration;
+
+    /// A small EEPROM where the first byte written sets the address to read or write
+    struct Eeprom {
+        memory: [u8; 16],
+        pointer: Option<usize>,
+    }
+
+    impl I2cTarget for Eeprom {
+        type Instant = Duration;
+        type Error = Infallible;
+
+        fn address(&self) -> u8 {
+            0x50
+        }
+
+        fn start(&mut self, _now: Duration, direction: I2cDirection) -> Result<bool, Self::Error> {
+            if direction == I2cDirection::Write {
+                self.pointer = None;
+            }
+            Ok(true)
+        }
+
+        fn write(&mut self, _now: Duration, byte: u8) -> Result<bool, Self::Error> {
+            match self.pointer {
+                None => self.pointer = Some(byte as usize % 16),
+                Some(pointer) => {
+                    self.memory[pointer] = byte;
+                    self.pointer = Some((pointer + 1) % 16);
+                }
+            }
+            Ok(true)
+        }
+
+        fn read(&mut self, _now: Duration, _last: bool) -> Result<u8, Self::Error> {
+            let pointer = self.pointer.unwrap_or(0);
+            self.pointer = Some((pointer + 1) % 16);
+            Ok(self.memory[pointer])
+        }
+
+        fn stop(&mut self, _now: Duration) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_write_then_read_back() {
+        let mut bus = I2cRouter::new();
+        bus.attach(Box::new(Eeprom {
+            memory: [0; 16],
+            pointer: None,
+        }));
+
+        assert_eq!(
+            bus.write_frame(Duration::ZERO, 0x50, &[4, 0xDE, 0xAD])
+                .unwrap(),
+            3
+        );
+
+        // set the address, then read from it after a repeated start
+        assert!(bus
+            .start(Duration::ZERO, 0x50, I2cDirection::Write)
+            .unwrap());
+        assert!(bus.write(Duration::ZERO, 4).unwrap());
+        let mut data = [0; 3];
+        assert!(bus.read_frame(Duration::ZERO, 0x50, &mut data).unwrap());
+        assert_eq!(data, [0xDE, 0xAD, 0]);
+
+        assert!(!bus.read_frame(Duration::ZERO, 0x51, &mut data).unwrap());
+        assert_eq!(data, [0xFF; 3]);
+        assert_eq!(bus.write_frame(Duration::ZERO, 0x51, &[1]).unwrap(), 0);
+    }
+}
@@ -0,0 +1,11 @@
+#![doc = include_str!("../README.md")]
+#![warn(missing_docs)]
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+mod i2c;
+pub use crate::i2c::*;
+
+mod spi;
+pub use crate::spi::*;
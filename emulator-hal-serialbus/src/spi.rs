@@ -0,0 +1,188 @@
+//! SPI style buses, where a selected target exchanges a byte for every byte sent to it
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use emulator_hal::{ErrorType, Instant as EmuInstant};
+
+/// A peripheral attached to an SPI style bus, such as a serial EEPROM or flash chip
+///
+/// The target is selected by its chip select line before a transaction, and deselected after
+/// it, which usually resets the target's command decoding.  Each byte sent by the controller is
+/// exchanged for a byte from the target at the same time
+pub trait SpiTarget {
+    /// The type for the time that transfers occur at
+    type Instant: EmuInstant;
+
+    /// The type of an error returned by this target
+    type Error: ErrorType;
+
+    /// Set the target's chip select line to active or inactive at time `now`
+    fn select(&mut self, now: Self::Instant, active: bool) -> Result<(), Self::Error>;
+
+    /// Send a byte to the selected target, and return the byte it sent back at the same time
+    fn exchange(&mut self, now: Self::Instant, byte: u8) -> Result<u8, Self::Error>;
+}
+
+/// The controller's side of an SPI style bus, with any number of targets
+pub trait SpiBus {
+    /// The type for the time that transfers occur at
+    type Instant: EmuInstant;
+
+    /// The type of an error returned by this bus
+    type Error: ErrorType;
+
+    /// Select the target with the given chip select number, or deselect all targets if `None`
+    fn select(&mut self, now: Self::Instant, target: Option<usize>) -> Result<(), Self::Error>;
+
+    /// Send a byte to the selected target, and return the byte it sent back at the same time
+    ///
+    /// If no target is selected, the data line is not driven and reads as 0xFF
+    fn exchange(&mut self, now: Self::Instant, byte: u8) -> Result<u8, Self::Error>;
+
+    /// Exchange each byte of `data` with the selected target, replacing it with the byte received
+    #[inline]
+    fn exchange_frame(&mut self, now: Self::Instant, data: &mut [u8]) -> Result<(), Self::Error> {
+        for byte in data.iter_mut() {
+            *byte = self.exchange(now, *byte)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T> SpiBus for &mut T
+where
+    T: SpiBus + ?Sized,
+{
+    type Instant = T::Instant;
+    type Error = T::Error;
+
+    #[inline]
+    fn select(&mut self, now: Self::Instant, target: Option<usize>) -> Result<(), Self::Error> {
+        T::select(self, now, target)
+    }
+
+    #[inline]
+    fn exchange(&mut self, now: Self::Instant, byte: u8) -> Result<u8, Self::Error> {
+        T::exchange(self, now, byte)
+    }
+}
+
+/// A boxed target that can be attached to an `SpiRouter`
+pub type DynSpiTarget<Instant, Error> = Box<dyn SpiTarget<Instant = Instant, Error = Error>>;
+
+/// An SPI bus which connects a controller to targets by chip select number
+///
+/// Each target attached is given the next chip select number, starting from 0.  Selecting a
+/// number without a target attached leaves the bus with no target selected
+pub struct SpiRouter<Instant, Error> {
+    targets: Vec<DynSpiTarget<Instant, Error>>,
+    selected: Option<usize>,
+}
+
+impl<Instant, Error> Default for SpiRouter<Instant, Error> {
+    fn default() -> Self {
+        Self {
+            targets: Vec::new(),
+            selected: None,
+        }
+    }
+}
+
+impl<Instant, Error> SpiRouter<Instant, Error>
+where
+    Instant: EmuInstant,
+    Error: ErrorType,
+{
+    /// Construct a new bus with no targets attached
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Attach a target to the bus, and return its chip select number
+    pub fn attach(&mut self, target: DynSpiTarget<Instant, Error>) -> usize {
+        self.targets.push(target);
+        self.targets.len() - 1
+    }
+
+    /// Returns the chip select number of the selected target, if any
+    pub fn selected(&self) -> Option<usize> {
+        self.selected
+    }
+}
+
+impl<Instant, Error> SpiBus for SpiRouter<Instant, Error>
+where
+    Instant: EmuInstant,
+    Error: ErrorType,
+{
+    type Instant = Instant;
+    type Error = Error;
+
+    fn select(&mut self, now: Self::Instant, target: Option<usize>) -> Result<(), Self::Error> {
+        let target = target.filter(|target| *target < self.targets.len());
+        if self.selected == target {
+            return Ok(());
+        }
+        if let Some(previous) = self.selected.take() {
+            self.targets[previous].select(now, false)?;
+        }
+        if let Some(target) = target {
+            self.targets[target].select(now, true)?;
+        }
+        self.selected = target;
+        Ok(())
+    }
+
+    fn exchange(&mut self, now: Self::Instant, byte: u8) -> Result<u8, Self::Error> {
+        match self.selected {
+            Some(target) => self.targets[target].exchange(now, byte),
+            None => Ok(0xFF),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::convert::Infallible;
+    use std::time::Duration;
+
+    /// A target which returns the previous byte it received, or its id after being selected
+    struct Shifter(u8, u8);
+
+    impl SpiTarget for Shifter {
+        type Instant = Duration;
+        type Error = Infallible;
+
+        fn select(&mut self, _now: Duration, active: bool) -> Result<(), Self::Error> {
+            if active {
+                self.1 = self.0;
+            }
+            Ok(())
+        }
+
+        fn exchange(&mut self, _now: Duration, byte: u8) -> Result<u8, Self::Error> {
+            Ok(core::mem::replace(&mut self.1, byte))
+        }
+    }
+
+    #[test]
+    fn test_select_and_exchange() {
+        let mut bus = SpiRouter::new();
+        assert_eq!(bus.attach(Box::new(Shifter(0xA0, 0))), 0);
+        assert_eq!(bus.attach(Box::new(Shifter(0xB0, 0))), 1);
+
+        bus.select(Duration::ZERO, Some(1)).unwrap();
+        let mut frame = [1, 2, 3];
+        bus.exchange_frame(Duration::ZERO, &mut frame).unwrap();
+        assert_eq!(frame, [0xB0, 1, 2]);
+
+        bus.select(Duration::ZERO, Some(0)).unwrap();
+        assert_eq!(bus.exchange(Duration::ZERO, 7).unwrap(), 0xA0);
+
+        bus.select(Duration::ZERO, Some(5)).unwrap();
+        assert_eq!(bus.selected(), None);
+        assert_eq!(bus.exchange(Duration::ZERO, 7).unwrap(), 0xFF);
+    }
+}
@@ -0,0 +1,176 @@
+//! Disk images stored in host files
+
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::storage::{BlockStorage, Geometry, StorageError};
+
+struct Media {
+    file: File,
+    path: PathBuf,
+    geometry: Geometry,
+    read_only: bool,
+}
+
+/// A drive that reads and writes raw disk images stored in host files
+///
+/// The image is accessed directly, so writes are saved to the file as they occur.  Images can
+/// be inserted and ejected while the system is running, as if swapping floppy disks, and the
+/// drive can be empty
+#[derive(Default)]
+pub struct DiskImage {
+    media: Option<Media>,
+    changed: bool,
+}
+
+impl DiskImage {
+    /// Construct a new drive with no media inserted
+    pub fn empty() -> Self {
+        Self::default()
+    }
+
+    /// Construct a new drive with the given image inserted
+    ///
+    /// If `read_only` is true, or the file can't be opened for writing, the media is write
+    /// protected
+    pub fn open<P: AsRef<Path>>(
+        path: P,
+        geometry: Geometry,
+        read_only: bool,
+    ) -> Result<Self, StorageError> {
+        let mut drive = Self::empty();
+        drive.insert(path, geometry, read_only)?;
+        Ok(drive)
+    }
+
+    /// Insert the given image, replacing any image already inserted
+    ///
+    /// The size of the file must match the geometry exactly
+    pub fn insert<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        geometry: Geometry,
+        read_only: bool,
+    ) -> Result<(), StorageError> {
+        let path = path.as_ref();
+        let (file, read_only) = match read_only {
+            true => (File::open(path)?, true),
+            false => match OpenOptions::new().read(true).write(true).open(path) {
+                Ok(file) => (file, false),
+                Err(_) => (File::open(path)?, true),
+            },
+        };
+        if file.metadata()?.len() != geometry.size() {
+            return Err(StorageError::InvalidImage);
+        }
+
+        self.media = Some(Media {
+            file,
+            path: path.to_path_buf(),
+            geometry,
+            read_only,
+        });
+        self.changed = true;
+        Ok(())
+    }
+
+    /// Remove the current image, if any
+    pub fn eject(&mut self) {
+        if self.media.take().is_some() {
+            self.changed = true;
+        }
+    }
+
+    /// Returns the path of the current image, if any
+    pub fn path(&self) -> Option<&Path> {
+        self.media.as_ref().map(|media| media.path.as_path())
+    }
+}
+
+impl BlockStorage for DiskImage {
+    type Error = StorageError;
+
+    fn geometry(&self) -> Option<Geometry> {
+        self.media.as_ref().map(|media| media.geometry)
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.media.as_ref().map_or(false, |media| media.read_only)
+    }
+
+    fn media_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+
+    fn read_sectors(&mut self, lba: u64, data: &mut [u8]) -> Result<(), Self::Error> {
+        let media = self.media.as_mut().ok_or(StorageError::NoMedia)?;
+        let offset = media.geometry.transfer_offset(lba, data.len())?;
+        media.file.seek(SeekFrom::Start(offset))?;
+        media.file.read_exact(data)?;
+        Ok(())
+    }
+
+    fn write_sectors(&mut self, lba: u64, data: &[u8]) -> Result<(), Self::Error> {
+        let media = self.media.as_mut().ok_or(StorageError::NoMedia)?;
+        if media.read_only {
+            return Err(StorageError::ReadOnly);
+        }
+        let offset = media.geometry.transfer_offset(lba, data.len())?;
+        media.file.seek(SeekFrom::Start(offset))?;
+        media.file.write_all(data)?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        if let Some(media) = self.media.as_mut() {
+            media.file.flush()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_disk_image() {
+        let path = std::env::temp_dir().join("emulator-hal-storage-image.img");
+        std::fs::write(&path, vec![0; 4 * 128]).unwrap();
+        let geometry = Geometry::new(2, 1, 2, 128);
+
+        let mut drive = DiskImage::empty();
+        assert!(matches!(
+            drive.read_sectors(0, &mut [0; 128]),
+            Err(StorageError::NoMedia)
+        ));
+        drive.insert(&path, geometry, false).unwrap();
+        assert!(drive.media_changed());
+        assert!(!drive.media_changed());
+
+        drive.write_sectors(2, &[0xAA; 256]).unwrap();
+        let mut data = [0; 128];
+        drive.read_sectors(3, &mut data).unwrap();
+        assert_eq!(data, [0xAA; 128]);
+        assert!(matches!(
+            drive.read_sectors(3, &mut [0; 256]),
+            Err(StorageError::OutOfRange(3))
+        ));
+        assert!(matches!(
+            drive.read_sectors(0, &mut [0; 100]),
+            Err(StorageError::InvalidLength(100))
+        ));
+
+        drive.insert(&path, geometry, true).unwrap();
+        assert!(drive.media_changed());
+        assert!(matches!(
+            drive.write_sectors(0, &[0; 128]),
+            Err(StorageError::ReadOnly)
+        ));
+        drive.eject();
+        assert!(drive.media_changed() && drive.geometry().is_none());
+
+        assert_eq!(std::fs::read(&path).unwrap()[256..], [0xAA; 256]);
+    }
+}
@@ -0,0 +1,24 @@
+#![doc = include_str!("../README.md")]
+#![warn(missing_docs)]
+#![cfg_attr(all(not(feature = "std"), not(test)), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "std")]
+mod image;
+#[cfg(feature = "std")]
+pub use crate::image::*;
+
+#[cfg(feature = "alloc")]
+mod memory;
+#[cfg(feature = "alloc")]
+pub use crate::memory::*;
+
+#[cfg(feature = "alloc")]
+mod overlay;
+#[cfg(feature = "alloc")]
+pub use crate::overlay::*;
+
+mod storage;
+pub use crate::storage::*;
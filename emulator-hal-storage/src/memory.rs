@@ -0,0 +1,118 @@
+//! Disk images stored in memory
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::storage::{BlockStorage, Geometry, StorageError};
+
+/// A disk whose contents are stored in memory
+///
+/// This is useful for RAM disks, for tests, and for images that are loaded from somewhere other
+/// than a file, such as an archive or a network connection.  The media is always present, and
+/// its contents can be taken back out with `into_inner()` to be saved
+#[derive(Clone, Debug)]
+pub struct MemoryDisk {
+    data: Vec<u8>,
+    geometry: Geometry,
+    read_only: bool,
+}
+
+impl MemoryDisk {
+    /// Construct a new disk with the given geometry, filled with zeros
+    pub fn new(geometry: Geometry) -> Self {
+        Self {
+            data: vec![0; geometry.size() as usize],
+            geometry,
+            read_only: false,
+        }
+    }
+
+    /// Construct a new disk with the given contents, whose size must match the geometry exactly
+    pub fn from_vec(data: Vec<u8>, geometry: Geometry) -> Result<Self, StorageError> {
+        if data.len() as u64 != geometry.size() {
+            return Err(StorageError::InvalidImage);
+        }
+        Ok(Self {
+            data,
+            geometry,
+            read_only: false,
+        })
+    }
+
+    /// Set whether the disk is write protected
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    /// Returns the contents of the disk
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Consume the disk and return its contents
+    pub fn into_inner(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+impl BlockStorage for MemoryDisk {
+    type Error = StorageError;
+
+    fn geometry(&self) -> Option<Geometry> {
+        Some(self.geometry)
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    fn media_changed(&mut self) -> bool {
+        false
+    }
+
+    fn read_sectors(&mut self, lba: u64, data: &mut [u8]) -> Result<(), Self::Error> {
+        let offset = self.geometry.transfer_offset(lba, data.len())? as usize;
+        data.copy_from_slice(&self.data[offset..offset + data.len()]);
+        Ok(())
+    }
+
+    fn write_sectors(&mut self, lba: u64, data: &[u8]) -> Result<(), Self::Error> {
+        if self.read_only {
+            return Err(StorageError::ReadOnly);
+        }
+        let offset = self.geometry.transfer_offset(lba, data.len())? as usize;
+        self.data[offset..offset + data.len()].copy_from_slice(data);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_memory_disk() {
+        let geometry = Geometry::linear(4, 16);
+        assert!(matches!(
+            MemoryDisk::from_vec(vec![0; 32], geometry),
+            Err(StorageError::InvalidImage)
+        ));
+
+        let mut disk = MemoryDisk::new(geometry);
+        disk.write_sectors(1, &[0x55; 32]).unwrap();
+        let mut data = [0; 16];
+        disk.read_sectors(2, &mut data).unwrap();
+        assert_eq!(data, [0x55; 16]);
+        assert!(matches!(
+            disk.read_sectors(4, &mut data),
+            Err(StorageError::OutOfRange(4))
+        ));
+
+        disk.set_read_only(true);
+        assert!(matches!(
+            disk.write_sectors(0, &data),
+            Err(StorageError::ReadOnly)
+        ));
+        assert_eq!(disk.into_inner()[16..48], [0x55; 32]);
+    }
+}
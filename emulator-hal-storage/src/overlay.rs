@@ -0,0 +1,137 @@
+//! A copy-on-write overlay for block storage
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+use crate::storage::{BlockStorage, Geometry, StorageError};
+
+/// A copy-on-write overlay, which keeps writes in memory instead of modifying the storage below
+///
+/// Sectors that have been written are read back from the overlay, and all others are read from
+/// the inner storage, so the guest sees a writable disk while the original image is left
+/// untouched.  This allows a read-only or shared image to be used as a writable disk, and
+/// changes to be thrown away with `discard()` or saved with `commit()`.  The overlay is
+/// discarded when the inner storage reports that its media has changed, since the written
+/// sectors belonged to the old media
+pub struct CopyOnWrite<Storage> {
+    /// The underlying storage that this overlay is above
+    pub inner: Storage,
+    sectors: BTreeMap<u64, Vec<u8>>,
+}
+
+impl<Storage> CopyOnWrite<Storage>
+where
+    Storage: BlockStorage,
+    Storage::Error: From<StorageError>,
+{
+    /// Construct a new overlay above the given storage, with no sectors written
+    pub fn new(inner: Storage) -> Self {
+        Self {
+            inner,
+            sectors: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the number of sectors that have been written to the overlay
+    pub fn dirty_sectors(&self) -> usize {
+        self.sectors.len()
+    }
+
+    /// Throw away all of the sectors that have been written
+    pub fn discard(&mut self) {
+        self.sectors.clear();
+    }
+
+    /// Write all of the sectors in the overlay to the inner storage, and then clear the overlay
+    ///
+    /// If a write fails, the sectors that haven't been written remain in the overlay
+    pub fn commit(&mut self) -> Result<(), Storage::Error> {
+        while let Some((&lba, data)) = self.sectors.iter().next() {
+            self.inner.write_sectors(lba, data)?;
+            self.sectors.remove(&lba);
+        }
+        self.inner.flush()
+    }
+
+    fn checked_geometry(&self, length: usize) -> Result<Geometry, Storage::Error> {
+        let geometry = self.inner.geometry().ok_or(StorageError::NoMedia)?;
+        if geometry.sector_size == 0 || length % geometry.sector_size != 0 {
+            return Err(StorageError::InvalidLength(length).into());
+        }
+        Ok(geometry)
+    }
+}
+
+impl<Storage> BlockStorage for CopyOnWrite<Storage>
+where
+    Storage: BlockStorage,
+    Storage::Error: From<StorageError>,
+{
+    type Error = Storage::Error;
+
+    fn geometry(&self) -> Option<Geometry> {
+        self.inner.geometry()
+    }
+
+    fn is_read_only(&self) -> bool {
+        false
+    }
+
+    fn media_changed(&mut self) -> bool {
+        let changed = self.inner.media_changed();
+        if changed {
+            self.discard();
+        }
+        changed
+    }
+
+    fn read_sectors(&mut self, lba: u64, data: &mut [u8]) -> Result<(), Self::Error> {
+        let geometry = self.checked_geometry(data.len())?;
+        geometry.transfer_offset(lba, data.len())?;
+        for (sector, chunk) in (lba..).zip(data.chunks_mut(geometry.sector_size)) {
+            match self.sectors.get(&sector) {
+                Some(written) => chunk.copy_from_slice(written),
+                None => self.inner.read_sectors(sector, chunk)?,
+            }
+        }
+        Ok(())
+    }
+
+    fn write_sectors(&mut self, lba: u64, data: &[u8]) -> Result<(), Self::Error> {
+        let geometry = self.checked_geometry(data.len())?;
+        geometry.transfer_offset(lba, data.len())?;
+        for (sector, chunk) in (lba..).zip(data.chunks(geometry.sector_size)) {
+            self.sectors.insert(sector, chunk.to_vec());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::memory::MemoryDisk;
+
+    #[test]
+    fn test_copy_on_write() {
+        let mut base = MemoryDisk::from_vec((0..64).collect(), Geometry::linear(4, 16)).unwrap();
+        base.set_read_only(true);
+        let mut disk = CopyOnWrite::new(base);
+
+        disk.write_sectors(1, &[0xFF; 16]).unwrap();
+        let mut data = [0; 48];
+        disk.read_sectors(0, &mut data).unwrap();
+        assert_eq!(data[..16], (0..16).collect::<Vec<u8>>()[..]);
+        assert_eq!(data[16..32], [0xFF; 16]);
+        assert_eq!(data[32..], (32..48).collect::<Vec<u8>>()[..]);
+        assert_eq!(disk.inner.data()[16], 16);
+        assert_eq!(disk.dirty_sectors(), 1);
+
+        // the base image is read-only, so the commit fails and the overlay is kept
+        assert!(matches!(disk.commit(), Err(StorageError::ReadOnly)));
+        disk.inner.set_read_only(false);
+        disk.commit().unwrap();
+        assert_eq!(disk.dirty_sectors(), 0);
+        assert_eq!(disk.inner.data()[16..32], [0xFF; 16]);
+    }
+}
@@ -0,0 +1,212 @@
+//! The interface between disk controller devices and the media they access
+
+use emulator_hal::ErrorType;
+
+/// An error that occurred while accessing block storage
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum StorageError {
+    /// There is no media present
+    NoMedia,
+    /// The sector is past the end of the media
+    OutOfRange(u64),
+    /// The media is write protected
+    ReadOnly,
+    /// The length of the data is not a multiple of the sector size
+    InvalidLength(usize),
+    /// The size of the image doesn't match the geometry given for it
+    InvalidImage,
+    /// An error occurred while accessing the host's file
+    #[cfg(feature = "std")]
+    Io(std::io::Error),
+}
+
+impl ErrorType for StorageError {}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for StorageError {
+    fn from(err: std::io::Error) -> Self {
+        StorageError::Io(err)
+    }
+}
+
+/// The physical layout of a disk
+///
+/// Sectors are addressed by their logical block address (LBA), starting from 0.  Controllers
+/// that address sectors by cylinder, head, and sector can convert with `chs_to_lba()`, where
+/// sectors are numbered from 1 as they are on real disks
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Geometry {
+    /// The number of cylinders (or tracks per side)
+    pub cylinders: u32,
+    /// The number of heads (or sides)
+    pub heads: u32,
+    /// The number of sectors on each track
+    pub sectors_per_track: u32,
+    /// The number of bytes in each sector
+    pub sector_size: usize,
+}
+
+impl Geometry {
+    /// Construct a new geometry
+    pub const fn new(
+        cylinders: u32,
+        heads: u32,
+        sectors_per_track: u32,
+        sector_size: usize,
+    ) -> Self {
+        Self {
+            cylinders,
+            heads,
+            sectors_per_track,
+            sector_size,
+        }
+    }
+
+    /// Construct a geometry for media that is only addressed linearly, such as a flash card
+    pub const fn linear(sectors: u32, sector_size: usize) -> Self {
+        Self::new(1, 1, sectors, sector_size)
+    }
+
+    /// Returns the total number of sectors
+    pub const fn sectors(&self) -> u64 {
+        self.cylinders as u64 * self.heads as u64 * self.sectors_per_track as u64
+    }
+
+    /// Returns the total number of bytes
+    pub const fn size(&self) -> u64 {
+        self.sectors() * self.sector_size as u64
+    }
+
+    /// Returns the logical block address of the given cylinder, head, and sector, or `None` if
+    /// it's outside of the geometry
+    pub fn chs_to_lba(&self, cylinder: u32, head: u32, sector: u32) -> Option<u64> {
+        if cylinder >= self.cylinders
+            || head >= self.heads
+            || sector == 0
+            || sector > self.sectors_per_track
+        {
+            return None;
+        }
+        let track = cylinder as u64 * self.heads as u64 + head as u64;
+        Some(track * self.sectors_per_track as u64 + (sector - 1) as u64)
+    }
+
+    /// Returns the cylinder, head, and sector of the given logical block address, or `None` if
+    /// it's outside of the geometry
+    pub fn lba_to_chs(&self, lba: u64) -> Option<(u32, u32, u32)> {
+        if lba >= self.sectors() {
+            return None;
+        }
+        let track = lba / self.sectors_per_track as u64;
+        let sector = (lba % self.sectors_per_track as u64) as u32 + 1;
+        let head = (track % self.heads as u64) as u32;
+        let cylinder = (track / self.heads as u64) as u32;
+        Some((cylinder, head, sector))
+    }
+
+    /// Returns the byte offset of a transfer of `length` bytes starting at the given logical
+    /// block address, after checking that it's a whole number of sectors within the geometry
+    pub fn transfer_offset(&self, lba: u64, length: usize) -> Result<u64, StorageError> {
+        if self.sector_size == 0 || length % self.sector_size != 0 {
+            return Err(StorageError::InvalidLength(length));
+        }
+        let count = (length / self.sector_size) as u64;
+        match lba.checked_add(count) {
+            Some(end) if end <= self.sectors() => Ok(lba * self.sector_size as u64),
+            _ => Err(StorageError::OutOfRange(lba)),
+        }
+    }
+}
+
+/// A sector-addressed storage medium, such as a floppy disk or hard drive
+///
+/// Transfers are made in whole sectors, so the length of the data must be a multiple of the
+/// sector size.  The media in a drive can be removed or replaced while the system is running,
+/// which controllers can detect with `media_changed()`
+pub trait BlockStorage {
+    /// The type of an error returned by this storage
+    type Error: ErrorType;
+
+    /// Returns the geometry of the current media, or `None` if there is no media present
+    fn geometry(&self) -> Option<Geometry>;
+
+    /// Returns true if the current media cannot be written
+    fn is_read_only(&self) -> bool;
+
+    /// Returns true if the media has been inserted, removed, or replaced since the last call
+    fn media_changed(&mut self) -> bool;
+
+    /// Read consecutive sectors starting at the given logical block address
+    fn read_sectors(&mut self, lba: u64, data: &mut [u8]) -> Result<(), Self::Error>;
+
+    /// Write consecutive sectors starting at the given logical block address
+    fn write_sectors(&mut self, lba: u64, data: &[u8]) -> Result<(), Self::Error>;
+
+    /// Save any writes that have been buffered to the media
+    ///
+    /// Controllers should call this when the guest flushes its cache or the system is shut
+    /// down.  The default implementation does nothing, for storage that isn't buffered
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+impl<T> BlockStorage for &mut T
+where
+    T: BlockStorage + ?Sized,
+{
+    type Error = T::Error;
+
+    #[inline]
+    fn geometry(&self) -> Option<Geometry> {
+        T::geometry(self)
+    }
+
+    #[inline]
+    fn is_read_only(&self) -> bool {
+        T::is_read_only(self)
+    }
+
+    #[inline]
+    fn media_changed(&mut self) -> bool {
+        T::media_changed(self)
+    }
+
+    #[inline]
+    fn read_sectors(&mut self, lba: u64, data: &mut [u8]) -> Result<(), Self::Error> {
+        T::read_sectors(self, lba, data)
+    }
+
+    #[inline]
+    fn write_sectors(&mut self, lba: u64, data: &[u8]) -> Result<(), Self::Error> {
+        T::write_sectors(self, lba, data)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        T::flush(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_chs_conversion() {
+        // a 1.44MB floppy
+        let geometry = Geometry::new(80, 2, 18, 512);
+        assert_eq!(geometry.size(), 1_474_560);
+
+        assert_eq!(geometry.chs_to_lba(0, 0, 1), Some(0));
+        assert_eq!(geometry.chs_to_lba(0, 1, 1), Some(18));
+        assert_eq!(geometry.chs_to_lba(1, 0, 3), Some(38));
+        assert_eq!(geometry.chs_to_lba(0, 0, 0), None);
+        assert_eq!(geometry.chs_to_lba(80, 0, 1), None);
+
+        assert_eq!(geometry.lba_to_chs(38), Some((1, 0, 3)));
+        assert_eq!(geometry.lba_to_chs(2879), Some((79, 1, 18)));
+        assert_eq!(geometry.lba_to_chs(2880), None);
+    }
+}
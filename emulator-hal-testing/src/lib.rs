@@ -0,0 +1,5 @@
+#![doc = include_str!("../README.md")]
+#![warn(missing_docs)]
+
+mod mock;
+pub use crate::mock::*;
@@ -0,0 +1,257 @@
+//! A mock bus that checks the transactions issued to it against a list of expectations
+
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::marker::PhantomData;
+use std::thread;
+
+use emulator_hal::{BasicBusError, BusAccess, BusOperation, Instant as EmuInstant};
+
+/// A bus transaction that a `MockBus` expects the device under test to make
+#[derive(Debug)]
+pub struct Expectation<Address> {
+    /// Whether the transaction is expected to be a read or a write
+    pub operation: BusOperation,
+    /// The address the transaction is expected to access
+    pub addr: Address,
+    /// The data returned by a read, or expected to be written by a write
+    pub data: Vec<u8>,
+    /// The error that the transaction will fail with instead, if any
+    pub error: Option<BasicBusError>,
+}
+
+impl<Address> Expectation<Address> {
+    /// Return the given data for this read, which must be the same length as the read
+    pub fn returns(&mut self, data: &[u8]) -> &mut Self {
+        self.data = data.to_vec();
+        self
+    }
+
+    /// Fail this transaction with the given error, instead of returning or writing any data
+    ///
+    /// The address is still checked, but not the length or the data
+    pub fn fails(&mut self, error: BasicBusError) -> &mut Self {
+        self.error = Some(error);
+        self
+    }
+}
+
+/// A bus that panics if the transactions issued to it don't exactly match a list of expectations
+///
+/// Expectations are added with `expect_read()` and `expect_write()`, and must be met in the
+/// order they were added.  A read must match the address and length of the next expectation,
+/// and is given the data from `returns()`.  A write must match the address and data of the next
+/// expectation.  Accesses made with `peek()`, `poke()`, and `read_modify_write()` are checked as
+/// the reads and writes they're made of by default.
+///
+/// The mock panics with a description of the mismatch as soon as an unexpected transaction is
+/// made, so it's meant to be used in tests.  Any expectations that haven't been met when the mock
+/// is dropped will also cause a panic, which can be checked earlier by calling `done()`
+pub struct MockBus<Address, Instant>
+where
+    Address: Debug,
+{
+    expectations: VecDeque<Expectation<Address>>,
+    position: usize,
+    instant: PhantomData<Instant>,
+}
+
+impl<Address, Instant> Default for MockBus<Address, Instant>
+where
+    Address: Debug,
+{
+    fn default() -> Self {
+        Self {
+            expectations: VecDeque::new(),
+            position: 0,
+            instant: PhantomData,
+        }
+    }
+}
+
+impl<Address, Instant> MockBus<Address, Instant>
+where
+    Address: Debug,
+{
+    /// Construct a new mock bus without any expectations
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Expect the next unmet transaction to be a read of the given address
+    ///
+    /// The data to return should be set by calling `returns()` on the expectation
+    pub fn expect_read(&mut self, addr: Address) -> &mut Expectation<Address> {
+        self.push(BusOperation::Read, addr, Vec::new())
+    }
+
+    /// Expect the next unmet transaction to be a write of the given data to the given address
+    pub fn expect_write(&mut self, addr: Address, data: &[u8]) -> &mut Expectation<Address> {
+        self.push(BusOperation::Write, addr, data.to_vec())
+    }
+
+    /// Returns the number of expectations which haven't been met yet
+    pub fn remaining(&self) -> usize {
+        self.expectations.len()
+    }
+
+    /// Check that every expectation has been met
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of the expectations haven't been met, and removes them so that the mock
+    /// doesn't panic again when it's dropped
+    pub fn done(&mut self) {
+        let remaining: Vec<_> = self.expectations.drain(..).collect();
+        if !remaining.is_empty() {
+            panic!(
+                "mock bus has {} unmet expectations, starting at #{}: {:?}",
+                remaining.len(),
+                self.position,
+                remaining
+            );
+        }
+    }
+
+    fn push(
+        &mut self,
+        operation: BusOperation,
+        addr: Address,
+        data: Vec<u8>,
+    ) -> &mut Expectation<Address> {
+        self.expectations.push_back(Expectation {
+            operation,
+            addr,
+            data,
+            error: None,
+        });
+        self.expectations.back_mut().unwrap()
+    }
+
+    fn next_expectation(
+        &mut self,
+        operation: BusOperation,
+        addr: Address,
+        len: usize,
+    ) -> Expectation<Address>
+    where
+        Address: PartialEq,
+    {
+        let position = self.position;
+        self.position += 1;
+        let expected = match self.expectations.pop_front() {
+            Some(expected) => expected,
+            None => panic!(
+                "mock bus got unexpected {:?} of {} bytes at {:?} (#{}), after all expectations were met",
+                operation, len, addr, position
+            ),
+        };
+        if expected.operation != operation
+            || expected.addr != addr
+            || (expected.error.is_none() && expected.data.len() != len)
+        {
+            panic!(
+                "mock bus got {:?} of {} bytes at {:?} (#{}), but expected {:?}",
+                operation, len, addr, position, expected
+            );
+        }
+        expected
+    }
+}
+
+impl<Address, Instant> Drop for MockBus<Address, Instant>
+where
+    Address: Debug,
+{
+    fn drop(&mut self) {
+        // avoid panicking again if the test has already failed
+        if !thread::panicking() {
+            self.done();
+        }
+    }
+}
+
+impl<Address, Instant> BusAccess<Address> for MockBus<Address, Instant>
+where
+    Address: Copy + PartialEq + Debug,
+    Instant: EmuInstant,
+{
+    type Instant = Instant;
+    type Error = BasicBusError;
+
+    fn read(
+        &mut self,
+        _now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        let expected = self.next_expectation(BusOperation::Read, addr, data.len());
+        if let Some(error) = expected.error {
+            return Err(error);
+        }
+        data.copy_from_slice(&expected.data);
+        Ok(data.len())
+    }
+
+    fn write(
+        &mut self,
+        _now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        let position = self.position;
+        let expected = self.next_expectation(BusOperation::Write, addr, data.len());
+        if let Some(error) = expected.error {
+            return Err(error);
+        }
+        if expected.data != data {
+            panic!(
+                "mock bus got Write of {:?} at {:?} (#{}), but expected {:?}",
+                data, addr, position, expected.data
+            );
+        }
+        Ok(data.len())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use emulator_hal::BusAccessExt;
+    use std::time::Duration;
+
+    #[test]
+    fn test_expectations_met() {
+        let now = Duration::ZERO;
+        let mut bus = MockBus::<u32, Duration>::new();
+        bus.expect_read(0x10).returns(&[0x12, 0x34]);
+        bus.expect_write(0x20, &[0x12, 0x34]);
+        bus.expect_read(0x30).fails(BasicBusError::UnmappedAddress);
+        assert_eq!(bus.remaining(), 3);
+
+        let value = bus.read_beu16(now, 0x10).unwrap();
+        bus.write_beu16(now, 0x20, value).unwrap();
+        assert!(matches!(
+            bus.read_beu32(now, 0x30),
+            Err(BasicBusError::UnmappedAddress)
+        ));
+        bus.done();
+    }
+
+    #[test]
+    #[should_panic(expected = "but expected")]
+    fn test_mismatched_write() {
+        let mut bus = MockBus::<u32, Duration>::new();
+        bus.expect_write(0x20, &[0x12, 0x34]);
+        bus.write_beu16(Duration::ZERO, 0x20, 0x1235).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "unmet expectations")]
+    fn test_leftover_expectations() {
+        let mut bus = MockBus::<u32, Duration>::new();
+        bus.expect_read(0x10).returns(&[0]);
+        bus.expect_read(0x11).returns(&[0]);
+        bus.read_u8(Duration::ZERO, 0x10).unwrap();
+    }
+}
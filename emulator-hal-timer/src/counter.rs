@@ -0,0 +1,343 @@
+//! A multi-channel timer/counter peripheral
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::convert::Infallible;
+
+use emulator_hal::{BasicBusError, BusAccess, Instant as EmuInstant, Signal, Step};
+
+/// The number of bytes of address space used by each channel's registers
+pub const CHANNEL_REGISTERS: usize = 8;
+
+/// Control register bit which enables counting
+pub const CONTROL_ENABLE: u8 = 0x01;
+/// Control register bit which resets the counter and keeps counting after a compare match,
+/// instead of disabling the channel
+pub const CONTROL_RELOAD: u8 = 0x02;
+/// Control register bit which raises the interrupt output while the channel's match flag is set
+pub const CONTROL_INTERRUPT: u8 = 0x04;
+/// Control register bit which clocks the channel from the previous channel's compare matches,
+/// instead of from its prescaler
+pub const CONTROL_CASCADE: u8 = 0x08;
+
+/// Status register bit which is set on a compare match, and is cleared by writing a 1 to it
+pub const STATUS_MATCH: u8 = 0x01;
+
+#[derive(Clone, Debug, Default)]
+struct Channel {
+    control: u8,
+    prescaler: u8,
+    prescale_count: u8,
+    counter: u16,
+    compare: u16,
+    matched: bool,
+}
+
+impl Channel {
+    fn enabled(&self) -> bool {
+        self.control & CONTROL_ENABLE != 0
+    }
+
+    /// Advance the prescaler by one input clock, and return true if the counter should count
+    fn prescale(&mut self) -> bool {
+        if self.prescale_count >= self.prescaler {
+            self.prescale_count = 0;
+            true
+        } else {
+            self.prescale_count += 1;
+            false
+        }
+    }
+
+    /// Advance the counter by one, and return true if it matched the compare value
+    fn count(&mut self) -> bool {
+        self.counter = self.counter.wrapping_add(1);
+        if self.counter != self.compare {
+            return false;
+        }
+
+        self.matched = true;
+        if self.control & CONTROL_RELOAD != 0 {
+            self.counter = 0;
+        } else {
+            self.control &= !CONTROL_ENABLE;
+        }
+        true
+    }
+
+    fn read(&self, register: usize) -> u8 {
+        match register {
+            0 => self.control,
+            1 => self.prescaler,
+            2 => (self.counter >> 8) as u8,
+            3 => self.counter as u8,
+            4 => (self.compare >> 8) as u8,
+            5 => self.compare as u8,
+            6 if self.matched => STATUS_MATCH,
+            _ => 0,
+        }
+    }
+
+    fn write(&mut self, register: usize, value: u8) {
+        match register {
+            0 => self.control = value,
+            1 => {
+                self.prescaler = value;
+                self.prescale_count = 0;
+            }
+            2 => self.counter = (self.counter & 0x00FF) | ((value as u16) << 8),
+            3 => self.counter = (self.counter & 0xFF00) | value as u16,
+            4 => self.compare = (self.compare & 0x00FF) | ((value as u16) << 8),
+            5 => self.compare = (self.compare & 0xFF00) | value as u16,
+            6 if value & STATUS_MATCH != 0 => self.matched = false,
+            _ => {}
+        }
+    }
+}
+
+/// A timer/counter with any number of 16-bit channels, and a shared interrupt output
+///
+/// Each step of the timer is one cycle of its input clock.  A channel's counter counts up once
+/// every `prescaler + 1` input clocks, or once for each compare match of the previous channel if
+/// it's cascaded.  When the counter reaches the compare value, the channel's match flag is set,
+/// and the counter is either reset to 0 or the channel is disabled, depending on the reload bit.
+/// A compare value of 0 matches when the counter wraps around, after 65536 counts.
+///
+/// Each channel occupies `CHANNEL_REGISTERS` bytes of the address space, starting at
+/// `channel * CHANNEL_REGISTERS`, with the 16-bit registers in big endian order:
+///
+/// | Offset | Register |
+/// |--------|----------|
+/// | 0      | control (`CONTROL_*` bits) |
+/// | 1      | prescaler |
+/// | 2-3    | counter |
+/// | 4-5    | compare/reload value |
+/// | 6      | status (`STATUS_*` bits) |
+///
+/// The `interrupt` line is active while any channel with `CONTROL_INTERRUPT` set has its match
+/// flag set
+pub struct TimerCounter<Instant>
+where
+    Instant: EmuInstant,
+{
+    channels: Vec<Channel>,
+    /// The period of the timer's input clock
+    pub clock_period: Instant::Duration,
+    /// The interrupt output line
+    pub interrupt: Signal,
+}
+
+impl<Instant> TimerCounter<Instant>
+where
+    Instant: EmuInstant,
+{
+    /// Construct a new timer with the given number of channels, and the given input clock period
+    pub fn new(channels: usize, clock_period: Instant::Duration) -> Self {
+        Self {
+            channels: vec![Channel::default(); channels],
+            clock_period,
+            interrupt: Signal::new(),
+        }
+    }
+
+    /// Returns the number of channels in the timer
+    pub fn channels(&self) -> usize {
+        self.channels.len()
+    }
+
+    /// Returns the current value of the given channel's counter
+    pub fn counter(&self, channel: usize) -> u16 {
+        self.channels[channel].counter
+    }
+
+    /// Advance the timer by one cycle of its input clock
+    pub fn tick(&mut self) {
+        let mut carry = false;
+        for channel in self.channels.iter_mut() {
+            let clocked = if channel.control & CONTROL_CASCADE != 0 {
+                carry
+            } else {
+                channel.enabled() && channel.prescale()
+            };
+            carry = channel.enabled() && clocked && channel.count();
+        }
+        self.update_interrupt();
+    }
+
+    fn update_interrupt(&self) {
+        let active = self
+            .channels
+            .iter()
+            .any(|channel| channel.matched && channel.control & CONTROL_INTERRUPT != 0);
+        self.interrupt.set(active);
+    }
+
+    fn register<Address>(&self, addr: Address) -> Result<(usize, usize), BasicBusError>
+    where
+        Address: TryInto<usize>,
+    {
+        let addr = addr
+            .try_into()
+            .map_err(|_| BasicBusError::UnmappedAddress)?;
+        let channel = addr / CHANNEL_REGISTERS;
+        if channel >= self.channels.len() {
+            return Err(BasicBusError::UnmappedAddress);
+        }
+        Ok((channel, addr % CHANNEL_REGISTERS))
+    }
+}
+
+impl<Address, Instant> BusAccess<Address> for TimerCounter<Instant>
+where
+    Address: TryInto<usize> + Copy,
+    Instant: EmuInstant,
+{
+    type Instant = Instant;
+    type Error = BasicBusError;
+
+    fn read(
+        &mut self,
+        _now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        let (channel, register) = self.register(addr)?;
+        for (i, byte) in data.iter_mut().enumerate() {
+            let (channel, register) = next_register(channel, register, i);
+            *byte = self
+                .channels
+                .get(channel)
+                .map(|channel| channel.read(register))
+                .ok_or(BasicBusError::UnmappedAddress)?;
+        }
+        Ok(data.len())
+    }
+
+    fn write(
+        &mut self,
+        _now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        let (channel, register) = self.register(addr)?;
+        for (i, byte) in data.iter().enumerate() {
+            let (channel, register) = next_register(channel, register, i);
+            self.channels
+                .get_mut(channel)
+                .ok_or(BasicBusError::UnmappedAddress)?
+                .write(register, *byte);
+        }
+        self.update_interrupt();
+        Ok(data.len())
+    }
+}
+
+/// Returns the channel and register that is `offset` bytes past the given register
+fn next_register(channel: usize, register: usize, offset: usize) -> (usize, usize) {
+    let register = register + offset;
+    (
+        channel + register / CHANNEL_REGISTERS,
+        register % CHANNEL_REGISTERS,
+    )
+}
+
+impl<Address, Bus, Instant> Step<Address, Bus> for TimerCounter<Instant>
+where
+    Address: Copy,
+    Bus: BusAccess<Address, Instant = Instant>,
+    Instant: EmuInstant,
+    Instant::Duration: Copy,
+{
+    type Error = Infallible;
+
+    fn is_running(&mut self) -> bool {
+        true
+    }
+
+    fn reset(&mut self, _now: Instant, _bus: &mut Bus) -> Result<(), Self::Error> {
+        self.channels.fill(Channel::default());
+        self.interrupt.lower();
+        Ok(())
+    }
+
+    fn step(&mut self, now: Instant, _bus: &mut Bus) -> Result<Instant, Self::Error> {
+        self.tick();
+        Ok(now + self.clock_period)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use emulator_hal::BusAccessExt;
+    use emulator_hal::NoBus;
+    use std::time::Duration;
+
+    fn timer() -> TimerCounter<Duration> {
+        TimerCounter::new(2, Duration::from_nanos(100))
+    }
+
+    #[test]
+    fn test_periodic_interrupt() {
+        let mut timer = timer();
+        let mut bus = NoBus::new();
+        let irq = timer.interrupt.clone();
+
+        // divide the clock by 2, and match every 3 counts
+        timer.write_u8(Duration::ZERO, 1_u32, 1).unwrap();
+        timer.write_beu16(Duration::ZERO, 4_u32, 3).unwrap();
+        timer
+            .write_u8(
+                Duration::ZERO,
+                0_u32,
+                CONTROL_ENABLE | CONTROL_RELOAD | CONTROL_INTERRUPT,
+            )
+            .unwrap();
+
+        let mut now = Duration::ZERO;
+        for _ in 0..5 {
+            now = Step::<u32, _>::step(&mut timer, now, &mut bus).unwrap();
+            assert!(!irq.get());
+        }
+        now = Step::<u32, _>::step(&mut timer, now, &mut bus).unwrap();
+        assert!(irq.get());
+        assert_eq!(now, Duration::from_nanos(600));
+        assert_eq!(timer.counter(0), 0);
+
+        timer.write_u8(now, 6_u32, STATUS_MATCH).unwrap();
+        assert!(!irq.get());
+        assert_eq!(
+            timer.read_u8(now, 0_u32).unwrap() & CONTROL_ENABLE,
+            CONTROL_ENABLE
+        );
+    }
+
+    #[test]
+    fn test_one_shot_and_cascade() {
+        let mut timer = timer();
+
+        timer.write_beu16(Duration::ZERO, 4_u32, 2).unwrap();
+        timer
+            .write_u8(Duration::ZERO, 0_u32, CONTROL_ENABLE | CONTROL_RELOAD)
+            .unwrap();
+        timer.write_beu16(Duration::ZERO, 12_u32, 3).unwrap();
+        timer
+            .write_u8(Duration::ZERO, 8_u32, CONTROL_ENABLE | CONTROL_CASCADE)
+            .unwrap();
+
+        for _ in 0..6 {
+            timer.tick();
+        }
+        assert_eq!(timer.read_u8(Duration::ZERO, 14_u32).unwrap(), STATUS_MATCH);
+        // the second channel is one-shot, so it stops after matching
+        assert_eq!(
+            timer.read_u8(Duration::ZERO, 8_u32).unwrap(),
+            CONTROL_CASCADE
+        );
+        for _ in 0..6 {
+            timer.tick();
+        }
+        assert_eq!(timer.counter(1), 3);
+        assert!(timer.read_u8(Duration::ZERO, 16_u32).is_err());
+    }
+}
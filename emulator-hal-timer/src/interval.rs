@@ -0,0 +1,374 @@
+//! A simple interval timer for generating periodic interrupts
+
+use core::convert::Infallible;
+
+use emulator_hal::{
+    BasicBusError, BusAccess, Instant as EmuInstant, Interrupt, InterruptSource, Signal, Step,
+};
+
+/// The offset of the control register
+pub const INTERVAL_CONTROL: usize = 0;
+/// The offset of the status register
+pub const INTERVAL_STATUS: usize = 1;
+/// The offset of the 16-bit interval register, in big endian order
+pub const INTERVAL_PERIOD: usize = 2;
+/// The offset of the prescaler register, which divides the input clock by its value plus one
+pub const INTERVAL_PRESCALER: usize = 4;
+
+/// Control register bit which starts the timer from the full interval when it's written as 1
+pub const INTERVAL_ENABLE: u8 = 0x01;
+/// Control register bit which restarts the timer each time it expires, instead of stopping it
+pub const INTERVAL_PERIODIC: u8 = 0x02;
+/// Control register bit which raises the interrupt output while the expired flag is set
+pub const INTERVAL_INTERRUPT: u8 = 0x04;
+
+/// Status register bit which is set when the timer expires, and is cleared by writing a 1 to it
+pub const INTERVAL_EXPIRED: u8 = 0x01;
+
+/// An interval timer which raises an interrupt after a programmed number of input clocks, either
+/// once or periodically
+///
+/// This is a simplified version of the timers found in chips like the 8253 or 6522, meant to be
+/// the periodic tick source of a system.  Rather than counting every input clock, the timer
+/// calculates when it will next expire, and returns that time from `step()`, so it's only
+/// stepped once per interval while it's running.  An interval of 0 is treated as 65536 counts,
+/// and each count takes the prescaler value plus one input clocks.
+///
+/// | Offset | Register |
+/// |--------|----------|
+/// | 0      | control (`INTERVAL_ENABLE`, `INTERVAL_PERIODIC`, `INTERVAL_INTERRUPT`) |
+/// | 1      | status (`INTERVAL_EXPIRED`) |
+/// | 2-3    | interval in counts |
+/// | 4      | prescaler, in input clocks per count minus one |
+///
+/// The `interrupt` line is active while the expired flag is set and `INTERVAL_INTERRUPT` is
+/// enabled.  It can be replaced with a line shared with an interrupt controller or CPU before the
+/// timer is used, or the timer can be given to a controller directly as an `InterruptSource`,
+/// which requests the `irq` interrupt while the line is active
+pub struct IntervalTimer<Instant>
+where
+    Instant: EmuInstant,
+{
+    control: u8,
+    interval: u16,
+    prescaler: u8,
+    expired: bool,
+    expires_at: Option<Instant>,
+    /// The period of the timer's input clock
+    pub clock_period: Instant::Duration,
+    /// The interrupt output line
+    pub interrupt: Signal,
+    /// The interrupt requested when used as an `InterruptSource`
+    pub irq: Interrupt,
+}
+
+impl<Instant> IntervalTimer<Instant>
+where
+    Instant: EmuInstant,
+    Instant::Duration: Copy,
+{
+    /// Construct a new, stopped interval timer with the given input clock period
+    pub fn new(clock_period: Instant::Duration) -> Self {
+        Self {
+            control: 0,
+            interval: 0,
+            prescaler: 0,
+            expired: false,
+            expires_at: None,
+            clock_period,
+            interrupt: Signal::new(),
+            irq: Interrupt::Irq(0),
+        }
+    }
+
+    /// Returns the time at which the timer will next expire, if it's running
+    pub fn expires_at(&self) -> Option<Instant> {
+        self.expires_at
+    }
+
+    /// Start the timer from the full interval at time `now`
+    pub fn start(&mut self, now: Instant) {
+        self.control |= INTERVAL_ENABLE;
+        self.expires_at = Some(now + self.duration());
+    }
+
+    /// Stop the timer without changing the expired flag
+    pub fn stop(&mut self) {
+        self.control &= !INTERVAL_ENABLE;
+        self.expires_at = None;
+    }
+
+    /// Update the timer to time `now`, and return true if it expired
+    pub fn update(&mut self, now: Instant) -> bool {
+        let mut fired = false;
+        while let Some(expires_at) = self.expires_at {
+            if expires_at > now {
+                break;
+            }
+            fired = true;
+            if self.control & INTERVAL_PERIODIC != 0 {
+                self.expires_at = Some(expires_at + self.duration());
+            } else {
+                self.stop();
+            }
+        }
+        self.expired |= fired;
+        self.update_interrupt();
+        fired
+    }
+
+    fn duration(&self) -> Instant::Duration {
+        let counts = if self.interval == 0 {
+            0x1_0000
+        } else {
+            self.interval as u32
+        };
+        self.clock_period * (counts * (self.prescaler as u32 + 1))
+    }
+
+    fn update_interrupt(&self) {
+        self.interrupt
+            .set(self.expired && self.control & INTERVAL_INTERRUPT != 0);
+    }
+
+    fn read_register(&self, register: usize) -> Option<u8> {
+        match register {
+            INTERVAL_CONTROL => Some(self.control),
+            INTERVAL_STATUS if self.expired => Some(INTERVAL_EXPIRED),
+            INTERVAL_STATUS => Some(0),
+            2 => Some((self.interval >> 8) as u8),
+            3 => Some(self.interval as u8),
+            INTERVAL_PRESCALER => Some(self.prescaler),
+            _ => None,
+        }
+    }
+
+    fn write_register(&mut self, now: Instant, register: usize, value: u8) -> Option<()> {
+        match register {
+            INTERVAL_CONTROL => {
+                self.control = value;
+                if value & INTERVAL_ENABLE != 0 {
+                    self.start(now);
+                } else {
+                    self.stop();
+                }
+            }
+            INTERVAL_STATUS => {
+                if value & INTERVAL_EXPIRED != 0 {
+                    self.expired = false;
+                }
+            }
+            2 => self.interval = (self.interval & 0x00FF) | ((value as u16) << 8),
+            3 => self.interval = (self.interval & 0xFF00) | value as u16,
+            INTERVAL_PRESCALER => self.prescaler = value,
+            _ => return None,
+        }
+        Some(())
+    }
+}
+
+impl<Address, Instant> BusAccess<Address> for IntervalTimer<Instant>
+where
+    Address: TryInto<usize> + Copy,
+    Instant: EmuInstant,
+    Instant::Duration: Copy,
+{
+    type Instant = Instant;
+    type Error = BasicBusError;
+
+    fn read(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        let addr = addr
+            .try_into()
+            .map_err(|_| BasicBusError::UnmappedAddress)?;
+        self.update(now);
+        for (i, byte) in data.iter_mut().enumerate() {
+            *byte = self
+                .read_register(addr + i)
+                .ok_or(BasicBusError::UnmappedAddress)?;
+        }
+        Ok(data.len())
+    }
+
+    fn write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        let addr = addr
+            .try_into()
+            .map_err(|_| BasicBusError::UnmappedAddress)?;
+        self.update(now);
+        for (i, byte) in data.iter().enumerate() {
+            self.write_register(now, addr + i, *byte)
+                .ok_or(BasicBusError::UnmappedAddress)?;
+        }
+        self.update_interrupt();
+        Ok(data.len())
+    }
+}
+
+impl<Address, Bus, Instant> Step<Address, Bus> for IntervalTimer<Instant>
+where
+    Address: Copy,
+    Bus: BusAccess<Address, Instant = Instant>,
+    Instant: EmuInstant,
+    Instant::Duration: Copy,
+{
+    type Error = Infallible;
+
+    fn is_running(&mut self) -> bool {
+        true
+    }
+
+    fn reset(&mut self, _now: Instant, _bus: &mut Bus) -> Result<(), Self::Error> {
+        self.control = 0;
+        self.interval = 0;
+        self.prescaler = 0;
+        self.expired = false;
+        self.expires_at = None;
+        self.interrupt.lower();
+        Ok(())
+    }
+
+    fn step(&mut self, now: Instant, _bus: &mut Bus) -> Result<Instant, Self::Error> {
+        self.update(now);
+        // while stopped, check again after one input clock in case the timer was started
+        Ok(self.expires_at.unwrap_or(now + self.clock_period))
+    }
+}
+
+impl<Instant> InterruptSource for IntervalTimer<Instant>
+where
+    Instant: EmuInstant,
+    Instant::Duration: Copy,
+{
+    type Instant = Instant;
+    type Error = Infallible;
+
+    fn requested(&mut self, now: Self::Instant) -> Option<Interrupt> {
+        self.update(now);
+        if self.interrupt.get() {
+            Some(self.irq)
+        } else {
+            None
+        }
+    }
+
+    /// The interrupt stays requested until the expired flag is cleared through the status register
+    fn acknowledge(
+        &mut self,
+        _now: Self::Instant,
+        _interrupt: Interrupt,
+    ) -> Result<Option<u8>, Self::Error> {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use emulator_hal::BusAccessExt;
+    use emulator_hal::NoBus;
+    use std::time::Duration;
+
+    #[test]
+    fn test_periodic_tick() {
+        let mut timer = IntervalTimer::<Duration>::new(Duration::from_micros(1));
+        let mut bus = NoBus::new();
+        let irq = timer.interrupt.clone();
+
+        timer
+            .write_beu16(Duration::ZERO, INTERVAL_PERIOD, 1000)
+            .unwrap();
+        timer
+            .write_u8(
+                Duration::ZERO,
+                INTERVAL_CONTROL,
+                INTERVAL_ENABLE | INTERVAL_PERIODIC | INTERVAL_INTERRUPT,
+            )
+            .unwrap();
+
+        let next = Step::<usize, _>::step(&mut timer, Duration::ZERO, &mut bus).unwrap();
+        assert_eq!(next, Duration::from_millis(1));
+        assert!(!irq.get());
+
+        let next = Step::<usize, _>::step(&mut timer, next, &mut bus).unwrap();
+        assert_eq!(next, Duration::from_millis(2));
+        assert!(irq.get());
+
+        timer
+            .write_u8(next, INTERVAL_STATUS, INTERVAL_EXPIRED)
+            .unwrap();
+        assert!(!irq.get());
+        assert_eq!(timer.read_u8(next, INTERVAL_STATUS).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_one_shot() {
+        let mut timer = IntervalTimer::<Duration>::new(Duration::from_micros(1));
+
+        timer
+            .write_beu16(Duration::ZERO, INTERVAL_PERIOD, 10)
+            .unwrap();
+        timer
+            .write_u8(Duration::ZERO, INTERVAL_CONTROL, INTERVAL_ENABLE)
+            .unwrap();
+        assert!(!timer.update(Duration::from_micros(9)));
+        assert!(timer.update(Duration::from_micros(25)));
+        assert_eq!(timer.expires_at(), None);
+        assert_eq!(
+            timer
+                .read_u8(Duration::from_micros(25), INTERVAL_STATUS)
+                .unwrap(),
+            INTERVAL_EXPIRED
+        );
+        assert_eq!(
+            timer
+                .read_u8(Duration::from_micros(25), INTERVAL_CONTROL)
+                .unwrap(),
+            0
+        );
+        assert!(!timer.interrupt.get());
+    }
+
+    #[test]
+    fn test_prescaler_and_interrupt_source() {
+        let mut timer = IntervalTimer::<Duration>::new(Duration::from_micros(1));
+        timer.irq = Interrupt::Irq(3);
+
+        timer
+            .write_beu16(Duration::ZERO, INTERVAL_PERIOD, 10)
+            .unwrap();
+        timer
+            .write_u8(Duration::ZERO, INTERVAL_PRESCALER, 3)
+            .unwrap();
+        timer
+            .write_u8(
+                Duration::ZERO,
+                INTERVAL_CONTROL,
+                INTERVAL_ENABLE | INTERVAL_INTERRUPT,
+            )
+            .unwrap();
+        assert_eq!(timer.expires_at(), Some(Duration::from_micros(40)));
+
+        assert_eq!(timer.requested(Duration::from_micros(39)), None);
+        assert_eq!(
+            timer.requested(Duration::from_micros(40)),
+            Some(Interrupt::Irq(3))
+        );
+        timer
+            .acknowledge(Duration::from_micros(40), Interrupt::Irq(3))
+            .unwrap();
+        assert!(timer.interrupt.get());
+        timer
+            .write_u8(Duration::from_micros(41), INTERVAL_STATUS, INTERVAL_EXPIRED)
+            .unwrap();
+        assert_eq!(timer.requested(Duration::from_micros(41)), None);
+    }
+}
@@ -0,0 +1,14 @@
+#![doc = include_str!("../README.md")]
+#![warn(missing_docs)]
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+mod counter;
+pub use crate::counter::*;
+
+mod interval;
+pub use crate::interval::*;
+
+mod watchdog;
+pub use crate::watchdog::*;
@@ -0,0 +1,304 @@
+//! A watchdog timer which resets the system if the guest stops servicing it
+
+use core::convert::Infallible;
+
+use emulator_hal::{BasicBusError, BusAccess, Instant as EmuInstant, Signal, Step};
+
+/// The offset of the control register
+pub const WATCHDOG_CONTROL: usize = 0;
+/// The offset of the status register
+pub const WATCHDOG_STATUS: usize = 1;
+/// The offset of the 16-bit timeout register, in big endian order
+pub const WATCHDOG_PERIOD: usize = 2;
+/// The offset of the service register, which restarts the timeout when `WATCHDOG_KEY` is written
+pub const WATCHDOG_SERVICE: usize = 4;
+
+/// Control register bit which starts the watchdog.  Once set, it can only be cleared by a reset
+pub const WATCHDOG_ENABLE: u8 = 0x01;
+/// Control register bit which raises the `nmi` line instead of the `reset` line on a timeout
+pub const WATCHDOG_NMI: u8 = 0x02;
+
+/// Status register bit which is set on a timeout, and is cleared by writing a 1 to it
+pub const WATCHDOG_TIMEOUT: u8 = 0x01;
+
+/// The value to write to the service register to restart the timeout.  Writing any other value
+/// causes an immediate timeout
+pub const WATCHDOG_KEY: u8 = 0xA5;
+
+/// A watchdog timer which asserts a reset or non-maskable interrupt line when the guest fails
+/// to service it within a programmed number of input clocks
+///
+/// Once the watchdog is enabled, it can't be disabled again except by a reset, and the guest
+/// must write `WATCHDOG_KEY` to the service register before the timeout expires.  Writing the
+/// wrong value to the service register is treated as a timeout, to catch runaway programs.
+///
+/// | Offset | Register |
+/// |--------|----------|
+/// | 0      | control (`WATCHDOG_ENABLE`, `WATCHDOG_NMI`) |
+/// | 1      | status (`WATCHDOG_TIMEOUT`) |
+/// | 2-3    | timeout in input clocks, where 0 is 65536 clocks |
+/// | 4      | service (write only) |
+///
+/// On a timeout, the `reset` line is raised, and stays raised until the watchdog itself is
+/// reset.  If `WATCHDOG_NMI` is set, the `nmi` line is raised instead, and stays raised until
+/// the timeout flag is cleared, and the watchdog restarts so that it can reset the system if
+/// the interrupt handler also fails to service it.  Like `IntervalTimer`, it calculates when
+/// it will next expire rather than counting every clock, so it's only stepped once per timeout
+pub struct Watchdog<Instant>
+where
+    Instant: EmuInstant,
+{
+    control: u8,
+    timeout: u16,
+    expired: bool,
+    expires_at: Option<Instant>,
+    /// The period of the watchdog's input clock
+    pub clock_period: Instant::Duration,
+    /// The reset output line
+    pub reset: Signal,
+    /// The non-maskable interrupt output line
+    pub nmi: Signal,
+}
+
+impl<Instant> Watchdog<Instant>
+where
+    Instant: EmuInstant,
+    Instant::Duration: Copy,
+{
+    /// Construct a new, disabled watchdog with the given input clock period
+    pub fn new(clock_period: Instant::Duration) -> Self {
+        Self {
+            control: 0,
+            timeout: 0,
+            expired: false,
+            expires_at: None,
+            clock_period,
+            reset: Signal::new(),
+            nmi: Signal::new(),
+        }
+    }
+
+    /// Returns the time at which the watchdog will time out, if it's enabled
+    pub fn expires_at(&self) -> Option<Instant> {
+        self.expires_at
+    }
+
+    /// Restart the timeout from time `now`, if the watchdog is enabled
+    pub fn service(&mut self, now: Instant) {
+        if self.control & WATCHDOG_ENABLE != 0 {
+            self.expires_at = Some(now + self.duration());
+        }
+    }
+
+    /// Update the watchdog to time `now`, and return true if it timed out
+    pub fn update(&mut self, now: Instant) -> bool {
+        match self.expires_at {
+            Some(expires_at) if expires_at <= now => {
+                self.expire(now);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expire(&mut self, now: Instant) {
+        self.expired = true;
+        if self.control & WATCHDOG_NMI != 0 {
+            self.nmi.raise();
+            self.service(now);
+        } else {
+            self.reset.raise();
+            self.expires_at = None;
+        }
+    }
+
+    fn duration(&self) -> Instant::Duration {
+        let clocks = if self.timeout == 0 {
+            0x1_0000
+        } else {
+            self.timeout as u32
+        };
+        self.clock_period * clocks
+    }
+
+    fn read_register(&self, register: usize) -> Option<u8> {
+        match register {
+            WATCHDOG_CONTROL => Some(self.control),
+            WATCHDOG_STATUS if self.expired => Some(WATCHDOG_TIMEOUT),
+            WATCHDOG_STATUS => Some(0),
+            2 => Some((self.timeout >> 8) as u8),
+            3 => Some(self.timeout as u8),
+            WATCHDOG_SERVICE => Some(0),
+            _ => None,
+        }
+    }
+
+    fn write_register(&mut self, now: Instant, register: usize, value: u8) -> Option<()> {
+        match register {
+            WATCHDOG_CONTROL => {
+                let enabled = self.control & WATCHDOG_ENABLE != 0;
+                self.control = value | (self.control & WATCHDOG_ENABLE);
+                if !enabled && value & WATCHDOG_ENABLE != 0 {
+                    self.service(now);
+                }
+            }
+            WATCHDOG_STATUS => {
+                if value & WATCHDOG_TIMEOUT != 0 {
+                    self.expired = false;
+                    self.nmi.lower();
+                }
+            }
+            2 => self.timeout = (self.timeout & 0x00FF) | ((value as u16) << 8),
+            3 => self.timeout = (self.timeout & 0xFF00) | value as u16,
+            WATCHDOG_SERVICE if value == WATCHDOG_KEY => self.service(now),
+            WATCHDOG_SERVICE if self.control & WATCHDOG_ENABLE != 0 => self.expire(now),
+            WATCHDOG_SERVICE => {}
+            _ => return None,
+        }
+        Some(())
+    }
+}
+
+impl<Address, Instant> BusAccess<Address> for Watchdog<Instant>
+where
+    Address: TryInto<usize> + Copy,
+    Instant: EmuInstant,
+    Instant::Duration: Copy,
+{
+    type Instant = Instant;
+    type Error = BasicBusError;
+
+    fn read(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        let addr = addr
+            .try_into()
+            .map_err(|_| BasicBusError::UnmappedAddress)?;
+        self.update(now);
+        for (i, byte) in data.iter_mut().enumerate() {
+            *byte = self
+                .read_register(addr + i)
+                .ok_or(BasicBusError::UnmappedAddress)?;
+        }
+        Ok(data.len())
+    }
+
+    fn write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        let addr = addr
+            .try_into()
+            .map_err(|_| BasicBusError::UnmappedAddress)?;
+        self.update(now);
+        for (i, byte) in data.iter().enumerate() {
+            self.write_register(now, addr + i, *byte)
+                .ok_or(BasicBusError::UnmappedAddress)?;
+        }
+        Ok(data.len())
+    }
+}
+
+impl<Address, Bus, Instant> Step<Address, Bus> for Watchdog<Instant>
+where
+    Address: Copy,
+    Bus: BusAccess<Address, Instant = Instant>,
+    Instant: EmuInstant,
+    Instant::Duration: Copy,
+{
+    type Error = Infallible;
+
+    fn is_running(&mut self) -> bool {
+        true
+    }
+
+    fn reset(&mut self, _now: Instant, _bus: &mut Bus) -> Result<(), Self::Error> {
+        self.control = 0;
+        self.timeout = 0;
+        self.expired = false;
+        self.expires_at = None;
+        self.reset.lower();
+        self.nmi.lower();
+        Ok(())
+    }
+
+    fn step(&mut self, now: Instant, _bus: &mut Bus) -> Result<Instant, Self::Error> {
+        self.update(now);
+        // while disabled, check again after one input clock in case the watchdog was enabled
+        Ok(self.expires_at.unwrap_or(now + self.clock_period))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use emulator_hal::BusAccessExt;
+    use emulator_hal::NoBus;
+    use std::time::Duration;
+
+    fn enabled_watchdog(control: u8) -> Watchdog<Duration> {
+        let mut watchdog = Watchdog::<Duration>::new(Duration::from_micros(1));
+        watchdog
+            .write_beu16(Duration::ZERO, WATCHDOG_PERIOD, 100)
+            .unwrap();
+        watchdog
+            .write_u8(Duration::ZERO, WATCHDOG_CONTROL, control)
+            .unwrap();
+        watchdog
+    }
+
+    #[test]
+    fn test_reset_when_not_serviced() {
+        let mut watchdog = enabled_watchdog(WATCHDOG_ENABLE);
+        let mut bus = NoBus::new();
+        let reset = watchdog.reset.clone();
+
+        // servicing in time pushes back the timeout, and it can't be disabled
+        watchdog
+            .write_u8(Duration::from_micros(90), WATCHDOG_SERVICE, WATCHDOG_KEY)
+            .unwrap();
+        watchdog
+            .write_u8(Duration::from_micros(90), WATCHDOG_CONTROL, 0)
+            .unwrap();
+        let next =
+            Step::<usize, _>::step(&mut watchdog, Duration::from_micros(100), &mut bus).unwrap();
+        assert_eq!(next, Duration::from_micros(190));
+        assert!(!reset.get());
+
+        Step::<usize, _>::step(&mut watchdog, next, &mut bus).unwrap();
+        assert!(reset.get());
+        assert_eq!(watchdog.expires_at(), None);
+
+        Step::<usize, _>::reset(&mut watchdog, next, &mut bus).unwrap();
+        assert!(!reset.get());
+        assert_eq!(watchdog.read_u8(next, WATCHDOG_CONTROL).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_nmi_and_bad_key() {
+        let mut watchdog = enabled_watchdog(WATCHDOG_ENABLE | WATCHDOG_NMI);
+
+        watchdog
+            .write_u8(Duration::from_micros(10), WATCHDOG_SERVICE, 0x00)
+            .unwrap();
+        assert!(watchdog.nmi.get());
+        assert!(!watchdog.reset.get());
+        assert_eq!(watchdog.expires_at(), Some(Duration::from_micros(110)));
+        assert_eq!(
+            watchdog
+                .read_u8(Duration::from_micros(10), WATCHDOG_STATUS)
+                .unwrap(),
+            WATCHDOG_TIMEOUT
+        );
+
+        watchdog
+            .write_u8(Duration::from_micros(20), WATCHDOG_STATUS, WATCHDOG_TIMEOUT)
+            .unwrap();
+        assert!(!watchdog.nmi.get());
+    }
+}
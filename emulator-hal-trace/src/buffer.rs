@@ -0,0 +1,163 @@
+//! A fixed-size trace buffer that doesn't require an allocator
+
+use emulator_hal::DeviceId;
+use heapless::HistoryBuffer;
+
+use crate::record::{Access, TraceEvent, TraceSink};
+
+/// The maximum number of data bytes stored in each `BufferedRecord`
+pub const BUFFERED_DATA_LEN: usize = 8;
+
+/// A recorded bus transaction which stores only the first `BUFFERED_DATA_LEN` bytes of data
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BufferedRecord<Address, Instant> {
+    /// The simulated time at which the transaction occurred
+    pub instant: Instant,
+    /// The id of the device that was accessed
+    pub device: DeviceId,
+    /// Whether the transaction was a read or a write
+    pub access: Access,
+    /// The address that was accessed
+    pub addr: Address,
+    /// The first `BUFFERED_DATA_LEN` bytes of the data transferred
+    pub data: heapless::Vec<u8, BUFFERED_DATA_LEN>,
+    /// The full length of the data transferred
+    pub length: usize,
+    /// The number of bytes the device reported as transferred, or `None` if it returned an error
+    pub count: Option<usize>,
+}
+
+impl<Address, Instant> BufferedRecord<Address, Instant>
+where
+    Address: Copy,
+    Instant: Copy,
+{
+    /// Returns a `TraceEvent` that borrows the data stored in this record
+    ///
+    /// The data of the event will only contain the bytes that were stored, which may be fewer
+    /// than the `length` of the original transaction
+    pub fn as_event(&self) -> TraceEvent<'_, Address, Instant> {
+        TraceEvent {
+            instant: self.instant,
+            device: self.device,
+            access: self.access,
+            addr: self.addr,
+            data: &self.data,
+            count: self.count,
+        }
+    }
+}
+
+/// A `TraceSink` which keeps only the most recent `N` transactions, without allocating
+///
+/// This is intended for hosts without an allocator, where a full trace can't be kept, but the
+/// last few transactions before a fault are still useful for debugging
+pub struct TraceBuffer<Address, Instant, const N: usize> {
+    records: HistoryBuffer<BufferedRecord<Address, Instant>, N>,
+}
+
+impl<Address, Instant, const N: usize> TraceBuffer<Address, Instant, N> {
+    /// Construct a new empty trace buffer
+    pub fn new() -> Self {
+        Self {
+            records: HistoryBuffer::new(),
+        }
+    }
+
+    /// Returns the number of records in the buffer
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Returns true if the buffer contains no records
+    pub fn is_empty(&self) -> bool {
+        self.records.len() == 0
+    }
+
+    /// Remove all records from the buffer
+    pub fn clear(&mut self) {
+        self.records.clear();
+    }
+
+    /// Returns an iterator over the records in the buffer, from oldest to newest
+    pub fn iter(&self) -> impl Iterator<Item = &BufferedRecord<Address, Instant>> {
+        self.records.oldest_ordered()
+    }
+}
+
+impl<Address, Instant, const N: usize> Default for TraceBuffer<Address, Instant, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Address, Instant, const N: usize> TraceSink<Address, Instant>
+    for TraceBuffer<Address, Instant, N>
+where
+    Address: Copy,
+    Instant: Copy,
+{
+    fn record(&mut self, event: &TraceEvent<'_, Address, Instant>) {
+        let length = event.data.len().min(BUFFERED_DATA_LEN);
+        self.records.write(BufferedRecord {
+            instant: event.instant,
+            device: event.device,
+            access: event.access,
+            addr: event.addr,
+            data: heapless::Vec::from_slice(&event.data[..length]).unwrap_or_default(),
+            length: event.data.len(),
+            count: event.count,
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::RecordingBus;
+    use emulator_hal::{BasicBusError, BusAccess, BusAccessExt};
+    use std::time::Duration;
+
+    /// A device which accepts every write and discards it
+    struct Sink;
+
+    impl BusAccess<u32> for Sink {
+        type Instant = Duration;
+        type Error = BasicBusError;
+
+        fn read(
+            &mut self,
+            _now: Duration,
+            _addr: u32,
+            data: &mut [u8],
+        ) -> Result<usize, Self::Error> {
+            data.fill(0);
+            Ok(data.len())
+        }
+
+        fn write(&mut self, _now: Duration, _addr: u32, data: &[u8]) -> Result<usize, Self::Error> {
+            Ok(data.len())
+        }
+    }
+
+    #[test]
+    fn test_buffer_keeps_recent_records() {
+        let mut buffer = TraceBuffer::<u32, Duration, 2>::new();
+        let mut bus = RecordingBus::new(DeviceId(0), Sink, &mut buffer);
+
+        for i in 0..3 {
+            bus.write_beu32(Duration::from_nanos(i), 0x100, i as u32)
+                .unwrap();
+        }
+        bus.write(Duration::from_nanos(4), 0x200, &[0xAA; 16])
+            .unwrap();
+
+        let records: Vec<_> = buffer.iter().collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].data.as_slice(), &[0, 0, 0, 2]);
+        assert_eq!(records[1].addr, 0x200);
+        assert_eq!(records[1].data.as_slice(), &[0xAA; BUFFERED_DATA_LEN]);
+        assert_eq!(records[1].length, 16);
+        assert_eq!(records[1].count, Some(16));
+    }
+}
@@ -0,0 +1,378 @@
+//! Differential testing of two implementations of the same device
+
+use alloc::vec::Vec;
+use core::fmt;
+use core::mem;
+
+use emulator_hal::{BusAccess, DeviceId, Instant as EmuInstant, Step};
+
+use crate::record::{RecordingBus, TraceRecord};
+
+/// One of the two implementations compared by a `DiffHarness`
+///
+/// The device is stepped using its own bus, which is wrapped in a `RecordingBus` so that the
+/// transactions issued during each step can be compared.  Both buses should be constructed with
+/// the same inputs, such as identical memory contents, or a `ReplayBus` of the same golden trace.
+/// The `state` function returns a snapshot of the device's state (eg. its registers) to be
+/// compared after each step
+pub struct DiffSide<Address, Device, Bus, StateFn>
+where
+    Address: Copy,
+    Bus: BusAccess<Address>,
+{
+    /// The device being compared
+    pub device: Device,
+    /// The bus used by this device, which records every transaction
+    pub bus: RecordingBus<Bus, Vec<TraceRecord<Address, Bus::Instant>>>,
+    /// The function used to take a snapshot of the device's state
+    pub state: StateFn,
+}
+
+impl<Address, Device, Bus, StateFn> DiffSide<Address, Device, Bus, StateFn>
+where
+    Address: Copy,
+    Bus: BusAccess<Address>,
+{
+    /// Construct a new side of a comparison, with the given device, bus, and state function
+    pub fn new(device: Device, bus: Bus, state: StateFn) -> Self {
+        Self {
+            device,
+            bus: RecordingBus::new(DeviceId::default(), bus, Vec::new()),
+            state,
+        }
+    }
+}
+
+/// The first point at which two implementations differed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Divergence<Address, Instant, State> {
+    /// One device stopped running while the other continued
+    Running {
+        /// The number of steps completed before the divergence
+        step: usize,
+        /// Whether the reference device was running
+        reference: bool,
+        /// Whether the candidate device was running
+        candidate: bool,
+    },
+    /// The bus transactions issued during a step were different
+    Trace {
+        /// The step during which the divergence occurred
+        step: usize,
+        /// The transactions issued by the reference device during the step
+        reference: Vec<TraceRecord<Address, Instant>>,
+        /// The transactions issued by the candidate device during the step
+        candidate: Vec<TraceRecord<Address, Instant>>,
+    },
+    /// The state of the devices after a step was different
+    State {
+        /// The step after which the divergence occurred
+        step: usize,
+        /// The state of the reference device
+        reference: State,
+        /// The state of the candidate device
+        candidate: State,
+    },
+    /// The instant at which the next step should occur was different
+    Timing {
+        /// The step which returned the different instants
+        step: usize,
+        /// The next instant returned by the reference device
+        reference: Instant,
+        /// The next instant returned by the candidate device
+        candidate: Instant,
+    },
+}
+
+impl<Address, Instant, State> fmt::Display for Divergence<Address, Instant, State>
+where
+    Address: PartialEq + fmt::Debug,
+    Instant: PartialEq + fmt::Debug,
+    State: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Divergence::Running {
+                step,
+                reference,
+                candidate,
+            } => write!(
+                f,
+                "running state diverged after step {}: reference running = {}, candidate running = {}",
+                step, reference, candidate
+            ),
+            Divergence::Trace {
+                step,
+                reference,
+                candidate,
+            } => {
+                writeln!(f, "bus transactions diverged during step {}", step)?;
+                let length = reference.len().max(candidate.len());
+                for i in 0..length {
+                    match (reference.get(i), candidate.get(i)) {
+                        (Some(r), Some(c)) if r == c => writeln!(f, "    {:?}", r)?,
+                        (r, c) => {
+                            writeln!(f, "  - {:?}", r)?;
+                            writeln!(f, "  + {:?}", c)?;
+                        },
+                    }
+                }
+                Ok(())
+            },
+            Divergence::State {
+                step,
+                reference,
+                candidate,
+            } => write!(
+                f,
+                "state diverged after step {}:\n  - {:?}\n  + {:?}",
+                step, reference, candidate
+            ),
+            Divergence::Timing {
+                step,
+                reference,
+                candidate,
+            } => write!(
+                f,
+                "timing diverged after step {}: reference next step at {:?}, candidate at {:?}",
+                step, reference, candidate
+            ),
+        }
+    }
+}
+
+/// An error returned by one of the devices while stepping
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DiffError<ReferenceError, CandidateError> {
+    /// The reference device returned an error
+    Reference(ReferenceError),
+    /// The candidate device returned an error
+    Candidate(CandidateError),
+}
+
+/// Runs two implementations of a device in lock step, and reports the first point where they differ
+///
+/// This is typically used to debug a rewritten core, by comparing it to a known-good `reference`
+/// implementation.  After each step, the bus transactions issued by each device, their states,
+/// and (if `compare_instants` is true) the instants at which they should next be stepped, are compared
+pub struct DiffHarness<Reference, Candidate> {
+    /// The known-good implementation
+    pub reference: Reference,
+    /// The implementation being tested
+    pub candidate: Candidate,
+    /// Whether the timing of the two implementations should be compared
+    pub compare_instants: bool,
+}
+
+impl<Reference, Candidate> DiffHarness<Reference, Candidate> {
+    /// Construct a new harness comparing the given implementations, including their timing
+    pub fn new(reference: Reference, candidate: Candidate) -> Self {
+        Self {
+            reference,
+            candidate,
+            compare_instants: true,
+        }
+    }
+}
+
+impl<Address, Instant, State, RefDevice, RefBus, RefState, CanDevice, CanBus, CanState>
+    DiffHarness<
+        DiffSide<Address, RefDevice, RefBus, RefState>,
+        DiffSide<Address, CanDevice, CanBus, CanState>,
+    >
+where
+    Address: Copy + PartialEq,
+    Instant: EmuInstant,
+    State: PartialEq,
+    RefBus: BusAccess<Address, Instant = Instant>,
+    RefDevice: Step<Address, RecordingBus<RefBus, Vec<TraceRecord<Address, Instant>>>>,
+    RefState: FnMut(&mut RefDevice) -> State,
+    CanBus: BusAccess<Address, Instant = Instant>,
+    CanDevice: Step<Address, RecordingBus<CanBus, Vec<TraceRecord<Address, Instant>>>>,
+    CanState: FnMut(&mut CanDevice) -> State,
+{
+    /// Step both devices starting at the given instant, until they diverge, both stop running, or
+    /// `max_steps` steps have been performed
+    ///
+    /// Returns the first divergence found, or `None` if the two implementations behaved the same
+    #[allow(clippy::type_complexity)]
+    pub fn run(
+        &mut self,
+        start: Instant,
+        max_steps: usize,
+    ) -> Result<
+        Option<Divergence<Address, Instant, State>>,
+        DiffError<RefDevice::Error, CanDevice::Error>,
+    > {
+        let (mut reference_now, mut candidate_now) = (start, start);
+
+        for step in 0..max_steps {
+            let reference_running = self.reference.device.is_running();
+            let candidate_running = self.candidate.device.is_running();
+            if reference_running != candidate_running {
+                return Ok(Some(Divergence::Running {
+                    step,
+                    reference: reference_running,
+                    candidate: candidate_running,
+                }));
+            }
+            if !reference_running {
+                break;
+            }
+
+            let reference_next = self
+                .reference
+                .device
+                .step(reference_now, &mut self.reference.bus)
+                .map_err(DiffError::Reference)?;
+            let candidate_next = self
+                .candidate
+                .device
+                .step(candidate_now, &mut self.candidate.bus)
+                .map_err(DiffError::Candidate)?;
+
+            let reference = mem::take(&mut self.reference.bus.sink);
+            let candidate = mem::take(&mut self.candidate.bus.sink);
+            if !self.traces_match(&reference, &candidate) {
+                return Ok(Some(Divergence::Trace {
+                    step,
+                    reference,
+                    candidate,
+                }));
+            }
+
+            let reference = (self.reference.state)(&mut self.reference.device);
+            let candidate = (self.candidate.state)(&mut self.candidate.device);
+            if reference != candidate {
+                return Ok(Some(Divergence::State {
+                    step,
+                    reference,
+                    candidate,
+                }));
+            }
+
+            if self.compare_instants && reference_next != candidate_next {
+                return Ok(Some(Divergence::Timing {
+                    step,
+                    reference: reference_next,
+                    candidate: candidate_next,
+                }));
+            }
+
+            reference_now = reference_next;
+            candidate_now = candidate_next;
+        }
+        Ok(None)
+    }
+
+    fn traces_match(
+        &self,
+        reference: &[TraceRecord<Address, Instant>],
+        candidate: &[TraceRecord<Address, Instant>],
+    ) -> bool {
+        reference.len() == candidate.len()
+            && reference.iter().zip(candidate.iter()).all(|(r, c)| {
+                (!self.compare_instants || r.instant == c.instant)
+                    && r.access == c.access
+                    && r.addr == c.addr
+                    && r.data == c.data
+                    && r.count == c.count
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use emulator_hal::BusAccessExt;
+    use emulator_hal::{Instant, ShortTransfer};
+    use std::time::Duration;
+
+    #[derive(Clone)]
+    /// Sums a list of bytes, writing the running total after each one
+    struct Summer {
+        pc: u32,
+        sum: u8,
+        buggy: bool,
+    }
+
+    impl<Bus> Step<u32, Bus> for Summer
+    where
+        Bus: BusAccess<u32, Instant = Duration>,
+        Bus::Error: From<ShortTransfer>,
+    {
+        type Error = Bus::Error;
+
+        fn is_running(&mut self) -> bool {
+            self.pc < 8
+        }
+
+        fn reset(&mut self, _now: Duration, _bus: &mut Bus) -> Result<(), Self::Error> {
+            self.pc = 0;
+            self.sum = 0;
+            Ok(())
+        }
+
+        fn step(&mut self, now: Duration, bus: &mut Bus) -> Result<Duration, Self::Error> {
+            let value = bus.read_u8(now, self.pc)?;
+            self.sum = if self.buggy && self.pc == 5 {
+                self.sum.wrapping_sub(value)
+            } else {
+                self.sum.wrapping_add(value)
+            };
+            bus.write_u8(now, 0x10 + self.pc, self.sum)?;
+            self.pc += 1;
+            Ok(now + Duration::from_nanos(10))
+        }
+    }
+
+    fn run_harness(buggy: bool) -> Option<Divergence<u32, Duration, (u32, u8)>> {
+        let memory = vec![
+            1, 2, 3, 4, 5, 6, 7, 8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        ];
+
+        let mut harness = DiffHarness::new(
+            DiffSide::new(
+                Summer {
+                    pc: 0,
+                    sum: 0,
+                    buggy: false,
+                },
+                memory.clone(),
+                |cpu: &mut Summer| (cpu.pc, cpu.sum),
+            ),
+            DiffSide::new(
+                Summer {
+                    pc: 0,
+                    sum: 0,
+                    buggy,
+                },
+                memory,
+                |cpu: &mut Summer| (cpu.pc, cpu.sum),
+            ),
+        );
+
+        harness.run(Duration::START, 100).unwrap()
+    }
+
+    #[test]
+    fn test_identical_devices() {
+        assert_eq!(run_harness(false), None);
+    }
+
+    #[test]
+    fn test_first_divergence() {
+        match run_harness(true) {
+            Some(Divergence::Trace {
+                step,
+                reference,
+                candidate,
+            }) => {
+                assert_eq!(step, 5);
+                assert_eq!(reference[1].data, vec![21]);
+                assert_eq!(candidate[1].data, vec![9]);
+            }
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+}
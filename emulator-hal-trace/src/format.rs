@@ -0,0 +1,394 @@
+//! A compact binary encoding for traces of bus transactions
+
+use core::time::Duration;
+
+#[cfg(feature = "std")]
+use emulator_hal::DeviceId;
+use emulator_hal::Instant as EmuInstant;
+
+#[cfg(feature = "std")]
+use crate::record::{Access, TraceEvent, TraceRecord, TraceSink};
+#[cfg(feature = "std")]
+use core::marker::PhantomData;
+#[cfg(feature = "std")]
+use std::io::{self, Read};
+
+/// The magic number at the start of every trace
+pub const TRACE_MAGIC: [u8; 4] = *b"EHTR";
+
+/// The version of the trace format produced by `TraceWriter`
+pub const TRACE_VERSION: u8 = 1;
+
+#[cfg(feature = "std")]
+const FLAG_WRITE: u8 = 0x01;
+#[cfg(feature = "std")]
+const FLAG_ERROR: u8 = 0x02;
+
+/// An address type that can be stored in a trace as a raw integer
+pub trait TraceAddress: Copy {
+    /// Convert this address into its raw form
+    fn to_raw(self) -> u64;
+
+    /// Convert a raw value back into an address
+    fn from_raw(raw: u64) -> Self;
+}
+
+macro_rules! impl_trace_address {
+    ($($ty:ty),*) => {
+        $(
+            impl TraceAddress for $ty {
+                #[inline]
+                fn to_raw(self) -> u64 {
+                    self as u64
+                }
+
+                #[inline]
+                fn from_raw(raw: u64) -> Self {
+                    raw as $ty
+                }
+            }
+        )*
+    };
+}
+
+impl_trace_address!(u8, u16, u32, u64, usize);
+
+/// An instant type that can be stored in a trace as a raw integer
+pub trait TraceInstant: EmuInstant {
+    /// Convert this instant into its raw form
+    fn to_raw(self) -> u64;
+
+    /// Convert a raw value back into an instant
+    fn from_raw(raw: u64) -> Self;
+}
+
+/// Stored as a number of nanoseconds
+impl TraceInstant for Duration {
+    #[inline]
+    fn to_raw(self) -> u64 {
+        self.as_nanos() as u64
+    }
+
+    #[inline]
+    fn from_raw(raw: u64) -> Self {
+        Duration::from_nanos(raw)
+    }
+}
+
+/// Writes bus transactions in the binary trace format to an `io::Write` object
+///
+/// This also implements `TraceSink`, so it can be used directly by a `RecordingBus`.  Since a
+/// sink cannot return an error, the first error that occurs is saved and returned by `finish()`,
+/// and no further records will be written
+#[cfg(feature = "std")]
+pub struct TraceWriter<W>
+where
+    W: io::Write,
+{
+    writer: W,
+    last_instant: u64,
+    error: Option<io::Error>,
+}
+
+#[cfg(feature = "std")]
+impl<W> TraceWriter<W>
+where
+    W: io::Write,
+{
+    /// Construct a new trace writer, and write the trace header to the given `writer`
+    pub fn new(mut writer: W) -> Result<Self, io::Error> {
+        writer.write_all(&TRACE_MAGIC)?;
+        writer.write_all(&[TRACE_VERSION])?;
+        Ok(Self {
+            writer,
+            last_instant: 0,
+            error: None,
+        })
+    }
+
+    /// Write a single record to the trace
+    pub fn write_record<Address, Instant>(
+        &mut self,
+        record: &TraceRecord<Address, Instant>,
+    ) -> Result<(), io::Error>
+    where
+        Address: TraceAddress,
+        Instant: TraceInstant,
+    {
+        self.write_event(&record.as_event())
+    }
+
+    /// Write a single transaction to the trace
+    pub fn write_event<Address, Instant>(
+        &mut self,
+        event: &TraceEvent<'_, Address, Instant>,
+    ) -> Result<(), io::Error>
+    where
+        Address: TraceAddress,
+        Instant: TraceInstant,
+    {
+        let mut flags = 0;
+        if event.access == Access::Write {
+            flags |= FLAG_WRITE;
+        }
+        if event.count.is_none() {
+            flags |= FLAG_ERROR;
+        }
+
+        let instant = event.instant.to_raw();
+        let delta = instant.wrapping_sub(self.last_instant) as i64;
+        self.last_instant = instant;
+
+        self.writer.write_all(&[flags])?;
+        write_unsigned(&mut self.writer, event.device.0 as u64)?;
+        write_unsigned(&mut self.writer, ((delta << 1) ^ (delta >> 63)) as u64)?;
+        write_unsigned(&mut self.writer, event.addr.to_raw())?;
+        write_unsigned(&mut self.writer, event.data.len() as u64)?;
+        self.writer.write_all(event.data)?;
+        if let Some(count) = event.count {
+            write_unsigned(&mut self.writer, count as u64)?;
+        }
+        Ok(())
+    }
+
+    /// Flush the trace, and return the underlying writer, or the first error that occurred
+    pub fn finish(mut self) -> Result<W, io::Error> {
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<Address, Instant, W> TraceSink<Address, Instant> for TraceWriter<W>
+where
+    Address: TraceAddress,
+    Instant: TraceInstant,
+    W: io::Write,
+{
+    fn record(&mut self, event: &TraceEvent<'_, Address, Instant>) {
+        if self.error.is_none() {
+            if let Err(err) = self.write_event(event) {
+                self.error = Some(err);
+            }
+        }
+    }
+}
+
+/// Reads bus transactions in the binary trace format from an `io::Read` object
+///
+/// The records can be read one at a time using `read_record()`, or by using the reader as an
+/// iterator
+#[cfg(feature = "std")]
+pub struct TraceReader<R, Address, Instant>
+where
+    R: io::Read,
+{
+    reader: R,
+    last_instant: u64,
+    address: PhantomData<Address>,
+    instant: PhantomData<Instant>,
+}
+
+#[cfg(feature = "std")]
+impl<R, Address, Instant> TraceReader<R, Address, Instant>
+where
+    R: io::Read,
+    Address: TraceAddress,
+    Instant: TraceInstant,
+{
+    /// Construct a new trace reader, after checking the trace header read from `reader`
+    pub fn new(mut reader: R) -> Result<Self, io::Error> {
+        let mut header = [0; 5];
+        reader.read_exact(&mut header)?;
+        if header[0..4] != TRACE_MAGIC {
+            return Err(invalid_data("not a bus trace"));
+        }
+        if header[4] > TRACE_VERSION {
+            return Err(invalid_data("unsupported trace version"));
+        }
+
+        Ok(Self {
+            reader,
+            last_instant: 0,
+            address: PhantomData,
+            instant: PhantomData,
+        })
+    }
+
+    /// Read the next record from the trace, or return `None` if the end of the trace was reached
+    pub fn read_record(&mut self) -> Result<Option<TraceRecord<Address, Instant>>, io::Error> {
+        let mut flags = [0; 1];
+        if self.reader.read(&mut flags)? == 0 {
+            return Ok(None);
+        }
+        let flags = flags[0];
+
+        let device = u32::try_from(read_unsigned(&mut self.reader)?)
+            .map_err(|_| invalid_data("device id too large"))?;
+        let delta = read_unsigned(&mut self.reader)?;
+        let delta = ((delta >> 1) as i64) ^ -((delta & 1) as i64);
+        let addr = read_unsigned(&mut self.reader)?;
+        let length = read_unsigned(&mut self.reader)?;
+
+        // the length isn't trusted, so the data is only allocated as it's read
+        let mut data = Vec::new();
+        (&mut self.reader).take(length).read_to_end(&mut data)?;
+        if (data.len() as u64) < length {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+
+        let count = if flags & FLAG_ERROR == 0 {
+            Some(read_unsigned(&mut self.reader)? as usize)
+        } else {
+            None
+        };
+
+        self.last_instant = self.last_instant.wrapping_add(delta as u64);
+
+        Ok(Some(TraceRecord {
+            instant: Instant::from_raw(self.last_instant),
+            device: DeviceId(device),
+            access: if flags & FLAG_WRITE != 0 {
+                Access::Write
+            } else {
+                Access::Read
+            },
+            addr: Address::from_raw(addr),
+            data,
+            count,
+        }))
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R, Address, Instant> Iterator for TraceReader<R, Address, Instant>
+where
+    R: io::Read,
+    Address: TraceAddress,
+    Instant: TraceInstant,
+{
+    type Item = Result<TraceRecord<Address, Instant>, io::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+#[cfg(feature = "std")]
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(feature = "std")]
+fn write_unsigned<W: io::Write>(writer: &mut W, mut value: u64) -> Result<(), io::Error> {
+    let mut buffer = [0; 10];
+    let mut i = 0;
+    loop {
+        buffer[i] = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            break;
+        }
+        buffer[i] |= 0x80;
+        i += 1;
+    }
+    writer.write_all(&buffer[..=i])
+}
+
+#[cfg(feature = "std")]
+fn read_unsigned<R: io::Read>(reader: &mut R) -> Result<u64, io::Error> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0; 1];
+        reader.read_exact(&mut byte)?;
+        // only the lowest bit of the tenth byte fits in a u64
+        if shift == 63 && byte[0] & 0x7F > 1 {
+            return Err(invalid_data("integer too large"));
+        }
+        value |= ((byte[0] & 0x7F) as u64) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(invalid_data("integer too large"))
+}
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_write_and_read_trace() {
+        let records = vec![
+            TraceRecord {
+                instant: Duration::from_nanos(1_000),
+                device: DeviceId(1),
+                access: Access::Write,
+                addr: 0xFF_8000_u32,
+                data: vec![0x12, 0x34],
+                count: Some(2),
+            },
+            TraceRecord {
+                instant: Duration::from_nanos(500),
+                device: DeviceId(300),
+                access: Access::Read,
+                addr: 0x10,
+                data: vec![0; 4],
+                count: None,
+            },
+        ];
+
+        let mut writer = TraceWriter::new(Vec::new()).unwrap();
+        for record in records.iter() {
+            writer.record(&record.as_event());
+        }
+        let trace = writer.finish().unwrap();
+
+        let reader = TraceReader::<_, u32, Duration>::new(trace.as_slice()).unwrap();
+        let result: Vec<_> = reader.collect::<Result<_, _>>().unwrap();
+        assert_eq!(result, records);
+    }
+
+    #[test]
+    fn test_reject_invalid_header() {
+        let trace = b"EHTR\xFF";
+        let result = TraceReader::<_, u32, Duration>::new(&trace[..]);
+        assert_eq!(result.err().unwrap().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_reject_truncated_record() {
+        // a record that claims to have 4 GiB of data, but ends after 2 bytes
+        let trace = b"EHTR\x01\x00\x01\x00\x10\xFF\xFF\xFF\xFF\x0F\x12\x34";
+        let mut reader = TraceReader::<_, u32, Duration>::new(&trace[..]).unwrap();
+        let result = reader.read_record();
+        assert_eq!(result.err().unwrap().kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_read_unsigned_limits() {
+        let max = b"\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\x01";
+        assert_eq!(read_unsigned(&mut &max[..]).unwrap(), u64::MAX);
+
+        // the tenth byte can only hold the top bit of a u64
+        let overflow = b"\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\x02";
+        let result = read_unsigned(&mut &overflow[..]);
+        assert_eq!(result.err().unwrap().kind(), io::ErrorKind::InvalidData);
+
+        let too_long = b"\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\x81\x00";
+        let result = read_unsigned(&mut &too_long[..]);
+        assert_eq!(result.err().unwrap().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_reject_device_id_overflow() {
+        // a record whose device id is 2^32, which doesn't fit in a `DeviceId`
+        let trace = b"EHTR\x01\x00\x80\x80\x80\x80\x10\x00\x10\x00\x00";
+        let mut reader = TraceReader::<_, u32, Duration>::new(&trace[..]).unwrap();
+        let result = reader.read_record();
+        assert_eq!(result.err().unwrap().kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,37 @@
+#![doc = include_str!("../README.md")]
+#![warn(missing_docs)]
+#![cfg_attr(all(not(feature = "std"), not(test)), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "heapless")]
+mod buffer;
+#[cfg(feature = "heapless")]
+pub use crate::buffer::*;
+
+#[cfg(feature = "alloc")]
+mod diff;
+#[cfg(feature = "alloc")]
+pub use crate::diff::*;
+
+mod format;
+pub use crate::format::*;
+
+#[cfg(feature = "log")]
+mod log;
+#[cfg(feature = "log")]
+pub use crate::log::*;
+
+mod record;
+pub use crate::record::*;
+
+#[cfg(feature = "alloc")]
+mod replay;
+#[cfg(feature = "alloc")]
+pub use crate::replay::*;
+
+#[cfg(feature = "alloc")]
+mod vgm;
+#[cfg(feature = "alloc")]
+pub use crate::vgm::*;
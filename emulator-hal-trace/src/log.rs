@@ -0,0 +1,39 @@
+//! Logging of bus transactions with the `log` crate
+
+use core::fmt;
+
+use crate::format::TraceAddress;
+use crate::record::{RecordingBus, TraceEvent, TraceSink};
+
+/// A bus wrapper that logs every transaction passed through it with the `log` crate
+///
+/// This is a `RecordingBus` with a `LogSink`, which is constructed with
+/// `TracingBus::new(device, bus, LogSink::new(log::Level::Trace))`
+pub type TracingBus<Bus> = RecordingBus<Bus, LogSink>;
+
+/// A sink which logs each transaction as a single line, at the given level
+///
+/// Messages are logged with the target `emulator_hal_trace`, so they can be enabled separately
+/// from the rest of an emulator's logging
+#[derive(Copy, Clone, Debug)]
+pub struct LogSink {
+    /// The level that transactions are logged at
+    pub level: log::Level,
+}
+
+impl LogSink {
+    /// Construct a new sink that logs at the given level
+    pub fn new(level: log::Level) -> Self {
+        Self { level }
+    }
+}
+
+impl<Address, Instant> TraceSink<Address, Instant> for LogSink
+where
+    Address: TraceAddress,
+    Instant: fmt::Debug,
+{
+    fn record(&mut self, event: &TraceEvent<'_, Address, Instant>) {
+        log::log!(target: "emulator_hal_trace", self.level, "{}", event);
+    }
+}
@@ -0,0 +1,500 @@
+//! Trace records, and the bus wrapper that captures them
+
+#[cfg(feature = "alloc")]
+use alloc::{rc::Rc, vec::Vec};
+#[cfg(feature = "alloc")]
+use core::cell::RefCell;
+
+use core::fmt;
+
+use emulator_hal::{BusAccess, DeviceId, Transaction};
+
+use crate::format::TraceAddress;
+
+/// The direction of a recorded bus transaction
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Access {
+    /// Data was read from the device
+    Read,
+    /// Data was written to the device
+    Write,
+}
+
+/// A bus transaction as it occurs, which borrows the data transferred
+///
+/// This is given to a `TraceSink`, which can store as much of it as needed without requiring
+/// an allocation for every transaction
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TraceEvent<'a, Address, Instant> {
+    /// The simulated time at which the transaction occurred
+    pub instant: Instant,
+    /// The id of the device that was accessed
+    pub device: DeviceId,
+    /// Whether the transaction was a read or a write
+    pub access: Access,
+    /// The address that was accessed
+    pub addr: Address,
+    /// The data transferred, which is always the full length requested by the bus master
+    ///
+    /// For a read, this is the contents of the buffer after the device returned
+    pub data: &'a [u8],
+    /// The number of bytes the device reported as transferred, or `None` if it returned an error
+    pub count: Option<usize>,
+}
+
+/// Formats the event as a single line, such as `1.5µs #2 write 0x1000 [12 34] -> 2`
+impl<'a, Address, Instant> fmt::Display for TraceEvent<'a, Address, Instant>
+where
+    Address: TraceAddress,
+    Instant: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let access = match self.access {
+            Access::Read => "read",
+            Access::Write => "write",
+        };
+        write!(
+            f,
+            "{:?} {} {} {:#x} [",
+            self.instant,
+            self.device,
+            access,
+            self.addr.to_raw()
+        )?;
+        for (i, byte) in self.data.iter().enumerate() {
+            if i != 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{:02x}", byte)?;
+        }
+        match self.count {
+            Some(count) => write!(f, "] -> {}", count),
+            None => write!(f, "] -> error"),
+        }
+    }
+}
+
+/// A single recorded bus transaction, which owns a copy of the data transferred
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceRecord<Address, Instant> {
+    /// The simulated time at which the transaction occurred
+    pub instant: Instant,
+    /// The id of the device that was accessed
+    pub device: DeviceId,
+    /// Whether the transaction was a read or a write
+    pub access: Access,
+    /// The address that was accessed
+    pub addr: Address,
+    /// The data transferred, which is always the full length requested by the bus master
+    ///
+    /// For a read, this is the contents of the buffer after the device returned
+    pub data: Vec<u8>,
+    /// The number of bytes the device reported as transferred, or `None` if it returned an error
+    pub count: Option<usize>,
+}
+
+#[cfg(feature = "alloc")]
+impl<Address, Instant> TraceRecord<Address, Instant>
+where
+    Address: Copy,
+    Instant: Copy,
+{
+    /// Returns a `TraceEvent` which borrows the data of this record
+    pub fn as_event(&self) -> TraceEvent<'_, Address, Instant> {
+        TraceEvent {
+            instant: self.instant,
+            device: self.device,
+            access: self.access,
+            addr: self.addr,
+            data: &self.data,
+            count: self.count,
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, Address, Instant> From<&TraceEvent<'a, Address, Instant>> for TraceRecord<Address, Instant>
+where
+    Address: Copy,
+    Instant: Copy,
+{
+    fn from(event: &TraceEvent<'a, Address, Instant>) -> Self {
+        Self {
+            instant: event.instant,
+            device: event.device,
+            access: event.access,
+            addr: event.addr,
+            data: event.data.to_vec(),
+            count: event.count,
+        }
+    }
+}
+
+/// A destination for recorded bus transactions
+pub trait TraceSink<Address, Instant> {
+    /// Record the given transaction
+    fn record(&mut self, event: &TraceEvent<'_, Address, Instant>);
+}
+
+#[cfg(feature = "alloc")]
+impl<Address, Instant> TraceSink<Address, Instant> for Vec<TraceRecord<Address, Instant>>
+where
+    Address: Copy,
+    Instant: Copy,
+{
+    fn record(&mut self, event: &TraceEvent<'_, Address, Instant>) {
+        self.push(event.into());
+    }
+}
+
+impl<Address, Instant, S> TraceSink<Address, Instant> for &mut S
+where
+    S: TraceSink<Address, Instant> + ?Sized,
+{
+    fn record(&mut self, event: &TraceEvent<'_, Address, Instant>) {
+        S::record(self, event)
+    }
+}
+
+/// A sink which passes each transaction to a closure, such as to print it or to check it
+/// against an expected value
+pub struct CallbackSink<F>(pub F);
+
+impl<Address, Instant, F> TraceSink<Address, Instant> for CallbackSink<F>
+where
+    F: FnMut(&TraceEvent<'_, Address, Instant>),
+{
+    fn record(&mut self, event: &TraceEvent<'_, Address, Instant>) {
+        (self.0)(event)
+    }
+}
+
+/// Allows multiple `RecordingBus` objects to record into the same trace
+#[cfg(feature = "alloc")]
+impl<Address, Instant, S> TraceSink<Address, Instant> for Rc<RefCell<S>>
+where
+    S: TraceSink<Address, Instant> + ?Sized,
+{
+    fn record(&mut self, event: &TraceEvent<'_, Address, Instant>) {
+        self.borrow_mut().record(event)
+    }
+}
+
+/// A bus wrapper that records every transaction passed through it to a `TraceSink`
+///
+/// The result of the wrapped bus is returned unmodified, so this can be inserted anywhere in
+/// a system without changing its behaviour
+pub struct RecordingBus<Bus, Sink> {
+    /// The underlying object implementing `BusAccess` that this object records
+    pub inner: Bus,
+    /// The id stored in each record to identify this device
+    pub device: DeviceId,
+    /// The destination that records are written to
+    pub sink: Sink,
+}
+
+impl<Bus, Sink> RecordingBus<Bus, Sink> {
+    /// Construct a new recording wrapper for the given `bus` object
+    pub fn new(device: DeviceId, inner: Bus, sink: Sink) -> Self {
+        Self {
+            inner,
+            device,
+            sink,
+        }
+    }
+
+    fn record<Address, Instant, E>(
+        &mut self,
+        instant: Instant,
+        access: Access,
+        addr: Address,
+        data: &[u8],
+        result: &Result<usize, E>,
+    ) where
+        Sink: TraceSink<Address, Instant>,
+    {
+        self.sink.record(&TraceEvent {
+            instant,
+            device: self.device,
+            access,
+            addr,
+            data,
+            count: result.as_ref().ok().copied(),
+        });
+    }
+}
+
+impl<Address, Bus, Sink> BusAccess<Address> for RecordingBus<Bus, Sink>
+where
+    Address: Copy,
+    Bus: BusAccess<Address>,
+    Sink: TraceSink<Address, Bus::Instant>,
+{
+    type Instant = Bus::Instant;
+    type Error = Bus::Error;
+
+    fn read(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        let result = self.inner.read(now, addr, data);
+        self.record(now, Access::Read, addr, data, &result);
+        result
+    }
+
+    fn write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        let result = self.inner.write(now, addr, data);
+        self.record(now, Access::Write, addr, data, &result);
+        result
+    }
+
+    fn read_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        let result = self.inner.read_ext(now, addr, data, transaction);
+        self.record(now, Access::Read, addr, data, &result);
+        result
+    }
+
+    fn write_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        let result = self.inner.write_ext(now, addr, data, transaction);
+        self.record(now, Access::Write, addr, data, &result);
+        result
+    }
+
+    fn read_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        let result = self.inner.read_timed(now, addr, data);
+        let count = result.as_ref().map(|(count, _)| *count);
+        self.record(now, Access::Read, addr, data, &count);
+        result
+    }
+
+    fn write_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        let result = self.inner.write_timed(now, addr, data);
+        let count = result.as_ref().map(|(count, _)| *count);
+        self.record(now, Access::Write, addr, data, &count);
+        result
+    }
+
+    /// The cycle is recorded as the read followed by the write, or as a failed read if the read
+    /// fails
+    fn read_modify_write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        modify: &mut dyn FnMut(&mut [u8]),
+    ) -> Result<usize, Self::Error> {
+        let (sink, device) = (&mut self.sink, self.device);
+        let mut read = false;
+        let result = self.inner.read_modify_write(now, addr, data, &mut |data| {
+            read = true;
+            sink.record(&TraceEvent {
+                instant: now,
+                device,
+                access: Access::Read,
+                addr,
+                data,
+                count: Some(data.len()),
+            });
+            modify(data);
+        });
+        let access = if read { Access::Write } else { Access::Read };
+        self.record(now, access, addr, data, &result);
+        result
+    }
+
+    /// Peeks are passed through without being recorded, since they don't change any state.
+    /// Pokes are recorded as writes, so that the recording can still be replayed
+    fn peek(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        self.inner.peek(now, addr, data)
+    }
+
+    fn poke(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        let result = self.inner.poke(now, addr, data);
+        self.record(now, Access::Write, addr, data, &result);
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use emulator_hal::BasicBusError;
+    use emulator_hal::BusAccessExt;
+    use std::string::{String, ToString};
+    use std::time::Duration;
+
+    struct Memory([u8; 16]);
+
+    impl BusAccess<u32> for Memory {
+        type Instant = Duration;
+        type Error = BasicBusError;
+
+        fn read(
+            &mut self,
+            _now: Duration,
+            addr: u32,
+            data: &mut [u8],
+        ) -> Result<usize, Self::Error> {
+            let addr = addr as usize;
+            let source = self
+                .0
+                .get(addr..addr + data.len())
+                .ok_or(BasicBusError::UnmappedAddress)?;
+            data.copy_from_slice(source);
+            Ok(data.len())
+        }
+
+        fn write(&mut self, _now: Duration, addr: u32, data: &[u8]) -> Result<usize, Self::Error> {
+            let addr = addr as usize;
+            self.0
+                .get_mut(addr..addr + data.len())
+                .ok_or(BasicBusError::UnmappedAddress)?
+                .copy_from_slice(data);
+            Ok(data.len())
+        }
+    }
+
+    /// A memory that counts the read-modify-write cycles made to it
+    struct AtomicMemory {
+        memory: Memory,
+        cycles: usize,
+    }
+
+    impl BusAccess<u32> for AtomicMemory {
+        type Instant = Duration;
+        type Error = BasicBusError;
+
+        fn read(
+            &mut self,
+            now: Duration,
+            addr: u32,
+            data: &mut [u8],
+        ) -> Result<usize, Self::Error> {
+            self.memory.read(now, addr, data)
+        }
+
+        fn write(&mut self, now: Duration, addr: u32, data: &[u8]) -> Result<usize, Self::Error> {
+            self.memory.write(now, addr, data)
+        }
+
+        fn read_modify_write(
+            &mut self,
+            now: Duration,
+            addr: u32,
+            data: &mut [u8],
+            modify: &mut dyn FnMut(&mut [u8]),
+        ) -> Result<usize, Self::Error> {
+            self.cycles += 1;
+            self.memory.read_modify_write(now, addr, data, modify)
+        }
+    }
+
+    #[test]
+    fn test_callback_sink() {
+        let mut lines: Vec<String> = Vec::new();
+        let sink =
+            CallbackSink(|event: &TraceEvent<'_, u32, Duration>| lines.push(event.to_string()));
+        let mut bus = RecordingBus::new(DeviceId(2), Memory([0; 16]), sink);
+
+        let now = Duration::from_micros(3);
+        bus.write_beu16(now, 0x0A, 0x1234).unwrap();
+        bus.read_u8(now, 0x0B).unwrap();
+        assert!(bus.read_u8(now, 0x10).is_err());
+
+        assert_eq!(
+            lines,
+            [
+                "3µs #2 write 0xa [12 34] -> 2",
+                "3µs #2 read 0xb [34] -> 1",
+                "3µs #2 read 0x10 [00] -> error",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pokes_are_recorded() {
+        let mut records: Vec<TraceRecord<u32, Duration>> = Vec::new();
+        let mut bus = RecordingBus::new(DeviceId(1), Memory([0; 16]), &mut records);
+
+        let now = Duration::from_micros(1);
+        bus.poke(now, 0x2, &[0xAB]).unwrap();
+        let mut data = [0];
+        bus.peek(now, 0x2, &mut data).unwrap();
+
+        assert_eq!(bus.inner.0[0x2], 0xAB);
+        assert_eq!(data, [0xAB]);
+        assert_eq!(records.len(), 1);
+        assert_eq!(
+            records[0].as_event().to_string(),
+            "1µs #1 write 0x2 [ab] -> 1"
+        );
+    }
+
+    #[test]
+    fn test_read_modify_write_is_recorded() {
+        let memory = AtomicMemory {
+            memory: Memory([0x12; 16]),
+            cycles: 0,
+        };
+        let mut records: Vec<TraceRecord<u32, Duration>> = Vec::new();
+        let mut bus = RecordingBus::new(DeviceId(1), memory, &mut records);
+
+        let now = Duration::from_micros(1);
+        let mut data = [0];
+        bus.read_modify_write(now, 0x4, &mut data, &mut |data| data[0] |= 0x80)
+            .unwrap();
+        bus.read_modify_write(now, 0x10, &mut data, &mut |_| {})
+            .unwrap_err();
+
+        assert_eq!(bus.inner.cycles, 2);
+        let lines: Vec<String> = records.iter().map(|r| r.as_event().to_string()).collect();
+        assert_eq!(
+            lines,
+            [
+                "1µs #1 read 0x4 [12] -> 1",
+                "1µs #1 write 0x4 [92] -> 1",
+                "1µs #1 read 0x10 [92] -> error",
+            ]
+        );
+    }
+}
@@ -0,0 +1,249 @@
+//! A bus that replays a recorded trace, checking the transactions issued to it
+
+use alloc::collections::VecDeque;
+
+use emulator_hal::{BusAccess, ErrorType, Instant as EmuInstant, ShortTransfer};
+
+use crate::record::{Access, TraceRecord};
+
+/// An error returned by `ReplayBus` when the device under test diverges from the trace
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ReplayError {
+    /// A transaction was issued after all the recorded transactions were replayed
+    Exhausted,
+    /// The transaction didn't match the record at the given position in the trace
+    Mismatch(usize),
+    /// The transaction matched, but the device returned an error when it was recorded
+    RecordedError,
+    /// The device under test stopped before the record at the given position was replayed
+    Unfinished(usize),
+    /// Fewer bytes were transferred than were requested, which is the number given
+    ShortTransfer(usize),
+}
+
+impl ErrorType for ReplayError {}
+
+impl From<ShortTransfer> for ReplayError {
+    fn from(err: ShortTransfer) -> Self {
+        ReplayError::ShortTransfer(err.0)
+    }
+}
+
+/// A bus that checks that the transactions issued to it match a recorded trace
+///
+/// Each read or write must match the next record in the trace, by its direction, address, and
+/// length, and for writes, by the data written.  The instant of a transaction is only compared
+/// if `check_timing()` has been called.  Reads return the recorded data, so the device under
+/// test can be run without the rest of the system that the trace was recorded from.
+///
+/// To use a trace as a golden reference in a regression test, run the device under test on the
+/// replay bus, and then call `finish()` to check that it issued every recorded transaction
+pub struct ReplayBus<Address, Instant> {
+    records: VecDeque<TraceRecord<Address, Instant>>,
+    position: usize,
+    timing: bool,
+}
+
+impl<Address, Instant> ReplayBus<Address, Instant> {
+    /// Construct a new replay bus that will expect the given records in order
+    pub fn new<I>(records: I) -> Self
+    where
+        I: IntoIterator<Item = TraceRecord<Address, Instant>>,
+    {
+        Self {
+            records: records.into_iter().collect(),
+            position: 0,
+            timing: false,
+        }
+    }
+
+    /// Also require each transaction to occur at the same instant as its record
+    pub fn check_timing(&mut self) {
+        self.timing = true;
+    }
+
+    /// Returns the number of records which have not yet been replayed
+    pub fn remaining(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Returns true if every record in the trace has been replayed
+    pub fn is_finished(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Returns `ReplayError::Unfinished` if any records have not yet been replayed
+    pub fn finish(&self) -> Result<(), ReplayError> {
+        if self.records.is_empty() {
+            Ok(())
+        } else {
+            Err(ReplayError::Unfinished(self.position))
+        }
+    }
+
+    fn next_record(
+        &mut self,
+        now: Instant,
+        access: Access,
+        addr: Address,
+        len: usize,
+    ) -> Result<TraceRecord<Address, Instant>, ReplayError>
+    where
+        Address: PartialEq,
+        Instant: PartialEq,
+    {
+        let record = self.records.pop_front().ok_or(ReplayError::Exhausted)?;
+        let position = self.position;
+        self.position += 1;
+
+        if record.access != access
+            || record.addr != addr
+            || record.data.len() != len
+            || (self.timing && record.instant != now)
+        {
+            return Err(ReplayError::Mismatch(position));
+        }
+        Ok(record)
+    }
+}
+
+impl<Address, Instant> BusAccess<Address> for ReplayBus<Address, Instant>
+where
+    Address: PartialEq + Copy,
+    Instant: EmuInstant,
+{
+    type Instant = Instant;
+    type Error = ReplayError;
+
+    fn read(&mut self, now: Instant, addr: Address, data: &mut [u8]) -> Result<usize, Self::Error> {
+        let record = self.next_record(now, Access::Read, addr, data.len())?;
+        let count = record.count.ok_or(ReplayError::RecordedError)?;
+        data.copy_from_slice(&record.data);
+        Ok(count)
+    }
+
+    fn write(&mut self, now: Instant, addr: Address, data: &[u8]) -> Result<usize, Self::Error> {
+        let position = self.position;
+        let record = self.next_record(now, Access::Write, addr, data.len())?;
+        if record.data != data {
+            return Err(ReplayError::Mismatch(position));
+        }
+        record.count.ok_or(ReplayError::RecordedError)
+    }
+
+    /// Peeks aren't recorded, so a peek returns the data of the next record without replaying
+    /// it, as long as that record is a read of the same address and length.  Pokes are replayed
+    /// as writes, since they're recorded as writes
+    fn peek(
+        &mut self,
+        _now: Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        let record = self.records.front().ok_or(ReplayError::Exhausted)?;
+        if record.access != Access::Read || record.addr != addr || record.data.len() != data.len() {
+            return Err(ReplayError::Mismatch(self.position));
+        }
+        let count = record.count.ok_or(ReplayError::RecordedError)?;
+        data.copy_from_slice(&record.data);
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::RecordingBus;
+    use emulator_hal::BusAccessExt;
+    use emulator_hal::{DeviceId, Instant};
+    use std::time::Duration;
+
+    fn run_device<B>(bus: &mut B) -> Result<u16, B::Error>
+    where
+        B: BusAccess<u32, Instant = Duration>,
+        B::Error: From<ShortTransfer>,
+    {
+        bus.write_beu16(Duration::from_nanos(10), 0x10, 0x1234)?;
+        let value = bus.read_beu16(Duration::from_nanos(20), 0x10)?;
+        bus.write_u8(Duration::from_nanos(30), 0x20, 0xFF)?;
+        Ok(value)
+    }
+
+    #[test]
+    fn test_record_and_replay() {
+        let mut records = Vec::new();
+        let mut bus = RecordingBus::new(DeviceId(1), vec![0_u8; 256], &mut records);
+        run_device(&mut bus).unwrap();
+
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[1].access, Access::Read);
+        assert_eq!(records[1].data, vec![0x12, 0x34]);
+
+        let mut replay = ReplayBus::new(records);
+        assert_eq!(run_device(&mut replay).unwrap(), 0x1234);
+        assert!(replay.is_finished());
+    }
+
+    #[test]
+    fn test_peek_does_not_replay() {
+        let mut records = Vec::new();
+        let mut bus = RecordingBus::new(DeviceId(1), vec![0_u8; 256], &mut records);
+        run_device(&mut bus).unwrap();
+
+        let mut replay = ReplayBus::new(records);
+        let mut data = [0; 2];
+        assert_eq!(
+            replay.peek(Duration::START, 0x10, &mut data),
+            Err(ReplayError::Mismatch(0))
+        );
+        replay
+            .write_beu16(Duration::from_nanos(10), 0x10, 0x1234)
+            .unwrap();
+        assert_eq!(replay.peek(Duration::START, 0x10, &mut data), Ok(2));
+        assert_eq!(data, [0x12, 0x34]);
+        assert_eq!(replay.remaining(), 2);
+        assert_eq!(
+            replay.read_beu16(Duration::from_nanos(20), 0x10),
+            Ok(0x1234)
+        );
+    }
+
+    #[test]
+    fn test_replay_mismatch() {
+        let mut records = Vec::new();
+        let mut bus = RecordingBus::new(DeviceId(1), vec![0_u8; 256], &mut records);
+        run_device(&mut bus).unwrap();
+
+        records[2].data[0] = 0x00;
+
+        let mut replay = ReplayBus::new(records);
+        assert_eq!(run_device(&mut replay), Err(ReplayError::Mismatch(2)));
+        assert_eq!(
+            replay.read_u8(Duration::START, 0x00),
+            Err(ReplayError::Exhausted)
+        );
+    }
+
+    #[test]
+    fn test_replay_timing_and_finish() {
+        let mut records = Vec::new();
+        let mut bus = RecordingBus::new(DeviceId(1), vec![0_u8; 256], &mut records);
+        run_device(&mut bus).unwrap();
+
+        let mut replay = ReplayBus::new(records.clone());
+        replay.check_timing();
+        replay
+            .write_beu16(Duration::from_nanos(10), 0x10, 0x1234)
+            .unwrap();
+        assert_eq!(replay.finish(), Err(ReplayError::Unfinished(1)));
+        assert_eq!(
+            replay.read_beu16(Duration::from_nanos(25), 0x10),
+            Err(ReplayError::Mismatch(1))
+        );
+
+        let mut replay = ReplayBus::new(records);
+        run_device(&mut replay).unwrap();
+        assert_eq!(replay.finish(), Ok(()));
+    }
+}
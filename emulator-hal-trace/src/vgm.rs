@@ -0,0 +1,461 @@
+//! Logging of sound chip register writes, and export to the VGM format
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::time::Duration;
+
+use emulator_hal::{BusAccess, Instant as EmuInstant, Transaction};
+
+/// The sample rate that delays in a VGM file are measured in
+pub const VGM_SAMPLE_RATE: u64 = 44_100;
+
+const VGM_VERSION: u32 = 0x0000_0151;
+const VGM_HEADER_SIZE: usize = 0x80;
+const VGM_END: u8 = 0x66;
+
+/// A sound chip supported by `VgmLogger`, which determines how writes to it are decoded
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum VgmChip {
+    /// A TI SN76489 or compatible PSG, where every write is a command byte
+    Sn76489,
+    /// A Yamaha YM2413 (OPLL), with the register number at offset 0 and the data at offset 1
+    Ym2413,
+    /// A Yamaha YM2612 (OPN2), with the register number and data at offsets 0 and 1 for the
+    /// first part, and offsets 2 and 3 for the second part
+    Ym2612,
+    /// A Yamaha YM2151 (OPM), with the register number at offset 0 and the data at offset 1
+    Ym2151,
+    /// A General Instrument AY-3-8910, with the register number at offset 0 and the data at
+    /// offset 1
+    Ay8910,
+}
+
+impl VgmChip {
+    /// Returns the offset in the VGM header of this chip's clock
+    fn clock_offset(self) -> usize {
+        match self {
+            VgmChip::Sn76489 => 0x0C,
+            VgmChip::Ym2413 => 0x10,
+            VgmChip::Ym2612 => 0x2C,
+            VgmChip::Ym2151 => 0x30,
+            VgmChip::Ay8910 => 0x74,
+        }
+    }
+
+    /// Decode a write of `value` at the given offset, and return the VGM command for a
+    /// completed register write, or `None` if the write only selected a register
+    fn decode(self, latch: &mut [u8; 2], offset: usize, value: u8) -> Option<VgmWrite> {
+        let (command, part) = match self {
+            VgmChip::Sn76489 => {
+                return Some(VgmWrite {
+                    command: 0x50,
+                    register: None,
+                    value,
+                })
+            }
+            VgmChip::Ym2413 => (0x51, 0),
+            VgmChip::Ym2612 => (0x52 + (offset >> 1 & 1) as u8, offset >> 1 & 1),
+            VgmChip::Ym2151 => (0x54, 0),
+            VgmChip::Ay8910 => (0xA0, 0),
+        };
+
+        if offset & 1 == 0 {
+            latch[part] = value;
+            None
+        } else {
+            Some(VgmWrite {
+                command,
+                register: Some(latch[part]),
+                value,
+            })
+        }
+    }
+}
+
+/// A register write to a sound chip, as it's stored in a VGM file
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct VgmWrite {
+    /// The VGM command byte, which identifies the chip and port written
+    pub command: u8,
+    /// The register written, or `None` for chips that don't have register numbers
+    pub register: Option<u8>,
+    /// The value written
+    pub value: u8,
+}
+
+/// A bus wrapper for a sound chip, which logs the chip's register writes with the time they
+/// occurred, and can export them as a VGM file for playing in external players
+///
+/// Writes to the chip's address and data ports are decoded into register writes according to
+/// the `VgmChip`, using the address offset within the chip.  Reads, and writes that the chip
+/// returns an error for, are passed through without being logged.  Delays between writes are
+/// measured from the `start` instant given when the logger is constructed, and rounded to the
+/// 44.1kHz samples used by the VGM format
+pub struct VgmLogger<Bus, Instant> {
+    /// The sound chip being logged
+    pub inner: Bus,
+    chip: VgmChip,
+    clock: u32,
+    start: Instant,
+    latch: [u8; 2],
+    writes: Vec<(Instant, VgmWrite)>,
+}
+
+impl<Bus, Instant> VgmLogger<Bus, Instant>
+where
+    Instant: EmuInstant + Into<Duration>,
+{
+    /// Construct a new logger for the given chip, running at `clock` Hz, with delays measured
+    /// from `start`
+    pub fn new(inner: Bus, chip: VgmChip, clock: u32, start: Instant) -> Self {
+        Self {
+            inner,
+            chip,
+            clock,
+            start,
+            latch: [0; 2],
+            writes: Vec::new(),
+        }
+    }
+
+    /// Returns the register writes logged so far, in the order they occurred
+    pub fn writes(&self) -> &[(Instant, VgmWrite)] {
+        &self.writes
+    }
+
+    /// Discard the logged writes, and measure delays from the new `start` instant
+    pub fn clear(&mut self, start: Instant) {
+        self.start = start;
+        self.writes.clear();
+    }
+
+    /// Returns the logged writes as a VGM file, ending at the time `end`
+    pub fn to_vgm(&self, end: Instant) -> Vec<u8> {
+        let mut data = vec![0; VGM_HEADER_SIZE];
+        let mut last = 0;
+        for (instant, write) in self.writes.iter() {
+            let sample = self.sample(*instant).max(last);
+            write_wait(&mut data, sample - last);
+            last = sample;
+
+            data.push(write.command);
+            if let Some(register) = write.register {
+                data.push(register);
+            }
+            data.push(write.value);
+        }
+        let total = self.sample(end).max(last);
+        write_wait(&mut data, total - last);
+        data.push(VGM_END);
+
+        let eof_offset = (data.len() - 0x04) as u32;
+        set_u32(&mut data, 0x00, u32::from_le_bytes(*b"Vgm "));
+        set_u32(&mut data, 0x04, eof_offset);
+        set_u32(&mut data, 0x08, VGM_VERSION);
+        set_u32(&mut data, 0x18, total.min(u32::MAX as u64) as u32);
+        set_u32(&mut data, 0x34, (VGM_HEADER_SIZE - 0x34) as u32);
+        set_u32(&mut data, self.chip.clock_offset(), self.clock);
+        if self.chip == VgmChip::Sn76489 {
+            // the feedback pattern and shift register width of the Sega variant
+            data[0x28..0x2A].copy_from_slice(&0x0009_u16.to_le_bytes());
+            data[0x2A] = 16;
+        }
+        data
+    }
+
+    /// Returns the number of samples from the start to the given instant
+    fn sample(&self, instant: Instant) -> u64 {
+        let elapsed = instant
+            .into()
+            .checked_sub(self.start.into())
+            .unwrap_or_default();
+        (elapsed.as_nanos() * VGM_SAMPLE_RATE as u128 / 1_000_000_000) as u64
+    }
+
+    /// Decode and log the register writes made by writing `data` to `addr`
+    fn log_writes<Address>(&mut self, now: Instant, addr: Address, data: &[u8])
+    where
+        Address: TryInto<usize>,
+    {
+        if let Ok(offset) = addr.try_into() {
+            for (i, value) in data.iter().enumerate() {
+                if let Some(write) = self.chip.decode(&mut self.latch, offset + i, *value) {
+                    self.writes.push((now, write));
+                }
+            }
+        }
+    }
+}
+
+fn set_u32(data: &mut [u8], offset: usize, value: u32) {
+    data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+/// Write the wait commands for the given number of samples
+fn write_wait(data: &mut Vec<u8>, mut samples: u64) {
+    while samples > 16 {
+        let wait = samples.min(u16::MAX as u64);
+        data.push(0x61);
+        data.extend_from_slice(&(wait as u16).to_le_bytes());
+        samples -= wait;
+    }
+    if samples > 0 {
+        data.push(0x70 + samples as u8 - 1);
+    }
+}
+
+impl<Address, Bus, Instant> BusAccess<Address> for VgmLogger<Bus, Instant>
+where
+    Address: TryInto<usize> + Copy,
+    Bus: BusAccess<Address, Instant = Instant>,
+    Instant: EmuInstant + Into<Duration>,
+{
+    type Instant = Instant;
+    type Error = Bus::Error;
+
+    #[inline]
+    fn read(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        self.inner.read(now, addr, data)
+    }
+
+    #[inline]
+    fn peek(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        self.inner.peek(now, addr, data)
+    }
+
+    /// Pokes are logged like writes, since they change the chip's registers
+    fn poke(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        let count = self.inner.poke(now, addr, data)?;
+        self.log_writes(now, addr, data);
+        Ok(count)
+    }
+
+    fn write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        let count = self.inner.write(now, addr, data)?;
+        self.log_writes(now, addr, data);
+        Ok(count)
+    }
+
+    /// Only the write of the cycle is logged, since reads don't change the chip's registers
+    fn read_modify_write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        modify: &mut dyn FnMut(&mut [u8]),
+    ) -> Result<usize, Self::Error> {
+        let count = self.inner.read_modify_write(now, addr, data, modify)?;
+        self.log_writes(now, addr, data);
+        Ok(count)
+    }
+
+    #[inline]
+    fn read_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        self.inner.read_ext(now, addr, data, transaction)
+    }
+
+    fn write_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        let count = self.inner.write_ext(now, addr, data, transaction)?;
+        self.log_writes(now, addr, data);
+        Ok(count)
+    }
+
+    #[inline]
+    fn read_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        self.inner.read_timed(now, addr, data)
+    }
+
+    fn write_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        let (count, done) = self.inner.write_timed(now, addr, data)?;
+        self.log_writes(now, addr, data);
+        Ok((count, done))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use emulator_hal::{BasicBusError, BusAccessExt};
+
+    /// The ports of a sound chip, which accept every write and read as zero
+    struct Ports;
+
+    impl BusAccess<u8> for Ports {
+        type Instant = Duration;
+        type Error = BasicBusError;
+
+        fn read(
+            &mut self,
+            _now: Duration,
+            _addr: u8,
+            data: &mut [u8],
+        ) -> Result<usize, Self::Error> {
+            data.fill(0);
+            Ok(data.len())
+        }
+
+        fn write(&mut self, _now: Duration, _addr: u8, data: &[u8]) -> Result<usize, Self::Error> {
+            Ok(data.len())
+        }
+    }
+
+    /// The ports of a sound chip, which count the read-modify-write cycles made to them
+    struct AtomicPorts {
+        cycles: usize,
+    }
+
+    impl BusAccess<u8> for AtomicPorts {
+        type Instant = Duration;
+        type Error = BasicBusError;
+
+        fn read(&mut self, now: Duration, addr: u8, data: &mut [u8]) -> Result<usize, Self::Error> {
+            Ports.read(now, addr, data)
+        }
+
+        fn write(&mut self, now: Duration, addr: u8, data: &[u8]) -> Result<usize, Self::Error> {
+            Ports.write(now, addr, data)
+        }
+
+        fn read_modify_write(
+            &mut self,
+            now: Duration,
+            addr: u8,
+            data: &mut [u8],
+            modify: &mut dyn FnMut(&mut [u8]),
+        ) -> Result<usize, Self::Error> {
+            self.cycles += 1;
+            Ports.read_modify_write(now, addr, data, modify)
+        }
+    }
+
+    #[test]
+    fn test_decode_port_writes() {
+        let start = Duration::from_secs(1);
+        let mut logger = VgmLogger::new(Ports, VgmChip::Ym2612, 7_670_453, start);
+
+        logger.write(start, 0_u8, &[0x28, 0xF0]).unwrap();
+        logger.write_u8(start, 2_u8, 0x30).unwrap();
+        logger.write_u8(start, 3_u8, 0x71).unwrap();
+        logger.read_u8(start, 0_u8).unwrap();
+        logger.peek(start, 0_u8, &mut [0]).unwrap();
+        logger.poke(start, 0_u8, &[0x2B, 0x80]).unwrap();
+
+        assert_eq!(
+            logger.writes(),
+            &[
+                (
+                    start,
+                    VgmWrite {
+                        command: 0x52,
+                        register: Some(0x28),
+                        value: 0xF0
+                    }
+                ),
+                (
+                    start,
+                    VgmWrite {
+                        command: 0x53,
+                        register: Some(0x30),
+                        value: 0x71
+                    }
+                ),
+                (
+                    start,
+                    VgmWrite {
+                        command: 0x52,
+                        register: Some(0x2B),
+                        value: 0x80
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_export_vgm() {
+        let mut logger = VgmLogger::new(Ports, VgmChip::Sn76489, 3_579_545, Duration::ZERO);
+
+        logger.write_u8(Duration::ZERO, 0_u8, 0x9F).unwrap();
+        // 10000 samples later, and then 2 seconds before the end
+        logger
+            .write_u8(Duration::from_micros(226_758), 0_u8, 0x80)
+            .unwrap();
+        let data = logger.to_vgm(Duration::from_micros(2_226_758));
+
+        assert_eq!(&data[0..4], b"Vgm ");
+        assert_eq!(data[0x04..0x08], ((data.len() - 4) as u32).to_le_bytes());
+        assert_eq!(data[0x0C..0x10], 3_579_545_u32.to_le_bytes());
+        assert_eq!(data[0x18..0x1C], 98_200_u32.to_le_bytes());
+        assert_eq!(
+            data[VGM_HEADER_SIZE..],
+            [0x50, 0x9F, 0x61, 0x10, 0x27, 0x50, 0x80, 0x61, 0xFF, 0xFF, 0x61, 0x89, 0x58, 0x66]
+        );
+    }
+
+    #[test]
+    fn test_read_modify_write_is_logged() {
+        let ports = AtomicPorts { cycles: 0 };
+        let mut logger = VgmLogger::new(ports, VgmChip::Sn76489, 3_579_545, Duration::ZERO);
+
+        let mut data = [0];
+        logger
+            .read_modify_write(Duration::ZERO, 0_u8, &mut data, &mut |data| data[0] |= 0x9F)
+            .unwrap();
+
+        assert_eq!(logger.inner.cycles, 1);
+        assert_eq!(
+            logger.writes(),
+            &[(
+                Duration::ZERO,
+                VgmWrite {
+                    command: 0x50,
+                    register: None,
+                    value: 0x9F
+                }
+            )]
+        );
+    }
+}
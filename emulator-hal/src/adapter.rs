@@ -1,7 +1,11 @@
 //! Bus Adapters to translate address and error type
 
-use crate::{BasicBusError, BusAccess, ErrorType, Instant as EmuInstant};
+use crate::{
+    BasicBusError, BusAccess, ConvertInstant, ErrorType, Instant as EmuInstant, RunState, Step,
+    Transaction,
+};
 use core::marker::PhantomData;
+use core::ops::{BitAnd, Range, Sub};
 
 /// Used to translate an address from one address space into another
 pub trait FromAddress<T> {
@@ -29,139 +33,1580 @@ where
 /// This object implements the `BusAccess` trait, and takes address of type `AddressIn`,
 /// applies the provided address translation function to produce an address of type `AddressOut`,
 /// and then calls the equivalent trait method with that produced address, return the result
-pub struct BusAdapter<AddressIn, AddressOut, Bus, ErrorOut>
+///
+/// The translation can be a plain function, or a closure that captures state, such as a base
+/// offset or a shared banking register, so that the translation can change at runtime
+pub struct BusAdapter<AddressIn, AddressOut, Bus, ErrorOut, F = fn(AddressIn) -> AddressOut>
+where
+    AddressIn: Copy,
+    AddressOut: Copy,
+    Bus: BusAccess<AddressOut>,
+    ErrorOut: From<Bus::Error>,
+    F: Fn(AddressIn) -> AddressOut,
+{
+    /// The underlying object implementing `BusAccess` that this object adapts
+    pub inner: Bus,
+    /// The translation function applied
+    pub translate: F,
+
+    address_in: PhantomData<AddressIn>,
+    address_out: PhantomData<AddressOut>,
+    error_out: PhantomData<ErrorOut>,
+}
+
+impl<AddressIn, AddressOut, Bus, ErrorOut, F> BusAdapter<AddressIn, AddressOut, Bus, ErrorOut, F>
+where
+    AddressIn: Copy,
+    AddressOut: Copy,
+    Bus: BusAccess<AddressOut>,
+    ErrorOut: From<Bus::Error>,
+    F: Fn(AddressIn) -> AddressOut,
+{
+    /// Construct a new instance of an adapter for the given `bus` object
+    pub fn new(inner: Bus, translate: F) -> Self {
+        Self {
+            inner,
+            translate,
+            address_in: PhantomData,
+            address_out: PhantomData,
+            error_out: PhantomData,
+        }
+    }
+}
+
+impl<AddressIn, AddressOut, Bus, ErrorOut, F> BusAccess<AddressIn>
+    for BusAdapter<AddressIn, AddressOut, Bus, ErrorOut, F>
+where
+    AddressIn: Copy,
+    AddressOut: Copy,
+    Bus: BusAccess<AddressOut>,
+    ErrorOut: ErrorType + From<Bus::Error>,
+    F: Fn(AddressIn) -> AddressOut,
+{
+    type Instant = Bus::Instant;
+    type Error = ErrorOut;
+
+    #[inline]
+    fn read(
+        &mut self,
+        now: Self::Instant,
+        addr: AddressIn,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        let addr = (self.translate)(addr);
+        self.inner.read(now, addr, data).map_err(|err| err.into())
+    }
+
+    #[inline]
+    fn write(
+        &mut self,
+        now: Self::Instant,
+        addr: AddressIn,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        let addr = (self.translate)(addr);
+        self.inner.write(now, addr, data).map_err(|err| err.into())
+    }
+
+    #[inline]
+    fn peek(
+        &mut self,
+        now: Self::Instant,
+        addr: AddressIn,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        let addr = (self.translate)(addr);
+        self.inner.peek(now, addr, data).map_err(|err| err.into())
+    }
+
+    #[inline]
+    fn poke(
+        &mut self,
+        now: Self::Instant,
+        addr: AddressIn,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        let addr = (self.translate)(addr);
+        self.inner.poke(now, addr, data).map_err(|err| err.into())
+    }
+
+    #[inline]
+    fn read_modify_write(
+        &mut self,
+        now: Self::Instant,
+        addr: AddressIn,
+        data: &mut [u8],
+        modify: &mut dyn FnMut(&mut [u8]),
+    ) -> Result<usize, Self::Error> {
+        let addr = (self.translate)(addr);
+        self.inner
+            .read_modify_write(now, addr, data, modify)
+            .map_err(|err| err.into())
+    }
+
+    #[inline]
+    fn read_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: AddressIn,
+        data: &mut [u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        let addr = (self.translate)(addr);
+        self.inner
+            .read_timed(now, addr, data)
+            .map_err(|err| err.into())
+    }
+
+    #[inline]
+    fn write_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: AddressIn,
+        data: &[u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        let addr = (self.translate)(addr);
+        self.inner
+            .write_timed(now, addr, data)
+            .map_err(|err| err.into())
+    }
+
+    #[inline]
+    fn read_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: AddressIn,
+        data: &mut [u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        let addr = (self.translate)(addr);
+        self.inner
+            .read_ext(now, addr, data, transaction)
+            .map_err(|err| err.into())
+    }
+
+    #[inline]
+    fn write_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: AddressIn,
+        data: &[u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        let addr = (self.translate)(addr);
+        self.inner
+            .write_ext(now, addr, data, transaction)
+            .map_err(|err| err.into())
+    }
+}
+
+/// An adapter that lets a device written for one type of bus be stepped with another
+///
+/// This is the equivalent of `BusAdapter` for the `Step` trait.  The wrapped device implements
+/// `Step` for a bus with addresses of type `AddressIn` and errors of type `ErrorOut`, and this
+/// object implements `Step` for any bus with addresses of type `AddressOut`.  Each time it's
+/// stepped or reset, the system bus is wrapped in a `BusAdapter` that applies the translation
+/// function to each address, and converts each error, and that adapter is given to the device
+pub struct StepAdapter<AddressIn, AddressOut, Device, ErrorOut, F = fn(AddressIn) -> AddressOut>
+where
+    F: Fn(AddressIn) -> AddressOut,
+{
+    /// The underlying device that this object adapts
+    pub inner: Device,
+    /// The translation function applied to the addresses the device accesses
+    pub translate: F,
+
+    address_in: PhantomData<AddressIn>,
+    address_out: PhantomData<AddressOut>,
+    error_out: PhantomData<ErrorOut>,
+}
+
+impl<AddressIn, AddressOut, Device, ErrorOut, F>
+    StepAdapter<AddressIn, AddressOut, Device, ErrorOut, F>
+where
+    F: Fn(AddressIn) -> AddressOut,
+{
+    /// Construct a new instance of an adapter for the given `device` object
+    pub fn new(inner: Device, translate: F) -> Self {
+        Self {
+            inner,
+            translate,
+            address_in: PhantomData,
+            address_out: PhantomData,
+            error_out: PhantomData,
+        }
+    }
+}
+
+impl<AddressIn, AddressOut, Bus, Device, ErrorOut, F, Error> Step<AddressOut, Bus>
+    for StepAdapter<AddressIn, AddressOut, Device, ErrorOut, F>
 where
     AddressIn: Copy,
     AddressOut: Copy,
     Bus: BusAccess<AddressOut>,
+    ErrorOut: ErrorType + From<Bus::Error>,
+    F: Fn(AddressIn) -> AddressOut,
+    for<'a> Device: Step<
+        AddressIn,
+        BusAdapter<AddressIn, AddressOut, &'a mut Bus, ErrorOut, &'a F>,
+        Error = Error,
+    >,
+{
+    type Error = Error;
+
+    #[inline]
+    fn is_running(&mut self) -> bool {
+        self.inner.is_running()
+    }
+
+    #[inline]
+    fn run_state(&mut self) -> RunState<Bus::Instant> {
+        self.inner.run_state()
+    }
+
+    #[inline]
+    fn reset(&mut self, now: Bus::Instant, bus: &mut Bus) -> Result<(), Self::Error> {
+        let mut bus = BusAdapter::new(bus, &self.translate);
+        self.inner.reset(now, &mut bus)
+    }
+
+    #[inline]
+    fn step(&mut self, now: Bus::Instant, bus: &mut Bus) -> Result<Bus::Instant, Self::Error> {
+        let mut bus = BusAdapter::new(bus, &self.translate);
+        self.inner.step(now, &mut bus)
+    }
+}
+
+/// An adapter that uses the `FromAddress` trait to translate an address before accessing a wrapped bus object
+///
+/// This object implements the `BusAccess` trait, and takes address of type `AddressIn`,
+/// applies `FromAddress<AddressIn>` trait to produce an address of type `AddressOut`,
+/// and then calls the equivalent trait method with that produced address, return the result
+pub struct AutoBusAdapter<AddressIn, AddressOut, Bus, ErrorOut>
+where
+    AddressOut: FromAddress<AddressIn> + Copy,
+    Bus: BusAccess<AddressOut>,
+    ErrorOut: From<Bus::Error>,
+{
+    /// The underlying object implementing `BusAccess` that this object adapts
+    pub inner: Bus,
+
+    address_in: PhantomData<AddressIn>,
+    address_out: PhantomData<AddressOut>,
+    error_out: PhantomData<ErrorOut>,
+}
+
+impl<AddressIn, AddressOut, Bus, ErrorOut> AutoBusAdapter<AddressIn, AddressOut, Bus, ErrorOut>
+where
+    AddressOut: FromAddress<AddressIn> + Copy,
+    Bus: BusAccess<AddressOut>,
     ErrorOut: From<Bus::Error>,
 {
+    /// Construct a new instance of an adapter for the given `bus` object
+    pub fn new(inner: Bus) -> Self {
+        Self {
+            inner,
+            address_in: PhantomData,
+            address_out: PhantomData,
+            error_out: PhantomData,
+        }
+    }
+}
+
+impl<AddressIn, AddressOut, Bus, ErrorOut> BusAccess<AddressIn>
+    for AutoBusAdapter<AddressIn, AddressOut, Bus, ErrorOut>
+where
+    AddressIn: Copy,
+    AddressOut: FromAddress<AddressIn> + Copy,
+    Bus: BusAccess<AddressOut>,
+    ErrorOut: ErrorType + From<Bus::Error>,
+{
+    type Instant = Bus::Instant;
+    type Error = ErrorOut;
+
+    #[inline]
+    fn read(
+        &mut self,
+        now: Self::Instant,
+        addr: AddressIn,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        let addr = addr.into_address();
+        self.inner.read(now, addr, data).map_err(|err| err.into())
+    }
+
+    #[inline]
+    fn write(
+        &mut self,
+        now: Self::Instant,
+        addr: AddressIn,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        let addr = addr.into_address();
+        self.inner.write(now, addr, data).map_err(|err| err.into())
+    }
+
+    #[inline]
+    fn peek(
+        &mut self,
+        now: Self::Instant,
+        addr: AddressIn,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        let addr = addr.into_address();
+        self.inner.peek(now, addr, data).map_err(|err| err.into())
+    }
+
+    #[inline]
+    fn poke(
+        &mut self,
+        now: Self::Instant,
+        addr: AddressIn,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        let addr = addr.into_address();
+        self.inner.poke(now, addr, data).map_err(|err| err.into())
+    }
+
+    #[inline]
+    fn read_modify_write(
+        &mut self,
+        now: Self::Instant,
+        addr: AddressIn,
+        data: &mut [u8],
+        modify: &mut dyn FnMut(&mut [u8]),
+    ) -> Result<usize, Self::Error> {
+        let addr = addr.into_address();
+        self.inner
+            .read_modify_write(now, addr, data, modify)
+            .map_err(|err| err.into())
+    }
+
+    #[inline]
+    fn read_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: AddressIn,
+        data: &mut [u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        let addr = addr.into_address();
+        self.inner
+            .read_timed(now, addr, data)
+            .map_err(|err| err.into())
+    }
+
+    #[inline]
+    fn write_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: AddressIn,
+        data: &[u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        let addr = addr.into_address();
+        self.inner
+            .write_timed(now, addr, data)
+            .map_err(|err| err.into())
+    }
+
+    #[inline]
+    fn read_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: AddressIn,
+        data: &mut [u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        let addr = addr.into_address();
+        self.inner
+            .read_ext(now, addr, data, transaction)
+            .map_err(|err| err.into())
+    }
+
+    #[inline]
+    fn write_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: AddressIn,
+        data: &[u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        let addr = addr.into_address();
+        self.inner
+            .write_ext(now, addr, data, transaction)
+            .map_err(|err| err.into())
+    }
+}
+
+/// An adapter that subtracts a base address, and then applies an address mask, before
+/// accessing a wrapped bus object
+///
+/// This is the translation needed by most memory mapped devices, such as a device mapped at
+/// `0xFF8000` with registers at `0x00..0x3F` which are mirrored throughout its range, and
+/// avoids writing a closure for `BusAdapter` each time.  Accesses to addresses below `base`
+/// return `BasicBusError::UnmappedAddress`, converted into the wrapped bus's error type
+pub struct OffsetAdapter<Address, Bus> {
+    /// The underlying object implementing `BusAccess` that this object adapts
+    pub inner: Bus,
+    /// The address subtracted from each address
+    pub base: Address,
+    /// The mask applied to each address after subtracting the base
+    pub mask: Address,
+}
+
+impl<Address, Bus> OffsetAdapter<Address, Bus>
+where
+    Address: Copy + PartialOrd + Sub<Output = Address> + BitAnd<Output = Address>,
+{
+    /// Construct a new adapter for the given `bus` object
+    pub fn new(inner: Bus, base: Address, mask: Address) -> Self {
+        Self { inner, base, mask }
+    }
+
+    /// Returns the address that the inner bus will be accessed at for the given address, or an
+    /// error if the address is below the base address
+    #[inline]
+    pub fn translate(&self, addr: Address) -> Result<Address, BasicBusError> {
+        if addr < self.base {
+            return Err(BasicBusError::UnmappedAddress);
+        }
+        Ok((addr - self.base) & self.mask)
+    }
+}
+
+impl<Address, Bus> BusAccess<Address> for OffsetAdapter<Address, Bus>
+where
+    Address: Copy + PartialOrd + Sub<Output = Address> + BitAnd<Output = Address>,
+    Bus: BusAccess<Address>,
+    Bus::Error: From<BasicBusError>,
+{
+    type Instant = Bus::Instant;
+    type Error = Bus::Error;
+
+    #[inline]
+    fn read(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        let addr = self.translate(addr)?;
+        self.inner.read(now, addr, data)
+    }
+
+    #[inline]
+    fn write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        let addr = self.translate(addr)?;
+        self.inner.write(now, addr, data)
+    }
+
+    #[inline]
+    fn peek(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        let addr = self.translate(addr)?;
+        self.inner.peek(now, addr, data)
+    }
+
+    #[inline]
+    fn poke(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        let addr = self.translate(addr)?;
+        self.inner.poke(now, addr, data)
+    }
+
+    #[inline]
+    fn read_modify_write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        modify: &mut dyn FnMut(&mut [u8]),
+    ) -> Result<usize, Self::Error> {
+        let addr = self.translate(addr)?;
+        self.inner.read_modify_write(now, addr, data, modify)
+    }
+
+    #[inline]
+    fn read_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        let addr = self.translate(addr)?;
+        self.inner.read_timed(now, addr, data)
+    }
+
+    #[inline]
+    fn write_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        let addr = self.translate(addr)?;
+        self.inner.write_timed(now, addr, data)
+    }
+
+    #[inline]
+    fn read_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        let addr = self.translate(addr)?;
+        self.inner.read_ext(now, addr, data, transaction)
+    }
+
+    #[inline]
+    fn write_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        let addr = self.translate(addr)?;
+        self.inner.write_ext(now, addr, data, transaction)
+    }
+}
+
+/// An adapter that applies an address mask before accessing a wrapped bus object
+///
+/// This can be used for devices that only decode some of their address lines, so that their
+/// registers or memory are mirrored throughout the range they're mapped to
+pub struct MaskAdapter<Address, Bus> {
+    /// The underlying object implementing `BusAccess` that this object adapts
+    pub inner: Bus,
+    /// The mask applied to each address
+    pub mask: Address,
+}
+
+impl<Address, Bus> MaskAdapter<Address, Bus>
+where
+    Address: Copy + BitAnd<Output = Address>,
+{
+    /// Construct a new adapter for the given `bus` object
+    pub fn new(inner: Bus, mask: Address) -> Self {
+        Self { inner, mask }
+    }
+}
+
+impl<Address, Bus> BusAccess<Address> for MaskAdapter<Address, Bus>
+where
+    Address: Copy + BitAnd<Output = Address>,
+    Bus: BusAccess<Address>,
+{
+    type Instant = Bus::Instant;
+    type Error = Bus::Error;
+
+    #[inline]
+    fn read(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        self.inner.read(now, addr & self.mask, data)
+    }
+
+    #[inline]
+    fn write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        self.inner.write(now, addr & self.mask, data)
+    }
+
+    #[inline]
+    fn peek(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        self.inner.peek(now, addr & self.mask, data)
+    }
+
+    #[inline]
+    fn poke(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        self.inner.poke(now, addr & self.mask, data)
+    }
+
+    #[inline]
+    fn read_modify_write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        modify: &mut dyn FnMut(&mut [u8]),
+    ) -> Result<usize, Self::Error> {
+        self.inner
+            .read_modify_write(now, addr & self.mask, data, modify)
+    }
+
+    #[inline]
+    fn read_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        self.inner.read_timed(now, addr & self.mask, data)
+    }
+
+    #[inline]
+    fn write_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        self.inner.write_timed(now, addr & self.mask, data)
+    }
+
+    #[inline]
+    fn read_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        self.inner
+            .read_ext(now, addr & self.mask, data, transaction)
+    }
+
+    #[inline]
+    fn write_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        self.inner
+            .write_ext(now, addr & self.mask, data, transaction)
+    }
+}
+
+/// An adapter that swaps the byte lanes of a bus that is `WIDTH` bytes wide
+///
+/// The byte at lane `n` of each aligned `WIDTH`-byte word is moved to lane `WIDTH - 1 - n`, as
+/// happens when a big endian CPU is wired to a little endian peripheral or bus, or by a bus
+/// bridge that swaps lanes.  An aligned access of the full bus width therefore has its bytes
+/// reversed, so a 32-bit register written by a big endian CPU through a 4-byte wide adapter is
+/// seen with the same value by a little endian device.  Narrower accesses are moved to the
+/// swapped lanes, and accesses that cross a word are split into one access per word
+///
+/// # Panics
+///
+/// `new()` panics if `WIDTH` isn't a power of two
+pub struct SwapBytesAdapter<Bus, const WIDTH: usize> {
+    /// The underlying object implementing `BusAccess` that this object adapts
+    pub inner: Bus,
+}
+
+impl<Bus, const WIDTH: usize> SwapBytesAdapter<Bus, WIDTH> {
+    /// Construct a new adapter for the given `bus` object
+    pub fn new(inner: Bus) -> Self {
+        assert!(WIDTH.is_power_of_two(), "bus width must be a power of two");
+        Self { inner }
+    }
+
+    /// Call `access` with the swapped address and the range of the data for each part of an
+    /// access to `addr` that falls within one word, and return the sum of the counts
+    fn for_each_word<Address, Error, F>(
+        addr: Address,
+        len: usize,
+        mut access: F,
+    ) -> Result<usize, Error>
+    where
+        Address: TryInto<usize> + TryFrom<usize>,
+        Error: From<BasicBusError>,
+        F: FnMut(Address, Range<usize>) -> Result<usize, Error>,
+    {
+        let start: usize = addr
+            .try_into()
+            .map_err(|_| BasicBusError::UnmappedAddress)?;
+        let mut offset = 0;
+        let mut count = 0;
+        while offset < len {
+            let word_addr = start + offset;
+            let length = (WIDTH - word_addr % WIDTH).min(len - offset);
+            let swapped = Address::try_from((word_addr + length - 1) ^ (WIDTH - 1))
+                .map_err(|_| BasicBusError::UnmappedAddress)?;
+            count += access(swapped, offset..offset + length)?;
+            offset += length;
+        }
+        Ok(count)
+    }
+
+    /// Returns the descriptor for the part of an access covering `range`, with its byte
+    /// enables reversed along with the data
+    fn swap_enables(transaction: Transaction, range: Range<usize>) -> Transaction {
+        let mut byte_enables = u32::MAX;
+        for (lane, index) in range.rev().enumerate() {
+            if lane < 32 && !transaction.is_byte_enabled(index) {
+                byte_enables &= !(1 << lane);
+            }
+        }
+        transaction.with_byte_enables(byte_enables)
+    }
+}
+
+impl<Address, Bus, const WIDTH: usize> BusAccess<Address> for SwapBytesAdapter<Bus, WIDTH>
+where
+    Address: Copy + TryInto<usize> + TryFrom<usize>,
+    Bus: BusAccess<Address>,
+    Bus::Error: From<BasicBusError>,
+{
+    type Instant = Bus::Instant;
+    type Error = Bus::Error;
+
+    fn read(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        let inner = &mut self.inner;
+        Self::for_each_word(addr, data.len(), |swapped, range| {
+            let word = &mut data[range];
+            let count = inner.read(now, swapped, word)?;
+            word.reverse();
+            Ok(count)
+        })
+    }
+
+    fn write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        let inner = &mut self.inner;
+        let mut buffer = [0; WIDTH];
+        Self::for_each_word(addr, data.len(), |swapped, range| {
+            let word = &mut buffer[..range.len()];
+            word.copy_from_slice(&data[range]);
+            word.reverse();
+            inner.write(now, swapped, word)
+        })
+    }
+
+    fn read_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        let inner = &mut self.inner;
+        Self::for_each_word(addr, data.len(), |swapped, range| {
+            let transaction = Self::swap_enables(transaction, range.clone());
+            let word = &mut data[range];
+            word.reverse();
+            let count = inner.read_ext(now, swapped, word, transaction)?;
+            word.reverse();
+            Ok(count)
+        })
+    }
+
+    fn write_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        let inner = &mut self.inner;
+        let mut buffer = [0; WIDTH];
+        Self::for_each_word(addr, data.len(), |swapped, range| {
+            let transaction = Self::swap_enables(transaction, range.clone());
+            let word = &mut buffer[..range.len()];
+            word.copy_from_slice(&data[range]);
+            word.reverse();
+            inner.write_ext(now, swapped, word, transaction)
+        })
+    }
+
+    fn read_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        let inner = &mut self.inner;
+        let mut time = now;
+        let count = Self::for_each_word::<_, Bus::Error, _>(addr, data.len(), |swapped, range| {
+            let word = &mut data[range];
+            let (count, done) = inner.read_timed(time, swapped, word)?;
+            word.reverse();
+            time = done;
+            Ok(count)
+        })?;
+        Ok((count, time))
+    }
+
+    fn write_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        let inner = &mut self.inner;
+        let mut buffer = [0; WIDTH];
+        let mut time = now;
+        let count = Self::for_each_word::<_, Bus::Error, _>(addr, data.len(), |swapped, range| {
+            let word = &mut buffer[..range.len()];
+            word.copy_from_slice(&data[range]);
+            word.reverse();
+            let (count, done) = inner.write_timed(time, swapped, word)?;
+            time = done;
+            Ok(count)
+        })?;
+        Ok((count, time))
+    }
+
+    fn read_modify_write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        modify: &mut dyn FnMut(&mut [u8]),
+    ) -> Result<usize, Self::Error> {
+        let start: usize = addr
+            .try_into()
+            .map_err(|_| BasicBusError::UnmappedAddress)?;
+        // an access that crosses a word can't be split without splitting the modification, so
+        // it's done as a separate read and write, which isn't indivisible
+        if start % WIDTH + data.len() > WIDTH {
+            self.read(now, addr, data)?;
+            modify(data);
+            return self.write(now, addr, data);
+        }
+
+        let inner = &mut self.inner;
+        Self::for_each_word(addr, data.len(), |swapped, range| {
+            let word = &mut data[range];
+            let count = inner.read_modify_write(now, swapped, word, &mut |word| {
+                word.reverse();
+                modify(word);
+                word.reverse();
+            })?;
+            word.reverse();
+            Ok(count)
+        })
+    }
+
+    fn peek(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        let inner = &mut self.inner;
+        Self::for_each_word(addr, data.len(), |swapped, range| {
+            let word = &mut data[range];
+            let count = inner.peek(now, swapped, word)?;
+            word.reverse();
+            Ok(count)
+        })
+    }
+
+    fn poke(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        let inner = &mut self.inner;
+        let mut buffer = [0; WIDTH];
+        Self::for_each_word(addr, data.len(), |swapped, range| {
+            let word = &mut buffer[..range.len()];
+            word.copy_from_slice(&data[range]);
+            word.reverse();
+            inner.poke(now, swapped, word)
+        })
+    }
+}
+
+/// An adapter that returns `BasicBusError::Misaligned` for accesses that aren't aligned to
+/// their size, up to a maximum of `width` bytes
+///
+/// Each access must start at a multiple of its length, or of `width` if the access is longer,
+/// so a `width` of 2 matches the 68000, where word and long word accesses must be at even
+/// addresses, and a `width` of 4 matches CPUs which require every access to be naturally
+/// aligned.  Lengths which aren't a power of two are aligned to the next smaller power of two.
+/// `peek()` and `poke()` are passed through without being checked, so that a debugger can
+/// access any address
+///
+/// # Panics
+///
+/// `new()` panics if `width` isn't a power of two
+pub struct AlignedBus<Bus> {
+    /// The underlying object implementing `BusAccess` that this object adapts
+    pub inner: Bus,
+    /// The largest alignment that an access must have
+    pub width: usize,
+}
+
+impl<Bus> AlignedBus<Bus> {
+    /// Construct a new adapter for the given `bus` object
+    pub fn new(inner: Bus, width: usize) -> Self {
+        assert!(width.is_power_of_two(), "width must be a power of two");
+        Self { inner, width }
+    }
+
+    /// Returns true if an access of `len` bytes at `addr` is aligned
+    pub fn is_aligned<Address>(&self, addr: Address, len: usize) -> bool
+    where
+        Address: TryInto<usize>,
+    {
+        let len = len.min(self.width);
+        if len <= 1 {
+            return true;
+        }
+        let alignment = 1 << (usize::BITS - 1 - len.leading_zeros());
+        match addr.try_into() {
+            Ok(addr) => addr & (alignment - 1) == 0,
+            Err(_) => false,
+        }
+    }
+
+    #[inline]
+    fn check<Address, Error>(&self, addr: Address, len: usize) -> Result<(), Error>
+    where
+        Address: TryInto<usize>,
+        Error: From<BasicBusError>,
+    {
+        if self.is_aligned(addr, len) {
+            Ok(())
+        } else {
+            Err(BasicBusError::Misaligned.into())
+        }
+    }
+}
+
+impl<Address, Bus> BusAccess<Address> for AlignedBus<Bus>
+where
+    Address: Copy + TryInto<usize>,
+    Bus: BusAccess<Address>,
+    Bus::Error: From<BasicBusError>,
+{
+    type Instant = Bus::Instant;
+    type Error = Bus::Error;
+
+    #[inline]
+    fn read(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        self.check(addr, data.len())?;
+        self.inner.read(now, addr, data)
+    }
+
+    #[inline]
+    fn write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        self.check(addr, data.len())?;
+        self.inner.write(now, addr, data)
+    }
+
+    #[inline]
+    fn read_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        self.check(addr, data.len())?;
+        self.inner.read_ext(now, addr, data, transaction)
+    }
+
+    #[inline]
+    fn write_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        self.check(addr, data.len())?;
+        self.inner.write_ext(now, addr, data, transaction)
+    }
+
+    #[inline]
+    fn read_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        self.check(addr, data.len())?;
+        self.inner.read_timed(now, addr, data)
+    }
+
+    #[inline]
+    fn write_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        self.check(addr, data.len())?;
+        self.inner.write_timed(now, addr, data)
+    }
+
+    #[inline]
+    fn peek(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        self.inner.peek(now, addr, data)
+    }
+
+    #[inline]
+    fn poke(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        self.inner.poke(now, addr, data)
+    }
+
+    #[inline]
+    fn read_modify_write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        modify: &mut dyn FnMut(&mut [u8]),
+    ) -> Result<usize, Self::Error> {
+        self.check(addr, data.len())?;
+        self.inner.read_modify_write(now, addr, data, modify)
+    }
+}
+
+/// An adapter that converts the instant of each access before accessing a wrapped bus object
+///
+/// This allows a device written with one `Instant` type to be accessed by a controller that
+/// uses another, such as a peripheral using `Duration` on the bus of a CPU using
+/// `femtos::Instant`.  The instants are converted with `ConvertInstant`, which rounds down
+/// to the resolution of the type being converted to.  The completion time returned by
+/// `read_timed()` and `write_timed()` is converted back, and is never earlier than the time of
+/// the access, even if the conversion lost precision
+pub struct InstantAdapter<Instant, Bus> {
+    /// The underlying object implementing `BusAccess` that this object adapts
+    pub inner: Bus,
+
+    instant: PhantomData<Instant>,
+}
+
+impl<Instant, Bus> InstantAdapter<Instant, Bus> {
+    /// Construct a new instance of an adapter for the given `bus` object
+    pub fn new(inner: Bus) -> Self {
+        Self {
+            inner,
+            instant: PhantomData,
+        }
+    }
+}
+
+impl<Address, Instant, Bus> BusAccess<Address> for InstantAdapter<Instant, Bus>
+where
+    Address: Copy,
+    Instant: ConvertInstant<Bus::Instant>,
+    Bus: BusAccess<Address>,
+    Bus::Instant: ConvertInstant<Instant>,
+{
+    type Instant = Instant;
+    type Error = Bus::Error;
+
+    #[inline]
+    fn read(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        self.inner.read(now.convert_instant(), addr, data)
+    }
+
+    #[inline]
+    fn write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        self.inner.write(now.convert_instant(), addr, data)
+    }
+
+    #[inline]
+    fn peek(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        self.inner.peek(now.convert_instant(), addr, data)
+    }
+
+    #[inline]
+    fn poke(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        self.inner.poke(now.convert_instant(), addr, data)
+    }
+
+    #[inline]
+    fn read_modify_write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        modify: &mut dyn FnMut(&mut [u8]),
+    ) -> Result<usize, Self::Error> {
+        self.inner
+            .read_modify_write(now.convert_instant(), addr, data, modify)
+    }
+
+    #[inline]
+    fn read_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        let (count, done) = self.inner.read_timed(now.convert_instant(), addr, data)?;
+        Ok((count, done.convert_instant().max(now)))
+    }
+
+    #[inline]
+    fn write_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        let (count, done) = self.inner.write_timed(now.convert_instant(), addr, data)?;
+        Ok((count, done.convert_instant().max(now)))
+    }
+
+    #[inline]
+    fn read_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        self.inner
+            .read_ext(now.convert_instant(), addr, data, transaction)
+    }
+
+    #[inline]
+    fn write_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        self.inner
+            .write_ext(now.convert_instant(), addr, data, transaction)
+    }
+}
+
+/// An adapter that adds a fixed number of wait states to each access of a wrapped bus object
+///
+/// The delays are added to the completion time returned by `read_timed()` and `write_timed()`,
+/// so a cycle-accurate CPU using those methods will see a slow ROM or peripheral take longer
+/// to access.  The untimed methods are passed through unchanged, since they can't report
+/// the delay
+pub struct WaitStates<Bus, Duration> {
+    /// The underlying object implementing `BusAccess` that this object adapts
+    pub inner: Bus,
+    /// The amount of time added to every read
+    pub read_delay: Duration,
+    /// The amount of time added to every write
+    pub write_delay: Duration,
+}
+
+impl<Bus, Duration> WaitStates<Bus, Duration> {
+    /// Construct a new adapter for the given `bus` object, with the given delays
+    pub fn new(inner: Bus, read_delay: Duration, write_delay: Duration) -> Self {
+        Self {
+            inner,
+            read_delay,
+            write_delay,
+        }
+    }
+}
+
+impl<Address, Bus, Duration> BusAccess<Address> for WaitStates<Bus, Duration>
+where
+    Address: Copy,
+    Bus: BusAccess<Address>,
+    Bus::Instant: EmuInstant<Duration = Duration>,
+    Duration: Copy,
+{
+    type Instant = Bus::Instant;
+    type Error = Bus::Error;
+
+    #[inline]
+    fn read(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        self.inner.read(now, addr, data)
+    }
+
+    #[inline]
+    fn write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        self.inner.write(now, addr, data)
+    }
+
+    #[inline]
+    fn peek(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        self.inner.peek(now, addr, data)
+    }
+
+    #[inline]
+    fn poke(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        self.inner.poke(now, addr, data)
+    }
+
+    #[inline]
+    fn read_modify_write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        modify: &mut dyn FnMut(&mut [u8]),
+    ) -> Result<usize, Self::Error> {
+        self.inner.read_modify_write(now, addr, data, modify)
+    }
+
+    #[inline]
+    fn read_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        let (count, done) = self.inner.read_timed(now, addr, data)?;
+        Ok((count, done + self.read_delay))
+    }
+
+    #[inline]
+    fn write_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        let (count, done) = self.inner.write_timed(now, addr, data)?;
+        Ok((count, done + self.write_delay))
+    }
+
+    #[inline]
+    fn read_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        self.inner.read_ext(now, addr, data, transaction)
+    }
+
+    #[inline]
+    fn write_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        self.inner.write_ext(now, addr, data, transaction)
+    }
+}
+
+/// What an adapter does with an access that it doesn't allow
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DeniedAccess {
+    /// Report that the whole access succeeded, discarding written data and reading zeros
+    Ignore,
+    /// Return a count of 0 bytes, so the caller can see that nothing was transferred
+    ZeroBytes,
+    /// Return `BasicBusError::ReadOnly` for writes, or `BasicBusError::WriteOnly` for reads
+    Error,
+}
+
+/// An adapter that allows reads of a wrapped bus object, but denies writes
+///
+/// Writes are handled according to `denied`, so that the same device, such as a `MemoryBlock`
+/// shared through a reference, can be mapped as ROM in one place and RAM in another.  `poke()`
+/// is still passed through to the wrapped bus, so that a debugger or loader can modify the ROM
+pub struct ReadOnly<Bus> {
     /// The underlying object implementing `BusAccess` that this object adapts
     pub inner: Bus,
-    /// The translation function applied
-    pub translate: fn(AddressIn) -> AddressOut,
-    /// Marker for the error type
-    error_out: PhantomData<ErrorOut>,
+    /// What happens when the adapter is written to
+    pub denied: DeniedAccess,
 }
 
-impl<AddressIn, AddressOut, Bus, ErrorOut> BusAdapter<AddressIn, AddressOut, Bus, ErrorOut>
-where
-    AddressIn: Copy,
-    AddressOut: Copy,
-    Bus: BusAccess<AddressOut>,
-    ErrorOut: From<Bus::Error>,
-{
-    /// Construct a new instance of an adapter for the given `bus` object
-    pub fn new(inner: Bus, translate: fn(AddressIn) -> AddressOut) -> Self {
-        Self {
-            inner,
-            translate,
-            error_out: PhantomData,
-        }
+impl<Bus> ReadOnly<Bus> {
+    /// Construct a new adapter for the given `bus` object
+    pub fn new(inner: Bus, denied: DeniedAccess) -> Self {
+        Self { inner, denied }
     }
 }
 
-impl<AddressIn, AddressOut, Bus, ErrorOut> BusAccess<AddressIn>
-    for BusAdapter<AddressIn, AddressOut, Bus, ErrorOut>
+impl<Address, Bus> BusAccess<Address> for ReadOnly<Bus>
 where
-    AddressIn: Copy,
-    AddressOut: Copy,
-    Bus: BusAccess<AddressOut>,
-    ErrorOut: ErrorType + From<Bus::Error>,
+    Address: Copy,
+    Bus: BusAccess<Address>,
+    Bus::Error: From<BasicBusError>,
 {
     type Instant = Bus::Instant;
-    type Error = ErrorOut;
+    type Error = Bus::Error;
 
     #[inline]
     fn read(
         &mut self,
         now: Self::Instant,
-        addr: AddressIn,
+        addr: Address,
         data: &mut [u8],
     ) -> Result<usize, Self::Error> {
-        let addr = (self.translate)(addr);
-        self.inner.read(now, addr, data).map_err(|err| err.into())
+        self.inner.read(now, addr, data)
     }
 
     #[inline]
     fn write(
+        &mut self,
+        _now: Self::Instant,
+        _addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        match self.denied {
+            DeniedAccess::Ignore => Ok(data.len()),
+            DeniedAccess::ZeroBytes => Ok(0),
+            DeniedAccess::Error => Err(BasicBusError::ReadOnly.into()),
+        }
+    }
+
+    #[inline]
+    fn read_ext(
         &mut self,
         now: Self::Instant,
-        addr: AddressIn,
+        addr: Address,
+        data: &mut [u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        self.inner.read_ext(now, addr, data, transaction)
+    }
+
+    #[inline]
+    fn write_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
         data: &[u8],
+        _transaction: Transaction,
     ) -> Result<usize, Self::Error> {
-        let addr = (self.translate)(addr);
-        self.inner.write(now, addr, data).map_err(|err| err.into())
+        self.write(now, addr, data)
+    }
+
+    #[inline]
+    fn read_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        self.inner.read_timed(now, addr, data)
+    }
+
+    #[inline]
+    fn peek(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        self.inner.peek(now, addr, data)
+    }
+
+    #[inline]
+    fn poke(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        self.inner.poke(now, addr, data)
     }
 }
 
-/// An adapter that uses the `FromAddress` trait to translate an address before accessing a wrapped bus object
+/// An adapter that allows writes to a wrapped bus object, but denies reads
 ///
-/// This object implements the `BusAccess` trait, and takes address of type `AddressIn`,
-/// applies `FromAddress<AddressIn>` trait to produce an address of type `AddressOut`,
-/// and then calls the equivalent trait method with that produced address, return the result
-pub struct AutoBusAdapter<AddressIn, AddressOut, Bus, ErrorOut>
-where
-    AddressOut: FromAddress<AddressIn> + Copy,
-    Bus: BusAccess<AddressOut>,
-    ErrorOut: From<Bus::Error>,
-{
+/// Reads are handled according to `denied`, which is useful for mapping latches and other
+/// devices that only decode writes.  `peek()` is still passed through to the wrapped bus, so
+/// that a debugger can see its contents
+pub struct WriteOnly<Bus> {
     /// The underlying object implementing `BusAccess` that this object adapts
     pub inner: Bus,
-
-    address_in: PhantomData<AddressIn>,
-    address_out: PhantomData<AddressOut>,
-    error_out: PhantomData<ErrorOut>,
+    /// What happens when the adapter is read from
+    pub denied: DeniedAccess,
 }
 
-impl<AddressIn, AddressOut, Bus, ErrorOut> AutoBusAdapter<AddressIn, AddressOut, Bus, ErrorOut>
-where
-    AddressOut: FromAddress<AddressIn> + Copy,
-    Bus: BusAccess<AddressOut>,
-    ErrorOut: From<Bus::Error>,
-{
-    /// Construct a new instance of an adapter for the given `bus` object
-    pub fn new(inner: Bus) -> Self {
-        Self {
-            inner,
-            address_in: PhantomData,
-            address_out: PhantomData,
-            error_out: PhantomData,
-        }
+impl<Bus> WriteOnly<Bus> {
+    /// Construct a new adapter for the given `bus` object
+    pub fn new(inner: Bus, denied: DeniedAccess) -> Self {
+        Self { inner, denied }
     }
 }
 
-impl<AddressIn, AddressOut, Bus, ErrorOut> BusAccess<AddressIn>
-    for AutoBusAdapter<AddressIn, AddressOut, Bus, ErrorOut>
+impl<Address, Bus> BusAccess<Address> for WriteOnly<Bus>
 where
-    AddressIn: Copy,
-    AddressOut: FromAddress<AddressIn> + Copy,
-    Bus: BusAccess<AddressOut>,
-    ErrorOut: ErrorType + From<Bus::Error>,
+    Address: Copy,
+    Bus: BusAccess<Address>,
+    Bus::Error: From<BasicBusError>,
 {
     type Instant = Bus::Instant;
-    type Error = ErrorOut;
+    type Error = Bus::Error;
 
     #[inline]
     fn read(
         &mut self,
-        now: Self::Instant,
-        addr: AddressIn,
+        _now: Self::Instant,
+        _addr: Address,
         data: &mut [u8],
     ) -> Result<usize, Self::Error> {
-        let addr = addr.into_address();
-        self.inner.read(now, addr, data).map_err(|err| err.into())
+        match self.denied {
+            DeniedAccess::Ignore => {
+                data.fill(0);
+                Ok(data.len())
+            }
+            DeniedAccess::ZeroBytes => Ok(0),
+            DeniedAccess::Error => Err(BasicBusError::WriteOnly.into()),
+        }
     }
 
     #[inline]
     fn write(
         &mut self,
         now: Self::Instant,
-        addr: AddressIn,
+        addr: Address,
         data: &[u8],
     ) -> Result<usize, Self::Error> {
-        let addr = addr.into_address();
-        self.inner.write(now, addr, data).map_err(|err| err.into())
+        self.inner.write(now, addr, data)
+    }
+
+    #[inline]
+    fn read_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        _transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        self.read(now, addr, data)
+    }
+
+    #[inline]
+    fn write_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        self.inner.write_ext(now, addr, data, transaction)
+    }
+
+    #[inline]
+    fn write_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        self.inner.write_timed(now, addr, data)
+    }
+
+    #[inline]
+    fn peek(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        self.inner.peek(now, addr, data)
+    }
+
+    #[inline]
+    fn poke(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        self.inner.poke(now, addr, data)
     }
 }
 
@@ -210,14 +1655,33 @@ where
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::BusAccessExt;
+    use crate::CycleInstant;
     use crate::ErrorType;
+    use crate::ShortTransfer;
+    use std::cell::Cell;
+    use std::rc::Rc;
     use std::time::Duration;
 
     #[derive(Clone, Debug)]
-    enum Error {}
+    enum Error {
+        Denied,
+    }
 
     impl ErrorType for Error {}
 
+    impl From<BasicBusError> for Error {
+        fn from(_err: BasicBusError) -> Self {
+            Error::Denied
+        }
+    }
+
+    impl From<ShortTransfer> for Error {
+        fn from(_err: ShortTransfer) -> Self {
+            Error::Denied
+        }
+    }
+
     struct Memory(Vec<u8>);
 
     impl BusAccess<u64> for Memory {
@@ -262,6 +1726,12 @@ mod test {
         }
     }
 
+    impl From<ShortTransfer> for Error2 {
+        fn from(_err: ShortTransfer) -> Self {
+            Error2::BusError
+        }
+    }
+
     #[test]
     fn test_adapt_address() {
         let bus = Memory(vec![0; 1024]);
@@ -290,6 +1760,54 @@ mod test {
         assert_eq!(result.unwrap(), expected_value);
     }
 
+    #[test]
+    fn test_adapt_with_closure() {
+        let bus = Memory(vec![0; 1024]);
+        let bank = Rc::new(Cell::new(0_u64));
+
+        let selected = bank.clone();
+        let mut adapter: BusAdapter<u16, u64, _, Error, _> =
+            BusAdapter::new(bus, move |addr| selected.get() * 0x100 + addr as u64);
+
+        adapter.write_u8(Duration::ZERO, 0x10, 0xAA).unwrap();
+        bank.set(2);
+        adapter.write_u8(Duration::ZERO, 0x10, 0xBB).unwrap();
+        assert_eq!(adapter.inner.0[0x010], 0xAA);
+        assert_eq!(adapter.inner.0[0x210], 0xBB);
+    }
+
+    #[test]
+    fn test_offset_and_mask() {
+        let bus = Memory(vec![0; 1024]);
+
+        let mut adapter = OffsetAdapter::new(bus, 0xFF8000_u64, 0x3F);
+        adapter.write_u8(Duration::ZERO, 0xFF8010, 0xAA).unwrap();
+        assert_eq!(adapter.inner.0[0x10], 0xAA);
+        assert_eq!(adapter.read_u8(Duration::ZERO, 0xFF8050).unwrap(), 0xAA);
+
+        let mut adapter = MaskAdapter::new(adapter.inner, 0x1FF_u64);
+        assert_eq!(adapter.read_u8(Duration::ZERO, 0x1010).unwrap(), 0xAA);
+    }
+
+    #[test]
+    fn test_offset_below_base() {
+        let mut adapter = OffsetAdapter::new([0_u8; 0x40], 0xFF8000_u32, 0x3F);
+
+        assert!(matches!(
+            adapter.translate(0xFF7FFF),
+            Err(BasicBusError::UnmappedAddress)
+        ));
+        assert!(matches!(
+            adapter.write_u8(Duration::ZERO, 0x10, 0xAA),
+            Err(BasicBusError::UnmappedAddress)
+        ));
+        assert!(matches!(
+            adapter.read_u8(Duration::ZERO, 0xFF7FFF),
+            Err(BasicBusError::UnmappedAddress)
+        ));
+        assert_eq!(adapter.read_u8(Duration::ZERO, 0xFF8000).unwrap(), 0);
+    }
+
     #[test]
     fn test_auto_adapt_address() {
         let bus = Memory(vec![0; 1024]);
@@ -317,4 +1835,262 @@ mod test {
         let result: Result<u16, Error2> = adapter.read_beu16(Duration::ZERO, 0);
         assert_eq!(result.unwrap(), expected_value);
     }
+
+    #[test]
+    fn test_read_only_and_write_only() {
+        let mut memory = Memory(vec![0; 16]);
+
+        let mut rom = ReadOnly::new(&mut memory, DeniedAccess::Ignore);
+        rom.poke(Duration::ZERO, 0, &[0x12, 0x34]).unwrap();
+        rom.write_u8(Duration::ZERO, 0, 0xFF).unwrap();
+        assert_eq!(rom.read_beu16(Duration::ZERO, 0).unwrap(), 0x1234);
+        rom.denied = DeniedAccess::ZeroBytes;
+        assert_eq!(rom.write(Duration::ZERO, 0, &[0xFF]).unwrap(), 0);
+        rom.denied = DeniedAccess::Error;
+        assert!(matches!(
+            rom.write_u8(Duration::ZERO, 0, 0xFF),
+            Err(Error::Denied)
+        ));
+
+        let mut ram = WriteOnly::new(&mut memory, DeniedAccess::Ignore);
+        ram.write_u8(Duration::ZERO, 1, 0x56).unwrap();
+        assert_eq!(ram.read_beu16(Duration::ZERO, 0).unwrap(), 0);
+        let mut data = [0; 2];
+        ram.peek(Duration::ZERO, 0, &mut data).unwrap();
+        assert_eq!(data, [0x12, 0x56]);
+        ram.denied = DeniedAccess::ZeroBytes;
+        assert_eq!(ram.read(Duration::ZERO, 0, &mut data).unwrap(), 0);
+    }
+
+    /// A memory that counts the read-modify-write cycles made to it
+    struct AtomicMemory {
+        memory: Memory,
+        cycles: usize,
+    }
+
+    impl BusAccess<u64> for AtomicMemory {
+        type Instant = Duration;
+        type Error = Error;
+
+        fn read(
+            &mut self,
+            now: Duration,
+            addr: u64,
+            data: &mut [u8],
+        ) -> Result<usize, Self::Error> {
+            self.memory.read(now, addr, data)
+        }
+
+        fn write(&mut self, now: Duration, addr: u64, data: &[u8]) -> Result<usize, Self::Error> {
+            self.memory.write(now, addr, data)
+        }
+
+        fn read_modify_write(
+            &mut self,
+            now: Duration,
+            addr: u64,
+            data: &mut [u8],
+            modify: &mut dyn FnMut(&mut [u8]),
+        ) -> Result<usize, Self::Error> {
+            self.cycles += 1;
+            self.memory.read_modify_write(now, addr, data, modify)
+        }
+    }
+
+    #[test]
+    fn test_swap_bytes() {
+        let mut bus = SwapBytesAdapter::<_, 4>::new(Memory(vec![0; 16]));
+        let now = Duration::ZERO;
+
+        // a full width access is seen with the same value by a little endian device
+        bus.write_beu32(now, 0x4, 0x1122_3344).unwrap();
+        assert_eq!(bus.inner.read_leu32(now, 0x4).unwrap(), 0x1122_3344);
+        assert_eq!(bus.read_beu32(now, 0x4).unwrap(), 0x1122_3344);
+
+        // narrower accesses are moved to the swapped lanes
+        bus.write_u8(now, 0x8, 0xAA).unwrap();
+        bus.write_beu16(now, 0xA, 0xBBCC).unwrap();
+        assert_eq!(&bus.inner.0[0x8..0xC], &[0xCC, 0xBB, 0x00, 0xAA]);
+        assert_eq!(bus.read_u8(now, 0x4).unwrap(), 0x11);
+
+        // an access which crosses a word is split
+        let mut data = [0; 4];
+        bus.read(now, 0x6, &mut data).unwrap();
+        assert_eq!(data, [0x33, 0x44, 0xAA, 0x00]);
+    }
+
+    #[test]
+    fn test_swap_bytes_read_modify_write() {
+        let memory = AtomicMemory {
+            memory: Memory(vec![0x11, 0x22, 0x33, 0x44, 0, 0, 0, 0]),
+            cycles: 0,
+        };
+        let mut bus = SwapBytesAdapter::<_, 4>::new(memory);
+        let now = Duration::ZERO;
+
+        // the modification sees and returns the bytes in the swapped order
+        let mut data = [0; 4];
+        bus.read_modify_write(now, 0x0, &mut data, &mut |data| data[0] |= 0x80)
+            .unwrap();
+        assert_eq!(data, [0xC4, 0x33, 0x22, 0x11]);
+        assert_eq!(bus.inner.memory.0[..4], [0x11, 0x22, 0x33, 0xC4]);
+        assert_eq!(bus.inner.cycles, 1);
+
+        // an access which crosses a word isn't forwarded as a single cycle
+        let mut data = [0; 2];
+        bus.read_modify_write(now, 0x3, &mut data, &mut |data| data[1] = 0xAA)
+            .unwrap();
+        assert_eq!(
+            bus.inner.memory.0[..8],
+            [0x11, 0x22, 0x33, 0xC4, 0, 0, 0, 0xAA]
+        );
+        assert_eq!(bus.inner.cycles, 1);
+    }
+
+    #[test]
+    fn test_aligned_bus() {
+        let mut bus = AlignedBus::new(Memory(vec![0; 16]), 2);
+        let now = Duration::ZERO;
+
+        bus.write_beu32(now, 2, 0x1234_5678).unwrap();
+        assert_eq!(bus.read_u8(now, 3).unwrap(), 0x34);
+        assert!(matches!(bus.read_beu16(now, 3), Err(Error::Denied)));
+        assert!(matches!(bus.write_beu32(now, 5, 0), Err(Error::Denied)));
+
+        let mut data = [0; 2];
+        bus.peek(now, 3, &mut data).unwrap();
+        assert_eq!(data, [0x34, 0x56]);
+
+        bus.width = 4;
+        assert!(bus.is_aligned(6u64, 2));
+        assert!(!bus.is_aligned(6u64, 4));
+        assert!(!bus.is_aligned(6u64, 8));
+        assert!(bus.is_aligned(8u64, 3));
+    }
+
+    #[test]
+    fn test_wait_states() {
+        let now = Duration::from_nanos(1000);
+        let rom = WaitStates::new(
+            Memory(vec![0x12, 0x34, 0, 0]),
+            Duration::from_nanos(250),
+            Duration::from_nanos(500),
+        );
+        let mut bus = OffsetAdapter::new(rom, 0x100u64, 0xFF);
+
+        let mut data = [0; 2];
+        assert_eq!(
+            bus.read_timed(now, 0x100, &mut data).unwrap(),
+            (2, Duration::from_nanos(1250))
+        );
+        assert_eq!(data, [0x12, 0x34]);
+        assert_eq!(
+            bus.write_timed(now, 0x102, &[0x56]).unwrap(),
+            (1, Duration::from_nanos(1500))
+        );
+        assert_eq!(bus.read_u8(now, 0x102).unwrap(), 0x56);
+
+        // buses without wait states complete at the time they were accessed
+        assert_eq!(
+            bus.inner.inner.read_timed(now, 0, &mut data).unwrap(),
+            (2, now)
+        );
+    }
+
+    #[test]
+    fn test_wait_states_through_wrappers() {
+        let now = Duration::from_nanos(1000);
+        let rom = WaitStates::new(
+            Memory(vec![0x12, 0x34, 0x56, 0x78]),
+            Duration::from_nanos(250),
+            Duration::from_nanos(500),
+        );
+        let rom = ReadOnly::new(rom, DeniedAccess::Error);
+        let mut bus = AlignedBus::new(SwapBytesAdapter::<_, 2>::new(rom), 2);
+
+        // the access is split into one access per word, which are made one after the other
+        let mut data = [0; 4];
+        assert_eq!(
+            bus.read_timed(now, 0_u64, &mut data).unwrap(),
+            (4, Duration::from_nanos(1500))
+        );
+        assert_eq!(data, [0x34, 0x12, 0x78, 0x56]);
+
+        let ram = WaitStates::new(
+            Memory(vec![0; 4]),
+            Duration::from_nanos(250),
+            Duration::from_nanos(500),
+        );
+        let mut bus = WriteOnly::new(ram, DeniedAccess::Error);
+        assert_eq!(
+            bus.write_timed(now, 0_u64, &[0x56]).unwrap(),
+            (1, Duration::from_nanos(1500))
+        );
+    }
+
+    #[test]
+    fn test_instant_adapter() {
+        type CpuInstant = CycleInstant<1_000_000>;
+
+        let memory = WaitStates::new(
+            Memory(vec![0; 0x10]),
+            Duration::from_nanos(2500),
+            Duration::ZERO,
+        );
+        let mut bus = InstantAdapter::<CpuInstant, _>::new(memory);
+        let now = CycleInstant(10);
+
+        bus.write_u8(now, 0x04, 0xAA).unwrap();
+        let mut data = [0];
+        // the wait states are rounded down to whole cycles of the cpu clock
+        assert_eq!(
+            bus.read_timed(now, 0x04, &mut data).unwrap(),
+            (1, CycleInstant(12))
+        );
+        assert_eq!(data, [0xAA]);
+    }
+
+    /// A CPU with 8-bit addresses which copies the byte at its program counter to the next address
+    struct Copier {
+        pc: Address,
+    }
+
+    impl<Bus> Step<Address, Bus> for Copier
+    where
+        Bus: BusAccess<Address, Instant = Duration, Error = Error2>,
+    {
+        type Error = Error2;
+
+        fn is_running(&mut self) -> bool {
+            true
+        }
+
+        fn reset(&mut self, _now: Duration, _bus: &mut Bus) -> Result<(), Self::Error> {
+            self.pc = 0;
+            Ok(())
+        }
+
+        fn step(&mut self, now: Duration, bus: &mut Bus) -> Result<Duration, Self::Error> {
+            let value = bus.read_u8(now, self.pc)?;
+            self.pc += 1;
+            bus.write_u8(now, self.pc, value)?;
+            Ok(now + Duration::from_micros(1))
+        }
+    }
+
+    #[test]
+    fn test_step_adapter() {
+        let mut bus = Memory(vec![0; 0x200]);
+        bus.0[0x100] = 0xAA;
+
+        let mut cpu: StepAdapter<Address, u64, _, Error2, _> =
+            StepAdapter::new(Copier { pc: 0x80 }, |addr| 0x100 + addr as u64);
+        cpu.reset(Duration::ZERO, &mut bus).unwrap();
+        let (next, _) = cpu
+            .run_for(Duration::ZERO, Duration::from_micros(3), &mut bus)
+            .unwrap();
+        assert_eq!(next, Duration::from_micros(3));
+        assert_eq!(cpu.inner.pc, 3);
+        assert_eq!(bus.0[0x100..0x104], [0xAA; 4]);
+    }
 }
@@ -0,0 +1,245 @@
+//! Asynchronous versions of the bus and step traits, for devices backed by slow real I/O
+
+// Devices and executors are single threaded in most emulators, so the futures returned by these
+// traits aren't required to be `Send`
+#![allow(async_fn_in_trait)]
+
+use crate::bus::{BusAccess, ErrorType};
+use crate::time::Instant as EmuInstant;
+
+/// An asynchronous version of `BusAccess`, for devices that may need to wait for real I/O
+///
+/// This is meant for devices backed by something slower than memory, such as a network
+/// adapter, a disk image on remote storage, or a debug probe connected to real hardware, which
+/// can await the I/O instead of blocking the thread the emulation is running on.  Every
+/// `BusAccess` also implements this trait, completing each access immediately, so existing
+/// devices can be used as is on an asynchronous bus.
+///
+/// This requires Rust 1.75 or later, for `async fn` in traits
+pub trait AsyncBusAccess<Address>
+where
+    Address: Copy,
+{
+    /// The type of an instant in simulated time that the bus access is meant to occur at
+    type Instant: EmuInstant;
+
+    /// The type of an error returned by this bus
+    type Error: ErrorType;
+
+    /// Read an arbitrary length of bytes from this device, at time `now`
+    ///
+    /// Returns the number of bytes read, which would normally be the same as `data.len()`
+    async fn read(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error>;
+
+    /// Write an arbitrary length of bytes to this device, at time `now`
+    ///
+    /// Returns the number of bytes written, which would normally be the same as `data.len()`
+    async fn write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error>;
+}
+
+impl<Address, T> AsyncBusAccess<Address> for T
+where
+    Address: Copy,
+    T: BusAccess<Address> + ?Sized,
+{
+    type Instant = T::Instant;
+    type Error = T::Error;
+
+    #[inline]
+    async fn read(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        BusAccess::read(self, now, addr, data)
+    }
+
+    #[inline]
+    async fn write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        BusAccess::write(self, now, addr, data)
+    }
+}
+
+/// An asynchronous version of `Step`, for devices that access an `AsyncBusAccess` bus
+pub trait AsyncStep<Address, Bus>
+where
+    Address: Copy,
+    Bus: AsyncBusAccess<Address>,
+{
+    /// A type that is return if the step cannot be performed
+    type Error;
+
+    /// Returns true if this device is still running.  This can be used to detect a stop or halt condition
+    fn is_running(&mut self) -> bool;
+
+    /// Reset the device to its initial state, as if the device's reset signal was asserted
+    async fn reset(&mut self, now: Bus::Instant, bus: &mut Bus) -> Result<(), Self::Error>;
+
+    /// Step the process by one unit of time, and return the time at which this function should be called again
+    async fn step(&mut self, now: Bus::Instant, bus: &mut Bus)
+        -> Result<Bus::Instant, Self::Error>;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::BasicBusError;
+    use std::future::Future;
+    use std::pin::pin;
+    use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+    use std::time::Duration;
+
+    /// Run a future to completion, polling it until it's ready
+    fn block_on<F: Future>(future: F) -> F::Output {
+        fn raw_waker() -> RawWaker {
+            fn clone(_: *const ()) -> RawWaker {
+                raw_waker()
+            }
+            fn noop(_: *const ()) {}
+            static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+            RawWaker::new(std::ptr::null(), &VTABLE)
+        }
+
+        let waker = unsafe { Waker::from_raw(raw_waker()) };
+        let mut context = Context::from_waker(&waker);
+        let mut future = pin!(future);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+                return output;
+            }
+        }
+    }
+
+    /// A future that is pending the first time it's polled
+    struct Yield(bool);
+
+    impl Future for Yield {
+        type Output = ();
+
+        fn poll(mut self: std::pin::Pin<&mut Self>, context: &mut Context<'_>) -> Poll<()> {
+            if self.0 {
+                Poll::Ready(())
+            } else {
+                self.0 = true;
+                context.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+    }
+
+    /// A memory which must wait before each access, like a remote device would
+    struct RemoteMemory(Vec<u8>);
+
+    impl AsyncBusAccess<u32> for RemoteMemory {
+        type Instant = Duration;
+        type Error = BasicBusError;
+
+        async fn read(
+            &mut self,
+            _now: Duration,
+            addr: u32,
+            data: &mut [u8],
+        ) -> Result<usize, Self::Error> {
+            Yield(false).await;
+            let addr = addr as usize;
+            data.copy_from_slice(&self.0[addr..addr + data.len()]);
+            Ok(data.len())
+        }
+
+        async fn write(
+            &mut self,
+            _now: Duration,
+            addr: u32,
+            data: &[u8],
+        ) -> Result<usize, Self::Error> {
+            Yield(false).await;
+            let addr = addr as usize;
+            self.0[addr..addr + data.len()].copy_from_slice(data);
+            Ok(data.len())
+        }
+    }
+
+    /// A cpu that adds up the bytes in memory until it reads a zero
+    #[derive(Default)]
+    struct Cpu {
+        pc: u32,
+        sum: u32,
+        running: bool,
+    }
+
+    impl<Bus> AsyncStep<u32, Bus> for Cpu
+    where
+        Bus: AsyncBusAccess<u32, Instant = Duration>,
+    {
+        type Error = Bus::Error;
+
+        fn is_running(&mut self) -> bool {
+            self.running
+        }
+
+        async fn reset(&mut self, _now: Duration, _bus: &mut Bus) -> Result<(), Self::Error> {
+            self.pc = 0;
+            self.running = true;
+            Ok(())
+        }
+
+        async fn step(&mut self, now: Duration, bus: &mut Bus) -> Result<Duration, Self::Error> {
+            let mut data = [0];
+            bus.read(now, self.pc, &mut data).await?;
+            self.pc += 1;
+            self.sum += data[0] as u32;
+            self.running = data[0] != 0;
+            Ok(now + Duration::from_nanos(100))
+        }
+    }
+
+    async fn run<Bus>(cpu: &mut Cpu, bus: &mut Bus) -> Result<Duration, Bus::Error>
+    where
+        Bus: AsyncBusAccess<u32, Instant = Duration>,
+    {
+        let mut now = Duration::ZERO;
+        cpu.reset(now, bus).await?;
+        while cpu.running {
+            now = cpu.step(now, bus).await?;
+        }
+        Ok(now)
+    }
+
+    #[test]
+    fn test_async_step() {
+        let mut remote = RemoteMemory(vec![1, 2, 3, 0]);
+        let mut cpu = Cpu::default();
+        assert_eq!(
+            block_on(run(&mut cpu, &mut remote)).unwrap(),
+            Duration::from_nanos(400)
+        );
+        assert_eq!(cpu.sum, 6);
+
+        // a blocking bus can be used in the same way
+        let mut memory = [4_u8, 5, 0];
+        let mut cpu = Cpu::default();
+        block_on(async {
+            AsyncBusAccess::write(&mut memory, Duration::ZERO, 0, &[10])
+                .await
+                .unwrap();
+            run(&mut cpu, &mut memory).await.unwrap();
+        });
+        assert_eq!(cpu.sum, 15);
+    }
+}
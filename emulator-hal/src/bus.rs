@@ -1,11 +1,76 @@
 //! Traits for emulating read and write bus operations
 
+use crate::iter::BusIter;
 use crate::time::Instant;
+use crate::transaction::Transaction;
+use core::any::TypeId;
 use core::convert::Infallible;
 use core::fmt;
+use core::ops::{Add, Range};
 
 /// Represents an error that occurred during a bus transaction
-pub trait ErrorType: fmt::Debug {}
+///
+/// A `dyn ErrorType`, such as the error wrapped by `BasicBusError::Other`, can be downcast back
+/// to its original type with `is()`, `downcast_ref()`, and `downcast_mut()`, so that higher
+/// layers can recover the details of a device-specific error
+pub trait ErrorType: fmt::Debug {
+    /// Returns the `TypeId` of the error's concrete type, which is used for downcasting
+    ///
+    /// This can't be overridden, since downcasting relies on it being correct
+    #[doc(hidden)]
+    fn error_type_id(&self, _: private::Internal) -> TypeId
+    where
+        Self: 'static,
+    {
+        TypeId::of::<Self>()
+    }
+}
+
+mod private {
+    /// Prevents `ErrorType::error_type_id()` from being overridden outside of this crate
+    #[derive(Debug)]
+    pub struct Internal;
+}
+
+impl dyn ErrorType {
+    /// Returns true if the error is of type `T`
+    pub fn is<T: ErrorType + 'static>(&self) -> bool {
+        self.error_type_id(private::Internal) == TypeId::of::<T>()
+    }
+
+    /// Returns a reference to the error as type `T`, if it is of that type
+    pub fn downcast_ref<T: ErrorType + 'static>(&self) -> Option<&T> {
+        if self.is::<T>() {
+            // Safety: the concrete type was just checked to be `T`
+            Some(unsafe { &*(self as *const dyn ErrorType as *const T) })
+        } else {
+            None
+        }
+    }
+
+    /// Returns a mutable reference to the error as type `T`, if it is of that type
+    pub fn downcast_mut<T: ErrorType + 'static>(&mut self) -> Option<&mut T> {
+        if self.is::<T>() {
+            // Safety: the concrete type was just checked to be `T`
+            Some(unsafe { &mut *(self as *mut dyn ErrorType as *mut T) })
+        } else {
+            None
+        }
+    }
+
+    /// Returns the boxed error as type `T`, or the original box if it isn't of that type
+    #[cfg(feature = "alloc")]
+    pub fn downcast<T: ErrorType + 'static>(
+        self: alloc::boxed::Box<Self>,
+    ) -> Result<alloc::boxed::Box<T>, alloc::boxed::Box<Self>> {
+        if self.is::<T>() {
+            // Safety: the concrete type was just checked to be `T`
+            Ok(unsafe { alloc::boxed::Box::from_raw(alloc::boxed::Box::into_raw(self) as *mut T) })
+        } else {
+            Err(self)
+        }
+    }
+}
 
 impl ErrorType for Infallible {}
 
@@ -16,9 +81,16 @@ pub enum BasicBusError {
     /// A write access was requested, but the target is read-only
     ReadOnly,
 
+    /// A read access was requested, but the target is write-only
+    WriteOnly,
+
     /// The address requested is not mapped to a device, so no data can be returned
     UnmappedAddress,
 
+    /// The address requested is not aligned to the size of the access, such as a word access
+    /// to an odd address on a 68000
+    Misaligned,
+
     /// Some other kind of error has occurred
     #[cfg(feature = "alloc")]
     Other(alloc::boxed::Box<dyn ErrorType>),
@@ -26,10 +98,52 @@ pub enum BasicBusError {
     /// Some other kind of error has occurred
     #[cfg(not(feature = "alloc"))]
     Other,
+
+    /// Some other kind of error has occurred, described by a short message
+    ///
+    /// This can be used to convey the details of a device-specific error without `alloc`
+    #[cfg(feature = "heapless")]
+    OtherMessage(heapless::String<32>),
+
+    /// Some other kind of error has occurred, described by a static message
+    ///
+    /// Unlike `Other`, this is available without `alloc`, for devices whose failures can be
+    /// described by a fixed set of messages
+    OtherStatic(&'static str),
+
+    /// Some other kind of error has occurred, identified by a device-specific error code
+    OtherCode(u32),
+
+    /// The device transferred fewer bytes than were requested, which is the number given
+    ShortTransfer(usize),
 }
 
 impl ErrorType for BasicBusError {}
 
+/// An error for a transfer that moved fewer bytes than were requested, which is the number given
+///
+/// This is returned by `BusAccessExt::read_exact()`, `write_all()`, and the numeric helpers, which
+/// are only available for buses whose error type can be converted from it
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ShortTransfer(pub usize);
+
+impl From<ShortTransfer> for BasicBusError {
+    fn from(err: ShortTransfer) -> Self {
+        BasicBusError::ShortTransfer(err.0)
+    }
+}
+
+impl BasicBusError {
+    /// Returns the error wrapped by `BasicBusError::Other` as type `T`, if it is of that type
+    #[cfg(feature = "alloc")]
+    pub fn downcast_other_ref<T: ErrorType + 'static>(&self) -> Option<&T> {
+        match self {
+            BasicBusError::Other(err) => err.downcast_ref(),
+            _ => None,
+        }
+    }
+}
+
 /// Represents the order of bytes in a `BusAccess` operation
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum ByteOrder {
@@ -39,12 +153,55 @@ pub enum ByteOrder {
     Big,
 }
 
+/// A single read or write operation in a batch given to `BusAccess::submit`
+#[derive(Debug)]
+pub enum BusOp<'a, Address> {
+    /// Read into the buffer from the given address
+    Read(Address, &'a mut [u8]),
+    /// Write the buffer to the given address
+    Write(Address, &'a [u8]),
+}
+
+impl<'a, Address> BusOp<'a, Address>
+where
+    Address: Copy,
+{
+    /// Returns the address of this operation
+    #[inline]
+    pub fn addr(&self) -> Address {
+        match self {
+            BusOp::Read(addr, _) | BusOp::Write(addr, _) => *addr,
+        }
+    }
+
+    /// Returns the number of bytes to be read or written by this operation
+    #[inline]
+    pub fn len(&self) -> usize {
+        match self {
+            BusOp::Read(_, data) => data.len(),
+            BusOp::Write(_, data) => data.len(),
+        }
+    }
+
+    /// Returns true if this operation doesn't transfer any bytes
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
 /// A device that can be addressed to read data from or write data to the device.
 ///
 /// This represents access to a peripheral device or a bus of multiple devices, which can be
 /// used by a controller (eg. CPU).  The address can either be a single number or a tuple to
 /// represent different address spaces, such as memory vs I/O spaces as in the Z80 CPUs, or
-/// supervisor vs user access as in the Function Code present on 68k CPUs.
+/// supervisor vs user access as in the Function Code present on 68k CPUs.  The `MemOrIo` and
+/// `FunctionCode` types can be used for these, with `SpaceRouter` to dispatch on the space, and
+/// `IoBus` to access each space separately from within a CPU.
+///
+/// This trait is object safe, so devices can be stored as `Box<dyn BusAccess<...>>`.  The
+/// helpers for reading and writing numeric values are provided by `BusAccessExt`, which is
+/// implemented for all `BusAccess` types, including trait objects
 pub trait BusAccess<Address>
 where
     Address: Copy,
@@ -77,27 +234,224 @@ where
         data: &[u8],
     ) -> Result<usize, Self::Error>;
 
+    /// Read an arbitrary length of bytes from this device without causing any side effects
+    ///
+    /// This is meant for debuggers and memory viewers, which need to show the contents of
+    /// registers without changing the state of the device, such as by clearing a status flag or
+    /// popping a byte from a FIFO.  Devices whose reads have side effects should override this
+    /// to return the current value instead.  The default implementation calls `read`
+    #[inline]
+    fn peek(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        self.read(now, addr, data)
+    }
+
+    /// Write an arbitrary length of bytes into this device on behalf of a debugger
+    ///
+    /// Devices can override this to change their state directly, such as writing to read-only
+    /// memory or setting a register without triggering the action a write would normally start.
+    /// The default implementation calls `write`
+    #[inline]
+    fn poke(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        self.write(now, addr, data)
+    }
+
+    /// Execute a batch of read and write operations in order, at time `now`
+    ///
+    /// Returns the total number of bytes read and written.  Execution stops at the first
+    /// operation that returns an error.  The default implementation calls `read` or `write` for
+    /// each operation, but devices can override it to transfer the whole batch at once, such as
+    /// for DMA bursts and block transfers, which also saves a dynamic dispatch per operation
+    /// when the device is a trait object
+    fn submit(
+        &mut self,
+        now: Self::Instant,
+        ops: &mut [BusOp<'_, Address>],
+    ) -> Result<usize, Self::Error> {
+        let mut total = 0;
+        for op in ops.iter_mut() {
+            total += match op {
+                BusOp::Read(addr, data) => self.read(now, *addr, data)?,
+                BusOp::Write(addr, data) => self.write(now, *addr, data)?,
+            };
+        }
+        Ok(total)
+    }
+
+    /// Read `data.len()` bytes at the given address, pass them to `modify`, and write the
+    /// modified bytes back to the same address, as a single indivisible bus cycle
+    ///
+    /// This is meant for instructions like TAS and CAS on the 68000, which must not have another
+    /// bus master access the location between the read and the write.  On return, `data` holds
+    /// the bytes that were written, so the closure must save the original value if it's needed.
+    /// The default implementation calls `read` and then `write`, which is only atomic if nothing
+    /// else can access the bus in between.  Buses shared between threads or bus masters should
+    /// override this to hold their lock, or otherwise block other masters, for both accesses
+    fn read_modify_write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        modify: &mut dyn FnMut(&mut [u8]),
+    ) -> Result<usize, Self::Error> {
+        self.read(now, addr, data)?;
+        modify(data);
+        self.write(now, addr, data)
+    }
+
+    /// Read an arbitrary length of bytes from this device, at time `now`, and return the time at
+    /// which the access completed along with the number of bytes read
+    ///
+    /// This lets slow devices, such as ROMs and peripherals that insert wait states, tell a
+    /// cycle-accurate CPU how long the access took, so it can delay its next step accordingly.
+    /// The default implementation calls `read` and returns `now`, for devices that respond
+    /// without delay
+    #[inline]
+    fn read_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        Ok((self.read(now, addr, data)?, now))
+    }
+
+    /// Write an arbitrary length of bytes into this device, at time `now`, and return the time
+    /// at which the access completed along with the number of bytes written
+    ///
+    /// The default implementation calls `write` and returns `now`
+    #[inline]
+    fn write_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        Ok((self.write(now, addr, data)?, now))
+    }
+
+    /// Read an arbitrary length of bytes from this device, at time `now`, with a descriptor of
+    /// the sideband signals of the transaction, such as whether it's an instruction fetch
+    ///
+    /// This lets devices that depend on those signals, such as memory protection units, act on
+    /// them.  The default implementation ignores the descriptor and calls `read`
+    #[inline]
+    fn read_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        _transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        self.read(now, addr, data)
+    }
+
+    /// Write an arbitrary length of bytes into this device, at time `now`, with a descriptor of
+    /// the sideband signals of the transaction, such as whether it's made in supervisor mode
+    ///
+    /// The default implementation ignores the descriptor and calls `write`
+    #[inline]
+    fn write_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+        _transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        self.write(now, addr, data)
+    }
+}
+
+/// Helper methods for reading and writing numeric values, implemented for every `BusAccess`
+///
+/// These are kept out of `BusAccess` so that the core trait only has the methods a device might
+/// implement.  Since this trait is implemented for `dyn BusAccess` as well, trait objects have
+/// the same helpers as concrete bus types
+pub trait BusAccessExt<Address>: BusAccess<Address>
+where
+    Address: Copy,
+{
+    /// Read exactly `data.len()` bytes at the given address
+    ///
+    /// Returns a `ShortTransfer` error, converted into the bus's error type, if fewer bytes were
+    /// read
+    #[inline]
+    fn read_exact(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<(), Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
+        let count = self.read(now, addr, data)?;
+        if count < data.len() {
+            return Err(ShortTransfer(count).into());
+        }
+        Ok(())
+    }
+
+    /// Write all of `data` at the given address
+    ///
+    /// Returns a `ShortTransfer` error, converted into the bus's error type, if fewer bytes were
+    /// written, such as when writing to read-only memory
+    #[inline]
+    fn write_all(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<(), Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
+        let count = self.write(now, addr, data)?;
+        if count < data.len() {
+            return Err(ShortTransfer(count).into());
+        }
+        Ok(())
+    }
+
     /// Read a single u8 value at the given address
     #[inline]
-    fn read_u8(&mut self, now: Self::Instant, addr: Address) -> Result<u8, Self::Error> {
+    fn read_u8(&mut self, now: Self::Instant, addr: Address) -> Result<u8, Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
         let mut data = [0; 1];
-        self.read(now, addr, &mut data)?;
+        self.read_exact(now, addr, &mut data)?;
         Ok(data[0])
     }
 
     /// Read a single u16 value in big endian byte order at the given address
     #[inline]
-    fn read_beu16(&mut self, now: Self::Instant, addr: Address) -> Result<u16, Self::Error> {
+    fn read_beu16(&mut self, now: Self::Instant, addr: Address) -> Result<u16, Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
         let mut data = [0; 2];
-        self.read(now, addr, &mut data)?;
+        self.read_exact(now, addr, &mut data)?;
         Ok(u16::from_be_bytes(data))
     }
 
     /// Read a single u16 value in little endian byte order at the given address
     #[inline]
-    fn read_leu16(&mut self, now: Self::Instant, addr: Address) -> Result<u16, Self::Error> {
+    fn read_leu16(&mut self, now: Self::Instant, addr: Address) -> Result<u16, Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
         let mut data = [0; 2];
-        self.read(now, addr, &mut data)?;
+        self.read_exact(now, addr, &mut data)?;
         Ok(u16::from_le_bytes(data))
     }
 
@@ -108,7 +462,10 @@ where
         order: ByteOrder,
         now: Self::Instant,
         addr: Address,
-    ) -> Result<u16, Self::Error> {
+    ) -> Result<u16, Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
         match order {
             ByteOrder::Little => self.read_leu16(now, addr),
             ByteOrder::Big => self.read_beu16(now, addr),
@@ -117,17 +474,23 @@ where
 
     /// Read a single u32 value in big endian byte order at the given address
     #[inline]
-    fn read_beu32(&mut self, now: Self::Instant, addr: Address) -> Result<u32, Self::Error> {
+    fn read_beu32(&mut self, now: Self::Instant, addr: Address) -> Result<u32, Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
         let mut data = [0; 4];
-        self.read(now, addr, &mut data)?;
+        self.read_exact(now, addr, &mut data)?;
         Ok(u32::from_be_bytes(data))
     }
 
     /// Read a single u32 value in little endian byte order at the given address
     #[inline]
-    fn read_leu32(&mut self, now: Self::Instant, addr: Address) -> Result<u32, Self::Error> {
+    fn read_leu32(&mut self, now: Self::Instant, addr: Address) -> Result<u32, Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
         let mut data = [0; 4];
-        self.read(now, addr, &mut data)?;
+        self.read_exact(now, addr, &mut data)?;
         Ok(u32::from_le_bytes(data))
     }
 
@@ -138,7 +501,10 @@ where
         order: ByteOrder,
         now: Self::Instant,
         addr: Address,
-    ) -> Result<u32, Self::Error> {
+    ) -> Result<u32, Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
         match order {
             ByteOrder::Little => self.read_leu32(now, addr),
             ByteOrder::Big => self.read_beu32(now, addr),
@@ -147,17 +513,23 @@ where
 
     /// Read a single u64 value in big endian byte order at the given address
     #[inline]
-    fn read_beu64(&mut self, now: Self::Instant, addr: Address) -> Result<u64, Self::Error> {
+    fn read_beu64(&mut self, now: Self::Instant, addr: Address) -> Result<u64, Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
         let mut data = [0; 8];
-        self.read(now, addr, &mut data)?;
+        self.read_exact(now, addr, &mut data)?;
         Ok(u64::from_be_bytes(data))
     }
 
     /// Read a single u64 value in little endian byte order at the given address
     #[inline]
-    fn read_leu64(&mut self, now: Self::Instant, addr: Address) -> Result<u64, Self::Error> {
+    fn read_leu64(&mut self, now: Self::Instant, addr: Address) -> Result<u64, Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
         let mut data = [0; 8];
-        self.read(now, addr, &mut data)?;
+        self.read_exact(now, addr, &mut data)?;
         Ok(u64::from_le_bytes(data))
     }
 
@@ -168,7 +540,10 @@ where
         order: ByteOrder,
         now: Self::Instant,
         addr: Address,
-    ) -> Result<u64, Self::Error> {
+    ) -> Result<u64, Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
         match order {
             ByteOrder::Little => self.read_leu64(now, addr),
             ByteOrder::Big => self.read_beu64(now, addr),
@@ -177,14 +552,12 @@ where
 
     /// Write a single u8 value to the given address
     #[inline]
-    fn write_u8(
-        &mut self,
-        now: Self::Instant,
-        addr: Address,
-        value: u8,
-    ) -> Result<(), Self::Error> {
+    fn write_u8(&mut self, now: Self::Instant, addr: Address, value: u8) -> Result<(), Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
         let data = [value];
-        self.write(now, addr, &data)?;
+        self.write_all(now, addr, &data)?;
         Ok(())
     }
 
@@ -195,9 +568,12 @@ where
         now: Self::Instant,
         addr: Address,
         value: u16,
-    ) -> Result<(), Self::Error> {
+    ) -> Result<(), Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
         let data = value.to_be_bytes();
-        self.write(now, addr, &data)?;
+        self.write_all(now, addr, &data)?;
         Ok(())
     }
 
@@ -208,9 +584,12 @@ where
         now: Self::Instant,
         addr: Address,
         value: u16,
-    ) -> Result<(), Self::Error> {
+    ) -> Result<(), Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
         let data = value.to_le_bytes();
-        self.write(now, addr, &data)?;
+        self.write_all(now, addr, &data)?;
         Ok(())
     }
 
@@ -222,7 +601,10 @@ where
         now: Self::Instant,
         addr: Address,
         value: u16,
-    ) -> Result<(), Self::Error> {
+    ) -> Result<(), Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
         match order {
             ByteOrder::Little => self.write_leu16(now, addr, value),
             ByteOrder::Big => self.write_beu16(now, addr, value),
@@ -236,9 +618,12 @@ where
         now: Self::Instant,
         addr: Address,
         value: u32,
-    ) -> Result<(), Self::Error> {
+    ) -> Result<(), Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
         let data = value.to_be_bytes();
-        self.write(now, addr, &data)?;
+        self.write_all(now, addr, &data)?;
         Ok(())
     }
 
@@ -249,9 +634,12 @@ where
         now: Self::Instant,
         addr: Address,
         value: u32,
-    ) -> Result<(), Self::Error> {
+    ) -> Result<(), Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
         let data = value.to_le_bytes();
-        self.write(now, addr, &data)?;
+        self.write_all(now, addr, &data)?;
         Ok(())
     }
 
@@ -263,7 +651,10 @@ where
         now: Self::Instant,
         addr: Address,
         value: u32,
-    ) -> Result<(), Self::Error> {
+    ) -> Result<(), Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
         match order {
             ByteOrder::Little => self.write_leu32(now, addr, value),
             ByteOrder::Big => self.write_beu32(now, addr, value),
@@ -277,9 +668,12 @@ where
         now: Self::Instant,
         addr: Address,
         value: u64,
-    ) -> Result<(), Self::Error> {
+    ) -> Result<(), Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
         let data = value.to_be_bytes();
-        self.write(now, addr, &data)?;
+        self.write_all(now, addr, &data)?;
         Ok(())
     }
 
@@ -290,9 +684,12 @@ where
         now: Self::Instant,
         addr: Address,
         value: u64,
-    ) -> Result<(), Self::Error> {
+    ) -> Result<(), Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
         let data = value.to_le_bytes();
-        self.write(now, addr, &data)?;
+        self.write_all(now, addr, &data)?;
         Ok(())
     }
 
@@ -304,116 +701,1095 @@ where
         now: Self::Instant,
         addr: Address,
         value: u64,
-    ) -> Result<(), Self::Error> {
+    ) -> Result<(), Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
         match order {
             ByteOrder::Little => self.write_leu64(now, addr, value),
             ByteOrder::Big => self.write_beu64(now, addr, value),
         }
     }
-}
 
-impl<Address, T> BusAccess<Address> for &mut T
-where
-    Address: Copy,
-    T: BusAccess<Address> + ?Sized,
-{
-    type Instant = T::Instant;
-    type Error = T::Error;
+    /// Read a single i8 value at the given address
+    #[inline]
+    fn read_i8(&mut self, now: Self::Instant, addr: Address) -> Result<i8, Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
+        Ok(self.read_u8(now, addr)? as i8)
+    }
 
+    /// Read a single i16 value in big endian byte order at the given address
     #[inline]
-    fn read(
+    fn read_bei16(&mut self, now: Self::Instant, addr: Address) -> Result<i16, Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
+        Ok(self.read_beu16(now, addr)? as i16)
+    }
+
+    /// Read a single i16 value in little endian byte order at the given address
+    #[inline]
+    fn read_lei16(&mut self, now: Self::Instant, addr: Address) -> Result<i16, Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
+        Ok(self.read_leu16(now, addr)? as i16)
+    }
+
+    /// Read a single i16 value in the given byte order at the given address
+    #[inline]
+    fn read_i16(
         &mut self,
+        order: ByteOrder,
         now: Self::Instant,
         addr: Address,
-        data: &mut [u8],
-    ) -> Result<usize, T::Error> {
-        T::read(self, now, addr, data)
+    ) -> Result<i16, Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
+        match order {
+            ByteOrder::Little => self.read_lei16(now, addr),
+            ByteOrder::Big => self.read_bei16(now, addr),
+        }
     }
 
+    /// Read a single i32 value in big endian byte order at the given address
     #[inline]
-    fn write(&mut self, now: Self::Instant, addr: Address, data: &[u8]) -> Result<usize, T::Error> {
-        T::write(self, now, addr, data)
+    fn read_bei32(&mut self, now: Self::Instant, addr: Address) -> Result<i32, Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
+        Ok(self.read_beu32(now, addr)? as i32)
     }
-}
 
-#[cfg(feature = "alloc")]
-impl<Address, T> BusAccess<Address> for alloc::boxed::Box<T>
-where
-    Address: Copy,
-    T: BusAccess<Address> + ?Sized,
-{
-    type Instant = T::Instant;
-    type Error = T::Error;
+    /// Read a single i32 value in little endian byte order at the given address
+    #[inline]
+    fn read_lei32(&mut self, now: Self::Instant, addr: Address) -> Result<i32, Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
+        Ok(self.read_leu32(now, addr)? as i32)
+    }
 
+    /// Read a single i32 value in the given byte order at the given address
     #[inline]
-    fn read(
+    fn read_i32(
         &mut self,
+        order: ByteOrder,
         now: Self::Instant,
         addr: Address,
-        data: &mut [u8],
-    ) -> Result<usize, T::Error> {
-        T::read(self, now, addr, data)
+    ) -> Result<i32, Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
+        match order {
+            ByteOrder::Little => self.read_lei32(now, addr),
+            ByteOrder::Big => self.read_bei32(now, addr),
+        }
     }
 
+    /// Read a single i64 value in big endian byte order at the given address
     #[inline]
-    fn write(&mut self, now: Self::Instant, addr: Address, data: &[u8]) -> Result<usize, T::Error> {
-        T::write(self, now, addr, data)
+    fn read_bei64(&mut self, now: Self::Instant, addr: Address) -> Result<i64, Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
+        Ok(self.read_beu64(now, addr)? as i64)
     }
-}
 
-#[cfg(test)]
-mod test {
-    use super::*;
-    use std::time::Duration;
-
-    #[test]
-    fn test_implemeting_memory() {
-        #[derive(Clone, Debug)]
-        enum Error {}
-
-        impl ErrorType for Error {}
+    /// Read a single i64 value in little endian byte order at the given address
+    #[inline]
+    fn read_lei64(&mut self, now: Self::Instant, addr: Address) -> Result<i64, Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
+        Ok(self.read_leu64(now, addr)? as i64)
+    }
 
-        struct Memory(Vec<u8>);
+    /// Read a single i64 value in the given byte order at the given address
+    #[inline]
+    fn read_i64(
+        &mut self,
+        order: ByteOrder,
+        now: Self::Instant,
+        addr: Address,
+    ) -> Result<i64, Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
+        match order {
+            ByteOrder::Little => self.read_lei64(now, addr),
+            ByteOrder::Big => self.read_bei64(now, addr),
+        }
+    }
 
-        impl BusAccess<u64> for Memory {
-            type Instant = Duration;
-            type Error = Error;
+    /// Read a single f32 value in big endian byte order at the given address
+    #[inline]
+    fn read_bef32(&mut self, now: Self::Instant, addr: Address) -> Result<f32, Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
+        Ok(f32::from_bits(self.read_beu32(now, addr)?))
+    }
 
-            fn read(
-                &mut self,
-                _now: Duration,
-                addr: u64,
-                data: &mut [u8],
-            ) -> Result<usize, Self::Error> {
-                let addr = addr as usize;
-                data.copy_from_slice(&self.0[addr..addr + data.len()]);
-                Ok(data.len())
-            }
+    /// Read a single f32 value in little endian byte order at the given address
+    #[inline]
+    fn read_lef32(&mut self, now: Self::Instant, addr: Address) -> Result<f32, Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
+        Ok(f32::from_bits(self.read_leu32(now, addr)?))
+    }
 
-            fn write(
-                &mut self,
-                _now: Duration,
-                addr: u64,
-                data: &[u8],
-            ) -> Result<usize, Self::Error> {
-                let addr = addr as usize;
-                self.0[addr..addr + data.len()].copy_from_slice(data);
-                Ok(data.len())
-            }
+    /// Read a single f32 value in the given byte order at the given address
+    #[inline]
+    fn read_f32(
+        &mut self,
+        order: ByteOrder,
+        now: Self::Instant,
+        addr: Address,
+    ) -> Result<f32, Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
+        match order {
+            ByteOrder::Little => self.read_lef32(now, addr),
+            ByteOrder::Big => self.read_bef32(now, addr),
         }
+    }
 
-        let mut bus = Memory(vec![0; 1024]);
+    /// Read a single f64 value in big endian byte order at the given address
+    #[inline]
+    fn read_bef64(&mut self, now: Self::Instant, addr: Address) -> Result<f64, Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
+        Ok(f64::from_bits(self.read_beu64(now, addr)?))
+    }
 
-        let number = 0x1234_5678;
-        bus.write_beu32(Duration::START, 0, number).unwrap();
-        assert_eq!(
-            u32::from_be_bytes(bus.0[0..4].try_into().unwrap()),
-            0x1234_5678
-        );
+    /// Read a single f64 value in little endian byte order at the given address
+    #[inline]
+    fn read_lef64(&mut self, now: Self::Instant, addr: Address) -> Result<f64, Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
+        Ok(f64::from_bits(self.read_leu64(now, addr)?))
+    }
+
+    /// Read a single f64 value in the given byte order at the given address
+    #[inline]
+    fn read_f64(
+        &mut self,
+        order: ByteOrder,
+        now: Self::Instant,
+        addr: Address,
+    ) -> Result<f64, Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
+        match order {
+            ByteOrder::Little => self.read_lef64(now, addr),
+            ByteOrder::Big => self.read_bef64(now, addr),
+        }
+    }
+
+    /// Write the given i8 value to the given address
+    #[inline]
+    fn write_i8(&mut self, now: Self::Instant, addr: Address, value: i8) -> Result<(), Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
+        self.write_u8(now, addr, value as u8)
+    }
+
+    /// Write the given i16 value in big endian byte order to the given address
+    #[inline]
+    fn write_bei16(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        value: i16,
+    ) -> Result<(), Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
+        self.write_beu16(now, addr, value as u16)
+    }
+
+    /// Write the given i16 value in little endian byte order to the given address
+    #[inline]
+    fn write_lei16(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        value: i16,
+    ) -> Result<(), Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
+        self.write_leu16(now, addr, value as u16)
+    }
+
+    /// Write the given i16 value in the given byte order to the given address
+    #[inline]
+    fn write_i16(
+        &mut self,
+        order: ByteOrder,
+        now: Self::Instant,
+        addr: Address,
+        value: i16,
+    ) -> Result<(), Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
+        match order {
+            ByteOrder::Little => self.write_lei16(now, addr, value),
+            ByteOrder::Big => self.write_bei16(now, addr, value),
+        }
+    }
+
+    /// Write the given i32 value in big endian byte order to the given address
+    #[inline]
+    fn write_bei32(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        value: i32,
+    ) -> Result<(), Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
+        self.write_beu32(now, addr, value as u32)
+    }
+
+    /// Write the given i32 value in little endian byte order to the given address
+    #[inline]
+    fn write_lei32(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        value: i32,
+    ) -> Result<(), Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
+        self.write_leu32(now, addr, value as u32)
+    }
+
+    /// Write the given i32 value in the given byte order to the given address
+    #[inline]
+    fn write_i32(
+        &mut self,
+        order: ByteOrder,
+        now: Self::Instant,
+        addr: Address,
+        value: i32,
+    ) -> Result<(), Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
+        match order {
+            ByteOrder::Little => self.write_lei32(now, addr, value),
+            ByteOrder::Big => self.write_bei32(now, addr, value),
+        }
+    }
+
+    /// Write the given i64 value in big endian byte order to the given address
+    #[inline]
+    fn write_bei64(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        value: i64,
+    ) -> Result<(), Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
+        self.write_beu64(now, addr, value as u64)
+    }
+
+    /// Write the given i64 value in little endian byte order to the given address
+    #[inline]
+    fn write_lei64(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        value: i64,
+    ) -> Result<(), Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
+        self.write_leu64(now, addr, value as u64)
+    }
+
+    /// Write the given i64 value in the given byte order to the given address
+    #[inline]
+    fn write_i64(
+        &mut self,
+        order: ByteOrder,
+        now: Self::Instant,
+        addr: Address,
+        value: i64,
+    ) -> Result<(), Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
+        match order {
+            ByteOrder::Little => self.write_lei64(now, addr, value),
+            ByteOrder::Big => self.write_bei64(now, addr, value),
+        }
+    }
+
+    /// Write the given f32 value in big endian byte order to the given address
+    #[inline]
+    fn write_bef32(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        value: f32,
+    ) -> Result<(), Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
+        self.write_beu32(now, addr, value.to_bits())
+    }
+
+    /// Write the given f32 value in little endian byte order to the given address
+    #[inline]
+    fn write_lef32(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        value: f32,
+    ) -> Result<(), Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
+        self.write_leu32(now, addr, value.to_bits())
+    }
+
+    /// Write the given f32 value in the given byte order to the given address
+    #[inline]
+    fn write_f32(
+        &mut self,
+        order: ByteOrder,
+        now: Self::Instant,
+        addr: Address,
+        value: f32,
+    ) -> Result<(), Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
+        match order {
+            ByteOrder::Little => self.write_lef32(now, addr, value),
+            ByteOrder::Big => self.write_bef32(now, addr, value),
+        }
+    }
+
+    /// Write the given f64 value in big endian byte order to the given address
+    #[inline]
+    fn write_bef64(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        value: f64,
+    ) -> Result<(), Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
+        self.write_beu64(now, addr, value.to_bits())
+    }
+
+    /// Write the given f64 value in little endian byte order to the given address
+    #[inline]
+    fn write_lef64(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        value: f64,
+    ) -> Result<(), Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
+        self.write_leu64(now, addr, value.to_bits())
+    }
+
+    /// Write the given f64 value in the given byte order to the given address
+    #[inline]
+    fn write_f64(
+        &mut self,
+        order: ByteOrder,
+        now: Self::Instant,
+        addr: Address,
+        value: f64,
+    ) -> Result<(), Self::Error>
+    where
+        Self::Error: From<ShortTransfer>,
+    {
+        match order {
+            ByteOrder::Little => self.write_lef64(now, addr, value),
+            ByteOrder::Big => self.write_bef64(now, addr, value),
+        }
+    }
+
+    /// Returns an iterator over the u8 values in the given range of addresses
+    #[inline]
+    fn iter_u8(
+        &mut self,
+        now: Self::Instant,
+        range: Range<Address>,
+    ) -> BusIter<'_, Address, Self, u8>
+    where
+        Address: Into<u64> + TryFrom<u64>,
+        Self::Error: From<ShortTransfer>,
+    {
+        BusIter::new(self, now, range, 1, |bytes| bytes[0])
+    }
+
+    /// Returns an iterator over the u16 values in big endian byte order in the given range of addresses
+    #[inline]
+    fn iter_beu16(
+        &mut self,
+        now: Self::Instant,
+        range: Range<Address>,
+    ) -> BusIter<'_, Address, Self, u16>
+    where
+        Address: Into<u64> + TryFrom<u64>,
+        Self::Error: From<ShortTransfer>,
+    {
+        BusIter::new(self, now, range, 2, |bytes| {
+            u16::from_be_bytes(bytes.try_into().unwrap())
+        })
+    }
+
+    /// Returns an iterator over the u16 values in little endian byte order in the given range of addresses
+    #[inline]
+    fn iter_leu16(
+        &mut self,
+        now: Self::Instant,
+        range: Range<Address>,
+    ) -> BusIter<'_, Address, Self, u16>
+    where
+        Address: Into<u64> + TryFrom<u64>,
+        Self::Error: From<ShortTransfer>,
+    {
+        BusIter::new(self, now, range, 2, |bytes| {
+            u16::from_le_bytes(bytes.try_into().unwrap())
+        })
+    }
+
+    /// Returns an iterator over the u32 values in big endian byte order in the given range of addresses
+    #[inline]
+    fn iter_beu32(
+        &mut self,
+        now: Self::Instant,
+        range: Range<Address>,
+    ) -> BusIter<'_, Address, Self, u32>
+    where
+        Address: Into<u64> + TryFrom<u64>,
+        Self::Error: From<ShortTransfer>,
+    {
+        BusIter::new(self, now, range, 4, |bytes| {
+            u32::from_be_bytes(bytes.try_into().unwrap())
+        })
+    }
+
+    /// Returns an iterator over the u32 values in little endian byte order in the given range of addresses
+    #[inline]
+    fn iter_leu32(
+        &mut self,
+        now: Self::Instant,
+        range: Range<Address>,
+    ) -> BusIter<'_, Address, Self, u32>
+    where
+        Address: Into<u64> + TryFrom<u64>,
+        Self::Error: From<ShortTransfer>,
+    {
+        BusIter::new(self, now, range, 4, |bytes| {
+            u32::from_le_bytes(bytes.try_into().unwrap())
+        })
+    }
+
+    /// Returns an iterator over the u64 values in big endian byte order in the given range of addresses
+    #[inline]
+    fn iter_beu64(
+        &mut self,
+        now: Self::Instant,
+        range: Range<Address>,
+    ) -> BusIter<'_, Address, Self, u64>
+    where
+        Address: Into<u64> + TryFrom<u64>,
+        Self::Error: From<ShortTransfer>,
+    {
+        BusIter::new(self, now, range, 8, |bytes| {
+            u64::from_be_bytes(bytes.try_into().unwrap())
+        })
+    }
+
+    /// Returns an iterator over the u64 values in little endian byte order in the given range of addresses
+    #[inline]
+    fn iter_leu64(
+        &mut self,
+        now: Self::Instant,
+        range: Range<Address>,
+    ) -> BusIter<'_, Address, Self, u64>
+    where
+        Address: Into<u64> + TryFrom<u64>,
+        Self::Error: From<ShortTransfer>,
+    {
+        BusIter::new(self, now, range, 8, |bytes| {
+            u64::from_le_bytes(bytes.try_into().unwrap())
+        })
+    }
+}
+
+impl<Address, T> BusAccessExt<Address> for T
+where
+    Address: Copy,
+    T: BusAccess<Address> + ?Sized,
+{
+}
+
+/// The number of bytes transferred by each read and write of `copy_between()`
+const COPY_CHUNK_SIZE: usize = 128;
+
+/// Copy `len` bytes from `src_addr` on one bus to `dst_addr` on another bus, at time `now`
+///
+/// The bytes are copied in chunks through a buffer on the stack, so large transfers like DMA or
+/// loading a program don't need a read and write for each byte, or a heap allocation.  Returns
+/// the number of bytes copied, which is less than `len` if either bus returns a short count, in
+/// which case the copy stops after that chunk.  The copy also stops early if an offset into the
+/// range can't be converted into an address.  To copy within a single bus, two clones of an
+/// `Rc<RefCell<T>>` of the bus can be given, as long as the ranges don't overlap
+pub fn copy_between<SrcAddress, DstAddress, Src, Dst>(
+    now: Src::Instant,
+    src: &mut Src,
+    src_addr: SrcAddress,
+    dst: &mut Dst,
+    dst_addr: DstAddress,
+    len: usize,
+) -> Result<usize, Dst::Error>
+where
+    SrcAddress: Copy + Add<Output = SrcAddress> + TryFrom<usize>,
+    DstAddress: Copy + Add<Output = DstAddress> + TryFrom<usize>,
+    Src: BusAccess<SrcAddress> + ?Sized,
+    Dst: BusAccess<DstAddress, Instant = Src::Instant> + ?Sized,
+    Dst::Error: From<Src::Error>,
+{
+    let mut buffer = [0; COPY_CHUNK_SIZE];
+    let mut copied = 0;
+    while copied < len {
+        // the offset is only converted when there's more to copy, so that a range ending at
+        // the top of the address space doesn't overflow
+        let (src_offset, dst_offset) =
+            match (SrcAddress::try_from(copied), DstAddress::try_from(copied)) {
+                (Ok(src_offset), Ok(dst_offset)) => (src_offset, dst_offset),
+                _ => break,
+            };
+        let chunk = (len - copied).min(COPY_CHUNK_SIZE);
+        let read = src.read(now, src_addr + src_offset, &mut buffer[..chunk])?;
+        let written = dst.write(now, dst_addr + dst_offset, &buffer[..read])?;
+        copied += written;
+        if written < chunk {
+            break;
+        }
+    }
+    Ok(copied)
+}
+
+impl<Address, T> BusAccess<Address> for &mut T
+where
+    Address: Copy,
+    T: BusAccess<Address> + ?Sized,
+{
+    type Instant = T::Instant;
+    type Error = T::Error;
+
+    #[inline]
+    fn read(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, T::Error> {
+        T::read(self, now, addr, data)
+    }
+
+    #[inline]
+    fn write(&mut self, now: Self::Instant, addr: Address, data: &[u8]) -> Result<usize, T::Error> {
+        T::write(self, now, addr, data)
+    }
+
+    #[inline]
+    fn peek(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, T::Error> {
+        T::peek(self, now, addr, data)
+    }
+
+    #[inline]
+    fn poke(&mut self, now: Self::Instant, addr: Address, data: &[u8]) -> Result<usize, T::Error> {
+        T::poke(self, now, addr, data)
+    }
+
+    #[inline]
+    fn submit(
+        &mut self,
+        now: Self::Instant,
+        ops: &mut [BusOp<'_, Address>],
+    ) -> Result<usize, T::Error> {
+        T::submit(self, now, ops)
+    }
+
+    #[inline]
+    fn read_modify_write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        modify: &mut dyn FnMut(&mut [u8]),
+    ) -> Result<usize, T::Error> {
+        T::read_modify_write(self, now, addr, data, modify)
+    }
+
+    #[inline]
+    fn read_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<(usize, Self::Instant), T::Error> {
+        T::read_timed(self, now, addr, data)
+    }
+
+    #[inline]
+    fn write_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<(usize, Self::Instant), T::Error> {
+        T::write_timed(self, now, addr, data)
+    }
+
+    #[inline]
+    fn read_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        transaction: Transaction,
+    ) -> Result<usize, T::Error> {
+        T::read_ext(self, now, addr, data, transaction)
+    }
+
+    #[inline]
+    fn write_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+        transaction: Transaction,
+    ) -> Result<usize, T::Error> {
+        T::write_ext(self, now, addr, data, transaction)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<Address, T> BusAccess<Address> for alloc::boxed::Box<T>
+where
+    Address: Copy,
+    T: BusAccess<Address> + ?Sized,
+{
+    type Instant = T::Instant;
+    type Error = T::Error;
+
+    #[inline]
+    fn read(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, T::Error> {
+        T::read(self, now, addr, data)
+    }
+
+    #[inline]
+    fn write(&mut self, now: Self::Instant, addr: Address, data: &[u8]) -> Result<usize, T::Error> {
+        T::write(self, now, addr, data)
+    }
+
+    #[inline]
+    fn peek(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, T::Error> {
+        T::peek(self, now, addr, data)
+    }
+
+    #[inline]
+    fn poke(&mut self, now: Self::Instant, addr: Address, data: &[u8]) -> Result<usize, T::Error> {
+        T::poke(self, now, addr, data)
+    }
+
+    #[inline]
+    fn submit(
+        &mut self,
+        now: Self::Instant,
+        ops: &mut [BusOp<'_, Address>],
+    ) -> Result<usize, T::Error> {
+        T::submit(self, now, ops)
+    }
+
+    #[inline]
+    fn read_modify_write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        modify: &mut dyn FnMut(&mut [u8]),
+    ) -> Result<usize, T::Error> {
+        T::read_modify_write(self, now, addr, data, modify)
+    }
+
+    #[inline]
+    fn read_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<(usize, Self::Instant), T::Error> {
+        T::read_timed(self, now, addr, data)
+    }
+
+    #[inline]
+    fn write_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<(usize, Self::Instant), T::Error> {
+        T::write_timed(self, now, addr, data)
+    }
+
+    #[inline]
+    fn read_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        transaction: Transaction,
+    ) -> Result<usize, T::Error> {
+        T::read_ext(self, now, addr, data, transaction)
+    }
+
+    #[inline]
+    fn write_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+        transaction: Transaction,
+    ) -> Result<usize, T::Error> {
+        T::write_ext(self, now, addr, data, transaction)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::marker::PhantomData;
+    use std::time::Duration;
+
+    #[test]
+    fn test_implemeting_memory() {
+        #[derive(Clone, Debug)]
+        enum Error {
+            ShortTransfer,
+        }
+
+        impl ErrorType for Error {}
+
+        impl From<ShortTransfer> for Error {
+            fn from(_err: ShortTransfer) -> Self {
+                Error::ShortTransfer
+            }
+        }
+
+        struct Memory(Vec<u8>);
+
+        impl BusAccess<u64> for Memory {
+            type Instant = Duration;
+            type Error = Error;
+
+            fn read(
+                &mut self,
+                _now: Duration,
+                addr: u64,
+                data: &mut [u8],
+            ) -> Result<usize, Self::Error> {
+                let addr = addr as usize;
+                data.copy_from_slice(&self.0[addr..addr + data.len()]);
+                Ok(data.len())
+            }
+
+            fn write(
+                &mut self,
+                _now: Duration,
+                addr: u64,
+                data: &[u8],
+            ) -> Result<usize, Self::Error> {
+                let addr = addr as usize;
+                self.0[addr..addr + data.len()].copy_from_slice(data);
+                Ok(data.len())
+            }
+        }
+
+        let mut bus = Memory(vec![0; 1024]);
+
+        let number = 0x1234_5678;
+        bus.write_beu32(Duration::START, 0, number).unwrap();
+        assert_eq!(
+            u32::from_be_bytes(bus.0[0..4].try_into().unwrap()),
+            0x1234_5678
+        );
 
         assert_eq!(
             bus.read_u32(ByteOrder::Big, Duration::START, 0).unwrap(),
             number
         );
+
+        bus.write_lei16(Duration::START, 8, -2).unwrap();
+        assert_eq!(bus.0[8..10], [0xFE, 0xFF]);
+        assert_eq!(bus.read_i8(Duration::START, 8).unwrap(), -2);
+        assert_eq!(bus.read_bei16(Duration::START, 8).unwrap(), -257);
+
+        bus.write_f64(ByteOrder::Big, Duration::START, 16, -1.5)
+            .unwrap();
+        assert_eq!(bus.read_bef64(Duration::START, 16).unwrap(), -1.5);
+        bus.write_lef32(Duration::START, 24, 0.25).unwrap();
+        assert_eq!(bus.0[24..28], 0.25_f32.to_le_bytes());
+        assert_eq!(
+            bus.read_f32(ByteOrder::Little, Duration::START, 24)
+                .unwrap(),
+            0.25
+        );
+    }
+
+    #[test]
+    fn test_copy_between() {
+        /// Memory which reads as many of the requested bytes as it has, instead of failing
+        struct Rom(Vec<u8>);
+
+        impl BusAccess<u32> for Rom {
+            type Instant = Duration;
+            type Error = BasicBusError;
+
+            fn read(
+                &mut self,
+                _now: Duration,
+                addr: u32,
+                data: &mut [u8],
+            ) -> Result<usize, Self::Error> {
+                let addr = addr as usize;
+                let len = data.len().min(self.0.len().saturating_sub(addr));
+                data[..len].copy_from_slice(&self.0[addr..addr + len]);
+                Ok(len)
+            }
+
+            fn write(
+                &mut self,
+                _now: Duration,
+                _addr: u32,
+                _data: &[u8],
+            ) -> Result<usize, Self::Error> {
+                Err(BasicBusError::ReadOnly)
+            }
+        }
+
+        let mut rom = Rom((0..=255).collect());
+        let mut ram = [0_u8; 0x400];
+        let copied = copy_between(
+            Duration::START,
+            &mut rom,
+            0x10,
+            &mut ram,
+            0x200,
+            0x80 + 0x30,
+        )
+        .unwrap();
+        assert_eq!(copied, 0xB0);
+        assert_eq!(ram[0x200], 0x10);
+        assert_eq!(ram[0x2AF], 0xBF);
+        assert_eq!(ram[0x2B0], 0);
+
+        // the copy stops when the source runs out
+        let copied = copy_between(Duration::START, &mut rom, 0xF0, &mut ram, 0, 0x100).unwrap();
+        assert_eq!(copied, 0x10);
+
+        // a copy of a whole 8-bit address space doesn't overflow the address
+        let mut src = [0; 0x100];
+        src.iter_mut()
+            .enumerate()
+            .for_each(|(i, byte)| *byte = i as u8);
+        let mut dst = [0; 0x100];
+        let copied = copy_between(Duration::START, &mut src, 0_u8, &mut dst, 0_u8, 0x100).unwrap();
+        assert_eq!(copied, 0x100);
+        assert_eq!(dst, src);
+    }
+
+    #[test]
+    fn test_submit_batch() {
+        let mut bus: Box<dyn BusAccess<usize, Instant = Duration, Error = BasicBusError>> =
+            Box::new([0_u8; 16]);
+        let mut result = [0; 4];
+        let mut ops = [BusOp::Write(4, &[1, 2, 3, 4]), BusOp::Read(2, &mut result)];
+        assert_eq!(bus.submit(Duration::START, &mut ops).unwrap(), 8);
+        assert_eq!(ops[1].len(), 4);
+        assert_eq!(result, [0, 0, 1, 2]);
+
+        let mut ops = [BusOp::Write(0, &[5]), BusOp::Write(16, &[6])];
+        assert!(bus.submit(Duration::START, &mut ops).is_err());
+        assert_eq!(bus.read_u8(Duration::START, 0).unwrap(), 5);
+
+        // the helpers are also available on unboxed trait objects
+        let bus: &mut dyn BusAccess<usize, Instant = Duration, Error = BasicBusError> = &mut *bus;
+        assert_eq!(bus.read_beu16(Duration::START, 4).unwrap(), 0x0102);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_downcast_other_error() {
+        #[derive(Debug, PartialEq)]
+        struct ParityError(u32);
+
+        impl ErrorType for ParityError {}
+
+        let err = BasicBusError::Other(Box::new(ParityError(0x40)));
+        assert_eq!(
+            err.downcast_other_ref::<ParityError>(),
+            Some(&ParityError(0x40))
+        );
+        assert!(err.downcast_other_ref::<BasicBusError>().is_none());
+        assert!(BasicBusError::ReadOnly
+            .downcast_other_ref::<ParityError>()
+            .is_none());
+
+        let mut boxed: Box<dyn ErrorType> = Box::new(ParityError(1));
+        assert!(boxed.is::<ParityError>());
+        boxed.downcast_mut::<ParityError>().unwrap().0 = 2;
+        let boxed = boxed.downcast::<BasicBusError>().unwrap_err();
+        assert_eq!(*boxed.downcast::<ParityError>().unwrap(), ParityError(2));
+    }
+
+    #[test]
+    fn test_device_specific_errors() {
+        struct Register;
+
+        impl BusAccess<u8> for Register {
+            type Instant = Duration;
+            type Error = BasicBusError;
+
+            fn read(
+                &mut self,
+                _now: Duration,
+                _addr: u8,
+                _data: &mut [u8],
+            ) -> Result<usize, Self::Error> {
+                Err(BasicBusError::OtherStatic("register is write only"))
+            }
+
+            fn write(
+                &mut self,
+                _now: Duration,
+                addr: u8,
+                _data: &[u8],
+            ) -> Result<usize, Self::Error> {
+                Err(BasicBusError::OtherCode(addr as u32))
+            }
+        }
+
+        let mut bus = Register;
+        assert!(matches!(
+            bus.read_u8(Duration::START, 0),
+            Err(BasicBusError::OtherStatic("register is write only"))
+        ));
+        assert!(matches!(
+            bus.write_u8(Duration::START, 3, 0),
+            Err(BasicBusError::OtherCode(3))
+        ));
+    }
+
+    #[test]
+    fn test_short_transfers() {
+        /// A 16-bit wide port, which transfers at most 2 bytes per access
+        struct Port<Error>(PhantomData<Error>);
+
+        impl<Error: ErrorType> BusAccess<u8> for Port<Error> {
+            type Instant = Duration;
+            type Error = Error;
+
+            fn read(
+                &mut self,
+                _now: Duration,
+                _addr: u8,
+                data: &mut [u8],
+            ) -> Result<usize, Self::Error> {
+                let count = data.len().min(2);
+                data[..count].fill(0xAA);
+                Ok(count)
+            }
+
+            fn write(
+                &mut self,
+                _now: Duration,
+                _addr: u8,
+                data: &[u8],
+            ) -> Result<usize, Self::Error> {
+                Ok(data.len().min(2))
+            }
+        }
+
+        let mut port = Port::<BasicBusError>(PhantomData);
+        assert_eq!(port.read_beu16(Duration::START, 0).unwrap(), 0xAAAA);
+        assert!(matches!(
+            port.read_beu32(Duration::START, 0),
+            Err(BasicBusError::ShortTransfer(2))
+        ));
+        assert!(matches!(
+            port.write_all(Duration::START, 0, &[1, 2, 3]),
+            Err(BasicBusError::ShortTransfer(2))
+        ));
+
+        // a bus whose error type can't represent a short transfer still works, without the
+        // helpers that need it
+        let mut port = Port::<Infallible>(PhantomData);
+        let mut data = [0; 4];
+        assert_eq!(port.read(Duration::START, 0, &mut data).unwrap(), 2);
+        assert_eq!(data, [0xAA, 0xAA, 0, 0]);
+        assert_eq!(port.write(Duration::START, 0, &data).unwrap(), 2);
+
+        // any error type that can be converted from a short transfer can use the helpers
+        #[derive(Debug, PartialEq)]
+        struct PortError(usize);
+
+        impl ErrorType for PortError {}
+
+        impl From<ShortTransfer> for PortError {
+            fn from(err: ShortTransfer) -> Self {
+                PortError(err.0)
+            }
+        }
+
+        let mut port = Port::<PortError>(PhantomData);
+        assert_eq!(port.read_beu32(Duration::START, 0), Err(PortError(2)));
+        assert_eq!(port.write_beu32(Duration::START, 0, 0), Err(PortError(2)));
     }
 }
@@ -0,0 +1,406 @@
+//! A bus wrapper that records which addresses have been read, written, or executed
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::bus::BusAccess;
+use crate::transaction::Transaction;
+use crate::translate::AccessKind;
+
+/// A bitmap with one bit for each address in the covered range
+struct Bitmap {
+    words: Vec<u64>,
+}
+
+impl Bitmap {
+    fn new(len: u64) -> Self {
+        Self {
+            words: vec![0; ((len + 63) / 64) as usize],
+        }
+    }
+
+    fn get(&self, offset: u64) -> bool {
+        self.words[(offset / 64) as usize] & (1 << (offset % 64)) != 0
+    }
+
+    fn set(&mut self, range: Range<u64>) {
+        for offset in range {
+            self.words[(offset / 64) as usize] |= 1 << (offset % 64);
+        }
+    }
+}
+
+/// A bus wrapper that records which addresses in a range have been read, written, or executed
+///
+/// This can be used to check how much of a ROM is exercised by a test suite, or to build a map
+/// of the code that was never run.  Only the kinds of access enabled with `track()` are
+/// recorded, each in a bitmap with one bit per address in the range, and only the bytes that
+/// were actually transferred by an access that succeeded are marked.  Reads made with
+/// `read_ext()` for an instruction fetch are recorded as executed rather than read.  For CPUs
+/// that don't describe their transactions, executed addresses can instead be marked by the code
+/// that steps the CPU, using `mark_executed()`.  Accesses made with `peek()` and `poke()` are
+/// never recorded, so a debugger can inspect memory without affecting the coverage
+pub struct CoverageBus<Address, Bus> {
+    /// The underlying object implementing `BusAccess` that this object records accesses to
+    pub inner: Bus,
+    range: Range<Address>,
+    bitmaps: [Option<Bitmap>; 3],
+}
+
+impl<Address, Bus> CoverageBus<Address, Bus>
+where
+    Address: Copy + Into<u64>,
+    Bus: BusAccess<Address>,
+{
+    /// Construct a new wrapper for the given `bus` object, which records accesses to the given
+    /// range of addresses once tracking is enabled with `track()`
+    pub fn new(inner: Bus, range: Range<Address>) -> Self {
+        Self {
+            inner,
+            range,
+            bitmaps: [None, None, None],
+        }
+    }
+
+    /// Start recording the given kind of access, if it isn't recorded already
+    pub fn track(&mut self, kind: AccessKind) {
+        let len = self.len();
+        self.bitmaps[Self::index(kind)].get_or_insert_with(|| Bitmap::new(len));
+    }
+
+    /// Mark the instruction of `len` bytes at `addr` as executed
+    pub fn mark_executed(&mut self, addr: Address, len: usize) {
+        self.mark(AccessKind::Execute, addr, len);
+    }
+
+    /// Returns true if the given address has been accessed in the given way
+    ///
+    /// This is always false for addresses outside of the range, or kinds that aren't tracked
+    pub fn is_covered(&self, kind: AccessKind, addr: Address) -> bool {
+        let (start, end) = self.bounds();
+        let addr = addr.into();
+        match &self.bitmaps[Self::index(kind)] {
+            Some(bitmap) if start <= addr && addr < end => bitmap.get(addr - start),
+            _ => false,
+        }
+    }
+
+    /// Returns the number of addresses that have been accessed in the given way
+    pub fn covered_count(&self, kind: AccessKind) -> u64 {
+        self.bitmaps[Self::index(kind)]
+            .as_ref()
+            .map(|bitmap| {
+                bitmap
+                    .words
+                    .iter()
+                    .map(|word| word.count_ones() as u64)
+                    .sum()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Returns the ranges of addresses that have been accessed in the given way, in order and
+    /// with adjacent addresses combined
+    pub fn covered_ranges(&self, kind: AccessKind) -> Vec<Range<u64>> {
+        self.ranges(kind, true)
+    }
+
+    /// Returns the ranges of addresses that haven't been accessed in the given way, in order
+    /// and with adjacent addresses combined
+    ///
+    /// This covers the whole range if the kind of access isn't tracked
+    pub fn uncovered_ranges(&self, kind: AccessKind) -> Vec<Range<u64>> {
+        self.ranges(kind, false)
+    }
+
+    /// Returns the bitmap for the given kind of access, if it's tracked
+    ///
+    /// Bit `n % 64` of word `n / 64` is set if the address `n` bytes from the start of the range
+    /// has been accessed
+    pub fn bitmap(&self, kind: AccessKind) -> Option<&[u64]> {
+        self.bitmaps[Self::index(kind)]
+            .as_ref()
+            .map(|bitmap| bitmap.words.as_slice())
+    }
+
+    /// Clear the recorded accesses of every kind, without changing which kinds are tracked
+    pub fn clear_coverage(&mut self) {
+        for bitmap in self.bitmaps.iter_mut().flatten() {
+            bitmap.words.fill(0);
+        }
+    }
+
+    fn index(kind: AccessKind) -> usize {
+        match kind {
+            AccessKind::Read => 0,
+            AccessKind::Write => 1,
+            AccessKind::Execute => 2,
+        }
+    }
+
+    fn bounds(&self) -> (u64, u64) {
+        let start = self.range.start.into();
+        (start, self.range.end.into().max(start))
+    }
+
+    fn len(&self) -> u64 {
+        let (start, end) = self.bounds();
+        end - start
+    }
+
+    fn mark(&mut self, kind: AccessKind, addr: Address, len: usize) {
+        let (start, end) = self.bounds();
+        if let Some(bitmap) = &mut self.bitmaps[Self::index(kind)] {
+            let addr = addr.into();
+            let first = addr.max(start);
+            let last = addr.saturating_add(len as u64).min(end);
+            if first < last {
+                bitmap.set(first - start..last - start);
+            }
+        }
+    }
+
+    fn ranges(&self, kind: AccessKind, covered: bool) -> Vec<Range<u64>> {
+        let (start, end) = self.bounds();
+        let mut ranges: Vec<Range<u64>> = Vec::new();
+        for addr in start..end {
+            if self.is_covered_offset(kind, addr - start) != covered {
+                continue;
+            }
+            match ranges.last_mut() {
+                Some(range) if range.end == addr => range.end = addr + 1,
+                _ => ranges.push(addr..addr + 1),
+            }
+        }
+        ranges
+    }
+
+    fn is_covered_offset(&self, kind: AccessKind, offset: u64) -> bool {
+        self.bitmaps[Self::index(kind)]
+            .as_ref()
+            .map(|bitmap| bitmap.get(offset))
+            .unwrap_or(false)
+    }
+}
+
+impl<Address, Bus> BusAccess<Address> for CoverageBus<Address, Bus>
+where
+    Address: Copy + Into<u64>,
+    Bus: BusAccess<Address>,
+{
+    type Instant = Bus::Instant;
+    type Error = Bus::Error;
+
+    fn read(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        let count = self.inner.read(now, addr, data)?;
+        self.mark(AccessKind::Read, addr, count);
+        Ok(count)
+    }
+
+    fn write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        let count = self.inner.write(now, addr, data)?;
+        self.mark(AccessKind::Write, addr, count);
+        Ok(count)
+    }
+
+    fn read_modify_write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        modify: &mut dyn FnMut(&mut [u8]),
+    ) -> Result<usize, Self::Error> {
+        let mut read = 0;
+        let count = self.inner.read_modify_write(now, addr, data, &mut |data| {
+            read = data.len();
+            modify(data);
+        })?;
+        self.mark(AccessKind::Read, addr, read);
+        self.mark(AccessKind::Write, addr, count);
+        Ok(count)
+    }
+
+    #[inline]
+    fn peek(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        self.inner.peek(now, addr, data)
+    }
+
+    #[inline]
+    fn poke(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        self.inner.poke(now, addr, data)
+    }
+
+    fn read_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        let (count, done) = self.inner.read_timed(now, addr, data)?;
+        self.mark(AccessKind::Read, addr, count);
+        Ok((count, done))
+    }
+
+    fn write_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        let (count, done) = self.inner.write_timed(now, addr, data)?;
+        self.mark(AccessKind::Write, addr, count);
+        Ok((count, done))
+    }
+
+    fn read_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        let count = self.inner.read_ext(now, addr, data, transaction)?;
+        let kind = if transaction.fetch {
+            AccessKind::Execute
+        } else {
+            AccessKind::Read
+        };
+        self.mark(kind, addr, count);
+        Ok(count)
+    }
+
+    fn write_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        let count = self.inner.write_ext(now, addr, data, transaction)?;
+        self.mark(AccessKind::Write, addr, count);
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{BasicBusError, BusAccessExt, Instant};
+    use std::time::Duration;
+
+    /// A memory that counts the read-modify-write cycles made to it
+    struct AtomicMemory {
+        memory: Vec<u8>,
+        cycles: usize,
+    }
+
+    impl BusAccess<u32> for AtomicMemory {
+        type Instant = Duration;
+        type Error = BasicBusError;
+
+        fn read(
+            &mut self,
+            now: Duration,
+            addr: u32,
+            data: &mut [u8],
+        ) -> Result<usize, Self::Error> {
+            self.memory.read(now, addr, data)
+        }
+
+        fn write(&mut self, now: Duration, addr: u32, data: &[u8]) -> Result<usize, Self::Error> {
+            self.memory.write(now, addr, data)
+        }
+
+        fn read_modify_write(
+            &mut self,
+            now: Duration,
+            addr: u32,
+            data: &mut [u8],
+            modify: &mut dyn FnMut(&mut [u8]),
+        ) -> Result<usize, Self::Error> {
+            self.cycles += 1;
+            self.memory.read_modify_write(now, addr, data, modify)
+        }
+    }
+
+    #[test]
+    fn test_coverage() {
+        let mut bus = CoverageBus::new(vec![0; 0x100], 0x40_u32..0x80);
+        bus.track(AccessKind::Read);
+        bus.track(AccessKind::Execute);
+
+        bus.read_beu32(Duration::START, 0x3E).unwrap();
+        bus.read_u8(Duration::START, 0x50).unwrap();
+        bus.write_beu32(Duration::START, 0x60, 0).unwrap();
+        bus.mark_executed(0x7E, 4);
+        bus.read_ext(
+            Duration::START,
+            0x44,
+            &mut [0; 2],
+            Transaction::new().as_fetch(),
+        )
+        .unwrap();
+        let mut data = [0; 4];
+        bus.peek(Duration::START, 0x70, &mut data).unwrap();
+
+        assert_eq!(
+            bus.covered_ranges(AccessKind::Read),
+            [0x40..0x42, 0x50..0x51]
+        );
+        assert_eq!(bus.covered_count(AccessKind::Read), 3);
+        assert_eq!(
+            bus.covered_ranges(AccessKind::Execute),
+            [0x44..0x46, 0x7E..0x80]
+        );
+        assert_eq!(bus.uncovered_ranges(AccessKind::Write), vec![0x40..0x80]);
+        assert!(bus.bitmap(AccessKind::Write).is_none());
+        assert_eq!(bus.bitmap(AccessKind::Read).unwrap(), [0x0001_0003]);
+        assert!(bus.is_covered(AccessKind::Read, 0x41));
+        assert!(!bus.is_covered(AccessKind::Read, 0x3F));
+
+        bus.clear_coverage();
+        assert!(bus.covered_ranges(AccessKind::Read).is_empty());
+        assert_eq!(bus.uncovered_ranges(AccessKind::Execute), vec![0x40..0x80]);
+    }
+
+    #[test]
+    fn test_read_modify_write_is_forwarded() {
+        let memory = AtomicMemory {
+            memory: vec![0; 0x100],
+            cycles: 0,
+        };
+        let mut bus = CoverageBus::new(memory, 0x40_u32..0x80);
+        bus.track(AccessKind::Read);
+        bus.track(AccessKind::Write);
+
+        let mut data = [0; 2];
+        bus.read_modify_write(Duration::START, 0x50, &mut data, &mut |data| data[0] = 0x80)
+            .unwrap();
+
+        assert_eq!(bus.inner.cycles, 1);
+        assert_eq!(bus.inner.memory[0x50], 0x80);
+        assert_eq!(bus.covered_ranges(AccessKind::Read), vec![0x50..0x52]);
+        assert_eq!(bus.covered_ranges(AccessKind::Write), vec![0x50..0x52]);
+    }
+}
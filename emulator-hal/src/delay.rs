@@ -0,0 +1,154 @@
+//! Adapters for running `embedded-hal` drivers against the simulated timebase
+//!
+//! Only the `embedded-hal` 1.0 timing trait, `DelayNs`, is implemented.  The `DelayMs` and
+//! `DelayUs` traits from `embedded-hal` 0.2 and the async `DelayNs` from `embedded-hal-async`
+//! are not provided
+
+use core::cell::Cell;
+
+use embedded_hal::delay::DelayNs;
+
+use crate::time::Instant as EmuInstant;
+
+/// A simulated clock that can be shared between the emulator and hosted drivers
+///
+/// The emulator (eg. a device's `step()` function) sets the current time before calling into
+/// a driver, and any delays performed by the driver will advance that same clock, so that the
+/// driver observes the passage of simulated time rather than host time
+pub struct SimulatedClock<Instant> {
+    now: Cell<Instant>,
+}
+
+impl<Instant> SimulatedClock<Instant>
+where
+    Instant: EmuInstant,
+{
+    /// Construct a new clock starting at the given instant
+    pub fn new(start: Instant) -> Self {
+        Self {
+            now: Cell::new(start),
+        }
+    }
+
+    /// Returns the current instant of this clock
+    pub fn now(&self) -> Instant {
+        self.now.get()
+    }
+
+    /// Set the current instant of this clock
+    pub fn set(&self, now: Instant) {
+        self.now.set(now);
+    }
+
+    /// Advance the current instant of this clock by the given duration
+    pub fn advance(&self, duration: Instant::Duration) {
+        self.now.set(self.now.get() + duration);
+    }
+
+    /// Returns an object implementing `embedded_hal::delay::DelayNs` which advances this clock
+    pub fn delay(&self) -> SimulatedDelay<'_, Instant> {
+        SimulatedDelay { clock: self }
+    }
+}
+
+/// An implementation of `embedded_hal::delay::DelayNs` that advances a `SimulatedClock`
+///
+/// Delays return immediately, having advanced the clock by the requested amount.  The delay is
+/// split into whole milliseconds, microseconds, and nanoseconds, each converted with
+/// `Instant::hertz_to_duration`, so an `Instant` with a coarse resolution will only lose the
+/// parts of the delay that are smaller than its resolution
+pub struct SimulatedDelay<'a, Instant> {
+    clock: &'a SimulatedClock<Instant>,
+}
+
+impl<'a, Instant> DelayNs for SimulatedDelay<'a, Instant>
+where
+    Instant: EmuInstant,
+{
+    fn delay_ns(&mut self, ns: u32) {
+        self.clock
+            .advance(Instant::hertz_to_duration(1_000) * (ns / 1_000_000));
+        self.clock
+            .advance(Instant::hertz_to_duration(1_000_000) * (ns / 1_000 % 1_000));
+        self.clock
+            .advance(Instant::hertz_to_duration(1_000_000_000) * (ns % 1_000));
+    }
+
+    fn delay_us(&mut self, us: u32) {
+        self.clock
+            .advance(Instant::hertz_to_duration(1_000) * (us / 1_000));
+        self.clock
+            .advance(Instant::hertz_to_duration(1_000_000) * (us % 1_000));
+    }
+
+    fn delay_ms(&mut self, ms: u32) {
+        self.clock.advance(Instant::hertz_to_duration(1_000) * ms);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_delay_advances_clock() {
+        let clock = SimulatedClock::new(Duration::START);
+
+        let mut delay = clock.delay();
+        delay.delay_ms(2);
+        delay.delay_us(1_500);
+        delay.delay_ns(2_000_250);
+
+        assert_eq!(clock.now(), Duration::from_nanos(5_500_250));
+    }
+
+    #[test]
+    fn test_delay_ns_advances_clock() {
+        let clock = SimulatedClock::new(Duration::START);
+
+        clock.delay().delay_ns(1_250);
+        assert_eq!(clock.now(), Duration::from_nanos(1_250));
+
+        clock.delay().delay_ns(u32::MAX);
+        assert_eq!(clock.now(), Duration::from_nanos(1_250 + u32::MAX as u64));
+    }
+
+    #[test]
+    fn test_delay_us_advances_clock() {
+        let clock = SimulatedClock::new(Duration::START);
+
+        clock.delay().delay_us(2_345);
+        assert_eq!(clock.now(), Duration::from_micros(2_345));
+
+        clock.delay().delay_us(u32::MAX);
+        assert_eq!(clock.now(), Duration::from_micros(2_345 + u32::MAX as u64));
+    }
+
+    #[test]
+    fn test_delay_ms_advances_clock() {
+        let clock = SimulatedClock::new(Duration::START);
+
+        clock.delay().delay_ms(7);
+        assert_eq!(clock.now(), Duration::from_millis(7));
+
+        clock.delay().delay_ms(u32::MAX);
+        assert_eq!(clock.now(), Duration::from_millis(7 + u32::MAX as u64));
+    }
+
+    #[test]
+    fn test_delay_with_coarse_instant() {
+        use crate::time::CycleInstant;
+
+        // A 1 MHz clock can't represent nanoseconds, so only those parts of the delay are lost
+        let clock = SimulatedClock::new(CycleInstant::<1_000_000>::START);
+
+        let mut delay = clock.delay();
+        delay.delay_ns(3_004_999);
+        assert_eq!(clock.now(), CycleInstant(3_004));
+        delay.delay_us(1_001);
+        assert_eq!(clock.now(), CycleInstant(4_005));
+        delay.delay_ms(2);
+        assert_eq!(clock.now(), CycleInstant(6_005));
+    }
+}
@@ -0,0 +1,130 @@
+//! Identifiers for the devices in an emulated system
+
+use core::fmt;
+
+#[cfg(feature = "alloc")]
+use alloc::string::String;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+/// Identifies a single device within an emulated system
+///
+/// This is used to refer to the same device consistently in logs, traces, save states, and
+/// debugger views.  The ids are normally assigned by a `DeviceRegistry`, which also associates
+/// each id with a human-readable name
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DeviceId(pub u32);
+
+impl fmt::Display for DeviceId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+impl From<u32> for DeviceId {
+    fn from(id: u32) -> Self {
+        DeviceId(id)
+    }
+}
+
+impl From<DeviceId> for u32 {
+    fn from(id: DeviceId) -> Self {
+        id.0
+    }
+}
+
+/// An error returned when a device cannot be registered
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DeviceRegistryError {
+    /// A device with the same name has already been registered with the given id
+    DuplicateName(DeviceId),
+}
+
+/// Assigns a `DeviceId` to each named device in a system
+///
+/// Ids are assigned sequentially starting at 0, in the order the devices are registered, so
+/// a system that registers its devices in the same order will always produce the same ids
+#[cfg(feature = "alloc")]
+#[derive(Clone, Debug, Default)]
+pub struct DeviceRegistry {
+    names: Vec<String>,
+}
+
+#[cfg(feature = "alloc")]
+impl DeviceRegistry {
+    /// Construct a new empty registry
+    pub fn new() -> Self {
+        Self { names: Vec::new() }
+    }
+
+    /// Register a new device with the given name, and return its id
+    ///
+    /// Names must be unique, so that they can be used to find the device again
+    pub fn register(&mut self, name: &str) -> Result<DeviceId, DeviceRegistryError> {
+        if let Some(id) = self.id(name) {
+            return Err(DeviceRegistryError::DuplicateName(id));
+        }
+        let id = DeviceId(self.names.len() as u32);
+        self.names.push(String::from(name));
+        Ok(id)
+    }
+
+    /// Returns the name of the device with the given id, if it has been registered
+    pub fn name(&self, id: DeviceId) -> Option<&str> {
+        self.names.get(id.0 as usize).map(|name| name.as_str())
+    }
+
+    /// Returns the id of the device with the given name, if it has been registered
+    pub fn id(&self, name: &str) -> Option<DeviceId> {
+        self.names
+            .iter()
+            .position(|existing| existing == name)
+            .map(|index| DeviceId(index as u32))
+    }
+
+    /// Returns the number of devices registered
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Returns true if no devices have been registered
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Returns an iterator over the id and name of every registered device, in order
+    pub fn iter(&self) -> impl Iterator<Item = (DeviceId, &str)> {
+        self.names
+            .iter()
+            .enumerate()
+            .map(|(index, name)| (DeviceId(index as u32), name.as_str()))
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_register_devices() {
+        let mut registry = DeviceRegistry::new();
+        let cpu = registry.register("cpu").unwrap();
+        let ram = registry.register("ram").unwrap();
+
+        assert_eq!(cpu, DeviceId(0));
+        assert_eq!(ram, DeviceId(1));
+        assert_eq!(registry.name(ram), Some("ram"));
+        assert_eq!(registry.id("cpu"), Some(cpu));
+        assert_eq!(registry.name(DeviceId(2)), None);
+        assert_eq!(
+            registry.register("ram"),
+            Err(DeviceRegistryError::DuplicateName(ram))
+        );
+        assert_eq!(
+            registry.iter().collect::<Vec<_>>(),
+            vec![(cpu, "cpu"), (ram, "ram")]
+        );
+    }
+}
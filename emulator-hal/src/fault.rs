@@ -0,0 +1,438 @@
+//! A bus wrapper that injects faults into selected accesses, for testing error handling
+
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::bus::{BasicBusError, BusAccess};
+use crate::report::BusOperation;
+use crate::transaction::Transaction;
+
+/// The message of the error returned by `Fault::Error`
+pub const FAULT_INJECTED: &str = "injected fault";
+
+/// A fault that a `FaultInjectionBus` can inject into an access
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Fault {
+    /// Return `BasicBusError::OtherStatic(FAULT_INJECTED)` without accessing the wrapped bus
+    Error,
+    /// Transfer at most the given number of bytes, and return the shorter count
+    ShortTransfer(usize),
+    /// Flip the bits that are set in the given mask in every byte read or written
+    Corrupt(u8),
+}
+
+/// Which accesses a scripted fault is injected into
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FaultTrigger<Address> {
+    /// Only the access with the given index, counting reads and writes from 0
+    Nth(usize),
+    /// Every read that overlaps the given range of addresses
+    Read(Range<Address>),
+    /// Every write that overlaps the given range of addresses
+    Write(Range<Address>),
+    /// Every read or write that overlaps the given range of addresses
+    Access(Range<Address>),
+}
+
+struct RandomFaults {
+    state: u64,
+    one_in: u64,
+    fault: Fault,
+}
+
+impl RandomFaults {
+    /// Returns the next value of a SplitMix64 sequence
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut value = self.state;
+        value = (value ^ (value >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        value = (value ^ (value >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        value ^ (value >> 31)
+    }
+}
+
+/// A bus wrapper that returns errors, short transfers, or corrupted data on selected accesses
+///
+/// This is a test harness for CPU and driver emulations, to check how they handle bus errors
+/// without needing a device that actually fails.  Faults can be scripted to occur on a given
+/// access or range of addresses, or injected at random with a given probability, using a
+/// seeded generator so that a failing test can be reproduced exactly.  Scripted faults are
+/// checked first, in the order they were added.  Accesses made with `peek()` and `poke()` are
+/// never faulted or counted, and `read_modify_write()` is counted as a read and then a write
+pub struct FaultInjectionBus<Address, Bus> {
+    /// The underlying object implementing `BusAccess` that this object injects faults into
+    pub inner: Bus,
+    scripted: Vec<(FaultTrigger<Address>, Fault)>,
+    random: Option<RandomFaults>,
+    accesses: usize,
+    injected: usize,
+}
+
+impl<Address, Bus> FaultInjectionBus<Address, Bus>
+where
+    Address: Copy + Into<u64>,
+    Bus: BusAccess<Address>,
+    Bus::Error: From<BasicBusError>,
+{
+    /// Construct a new wrapper for the given `bus` object, which doesn't inject any faults
+    pub fn new(inner: Bus) -> Self {
+        Self {
+            inner,
+            scripted: Vec::new(),
+            random: None,
+            accesses: 0,
+            injected: 0,
+        }
+    }
+
+    /// Inject `fault` into the accesses selected by `trigger`
+    pub fn inject(&mut self, trigger: FaultTrigger<Address>, fault: Fault) {
+        self.scripted.push((trigger, fault));
+    }
+
+    /// Inject `fault` into one in every `one_in` accesses on average, chosen by a pseudo-random
+    /// sequence that starts from `seed`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `one_in` is zero
+    pub fn inject_random(&mut self, seed: u64, one_in: u64, fault: Fault) {
+        assert!(one_in != 0, "fault probability must not be zero");
+        self.random = Some(RandomFaults {
+            state: seed,
+            one_in,
+            fault,
+        });
+    }
+
+    /// Remove all scripted and random faults
+    pub fn clear_faults(&mut self) {
+        self.scripted.clear();
+        self.random = None;
+    }
+
+    /// Returns the number of reads and writes that have been made through the wrapper
+    pub fn accesses(&self) -> usize {
+        self.accesses
+    }
+
+    /// Returns the number of accesses that a fault has been injected into
+    pub fn injected(&self) -> usize {
+        self.injected
+    }
+
+    fn next_fault(&mut self, operation: BusOperation, addr: Address, len: usize) -> Option<Fault> {
+        let index = self.accesses;
+        self.accesses += 1;
+
+        let start = addr.into();
+        let end = start.saturating_add(len as u64);
+        let overlaps =
+            |range: &Range<Address>| start < range.end.into() && range.start.into() < end;
+        let scripted = self.scripted.iter().find(|(trigger, _)| match trigger {
+            FaultTrigger::Nth(nth) => *nth == index,
+            FaultTrigger::Read(range) => operation == BusOperation::Read && overlaps(range),
+            FaultTrigger::Write(range) => operation == BusOperation::Write && overlaps(range),
+            FaultTrigger::Access(range) => overlaps(range),
+        });
+
+        // the random sequence advances on every access, so that scripted faults don't change
+        // which accesses are randomly faulted
+        let random = self.random.as_mut().and_then(|random| {
+            if random.next_u64() % random.one_in == 0 {
+                Some(random.fault)
+            } else {
+                None
+            }
+        });
+
+        let fault = scripted.map(|(_, fault)| *fault).or(random);
+        if fault.is_some() {
+            self.injected += 1;
+        }
+        fault
+    }
+
+    fn faulted_read<T, F>(
+        &mut self,
+        addr: Address,
+        data: &mut [u8],
+        read: F,
+    ) -> Result<T, Bus::Error>
+    where
+        F: FnOnce(&mut Bus, &mut [u8]) -> Result<T, Bus::Error>,
+    {
+        match self.next_fault(BusOperation::Read, addr, data.len()) {
+            None => read(&mut self.inner, data),
+            Some(Fault::Error) => Err(BasicBusError::OtherStatic(FAULT_INJECTED).into()),
+            Some(Fault::ShortTransfer(len)) => {
+                let len = len.min(data.len());
+                read(&mut self.inner, &mut data[..len])
+            }
+            Some(Fault::Corrupt(mask)) => {
+                let result = read(&mut self.inner, data)?;
+                data.iter_mut().for_each(|byte| *byte ^= mask);
+                Ok(result)
+            }
+        }
+    }
+
+    fn faulted_write<T, F>(&mut self, addr: Address, data: &[u8], write: F) -> Result<T, Bus::Error>
+    where
+        F: FnOnce(&mut Bus, &[u8]) -> Result<T, Bus::Error>,
+    {
+        match self.next_fault(BusOperation::Write, addr, data.len()) {
+            None => write(&mut self.inner, data),
+            Some(Fault::Error) => Err(BasicBusError::OtherStatic(FAULT_INJECTED).into()),
+            Some(Fault::ShortTransfer(len)) => {
+                let len = len.min(data.len());
+                write(&mut self.inner, &data[..len])
+            }
+            Some(Fault::Corrupt(mask)) => {
+                let corrupted: Vec<u8> = data.iter().map(|byte| byte ^ mask).collect();
+                write(&mut self.inner, &corrupted)
+            }
+        }
+    }
+}
+
+impl<Address, Bus> BusAccess<Address> for FaultInjectionBus<Address, Bus>
+where
+    Address: Copy + Into<u64>,
+    Bus: BusAccess<Address>,
+    Bus::Error: From<BasicBusError>,
+{
+    type Instant = Bus::Instant;
+    type Error = Bus::Error;
+
+    fn read(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        self.faulted_read(addr, data, |bus, data| bus.read(now, addr, data))
+    }
+
+    fn write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        self.faulted_write(addr, data, |bus, data| bus.write(now, addr, data))
+    }
+
+    fn read_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        self.faulted_read(addr, data, |bus, data| {
+            bus.read_ext(now, addr, data, transaction)
+        })
+    }
+
+    fn write_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        self.faulted_write(addr, data, |bus, data| {
+            bus.write_ext(now, addr, data, transaction)
+        })
+    }
+
+    fn read_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        self.faulted_read(addr, data, |bus, data| bus.read_timed(now, addr, data))
+    }
+
+    fn write_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        self.faulted_write(addr, data, |bus, data| bus.write_timed(now, addr, data))
+    }
+
+    fn read_modify_write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        modify: &mut dyn FnMut(&mut [u8]),
+    ) -> Result<usize, Self::Error> {
+        // the read and the write are counted as separate accesses, but the faults are chosen
+        // before the cycle starts, so that the inner bus still sees a single indivisible access
+        let read_fault = self.next_fault(BusOperation::Read, addr, data.len());
+        let write_fault = self.next_fault(BusOperation::Write, addr, data.len());
+
+        let mut len = data.len();
+        let mut masks = [0; 2];
+        for (fault, mask) in [read_fault, write_fault].iter().zip(masks.iter_mut()) {
+            match fault {
+                None => {}
+                Some(Fault::Error) => {
+                    return Err(BasicBusError::OtherStatic(FAULT_INJECTED).into());
+                }
+                Some(Fault::ShortTransfer(max)) => len = len.min(*max),
+                Some(Fault::Corrupt(corrupt)) => *mask = *corrupt,
+            }
+        }
+        let [read_mask, write_mask] = masks;
+
+        let data = &mut data[..len];
+        let result = self.inner.read_modify_write(now, addr, data, &mut |data| {
+            data.iter_mut().for_each(|byte| *byte ^= read_mask);
+            modify(data);
+            data.iter_mut().for_each(|byte| *byte ^= write_mask);
+        })?;
+        // the caller gets back the bytes it meant to write, rather than the corrupted ones
+        data.iter_mut().for_each(|byte| *byte ^= write_mask);
+        Ok(result)
+    }
+
+    #[inline]
+    fn peek(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        self.inner.peek(now, addr, data)
+    }
+
+    #[inline]
+    fn poke(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        self.inner.poke(now, addr, data)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::BusAccessExt;
+    use std::time::Duration;
+
+    #[test]
+    fn test_scripted_faults() {
+        let mut bus = FaultInjectionBus::<u32, _>::new([0_u8; 0x100]);
+        let now = Duration::ZERO;
+        bus.inject(FaultTrigger::Nth(1), Fault::Error);
+        bus.inject(FaultTrigger::Write(0x40..0x44), Fault::ShortTransfer(1));
+        bus.inject(FaultTrigger::Read(0x80..0x81), Fault::Corrupt(0x01));
+
+        bus.write_beu16(now, 0x10, 0x1234).unwrap();
+        assert!(matches!(
+            bus.read_beu16(now, 0x10),
+            Err(BasicBusError::OtherStatic(FAULT_INJECTED))
+        ));
+        assert_eq!(bus.read_beu16(now, 0x10).unwrap(), 0x1234);
+
+        assert_eq!(bus.write(now, 0x42, &[0xAA, 0xBB]).unwrap(), 1);
+        assert_eq!(bus.inner[0x42..0x44], [0xAA, 0x00]);
+
+        bus.write_u8(now, 0x80, 0x10).unwrap();
+        assert_eq!(bus.read_u8(now, 0x80).unwrap(), 0x11);
+        let mut data = [0];
+        bus.peek(now, 0x80, &mut data).unwrap();
+        assert_eq!(data, [0x10]);
+
+        assert_eq!(bus.accesses(), 6);
+        assert_eq!(bus.injected(), 3);
+    }
+
+    /// A memory that counts the read-modify-write cycles made to it
+    struct AtomicMemory {
+        memory: [u8; 0x10],
+        cycles: usize,
+    }
+
+    impl BusAccess<u32> for AtomicMemory {
+        type Instant = Duration;
+        type Error = BasicBusError;
+
+        fn read(
+            &mut self,
+            now: Duration,
+            addr: u32,
+            data: &mut [u8],
+        ) -> Result<usize, Self::Error> {
+            self.memory.read(now, addr, data)
+        }
+
+        fn write(&mut self, now: Duration, addr: u32, data: &[u8]) -> Result<usize, Self::Error> {
+            self.memory.write(now, addr, data)
+        }
+
+        fn read_modify_write(
+            &mut self,
+            now: Duration,
+            addr: u32,
+            data: &mut [u8],
+            modify: &mut dyn FnMut(&mut [u8]),
+        ) -> Result<usize, Self::Error> {
+            self.cycles += 1;
+            self.memory.read_modify_write(now, addr, data, modify)
+        }
+    }
+
+    #[test]
+    fn test_read_modify_write_is_forwarded() {
+        let mut bus = FaultInjectionBus::new(AtomicMemory {
+            memory: [0; 0x10],
+            cycles: 0,
+        });
+        let now = Duration::ZERO;
+        bus.inject(FaultTrigger::Read(0x4..0x5), Fault::Corrupt(0x01));
+        bus.inject(FaultTrigger::Write(0x8..0x9), Fault::Error);
+
+        let mut data = [0];
+        bus.read_modify_write(now, 0x0, &mut data, &mut |data| data[0] |= 0x80)
+            .unwrap();
+        bus.read_modify_write(now, 0x4, &mut data, &mut |data| data[0] |= 0x80)
+            .unwrap();
+        assert!(bus
+            .read_modify_write(now, 0x8, &mut data, &mut |data| data[0] |= 0x80)
+            .is_err());
+
+        assert_eq!(bus.inner.cycles, 2);
+        assert_eq!(bus.inner.memory[..9], [0x80, 0, 0, 0, 0x81, 0, 0, 0, 0]);
+        assert_eq!(bus.accesses(), 6);
+        assert_eq!(bus.injected(), 2);
+    }
+
+    #[test]
+    fn test_random_faults_are_reproducible() {
+        let run = |seed| {
+            let mut bus = FaultInjectionBus::<u32, _>::new([0_u8; 0x100]);
+            bus.inject_random(seed, 4, Fault::Error);
+            (0..64)
+                .map(|addr| bus.read_u8(Duration::ZERO, addr).is_err())
+                .collect::<Vec<bool>>()
+        };
+
+        let faults = run(1234);
+        assert_eq!(faults, run(1234));
+        assert_ne!(faults, run(5678));
+        let count = faults.iter().filter(|faulted| **faulted).count();
+        assert!((4..32).contains(&count));
+    }
+}
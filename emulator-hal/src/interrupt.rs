@@ -0,0 +1,247 @@
+//! Traits for requesting and acknowledging interrupts between peripherals and CPUs
+
+use crate::bus::ErrorType;
+use crate::time::Instant as EmuInstant;
+
+#[cfg(feature = "alloc")]
+use crate::signal::Signal;
+#[cfg(feature = "alloc")]
+use core::{convert::Infallible, marker::PhantomData};
+
+/// An interrupt request, ordered by priority so that `Nmi` is higher than any `Irq`
+///
+/// The level of an `Irq` is CPU specific, such as the 7 priority levels of the 68000 or the
+/// single level of the 6502, but a higher level is always a higher priority
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum Interrupt {
+    /// A maskable interrupt request at the given priority level
+    Irq(u8),
+    /// A non-maskable interrupt request
+    Nmi,
+}
+
+/// A peripheral that can request an interrupt
+pub trait InterruptSource {
+    /// The type for the time that interrupts are requested at
+    type Instant: EmuInstant;
+
+    /// The type of an error returned by this source
+    type Error: ErrorType;
+
+    /// Returns the interrupt currently being requested, if any
+    fn requested(&mut self, now: Self::Instant) -> Option<Interrupt>;
+
+    /// Acknowledge the requested interrupt, and return the vector number supplied by the
+    /// peripheral during the acknowledge cycle
+    ///
+    /// Returns `None` if the peripheral doesn't supply a vector, in which case the controller or
+    /// CPU uses its own default, such as an autovector on the 68000
+    fn acknowledge(
+        &mut self,
+        now: Self::Instant,
+        interrupt: Interrupt,
+    ) -> Result<Option<u8>, Self::Error>;
+}
+
+/// The interrupt inputs of a CPU, which may be connected to a single peripheral, or to an
+/// interrupt controller that prioritizes many peripherals
+///
+/// The CPU checks `pending()` at each instruction boundary, and if it will take the interrupt,
+/// calls `acknowledge()` to perform the acknowledge cycle and get the vector number to use
+pub trait InterruptController {
+    /// The type for the time that interrupts are requested at
+    type Instant: EmuInstant;
+
+    /// The type of an error returned by this controller
+    type Error: ErrorType;
+
+    /// Returns the highest priority interrupt currently pending, if any
+    fn pending(&mut self, now: Self::Instant) -> Option<Interrupt>;
+
+    /// Acknowledge the given pending interrupt, and return its vector number
+    fn acknowledge(&mut self, now: Self::Instant, interrupt: Interrupt) -> Result<u8, Self::Error>;
+}
+
+impl<T> InterruptSource for &mut T
+where
+    T: InterruptSource + ?Sized,
+{
+    type Instant = T::Instant;
+    type Error = T::Error;
+
+    #[inline]
+    fn requested(&mut self, now: Self::Instant) -> Option<Interrupt> {
+        T::requested(self, now)
+    }
+
+    #[inline]
+    fn acknowledge(
+        &mut self,
+        now: Self::Instant,
+        interrupt: Interrupt,
+    ) -> Result<Option<u8>, Self::Error> {
+        T::acknowledge(self, now, interrupt)
+    }
+}
+
+impl<T> InterruptController for &mut T
+where
+    T: InterruptController + ?Sized,
+{
+    type Instant = T::Instant;
+    type Error = T::Error;
+
+    #[inline]
+    fn pending(&mut self, now: Self::Instant) -> Option<Interrupt> {
+        T::pending(self, now)
+    }
+
+    #[inline]
+    fn acknowledge(&mut self, now: Self::Instant, interrupt: Interrupt) -> Result<u8, Self::Error> {
+        T::acknowledge(self, now, interrupt)
+    }
+}
+
+/// An interrupt source driven by a `Signal` line, which requests a fixed interrupt while the
+/// line is active
+///
+/// This connects the interrupt output of a peripheral, such as `IntervalTimer`, to anything
+/// expecting an `InterruptSource`.  It can also be used as the `InterruptController` of a CPU
+/// with a single interrupt line, in which case acknowledging returns the vector given to `new()`
+#[cfg(feature = "alloc")]
+pub struct SignalInterrupt<Instant> {
+    /// The line which requests the interrupt while it's active
+    pub line: Signal,
+    /// The interrupt requested
+    pub interrupt: Interrupt,
+    /// The vector number supplied when the interrupt is acknowledged, if any
+    pub vector: Option<u8>,
+    instant: PhantomData<Instant>,
+}
+
+#[cfg(feature = "alloc")]
+impl<Instant> SignalInterrupt<Instant> {
+    /// Construct a new source which requests `interrupt` while `line` is active
+    pub fn new(line: Signal, interrupt: Interrupt, vector: Option<u8>) -> Self {
+        Self {
+            line,
+            interrupt,
+            vector,
+            instant: PhantomData,
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<Instant> InterruptSource for SignalInterrupt<Instant>
+where
+    Instant: EmuInstant,
+{
+    type Instant = Instant;
+    type Error = Infallible;
+
+    #[inline]
+    fn requested(&mut self, _now: Self::Instant) -> Option<Interrupt> {
+        if self.line.get() {
+            Some(self.interrupt)
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    fn acknowledge(
+        &mut self,
+        _now: Self::Instant,
+        _interrupt: Interrupt,
+    ) -> Result<Option<u8>, Self::Error> {
+        Ok(self.vector)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<Instant> InterruptController for SignalInterrupt<Instant>
+where
+    Instant: EmuInstant,
+{
+    type Instant = Instant;
+    type Error = Infallible;
+
+    #[inline]
+    fn pending(&mut self, now: Self::Instant) -> Option<Interrupt> {
+        self.requested(now)
+    }
+
+    #[inline]
+    fn acknowledge(
+        &mut self,
+        _now: Self::Instant,
+        _interrupt: Interrupt,
+    ) -> Result<u8, Self::Error> {
+        Ok(self.vector.unwrap_or(0))
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    /// A controller which prioritizes its sources, and autovectors sources without a vector
+    struct Controller(Vec<SignalInterrupt<Duration>>);
+
+    impl InterruptController for Controller {
+        type Instant = Duration;
+        type Error = Infallible;
+
+        fn pending(&mut self, now: Duration) -> Option<Interrupt> {
+            self.0
+                .iter_mut()
+                .filter_map(|source| source.requested(now))
+                .max()
+        }
+
+        fn acknowledge(&mut self, now: Duration, interrupt: Interrupt) -> Result<u8, Infallible> {
+            let source = self
+                .0
+                .iter_mut()
+                .find(|source| source.line.get() && source.interrupt == interrupt)
+                .unwrap();
+            let vector = InterruptSource::acknowledge(source, now, interrupt)?;
+            Ok(vector.unwrap_or(match interrupt {
+                Interrupt::Irq(level) => 24 + level,
+                _ => 31,
+            }))
+        }
+    }
+
+    #[test]
+    fn test_prioritized_acknowledge() {
+        let timer = Signal::new();
+        let uart = Signal::new();
+        let reset_button = Signal::new();
+        let mut controller = Controller(vec![
+            SignalInterrupt::new(timer.clone(), Interrupt::Irq(6), None),
+            SignalInterrupt::new(uart.clone(), Interrupt::Irq(4), Some(0x40)),
+            SignalInterrupt::new(reset_button.clone(), Interrupt::Nmi, None),
+        ]);
+
+        assert_eq!(controller.pending(Duration::ZERO), None);
+        uart.raise();
+        timer.raise();
+        assert_eq!(controller.pending(Duration::ZERO), Some(Interrupt::Irq(6)));
+        assert_eq!(
+            controller.acknowledge(Duration::ZERO, Interrupt::Irq(6)),
+            Ok(30)
+        );
+
+        timer.lower();
+        assert_eq!(
+            controller.acknowledge(Duration::ZERO, Interrupt::Irq(4)),
+            Ok(0x40)
+        );
+        reset_button.raise();
+        assert_eq!(controller.pending(Duration::ZERO), Some(Interrupt::Nmi));
+    }
+}
@@ -0,0 +1,168 @@
+//! Iterators over the values stored in a range of addresses on a bus
+
+use core::ops::Range;
+
+use crate::bus::{BusAccess, BusAccessExt, ShortTransfer};
+
+/// The number of bytes read from the bus at a time by a `BusIter`
+const ITER_CHUNK_SIZE: usize = 64;
+
+/// An iterator over the values stored in a range of addresses on a bus
+///
+/// The range is read from the bus in chunks of up to 64 bytes, so iterating over a table or a
+/// string doesn't make a separate read for each value.  Each value is decoded from `size` bytes
+/// by the `decode` function, and any bytes at the end of the range that are too few to make a
+/// whole value are ignored.  If a read fails, its error is returned as the next item, and the
+/// iterator ends.  This is usually constructed with one of the `BusAccessExt` methods, such as
+/// `iter_beu16()`
+pub struct BusIter<'a, Address, Bus, T>
+where
+    Address: Copy,
+    Bus: BusAccess<Address> + ?Sized,
+{
+    bus: &'a mut Bus,
+    now: Option<Bus::Instant>,
+    next: u64,
+    end: u64,
+    size: usize,
+    decode: fn(&[u8]) -> T,
+    buffer: [u8; ITER_CHUNK_SIZE],
+    buffered: Range<usize>,
+}
+
+impl<'a, Address, Bus, T> BusIter<'a, Address, Bus, T>
+where
+    Address: Copy + Into<u64> + TryFrom<u64>,
+    Bus: BusAccess<Address> + ?Sized,
+    Bus::Error: From<ShortTransfer>,
+{
+    /// Construct a new iterator over the given range of addresses at time `now`, which decodes
+    /// each value of `size` bytes with the given function
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is zero or greater than 64
+    pub fn new(
+        bus: &'a mut Bus,
+        now: Bus::Instant,
+        range: Range<Address>,
+        size: usize,
+        decode: fn(&[u8]) -> T,
+    ) -> Self {
+        assert!(
+            size != 0 && size <= ITER_CHUNK_SIZE,
+            "value size must be between 1 and 64 bytes"
+        );
+        let next = range.start.into();
+        Self {
+            bus,
+            now: Some(now),
+            next,
+            end: range.end.into().max(next),
+            size,
+            decode,
+            buffer: [0; ITER_CHUNK_SIZE],
+            buffered: 0..0,
+        }
+    }
+
+    /// Returns the address of the next value that will be returned
+    pub fn address(&self) -> u64 {
+        self.next - self.buffered.len() as u64
+    }
+
+    fn fill(&mut self, now: Bus::Instant) -> Result<bool, Bus::Error> {
+        let remaining = (self.end - self.next).min(ITER_CHUNK_SIZE as u64) as usize;
+        let chunk = remaining / self.size * self.size;
+        if chunk == 0 {
+            return Ok(false);
+        }
+        let addr = match Address::try_from(self.next) {
+            Ok(addr) => addr,
+            Err(_) => return Ok(false),
+        };
+        self.bus.read_exact(now, addr, &mut self.buffer[..chunk])?;
+        self.next += chunk as u64;
+        self.buffered = 0..chunk;
+        Ok(true)
+    }
+}
+
+impl<'a, Address, Bus, T> Iterator for BusIter<'a, Address, Bus, T>
+where
+    Address: Copy + Into<u64> + TryFrom<u64>,
+    Bus: BusAccess<Address> + ?Sized,
+    Bus::Error: From<ShortTransfer>,
+{
+    type Item = Result<T, Bus::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let now = self.now?;
+        if self.buffered.is_empty() {
+            match self.fill(now) {
+                Ok(true) => {}
+                Ok(false) => {
+                    self.now = None;
+                    return None;
+                }
+                Err(err) => {
+                    self.now = None;
+                    return Some(Err(err));
+                }
+            }
+        }
+        let start = self.buffered.start;
+        self.buffered.start += self.size;
+        Some(Ok((self.decode)(&self.buffer[start..start + self.size])))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.now.is_none() {
+            return (0, Some(0));
+        }
+        let bytes = self.buffered.len() as u64 + (self.end - self.next);
+        let count = (bytes / self.size as u64) as usize;
+        (0, Some(count))
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod test {
+    use crate::{BasicBusError, BusAccessExt, Instant};
+    use alloc::vec::Vec;
+    use std::time::Duration;
+
+    #[test]
+    fn test_iterate_over_range() {
+        let now = Duration::START;
+        let mut bus: Vec<u8> = (0..=255).collect();
+
+        // crosses several chunks, and ignores the odd byte at the end
+        let values: Vec<u16> = bus
+            .iter_beu16(now, 0x11_u32..0xA0)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(values.len(), 71);
+        assert_eq!(values[0], 0x1112);
+        assert_eq!(values[70], 0x9D9E);
+        let sum: u32 = bus
+            .iter_u8(now, 0_u32..0x10)
+            .map(|value| value.unwrap() as u32)
+            .sum();
+        assert_eq!(sum, 120);
+        assert_eq!(
+            bus.iter_leu32(now, 0xF8_u32..0x100).size_hint(),
+            (0, Some(2))
+        );
+
+        // the values in the first chunk are returned before the error from the second
+        let mut iter = bus.iter_beu64(now, 0xC0_u32..0x110);
+        assert_eq!(iter.nth(7).unwrap().unwrap(), 0xF8F9_FAFB_FCFD_FEFF);
+        assert_eq!(iter.address(), 0x100);
+        assert!(matches!(
+            iter.next(),
+            Some(Err(BasicBusError::UnmappedAddress))
+        ));
+        assert!(iter.next().is_none());
+    }
+}
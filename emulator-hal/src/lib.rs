@@ -8,14 +8,96 @@ extern crate alloc;
 mod adapter;
 pub use crate::adapter::*;
 
+#[cfg(feature = "async")]
+mod asynchronous;
+#[cfg(feature = "async")]
+pub use crate::asynchronous::*;
+
 mod bus;
 pub use crate::bus::*;
 
-//mod interrupt;
-//pub use crate::interrupt::*;
+#[cfg(feature = "alloc")]
+mod coverage;
+#[cfg(feature = "alloc")]
+pub use crate::coverage::*;
+
+#[cfg(feature = "embedded-hal")]
+mod delay;
+#[cfg(feature = "embedded-hal")]
+pub use crate::delay::*;
+
+mod device;
+pub use crate::device::*;
+
+#[cfg(feature = "alloc")]
+mod fault;
+#[cfg(feature = "alloc")]
+pub use crate::fault::*;
+
+mod interrupt;
+pub use crate::interrupt::*;
+
+mod iter;
+pub use crate::iter::*;
+
+mod mastership;
+pub use crate::mastership::*;
+
+#[cfg(feature = "std")]
+mod metrics;
+#[cfg(feature = "std")]
+pub use crate::metrics::*;
+
+#[cfg(feature = "alloc")]
+mod profile;
+#[cfg(feature = "alloc")]
+pub use crate::profile::*;
+
+mod registers;
+pub use crate::registers::*;
+
+#[cfg(feature = "alloc")]
+mod report;
+#[cfg(feature = "alloc")]
+pub use crate::report::*;
+
+mod reset;
+pub use crate::reset::*;
+
+#[cfg(feature = "alloc")]
+mod scheduler;
+#[cfg(feature = "alloc")]
+pub use crate::scheduler::*;
+
+#[cfg(feature = "alloc")]
+mod shared;
+
+#[cfg(feature = "alloc")]
+mod signal;
+#[cfg(feature = "alloc")]
+pub use crate::signal::*;
+
+mod slice;
+
+mod snapshot;
+pub use crate::snapshot::*;
+
+mod space;
+pub use crate::space::*;
 
 mod step;
 pub use crate::step::*;
 
 mod time;
 pub use crate::time::*;
+
+mod transaction;
+pub use crate::transaction::*;
+
+mod translate;
+pub use crate::translate::*;
+
+#[cfg(feature = "alloc")]
+mod watch;
+#[cfg(feature = "alloc")]
+pub use crate::watch::*;
@@ -0,0 +1,205 @@
+//! Traits for arbitrating control of a bus shared between multiple bus masters
+
+/// A device that can request to become the bus master, such as a DMA controller or a
+/// coprocessor
+pub trait BusRequest {
+    /// Returns true while the device is requesting control of the bus
+    fn bus_requested(&mut self) -> bool;
+
+    /// Notify the device that it has been granted the bus, or that the bus was taken back
+    ///
+    /// The default implementation does nothing, for devices that are only stepped while they
+    /// own the bus
+    fn set_bus_granted(&mut self, granted: bool) {
+        let _ = granted;
+    }
+}
+
+/// The default bus master, usually a CPU, which can release the bus to another master
+///
+/// While the bus is released, the master must not access the bus, but it should still be
+/// stepped, so that it can stall until the bus is returned.  This is how cycles are stolen from
+/// the CPU by another bus master
+pub trait BusGrant {
+    /// Returns true if the master can release the bus now, such as between bus cycles or
+    /// instructions
+    fn can_release_bus(&mut self) -> bool;
+
+    /// Release the bus to another master, or take it back when `released` is false
+    fn set_bus_released(&mut self, released: bool);
+}
+
+impl<T> BusRequest for &mut T
+where
+    T: BusRequest + ?Sized,
+{
+    #[inline]
+    fn bus_requested(&mut self) -> bool {
+        T::bus_requested(self)
+    }
+
+    #[inline]
+    fn set_bus_granted(&mut self, granted: bool) {
+        T::set_bus_granted(self, granted)
+    }
+}
+
+impl<T> BusGrant for &mut T
+where
+    T: BusGrant + ?Sized,
+{
+    #[inline]
+    fn can_release_bus(&mut self) -> bool {
+        T::can_release_bus(self)
+    }
+
+    #[inline]
+    fn set_bus_released(&mut self, released: bool) {
+        T::set_bus_released(self, released)
+    }
+}
+
+/// Decides which master owns the bus, between a default master and a number of requesters
+///
+/// The requesters are given in order of priority, with the first having the highest priority.
+/// A requester keeps the bus until it stops requesting it, even if a higher priority requester
+/// makes a request in the meantime, and the bus is only taken from the default master when
+/// `BusGrant::can_release_bus()` returns true.  `arbitrate()` should be called before stepping
+/// the devices, such as once per step of the default master
+#[derive(Clone, Debug, Default)]
+pub struct BusArbiter {
+    owner: Option<usize>,
+}
+
+impl BusArbiter {
+    /// Construct a new arbiter, with the bus owned by the default master
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the index of the requester that owns the bus, or `None` if the default master
+    /// owns it
+    pub fn owner(&self) -> Option<usize> {
+        self.owner
+    }
+
+    /// Update the owner of the bus according to the current requests, and return it
+    pub fn arbitrate<Master>(
+        &mut self,
+        master: &mut Master,
+        requesters: &mut [&mut dyn BusRequest],
+    ) -> Option<usize>
+    where
+        Master: BusGrant + ?Sized,
+    {
+        if let Some(owner) = self.owner {
+            if let Some(requester) = requesters.get_mut(owner) {
+                if requester.bus_requested() {
+                    return self.owner;
+                }
+                requester.set_bus_granted(false);
+            }
+            self.owner = None;
+        }
+
+        let next = requesters
+            .iter_mut()
+            .position(|requester| requester.bus_requested());
+        match next {
+            Some(index) if master.can_release_bus() => {
+                master.set_bus_released(true);
+                requesters[index].set_bus_granted(true);
+                self.owner = Some(index);
+            }
+            Some(_) => {}
+            None => master.set_bus_released(false),
+        }
+        self.owner
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Default)]
+    struct Cpu {
+        in_bus_cycle: bool,
+        released: bool,
+    }
+
+    impl BusGrant for Cpu {
+        fn can_release_bus(&mut self) -> bool {
+            !self.in_bus_cycle
+        }
+
+        fn set_bus_released(&mut self, released: bool) {
+            self.released = released;
+        }
+    }
+
+    #[derive(Default)]
+    struct Dma {
+        requesting: bool,
+        granted: bool,
+    }
+
+    impl BusRequest for Dma {
+        fn bus_requested(&mut self) -> bool {
+            self.requesting
+        }
+
+        fn set_bus_granted(&mut self, granted: bool) {
+            self.granted = granted;
+        }
+    }
+
+    #[test]
+    fn test_arbitration() {
+        let mut arbiter = BusArbiter::new();
+        let mut cpu = Cpu {
+            in_bus_cycle: true,
+            released: false,
+        };
+        let mut disk = Dma::default();
+        let mut video = Dma::default();
+
+        assert_eq!(
+            arbiter.arbitrate(&mut cpu, &mut [&mut disk, &mut video]),
+            None
+        );
+        video.requesting = true;
+        assert_eq!(
+            arbiter.arbitrate(&mut cpu, &mut [&mut disk, &mut video]),
+            None
+        );
+        assert!(!cpu.released);
+
+        // the cpu only releases the bus between bus cycles, and the owner keeps it until done
+        cpu.in_bus_cycle = false;
+        assert_eq!(
+            arbiter.arbitrate(&mut cpu, &mut [&mut disk, &mut video]),
+            Some(1)
+        );
+        disk.requesting = true;
+        assert_eq!(
+            arbiter.arbitrate(&mut cpu, &mut [&mut disk, &mut video]),
+            Some(1)
+        );
+        assert!(cpu.released && video.granted && !disk.granted);
+
+        video.requesting = false;
+        assert_eq!(
+            arbiter.arbitrate(&mut cpu, &mut [&mut disk, &mut video]),
+            Some(0)
+        );
+        assert!(!video.granted && disk.granted);
+
+        disk.requesting = false;
+        assert_eq!(
+            arbiter.arbitrate(&mut cpu, &mut [&mut disk, &mut video]),
+            None
+        );
+        assert!(!cpu.released && !disk.granted);
+    }
+}
@@ -0,0 +1,561 @@
+//! Counters for monitoring a running emulator, which can be exported in the Prometheus text format
+
+use std::fmt::{self, Write};
+use std::string::String;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant as StdInstant};
+use std::vec::Vec;
+
+use crate::bus::BusAccess;
+use crate::step::{RunState, Step};
+use crate::transaction::Transaction;
+
+/// A monotonically increasing count, such as the number of steps performed by a device
+#[derive(Clone, Debug, Default)]
+pub struct Counter(Arc<AtomicU64>);
+
+impl Counter {
+    /// Increment the counter by one
+    #[inline]
+    pub fn inc(&self) {
+        self.add(1);
+    }
+
+    /// Increment the counter by the given amount
+    #[inline]
+    pub fn add(&self, count: u64) {
+        self.0.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Returns the current value of the counter
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// A value that can go up or down, such as the current emulation speed
+#[derive(Clone, Debug, Default)]
+pub struct Gauge(Arc<AtomicU64>);
+
+impl Gauge {
+    /// Set the current value of the gauge
+    #[inline]
+    pub fn set(&self, value: f64) {
+        self.0.store(value.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Returns the current value of the gauge
+    pub fn get(&self) -> f64 {
+        f64::from_bits(self.0.load(Ordering::Relaxed))
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Metric {
+    Counter(Counter),
+    Gauge(Gauge),
+}
+
+#[derive(Debug)]
+struct Entry {
+    name: String,
+    labels: Vec<(String, String)>,
+    metric: Metric,
+}
+
+/// A collection of named metrics, which can be shared between threads
+///
+/// Each metric is identified by a name and a set of labels, such as the name of a device or
+/// bus region.  Requesting the same name and labels again returns the same metric, so they can
+/// be created wherever it's convenient.  Cloning this object produces a handle to the same
+/// collection, and `render()` can be called from another thread (eg. an HTTP handler) while the
+/// emulator is running
+#[derive(Clone, Debug, Default)]
+pub struct Metrics {
+    entries: Arc<Mutex<Vec<Entry>>>,
+}
+
+impl Metrics {
+    /// Construct a new empty collection of metrics
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the counter with the given name and labels, creating it if it doesn't exist
+    ///
+    /// # Panics
+    ///
+    /// Panics if a gauge with the same name and labels already exists
+    pub fn counter(&self, name: &str, labels: &[(&str, &str)]) -> Counter {
+        match self.get_or_insert(name, labels, || Metric::Counter(Counter::default())) {
+            Metric::Counter(counter) => counter,
+            Metric::Gauge(_) => panic!("metric {} is already registered as a gauge", name),
+        }
+    }
+
+    /// Returns the gauge with the given name and labels, creating it if it doesn't exist
+    ///
+    /// # Panics
+    ///
+    /// Panics if a counter with the same name and labels already exists
+    pub fn gauge(&self, name: &str, labels: &[(&str, &str)]) -> Gauge {
+        match self.get_or_insert(name, labels, || Metric::Gauge(Gauge::default())) {
+            Metric::Gauge(gauge) => gauge,
+            Metric::Counter(_) => panic!("metric {} is already registered as a counter", name),
+        }
+    }
+
+    fn get_or_insert(
+        &self,
+        name: &str,
+        labels: &[(&str, &str)],
+        create: impl FnOnce() -> Metric,
+    ) -> Metric {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        let existing = entries.iter().find(|entry| {
+            entry.name == name
+                && entry.labels.len() == labels.len()
+                && entry.labels.iter().zip(labels.iter()).all(
+                    |((key, value), (other_key, other_value))| {
+                        key == other_key && value == other_value
+                    },
+                )
+        });
+
+        match existing {
+            Some(entry) => entry.metric.clone(),
+            None => {
+                let metric = create();
+                entries.push(Entry {
+                    name: String::from(name),
+                    labels: labels
+                        .iter()
+                        .map(|(key, value)| (String::from(*key), String::from(*value)))
+                        .collect(),
+                    metric: metric.clone(),
+                });
+                metric
+            }
+        }
+    }
+
+    /// Write the current value of every metric in the Prometheus text exposition format
+    pub fn write_prometheus<W: Write>(&self, writer: &mut W) -> fmt::Result {
+        let entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let mut names: Vec<&str> = entries.iter().map(|entry| entry.name.as_str()).collect();
+        names.sort_unstable();
+        names.dedup();
+
+        for name in names {
+            let mut group = entries.iter().filter(|entry| entry.name == name).peekable();
+            let kind = match group.peek().map(|entry| &entry.metric) {
+                Some(Metric::Gauge(_)) => "gauge",
+                _ => "counter",
+            };
+            writeln!(writer, "# TYPE {} {}", name, kind)?;
+
+            for entry in group {
+                write!(writer, "{}", entry.name)?;
+                if !entry.labels.is_empty() {
+                    write!(writer, "{{")?;
+                    for (i, (key, value)) in entry.labels.iter().enumerate() {
+                        if i != 0 {
+                            write!(writer, ",")?;
+                        }
+                        write!(writer, "{}=\"", key)?;
+                        for ch in value.chars() {
+                            match ch {
+                                '\\' => write!(writer, "\\\\")?,
+                                '"' => write!(writer, "\\\"")?,
+                                '\n' => write!(writer, "\\n")?,
+                                _ => writer.write_char(ch)?,
+                            }
+                        }
+                        write!(writer, "\"")?;
+                    }
+                    write!(writer, "}}")?;
+                }
+                match &entry.metric {
+                    Metric::Counter(counter) => writeln!(writer, " {}", counter.get())?,
+                    Metric::Gauge(gauge) => writeln!(writer, " {}", gauge.get())?,
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the current value of every metric in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let mut output = String::new();
+        // writing to a String cannot fail
+        let _ = self.write_prometheus(&mut output);
+        output
+    }
+}
+
+/// An adapter that counts the transactions and bytes transferred through the wrapped bus object
+///
+/// The counts are recorded in the `bus_transactions_total`, `bus_bytes_total`, and
+/// `bus_errors_total` metrics, labelled with the given region name and the access type
+pub struct MetricsBus<Bus> {
+    /// The underlying object implementing `BusAccess` that this object adapts
+    pub inner: Bus,
+    reads: Counter,
+    writes: Counter,
+    read_bytes: Counter,
+    write_bytes: Counter,
+    errors: Counter,
+}
+
+impl<Bus> MetricsBus<Bus> {
+    /// Construct a new adapter for the given `bus` object, recording metrics for the given region
+    pub fn new(metrics: &Metrics, region: &str, inner: Bus) -> Self {
+        let read = [("region", region), ("access", "read")];
+        let write = [("region", region), ("access", "write")];
+        Self {
+            inner,
+            reads: metrics.counter("bus_transactions_total", &read),
+            writes: metrics.counter("bus_transactions_total", &write),
+            read_bytes: metrics.counter("bus_bytes_total", &read),
+            write_bytes: metrics.counter("bus_bytes_total", &write),
+            errors: metrics.counter("bus_errors_total", &[("region", region)]),
+        }
+    }
+
+    fn record<E>(&self, transactions: &Counter, bytes: &Counter, result: &Result<usize, E>) {
+        transactions.inc();
+        match result {
+            Ok(count) => bytes.add(*count as u64),
+            Err(_) => self.errors.inc(),
+        }
+    }
+}
+
+impl<Address, Bus> BusAccess<Address> for MetricsBus<Bus>
+where
+    Address: Copy,
+    Bus: BusAccess<Address>,
+{
+    type Instant = Bus::Instant;
+    type Error = Bus::Error;
+
+    #[inline]
+    fn read(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        let result = self.inner.read(now, addr, data);
+        self.record(&self.reads, &self.read_bytes, &result);
+        result
+    }
+
+    #[inline]
+    fn write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        let result = self.inner.write(now, addr, data);
+        self.record(&self.writes, &self.write_bytes, &result);
+        result
+    }
+
+    #[inline]
+    fn read_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        let result = self.inner.read_ext(now, addr, data, transaction);
+        self.record(&self.reads, &self.read_bytes, &result);
+        result
+    }
+
+    #[inline]
+    fn write_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        let result = self.inner.write_ext(now, addr, data, transaction);
+        self.record(&self.writes, &self.write_bytes, &result);
+        result
+    }
+
+    #[inline]
+    fn read_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        let result = self.inner.read_timed(now, addr, data);
+        let count = result.as_ref().map(|(count, _)| *count);
+        self.record(&self.reads, &self.read_bytes, &count);
+        result
+    }
+
+    #[inline]
+    fn write_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        let result = self.inner.write_timed(now, addr, data);
+        let count = result.as_ref().map(|(count, _)| *count);
+        self.record(&self.writes, &self.write_bytes, &count);
+        result
+    }
+
+    /// The cycle is counted as a read followed by a write, or as a failed read if the read fails
+    #[inline]
+    fn read_modify_write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        modify: &mut dyn FnMut(&mut [u8]),
+    ) -> Result<usize, Self::Error> {
+        let (reads, read_bytes) = (&self.reads, &self.read_bytes);
+        let mut read = false;
+        let result = self.inner.read_modify_write(now, addr, data, &mut |data| {
+            read = true;
+            reads.inc();
+            read_bytes.add(data.len() as u64);
+            modify(data);
+        });
+        if read {
+            self.record(&self.writes, &self.write_bytes, &result);
+        } else {
+            self.record(&self.reads, &self.read_bytes, &result);
+        }
+        result
+    }
+
+    /// Peeks are passed through without being counted, since they're made by debuggers
+    #[inline]
+    fn peek(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        self.inner.peek(now, addr, data)
+    }
+
+    /// Pokes are passed through without being counted, since they're made by debuggers
+    #[inline]
+    fn poke(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        self.inner.poke(now, addr, data)
+    }
+}
+
+/// An adapter that counts the number of steps performed by the wrapped device
+///
+/// The count is recorded in the `device_steps_total` metric, labelled with the given device name
+pub struct MetricsStep<Device> {
+    /// The underlying device that this object adapts
+    pub inner: Device,
+    steps: Counter,
+}
+
+impl<Device> MetricsStep<Device> {
+    /// Construct a new adapter for the given device, recording metrics under the given name
+    pub fn new(metrics: &Metrics, device: &str, inner: Device) -> Self {
+        Self {
+            inner,
+            steps: metrics.counter("device_steps_total", &[("device", device)]),
+        }
+    }
+}
+
+impl<Address, Bus, Device> Step<Address, Bus> for MetricsStep<Device>
+where
+    Address: Copy,
+    Bus: BusAccess<Address>,
+    Device: Step<Address, Bus>,
+{
+    type Error = Device::Error;
+
+    #[inline]
+    fn is_running(&mut self) -> bool {
+        self.inner.is_running()
+    }
+
+    #[inline]
+    fn run_state(&mut self) -> RunState<Bus::Instant> {
+        self.inner.run_state()
+    }
+
+    #[inline]
+    fn reset(&mut self, now: Bus::Instant, bus: &mut Bus) -> Result<(), Self::Error> {
+        self.inner.reset(now, bus)
+    }
+
+    #[inline]
+    fn step(&mut self, now: Bus::Instant, bus: &mut Bus) -> Result<Bus::Instant, Self::Error> {
+        self.steps.inc();
+        self.inner.step(now, bus)
+    }
+}
+
+/// Measures the speed of the emulator, as the ratio of simulated time to real time
+///
+/// The ratio is recorded in the `emulation_speed_ratio` gauge, where 1.0 means the emulator is
+/// running in real time
+pub struct SpeedMeter {
+    gauge: Gauge,
+    last_update: StdInstant,
+}
+
+impl SpeedMeter {
+    /// Construct a new speed meter, which starts measuring from now
+    pub fn new(metrics: &Metrics) -> Self {
+        Self {
+            gauge: metrics.gauge("emulation_speed_ratio", &[]),
+            last_update: StdInstant::now(),
+        }
+    }
+
+    /// Update the speed, given the amount of simulated time that has passed since the last update
+    pub fn update(&mut self, simulated: Duration) {
+        let now = StdInstant::now();
+        let real = now.duration_since(self.last_update);
+        self.last_update = now;
+        if !real.is_zero() {
+            self.gauge.set(simulated.as_secs_f64() / real.as_secs_f64());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::BusAccessExt;
+    use crate::{BasicBusError, Instant};
+
+    #[test]
+    fn test_bus_metrics() {
+        let metrics = Metrics::new();
+        let mut bus = MetricsBus::new(&metrics, "ram", [0_u8; 16]);
+
+        bus.write_beu32(Duration::START, 0, 0x1234_5678).unwrap();
+        bus.read_beu16(Duration::START, 2).unwrap();
+        bus.read_beu16(Duration::START, 20).unwrap_err();
+        metrics.gauge("emulation_speed_ratio", &[]).set(0.5);
+
+        assert_eq!(
+            metrics.render(),
+            "\
+# TYPE bus_bytes_total counter
+bus_bytes_total{region=\"ram\",access=\"read\"} 2
+bus_bytes_total{region=\"ram\",access=\"write\"} 4
+# TYPE bus_errors_total counter
+bus_errors_total{region=\"ram\"} 1
+# TYPE bus_transactions_total counter
+bus_transactions_total{region=\"ram\",access=\"read\"} 2
+bus_transactions_total{region=\"ram\",access=\"write\"} 1
+# TYPE emulation_speed_ratio gauge
+emulation_speed_ratio 0.5
+"
+        );
+    }
+
+    /// A memory that counts the read-modify-write cycles made to it
+    struct AtomicMemory {
+        memory: [u8; 16],
+        cycles: usize,
+    }
+
+    impl BusAccess<u32> for AtomicMemory {
+        type Instant = Duration;
+        type Error = BasicBusError;
+
+        fn read(
+            &mut self,
+            now: Duration,
+            addr: u32,
+            data: &mut [u8],
+        ) -> Result<usize, Self::Error> {
+            self.memory.read(now, addr, data)
+        }
+
+        fn write(&mut self, now: Duration, addr: u32, data: &[u8]) -> Result<usize, Self::Error> {
+            self.memory.write(now, addr, data)
+        }
+
+        fn read_modify_write(
+            &mut self,
+            now: Duration,
+            addr: u32,
+            data: &mut [u8],
+            modify: &mut dyn FnMut(&mut [u8]),
+        ) -> Result<usize, Self::Error> {
+            self.cycles += 1;
+            self.memory.read_modify_write(now, addr, data, modify)
+        }
+    }
+
+    #[test]
+    fn test_read_modify_write_metrics() {
+        let metrics = Metrics::new();
+        let memory = AtomicMemory {
+            memory: [0; 16],
+            cycles: 0,
+        };
+        let mut bus = MetricsBus::new(&metrics, "ram", memory);
+
+        let mut data = [0; 2];
+        bus.read_modify_write(Duration::START, 4, &mut data, &mut |data| data[0] = 0x80)
+            .unwrap();
+        bus.read_modify_write(Duration::START, 20, &mut data, &mut |_| {})
+            .unwrap_err();
+
+        assert_eq!(bus.inner.cycles, 2);
+        assert_eq!(bus.inner.memory[4], 0x80);
+        assert_eq!(
+            metrics.render(),
+            "\
+# TYPE bus_bytes_total counter
+bus_bytes_total{region=\"ram\",access=\"read\"} 2
+bus_bytes_total{region=\"ram\",access=\"write\"} 2
+# TYPE bus_errors_total counter
+bus_errors_total{region=\"ram\"} 1
+# TYPE bus_transactions_total counter
+bus_transactions_total{region=\"ram\",access=\"read\"} 2
+bus_transactions_total{region=\"ram\",access=\"write\"} 1
+"
+        );
+    }
+
+    #[test]
+    fn test_shared_counters() {
+        let metrics = Metrics::new();
+        let counter = metrics.counter("device_steps_total", &[("device", "cpu \"0\"")]);
+        counter.add(3);
+        metrics
+            .counter("device_steps_total", &[("device", "cpu \"0\"")])
+            .inc();
+
+        assert_eq!(counter.get(), 4);
+        assert_eq!(
+            metrics.render(),
+            "# TYPE device_steps_total counter\ndevice_steps_total{device=\"cpu \\\"0\\\"\"} 4\n"
+        );
+    }
+}
@@ -0,0 +1,342 @@
+//! A bus wrapper that counts the accesses made to each address, to find the hottest addresses
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+
+use crate::bus::BusAccess;
+use crate::transaction::Transaction;
+
+/// The number of reads and writes made to an address or bucket of addresses
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct AccessCounts {
+    /// The number of reads, including instruction fetches
+    pub reads: u64,
+    /// The number of writes
+    pub writes: u64,
+}
+
+impl AccessCounts {
+    /// Returns the total number of reads and writes
+    pub fn total(&self) -> u64 {
+        self.reads + self.writes
+    }
+}
+
+/// A bus wrapper that counts the reads and writes made to each address, or bucket of addresses
+///
+/// This can be used to find the addresses a guest accesses the most, such as a status register
+/// that it polls in a loop, to see which devices are worth optimizing.  Addresses are grouped
+/// into buckets of `bucket_size` bytes, and each access is counted once, in the bucket of the
+/// address it starts at, whatever its length.  Only accesses that succeed are counted, and
+/// accesses made with `peek()` and `poke()` are never counted
+pub struct ProfilingBus<Address, Bus> {
+    /// The underlying object implementing `BusAccess` that this object profiles
+    pub inner: Bus,
+    bucket_size: u64,
+    counts: BTreeMap<u64, AccessCounts>,
+    address: PhantomData<Address>,
+}
+
+impl<Address, Bus> ProfilingBus<Address, Bus>
+where
+    Address: Copy + Into<u64>,
+    Bus: BusAccess<Address>,
+{
+    /// Construct a new wrapper for the given `bus` object, which counts accesses to each address
+    pub fn new(inner: Bus) -> Self {
+        Self::with_bucket_size(inner, 1)
+    }
+
+    /// Construct a new wrapper for the given `bus` object, which counts accesses to each bucket
+    /// of `bucket_size` addresses, starting from address 0
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bucket_size` is zero
+    pub fn with_bucket_size(inner: Bus, bucket_size: u64) -> Self {
+        assert!(bucket_size != 0, "bucket size must not be zero");
+        Self {
+            inner,
+            bucket_size,
+            counts: BTreeMap::new(),
+            address: PhantomData,
+        }
+    }
+
+    /// Returns the counts for the bucket containing the given address
+    pub fn counts(&self, addr: Address) -> AccessCounts {
+        self.counts
+            .get(&self.bucket(addr))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Returns the total counts for every address
+    pub fn total(&self) -> AccessCounts {
+        self.counts
+            .values()
+            .fold(AccessCounts::default(), |total, counts| AccessCounts {
+                reads: total.reads + counts.reads,
+                writes: total.writes + counts.writes,
+            })
+    }
+
+    /// Returns up to `n` of the most accessed buckets, with the address each one starts at
+    ///
+    /// The buckets are sorted by their total number of accesses, from most to least, and
+    /// buckets with the same total are sorted by address
+    pub fn hottest(&self, n: usize) -> Vec<(u64, AccessCounts)> {
+        let mut buckets: Vec<(u64, AccessCounts)> = self
+            .counts
+            .iter()
+            .map(|(bucket, counts)| (bucket * self.bucket_size, *counts))
+            .collect();
+        // the map is in order of address, and the sort is stable
+        buckets.sort_by_key(|(_, counts)| core::cmp::Reverse(counts.total()));
+        buckets.truncate(n);
+        buckets
+    }
+
+    /// Clear all of the counts
+    pub fn clear_counts(&mut self) {
+        self.counts.clear();
+    }
+
+    fn bucket(&self, addr: Address) -> u64 {
+        addr.into() / self.bucket_size
+    }
+
+    fn count_read(&mut self, addr: Address) {
+        self.counts.entry(self.bucket(addr)).or_default().reads += 1;
+    }
+
+    fn count_write(&mut self, addr: Address) {
+        self.counts.entry(self.bucket(addr)).or_default().writes += 1;
+    }
+}
+
+impl<Address, Bus> BusAccess<Address> for ProfilingBus<Address, Bus>
+where
+    Address: Copy + Into<u64>,
+    Bus: BusAccess<Address>,
+{
+    type Instant = Bus::Instant;
+    type Error = Bus::Error;
+
+    fn read(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        let count = self.inner.read(now, addr, data)?;
+        self.count_read(addr);
+        Ok(count)
+    }
+
+    fn write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        let count = self.inner.write(now, addr, data)?;
+        self.count_write(addr);
+        Ok(count)
+    }
+
+    fn read_modify_write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        modify: &mut dyn FnMut(&mut [u8]),
+    ) -> Result<usize, Self::Error> {
+        let bucket = self.bucket(addr);
+        let counts = &mut self.counts;
+        let count = self.inner.read_modify_write(now, addr, data, &mut |data| {
+            counts.entry(bucket).or_default().reads += 1;
+            modify(data);
+        })?;
+        self.count_write(addr);
+        Ok(count)
+    }
+
+    #[inline]
+    fn peek(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        self.inner.peek(now, addr, data)
+    }
+
+    #[inline]
+    fn poke(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        self.inner.poke(now, addr, data)
+    }
+
+    fn read_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        let result = self.inner.read_timed(now, addr, data)?;
+        self.count_read(addr);
+        Ok(result)
+    }
+
+    fn write_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        let result = self.inner.write_timed(now, addr, data)?;
+        self.count_write(addr);
+        Ok(result)
+    }
+
+    fn read_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        let count = self.inner.read_ext(now, addr, data, transaction)?;
+        self.count_read(addr);
+        Ok(count)
+    }
+
+    fn write_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        let count = self.inner.write_ext(now, addr, data, transaction)?;
+        self.count_write(addr);
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{BasicBusError, BusAccessExt, Instant};
+    use std::time::Duration;
+
+    /// A memory that counts the read-modify-write cycles made to it
+    struct AtomicMemory {
+        memory: Vec<u8>,
+        cycles: usize,
+    }
+
+    impl BusAccess<u32> for AtomicMemory {
+        type Instant = Duration;
+        type Error = BasicBusError;
+
+        fn read(
+            &mut self,
+            now: Duration,
+            addr: u32,
+            data: &mut [u8],
+        ) -> Result<usize, Self::Error> {
+            self.memory.read(now, addr, data)
+        }
+
+        fn write(&mut self, now: Duration, addr: u32, data: &[u8]) -> Result<usize, Self::Error> {
+            self.memory.write(now, addr, data)
+        }
+
+        fn read_modify_write(
+            &mut self,
+            now: Duration,
+            addr: u32,
+            data: &mut [u8],
+            modify: &mut dyn FnMut(&mut [u8]),
+        ) -> Result<usize, Self::Error> {
+            self.cycles += 1;
+            self.memory.read_modify_write(now, addr, data, modify)
+        }
+    }
+
+    #[test]
+    fn test_hottest_buckets() {
+        let now = Duration::START;
+        let mut bus = ProfilingBus::with_bucket_size(vec![0; 0x100], 0x10);
+        for _ in 0..5 {
+            bus.read_u8(now, 0x42_u32).unwrap();
+        }
+        bus.write_beu32(now, 0x4C, 0).unwrap();
+        bus.write_u8(now, 0x80, 0).unwrap();
+        bus.read_u8(now, 0x81).unwrap();
+        bus.peek(now, 0x80, &mut [0]).unwrap();
+        assert!(bus.read_u8(now, 0x100).is_err());
+
+        let hottest = bus.hottest(2);
+        assert_eq!(
+            hottest,
+            [
+                (
+                    0x40,
+                    AccessCounts {
+                        reads: 5,
+                        writes: 1
+                    }
+                ),
+                (
+                    0x80,
+                    AccessCounts {
+                        reads: 1,
+                        writes: 1
+                    }
+                ),
+            ]
+        );
+        assert_eq!(bus.counts(0x4F).total(), 6);
+        assert_eq!(
+            bus.total(),
+            AccessCounts {
+                reads: 6,
+                writes: 2
+            }
+        );
+
+        bus.clear_counts();
+        assert!(bus.hottest(10).is_empty());
+    }
+
+    #[test]
+    fn test_read_modify_write_is_forwarded() {
+        let memory = AtomicMemory {
+            memory: vec![0; 0x100],
+            cycles: 0,
+        };
+        let mut bus = ProfilingBus::new(memory);
+
+        let mut data = [0];
+        bus.read_modify_write(Duration::START, 0x42, &mut data, &mut |data| data[0] = 0x80)
+            .unwrap();
+
+        assert_eq!(bus.inner.cycles, 1);
+        assert_eq!(bus.inner.memory[0x42], 0x80);
+        assert_eq!(
+            bus.counts(0x42),
+            AccessCounts {
+                reads: 1,
+                writes: 1
+            }
+        );
+    }
+}
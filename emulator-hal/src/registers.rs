@@ -0,0 +1,280 @@
+//! Traits for reading and writing the registers of a CPU device
+
+/// The value of a register, sized to match the register
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum RegisterValue {
+    /// An 8-bit register value
+    U8(u8),
+    /// A 16-bit register value
+    U16(u16),
+    /// A 32-bit register value
+    U32(u32),
+    /// A 64-bit register value
+    U64(u64),
+}
+
+impl RegisterValue {
+    /// Returns the size of the value in bytes
+    pub fn size(&self) -> usize {
+        match self {
+            RegisterValue::U8(_) => 1,
+            RegisterValue::U16(_) => 2,
+            RegisterValue::U32(_) => 4,
+            RegisterValue::U64(_) => 8,
+        }
+    }
+
+    /// Returns the value zero-extended to a u64
+    pub fn as_u64(&self) -> u64 {
+        match *self {
+            RegisterValue::U8(value) => value as u64,
+            RegisterValue::U16(value) => value as u64,
+            RegisterValue::U32(value) => value as u64,
+            RegisterValue::U64(value) => value,
+        }
+    }
+
+    /// Returns a value of the given size in bytes, truncating `value` to fit, or `None` if
+    /// the size isn't supported
+    pub fn from_u64(size: usize, value: u64) -> Option<Self> {
+        match size {
+            1 => Some(RegisterValue::U8(value as u8)),
+            2 => Some(RegisterValue::U16(value as u16)),
+            4 => Some(RegisterValue::U32(value as u32)),
+            8 => Some(RegisterValue::U64(value)),
+            _ => None,
+        }
+    }
+}
+
+impl From<u8> for RegisterValue {
+    fn from(value: u8) -> Self {
+        RegisterValue::U8(value)
+    }
+}
+
+impl From<u16> for RegisterValue {
+    fn from(value: u16) -> Self {
+        RegisterValue::U16(value)
+    }
+}
+
+impl From<u32> for RegisterValue {
+    fn from(value: u32) -> Self {
+        RegisterValue::U32(value)
+    }
+}
+
+impl From<u64> for RegisterValue {
+    fn from(value: u64) -> Self {
+        RegisterValue::U64(value)
+    }
+}
+
+/// The role of a register, so that frontends can find registers like the program counter
+/// without knowing the CPU
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum RegisterKind {
+    /// The address of the next instruction to execute
+    ProgramCounter,
+    /// The current stack pointer
+    StackPointer,
+    /// The frame pointer, if the CPU has a dedicated one
+    FramePointer,
+    /// The flags or status register
+    Status,
+    /// A general purpose data or address register
+    General,
+    /// Any other register, such as a segment or control register
+    Other,
+}
+
+/// A description of a single register of a CPU
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RegisterInfo<RegisterId> {
+    /// The device-defined identifier used to access the register
+    pub id: RegisterId,
+    /// The name of the register, as used by the CPU's assembly syntax
+    pub name: &'static str,
+    /// The size of the register in bytes
+    pub size: usize,
+    /// The role of the register
+    pub kind: RegisterKind,
+}
+
+/// Read and write the registers of a CPU device
+///
+/// This gives debuggers and GDB stubs a portable way of accessing the registers of any CPU.
+/// Registers are identified by a device-defined `RegisterId`, usually an enum, and are
+/// enumerated in the order that a frontend should display them
+pub trait Registers {
+    /// The type used to identify a register
+    type RegisterId: Copy + PartialEq;
+
+    /// Represents an error that can occur while accessing a register
+    type Error;
+
+    /// Returns a description of every register of this device
+    fn registers(&self) -> &[RegisterInfo<Self::RegisterId>];
+
+    /// Returns the current value of the given register
+    fn read_register(&mut self, id: Self::RegisterId) -> Result<RegisterValue, Self::Error>;
+
+    /// Sets the value of the given register
+    fn write_register(
+        &mut self,
+        id: Self::RegisterId,
+        value: RegisterValue,
+    ) -> Result<(), Self::Error>;
+
+    /// Returns the id of the first register with the given role, if any
+    fn register_of_kind(&self, kind: RegisterKind) -> Option<Self::RegisterId> {
+        self.registers()
+            .iter()
+            .find(|info| info.kind == kind)
+            .map(|info| info.id)
+    }
+
+    /// Returns the id of the register with the given name, if any
+    fn register_by_name(&self, name: &str) -> Option<Self::RegisterId> {
+        self.registers()
+            .iter()
+            .find(|info| info.name.eq_ignore_ascii_case(name))
+            .map(|info| info.id)
+    }
+}
+
+impl<T> Registers for &mut T
+where
+    T: Registers + ?Sized,
+{
+    type RegisterId = T::RegisterId;
+    type Error = T::Error;
+
+    #[inline]
+    fn registers(&self) -> &[RegisterInfo<Self::RegisterId>] {
+        T::registers(self)
+    }
+
+    #[inline]
+    fn read_register(&mut self, id: Self::RegisterId) -> Result<RegisterValue, Self::Error> {
+        T::read_register(self, id)
+    }
+
+    #[inline]
+    fn write_register(
+        &mut self,
+        id: Self::RegisterId,
+        value: RegisterValue,
+    ) -> Result<(), Self::Error> {
+        T::write_register(self, id, value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    enum Reg {
+        A,
+        Flags,
+        Sp,
+        Pc,
+    }
+
+    const REGISTERS: &[RegisterInfo<Reg>] = &[
+        RegisterInfo {
+            id: Reg::A,
+            name: "a",
+            size: 1,
+            kind: RegisterKind::General,
+        },
+        RegisterInfo {
+            id: Reg::Flags,
+            name: "f",
+            size: 1,
+            kind: RegisterKind::Status,
+        },
+        RegisterInfo {
+            id: Reg::Sp,
+            name: "sp",
+            size: 2,
+            kind: RegisterKind::StackPointer,
+        },
+        RegisterInfo {
+            id: Reg::Pc,
+            name: "pc",
+            size: 2,
+            kind: RegisterKind::ProgramCounter,
+        },
+    ];
+
+    #[derive(Default)]
+    struct Cpu {
+        a: u8,
+        flags: u8,
+        sp: u16,
+        pc: u16,
+    }
+
+    impl Registers for Cpu {
+        type RegisterId = Reg;
+        type Error = ();
+
+        fn registers(&self) -> &[RegisterInfo<Reg>] {
+            REGISTERS
+        }
+
+        fn read_register(&mut self, id: Reg) -> Result<RegisterValue, ()> {
+            Ok(match id {
+                Reg::A => self.a.into(),
+                Reg::Flags => self.flags.into(),
+                Reg::Sp => self.sp.into(),
+                Reg::Pc => self.pc.into(),
+            })
+        }
+
+        fn write_register(&mut self, id: Reg, value: RegisterValue) -> Result<(), ()> {
+            match (id, value) {
+                (Reg::A, RegisterValue::U8(value)) => self.a = value,
+                (Reg::Flags, RegisterValue::U8(value)) => self.flags = value,
+                (Reg::Sp, RegisterValue::U16(value)) => self.sp = value,
+                (Reg::Pc, RegisterValue::U16(value)) => self.pc = value,
+                _ => return Err(()),
+            }
+            Ok(())
+        }
+    }
+
+    /// Sets the program counter of any CPU, the way a debugger frontend would
+    fn jump<R: Registers>(cpu: &mut R, address: u64) -> Result<(), R::Error> {
+        let pc = cpu.register_of_kind(RegisterKind::ProgramCounter).unwrap();
+        let size = cpu
+            .registers()
+            .iter()
+            .find(|info| info.id == pc)
+            .unwrap()
+            .size;
+        cpu.write_register(pc, RegisterValue::from_u64(size, address).unwrap())
+    }
+
+    #[test]
+    fn test_generic_register_access() {
+        let mut cpu = Cpu::default();
+
+        jump(&mut cpu, 0x1234).unwrap();
+        assert_eq!(cpu.pc, 0x1234);
+        assert_eq!(cpu.register_by_name("SP"), Some(Reg::Sp));
+        assert_eq!(cpu.write_register(Reg::A, RegisterValue::U32(1)), Err(()));
+
+        cpu.write_register(Reg::A, 0x55_u8.into()).unwrap();
+        let values: Vec<u64> = REGISTERS
+            .iter()
+            .map(|info| cpu.read_register(info.id).unwrap().as_u64())
+            .collect();
+        assert_eq!(values, [0x55, 0, 0, 0x1234]);
+    }
+}
@@ -0,0 +1,372 @@
+//! Errors with the context of where and when they occurred
+
+use alloc::vec::Vec;
+use core::fmt;
+use core::marker::PhantomData;
+
+use crate::bus::{BusAccess, ErrorType, ShortTransfer};
+use crate::transaction::Transaction;
+
+/// The kind of bus transaction that was being performed
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BusOperation {
+    /// Data was being read from the bus
+    Read,
+    /// Data was being written to the bus
+    Write,
+}
+
+/// A description of the bus transaction that was being performed when an error occurred
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ErrorContext<Instant> {
+    /// The name of the device or bus that was accessed
+    pub device: &'static str,
+    /// Whether the transaction was a read or a write
+    pub operation: BusOperation,
+    /// The address that was accessed, as seen by this device
+    pub addr: u64,
+    /// The number of bytes that were requested
+    pub size: usize,
+    /// The simulated time at which the transaction occurred
+    pub now: Instant,
+}
+
+impl<Instant> fmt::Display for ErrorContext<Instant>
+where
+    Instant: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (operation, preposition) = match self.operation {
+            BusOperation::Read => ("reading", "from"),
+            BusOperation::Write => ("writing", "to"),
+        };
+        let plural = if self.size == 1 { "" } else { "s" };
+        write!(
+            f,
+            "while {} {} byte{} {} {} at {:#x} (time {:?})",
+            operation, self.size, plural, preposition, self.device, self.addr, self.now
+        )
+    }
+}
+
+/// An error returned by a device, along with the context accumulated as it propagated to the caller
+///
+/// The context is added by each `ContextBus` the error passes through, so when devices are nested
+/// behind adapters, the report records the address seen at each level.  The context is ordered
+/// from the innermost device outwards
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BusErrorReport<Error, Instant> {
+    /// The original error returned by the device
+    pub error: Error,
+    /// The context added to the error, from innermost to outermost
+    pub context: Vec<ErrorContext<Instant>>,
+}
+
+impl<Error, Instant> BusErrorReport<Error, Instant> {
+    /// Construct a new report for the given error, without any context
+    pub fn new(error: Error) -> Self {
+        Self {
+            error,
+            context: Vec::new(),
+        }
+    }
+
+    /// Add a level of context to the report, which is assumed to be outside the previous levels
+    pub fn push_context(&mut self, context: ErrorContext<Instant>) {
+        self.context.push(context);
+    }
+
+    /// Add a level of context to the report, and return the report
+    pub fn with_context(mut self, context: ErrorContext<Instant>) -> Self {
+        self.push_context(context);
+        self
+    }
+
+    /// Returns the original error, discarding the context
+    pub fn into_inner(self) -> Error {
+        self.error
+    }
+}
+
+impl<Error, Instant> From<Error> for BusErrorReport<Error, Instant>
+where
+    Error: ErrorType,
+{
+    fn from(error: Error) -> Self {
+        Self::new(error)
+    }
+}
+
+/// Reports a short transfer as the original error, without any context
+impl<Error, Instant> From<ShortTransfer> for BusErrorReport<Error, Instant>
+where
+    Error: From<ShortTransfer>,
+{
+    fn from(err: ShortTransfer) -> Self {
+        Self::new(err.into())
+    }
+}
+
+impl<Error, Instant> ErrorType for BusErrorReport<Error, Instant>
+where
+    Error: ErrorType,
+    Instant: fmt::Debug,
+{
+}
+
+impl<Error, Instant> fmt::Display for BusErrorReport<Error, Instant>
+where
+    Error: fmt::Debug,
+    Instant: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.error)?;
+        for context in self.context.iter() {
+            write!(f, "\n    {}", context)?;
+        }
+        Ok(())
+    }
+}
+
+/// An adapter that adds context to any error returned by the wrapped bus object
+///
+/// The wrapped bus can return either a plain error of type `Error`, or a `BusErrorReport`
+/// produced by another `ContextBus`, in which case the context is added to the existing report.
+/// Addresses are recorded as a `u64`, so the address type must be convertible into one
+pub struct ContextBus<Bus, Error> {
+    /// The underlying object implementing `BusAccess` that this object adapts
+    pub inner: Bus,
+    /// The name of the device or bus, which is added to the context of any errors
+    pub device: &'static str,
+    /// Marker for the error type
+    error: PhantomData<Error>,
+}
+
+impl<Bus, Error> ContextBus<Bus, Error> {
+    /// Construct a new adapter with the given device name, for the given `bus` object
+    pub fn new(device: &'static str, inner: Bus) -> Self {
+        Self {
+            inner,
+            device,
+            error: PhantomData,
+        }
+    }
+
+    fn context<Instant>(
+        &self,
+        operation: BusOperation,
+        addr: u64,
+        size: usize,
+        now: Instant,
+    ) -> ErrorContext<Instant> {
+        ErrorContext {
+            device: self.device,
+            operation,
+            addr,
+            size,
+            now,
+        }
+    }
+}
+
+impl<Address, Bus, Error> BusAccess<Address> for ContextBus<Bus, Error>
+where
+    Address: Copy + Into<u64>,
+    Bus: BusAccess<Address>,
+    Bus::Error: Into<BusErrorReport<Error, Bus::Instant>>,
+    Error: ErrorType,
+{
+    type Instant = Bus::Instant;
+    type Error = BusErrorReport<Error, Bus::Instant>;
+
+    #[inline]
+    fn read(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        self.inner.read(now, addr, data).map_err(|err| {
+            err.into()
+                .with_context(self.context(BusOperation::Read, addr.into(), data.len(), now))
+        })
+    }
+
+    #[inline]
+    fn write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        self.inner.write(now, addr, data).map_err(|err| {
+            err.into()
+                .with_context(self.context(BusOperation::Write, addr.into(), data.len(), now))
+        })
+    }
+
+    #[inline]
+    fn peek(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        self.inner.peek(now, addr, data).map_err(|err| {
+            err.into()
+                .with_context(self.context(BusOperation::Read, addr.into(), data.len(), now))
+        })
+    }
+
+    #[inline]
+    fn poke(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        self.inner.poke(now, addr, data).map_err(|err| {
+            err.into()
+                .with_context(self.context(BusOperation::Write, addr.into(), data.len(), now))
+        })
+    }
+
+    #[inline]
+    fn read_modify_write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        modify: &mut dyn FnMut(&mut [u8]),
+    ) -> Result<usize, Self::Error> {
+        let size = data.len();
+        self.inner
+            .read_modify_write(now, addr, data, modify)
+            .map_err(|err| {
+                err.into()
+                    .with_context(self.context(BusOperation::Write, addr.into(), size, now))
+            })
+    }
+
+    #[inline]
+    fn read_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        self.inner
+            .read_ext(now, addr, data, transaction)
+            .map_err(|err| {
+                err.into().with_context(self.context(
+                    BusOperation::Read,
+                    addr.into(),
+                    data.len(),
+                    now,
+                ))
+            })
+    }
+
+    #[inline]
+    fn write_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        self.inner
+            .write_ext(now, addr, data, transaction)
+            .map_err(|err| {
+                err.into().with_context(self.context(
+                    BusOperation::Write,
+                    addr.into(),
+                    data.len(),
+                    now,
+                ))
+            })
+    }
+
+    #[inline]
+    fn read_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        self.inner.read_timed(now, addr, data).map_err(|err| {
+            err.into()
+                .with_context(self.context(BusOperation::Read, addr.into(), data.len(), now))
+        })
+    }
+
+    #[inline]
+    fn write_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        self.inner.write_timed(now, addr, data).map_err(|err| {
+            err.into()
+                .with_context(self.context(BusOperation::Write, addr.into(), data.len(), now))
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::BusAccessExt;
+    use crate::{BasicBusError, BusAdapter, DeniedAccess, ReadOnly};
+    use std::string::ToString;
+    use std::time::Duration;
+
+    #[test]
+    fn test_nested_context() {
+        let memory = ContextBus::<_, BasicBusError>::new(
+            "rom",
+            ReadOnly::new([0_u8; 16], DeniedAccess::Error),
+        );
+        let window: BusAdapter<_, _, _, BusErrorReport<_, _>> =
+            BusAdapter::new(memory, |addr: u16| addr as u32 - 0x100);
+        let mut bus = ContextBus::new("system", window);
+
+        let now = Duration::from_micros(10);
+        assert_eq!(bus.read_u8(now, 0x104).unwrap(), 0);
+
+        let report = bus.write_u8(now, 0x108, 0xFF).unwrap_err();
+        assert!(matches!(report.error, BasicBusError::ReadOnly));
+        assert_eq!(
+            report.context,
+            vec![
+                ErrorContext {
+                    device: "rom",
+                    operation: BusOperation::Write,
+                    addr: 0x08,
+                    size: 1,
+                    now,
+                },
+                ErrorContext {
+                    device: "system",
+                    operation: BusOperation::Write,
+                    addr: 0x108,
+                    size: 1,
+                    now,
+                },
+            ]
+        );
+        assert_eq!(
+            report.to_string(),
+            "ReadOnly\n    while writing 1 byte to rom at 0x8 (time 10µs)\n    while writing 1 byte to system at 0x108 (time 10µs)"
+        );
+
+        let report = bus.read_beu32(now, 0x10E).unwrap_err();
+        assert!(matches!(report.error, BasicBusError::UnmappedAddress));
+        assert_eq!(
+            report.context[0].to_string(),
+            "while reading 4 bytes from rom at 0xe (time 10µs)"
+        );
+    }
+}
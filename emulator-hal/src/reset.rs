@@ -0,0 +1,317 @@
+//! Resetting the devices of a system together, in a defined order
+
+#[cfg(feature = "alloc")]
+use alloc::boxed::Box;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+use crate::bus::BusAccess;
+#[cfg(feature = "alloc")]
+use crate::device::DeviceId;
+use crate::step::Step;
+
+/// The kind of reset that is asserted
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ResetKind {
+    /// A power-on reset, after which the device should be in the same state as when it was first
+    /// created, including the contents of any memory it has
+    Cold,
+    /// A reset from a reset button or a watchdog, which the device should treat as its reset
+    /// signal being asserted, and which leaves the contents of memory intact
+    Warm,
+}
+
+/// A device that can be reset, either at power-on or while running
+///
+/// This is separate from `Step::reset()` so that devices that aren't stepped, such as memory,
+/// can also be reset, and so that devices can tell a cold reset apart from a warm one.  Devices
+/// that implement `Step` can be wrapped in a `StepReset` to reset them with `Step::reset()`
+pub trait Resettable<Address, Bus>
+where
+    Address: Copy,
+    Bus: BusAccess<Address>,
+{
+    /// A type that is returned if the device can't be reset
+    type Error;
+
+    /// Reset the device at time `now`, using the given bus if it needs to access the system,
+    /// such as a CPU fetching its reset vector
+    fn reset_device(
+        &mut self,
+        kind: ResetKind,
+        now: Bus::Instant,
+        bus: &mut Bus,
+    ) -> Result<(), Self::Error>;
+}
+
+impl<Address, Bus, T> Resettable<Address, Bus> for &mut T
+where
+    Address: Copy,
+    Bus: BusAccess<Address>,
+    T: Resettable<Address, Bus> + ?Sized,
+{
+    type Error = T::Error;
+
+    #[inline]
+    fn reset_device(
+        &mut self,
+        kind: ResetKind,
+        now: Bus::Instant,
+        bus: &mut Bus,
+    ) -> Result<(), Self::Error> {
+        T::reset_device(self, kind, now, bus)
+    }
+}
+
+/// A wrapper that resets a device with `Step::reset()`, for either kind of reset
+pub struct StepReset<Device> {
+    /// The underlying device that is reset
+    pub inner: Device,
+}
+
+impl<Device> StepReset<Device> {
+    /// Construct a new wrapper for the given device
+    pub fn new(inner: Device) -> Self {
+        Self { inner }
+    }
+}
+
+impl<Address, Bus, Device> Resettable<Address, Bus> for StepReset<Device>
+where
+    Address: Copy,
+    Bus: BusAccess<Address>,
+    Device: Step<Address, Bus>,
+{
+    type Error = Device::Error;
+
+    #[inline]
+    fn reset_device(
+        &mut self,
+        _kind: ResetKind,
+        now: Bus::Instant,
+        bus: &mut Bus,
+    ) -> Result<(), Self::Error> {
+        self.inner.reset(now, bus)
+    }
+}
+
+/// The stage of a system reset that a device is reset in
+///
+/// Stages are reset in the order they're listed, so that memory is cleared before the
+/// peripherals are reset, and every peripheral is in its reset state before a CPU starts
+/// fetching from the bus
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum ResetStage {
+    /// Memory, and other devices that only hold data
+    Memory,
+    /// Peripheral devices, such as timers, serial ports, and interrupt controllers
+    Peripheral,
+    /// CPUs and other bus masters, such as DMA controllers
+    Cpu,
+}
+
+/// A boxed device that can be connected to a `ResetLine`
+#[cfg(feature = "alloc")]
+pub type BoxedResettable<Address, Bus, Error> = Box<dyn Resettable<Address, Bus, Error = Error>>;
+
+/// A reset signal connected to a set of devices, which resets all of them with a single call
+///
+/// Devices are reset in order of their `ResetStage`, and devices in the same stage are reset in
+/// the order they were connected.  Each device is identified by the `DeviceId` it's connected
+/// with, which would normally be assigned by the system's `DeviceRegistry`.  A device that is
+/// also stepped by a `Scheduler` can be shared with it by connecting an `Rc<RefCell<T>>`
+#[cfg(feature = "alloc")]
+pub struct ResetLine<Address, Bus, Error>
+where
+    Address: Copy,
+    Bus: BusAccess<Address>,
+{
+    devices: Vec<(DeviceId, ResetStage, BoxedResettable<Address, Bus, Error>)>,
+    order: Vec<usize>,
+}
+
+#[cfg(feature = "alloc")]
+impl<Address, Bus, Error> Default for ResetLine<Address, Bus, Error>
+where
+    Address: Copy,
+    Bus: BusAccess<Address>,
+{
+    fn default() -> Self {
+        Self {
+            devices: Vec::new(),
+            order: Vec::new(),
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<Address, Bus, Error> ResetLine<Address, Bus, Error>
+where
+    Address: Copy,
+    Bus: BusAccess<Address>,
+{
+    /// Construct a new reset line with no devices connected
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Connect a device with the given id, to be reset in the given stage
+    ///
+    /// # Panics
+    ///
+    /// Panics if a device with the same id has already been connected
+    pub fn connect(
+        &mut self,
+        id: DeviceId,
+        stage: ResetStage,
+        device: BoxedResettable<Address, Bus, Error>,
+    ) {
+        assert!(
+            self.devices.iter().all(|(existing, _, _)| *existing != id),
+            "device {} is already connected to the reset line",
+            id
+        );
+        self.devices.push((id, stage, device));
+        let devices = &self.devices;
+        self.order = (0..devices.len()).collect();
+        // the sort is stable, so devices in the same stage keep the order they were connected
+        self.order.sort_by_key(|index| devices[*index].1);
+    }
+
+    /// Returns the device with the given id, if any
+    pub fn device_mut(
+        &mut self,
+        id: DeviceId,
+    ) -> Option<&mut BoxedResettable<Address, Bus, Error>> {
+        self.devices
+            .iter_mut()
+            .find(|(existing, _, _)| *existing == id)
+            .map(|(_, _, device)| device)
+    }
+
+    /// Returns the number of devices connected to the reset line
+    pub fn len(&self) -> usize {
+        self.devices.len()
+    }
+
+    /// Returns true if no devices are connected to the reset line
+    pub fn is_empty(&self) -> bool {
+        self.devices.is_empty()
+    }
+
+    /// Reset every device at time `now`, in order of their stages
+    ///
+    /// If a device fails to reset, the error is returned and the remaining devices aren't reset
+    pub fn assert(
+        &mut self,
+        kind: ResetKind,
+        now: Bus::Instant,
+        bus: &mut Bus,
+    ) -> Result<(), Error> {
+        for index in self.order.iter() {
+            self.devices[*index].2.reset_device(kind, now, bus)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod test {
+    use super::*;
+    use crate::{BasicBusError, BusAccessExt, DeviceRegistry, Instant};
+    use alloc::rc::Rc;
+    use core::cell::RefCell;
+    use std::time::Duration;
+
+    type Log = Rc<RefCell<Vec<(&'static str, ResetKind)>>>;
+
+    struct Device {
+        name: &'static str,
+        log: Log,
+    }
+
+    impl Resettable<u32, Vec<u8>> for Device {
+        type Error = BasicBusError;
+
+        fn reset_device(
+            &mut self,
+            kind: ResetKind,
+            now: Duration,
+            bus: &mut Vec<u8>,
+        ) -> Result<(), Self::Error> {
+            self.log.borrow_mut().push((self.name, kind));
+            if self.name == "rom" {
+                bus.write_beu32(now, 0, 0x400)?;
+            }
+            Ok(())
+        }
+    }
+
+    struct Cpu {
+        pc: u32,
+    }
+
+    impl Step<u32, Vec<u8>> for Cpu {
+        type Error = BasicBusError;
+
+        fn is_running(&mut self) -> bool {
+            true
+        }
+
+        fn reset(&mut self, now: Duration, bus: &mut Vec<u8>) -> Result<(), Self::Error> {
+            self.pc = bus.read_beu32(now, 0)?;
+            Ok(())
+        }
+
+        fn step(&mut self, now: Duration, _bus: &mut Vec<u8>) -> Result<Duration, Self::Error> {
+            Ok(now)
+        }
+    }
+
+    #[test]
+    fn test_reset_in_stages() {
+        let log = Log::default();
+        let device = |name| {
+            Box::new(Device {
+                name,
+                log: log.clone(),
+            })
+        };
+        let cpu = Rc::new(RefCell::new(Cpu { pc: 0 }));
+
+        let mut registry = DeviceRegistry::new();
+        let mut line = ResetLine::new();
+        let cpu_id = registry.register("cpu").unwrap();
+        line.connect(
+            cpu_id,
+            ResetStage::Cpu,
+            Box::new(StepReset::new(cpu.clone())),
+        );
+        for (name, stage) in [
+            ("timer", ResetStage::Peripheral),
+            ("rom", ResetStage::Memory),
+            ("serial", ResetStage::Peripheral),
+        ] {
+            line.connect(registry.register(name).unwrap(), stage, device(name));
+        }
+        assert_eq!(line.len(), 4);
+        assert!(line.device_mut(cpu_id).is_some());
+        assert!(line.device_mut(DeviceId(4)).is_none());
+
+        let mut bus = vec![0; 4];
+        line.assert(ResetKind::Warm, Duration::START, &mut bus)
+            .unwrap();
+        assert_eq!(
+            *log.borrow(),
+            [
+                ("rom", ResetKind::Warm),
+                ("timer", ResetKind::Warm),
+                ("serial", ResetKind::Warm)
+            ]
+        );
+        // the cpu is reset last, after the reset vector has been loaded
+        assert_eq!(cpu.borrow().pc, 0x400);
+    }
+}
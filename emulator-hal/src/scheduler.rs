@@ -0,0 +1,344 @@
+//! A scheduler for stepping multiple devices in order of their next step time
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use crate::bus::BusAccess;
+use crate::device::DeviceId;
+use crate::step::{RunState, Step};
+use crate::time::Instant as EmuInstant;
+
+/// A boxed device that can be added to a `Scheduler`
+pub type BoxedStep<Address, Bus, Error> = Box<dyn Step<Address, Bus, Error = Error>>;
+
+/// Steps a set of devices sharing a bus, always stepping the device whose next step is the
+/// earliest, so that the devices advance together through simulated time
+///
+/// Each device is identified by the `DeviceId` it's added with, which would normally be assigned
+/// by the system's `DeviceRegistry`, so the same id refers to the device in traces, logs, and
+/// save states.  Devices are scheduled according to `Step::run_state()`.
+/// Devices that are halted aren't stepped before the time they're halted until, and devices that
+/// are waiting for an interrupt or stopped are skipped until they report that they're running
+/// again.  When two devices are due at the same time, the one added first is stepped first.
+///
+/// The time each device is next due is stored in its own contiguous array, separate from the
+/// boxed devices, so finding the next device only scans that array
+pub struct Scheduler<Address, Bus, Error>
+where
+    Address: Copy,
+    Bus: BusAccess<Address>,
+{
+    now: Bus::Instant,
+    next: Vec<Bus::Instant>,
+    ids: Vec<DeviceId>,
+    devices: Vec<BoxedStep<Address, Bus, Error>>,
+}
+
+impl<Address, Bus, Error> Default for Scheduler<Address, Bus, Error>
+where
+    Address: Copy,
+    Bus: BusAccess<Address>,
+{
+    fn default() -> Self {
+        Self {
+            now: Bus::Instant::START,
+            next: Vec::new(),
+            ids: Vec::new(),
+            devices: Vec::new(),
+        }
+    }
+}
+
+impl<Address, Bus, Error> Scheduler<Address, Bus, Error>
+where
+    Address: Copy,
+    Bus: BusAccess<Address>,
+{
+    /// Construct a new scheduler with no devices, starting at `Instant::START`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a device with the given id, which will first be stepped at the current time
+    ///
+    /// # Panics
+    ///
+    /// Panics if a device with the same id has already been added
+    pub fn insert(&mut self, id: DeviceId, device: BoxedStep<Address, Bus, Error>) {
+        assert!(
+            self.index_of(id).is_none(),
+            "device {} is already in the scheduler",
+            id
+        );
+        self.next.push(self.now);
+        self.ids.push(id);
+        self.devices.push(device);
+    }
+
+    /// Returns the current simulated time, which is the time of the last step
+    pub fn now(&self) -> Bus::Instant {
+        self.now
+    }
+
+    /// Returns the time that the given device will next be stepped at
+    pub fn next_due(&self, id: DeviceId) -> Option<Bus::Instant> {
+        self.index_of(id).map(|index| self.next[index])
+    }
+
+    /// Returns the device with the given id, if any
+    pub fn device_mut(&mut self, id: DeviceId) -> Option<&mut BoxedStep<Address, Bus, Error>> {
+        let index = self.index_of(id)?;
+        Some(&mut self.devices[index])
+    }
+
+    /// Returns the number of devices in the scheduler
+    pub fn len(&self) -> usize {
+        self.devices.len()
+    }
+
+    /// Returns true if the scheduler contains no devices
+    pub fn is_empty(&self) -> bool {
+        self.devices.is_empty()
+    }
+
+    /// Reset every device, and schedule them all to be stepped at the current time
+    pub fn reset(&mut self, bus: &mut Bus) -> Result<(), Error> {
+        for (device, next) in self.devices.iter_mut().zip(self.next.iter_mut()) {
+            device.reset(self.now, bus)?;
+            *next = self.now;
+        }
+        Ok(())
+    }
+
+    /// Step the running device that is due the earliest, and return its id, or `None` if no
+    /// devices are running
+    pub fn step(&mut self, bus: &mut Bus) -> Result<Option<DeviceId>, Error> {
+        let index = match self.next_running() {
+            Some(index) => index,
+            None => return Ok(None),
+        };
+        self.step_device(index, bus)?;
+        Ok(Some(self.ids[index]))
+    }
+
+    /// Step devices until the next device is due at or after `until`, and then advance the
+    /// current time to `until`
+    pub fn run_until(&mut self, bus: &mut Bus, until: Bus::Instant) -> Result<(), Error> {
+        while let Some(index) = self.next_running() {
+            if self.next[index] >= until {
+                break;
+            }
+            self.step_device(index, bus)?;
+        }
+        if until > self.now {
+            self.now = until;
+        }
+        Ok(())
+    }
+
+    /// Step devices until none of them are running
+    pub fn run_forever(&mut self, bus: &mut Bus) -> Result<(), Error> {
+        while self.step(bus)?.is_some() {}
+        Ok(())
+    }
+
+    fn index_of(&self, id: DeviceId) -> Option<usize> {
+        self.ids.iter().position(|existing| *existing == id)
+    }
+
+    /// Returns the index of the running device that is due the earliest
+    ///
+    /// A halted device is due no earlier than the time it's halted until, so its next step is
+    /// delayed if needed.  Since that can only make a device due later, the state of a device is
+    /// only checked if it could be due earlier than the earliest device found so far
+    fn next_running(&mut self) -> Option<usize> {
+        let mut earliest: Option<usize> = None;
+        for (index, device) in self.devices.iter_mut().enumerate() {
+            let is_earlier = |next: &[Bus::Instant]| {
+                earliest.map_or(true, |earliest| next[index] < next[earliest])
+            };
+            if !is_earlier(&self.next) {
+                continue;
+            }
+            match device.run_state() {
+                RunState::Running => {}
+                RunState::Halted(until) if until > self.next[index] => self.next[index] = until,
+                RunState::Halted(_) => {}
+                _ => continue,
+            }
+            if is_earlier(&self.next) {
+                earliest = Some(index);
+            }
+        }
+        earliest
+    }
+
+    fn step_device(&mut self, index: usize, bus: &mut Bus) -> Result<(), Error> {
+        let due = self.next[index];
+        if due > self.now {
+            self.now = due;
+        }
+        self.next[index] = self.devices[index].step(self.now, bus)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{DeviceRegistry, NoBus};
+    use std::cell::{Cell, RefCell};
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    type Bus = NoBus<Duration>;
+
+    /// A device which logs the times it was stepped at, and stops after a number of steps
+    struct Ticker {
+        name: char,
+        period: Duration,
+        remaining: usize,
+        log: Rc<RefCell<Vec<(char, Duration)>>>,
+    }
+
+    impl Step<u32, Bus> for Ticker {
+        type Error = ();
+
+        fn is_running(&mut self) -> bool {
+            self.remaining > 0
+        }
+
+        fn reset(&mut self, _now: Duration, _bus: &mut Bus) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn step(&mut self, now: Duration, _bus: &mut Bus) -> Result<Duration, Self::Error> {
+            self.remaining -= 1;
+            self.log.borrow_mut().push((self.name, now));
+            Ok(now + self.period)
+        }
+    }
+
+    fn ticker(
+        name: char,
+        period_us: u64,
+        remaining: usize,
+        log: &Rc<RefCell<Vec<(char, Duration)>>>,
+    ) -> BoxedStep<u32, Bus, ()> {
+        Box::new(Ticker {
+            name,
+            period: Duration::from_micros(period_us),
+            remaining,
+            log: log.clone(),
+        })
+    }
+
+    #[test]
+    fn test_steps_in_time_order() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut scheduler = Scheduler::new();
+        let mut bus = NoBus::new();
+        let mut registry = DeviceRegistry::new();
+        let cpu = registry.register("cpu").unwrap();
+        let timer = registry.register("timer").unwrap();
+        scheduler.insert(cpu, ticker('c', 2, usize::MAX, &log));
+        scheduler.insert(timer, ticker('t', 3, usize::MAX, &log));
+
+        scheduler
+            .run_until(&mut bus, Duration::from_micros(6))
+            .unwrap();
+        let us = Duration::from_micros;
+        assert_eq!(
+            *log.borrow(),
+            [
+                ('c', us(0)),
+                ('t', us(0)),
+                ('c', us(2)),
+                ('t', us(3)),
+                ('c', us(4)),
+            ]
+        );
+        assert_eq!(scheduler.now(), us(6));
+        assert_eq!(scheduler.next_due(cpu), Some(us(6)));
+        assert_eq!(scheduler.step(&mut bus), Ok(Some(cpu)));
+        assert_eq!(scheduler.next_due(DeviceId(2)), None);
+    }
+
+    #[test]
+    fn test_run_until_stopped() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut scheduler = Scheduler::new();
+        let mut bus = NoBus::new();
+        scheduler.insert(DeviceId(10), ticker('a', 5, 2, &log));
+        scheduler.insert(DeviceId(20), ticker('b', 1, 3, &log));
+
+        scheduler.run_forever(&mut bus).unwrap();
+        assert_eq!(log.borrow().len(), 5);
+        assert_eq!(scheduler.now(), Duration::from_micros(5));
+        assert_eq!(scheduler.step(&mut bus), Ok(None));
+    }
+
+    /// A device whose power state is controlled by the test
+    struct Sleeper {
+        state: Rc<Cell<RunState<Duration>>>,
+        log: Rc<RefCell<Vec<(char, Duration)>>>,
+    }
+
+    impl Step<u32, Bus> for Sleeper {
+        type Error = ();
+
+        fn is_running(&mut self) -> bool {
+            self.state.get().is_running()
+        }
+
+        fn run_state(&mut self) -> RunState<Duration> {
+            self.state.get()
+        }
+
+        fn reset(&mut self, _now: Duration, _bus: &mut Bus) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn step(&mut self, now: Duration, _bus: &mut Bus) -> Result<Duration, Self::Error> {
+            self.log.borrow_mut().push(('s', now));
+            Ok(now + Duration::from_micros(1))
+        }
+    }
+
+    #[test]
+    fn test_halted_and_waiting_devices() {
+        let us = Duration::from_micros;
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let state = Rc::new(Cell::new(RunState::Halted(us(5))));
+        let mut scheduler = Scheduler::new();
+        let mut bus = NoBus::new();
+        scheduler.insert(DeviceId(0), ticker('c', 2, usize::MAX, &log));
+        scheduler.insert(
+            DeviceId(1),
+            Box::new(Sleeper {
+                state: state.clone(),
+                log: log.clone(),
+            }),
+        );
+
+        scheduler.run_until(&mut bus, us(6)).unwrap();
+        state.set(RunState::WaitingForInterrupt);
+        scheduler.run_until(&mut bus, us(10)).unwrap();
+        // the interrupt wakes the device, which is stepped at the current time
+        state.set(RunState::Running);
+        scheduler.run_until(&mut bus, us(11)).unwrap();
+        assert_eq!(
+            *log.borrow(),
+            [
+                ('c', us(0)),
+                ('c', us(2)),
+                ('c', us(4)),
+                ('s', us(5)),
+                ('c', us(6)),
+                ('c', us(8)),
+                ('s', us(10)),
+                ('c', us(10)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,509 @@
+//! Implementations of `BusAccess` for buses shared between multiple owners
+
+use alloc::rc::Rc;
+use core::cell::RefCell;
+
+#[cfg(feature = "std")]
+use std::sync::PoisonError;
+
+#[cfg(all(feature = "std", loom))]
+use loom::sync::{Arc, Mutex};
+#[cfg(all(feature = "std", not(loom)))]
+use std::sync::{Arc, Mutex};
+
+use crate::bus::BusAccess;
+use crate::reset::{ResetKind, Resettable};
+use crate::step::{RunState, Step};
+use crate::transaction::Transaction;
+
+/// Allows a device to be shared between the bus it's mapped into and the code that steps it
+///
+/// A device that accesses the bus while it's being stepped, such as a DMA controller or a video
+/// chip that fetches from memory, can't be owned by the same bus it accesses, since stepping it
+/// would need two mutable borrows of the bus.  Instead, the bus can hold one `Rc<RefCell<T>>` of
+/// the device, and the device can be stepped through another clone with the bus passed to it.
+/// The cell is only borrowed for the duration of each call.
+///
+/// # Panics
+///
+/// Panics if the device is accessed while it's already borrowed, such as when a device being
+/// stepped accesses its own registers through the bus
+impl<Address, T> BusAccess<Address> for Rc<RefCell<T>>
+where
+    Address: Copy,
+    T: BusAccess<Address> + ?Sized,
+{
+    type Instant = T::Instant;
+    type Error = T::Error;
+
+    #[inline]
+    fn read(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, T::Error> {
+        self.borrow_mut().read(now, addr, data)
+    }
+
+    #[inline]
+    fn write(&mut self, now: Self::Instant, addr: Address, data: &[u8]) -> Result<usize, T::Error> {
+        self.borrow_mut().write(now, addr, data)
+    }
+
+    #[inline]
+    fn peek(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, T::Error> {
+        self.borrow_mut().peek(now, addr, data)
+    }
+
+    #[inline]
+    fn poke(&mut self, now: Self::Instant, addr: Address, data: &[u8]) -> Result<usize, T::Error> {
+        self.borrow_mut().poke(now, addr, data)
+    }
+
+    #[inline]
+    fn read_modify_write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        modify: &mut dyn FnMut(&mut [u8]),
+    ) -> Result<usize, T::Error> {
+        self.borrow_mut().read_modify_write(now, addr, data, modify)
+    }
+
+    #[inline]
+    fn read_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<(usize, Self::Instant), T::Error> {
+        self.borrow_mut().read_timed(now, addr, data)
+    }
+
+    #[inline]
+    fn write_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<(usize, Self::Instant), T::Error> {
+        self.borrow_mut().write_timed(now, addr, data)
+    }
+
+    #[inline]
+    fn read_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        transaction: Transaction,
+    ) -> Result<usize, T::Error> {
+        self.borrow_mut().read_ext(now, addr, data, transaction)
+    }
+
+    #[inline]
+    fn write_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+        transaction: Transaction,
+    ) -> Result<usize, T::Error> {
+        self.borrow_mut().write_ext(now, addr, data, transaction)
+    }
+}
+
+/// Allows a device that is also mapped into a bus to be stepped with that bus
+///
+/// # Panics
+///
+/// Panics if the device is already borrowed, such as when it accesses its own registers
+/// through the bus while it's being stepped
+impl<Address, Bus, T> Step<Address, Bus> for Rc<RefCell<T>>
+where
+    Address: Copy,
+    Bus: BusAccess<Address>,
+    T: Step<Address, Bus> + ?Sized,
+{
+    type Error = T::Error;
+
+    #[inline]
+    fn is_running(&mut self) -> bool {
+        self.borrow_mut().is_running()
+    }
+
+    #[inline]
+    fn run_state(&mut self) -> RunState<Bus::Instant> {
+        self.borrow_mut().run_state()
+    }
+
+    #[inline]
+    fn reset(&mut self, now: Bus::Instant, bus: &mut Bus) -> Result<(), Self::Error> {
+        self.borrow_mut().reset(now, bus)
+    }
+
+    #[inline]
+    fn step(&mut self, now: Bus::Instant, bus: &mut Bus) -> Result<Bus::Instant, Self::Error> {
+        self.borrow_mut().step(now, bus)
+    }
+}
+
+/// Allows a device to be connected to a `ResetLine` while it's also stepped or mapped into a bus
+///
+/// # Panics
+///
+/// Panics if the device is already borrowed
+impl<Address, Bus, T> Resettable<Address, Bus> for Rc<RefCell<T>>
+where
+    Address: Copy,
+    Bus: BusAccess<Address>,
+    T: Resettable<Address, Bus> + ?Sized,
+{
+    type Error = T::Error;
+
+    #[inline]
+    fn reset_device(
+        &mut self,
+        kind: ResetKind,
+        now: Bus::Instant,
+        bus: &mut Bus,
+    ) -> Result<(), Self::Error> {
+        self.borrow_mut().reset_device(kind, now, bus)
+    }
+}
+
+/// Allows a bus to be shared between threads, such as two CPUs that are stepped on separate threads
+///
+/// The mutex is held for the duration of each `read` or `write` call, so transactions from
+/// different owners are never interleaved with each other.  If another owner panicked while
+/// holding the lock, the bus is still used, since a panic doesn't necessarily leave an emulated
+/// device in an invalid state
+#[cfg(feature = "std")]
+impl<Address, T> BusAccess<Address> for Arc<Mutex<T>>
+where
+    Address: Copy,
+    T: BusAccess<Address> + ?Sized,
+{
+    type Instant = T::Instant;
+    type Error = T::Error;
+
+    #[inline]
+    fn read(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, T::Error> {
+        let mut bus = self.lock().unwrap_or_else(PoisonError::into_inner);
+        bus.read(now, addr, data)
+    }
+
+    #[inline]
+    fn write(&mut self, now: Self::Instant, addr: Address, data: &[u8]) -> Result<usize, T::Error> {
+        let mut bus = self.lock().unwrap_or_else(PoisonError::into_inner);
+        bus.write(now, addr, data)
+    }
+
+    #[inline]
+    fn peek(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, T::Error> {
+        let mut bus = self.lock().unwrap_or_else(PoisonError::into_inner);
+        bus.peek(now, addr, data)
+    }
+
+    #[inline]
+    fn poke(&mut self, now: Self::Instant, addr: Address, data: &[u8]) -> Result<usize, T::Error> {
+        let mut bus = self.lock().unwrap_or_else(PoisonError::into_inner);
+        bus.poke(now, addr, data)
+    }
+
+    #[inline]
+    fn read_modify_write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        modify: &mut dyn FnMut(&mut [u8]),
+    ) -> Result<usize, T::Error> {
+        let mut bus = self.lock().unwrap_or_else(PoisonError::into_inner);
+        bus.read_modify_write(now, addr, data, modify)
+    }
+
+    #[inline]
+    fn read_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<(usize, Self::Instant), T::Error> {
+        let mut bus = self.lock().unwrap_or_else(PoisonError::into_inner);
+        bus.read_timed(now, addr, data)
+    }
+
+    #[inline]
+    fn write_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<(usize, Self::Instant), T::Error> {
+        let mut bus = self.lock().unwrap_or_else(PoisonError::into_inner);
+        bus.write_timed(now, addr, data)
+    }
+
+    #[inline]
+    fn read_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        transaction: Transaction,
+    ) -> Result<usize, T::Error> {
+        let mut bus = self.lock().unwrap_or_else(PoisonError::into_inner);
+        bus.read_ext(now, addr, data, transaction)
+    }
+
+    #[inline]
+    fn write_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+        transaction: Transaction,
+    ) -> Result<usize, T::Error> {
+        let mut bus = self.lock().unwrap_or_else(PoisonError::into_inner);
+        bus.write_ext(now, addr, data, transaction)
+    }
+}
+
+/// Allows a device that is also mapped into a bus to be stepped with that bus, from any thread
+///
+/// The mutex is held for the whole step, so a device that accesses its own registers through
+/// the bus while it's being stepped will deadlock
+#[cfg(feature = "std")]
+impl<Address, Bus, T> Step<Address, Bus> for Arc<Mutex<T>>
+where
+    Address: Copy,
+    Bus: BusAccess<Address>,
+    T: Step<Address, Bus> + ?Sized,
+{
+    type Error = T::Error;
+
+    #[inline]
+    fn is_running(&mut self) -> bool {
+        self.lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .is_running()
+    }
+
+    #[inline]
+    fn run_state(&mut self) -> RunState<Bus::Instant> {
+        self.lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .run_state()
+    }
+
+    #[inline]
+    fn reset(&mut self, now: Bus::Instant, bus: &mut Bus) -> Result<(), Self::Error> {
+        let mut device = self.lock().unwrap_or_else(PoisonError::into_inner);
+        device.reset(now, bus)
+    }
+
+    #[inline]
+    fn step(&mut self, now: Bus::Instant, bus: &mut Bus) -> Result<Bus::Instant, Self::Error> {
+        let mut device = self.lock().unwrap_or_else(PoisonError::into_inner);
+        device.step(now, bus)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::BasicBusError;
+    use crate::BusAccessExt;
+    use std::time::Duration;
+
+    #[cfg(all(feature = "std", loom))]
+    use loom::thread;
+    #[cfg(all(feature = "std", not(loom)))]
+    use std::thread;
+
+    /// A dma controller which copies one byte each step, with its registers mapped at 0x100
+    #[derive(Default)]
+    struct Dma {
+        source: u8,
+        destination: u8,
+        count: u8,
+    }
+
+    impl BusAccess<u64> for Dma {
+        type Instant = Duration;
+        type Error = BasicBusError;
+
+        fn read(
+            &mut self,
+            _now: Duration,
+            addr: u64,
+            data: &mut [u8],
+        ) -> Result<usize, Self::Error> {
+            data[0] = [self.source, self.destination, self.count][addr as usize];
+            Ok(1)
+        }
+
+        fn write(&mut self, _now: Duration, addr: u64, data: &[u8]) -> Result<usize, Self::Error> {
+            *[&mut self.source, &mut self.destination, &mut self.count][addr as usize] = data[0];
+            Ok(1)
+        }
+    }
+
+    impl<Bus> Step<u64, Bus> for Dma
+    where
+        Bus: BusAccess<u64, Instant = Duration, Error = BasicBusError>,
+    {
+        type Error = BasicBusError;
+
+        fn is_running(&mut self) -> bool {
+            self.count != 0
+        }
+
+        fn reset(&mut self, _now: Duration, _bus: &mut Bus) -> Result<(), Self::Error> {
+            *self = Dma::default();
+            Ok(())
+        }
+
+        fn step(&mut self, now: Duration, bus: &mut Bus) -> Result<Duration, Self::Error> {
+            if self.count != 0 {
+                let byte = bus.read_u8(now, self.source as u64)?;
+                bus.write_u8(now, self.destination as u64, byte)?;
+                self.source += 1;
+                self.destination += 1;
+                self.count -= 1;
+            }
+            Ok(now + Duration::from_nanos(100))
+        }
+    }
+
+    /// A system bus with memory below 0x100, and the dma controller's registers above it
+    struct System {
+        memory: [u8; 0x100],
+        dma: Rc<RefCell<Dma>>,
+    }
+
+    impl BusAccess<u64> for System {
+        type Instant = Duration;
+        type Error = BasicBusError;
+
+        fn read(
+            &mut self,
+            now: Duration,
+            addr: u64,
+            data: &mut [u8],
+        ) -> Result<usize, BasicBusError> {
+            match addr {
+                0x100.. => self.dma.read(now, addr - 0x100, data),
+                _ => self.memory.read(now, addr, data),
+            }
+        }
+
+        fn write(&mut self, now: Duration, addr: u64, data: &[u8]) -> Result<usize, BasicBusError> {
+            match addr {
+                0x100.. => self.dma.write(now, addr - 0x100, data),
+                _ => self.memory.write(now, addr, data),
+            }
+        }
+    }
+
+    #[cfg(not(loom))]
+    #[test]
+    fn test_step_device_on_its_own_bus() {
+        let mut dma = Rc::new(RefCell::new(Dma::default()));
+        let mut system = System {
+            memory: [0; 0x100],
+            dma: dma.clone(),
+        };
+        let now = Duration::ZERO;
+
+        system.write_beu32(now, 0x10, 0x1234_5678).unwrap();
+        system.write_u8(now, 0x100, 0x10).unwrap();
+        system.write_u8(now, 0x101, 0x80).unwrap();
+        system.write_u8(now, 0x102, 4).unwrap();
+        while dma.borrow().count != 0 {
+            dma.step(now, &mut system).unwrap();
+        }
+
+        assert_eq!(system.read_beu32(now, 0x80).unwrap(), 0x1234_5678);
+        assert_eq!(system.read_u8(now, 0x101).unwrap(), 0x84);
+    }
+
+    #[cfg(feature = "std")]
+    fn run_two_writers() {
+        let mut bus = Arc::new(Mutex::new([0_u8; 16]));
+
+        let mut other = bus.clone();
+        let handle = thread::spawn(move || {
+            other
+                .write_beu64(Duration::ZERO, 0, 0x1111_1111_1111_1111)
+                .unwrap();
+        });
+        bus.write_beu64(Duration::ZERO, 0, 0x2222_2222_2222_2222)
+            .unwrap();
+        handle.join().unwrap();
+
+        // the two writes must never be interleaved with each other
+        let value = bus.read_beu64(Duration::ZERO, 0).unwrap();
+        assert!(value == 0x1111_1111_1111_1111 || value == 0x2222_2222_2222_2222);
+    }
+
+    #[cfg(feature = "std")]
+    fn run_two_incrementers() {
+        let mut bus = Arc::new(Mutex::new([0_u8; 16]));
+
+        let mut other = bus.clone();
+        let handle = thread::spawn(move || {
+            other
+                .read_modify_write(Duration::ZERO, 0, &mut [0], &mut |data| data[0] += 1)
+                .unwrap();
+        });
+        bus.read_modify_write(Duration::ZERO, 0, &mut [0], &mut |data| data[0] += 1)
+            .unwrap();
+        handle.join().unwrap();
+
+        // neither increment can be lost between the other's read and write
+        assert_eq!(bus.read_u8(Duration::ZERO, 0).unwrap(), 2);
+    }
+
+    #[cfg(all(feature = "std", not(loom)))]
+    #[test]
+    fn test_shared_bus_between_threads() {
+        run_two_writers();
+    }
+
+    #[cfg(all(feature = "std", not(loom)))]
+    #[test]
+    fn test_shared_read_modify_write() {
+        run_two_incrementers();
+    }
+
+    #[cfg(all(feature = "std", loom))]
+    #[test]
+    fn test_loom_shared_bus_between_threads() {
+        loom::model(run_two_writers);
+    }
+
+    #[cfg(all(feature = "std", loom))]
+    #[test]
+    fn test_loom_shared_read_modify_write() {
+        loom::model(run_two_incrementers);
+    }
+}
@@ -0,0 +1,60 @@
+//! Digital signal lines shared between devices
+
+use alloc::rc::Rc;
+use core::cell::Cell;
+
+/// A digital signal line, such as an interrupt request or a handshake line
+///
+/// A `Signal` is a shared handle, so cloning it produces another handle to the same line.  The
+/// device that drives the line keeps one handle, and gives clones to the devices that sense it.
+/// The line is active when it's `true`, regardless of the polarity of the real hardware signal
+#[derive(Clone, Debug, Default)]
+pub struct Signal(Rc<Cell<bool>>);
+
+impl Signal {
+    /// Construct a new inactive signal line
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the line to the given level
+    #[inline]
+    pub fn set(&self, level: bool) {
+        self.0.set(level);
+    }
+
+    /// Returns the current level of the line
+    #[inline]
+    pub fn get(&self) -> bool {
+        self.0.get()
+    }
+
+    /// Make the line active
+    #[inline]
+    pub fn raise(&self) {
+        self.set(true);
+    }
+
+    /// Make the line inactive
+    #[inline]
+    pub fn lower(&self) {
+        self.set(false);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_shared_line() {
+        let output = Signal::new();
+        let input = output.clone();
+
+        assert!(!input.get());
+        output.raise();
+        assert!(input.get());
+        output.lower();
+        assert!(!input.get());
+    }
+}
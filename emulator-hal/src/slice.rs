@@ -0,0 +1,149 @@
+//! Implementations of `BusAccess` for plain byte buffers
+
+use core::ops::Range;
+use core::time::Duration;
+
+use crate::bus::{BasicBusError, BusAccess};
+
+/// Returns the range of `contents_len` bytes covered by an access, or an error if any of it is
+/// out of bounds
+fn access_range<Address>(
+    contents_len: usize,
+    addr: Address,
+    len: usize,
+) -> Result<Range<usize>, BasicBusError>
+where
+    Address: TryInto<usize>,
+{
+    let start = addr
+        .try_into()
+        .map_err(|_| BasicBusError::UnmappedAddress)?;
+    match start.checked_add(len) {
+        Some(end) if end <= contents_len => Ok(start..end),
+        _ => Err(BasicBusError::UnmappedAddress),
+    }
+}
+
+/// Allows a byte slice to be used directly as memory, such as in tests and prototypes
+///
+/// Addresses are offsets into the slice, and any access that goes past the end fails with
+/// `BasicBusError::UnmappedAddress`.  The time is given as a `Duration`, but a `MemoryBlock`
+/// from the `emulator-hal-memory` crate can be used for memory with another type of `Instant`
+impl<Address> BusAccess<Address> for [u8]
+where
+    Address: TryInto<usize> + Copy,
+{
+    type Instant = Duration;
+    type Error = BasicBusError;
+
+    #[inline]
+    fn read(
+        &mut self,
+        _now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        let range = access_range(self.len(), addr, data.len())?;
+        data.copy_from_slice(&self[range]);
+        Ok(data.len())
+    }
+
+    #[inline]
+    fn write(
+        &mut self,
+        _now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        let range = access_range(self.len(), addr, data.len())?;
+        self[range].copy_from_slice(data);
+        Ok(data.len())
+    }
+}
+
+/// Allows a byte array to be used directly as memory, in the same way as a byte slice
+impl<Address, const N: usize> BusAccess<Address> for [u8; N]
+where
+    Address: TryInto<usize> + Copy,
+{
+    type Instant = Duration;
+    type Error = BasicBusError;
+
+    #[inline]
+    fn read(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        self.as_mut_slice().read(now, addr, data)
+    }
+
+    #[inline]
+    fn write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        self.as_mut_slice().write(now, addr, data)
+    }
+}
+
+/// Allows a byte vector to be used directly as memory, in the same way as a byte slice
+///
+/// The vector is never resized, so writes past the end fail instead of extending it
+#[cfg(feature = "alloc")]
+impl<Address> BusAccess<Address> for alloc::vec::Vec<u8>
+where
+    Address: TryInto<usize> + Copy,
+{
+    type Instant = Duration;
+    type Error = BasicBusError;
+
+    #[inline]
+    fn read(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        self.as_mut_slice().read(now, addr, data)
+    }
+
+    #[inline]
+    fn write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        self.as_mut_slice().write(now, addr, data)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{BusAccessExt, Instant};
+
+    #[test]
+    fn test_byte_buffers_as_memory() {
+        let mut memory = vec![0; 8];
+        memory
+            .write_beu32(Duration::START, 2_u32, 0x1234_5678)
+            .unwrap();
+        assert_eq!(memory, [0, 0, 0x12, 0x34, 0x56, 0x78, 0, 0]);
+        assert!(matches!(
+            memory.read_beu32(Duration::START, 6_u32),
+            Err(BasicBusError::UnmappedAddress)
+        ));
+
+        let mut array = [0xAA; 4];
+        assert_eq!(array.read_leu16(Duration::START, 1_u8).unwrap(), 0xAAAA);
+        assert!(array.write_u8(Duration::START, -1_i32, 0).is_err());
+
+        let slice = &mut memory[2..6];
+        assert_eq!(slice.read_beu16(Duration::START, 2_usize).unwrap(), 0x5678);
+    }
+}
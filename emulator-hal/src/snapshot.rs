@@ -0,0 +1,140 @@
+//! Traits for saving and restoring the state of a device
+
+/// An error that occurred while saving or restoring the state of a device
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum SnapshotError {
+    /// The saved state ended before all of the expected data was read
+    UnexpectedEnd,
+
+    /// The saved state was written with a version that is not supported
+    UnsupportedVersion(u32),
+
+    /// The saved state contains data that is not valid
+    InvalidData,
+
+    /// The saved state requires a feature that is not supported, such as a compression method
+    Unsupported,
+
+    /// The saved state does not contain any data for the requested device
+    NotFound,
+}
+
+/// A destination for the saved state of a device
+///
+/// Multi-byte values are always written in little endian byte order
+pub trait StateWriter {
+    /// Write the given bytes to the saved state
+    fn write_bytes(&mut self, data: &[u8]) -> Result<(), SnapshotError>;
+
+    /// Write a single u8 value
+    #[inline]
+    fn write_u8(&mut self, value: u8) -> Result<(), SnapshotError> {
+        self.write_bytes(&[value])
+    }
+
+    /// Write a single u16 value
+    #[inline]
+    fn write_u16(&mut self, value: u16) -> Result<(), SnapshotError> {
+        self.write_bytes(&value.to_le_bytes())
+    }
+
+    /// Write a single u32 value
+    #[inline]
+    fn write_u32(&mut self, value: u32) -> Result<(), SnapshotError> {
+        self.write_bytes(&value.to_le_bytes())
+    }
+
+    /// Write a single u64 value
+    #[inline]
+    fn write_u64(&mut self, value: u64) -> Result<(), SnapshotError> {
+        self.write_bytes(&value.to_le_bytes())
+    }
+
+    /// Write a single bool value
+    #[inline]
+    fn write_bool(&mut self, value: bool) -> Result<(), SnapshotError> {
+        self.write_u8(value as u8)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl StateWriter for alloc::vec::Vec<u8> {
+    fn write_bytes(&mut self, data: &[u8]) -> Result<(), SnapshotError> {
+        self.extend_from_slice(data);
+        Ok(())
+    }
+}
+
+/// A source of the previously saved state of a device
+///
+/// Multi-byte values are always read in little endian byte order
+pub trait StateReader {
+    /// Returns the version of the state, as given by `Snapshot::state_version()` when it was saved
+    fn version(&self) -> u32;
+
+    /// Read exactly enough bytes to fill `data` from the saved state
+    fn read_bytes(&mut self, data: &mut [u8]) -> Result<(), SnapshotError>;
+
+    /// Read a single u8 value
+    #[inline]
+    fn read_u8(&mut self) -> Result<u8, SnapshotError> {
+        let mut data = [0; 1];
+        self.read_bytes(&mut data)?;
+        Ok(data[0])
+    }
+
+    /// Read a single u16 value
+    #[inline]
+    fn read_u16(&mut self) -> Result<u16, SnapshotError> {
+        let mut data = [0; 2];
+        self.read_bytes(&mut data)?;
+        Ok(u16::from_le_bytes(data))
+    }
+
+    /// Read a single u32 value
+    #[inline]
+    fn read_u32(&mut self) -> Result<u32, SnapshotError> {
+        let mut data = [0; 4];
+        self.read_bytes(&mut data)?;
+        Ok(u32::from_le_bytes(data))
+    }
+
+    /// Read a single u64 value
+    #[inline]
+    fn read_u64(&mut self) -> Result<u64, SnapshotError> {
+        let mut data = [0; 8];
+        self.read_bytes(&mut data)?;
+        Ok(u64::from_le_bytes(data))
+    }
+
+    /// Read a single bool value
+    #[inline]
+    fn read_bool(&mut self) -> Result<bool, SnapshotError> {
+        match self.read_u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(SnapshotError::InvalidData),
+        }
+    }
+}
+
+/// A device whose internal state can be saved and later restored
+///
+/// This is intended to be implemented alongside `Step` and `BusAccess` so that every device in
+/// a system can participate in whole-machine save states
+pub trait Snapshot {
+    /// Returns the version of the state written by `save_state()`
+    ///
+    /// This should be incremented whenever the saved data changes, so that `load_state()` can
+    /// check `StateReader::version()` and either convert an older state or reject it
+    fn state_version(&self) -> u32 {
+        0
+    }
+
+    /// Write the current state of the device to the given writer
+    fn save_state(&self, writer: &mut dyn StateWriter) -> Result<(), SnapshotError>;
+
+    /// Replace the current state of the device with the state read from the given reader
+    fn load_state(&mut self, reader: &mut dyn StateReader) -> Result<(), SnapshotError>;
+}
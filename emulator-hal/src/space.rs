@@ -0,0 +1,519 @@
+//! Address spaces for CPUs that qualify each address with the kind of access
+
+use core::marker::PhantomData;
+
+use crate::adapter::FromAddress;
+use crate::bus::{BusAccess, BusAccessExt, ShortTransfer};
+use crate::transaction::Transaction;
+
+/// The address space of a CPU with separate memory and I/O instructions, such as the Z80 or x86
+///
+/// Devices are accessed with a tuple of `(MemOrIo, Address)`
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum MemOrIo {
+    /// An access to memory, such as by a load or store
+    Memory,
+    /// An access to an I/O port, such as by an IN or OUT instruction
+    Io,
+}
+
+/// An address in a CPU with separate memory and I/O spaces, which is a 16-bit address by default
+/// as on the Z80
+pub type IoAddress<Address = u16> = (MemOrIo, Address);
+
+/// The function code output by 68000 family CPUs, which qualifies each access by whether it is
+/// for a program or data, and whether the CPU is in supervisor mode
+///
+/// Devices are accessed with a tuple of `(FunctionCode, Address)`.  The discriminants are the
+/// values of the FC0-FC2 pins
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum FunctionCode {
+    /// A data access in user mode
+    UserData = 1,
+    /// An instruction fetch in user mode
+    UserProgram = 2,
+    /// A data access in supervisor mode
+    SupervisorData = 5,
+    /// An instruction fetch in supervisor mode
+    SupervisorProgram = 6,
+    /// A CPU space cycle, such as an interrupt acknowledge or coprocessor access
+    CpuSpace = 7,
+}
+
+impl FunctionCode {
+    /// Returns the function code for the value of the FC0-FC2 pins, or `None` if it's reserved
+    pub fn from_bits(bits: u8) -> Option<Self> {
+        match bits {
+            1 => Some(FunctionCode::UserData),
+            2 => Some(FunctionCode::UserProgram),
+            5 => Some(FunctionCode::SupervisorData),
+            6 => Some(FunctionCode::SupervisorProgram),
+            7 => Some(FunctionCode::CpuSpace),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of the FC0-FC2 pins for this function code
+    pub fn bits(self) -> u8 {
+        self as u8
+    }
+
+    /// Returns true if the access was made in supervisor mode
+    pub fn is_supervisor(self) -> bool {
+        self.bits() & 0b100 != 0
+    }
+
+    /// Returns true if the access is an instruction fetch
+    pub fn is_program(self) -> bool {
+        matches!(
+            self,
+            FunctionCode::UserProgram | FunctionCode::SupervisorProgram
+        )
+    }
+}
+
+/// Drops the address space, for devices that respond the same way in every space
+impl<Address> FromAddress<(MemOrIo, Address)> for Address {
+    fn from_address(address: (MemOrIo, Address)) -> Self {
+        address.1
+    }
+}
+
+/// Drops the function code, for devices that respond the same way in every space
+impl<Address> FromAddress<(FunctionCode, Address)> for Address {
+    fn from_address(address: (FunctionCode, Address)) -> Self {
+        address.1
+    }
+}
+
+/// An address space that a `SpaceRouter` can divide between two buses
+pub trait RouteSpace: Copy {
+    /// Returns true if accesses in this space go to the secondary bus
+    fn is_secondary(&self) -> bool;
+}
+
+/// I/O accesses go to the secondary bus
+impl RouteSpace for MemOrIo {
+    fn is_secondary(&self) -> bool {
+        *self == MemOrIo::Io
+    }
+}
+
+/// CPU space cycles go to the secondary bus, and all other accesses go to the primary bus
+impl RouteSpace for FunctionCode {
+    fn is_secondary(&self) -> bool {
+        *self == FunctionCode::CpuSpace
+    }
+}
+
+/// A bus that dispatches each access to one of two buses based on the address space component
+/// of a `(Space, Address)` tuple, and passes the rest of the address to that bus
+///
+/// For a Z80, the primary bus would be the memory map and the secondary bus the I/O ports.  For
+/// a 68000, the primary bus would be the memory map and the secondary bus would handle CPU
+/// space cycles such as interrupt acknowledges.  Other ways of dividing a space, such as
+/// separate program and data memories, can be expressed by implementing `RouteSpace` for a
+/// custom space type.  The two buses must use the same `Instant`, and the error of the
+/// secondary bus must be convertible into the error of the primary bus
+#[derive(Default)]
+pub struct SpaceRouter<Primary, Secondary> {
+    /// The bus that receives accesses where `RouteSpace::is_secondary()` returns false
+    pub primary: Primary,
+    /// The bus that receives accesses where `RouteSpace::is_secondary()` returns true
+    pub secondary: Secondary,
+}
+
+impl<Primary, Secondary> SpaceRouter<Primary, Secondary> {
+    /// Construct a new router from the given buses
+    pub fn new(primary: Primary, secondary: Secondary) -> Self {
+        Self { primary, secondary }
+    }
+}
+
+impl<Space, Address, Primary, Secondary> BusAccess<(Space, Address)>
+    for SpaceRouter<Primary, Secondary>
+where
+    Space: RouteSpace,
+    Address: Copy,
+    Primary: BusAccess<Address>,
+    Primary::Error: From<Secondary::Error>,
+    Secondary: BusAccess<Address, Instant = Primary::Instant>,
+{
+    type Instant = Primary::Instant;
+    type Error = Primary::Error;
+
+    #[inline]
+    fn read(
+        &mut self,
+        now: Self::Instant,
+        (space, addr): (Space, Address),
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        if space.is_secondary() {
+            Ok(self.secondary.read(now, addr, data)?)
+        } else {
+            self.primary.read(now, addr, data)
+        }
+    }
+
+    #[inline]
+    fn write(
+        &mut self,
+        now: Self::Instant,
+        (space, addr): (Space, Address),
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        if space.is_secondary() {
+            Ok(self.secondary.write(now, addr, data)?)
+        } else {
+            self.primary.write(now, addr, data)
+        }
+    }
+
+    #[inline]
+    fn read_ext(
+        &mut self,
+        now: Self::Instant,
+        (space, addr): (Space, Address),
+        data: &mut [u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        if space.is_secondary() {
+            Ok(self.secondary.read_ext(now, addr, data, transaction)?)
+        } else {
+            self.primary.read_ext(now, addr, data, transaction)
+        }
+    }
+
+    #[inline]
+    fn write_ext(
+        &mut self,
+        now: Self::Instant,
+        (space, addr): (Space, Address),
+        data: &[u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        if space.is_secondary() {
+            Ok(self.secondary.write_ext(now, addr, data, transaction)?)
+        } else {
+            self.primary.write_ext(now, addr, data, transaction)
+        }
+    }
+
+    #[inline]
+    fn read_timed(
+        &mut self,
+        now: Self::Instant,
+        (space, addr): (Space, Address),
+        data: &mut [u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        if space.is_secondary() {
+            Ok(self.secondary.read_timed(now, addr, data)?)
+        } else {
+            self.primary.read_timed(now, addr, data)
+        }
+    }
+
+    #[inline]
+    fn write_timed(
+        &mut self,
+        now: Self::Instant,
+        (space, addr): (Space, Address),
+        data: &[u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        if space.is_secondary() {
+            Ok(self.secondary.write_timed(now, addr, data)?)
+        } else {
+            self.primary.write_timed(now, addr, data)
+        }
+    }
+
+    #[inline]
+    fn peek(
+        &mut self,
+        now: Self::Instant,
+        (space, addr): (Space, Address),
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        if space.is_secondary() {
+            Ok(self.secondary.peek(now, addr, data)?)
+        } else {
+            self.primary.peek(now, addr, data)
+        }
+    }
+
+    #[inline]
+    fn poke(
+        &mut self,
+        now: Self::Instant,
+        (space, addr): (Space, Address),
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        if space.is_secondary() {
+            Ok(self.secondary.poke(now, addr, data)?)
+        } else {
+            self.primary.poke(now, addr, data)
+        }
+    }
+
+    #[inline]
+    fn read_modify_write(
+        &mut self,
+        now: Self::Instant,
+        (space, addr): (Space, Address),
+        data: &mut [u8],
+        modify: &mut dyn FnMut(&mut [u8]),
+    ) -> Result<usize, Self::Error> {
+        if space.is_secondary() {
+            Ok(self.secondary.read_modify_write(now, addr, data, modify)?)
+        } else {
+            self.primary.read_modify_write(now, addr, data, modify)
+        }
+    }
+}
+
+/// A wrapper for a bus with separate memory and I/O spaces, for use inside a CPU such as the Z80
+///
+/// The CPU can be written against any `BusAccess<IoAddress>`, such as a `SpaceRouter` with a
+/// memory bus and an I/O bus, and wrap the bus it's given in an `IoBus` while it executes.  The
+/// wrapper implements `BusAccess<Address>` for the memory space, so instructions and operands can
+/// be fetched with the `BusAccessExt` helpers using plain addresses, and I/O instructions can use
+/// `input()` and `output()`, or `in_u8()` and `out_u8()`, to access the I/O space
+pub struct IoBus<Address, Bus> {
+    /// The underlying object implementing `BusAccess` for both spaces
+    pub inner: Bus,
+    address: PhantomData<Address>,
+}
+
+impl<Address, Bus> IoBus<Address, Bus>
+where
+    Address: Copy,
+    Bus: BusAccess<IoAddress<Address>>,
+{
+    /// Construct a new wrapper for the given bus
+    pub fn new(inner: Bus) -> Self {
+        Self {
+            inner,
+            address: PhantomData,
+        }
+    }
+
+    /// Read from the given I/O port, such as for an IN instruction
+    #[inline]
+    pub fn input(
+        &mut self,
+        now: Bus::Instant,
+        port: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Bus::Error> {
+        self.inner.read(now, (MemOrIo::Io, port), data)
+    }
+
+    /// Write to the given I/O port, such as for an OUT instruction
+    #[inline]
+    pub fn output(
+        &mut self,
+        now: Bus::Instant,
+        port: Address,
+        data: &[u8],
+    ) -> Result<usize, Bus::Error> {
+        self.inner.write(now, (MemOrIo::Io, port), data)
+    }
+
+    /// Read a single byte from the given I/O port
+    #[inline]
+    pub fn in_u8(&mut self, now: Bus::Instant, port: Address) -> Result<u8, Bus::Error>
+    where
+        Bus::Error: From<ShortTransfer>,
+    {
+        self.inner.read_u8(now, (MemOrIo::Io, port))
+    }
+
+    /// Write a single byte to the given I/O port
+    #[inline]
+    pub fn out_u8(&mut self, now: Bus::Instant, port: Address, value: u8) -> Result<(), Bus::Error>
+    where
+        Bus::Error: From<ShortTransfer>,
+    {
+        self.inner.write_u8(now, (MemOrIo::Io, port), value)
+    }
+}
+
+/// Accesses the memory space of the wrapped bus
+impl<Address, Bus> BusAccess<Address> for IoBus<Address, Bus>
+where
+    Address: Copy,
+    Bus: BusAccess<IoAddress<Address>>,
+{
+    type Instant = Bus::Instant;
+    type Error = Bus::Error;
+
+    #[inline]
+    fn read(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        self.inner.read(now, (MemOrIo::Memory, addr), data)
+    }
+
+    #[inline]
+    fn write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        self.inner.write(now, (MemOrIo::Memory, addr), data)
+    }
+
+    #[inline]
+    fn peek(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        self.inner.peek(now, (MemOrIo::Memory, addr), data)
+    }
+
+    #[inline]
+    fn poke(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        self.inner.poke(now, (MemOrIo::Memory, addr), data)
+    }
+
+    #[inline]
+    fn read_modify_write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        modify: &mut dyn FnMut(&mut [u8]),
+    ) -> Result<usize, Self::Error> {
+        self.inner
+            .read_modify_write(now, (MemOrIo::Memory, addr), data, modify)
+    }
+
+    #[inline]
+    fn read_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        self.inner.read_timed(now, (MemOrIo::Memory, addr), data)
+    }
+
+    #[inline]
+    fn write_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        self.inner.write_timed(now, (MemOrIo::Memory, addr), data)
+    }
+
+    #[inline]
+    fn read_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        self.inner
+            .read_ext(now, (MemOrIo::Memory, addr), data, transaction)
+    }
+
+    #[inline]
+    fn write_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        self.inner
+            .write_ext(now, (MemOrIo::Memory, addr), data, transaction)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{AutoBusAdapter, BasicBusError, BusAccessExt};
+    use std::time::Duration;
+
+    #[test]
+    fn test_memory_and_io_spaces() {
+        let mut bus = SpaceRouter::new([0_u8; 0x100], [0_u8; 0x10]);
+        let now = Duration::ZERO;
+
+        bus.write_u8(now, (MemOrIo::Memory, 0x10), 0xAA).unwrap();
+        bus.write_u8(now, (MemOrIo::Io, 0x0F), 0x55).unwrap();
+        assert_eq!(bus.primary[0x10], 0xAA);
+        assert_eq!(bus.secondary[0x0F], 0x55);
+        assert_eq!(bus.read_u8(now, (MemOrIo::Io, 0x0F)).unwrap(), 0x55);
+    }
+
+    #[test]
+    fn test_io_bus() {
+        let now = Duration::ZERO;
+        let mut bus = IoBus::new(SpaceRouter::new([0_u8; 0x100], [0_u8; 0x10]));
+
+        // LD A,n / OUT (n),A / IN A,(n)
+        bus.write_beu16(now, 0x00, 0x3E42).unwrap();
+        let value = bus.read_u8(now, 0x01).unwrap();
+        bus.out_u8(now, 0x0C, value).unwrap();
+        assert_eq!(bus.in_u8(now, 0x0C).unwrap(), 0x42);
+        assert_eq!(bus.inner.primary[..2], [0x3E, 0x42]);
+        assert_eq!(bus.inner.secondary[0x0C], 0x42);
+        assert!(bus.input(now, 0x0C, &mut [0; 2]).is_ok());
+    }
+
+    #[test]
+    fn test_function_codes() {
+        assert_eq!(
+            FunctionCode::from_bits(6),
+            Some(FunctionCode::SupervisorProgram)
+        );
+        assert_eq!(FunctionCode::from_bits(3), None);
+        assert!(FunctionCode::SupervisorData.is_supervisor());
+        assert!(!FunctionCode::UserProgram.is_supervisor());
+        assert!(FunctionCode::UserProgram.is_program());
+
+        // memory can be connected directly to the cpu by dropping the function code
+        let mut memory: AutoBusAdapter<(FunctionCode, u16), u16, _, BasicBusError> =
+            AutoBusAdapter::new([0_u8; 0x100]);
+        let now = Duration::ZERO;
+        memory
+            .write_beu16(now, (FunctionCode::SupervisorData, 0x20), 0x4E71)
+            .unwrap();
+        assert_eq!(
+            memory
+                .read_beu16(now, (FunctionCode::UserProgram, 0x20))
+                .unwrap(),
+            0x4E71
+        );
+
+        // or interrupt acknowledges can be sent to a separate bus
+        let mut bus = SpaceRouter::new(memory.inner, [0x40_u8; 0x10]);
+        assert_eq!(
+            bus.read_beu16(now, (FunctionCode::SupervisorProgram, 0x20))
+                .unwrap(),
+            0x4E71
+        );
+        assert_eq!(
+            bus.read_u8(now, (FunctionCode::CpuSpace, 0x07)).unwrap(),
+            0x40
+        );
+    }
+}
@@ -3,6 +3,9 @@
 use core::fmt;
 
 use crate::bus::BusAccess;
+use crate::interrupt::Interrupt;
+use crate::registers::RegisterValue;
+use crate::time::Instant as EmuInstant;
 
 /// Represents a device that can change state with the passage of a clock signal
 ///
@@ -21,6 +24,19 @@ where
     /// Returns true if this device is still running.  This can be used to detect a stop or halt condition
     fn is_running(&mut self) -> bool;
 
+    /// Returns the power state of the device, which tells a scheduler whether to keep stepping it
+    ///
+    /// This gives more detail than `is_running()`, such as whether the device can be woken by an
+    /// interrupt.  The default implementation returns `RunState::Running` if `is_running()`
+    /// returns true, and `RunState::Stopped` otherwise
+    fn run_state(&mut self) -> RunState<Bus::Instant> {
+        if self.is_running() {
+            RunState::Running
+        } else {
+            RunState::Stopped
+        }
+    }
+
     /// Reset the device to its initial state, as if the device's reset signal was asserted
     fn reset(&mut self, now: Bus::Instant, bus: &mut Bus) -> Result<(), Self::Error>;
 
@@ -30,6 +46,128 @@ where
     /// next step should occur, according to the device itself.  The given bus can be used to access the system
     /// during this step of execution
     fn step(&mut self, now: Bus::Instant, bus: &mut Bus) -> Result<Bus::Instant, Self::Error>;
+
+    /// Step the device repeatedly from `start`, at the times it returns, until `duration` has passed
+    ///
+    /// Returns the time at which the device should be stepped again, which may be past the end
+    /// of the duration, and `StopReason::Halted` if the device stopped or started waiting for an
+    /// interrupt before the end.  A device that is halted until a later time isn't stepped
+    /// before then, as with the `Scheduler`.  If the device returns a time before the step it
+    /// was given, it's stepped again at the same time, so that simulated time never goes
+    /// backwards
+    #[allow(clippy::type_complexity)]
+    fn run_for(
+        &mut self,
+        start: Bus::Instant,
+        duration: <Bus::Instant as EmuInstant>::Duration,
+        bus: &mut Bus,
+    ) -> Result<(Bus::Instant, Option<StopReason<Address>>), Self::Error> {
+        let end = start + duration;
+        let mut now = start;
+        while now < end {
+            match self.run_state() {
+                RunState::Running => {}
+                RunState::Halted(until) if until > now => {
+                    now = until;
+                    continue;
+                }
+                RunState::Halted(_) => {}
+                RunState::WaitingForInterrupt | RunState::Stopped => {
+                    return Ok((now, Some(StopReason::Halted)));
+                }
+            }
+            now = self.step(now, bus)?.max(now);
+        }
+        Ok((now, None))
+    }
+}
+
+/// The power state of a device, as returned by `Step::run_state()`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RunState<Instant> {
+    /// The device is running, and should be stepped at the time returned by its last step
+    Running,
+    /// The device is paused until the given time, such as a CPU in a low power sleep with a
+    /// timeout, and shouldn't be stepped before then
+    Halted(Instant),
+    /// The device is idle until one of its interrupts is asserted, such as a CPU that executed a
+    /// `STOP` or `WFI` instruction, after which it will report that it's running again
+    WaitingForInterrupt,
+    /// The device has stopped, and won't run again until it's reset
+    Stopped,
+}
+
+impl<Instant> RunState<Instant> {
+    /// Returns true if the device should still be stepped, possibly after a delay
+    pub fn is_running(&self) -> bool {
+        matches!(self, RunState::Running | RunState::Halted(_))
+    }
+
+    /// Returns true if the device will start running again when an interrupt is asserted
+    pub fn wakes_on_interrupt(&self) -> bool {
+        matches!(self, RunState::WaitingForInterrupt)
+    }
+}
+
+/// A common event that a device can report from a step
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum StepEvent<Address> {
+    /// The device has stopped, such as a CPU executing a halt instruction
+    Halted,
+    /// A CPU reached a breakpoint at the given address, before executing the instruction there
+    Breakpoint(Address),
+    /// The device started requesting the given interrupt
+    InterruptRequested(Interrupt),
+    /// A video device finished drawing a frame
+    FrameComplete,
+}
+
+/// The outcome of a step that can report an event
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct StepResult<Instant, Event> {
+    /// The time at which the device should be stepped again
+    pub next: Instant,
+    /// The event that occurred during the step, if any
+    pub event: Option<Event>,
+}
+
+impl<Instant, Event> StepResult<Instant, Event> {
+    /// Construct a result for a step where nothing of note happened
+    pub fn next(next: Instant) -> Self {
+        Self { next, event: None }
+    }
+
+    /// Construct a result for a step where the given event happened
+    pub fn with_event(next: Instant, event: Event) -> Self {
+        Self {
+            next,
+            event: Some(event),
+        }
+    }
+}
+
+/// A device that can report events from a step, instead of having its state polled after each step
+///
+/// `Event` can be `StepEvent`, or a device-specific type for events that are only meaningful to a
+/// particular system.  Implementations of `Step::step()` would normally call `step_with_event()` and
+/// discard the event, so that the device can be used either way
+pub trait StepWithEvent<Address, Bus>: Step<Address, Bus>
+where
+    Address: Copy,
+    Bus: BusAccess<Address>,
+{
+    /// The type of an event reported by this device
+    type Event;
+
+    /// Step the process by one unit of time, and return the time at which it should be stepped again,
+    /// along with the event that occurred during the step, if any
+    fn step_with_event(
+        &mut self,
+        now: Bus::Instant,
+        bus: &mut Bus,
+    ) -> Result<StepResult<Bus::Instant, Self::Event>, Self::Error>;
 }
 
 // TODO should this depend on Step, which is the most common way it will be used, even though it technically could
@@ -62,6 +200,169 @@ where
     fn detailed_summary(&mut self, bus: &mut Bus, writer: &mut Writer) -> Result<(), Self::Error>;
 }
 
+/// The value of a single field of a device's state, as emitted by `InspectState`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum StateValue<'a> {
+    /// An unsigned value, such as a register or counter, sized to match it so that frontends
+    /// can display it with the right number of hex digits
+    Unsigned(RegisterValue),
+    /// A signed value
+    Signed(i64),
+    /// A single flag, such as a bit of a status register or whether a device is enabled
+    Flag(bool),
+    /// A value that can only be described as text, such as a state machine's current state
+    Text(&'a str),
+}
+
+impl<'a> From<u8> for StateValue<'a> {
+    fn from(value: u8) -> Self {
+        StateValue::Unsigned(value.into())
+    }
+}
+
+impl<'a> From<u16> for StateValue<'a> {
+    fn from(value: u16) -> Self {
+        StateValue::Unsigned(value.into())
+    }
+}
+
+impl<'a> From<u32> for StateValue<'a> {
+    fn from(value: u32) -> Self {
+        StateValue::Unsigned(value.into())
+    }
+}
+
+impl<'a> From<u64> for StateValue<'a> {
+    fn from(value: u64) -> Self {
+        StateValue::Unsigned(value.into())
+    }
+}
+
+impl<'a> From<i64> for StateValue<'a> {
+    fn from(value: i64) -> Self {
+        StateValue::Signed(value)
+    }
+}
+
+impl<'a> From<bool> for StateValue<'a> {
+    fn from(value: bool) -> Self {
+        StateValue::Flag(value)
+    }
+}
+
+impl<'a> From<&'a str> for StateValue<'a> {
+    fn from(value: &'a str) -> Self {
+        StateValue::Text(value)
+    }
+}
+
+/// Receives the fields of a device's state from `InspectState`
+///
+/// Fields can be organized into named groups, such as one for each channel of a sound chip,
+/// which a visitor that only needs a flat list of fields can ignore
+pub trait StateVisitor {
+    /// Receive the value of a single named field
+    fn field(&mut self, name: &str, value: StateValue<'_>);
+
+    /// Start a group of fields, which lasts until the matching call to `end_group()`
+    fn begin_group(&mut self, _name: &str) {}
+
+    /// End the most recently started group of fields
+    fn end_group(&mut self) {}
+}
+
+/// Inspect the state of a device as individual named fields, rather than as formatted text
+///
+/// This is the structured counterpart of `Inspect`, for frontends such as GUI debuggers that
+/// display each register, flag, or counter in its own widget, and which would otherwise need to
+/// parse the text written by `Inspect`
+pub trait InspectState<Address, Bus>
+where
+    Address: Copy,
+    Bus: BusAccess<Address>,
+{
+    /// A type that is returned if the state cannot be inspected
+    type Error;
+
+    /// Emit each field of the device's current state to the given visitor, or return an error
+    fn inspect_state(
+        &mut self,
+        bus: &mut Bus,
+        visitor: &mut dyn StateVisitor,
+    ) -> Result<(), Self::Error>;
+}
+
+/// The size and location of an instruction decoded by `Disassemble`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Disassembly<Address> {
+    /// The number of bytes the instruction occupies
+    pub length: usize,
+    /// The address of the instruction that follows this one in memory
+    pub next: Address,
+}
+
+/// Decode the instructions of a CPU device into text, for debugger views and trace listings
+///
+/// Instructions should be read from the bus with `peek()`, so that disassembling doesn't trigger
+/// any side effects in the devices on the bus or change the state of the CPU
+pub trait Disassemble<Address, Bus, Writer>
+where
+    Address: Copy,
+    Bus: BusAccess<Address>,
+    Writer: fmt::Write,
+{
+    /// A type that is returned if the instruction cannot be decoded or written
+    type Error;
+
+    /// Decode the instruction at `addr`, and write its mnemonic and operands to the given writer
+    fn disassemble(
+        &mut self,
+        now: Bus::Instant,
+        addr: Address,
+        bus: &mut Bus,
+        writer: &mut Writer,
+    ) -> Result<Disassembly<Address>, Self::Error>;
+
+    /// Write `count` instructions starting at `addr`, one per line preceded by its address, and
+    /// return the address of the instruction after the last one
+    fn disassemble_listing(
+        &mut self,
+        now: Bus::Instant,
+        addr: Address,
+        count: usize,
+        bus: &mut Bus,
+        writer: &mut Writer,
+    ) -> Result<Address, Self::Error>
+    where
+        Address: fmt::LowerHex,
+        Self::Error: From<fmt::Error>,
+    {
+        let mut addr = addr;
+        for _ in 0..count {
+            write!(writer, "{:08x}: ", addr)?;
+            addr = self.disassemble(now, addr, bus, writer)?.next;
+            writeln!(writer)?;
+        }
+        Ok(addr)
+    }
+}
+
+/// The reason a CPU device stopped during a step, as reported by `Debug::take_stop_reason()`
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum StopReason<Address> {
+    /// Execution reached a breakpoint at the given address, before executing the instruction there
+    Breakpoint(Address),
+    /// An instruction accessed the given watched address
+    Watchpoint(Address),
+    /// The device has stopped running, such as a CPU executing a halt instruction
+    Halted,
+    /// The CPU took a trap or exception with the given vector number, such as a debug trap
+    /// instruction
+    Trap(u32),
+}
+
 /// Control the execution of a CPU device for debugging purposes
 pub trait Debug<Address, Bus, Writer>: Inspect<Address, Bus, Writer> + Step<Address, Bus>
 where
@@ -83,14 +384,57 @@ where
     fn remove_breakpoint(&mut self, address: Address);
     /// Clear all breakpoints
     fn clear_breakpoints(&mut self);
+
+    /// Returns the reason the device stopped during the most recent step, if it did, and clears it
+    ///
+    /// The default implementation never reports a reason, which is suitable for devices that
+    /// don't check breakpoints themselves.  A device that has stopped running or is waiting for
+    /// an interrupt is reported as `Halted` by `run_until_stop()` without needing to implement
+    /// this
+    fn take_stop_reason(&mut self) -> Option<StopReason<Address>> {
+        None
+    }
+
+    /// Step the device until it reports a reason to stop, or until `limit` steps have been taken
+    ///
+    /// Returns the time at which the device should be stepped again, and the reason it stopped,
+    /// which is `None` if the limit was reached first.  A device that is halted until a later
+    /// time is next stepped at that time
+    #[allow(clippy::type_complexity)]
+    fn run_until_stop(
+        &mut self,
+        now: Bus::Instant,
+        bus: &mut Bus,
+        limit: usize,
+    ) -> Result<(Bus::Instant, Option<StopReason<Address>>), <Self as Step<Address, Bus>>::Error>
+    {
+        let mut now = now;
+        for _ in 0..limit {
+            match self.run_state() {
+                RunState::Running => {}
+                RunState::Halted(until) if until > now => now = until,
+                RunState::Halted(_) => {}
+                RunState::WaitingForInterrupt | RunState::Stopped => {
+                    return Ok((now, Some(StopReason::Halted)));
+                }
+            }
+            now = self.step(now, bus)?;
+            if let Some(reason) = self.take_stop_reason() {
+                return Ok((now, Some(reason)));
+            }
+        }
+        Ok((now, None))
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::BusAccessExt;
 
     use crate::time::Instant;
-    use crate::{BasicBusError, BusAdapter, ErrorType};
+    use crate::{BasicBusError, BusAdapter, ErrorType, ShortTransfer};
+    use std::fmt::Write;
     use std::ops::Range;
     use std::str;
     use std::time::Duration;
@@ -108,6 +452,12 @@ mod test {
         }
     }
 
+    impl From<ShortTransfer> for Error {
+        fn from(_err: ShortTransfer) -> Self {
+            Error::BusError
+        }
+    }
+
     struct Memory(Vec<u8>);
 
     impl BusAccess<u32> for Memory {
@@ -132,6 +482,12 @@ mod test {
         }
     }
 
+    impl From<fmt::Error> for Error {
+        fn from(_err: fmt::Error) -> Self {
+            Error::BusError
+        }
+    }
+
     #[derive(Clone, Debug)]
     enum OutputError {
         Utf8Error,
@@ -206,11 +562,10 @@ mod test {
         }
     }
 
+    type BoxedDevice = Box<dyn BusAccess<u64, Instant = Duration, Error = Error>>;
+
     struct DynamicBus {
-        devices: Vec<(
-            Range<u64>,
-            Box<dyn BusAccess<u64, Instant = Duration, Error = Error>>,
-        )>,
+        devices: Vec<(Range<u64>, BoxedDevice)>,
     }
 
     impl BusAccess<u64> for DynamicBus {
@@ -251,6 +606,7 @@ mod test {
     impl<Bus> Step<u64, Bus> for Cpu
     where
         Bus: BusAccess<u64, Instant = Duration>,
+        Bus::Error: From<ShortTransfer>,
         Error: From<Bus::Error>,
     {
         type Error = Error;
@@ -280,6 +636,234 @@ mod test {
         }
     }
 
+    impl<Bus> InspectState<u64, Bus> for Cpu
+    where
+        Bus: BusAccess<u64, Instant = Duration>,
+    {
+        type Error = Error;
+
+        fn inspect_state(
+            &mut self,
+            _bus: &mut Bus,
+            visitor: &mut dyn StateVisitor,
+        ) -> Result<(), Self::Error> {
+            visitor.field("pc", self.pc.into());
+            visitor.begin_group("alu");
+            visitor.field("sum", self.sum.into());
+            visitor.end_group();
+            visitor.field("running", self.running.into());
+            Ok(())
+        }
+    }
+
+    impl<Bus> Disassemble<u64, Bus, String> for Cpu
+    where
+        Bus: BusAccess<u64, Instant = Duration>,
+        Error: From<Bus::Error>,
+    {
+        type Error = Error;
+
+        fn disassemble(
+            &mut self,
+            now: Duration,
+            addr: u64,
+            bus: &mut Bus,
+            writer: &mut String,
+        ) -> Result<Disassembly<u64>, Self::Error> {
+            let mut data = [0; 4];
+            bus.peek(now, addr, &mut data)?;
+            match u32::from_be_bytes(data) {
+                0 => write!(writer, "halt")?,
+                value => write!(writer, "add #{:#x}", value)?,
+            }
+            Ok(Disassembly {
+                length: 4,
+                next: addr + 4,
+            })
+        }
+    }
+
+    /// Collects the fields of a device's state as a list of names and values
+    #[derive(Default)]
+    struct Fields {
+        group: Option<String>,
+        fields: Vec<(String, String)>,
+    }
+
+    impl StateVisitor for Fields {
+        fn field(&mut self, name: &str, value: StateValue<'_>) {
+            let name = match &self.group {
+                Some(group) => format!("{}.{}", group, name),
+                None => name.to_string(),
+            };
+            let value = match value {
+                StateValue::Unsigned(value) => format!("{:#x}", value.as_u64()),
+                StateValue::Flag(flag) => flag.to_string(),
+                value => format!("{:?}", value),
+            };
+            self.fields.push((name, value));
+        }
+
+        fn begin_group(&mut self, name: &str) {
+            self.group = Some(name.to_string());
+        }
+
+        fn end_group(&mut self) {
+            self.group = None;
+        }
+    }
+
+    #[test]
+    fn test_inspect_state() {
+        let mut bus = Memory(vec![0; 16]);
+        let mut bus = BusAdapter::<u64, u32, _, Error>::new(&mut bus, |addr| addr as u32);
+        let mut cpu = Cpu {
+            pc: 0x40,
+            sum: 18,
+            running: true,
+        };
+
+        let mut fields = Fields::default();
+        cpu.inspect_state(&mut bus, &mut fields).unwrap();
+        let fields: Vec<(&str, &str)> = fields
+            .fields
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        assert_eq!(
+            fields,
+            [("pc", "0x40"), ("alu.sum", "0x12"), ("running", "true")]
+        );
+    }
+
+    #[test]
+    fn test_disassemble() {
+        let mut bus = Memory(vec![0; 16]);
+        bus.write_beu32(Duration::ZERO, 4, 0x20).unwrap();
+        let mut bus = BusAdapter::<u64, u32, _, Error>::new(&mut bus, |addr| addr as u32);
+        let mut cpu = Cpu::default();
+
+        let mut text = String::new();
+        let instruction = cpu
+            .disassemble(Duration::ZERO, 4, &mut bus, &mut text)
+            .unwrap();
+        assert_eq!(instruction, Disassembly { length: 4, next: 8 });
+        assert_eq!(text, "add #0x20");
+
+        let mut listing = String::new();
+        let next = cpu
+            .disassemble_listing(Duration::ZERO, 4, 2, &mut bus, &mut listing)
+            .unwrap();
+        assert_eq!(next, 12);
+        assert_eq!(listing, "00000004: add #0x20\n00000008: halt\n");
+        assert_eq!(cpu.pc, 0);
+    }
+
+    /// A device which asks to be stepped in the past every other step
+    struct Stubborn {
+        steps: usize,
+    }
+
+    impl Step<u64, Memory64> for Stubborn {
+        type Error = Error;
+
+        fn is_running(&mut self) -> bool {
+            self.steps < 10
+        }
+
+        fn reset(&mut self, _now: Duration, _bus: &mut Memory64) -> Result<(), Self::Error> {
+            self.steps = 0;
+            Ok(())
+        }
+
+        fn step(&mut self, now: Duration, _bus: &mut Memory64) -> Result<Duration, Self::Error> {
+            self.steps += 1;
+            if self.steps % 2 == 0 {
+                Ok(now.saturating_sub(Duration::from_micros(5)))
+            } else {
+                Ok(now + Duration::from_micros(3))
+            }
+        }
+    }
+
+    type Memory64 = BusAdapter<u64, u32, Memory, Error>;
+
+    #[test]
+    fn test_run_for() {
+        let mut bus = Memory64::new(Memory(vec![]), |addr| addr as u32);
+        let mut device = Stubborn { steps: 0 };
+
+        let (now, reason) = device
+            .run_for(Duration::ZERO, Duration::from_micros(7), &mut bus)
+            .unwrap();
+        assert_eq!((now, reason), (Duration::from_micros(9), None));
+        assert_eq!(device.steps, 5);
+
+        let (now, reason) = device
+            .run_for(now, Duration::from_secs(1), &mut bus)
+            .unwrap();
+        assert_eq!(
+            (now, reason),
+            (Duration::from_micros(15), Some(StopReason::Halted))
+        );
+    }
+
+    /// A device which sleeps for a while after its first step, and then waits for an interrupt
+    struct Sleeper {
+        state: RunState<Duration>,
+        steps: Vec<Duration>,
+    }
+
+    impl Step<u64, Memory64> for Sleeper {
+        type Error = Error;
+
+        fn is_running(&mut self) -> bool {
+            self.state.is_running()
+        }
+
+        fn run_state(&mut self) -> RunState<Duration> {
+            self.state
+        }
+
+        fn reset(&mut self, _now: Duration, _bus: &mut Memory64) -> Result<(), Self::Error> {
+            self.state = RunState::Running;
+            Ok(())
+        }
+
+        fn step(&mut self, now: Duration, _bus: &mut Memory64) -> Result<Duration, Self::Error> {
+            self.steps.push(now);
+            self.state = match self.state {
+                RunState::Running => RunState::Halted(now + Duration::from_micros(10)),
+                _ => RunState::WaitingForInterrupt,
+            };
+            Ok(now + Duration::from_micros(1))
+        }
+    }
+
+    #[test]
+    fn test_run_for_sleeping_device() {
+        let mut bus = Memory64::new(Memory(vec![]), |addr| addr as u32);
+        let mut device = Sleeper {
+            state: RunState::Running,
+            steps: vec![],
+        };
+
+        let (now, reason) = device
+            .run_for(Duration::ZERO, Duration::from_micros(5), &mut bus)
+            .unwrap();
+        assert_eq!((now, reason), (Duration::from_micros(10), None));
+        assert_eq!(device.steps, [Duration::ZERO]);
+
+        let (now, reason) = device
+            .run_for(now, Duration::from_micros(20), &mut bus)
+            .unwrap();
+        assert_eq!(
+            (now, reason),
+            (Duration::from_micros(11), Some(StopReason::Halted))
+        );
+        assert_eq!(device.steps, [Duration::ZERO, Duration::from_micros(10)]);
+    }
+
     #[test]
     fn test_static_system() {
         let memory = Memory(vec![0; 1024]);
@@ -291,7 +875,7 @@ mod test {
 
         let location = 0x100;
         bus.memory
-            .write_beu32(Duration::START, 0x0000, location as u32)
+            .write_beu32(Duration::START, 0x0000, location)
             .unwrap();
 
         for i in 0..100 {
@@ -320,6 +904,185 @@ mod test {
         assert_eq!(cpu.sum, 5050);
     }
 
+    /// A cpu which sums values like `Cpu`, and reports breakpoints and halting as events
+    struct EventCpu {
+        cpu: Cpu,
+        breakpoint: u64,
+        stop: Option<StopReason<u64>>,
+    }
+
+    impl<Bus> Step<u64, Bus> for EventCpu
+    where
+        Bus: BusAccess<u64, Instant = Duration>,
+        Bus::Error: From<ShortTransfer>,
+        Error: From<Bus::Error>,
+    {
+        type Error = Error;
+
+        fn is_running(&mut self) -> bool {
+            Step::<u64, Bus>::is_running(&mut self.cpu)
+        }
+
+        fn reset(&mut self, now: Duration, bus: &mut Bus) -> Result<(), Self::Error> {
+            self.cpu.reset(now, bus)
+        }
+
+        fn step(&mut self, now: Duration, bus: &mut Bus) -> Result<Duration, Self::Error> {
+            let result = self.step_with_event(now, bus)?;
+            if let Some(StepEvent::Breakpoint(addr)) = result.event {
+                self.stop = Some(StopReason::Breakpoint(addr));
+            }
+            Ok(result.next)
+        }
+    }
+
+    impl<Bus> StepWithEvent<u64, Bus> for EventCpu
+    where
+        Bus: BusAccess<u64, Instant = Duration>,
+        Bus::Error: From<ShortTransfer>,
+        Error: From<Bus::Error>,
+    {
+        type Event = StepEvent<u64>;
+
+        fn step_with_event(
+            &mut self,
+            now: Duration,
+            bus: &mut Bus,
+        ) -> Result<StepResult<Duration, Self::Event>, Self::Error> {
+            if self.cpu.pc == self.breakpoint {
+                self.breakpoint = u64::MAX;
+                return Ok(StepResult::with_event(
+                    now,
+                    StepEvent::Breakpoint(self.cpu.pc),
+                ));
+            }
+            let next = self.cpu.step(now, bus)?;
+            if Step::<u64, Bus>::is_running(&mut self.cpu) {
+                Ok(StepResult::next(next))
+            } else {
+                Ok(StepResult::with_event(next, StepEvent::Halted))
+            }
+        }
+    }
+
+    #[test]
+    fn test_step_events() {
+        let mut memory = Memory(vec![0; 0x100]);
+        memory.write_beu32(Duration::START, 0x0000, 0x10).unwrap();
+        for (i, value) in [5, 6, 7].iter().enumerate() {
+            memory
+                .write_beu32(Duration::START, 0x10 + 4 * i as u32, *value)
+                .unwrap();
+        }
+        let mut bus = BusAdapter::<u64, u32, _, Error>::new(memory, |addr| addr as u32);
+
+        let mut cpu = EventCpu {
+            cpu: Cpu::default(),
+            breakpoint: 0x18,
+            stop: None,
+        };
+        cpu.reset(Duration::START, &mut bus).unwrap();
+
+        let mut now = Duration::START;
+        let mut events = vec![];
+        loop {
+            let result = cpu.step_with_event(now, &mut bus).unwrap();
+            now = result.next;
+            match result.event {
+                Some(StepEvent::Halted) => break,
+                Some(event) => events.push((event, cpu.cpu.sum)),
+                None => {}
+            }
+        }
+        assert_eq!(events, vec![(StepEvent::Breakpoint(0x18), 11)]);
+        assert_eq!(cpu.cpu.sum, 18);
+        assert_eq!(now, Duration::from_nanos(400));
+    }
+
+    impl<Bus> Inspect<u64, Bus, String> for EventCpu
+    where
+        Bus: BusAccess<u64, Instant = Duration>,
+        Error: From<Bus::Error>,
+    {
+        type InfoType = ();
+        type Error = fmt::Error;
+
+        fn inspect(&mut self, _info: (), _bus: &mut Bus, _writer: &mut String) -> fmt::Result {
+            Ok(())
+        }
+
+        fn brief_summary(&mut self, _bus: &mut Bus, writer: &mut String) -> fmt::Result {
+            write!(writer, "pc: {:#x}", self.cpu.pc)
+        }
+
+        fn detailed_summary(&mut self, bus: &mut Bus, writer: &mut String) -> fmt::Result {
+            self.brief_summary(bus, writer)
+        }
+    }
+
+    impl<Bus> Debug<u64, Bus, String> for EventCpu
+    where
+        Bus: BusAccess<u64, Instant = Duration>,
+        Bus::Error: From<ShortTransfer>,
+        Error: From<Bus::Error>,
+    {
+        type DebugError = Error;
+
+        fn get_execution_address(&mut self) -> Result<u64, Self::DebugError> {
+            Ok(self.cpu.pc)
+        }
+
+        fn set_execution_address(&mut self, address: u64) -> Result<(), Self::DebugError> {
+            self.cpu.pc = address;
+            Ok(())
+        }
+
+        fn add_breakpoint(&mut self, address: u64) {
+            self.breakpoint = address;
+        }
+
+        fn remove_breakpoint(&mut self, _address: u64) {
+            self.breakpoint = u64::MAX;
+        }
+
+        fn clear_breakpoints(&mut self) {
+            self.breakpoint = u64::MAX;
+        }
+
+        fn take_stop_reason(&mut self) -> Option<StopReason<u64>> {
+            self.stop.take()
+        }
+    }
+
+    #[test]
+    fn test_run_until_stop() {
+        let mut memory = Memory(vec![0; 0x100]);
+        memory.write_beu32(Duration::START, 0x0000, 0x10).unwrap();
+        for (i, value) in [5, 6, 7].iter().enumerate() {
+            memory
+                .write_beu32(Duration::START, 0x10 + 4 * i as u32, *value)
+                .unwrap();
+        }
+        let mut bus = BusAdapter::<u64, u32, _, Error>::new(memory, |addr| addr as u32);
+
+        let mut cpu = EventCpu {
+            cpu: Cpu::default(),
+            breakpoint: 0x18,
+            stop: None,
+        };
+        cpu.reset(Duration::START, &mut bus).unwrap();
+
+        let (now, reason) = cpu.run_until_stop(Duration::START, &mut bus, 1).unwrap();
+        assert_eq!(reason, None);
+        let (now, reason) = cpu.run_until_stop(now, &mut bus, 100).unwrap();
+        assert_eq!(reason, Some(StopReason::Breakpoint(0x18)));
+        assert_eq!(cpu.cpu.sum, 11);
+
+        let (_, reason) = cpu.run_until_stop(now, &mut bus, 100).unwrap();
+        assert_eq!(reason, Some(StopReason::Halted));
+        assert_eq!(cpu.cpu.sum, 18);
+    }
+
     #[cfg(feature = "alloc")]
     #[test]
     fn test_dynamic_system() {
@@ -341,7 +1104,7 @@ mod test {
 
         let mut cpu = Cpu::default();
 
-        let location = 0x100 as u64;
+        let location = 0x100_u64;
         bus.write_beu32(Duration::START, 0x0000, location as u32)
             .unwrap();
 
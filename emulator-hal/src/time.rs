@@ -1,7 +1,7 @@
 //! Traits and implementations for coordinating time between emulated components
 
 use core::fmt::Debug;
-use core::ops::{Add, Mul};
+use core::ops::{Add, Mul, Sub};
 use core::time::Duration;
 
 /// Represents a monotonic instant in time
@@ -14,6 +14,34 @@ pub trait Instant: Add<Self::Duration, Output = Self> + Eq + Ord + Debug + Copy
 
     /// Returns the duration of one period of the given frequency is hertz
     fn hertz_to_duration(hertz: u64) -> Self::Duration;
+
+    /// Returns the amount of time from `earlier` to this instant
+    ///
+    /// If `earlier` is later than this instant, a zero duration is returned
+    fn duration_since(&self, earlier: Self) -> Self::Duration;
+
+    /// Returns the amount of time from `earlier` to this instant, or `None` if `earlier` is later
+    /// than this instant
+    fn checked_duration_since(&self, earlier: Self) -> Option<Self::Duration> {
+        if earlier <= *self {
+            Some(self.duration_since(earlier))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the instant that is `duration` before this instant, or `None` if it would be
+    /// before `START` or can't otherwise be represented
+    fn checked_sub(&self, duration: Self::Duration) -> Option<Self>;
+
+    /// Returns the duration of `cycles` periods of the given frequency in hertz
+    ///
+    /// The default implementation multiplies the duration returned by `hertz_to_duration()`,
+    /// which rounds each period separately, so implementations should override this to only
+    /// round the total
+    fn cycles_to_duration(hertz: u64, cycles: u32) -> Self::Duration {
+        Self::hertz_to_duration(hertz) * cycles
+    }
 }
 
 /*
@@ -38,6 +66,103 @@ impl Instant for Duration {
     fn hertz_to_duration(hertz: u64) -> Self::Duration {
         Duration::from_nanos(1_000_000_000 / hertz)
     }
+
+    fn duration_since(&self, earlier: Self) -> Self::Duration {
+        self.saturating_sub(earlier)
+    }
+
+    fn checked_sub(&self, duration: Self::Duration) -> Option<Self> {
+        Duration::checked_sub(*self, duration)
+    }
+
+    fn cycles_to_duration(hertz: u64, cycles: u32) -> Self::Duration {
+        let nanos = cycles as u128 * 1_000_000_000 / hertz as u128;
+        Duration::new(
+            (nanos / 1_000_000_000) as u64,
+            (nanos % 1_000_000_000) as u32,
+        )
+    }
+}
+
+/// An instant measured in cycles of a master clock running at `MASTER_HZ`
+///
+/// This is for emulators that reason purely in clock cycles, where converting through a
+/// `Duration` would add rounding errors.  The frequency of the master clock is only used by
+/// `hertz_to_duration()`, which returns the number of master clock cycles in one period of the
+/// given frequency, rounded down, so clocks derived from the master clock should divide it
+/// evenly
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CycleInstant<const MASTER_HZ: u64>(pub u64);
+
+/// A number of cycles of a master clock, which can be added to a `CycleInstant`
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Cycles(pub u64);
+
+impl<const MASTER_HZ: u64> CycleInstant<MASTER_HZ> {
+    /// Returns the number of cycles since `START` as a `Duration`, rounded down to the
+    /// nearest nanosecond
+    pub fn as_duration(&self) -> Duration {
+        let nanos = self.0 as u128 * 1_000_000_000 / MASTER_HZ as u128;
+        Duration::new(
+            (nanos / 1_000_000_000) as u64,
+            (nanos % 1_000_000_000) as u32,
+        )
+    }
+}
+
+impl<const MASTER_HZ: u64> Instant for CycleInstant<MASTER_HZ> {
+    const START: Self = CycleInstant(0);
+
+    type Duration = Cycles;
+
+    fn hertz_to_duration(hertz: u64) -> Self::Duration {
+        Cycles(MASTER_HZ / hertz)
+    }
+
+    fn duration_since(&self, earlier: Self) -> Self::Duration {
+        Cycles(self.0.saturating_sub(earlier.0))
+    }
+
+    fn checked_sub(&self, duration: Self::Duration) -> Option<Self> {
+        self.0.checked_sub(duration.0).map(CycleInstant)
+    }
+
+    fn cycles_to_duration(hertz: u64, cycles: u32) -> Self::Duration {
+        Cycles((MASTER_HZ as u128 * cycles as u128 / hertz as u128) as u64)
+    }
+}
+
+impl<const MASTER_HZ: u64> Add<Cycles> for CycleInstant<MASTER_HZ> {
+    type Output = Self;
+
+    fn add(self, rhs: Cycles) -> Self::Output {
+        CycleInstant(self.0 + rhs.0)
+    }
+}
+
+/// Subtracting a later instant saturates to zero cycles, the same as `duration_since()`
+impl<const MASTER_HZ: u64> Sub for CycleInstant<MASTER_HZ> {
+    type Output = Cycles;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Cycles(self.0.saturating_sub(rhs.0))
+    }
+}
+
+impl Add for Cycles {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Cycles(self.0 + rhs.0)
+    }
+}
+
+impl Mul<u32> for Cycles {
+    type Output = Self;
+
+    fn mul(self, rhs: u32) -> Self::Output {
+        Cycles(self.0 * rhs as u64)
+    }
 }
 
 #[cfg(feature = "fugit")]
@@ -52,6 +177,26 @@ where
     fn hertz_to_duration(hertz: u64) -> Self::Duration {
         fugit::Duration::<u32, NOM, DENOM>::from_ticks(DENOM / hertz as u32)
     }
+
+    fn duration_since(&self, earlier: Self) -> Self::Duration {
+        self.checked_duration_since(earlier)
+            .unwrap_or(fugit::Duration::<u32, NOM, DENOM>::from_ticks(0))
+    }
+
+    fn checked_duration_since(&self, earlier: Self) -> Option<Self::Duration> {
+        fugit::Instant::<u32, NOM, DENOM>::checked_duration_since(*self, earlier)
+    }
+
+    fn checked_sub(&self, duration: Self::Duration) -> Option<Self> {
+        self.ticks()
+            .checked_sub(duration.ticks())
+            .map(fugit::Instant::<u32, NOM, DENOM>::from_ticks)
+    }
+
+    fn cycles_to_duration(hertz: u64, cycles: u32) -> Self::Duration {
+        let ticks = DENOM as u64 * cycles as u64 / (NOM as u64 * hertz);
+        fugit::Duration::<u32, NOM, DENOM>::from_ticks(ticks as u32)
+    }
 }
 
 #[cfg(feature = "fugit")]
@@ -66,6 +211,26 @@ where
     fn hertz_to_duration(hertz: u64) -> Self::Duration {
         fugit::Duration::<u64, NOM, DENOM>::from_ticks(DENOM as u64 / hertz)
     }
+
+    fn duration_since(&self, earlier: Self) -> Self::Duration {
+        self.checked_duration_since(earlier)
+            .unwrap_or(fugit::Duration::<u64, NOM, DENOM>::from_ticks(0))
+    }
+
+    fn checked_duration_since(&self, earlier: Self) -> Option<Self::Duration> {
+        fugit::Instant::<u64, NOM, DENOM>::checked_duration_since(*self, earlier)
+    }
+
+    fn checked_sub(&self, duration: Self::Duration) -> Option<Self> {
+        self.ticks()
+            .checked_sub(duration.ticks())
+            .map(fugit::Instant::<u64, NOM, DENOM>::from_ticks)
+    }
+
+    fn cycles_to_duration(hertz: u64, cycles: u32) -> Self::Duration {
+        let ticks = DENOM as u128 * cycles as u128 / (NOM as u128 * hertz as u128);
+        fugit::Duration::<u64, NOM, DENOM>::from_ticks(ticks as u64)
+    }
 }
 
 #[cfg(feature = "femtos")]
@@ -77,7 +242,432 @@ impl Instant for femtos::Instant {
     fn hertz_to_duration(hertz: u64) -> Self::Duration {
         femtos::Duration::from_femtos(1_000_000_000_000_000 / hertz as femtos::Femtos)
     }
+
+    fn duration_since(&self, earlier: Self) -> Self::Duration {
+        self.checked_duration_since(earlier)
+            .unwrap_or(femtos::Duration::ZERO)
+    }
+
+    fn checked_duration_since(&self, earlier: Self) -> Option<Self::Duration> {
+        self.as_duration().checked_sub(earlier.as_duration())
+    }
+
+    fn checked_sub(&self, duration: Self::Duration) -> Option<Self> {
+        femtos::Instant::checked_sub(*self, duration)
+    }
+
+    fn cycles_to_duration(hertz: u64, cycles: u32) -> Self::Duration {
+        femtos::Duration::from_femtos(
+            1_000_000_000_000_000 * cycles as femtos::Femtos / hertz as femtos::Femtos,
+        )
+    }
+}
+
+/// Converts instants and durations of one `Instant` type into another
+///
+/// This allows components written with different time types, such as a CPU using
+/// `femtos::Instant` and a peripheral using `Duration`, to be connected with `InstantAdapter`.
+/// It's implemented between every pair of `Duration`, `CycleInstant`, and, when their features
+/// are enabled, `femtos::Instant` and `fugit::Instant`.  The conversion is exact when the
+/// target type can represent the value, otherwise it's rounded down to the target's resolution,
+/// such as to the nearest nanosecond for `Duration`.  Values too large for the target type
+/// saturate at its maximum value
+pub trait ConvertInstant<Other>: Instant
+where
+    Other: Instant,
+{
+    /// Returns the same instant as this one, represented as an `Other`
+    fn convert_instant(self) -> Other;
+
+    /// Returns the same length of time as `duration`, represented as an `Other::Duration`
+    fn convert_duration(duration: Self::Duration) -> Other::Duration;
+}
+
+impl<I, T> ConvertInstant<T> for I
+where
+    I: ticks::Ticks,
+    T: ticks::Ticks,
+{
+    #[inline]
+    fn convert_instant(self) -> T {
+        T::from_instant_ticks(ticks::rescale::<I, T>(self.instant_ticks()))
+    }
+
+    #[inline]
+    fn convert_duration(duration: Self::Duration) -> T::Duration {
+        T::from_duration_ticks(ticks::rescale::<I, T>(I::duration_ticks(&duration)))
+    }
+}
+
+mod ticks {
+    use super::{CycleInstant, Cycles, Duration, Instant};
+
+    /// An `Instant` type that counts ticks of a fixed fraction of a second
+    pub trait Ticks: Instant {
+        /// The length of one tick in seconds, as a numerator and denominator
+        const TICK: (u128, u128);
+
+        fn instant_ticks(&self) -> u128;
+        fn from_instant_ticks(ticks: u128) -> Self;
+        fn duration_ticks(duration: &Self::Duration) -> u128;
+        fn from_duration_ticks(ticks: u128) -> Self::Duration;
+    }
+
+    /// Returns the number of `T` ticks in the given number of `I` ticks, rounded down
+    pub fn rescale<I: Ticks, T: Ticks>(ticks: u128) -> u128 {
+        let mul = I::TICK.0 * T::TICK.1;
+        let div = I::TICK.1 * T::TICK.0;
+        // split the value so that the multiplication doesn't overflow for large values
+        (ticks / div)
+            .saturating_mul(mul)
+            .saturating_add((ticks % div).saturating_mul(mul) / div)
+    }
+
+    fn duration_from_nanos(nanos: u128) -> Duration {
+        let secs = nanos / 1_000_000_000;
+        if secs > u64::MAX as u128 {
+            Duration::MAX
+        } else {
+            Duration::new(secs as u64, (nanos % 1_000_000_000) as u32)
+        }
+    }
+
+    impl Ticks for Duration {
+        const TICK: (u128, u128) = (1, 1_000_000_000);
+
+        fn instant_ticks(&self) -> u128 {
+            self.as_nanos()
+        }
+
+        fn from_instant_ticks(ticks: u128) -> Self {
+            duration_from_nanos(ticks)
+        }
+
+        fn duration_ticks(duration: &Self::Duration) -> u128 {
+            duration.as_nanos()
+        }
+
+        fn from_duration_ticks(ticks: u128) -> Self::Duration {
+            duration_from_nanos(ticks)
+        }
+    }
+
+    impl<const MASTER_HZ: u64> Ticks for CycleInstant<MASTER_HZ> {
+        const TICK: (u128, u128) = (1, MASTER_HZ as u128);
+
+        fn instant_ticks(&self) -> u128 {
+            self.0 as u128
+        }
+
+        fn from_instant_ticks(ticks: u128) -> Self {
+            CycleInstant(ticks.min(u64::MAX as u128) as u64)
+        }
+
+        fn duration_ticks(duration: &Self::Duration) -> u128 {
+            duration.0 as u128
+        }
+
+        fn from_duration_ticks(ticks: u128) -> Self::Duration {
+            Cycles(ticks.min(u64::MAX as u128) as u64)
+        }
+    }
+
+    // `Femtos` is a `u64` on some targets, so the casts aren't always to the same type
+    #[cfg(feature = "femtos")]
+    #[allow(clippy::unnecessary_cast)]
+    impl Ticks for femtos::Instant {
+        const TICK: (u128, u128) = (1, 1_000_000_000_000_000);
+
+        fn instant_ticks(&self) -> u128 {
+            self.as_duration().as_femtos() as u128
+        }
+
+        fn from_instant_ticks(ticks: u128) -> Self {
+            femtos::Instant::START + Self::from_duration_ticks(ticks)
+        }
+
+        fn duration_ticks(duration: &Self::Duration) -> u128 {
+            duration.as_femtos() as u128
+        }
+
+        fn from_duration_ticks(ticks: u128) -> Self::Duration {
+            femtos::Duration::from_femtos(ticks.min(femtos::Femtos::MAX as u128) as femtos::Femtos)
+        }
+    }
+
+    #[cfg(feature = "fugit")]
+    impl<const NOM: u32, const DENOM: u32> Ticks for fugit::Instant<u32, NOM, DENOM>
+    where
+        Self: Instant<Duration = fugit::Duration<u32, NOM, DENOM>>,
+    {
+        const TICK: (u128, u128) = (NOM as u128, DENOM as u128);
+
+        fn instant_ticks(&self) -> u128 {
+            self.ticks() as u128
+        }
+
+        fn from_instant_ticks(ticks: u128) -> Self {
+            Self::from_ticks(ticks.min(u32::MAX as u128) as u32)
+        }
+
+        fn duration_ticks(duration: &Self::Duration) -> u128 {
+            duration.ticks() as u128
+        }
+
+        fn from_duration_ticks(ticks: u128) -> Self::Duration {
+            fugit::Duration::<u32, NOM, DENOM>::from_ticks(ticks.min(u32::MAX as u128) as u32)
+        }
+    }
+
+    #[cfg(feature = "fugit")]
+    impl<const NOM: u32, const DENOM: u32> Ticks for fugit::Instant<u64, NOM, DENOM>
+    where
+        Self: Instant<Duration = fugit::Duration<u64, NOM, DENOM>>,
+    {
+        const TICK: (u128, u128) = (NOM as u128, DENOM as u128);
+
+        fn instant_ticks(&self) -> u128 {
+            self.ticks() as u128
+        }
+
+        fn from_instant_ticks(ticks: u128) -> Self {
+            Self::from_ticks(ticks.min(u64::MAX as u128) as u64)
+        }
+
+        fn duration_ticks(duration: &Self::Duration) -> u128 {
+            duration.ticks() as u128
+        }
+
+        fn from_duration_ticks(ticks: u128) -> Self::Duration {
+            fugit::Duration::<u64, NOM, DENOM>::from_ticks(ticks.min(u64::MAX as u128) as u64)
+        }
+    }
+}
+
+/// A clock frequency in hertz
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Frequency(pub u64);
+
+impl Frequency {
+    /// Returns a frequency of the given number of hertz
+    pub const fn from_hz(hertz: u64) -> Self {
+        Self(hertz)
+    }
+
+    /// Returns a frequency of the given number of kilohertz
+    pub const fn from_khz(kilohertz: u64) -> Self {
+        Self(kilohertz * 1_000)
+    }
+
+    /// Returns a frequency of the given number of megahertz
+    pub const fn from_mhz(megahertz: u64) -> Self {
+        Self(megahertz * 1_000_000)
+    }
+
+    /// Returns the frequency in hertz
+    pub const fn as_hz(&self) -> u64 {
+        self.0
+    }
+
+    /// Returns the duration of one period of this frequency
+    pub fn period<Instant: self::Instant>(&self) -> Instant::Duration {
+        Instant::hertz_to_duration(self.0)
+    }
+}
+
+/// A clock derived by dividing a source clock, which produces the instant of each of its ticks
+///
+/// A divided clock often has a period that isn't a whole number of the `Instant` type's units,
+/// such as a 7.16 MHz clock divided by 4, so adding the rounded period on each tick would slowly
+/// drift from the real clock.  Instead, the instant of each tick is calculated from the number
+/// of source cycles since the last whole second after the first tick, so the rounding error
+/// never accumulates
+#[derive(Copy, Clone, Debug)]
+pub struct ClockDivider<Instant> {
+    /// The frequency of the source clock
+    pub source: Frequency,
+    /// The number of source clock cycles in each tick of the divided clock
+    pub divisor: u32,
+    start: Instant,
+    cycles: u64,
+}
+
+impl<Instant> ClockDivider<Instant>
+where
+    Instant: self::Instant,
+{
+    /// Construct a divided clock with its first tick at `start`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the source frequency or the divisor are zero, or if the source frequency is
+    /// more than `u32::MAX` hertz
+    pub fn new(source: Frequency, divisor: u32, start: Instant) -> Self {
+        assert!(
+            source.0 != 0 && divisor != 0,
+            "clock frequency and divisor must not be zero"
+        );
+        assert!(
+            source.0 <= u32::MAX as u64,
+            "clock frequency must fit in 32 bits"
+        );
+        Self {
+            source,
+            divisor,
+            start,
+            cycles: 0,
+        }
+    }
+
+    /// Returns the frequency of the divided clock, rounded down to the nearest hertz
+    pub fn frequency(&self) -> Frequency {
+        Frequency(self.source.0 / self.divisor as u64)
+    }
+
+    /// Returns the duration of one tick of the divided clock, rounded down
+    ///
+    /// The period alone can't be used to calculate when later ticks occur without drifting,
+    /// which is what `next_tick()` and `advance()` are for
+    pub fn period(&self) -> Instant::Duration {
+        Instant::cycles_to_duration(self.source.0, self.divisor)
+    }
+
+    /// Returns the instant of the next tick of the divided clock
+    pub fn next_tick(&self) -> Instant {
+        // the cycles are always less than the source frequency, so they fit in 32 bits
+        self.start + Instant::cycles_to_duration(self.source.0, self.cycles as u32)
+    }
+
+    /// Move to the following tick of the divided clock, and return its instant
+    pub fn advance(&mut self) -> Instant {
+        self.cycles += self.divisor as u64;
+        if self.cycles >= self.source.0 {
+            // whole seconds can be represented exactly, so move the start forward by them
+            let seconds = self.cycles / self.source.0;
+            self.start = self.start + Instant::hertz_to_duration(1) * seconds as u32;
+            self.cycles %= self.source.0;
+        }
+        self.next_tick()
+    }
 }
 
 #[cfg(test)]
-mod test {}
+mod test {
+    use super::*;
+
+    /// The master clock of the NTSC Sega Genesis
+    type GenesisInstant = CycleInstant<53_693_175>;
+
+    #[test]
+    fn test_cycle_instant() {
+        // the 68000 runs at the master clock divided by 7
+        let cpu_period = GenesisInstant::hertz_to_duration(53_693_175 / 7);
+        assert_eq!(cpu_period, Cycles(7));
+
+        let now = GenesisInstant::START + cpu_period * 4;
+        assert_eq!(now, CycleInstant(28));
+        assert_eq!(now - GenesisInstant::START, Cycles(28));
+        assert_eq!(now.duration_since(CycleInstant(8)), Cycles(20));
+        assert_eq!(now.checked_sub(Cycles(29)), None);
+        assert_eq!(
+            CycleInstant::<53_693_175>(53_693_175 * 2).as_duration(),
+            Duration::from_secs(2)
+        );
+    }
+
+    #[test]
+    fn test_cycle_instant_sub_saturates() {
+        let earlier = GenesisInstant::START + Cycles(10);
+        let later = earlier + Cycles(5);
+        assert_eq!(later - earlier, Cycles(5));
+        assert_eq!(earlier - later, Cycles(0));
+        assert_eq!(GenesisInstant::START - CycleInstant(u64::MAX), Cycles(0));
+    }
+
+    #[test]
+    fn test_duration_since() {
+        let earlier = Duration::from_millis(1500);
+        let later = earlier + Duration::from_millis(20);
+        assert_eq!(later.duration_since(earlier), Duration::from_millis(20));
+        assert_eq!(earlier.duration_since(later), Duration::ZERO);
+        assert_eq!(earlier.checked_duration_since(later), None);
+        assert_eq!(Instant::checked_sub(&later, Duration::from_secs(2)), None);
+        assert_eq!(
+            Instant::checked_sub(&later, Duration::from_millis(20)),
+            Some(earlier)
+        );
+    }
+
+    #[test]
+    fn test_clock_divider() {
+        // a 7.16 MHz clock divided by 4 has a period of about 558.7ns
+        let mut clock = ClockDivider::new(Frequency::from_hz(7_159_090), 4, Duration::START);
+        assert_eq!(clock.period(), Duration::from_nanos(558));
+        assert_eq!(clock.advance(), Duration::from_nanos(558));
+        assert_eq!(clock.advance(), Duration::from_nanos(1117));
+        for _ in 0..7_159_088 {
+            clock.advance();
+        }
+        assert_eq!(clock.next_tick(), Duration::from_secs(4));
+
+        let mut clock = ClockDivider::new(Frequency::from_mhz(3), 7, GenesisInstant::START);
+        assert_eq!(clock.frequency(), Frequency(428_571));
+        assert_eq!(clock.period(), Cycles(125));
+        for _ in 0..3 {
+            clock.advance();
+        }
+        assert_eq!(clock.advance(), CycleInstant(501));
+    }
+
+    #[test]
+    fn test_clock_divider_coprime() {
+        // the source and divisor share no factors, so the ticks never fall on a whole second,
+        // and the total number of cycles overflows 32 bits after about 2.1 million ticks
+        let source = 3_579_545;
+        let mut clock = ClockDivider::new(Frequency::from_hz(source), 2048, Duration::START);
+        let ticks = 3_000_000_u64;
+        for _ in 0..ticks {
+            clock.advance();
+        }
+        let nanos = ticks as u128 * 2048 * 1_000_000_000 / source as u128;
+        assert_eq!(clock.next_tick(), Duration::from_nanos(nanos as u64));
+    }
+
+    #[test]
+    fn test_convert_instant() {
+        let now = Duration::from_nanos(1_500_000_999);
+        assert_eq!(
+            ConvertInstant::<GenesisInstant>::convert_instant(now),
+            CycleInstant(80_539_816)
+        );
+        assert_eq!(
+            <GenesisInstant as ConvertInstant<Duration>>::convert_duration(Cycles(53_693_175 * 3)),
+            Duration::from_secs(3)
+        );
+        // precision lost converting to a coarser type isn't recovered converting back
+        let cycles: CycleInstant<1_000> = now.convert_instant();
+        assert_eq!(cycles, CycleInstant(1_500));
+        assert_eq!(
+            ConvertInstant::<Duration>::convert_instant(cycles),
+            Duration::from_millis(1_500)
+        );
+        assert_eq!(
+            ConvertInstant::<CycleInstant<1>>::convert_instant(CycleInstant::<1_000>(u64::MAX)),
+            CycleInstant(u64::MAX / 1_000)
+        );
+    }
+
+    #[cfg(feature = "femtos")]
+    #[test]
+    fn test_convert_femtos_instant() {
+        let now = femtos::Instant::START + femtos::Duration::from_picos(1_234_567);
+        let converted: Duration = now.convert_instant();
+        assert_eq!(converted, Duration::from_nanos(1_234));
+        assert_eq!(
+            <Duration as ConvertInstant<femtos::Instant>>::convert_duration(Duration::from_nanos(
+                5
+            )),
+            femtos::Duration::from_nanos(5)
+        );
+    }
+}
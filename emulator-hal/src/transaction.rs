@@ -0,0 +1,183 @@
+//! Descriptors for the sideband signals of a bus transaction
+
+use crate::space::FunctionCode;
+
+/// A description of a bus transaction, giving the sideband signals that a CPU outputs along with
+/// the address, such as whether it's an instruction fetch or a data access
+///
+/// This is passed to `BusAccess::read_ext()` and `BusAccess::write_ext()`, so that devices such as
+/// memory protection units can act on it.  It's constructed with `new()`, which describes a data
+/// access in user mode with every byte enabled, and then modified with the other methods
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Transaction {
+    /// True if the transaction is an instruction fetch rather than a data access
+    pub fetch: bool,
+    /// True if the transaction was made while the CPU was in supervisor (privileged) mode
+    pub supervisor: bool,
+    /// The byte lanes which are enabled, where bit `n` is set if `data[n]` is transferred
+    ///
+    /// On a 68000, for example, a byte access to an odd address only enables the lower data
+    /// strobe (LDS).  Devices that ignore the descriptor transfer every byte
+    pub byte_enables: u32,
+}
+
+impl Default for Transaction {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Transaction {
+    /// Returns a descriptor for a data access in user mode, with every byte enabled
+    pub const fn new() -> Self {
+        Self {
+            fetch: false,
+            supervisor: false,
+            byte_enables: u32::MAX,
+        }
+    }
+
+    /// Returns this descriptor marked as an instruction fetch
+    pub const fn as_fetch(mut self) -> Self {
+        self.fetch = true;
+        self
+    }
+
+    /// Returns this descriptor marked as being made in supervisor mode
+    pub const fn as_supervisor(mut self) -> Self {
+        self.supervisor = true;
+        self
+    }
+
+    /// Returns this descriptor with only the given byte lanes enabled
+    pub const fn with_byte_enables(mut self, byte_enables: u32) -> Self {
+        self.byte_enables = byte_enables;
+        self
+    }
+
+    /// Returns true if `data[index]` is transferred by this transaction
+    ///
+    /// Bytes after the first 32 can't be disabled, so they're always transferred
+    pub const fn is_byte_enabled(&self, index: usize) -> bool {
+        index >= 32 || self.byte_enables & (1 << index) != 0
+    }
+}
+
+/// Describes an access with the given 68000 function code
+///
+/// CPU space cycles are described as supervisor data accesses
+impl From<FunctionCode> for Transaction {
+    fn from(code: FunctionCode) -> Self {
+        Self {
+            fetch: code.is_program(),
+            supervisor: code.is_supervisor(),
+            byte_enables: u32::MAX,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{BasicBusError, BusAccess, Instant, OffsetAdapter};
+    use std::time::Duration;
+
+    /// Memory where only supervisor mode can write, and only the first half can be executed
+    struct ProtectedMemory([u8; 8]);
+
+    impl BusAccess<u32> for ProtectedMemory {
+        type Instant = Duration;
+        type Error = BasicBusError;
+
+        fn read(
+            &mut self,
+            now: Duration,
+            addr: u32,
+            data: &mut [u8],
+        ) -> Result<usize, Self::Error> {
+            self.read_ext(now, addr, data, Transaction::new())
+        }
+
+        fn write(&mut self, now: Duration, addr: u32, data: &[u8]) -> Result<usize, Self::Error> {
+            self.write_ext(now, addr, data, Transaction::new())
+        }
+
+        fn read_ext(
+            &mut self,
+            _now: Duration,
+            addr: u32,
+            data: &mut [u8],
+            transaction: Transaction,
+        ) -> Result<usize, Self::Error> {
+            if transaction.fetch && addr >= 4 {
+                return Err(BasicBusError::OtherStatic("no execute"));
+            }
+            data.copy_from_slice(&self.0[addr as usize..addr as usize + data.len()]);
+            Ok(data.len())
+        }
+
+        fn write_ext(
+            &mut self,
+            _now: Duration,
+            addr: u32,
+            data: &[u8],
+            transaction: Transaction,
+        ) -> Result<usize, Self::Error> {
+            if !transaction.supervisor {
+                return Err(BasicBusError::ReadOnly);
+            }
+            for (i, byte) in data.iter().enumerate() {
+                if transaction.is_byte_enabled(i) {
+                    self.0[addr as usize + i] = *byte;
+                }
+            }
+            Ok(data.len())
+        }
+    }
+
+    #[test]
+    fn test_transactions_reach_device() {
+        let mut bus = OffsetAdapter::new(ProtectedMemory([0; 8]), 0x100, 0xFF);
+        let now = Duration::START;
+
+        assert!(matches!(
+            bus.write(now, 0x100, &[1, 2]),
+            Err(BasicBusError::ReadOnly)
+        ));
+        let supervisor = Transaction::from(FunctionCode::SupervisorData).with_byte_enables(0b01);
+        bus.write_ext(now, 0x100, &[1, 2], supervisor).unwrap();
+        assert_eq!(bus.inner.0[..2], [1, 0]);
+
+        let fetch = Transaction::from(FunctionCode::UserProgram);
+        assert!(fetch.fetch && !fetch.supervisor);
+        let mut data = [0; 2];
+        bus.read_ext(now, 0x100, &mut data, fetch).unwrap();
+        assert!(bus.read_ext(now, 0x104, &mut data, fetch).is_err());
+        assert_eq!(bus.read(now, 0x104, &mut data).unwrap(), 2);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn test_transactions_pass_through_wrappers() {
+        use crate::{
+            AlignedBus, ContextBus, DeniedAccess, FaultInjectionBus, ReadOnly, SwapBytesAdapter,
+            WatchBus,
+        };
+
+        let memory = ReadOnly::new(ProtectedMemory([0; 8]), DeniedAccess::Error);
+        let mut bus: ContextBus<_, BasicBusError> = ContextBus::new(
+            "rom",
+            WatchBus::new(FaultInjectionBus::new(AlignedBus::new(
+                SwapBytesAdapter::<_, 2>::new(memory),
+                2,
+            ))),
+        );
+        let now = Duration::START;
+        let fetch = Transaction::new().as_fetch();
+
+        let mut data = [0; 2];
+        bus.read_ext(now, 2, &mut data, fetch).unwrap();
+        assert!(bus.read_ext(now, 4, &mut data, fetch).is_err());
+        assert_eq!(bus.read(now, 4, &mut data).unwrap(), 2);
+    }
+}
@@ -0,0 +1,335 @@
+//! Traits for translating virtual addresses into physical addresses, such as by an MMU
+
+use core::marker::PhantomData;
+
+use crate::bus::{BusAccess, ErrorType};
+use crate::transaction::Transaction;
+
+/// The kind of access that an address is being translated for
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum AccessKind {
+    /// Data is being read
+    Read,
+    /// Data is being written
+    Write,
+    /// An instruction is being fetched
+    Execute,
+}
+
+/// An error raised when a virtual address can't be translated for the given kind of access
+///
+/// This is either because the address isn't mapped, or because the mapping doesn't allow the
+/// access, such as writing to a write-protected page
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PageFault<Address> {
+    /// The virtual address that caused the fault
+    pub address: Address,
+    /// The kind of access that caused the fault
+    pub access: AccessKind,
+}
+
+impl<Address> ErrorType for PageFault<Address> where Address: core::fmt::Debug {}
+
+/// Translates virtual addresses into physical addresses
+///
+/// This models an MMU, such as the 68851 or the MMU of an ARM or 68030 CPU, so that it can be
+/// implemented separately from the CPU.  The translation is done on every access, so an
+/// implementation that walks page tables should keep its own cache of recent translations
+pub trait Translate<VirtualAddress> {
+    /// The type of a physical address produced by this translation
+    type PhysicalAddress: Copy;
+
+    /// Translate the given virtual address for the given kind of access, or return a fault
+    fn translate(
+        &mut self,
+        addr: VirtualAddress,
+        access: AccessKind,
+    ) -> Result<Self::PhysicalAddress, PageFault<VirtualAddress>>;
+}
+
+impl<VirtualAddress, T> Translate<VirtualAddress> for &mut T
+where
+    T: Translate<VirtualAddress> + ?Sized,
+{
+    type PhysicalAddress = T::PhysicalAddress;
+
+    #[inline]
+    fn translate(
+        &mut self,
+        addr: VirtualAddress,
+        access: AccessKind,
+    ) -> Result<Self::PhysicalAddress, PageFault<VirtualAddress>> {
+        T::translate(self, addr, access)
+    }
+}
+
+/// An adapter that translates each address with a `Translate` implementation before accessing
+/// a wrapped bus object
+///
+/// This object implements `BusAccess` for virtual addresses, and returns an error of type
+/// `Error`, which must be convertible from both a `PageFault` and the wrapped bus's error, so
+/// that the CPU can raise the appropriate exception.  Only the first address of each access is
+/// translated, so the CPU should split accesses that cross a page boundary.  Reads and writes
+/// are translated as `AccessKind::Read` and `AccessKind::Write`, except for reads made with
+/// `read_ext()` for an instruction fetch, which are translated as `AccessKind::Execute`.  The
+/// transaction is passed on to the wrapped bus, so that it can also check it
+pub struct TranslateAdapter<VirtualAddress, Mmu, Bus, Error>
+where
+    Mmu: Translate<VirtualAddress>,
+    Bus: BusAccess<Mmu::PhysicalAddress>,
+{
+    /// The translation applied to each address
+    pub mmu: Mmu,
+    /// The underlying object implementing `BusAccess` that is accessed with physical addresses
+    pub inner: Bus,
+
+    address: PhantomData<VirtualAddress>,
+    error: PhantomData<Error>,
+}
+
+impl<VirtualAddress, Mmu, Bus, Error> TranslateAdapter<VirtualAddress, Mmu, Bus, Error>
+where
+    Mmu: Translate<VirtualAddress>,
+    Bus: BusAccess<Mmu::PhysicalAddress>,
+{
+    /// Construct a new adapter which translates addresses with `mmu` before accessing `inner`
+    pub fn new(mmu: Mmu, inner: Bus) -> Self {
+        Self {
+            mmu,
+            inner,
+            address: PhantomData,
+            error: PhantomData,
+        }
+    }
+}
+
+impl<VirtualAddress, Mmu, Bus, Error> BusAccess<VirtualAddress>
+    for TranslateAdapter<VirtualAddress, Mmu, Bus, Error>
+where
+    VirtualAddress: Copy,
+    Mmu: Translate<VirtualAddress>,
+    Bus: BusAccess<Mmu::PhysicalAddress>,
+    Error: ErrorType + From<PageFault<VirtualAddress>> + From<Bus::Error>,
+{
+    type Instant = Bus::Instant;
+    type Error = Error;
+
+    #[inline]
+    fn read(
+        &mut self,
+        now: Self::Instant,
+        addr: VirtualAddress,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        let addr = self.mmu.translate(addr, AccessKind::Read)?;
+        Ok(self.inner.read(now, addr, data)?)
+    }
+
+    #[inline]
+    fn write(
+        &mut self,
+        now: Self::Instant,
+        addr: VirtualAddress,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        let addr = self.mmu.translate(addr, AccessKind::Write)?;
+        Ok(self.inner.write(now, addr, data)?)
+    }
+
+    #[inline]
+    fn peek(
+        &mut self,
+        now: Self::Instant,
+        addr: VirtualAddress,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        let addr = self.mmu.translate(addr, AccessKind::Read)?;
+        Ok(self.inner.peek(now, addr, data)?)
+    }
+
+    #[inline]
+    fn poke(
+        &mut self,
+        now: Self::Instant,
+        addr: VirtualAddress,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        let addr = self.mmu.translate(addr, AccessKind::Write)?;
+        Ok(self.inner.poke(now, addr, data)?)
+    }
+
+    #[inline]
+    fn read_modify_write(
+        &mut self,
+        now: Self::Instant,
+        addr: VirtualAddress,
+        data: &mut [u8],
+        modify: &mut dyn FnMut(&mut [u8]),
+    ) -> Result<usize, Self::Error> {
+        let addr = self.mmu.translate(addr, AccessKind::Write)?;
+        Ok(self.inner.read_modify_write(now, addr, data, modify)?)
+    }
+
+    #[inline]
+    fn read_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: VirtualAddress,
+        data: &mut [u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        let access = if transaction.fetch {
+            AccessKind::Execute
+        } else {
+            AccessKind::Read
+        };
+        let addr = self.mmu.translate(addr, access)?;
+        Ok(self.inner.read_ext(now, addr, data, transaction)?)
+    }
+
+    #[inline]
+    fn write_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: VirtualAddress,
+        data: &[u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        let addr = self.mmu.translate(addr, AccessKind::Write)?;
+        Ok(self.inner.write_ext(now, addr, data, transaction)?)
+    }
+
+    #[inline]
+    fn read_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: VirtualAddress,
+        data: &mut [u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        let addr = self.mmu.translate(addr, AccessKind::Read)?;
+        Ok(self.inner.read_timed(now, addr, data)?)
+    }
+
+    #[inline]
+    fn write_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: VirtualAddress,
+        data: &[u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        let addr = self.mmu.translate(addr, AccessKind::Write)?;
+        Ok(self.inner.write_timed(now, addr, data)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::BasicBusError;
+    use crate::BusAccessExt;
+    use crate::ShortTransfer;
+    use std::time::Duration;
+
+    /// An MMU with 256 byte pages, which maps each virtual page to a physical frame, and doesn't
+    /// allow writable pages to be executed
+    struct Mmu {
+        /// The physical frame and whether it's writable, for each virtual page
+        pages: Vec<Option<(u32, bool)>>,
+    }
+
+    impl Translate<u32> for Mmu {
+        type PhysicalAddress = u32;
+
+        fn translate(&mut self, addr: u32, access: AccessKind) -> Result<u32, PageFault<u32>> {
+            let fault = PageFault {
+                address: addr,
+                access,
+            };
+            match self.pages.get((addr >> 8) as usize) {
+                Some(Some((_, false))) if access == AccessKind::Write => Err(fault),
+                Some(Some((_, true))) if access == AccessKind::Execute => Err(fault),
+                Some(Some((frame, _))) => Ok(frame << 8 | (addr & 0xFF)),
+                _ => Err(fault),
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    enum Exception {
+        PageFault(PageFault<u32>),
+        BusError,
+    }
+
+    impl ErrorType for Exception {}
+
+    impl From<PageFault<u32>> for Exception {
+        fn from(fault: PageFault<u32>) -> Self {
+            Exception::PageFault(fault)
+        }
+    }
+
+    impl From<BasicBusError> for Exception {
+        fn from(_err: BasicBusError) -> Self {
+            Exception::BusError
+        }
+    }
+
+    impl From<ShortTransfer> for Exception {
+        fn from(_err: ShortTransfer) -> Self {
+            Exception::BusError
+        }
+    }
+
+    #[test]
+    fn test_translated_accesses() {
+        let mmu = Mmu {
+            pages: vec![Some((3, true)), Some((0, false)), None, Some((8, true))],
+        };
+        let mut bus: TranslateAdapter<u32, _, _, Exception> =
+            TranslateAdapter::new(mmu, [0_u8; 0x400]);
+
+        bus.write_beu16(Duration::ZERO, 0x10, 0x1234).unwrap();
+        assert_eq!(bus.inner[0x310..0x312], [0x12, 0x34]);
+        assert_eq!(bus.read_u8(Duration::ZERO, 0x100).unwrap(), 0);
+
+        assert!(matches!(
+            bus.write_u8(Duration::ZERO, 0x104, 1),
+            Err(Exception::PageFault(PageFault {
+                address: 0x104,
+                access: AccessKind::Write,
+            }))
+        ));
+        assert!(matches!(
+            bus.read_u8(Duration::ZERO, 0x200),
+            Err(Exception::PageFault(_))
+        ));
+        assert!(matches!(
+            bus.read_u8(Duration::ZERO, 0x300),
+            Err(Exception::BusError)
+        ));
+    }
+
+    #[test]
+    fn test_fetches_are_translated_as_execute() {
+        let mmu = Mmu {
+            pages: vec![Some((3, true)), Some((0, false))],
+        };
+        let mut bus: TranslateAdapter<u32, _, _, Exception> =
+            TranslateAdapter::new(mmu, [0_u8; 0x400]);
+        let fetch = Transaction::new().as_fetch();
+
+        let mut data = [0; 2];
+        bus.read_ext(Duration::ZERO, 0x100, &mut data, fetch)
+            .unwrap();
+        bus.read_ext(Duration::ZERO, 0x10, &mut data, Transaction::new())
+            .unwrap();
+        assert!(matches!(
+            bus.read_ext(Duration::ZERO, 0x10, &mut data, fetch),
+            Err(Exception::PageFault(PageFault {
+                address: 0x10,
+                access: AccessKind::Execute,
+            }))
+        ));
+    }
+}
@@ -0,0 +1,402 @@
+//! A bus wrapper that calls user callbacks when watched addresses are accessed
+
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::bus::BusAccess;
+use crate::report::BusOperation;
+use crate::transaction::Transaction;
+
+/// Which kinds of access a watch is triggered by
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WatchKind {
+    /// Only reads trigger the watch
+    Read,
+    /// Only writes trigger the watch
+    Write,
+    /// Both reads and writes trigger the watch
+    Access,
+}
+
+impl WatchKind {
+    fn matches(self, operation: BusOperation) -> bool {
+        match self {
+            WatchKind::Read => operation == BusOperation::Read,
+            WatchKind::Write => operation == BusOperation::Write,
+            WatchKind::Access => true,
+        }
+    }
+}
+
+/// A transaction that accessed a watched range of addresses
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct WatchEvent<'a, Address, Instant> {
+    /// The simulated time at which the transaction occurred
+    pub now: Instant,
+    /// Whether the transaction was a read or a write
+    pub operation: BusOperation,
+    /// The address of the transaction, which may start before the watched range
+    pub addr: Address,
+    /// The contents of the addresses before the transaction
+    ///
+    /// For a write, this is what `peek()` returned just before the write, which is empty if the
+    /// wrapped bus doesn't support peeking.  For a read, this is the same as `after`
+    pub before: &'a [u8],
+    /// The contents of the addresses after the transaction, which is the data read or written
+    pub after: &'a [u8],
+}
+
+/// A callback that is called when a watched range of addresses is accessed
+pub type WatchCallback<Address, Instant> = Box<dyn FnMut(&WatchEvent<'_, Address, Instant>)>;
+
+struct Watch<Address, Instant> {
+    id: usize,
+    range: Range<Address>,
+    kind: WatchKind,
+    callback: WatchCallback<Address, Instant>,
+}
+
+/// A bus wrapper that calls a callback whenever a watched range of addresses is read or written
+///
+/// This can be used to log accesses to particular device registers, to implement data
+/// breakpoints in a debugger, or to detect self-modifying code by watching for writes to the
+/// program, all without modifying the wrapped devices.  A transaction triggers a watch if any
+/// of the bytes it transfers are in the watched range, and only after it completes without an
+/// error.  Accesses made with `peek()` and `poke()` don't trigger any watches, so a debugger
+/// can inspect the watched addresses without triggering its own watches
+pub struct WatchBus<Address, Bus>
+where
+    Address: Copy,
+    Bus: BusAccess<Address>,
+{
+    /// The underlying object implementing `BusAccess` that this object watches
+    pub inner: Bus,
+    watches: Vec<Watch<Address, Bus::Instant>>,
+    next_id: usize,
+}
+
+impl<Address, Bus> WatchBus<Address, Bus>
+where
+    Address: Copy + Into<u64>,
+    Bus: BusAccess<Address>,
+{
+    /// Construct a new wrapper for the given `bus` object, without any watches
+    pub fn new(inner: Bus) -> Self {
+        Self {
+            inner,
+            watches: Vec::new(),
+            next_id: 0,
+        }
+    }
+
+    /// Call `callback` whenever the given range of addresses is accessed in the given way, and
+    /// return an id which can be used to remove the watch
+    pub fn watch<F>(&mut self, range: Range<Address>, kind: WatchKind, callback: F) -> usize
+    where
+        F: FnMut(&WatchEvent<'_, Address, Bus::Instant>) + 'static,
+    {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.watches.push(Watch {
+            id,
+            range,
+            kind,
+            callback: Box::new(callback),
+        });
+        id
+    }
+
+    /// Remove the watch with the given id, and return true if it existed
+    pub fn unwatch(&mut self, id: usize) -> bool {
+        let len = self.watches.len();
+        self.watches.retain(|watch| watch.id != id);
+        self.watches.len() != len
+    }
+
+    /// Remove all watches
+    pub fn clear_watches(&mut self) {
+        self.watches.clear();
+    }
+
+    fn is_watched(&self, operation: BusOperation, addr: Address, len: usize) -> bool {
+        self.watches
+            .iter()
+            .any(|watch| Self::triggers(watch, operation, addr, len))
+    }
+
+    fn triggers(
+        watch: &Watch<Address, Bus::Instant>,
+        operation: BusOperation,
+        addr: Address,
+        len: usize,
+    ) -> bool {
+        let start = addr.into();
+        let end = start.saturating_add(len as u64);
+        watch.kind.matches(operation)
+            && start < watch.range.end.into()
+            && watch.range.start.into() < end
+    }
+
+    fn notify(&mut self, event: &WatchEvent<'_, Address, Bus::Instant>) {
+        for watch in self.watches.iter_mut() {
+            if Self::triggers(watch, event.operation, event.addr, event.after.len()) {
+                (watch.callback)(event);
+            }
+        }
+    }
+
+    fn notify_read(&mut self, now: Bus::Instant, addr: Address, data: &[u8]) {
+        if self.is_watched(BusOperation::Read, addr, data.len()) {
+            self.notify(&WatchEvent {
+                now,
+                operation: BusOperation::Read,
+                addr,
+                before: data,
+                after: data,
+            });
+        }
+    }
+
+    /// Returns the contents of memory before a write, if the write is watched
+    fn before_write(&mut self, now: Bus::Instant, addr: Address, len: usize) -> Option<Vec<u8>> {
+        if !self.is_watched(BusOperation::Write, addr, len) {
+            return None;
+        }
+        let mut before = vec![0; len];
+        let peeked = self.inner.peek(now, addr, &mut before).unwrap_or(0);
+        before.truncate(peeked);
+        Some(before)
+    }
+
+    fn notify_write(
+        &mut self,
+        now: Bus::Instant,
+        addr: Address,
+        before: Option<Vec<u8>>,
+        data: &[u8],
+    ) {
+        if let Some(before) = before {
+            self.notify(&WatchEvent {
+                now,
+                operation: BusOperation::Write,
+                addr,
+                before: &before,
+                after: data,
+            });
+        }
+    }
+}
+
+impl<Address, Bus> BusAccess<Address> for WatchBus<Address, Bus>
+where
+    Address: Copy + Into<u64>,
+    Bus: BusAccess<Address>,
+{
+    type Instant = Bus::Instant;
+    type Error = Bus::Error;
+
+    fn read(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        let count = self.inner.read(now, addr, data)?;
+        self.notify_read(now, addr, &data[..count]);
+        Ok(count)
+    }
+
+    fn write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        let before = self.before_write(now, addr, data.len());
+        let count = self.inner.write(now, addr, data)?;
+        self.notify_write(now, addr, before, &data[..count]);
+        Ok(count)
+    }
+
+    #[inline]
+    fn peek(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<usize, Self::Error> {
+        self.inner.peek(now, addr, data)
+    }
+
+    #[inline]
+    fn poke(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<usize, Self::Error> {
+        self.inner.poke(now, addr, data)
+    }
+
+    fn read_modify_write(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        modify: &mut dyn FnMut(&mut [u8]),
+    ) -> Result<usize, Self::Error> {
+        let len = data.len();
+        if !self.is_watched(BusOperation::Read, addr, len)
+            && !self.is_watched(BusOperation::Write, addr, len)
+        {
+            return self.inner.read_modify_write(now, addr, data, modify);
+        }
+
+        let mut before = Vec::new();
+        let count = self
+            .inner
+            .read_modify_write(now, addr, data, &mut |data: &mut [u8]| {
+                before = data.to_vec();
+                modify(data);
+            })?;
+        for (operation, after) in [
+            (BusOperation::Read, &before[..]),
+            (BusOperation::Write, &data[..count]),
+        ] {
+            self.notify(&WatchEvent {
+                now,
+                operation,
+                addr,
+                before: &before,
+                after,
+            });
+        }
+        Ok(count)
+    }
+
+    fn read_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        let count = self.inner.read_ext(now, addr, data, transaction)?;
+        self.notify_read(now, addr, &data[..count]);
+        Ok(count)
+    }
+
+    fn write_ext(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+        transaction: Transaction,
+    ) -> Result<usize, Self::Error> {
+        let before = self.before_write(now, addr, data.len());
+        let count = self.inner.write_ext(now, addr, data, transaction)?;
+        self.notify_write(now, addr, before, &data[..count]);
+        Ok(count)
+    }
+
+    fn read_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &mut [u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        let (count, done) = self.inner.read_timed(now, addr, data)?;
+        self.notify_read(now, addr, &data[..count]);
+        Ok((count, done))
+    }
+
+    fn write_timed(
+        &mut self,
+        now: Self::Instant,
+        addr: Address,
+        data: &[u8],
+    ) -> Result<(usize, Self::Instant), Self::Error> {
+        let before = self.before_write(now, addr, data.len());
+        let (count, done) = self.inner.write_timed(now, addr, data)?;
+        self.notify_write(now, addr, before, &data[..count]);
+        Ok((count, done))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::BusAccessExt;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    #[test]
+    fn test_watch_ranges() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut bus = WatchBus::<u32, _>::new([0_u8; 0x100]);
+        let now = Duration::ZERO;
+
+        let writes = log.clone();
+        let id = bus.watch(0x12..0x14, WatchKind::Write, move |event| {
+            writes
+                .borrow_mut()
+                .push((event.addr, event.before.to_vec(), event.after.to_vec()));
+        });
+
+        // a write that overlaps the range triggers the watch, with the previous contents
+        bus.write_beu32(now, 0x10, 0x1122_3344).unwrap();
+        bus.write_beu32(now, 0x10, 0x5566_7788).unwrap();
+        bus.write_u8(now, 0x14, 0xFF).unwrap();
+        bus.read_u8(now, 0x12).unwrap();
+        bus.poke(now, 0x12, &[0]).unwrap();
+        assert_eq!(
+            *log.borrow(),
+            vec![
+                (0x10, vec![0, 0, 0, 0], vec![0x11, 0x22, 0x33, 0x44]),
+                (
+                    0x10,
+                    vec![0x11, 0x22, 0x33, 0x44],
+                    vec![0x55, 0x66, 0x77, 0x88]
+                ),
+            ]
+        );
+
+        assert!(bus.unwatch(id));
+        assert!(!bus.unwatch(id));
+        bus.write_u8(now, 0x12, 0).unwrap();
+        assert_eq!(log.borrow().len(), 2);
+    }
+
+    #[test]
+    fn test_watch_reads() {
+        let reads = Rc::new(RefCell::new(Vec::new()));
+        let mut bus = WatchBus::<u32, _>::new([0_u8; 0x100]);
+        let now = Duration::ZERO;
+
+        let log = reads.clone();
+        bus.watch(0x80..0x81, WatchKind::Access, move |event| {
+            log.borrow_mut()
+                .push((event.operation, event.before[0], event.after[0]));
+        });
+        bus.write_u8(now, 0x80, 0x05).unwrap();
+        assert_eq!(bus.read_u8(now, 0x80).unwrap(), 0x05);
+        bus.read_modify_write(now, 0x80, &mut [0], &mut |data| data[0] |= 0x80)
+            .unwrap();
+        let mut data = [0];
+        bus.peek(now, 0x80, &mut data).unwrap();
+
+        assert_eq!(data, [0x85]);
+        assert_eq!(
+            *reads.borrow(),
+            vec![
+                (BusOperation::Write, 0x00, 0x05),
+                (BusOperation::Read, 0x05, 0x05),
+                (BusOperation::Read, 0x05, 0x05),
+                (BusOperation::Write, 0x05, 0x85),
+            ]
+        );
+    }
+}
@@ -0,0 +1,111 @@
+#![no_main]
+
+use std::time::Duration;
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+
+use emulator_hal::{
+    AutoBusAdapter, BasicBusError, BusAccess, BusAccessExt, BusAdapter, ByteOrder, FromAddress,
+    Instant,
+};
+use emulator_hal_memory::MemoryBlock;
+
+/// An address in a word-addressed window, which is translated into a byte address
+#[derive(Copy, Clone, Debug)]
+struct Window(u32);
+
+impl FromAddress<u16> for Window {
+    fn from_address(address: u16) -> Self {
+        Window(address as u32 * 4)
+    }
+}
+
+/// Translates into a physical address below the window, which can wrap around the address space
+fn window_to_physical(addr: Window) -> u64 {
+    (addr.0 as u64).wrapping_sub(0x100)
+}
+
+fn translate(addr: u16) -> u64 {
+    window_to_physical(Window::from_address(addr))
+}
+
+#[derive(Arbitrary, Debug)]
+enum Operation {
+    Read {
+        addr: u16,
+        length: u8,
+    },
+    Write {
+        addr: u16,
+        data: Vec<u8>,
+    },
+    ReadU64 {
+        addr: u16,
+        big_endian: bool,
+    },
+    WriteU16 {
+        addr: u16,
+        value: u16,
+        big_endian: bool,
+    },
+}
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    size: u16,
+    operations: Vec<Operation>,
+}
+
+fn byte_order(big_endian: bool) -> ByteOrder {
+    if big_endian {
+        ByteOrder::Big
+    } else {
+        ByteOrder::Little
+    }
+}
+
+fuzz_target!(|input: Input| {
+    let mut memory = MemoryBlock::<Duration>::from(vec![0; input.size as usize]);
+    let mut reference = MemoryBlock::<Duration>::from(vec![0; input.size as usize]);
+
+    let physical: BusAdapter<Window, u64, _, BasicBusError> =
+        BusAdapter::new(&mut memory, window_to_physical);
+    let mut bus: AutoBusAdapter<u16, Window, _, BasicBusError> = AutoBusAdapter::new(physical);
+
+    // every access through the stacked adapters must behave the same as a direct access to the
+    // translated address
+    for operation in input.operations {
+        match operation {
+            Operation::Read { addr, length } => {
+                let mut data = vec![0; length as usize];
+                let mut expected_data = vec![0; length as usize];
+                let result = bus.read(Duration::START, addr, &mut data);
+                let expected = reference.read(Duration::START, translate(addr), &mut expected_data);
+                assert_eq!(result.ok(), expected.ok());
+                assert_eq!(data, expected_data);
+            }
+            Operation::Write { addr, data } => {
+                let result = bus.write(Duration::START, addr, &data);
+                let expected = reference.write(Duration::START, translate(addr), &data);
+                assert_eq!(result.ok(), expected.ok());
+            }
+            Operation::ReadU64 { addr, big_endian } => {
+                let order = byte_order(big_endian);
+                let result = bus.read_u64(order, Duration::START, addr);
+                let expected = reference.read_u64(order, Duration::START, translate(addr));
+                assert_eq!(result.ok(), expected.ok());
+            }
+            Operation::WriteU16 {
+                addr,
+                value,
+                big_endian,
+            } => {
+                let order = byte_order(big_endian);
+                let result = bus.write_u16(order, Duration::START, addr, value);
+                let expected = reference.write_u16(order, Duration::START, translate(addr), value);
+                assert_eq!(result.ok(), expected.ok());
+            }
+        }
+    }
+});
@@ -0,0 +1,135 @@
+#![no_main]
+
+use std::time::Duration;
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+
+use emulator_hal::{BusAccess, BusAccessExt, ByteOrder, Instant};
+use emulator_hal_memory::MemoryBlock;
+
+#[derive(Arbitrary, Debug)]
+enum Operation {
+    Read {
+        addr: u64,
+        length: u16,
+    },
+    Write {
+        addr: u64,
+        data: Vec<u8>,
+    },
+    ReadU32 {
+        addr: u64,
+        big_endian: bool,
+    },
+    WriteU64 {
+        addr: u64,
+        value: u64,
+        big_endian: bool,
+    },
+    Resize {
+        size: u16,
+    },
+}
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    size: u16,
+    read_only: bool,
+    operations: Vec<Operation>,
+}
+
+fn byte_order(big_endian: bool) -> ByteOrder {
+    if big_endian {
+        ByteOrder::Big
+    } else {
+        ByteOrder::Little
+    }
+}
+
+/// Returns the range of the model covered by an access, or `None` if any of it is out of bounds
+fn in_bounds(model: &[u8], addr: u64, length: usize) -> Option<std::ops::Range<usize>> {
+    let start = usize::try_from(addr).ok()?;
+    let end = start.checked_add(length)?;
+    (end <= model.len()).then_some(start..end)
+}
+
+fuzz_target!(|input: Input| {
+    let mut memory = MemoryBlock::<Duration>::from(vec![0; input.size as usize]);
+    let mut model = vec![0; input.size as usize];
+    if input.read_only {
+        memory.read_only();
+    }
+
+    for operation in input.operations {
+        match operation {
+            Operation::Read { addr, length } => {
+                let mut data = vec![0; length as usize];
+                let result = memory.read(Duration::START, addr, &mut data);
+                match in_bounds(&model, addr, data.len()) {
+                    Some(range) => {
+                        assert_eq!(result.unwrap(), data.len());
+                        assert_eq!(data, model[range]);
+                    }
+                    None => assert!(result.is_err()),
+                }
+            }
+            Operation::Write { addr, data } => {
+                let result = memory.write(Duration::START, addr, &data);
+                if input.read_only {
+                    assert_eq!(result.unwrap(), 0);
+                    continue;
+                }
+                match in_bounds(&model, addr, data.len()) {
+                    Some(range) => {
+                        assert_eq!(result.unwrap(), data.len());
+                        model[range].copy_from_slice(&data);
+                    }
+                    None => assert!(result.is_err()),
+                }
+            }
+            Operation::ReadU32 { addr, big_endian } => {
+                let result = memory.read_u32(byte_order(big_endian), Duration::START, addr);
+                match in_bounds(&model, addr, 4) {
+                    Some(range) => {
+                        let bytes = model[range].try_into().unwrap();
+                        let expected = if big_endian {
+                            u32::from_be_bytes(bytes)
+                        } else {
+                            u32::from_le_bytes(bytes)
+                        };
+                        assert_eq!(result.unwrap(), expected);
+                    }
+                    None => assert!(result.is_err()),
+                }
+            }
+            Operation::WriteU64 {
+                addr,
+                value,
+                big_endian,
+            } => {
+                let result = memory.write_u64(byte_order(big_endian), Duration::START, addr, value);
+                if input.read_only {
+                    assert!(result.is_ok());
+                    continue;
+                }
+                match in_bounds(&model, addr, 8) {
+                    Some(range) => {
+                        assert!(result.is_ok());
+                        let bytes = if big_endian {
+                            value.to_be_bytes()
+                        } else {
+                            value.to_le_bytes()
+                        };
+                        model[range].copy_from_slice(&bytes);
+                    }
+                    None => assert!(result.is_err()),
+                }
+            }
+            Operation::Resize { size } => {
+                memory.resize(size as usize);
+                model.resize(size as usize, 0);
+            }
+        }
+    }
+});
@@ -0,0 +1,82 @@
+#![no_main]
+
+use std::ops::Range;
+use std::time::Duration;
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+
+use emulator_hal::{BasicBusError, BusAccess, DeviceId, Instant};
+use emulator_hal_bus::BusRouter;
+use emulator_hal_memory::MemoryBlock;
+
+#[derive(Arbitrary, Debug)]
+enum Operation {
+    Read { addr: u32, length: u8 },
+    Write { addr: u32, data: Vec<u8> },
+}
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    regions: Vec<(u16, u8)>,
+    operations: Vec<Operation>,
+}
+
+/// Returns the region and the range within it covered by an access, or `None` if the access
+/// doesn't start in a region, or runs past the end of the region it starts in
+fn in_bounds(
+    model: &mut [(Range<u32>, Vec<u8>)],
+    addr: u32,
+    length: usize,
+) -> Option<(&mut Vec<u8>, Range<usize>)> {
+    let (range, contents) = model.iter_mut().find(|(range, _)| range.contains(&addr))?;
+    let start = (addr - range.start) as usize;
+    let end = start.checked_add(length)?;
+    (end <= contents.len()).then_some((contents, start..end))
+}
+
+fuzz_target!(|input: Input| {
+    let mut router = BusRouter::<u32, Duration, BasicBusError>::new();
+    let mut model: Vec<(Range<u32>, Vec<u8>)> = vec![];
+
+    for (index, (start, length)) in input.regions.into_iter().enumerate() {
+        let range = start as u32..start as u32 + length as u32;
+        let memory = MemoryBlock::<Duration>::from(vec![0; length as usize]);
+        let overlaps = model
+            .iter()
+            .any(|(existing, _)| range.start < existing.end && existing.start < range.end);
+        let result = router.insert(DeviceId(index as u32), range.clone(), Box::new(memory));
+        if range.is_empty() || overlaps {
+            assert!(result.is_err());
+        } else {
+            result.unwrap();
+            model.push((range, vec![0; length as usize]));
+        }
+    }
+
+    for operation in input.operations {
+        match operation {
+            Operation::Read { addr, length } => {
+                let mut data = vec![0; length as usize];
+                let result = router.read(Duration::START, addr, &mut data);
+                match in_bounds(&mut model, addr, data.len()) {
+                    Some((contents, range)) => {
+                        assert_eq!(result.unwrap(), data.len());
+                        assert_eq!(data, contents[range]);
+                    }
+                    None => assert!(result.is_err()),
+                }
+            }
+            Operation::Write { addr, data } => {
+                let result = router.write(Duration::START, addr, &data);
+                match in_bounds(&mut model, addr, data.len()) {
+                    Some((contents, range)) => {
+                        assert_eq!(result.unwrap(), data.len());
+                        contents[range].copy_from_slice(&data);
+                    }
+                    None => assert!(result.is_err()),
+                }
+            }
+        }
+    }
+});